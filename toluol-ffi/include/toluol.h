@@ -0,0 +1,104 @@
+#ifndef TOLUOL_H
+#define TOLUOL_H
+
+/* This file is generated by cbindgen, see tests/header.rs. Do not edit it manually. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The result of calling one of the library's functions.
+ */
+typedef enum ToluolStatus {
+  /**
+   * The call succeeded.
+   */
+  TOLUOL_STATUS_OK = 0,
+  /**
+   * A pointer argument was NULL.
+   */
+  TOLUOL_STATUS_NULL_POINTER = 1,
+  /**
+   * An argument was invalid, e.g. a domain name was not valid ASCII.
+   */
+  TOLUOL_STATUS_INVALID_ARGUMENT = 2,
+  /**
+   * The given bytes are not a valid DNS message.
+   */
+  TOLUOL_STATUS_PARSE_ERROR = 3,
+  /**
+   * The DNS message could not be encoded.
+   */
+  TOLUOL_STATUS_ENCODE_ERROR = 4,
+} ToluolStatus;
+
+/**
+ * A byte buffer allocated by this library. Must be freed with [`toluol_buffer_free()`].
+ */
+typedef struct ToluolBuffer {
+  uint8_t *data;
+  size_t len;
+} ToluolBuffer;
+
+/**
+ * Parses a DNS message in wire format and writes its JSON representation to `out` as a
+ * NUL-terminated string, which must be freed with [`toluol_string_free()`].
+ *
+ * # Safety
+ * `msg` must point to at least `len` readable bytes, and `out` must be valid for writes.
+ */
+enum ToluolStatus toluol_message_to_json(const uint8_t *msg, size_t len, char **out);
+
+/**
+ * Parses a DNS message in wire format and writes a human readable description of it (the same
+ * as `toluol +verbose` prints) to `out` as a NUL-terminated string, which must be freed with
+ * [`toluol_string_free()`].
+ *
+ * # Safety
+ * `msg` must point to at least `len` readable bytes, and `out` must be valid for writes.
+ */
+enum ToluolStatus toluol_message_format(const uint8_t *msg, size_t len, char **out);
+
+/**
+ * Encodes a recursive query for `name` (a NUL-terminated ASCII string) with the given record type
+ * and class, e.g. 1 and 1 for an `A` query in class `IN`. An EDNS OPT record is always added;
+ * `dnssec_ok` sets its DO flag.
+ *
+ * The encoded query is written to `out` and must be freed with [`toluol_buffer_free()`].
+ *
+ * # Safety
+ * `name` must be a valid NUL-terminated string, and `out` must be valid for writes.
+ */
+enum ToluolStatus toluol_query_encode(const char *name,
+                                      uint16_t qtype,
+                                      uint16_t qclass,
+                                      bool dnssec_ok,
+                                      struct ToluolBuffer *out);
+
+/**
+ * Returns a static, NUL-terminated description of `status`, which should be one of the values of
+ * [`ToluolStatus`]; other values are described as an unknown status. The returned string must
+ * not be freed.
+ */
+const char *toluol_status_str(int status);
+
+/**
+ * Frees a string returned by this library. Does nothing if `s` is NULL.
+ *
+ * # Safety
+ * `s` must be NULL or have been returned by this library, and must not be used afterwards.
+ */
+void toluol_string_free(char *s);
+
+/**
+ * Frees a buffer returned by this library. Does nothing if `buf.data` is NULL.
+ *
+ * # Safety
+ * `buf` must have been returned by this library, and must not be used afterwards.
+ */
+void toluol_buffer_free(struct ToluolBuffer buf);
+
+#endif /* TOLUOL_H */
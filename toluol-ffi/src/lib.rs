@@ -0,0 +1,292 @@
+//! C bindings for the DNS message codec of [`toluol_proto`].
+//!
+//! All functions return a [`ToluolStatus`] and write their result to an out pointer, which is
+//! only written to on success. Memory returned by this library must be freed with
+//! [`toluol_string_free()`] or [`toluol_buffer_free()`], respectively.
+//!
+//! The C header (`include/toluol.h`) is generated by cbindgen; run the tests with `UPDATE_HEADER=1`
+//! to regenerate it after changing the public functions or types.
+
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::io::Cursor;
+use std::ptr;
+use std::slice;
+
+use toluol_proto::{Class, EdnsConfig, HeaderFlags, Message, Name, Opcode, RecordType};
+
+/// The result of calling one of the library's functions.
+#[repr(C)]
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum ToluolStatus {
+    /// The call succeeded.
+    Ok = 0,
+    /// A pointer argument was NULL.
+    NullPointer = 1,
+    /// An argument was invalid, e.g. a domain name was not valid ASCII.
+    InvalidArgument = 2,
+    /// The given bytes are not a valid DNS message.
+    ParseError = 3,
+    /// The DNS message could not be encoded.
+    EncodeError = 4,
+}
+
+/// A byte buffer allocated by this library. Must be freed with [`toluol_buffer_free()`].
+#[repr(C)]
+pub struct ToluolBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl From<Vec<u8>> for ToluolBuffer {
+    fn from(data: Vec<u8>) -> Self {
+        let len = data.len();
+        let data = Box::into_raw(data.into_boxed_slice()) as *mut u8;
+        Self { data, len }
+    }
+}
+
+/// Parses the DNS message in wire format given by `msg` and `len`.
+///
+/// # Safety
+/// `msg` must point to at least `len` readable bytes.
+unsafe fn parse_message(msg: *const u8, len: usize) -> Result<Message, ToluolStatus> {
+    if msg.is_null() {
+        return Err(ToluolStatus::NullPointer);
+    }
+    let msg = slice::from_raw_parts(msg, len);
+    Message::parse(&mut Cursor::new(msg)).map_err(|_| ToluolStatus::ParseError)
+}
+
+/// Writes `s` to `out` as a NUL-terminated string.
+///
+/// # Safety
+/// `out` must be NULL or valid for writes.
+unsafe fn write_string(s: String, out: *mut *mut c_char) -> ToluolStatus {
+    if out.is_null() {
+        return ToluolStatus::NullPointer;
+    }
+    match CString::new(s) {
+        Ok(s) => {
+            *out = s.into_raw();
+            ToluolStatus::Ok
+        }
+        Err(_) => ToluolStatus::EncodeError,
+    }
+}
+
+/// Parses a DNS message in wire format and writes its JSON representation to `out` as a
+/// NUL-terminated string, which must be freed with [`toluol_string_free()`].
+///
+/// # Safety
+/// `msg` must point to at least `len` readable bytes, and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn toluol_message_to_json(
+    msg: *const u8,
+    len: usize,
+    out: *mut *mut c_char,
+) -> ToluolStatus {
+    let msg = match parse_message(msg, len) {
+        Ok(msg) => msg,
+        Err(status) => return status,
+    };
+    match serde_json::to_string(&msg) {
+        Ok(json) => write_string(json, out),
+        Err(_) => ToluolStatus::EncodeError,
+    }
+}
+
+/// Parses a DNS message in wire format and writes a human readable description of it (the same
+/// as `toluol +verbose` prints) to `out` as a NUL-terminated string, which must be freed with
+/// [`toluol_string_free()`].
+///
+/// # Safety
+/// `msg` must point to at least `len` readable bytes, and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn toluol_message_format(
+    msg: *const u8,
+    len: usize,
+    out: *mut *mut c_char,
+) -> ToluolStatus {
+    match parse_message(msg, len) {
+        Ok(msg) => write_string(msg.as_string(None), out),
+        Err(status) => status,
+    }
+}
+
+/// Encodes a recursive query for `name` (a NUL-terminated ASCII string) with the given record type
+/// and class, e.g. 1 and 1 for an `A` query in class `IN`. An EDNS OPT record is always added;
+/// `dnssec_ok` sets its DO flag.
+///
+/// The encoded query is written to `out` and must be freed with [`toluol_buffer_free()`].
+///
+/// # Safety
+/// `name` must be a valid NUL-terminated string, and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn toluol_query_encode(
+    name: *const c_char,
+    qtype: u16,
+    qclass: u16,
+    dnssec_ok: bool,
+    out: *mut ToluolBuffer,
+) -> ToluolStatus {
+    if name.is_null() || out.is_null() {
+        return ToluolStatus::NullPointer;
+    }
+    let name = match CStr::from_ptr(name).to_str().map(Name::from_ascii) {
+        Ok(Ok(name)) => name,
+        _ => return ToluolStatus::InvalidArgument,
+    };
+    let qclass = match Class::parse(qclass) {
+        Ok(qclass) => qclass,
+        Err(_) => return ToluolStatus::InvalidArgument,
+    };
+    let flags = HeaderFlags {
+        aa: false,
+        tc: false,
+        rd: true,
+        ra: false,
+        ad: false,
+        cd: false,
+    };
+    let edns = EdnsConfig {
+        do_flag: dnssec_ok,
+        bufsize: 4096,
+        client_cookie: None,
+        client_subnet: None,
+        edns_version: 0,
+        extra_options: Vec::new(),
+    };
+
+    let msg = Message::new_query_with_class(
+        name,
+        RecordType::from(qtype),
+        qclass,
+        Opcode::QUERY,
+        flags,
+        Some(edns),
+    );
+    match msg.and_then(|msg| msg.encode()) {
+        Ok(encoded) => {
+            *out = encoded.into();
+            ToluolStatus::Ok
+        }
+        Err(_) => ToluolStatus::EncodeError,
+    }
+}
+
+/// Returns a static, NUL-terminated description of `status`, which should be one of the values of
+/// [`ToluolStatus`]; other values are described as an unknown status. The returned string must
+/// not be freed.
+#[no_mangle]
+pub extern "C" fn toluol_status_str(status: c_int) -> *const c_char {
+    // C may pass any integer, which must not be interpreted as a ToluolStatus directly
+    let s = match status {
+        s if s == ToluolStatus::Ok as c_int => "success\0",
+        s if s == ToluolStatus::NullPointer as c_int => "a pointer argument was NULL\0",
+        s if s == ToluolStatus::InvalidArgument as c_int => "invalid argument\0",
+        s if s == ToluolStatus::ParseError as c_int => "could not parse DNS message\0",
+        s if s == ToluolStatus::EncodeError as c_int => "could not encode DNS message\0",
+        _ => "unknown status\0",
+    };
+    s.as_ptr() as *const c_char
+}
+
+/// Frees a string returned by this library. Does nothing if `s` is NULL.
+///
+/// # Safety
+/// `s` must be NULL or have been returned by this library, and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn toluol_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Frees a buffer returned by this library. Does nothing if `buf.data` is NULL.
+///
+/// # Safety
+/// `buf` must have been returned by this library, and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn toluol_buffer_free(buf: ToluolBuffer) {
+    if !buf.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            buf.data, buf.len,
+        )));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Calls `f` with an out pointer for a string and returns the status and the string.
+    fn string_out(f: impl FnOnce(*mut *mut c_char) -> ToluolStatus) -> (ToluolStatus, String) {
+        let mut out = ptr::null_mut();
+        let status = f(&mut out);
+        if out.is_null() {
+            return (status, String::new());
+        }
+        let s = unsafe { CStr::from_ptr(out) }.to_str().unwrap().to_string();
+        unsafe { toluol_string_free(out) };
+        (status, s)
+    }
+
+    #[test]
+    fn query_round_trip() {
+        let mut query = ToluolBuffer {
+            data: ptr::null_mut(),
+            len: 0,
+        };
+        let name = CString::new("example.com").unwrap();
+        let status = unsafe { toluol_query_encode(name.as_ptr(), 16, 1, true, &mut query) };
+        assert_eq!(status, ToluolStatus::Ok);
+
+        let (status, json) =
+            string_out(|out| unsafe { toluol_message_to_json(query.data, query.len, out) });
+        assert_eq!(status, ToluolStatus::Ok);
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            json["questions"][0]["qname"]["labels"],
+            serde_json::json!(["example", "com"])
+        );
+        assert_eq!(json["questions"][0]["qtype"], "TXT");
+
+        let (status, text) =
+            string_out(|out| unsafe { toluol_message_format(query.data, query.len, out) });
+        assert_eq!(status, ToluolStatus::Ok);
+        assert!(text.contains("example.com"));
+
+        // the query is truncated
+        let (status, _) =
+            string_out(|out| unsafe { toluol_message_to_json(query.data, query.len - 1, out) });
+        assert_eq!(status, ToluolStatus::ParseError);
+
+        unsafe { toluol_buffer_free(query) };
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        let mut query = ToluolBuffer {
+            data: ptr::null_mut(),
+            len: 0,
+        };
+        let name = CString::new("exa mple..com").unwrap();
+        let status = unsafe { toluol_query_encode(name.as_ptr(), 1, 1, false, &mut query) };
+        assert_eq!(status, ToluolStatus::InvalidArgument);
+        let status = unsafe { toluol_query_encode(ptr::null(), 1, 1, false, &mut query) };
+        assert_eq!(status, ToluolStatus::NullPointer);
+        assert!(query.data.is_null());
+
+        let status_str = |status| {
+            unsafe { CStr::from_ptr(toluol_status_str(status)) }
+                .to_str()
+                .unwrap()
+        };
+        assert_eq!(
+            status_str(ToluolStatus::ParseError as c_int),
+            "could not parse DNS message"
+        );
+        assert_eq!(status_str(42), "unknown status");
+        assert_eq!(status_str(-1), "unknown status");
+    }
+}
@@ -0,0 +1,35 @@
+//! Checks that the C header `include/toluol.h` matches the bindings.
+//!
+//! Run the tests with `UPDATE_HEADER=1` to regenerate the header after changing the bindings, and
+//! review the diff before committing.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+#[test]
+fn header_is_up_to_date() {
+    let crate_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml"))
+        .expect("Could not read cbindgen.toml.");
+    let mut generated = Vec::new();
+    cbindgen::Builder::new()
+        .with_crate(crate_dir)
+        .with_config(config)
+        .generate()
+        .expect("Could not generate C bindings.")
+        .write(&mut generated);
+    let generated = String::from_utf8(generated).unwrap();
+
+    let path = crate_dir.join("include").join("toluol.h");
+    if env::var_os("UPDATE_HEADER").is_some() {
+        fs::write(&path, generated).unwrap();
+        return;
+    }
+    let header = fs::read_to_string(&path).unwrap_or_default();
+    assert!(
+        header == generated,
+        "{} is out of date, run the tests with UPDATE_HEADER=1",
+        path.display()
+    );
+}
@@ -0,0 +1,396 @@
+//! Generates the enums for the IANA registries in `iana/` (option codes, RCODEs, DNSSEC algorithms,
+//! and digest types), so that updating a registry only requires replacing its CSV file.
+//!
+//! Each entry with a single value and a name becomes a variant with a discriminant (merged with
+//! other entries for the same value, like `BADVERS`/`BADSIG`), documented with its description and
+//! links to its references. Ranges, unassigned and reserved values are covered by the fallback
+//! variant. Variant names are derived from the registry names, except for those in `renames`,
+//! which keep the names the variants had before they were generated.
+
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+struct Registry {
+    /// The CSV file in `iana/`, as downloaded from <https://www.iana.org/protocols>.
+    csv: &'static str,
+    /// The file in `OUT_DIR` that is `include!`d by the crate.
+    out: &'static str,
+    /// The doc comment and attributes of the enum, followed by its signature.
+    header: &'static str,
+    /// The fallback variant of a `repr_with_fallback!` enum; plain enums have none.
+    fallback: Option<&'static str>,
+    /// The column containing the name that is returned by `iana_name()`.
+    name_column: usize,
+    /// The column containing the description used in the doc comment, if other than the name.
+    description_column: Option<usize>,
+    variant_name: fn(&str) -> String,
+    /// Variant names that differ from those derived from the registry.
+    renames: &'static [(u16, &'static str)],
+    /// Additional paragraphs for the doc comments of some variants.
+    notes: &'static [(u16, &'static str)],
+}
+
+const DONT_USE_SHA1: &str = "DON'T USE THIS! SHA1 is not considered secure anymore, and this is \
+                             only provided for compatibility.";
+
+const REGISTRIES: &[Registry] = &[
+    Registry {
+        csv: "dns-parameters-11.csv",
+        out: "option_code.rs",
+        header: "/// The type of option as per [the IANA assignment](
+/// https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#dns-parameters-11).
+#[cfg_attr(feature = \"serde\", derive(Serialize))]
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Hash)]
+#[non_exhaustive]
+pub enum OptionCode",
+        fallback: Some("Unknown(u16)"),
+        name_column: 1,
+        description_column: None,
+        variant_name: camel_case,
+        renames: &[
+            (8, "ClientSubnet"),
+            (9, "Expire"),
+            (10, "Cookie"),
+            (11, "TcpKeepalive"),
+            (14, "KeyTag"),
+            (16, "ClientTag"),
+            (17, "ServerTag"),
+            (19, "ZoneVersion"),
+            (26946, "DeviceId"),
+        ],
+        notes: &[
+            (
+                8,
+                "\"Allows Recursive Resolvers, if they are willing, to forward details about the \
+                 origin network from which a query is coming when talking to other nameservers.\"",
+            ),
+            (
+                10,
+                "\"A lightweight DNS transaction security mechanism that provides limited \
+                 protection to DNS servers and clients against a variety of increasingly common \
+                 denial-of-service and amplification/forgery or cache poisoning attacks by \
+                 off-path attackers.\"",
+            ),
+            (
+                12,
+                "\"Allows DNS clients and servers to pad request and response messages by a \
+                 variable number of octets.\"",
+            ),
+        ],
+    },
+    Registry {
+        csv: "dns-parameters-6.csv",
+        out: "rcode.rs",
+        header: "/// Represents a DNS RCODE, including those introduced by EDNS.
+///
+/// See
+/// [here](https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#dns-parameters-6)
+/// for further information.
+#[cfg_attr(feature = \"serde\", derive(Serialize))]
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+#[non_exhaustive]
+pub enum RCode",
+        fallback: None,
+        name_column: 1,
+        description_column: Some(2),
+        variant_name: upper_case,
+        renames: &[],
+        notes: &[],
+    },
+    Registry {
+        csv: "dns-sec-alg-numbers-1.csv",
+        out: "algorithm.rs",
+        header: "/// Algorithms for use in zone signing (see [`DNSKEY`]) and storing certificates in the DNS
+/// (see [`CERT`](super::cert::CERT)).
+///
+/// See <https://www.iana.org/assignments/dns-sec-alg-numbers/dns-sec-alg-numbers.xhtml> for the
+/// official list.
+#[cfg_attr(feature = \"serde\", derive(Serialize))]
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+#[non_exhaustive]
+#[allow(non_camel_case_types)]
+pub enum Algorithm",
+        fallback: Some("Unassigned(u8)"),
+        name_column: 2,
+        description_column: Some(1),
+        variant_name: snake_mnemonic,
+        renames: &[],
+        notes: &[
+            (
+                1,
+                "DON'T USE THIS! MD5 is not considered secure anymore, and this is only provided \
+                 for compatibility.",
+            ),
+            (
+                3,
+                "DON'T USE THIS! DSA is not considered secure anymore, and this is only provided \
+                 for compatibility.",
+            ),
+            (5, DONT_USE_SHA1),
+            (
+                6,
+                "DON'T USE THIS! DSA and SHA1 are not considered secure anymore, and this is only \
+                 provided for compatibility.",
+            ),
+            (7, DONT_USE_SHA1),
+        ],
+    },
+    Registry {
+        csv: "ds-rr-types-1.csv",
+        out: "digest_type.rs",
+        header: "/// Digest algorithms for the [`DS`] record.
+///
+/// See <https://www.iana.org/assignments/ds-rr-types/ds-rr-types.xhtml> for the official list.
+#[cfg_attr(feature = \"serde\", derive(Serialize))]
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+#[non_exhaustive]
+pub enum DigestType",
+        fallback: Some("Unassigned(u8)"),
+        name_column: 1,
+        description_column: None,
+        variant_name: upper_case,
+        renames: &[(3, "GOST"), (5, "GOST12")],
+        notes: &[],
+    },
+];
+
+/// A value of a registry with the names and descriptions of all its entries.
+struct Entry {
+    value: u16,
+    variant: String,
+    name: String,
+    docs: Vec<String>,
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    let out_dir = env::var("OUT_DIR").expect("Cargo sets OUT_DIR for build scripts.");
+    for registry in REGISTRIES {
+        let path = Path::new("iana").join(registry.csv);
+        println!("cargo:rerun-if-changed={}", path.display());
+        let csv = fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("Could not read {}: {e}", path.display()));
+        let code = generate(registry, &entries(registry, &csv));
+        fs::write(Path::new(&out_dir).join(registry.out), code)
+            .unwrap_or_else(|e| panic!("Could not write {}: {e}", registry.out));
+    }
+}
+
+fn entries(registry: &Registry, csv: &str) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = Vec::new();
+    // the first line contains the column names
+    for line in csv.lines().skip(1).filter(|line| !line.trim().is_empty()) {
+        let fields = parse_line(line);
+        let field = |i: usize| fields.get(i).map(|f| f.trim()).unwrap_or_default();
+        let name = field(registry.name_column);
+        // ranges ("24-3840") and values without a name are covered by the fallback variant
+        let Ok(value) = field(0).parse::<u16>() else {
+            continue;
+        };
+        if name.is_empty() || name.starts_with("Reserved") || name.starts_with("Unassigned") {
+            continue;
+        }
+
+        let description = registry.description_column.map_or(name, field);
+        let mut doc = description.to_string();
+        for reference in references(field(fields.len().saturating_sub(1))) {
+            write!(doc, " {reference}").unwrap();
+        }
+
+        match entries.iter_mut().find(|e| e.value == value) {
+            Some(entry) => {
+                if entry.name != name {
+                    entry.name = format!("{}/{name}", entry.name);
+                    entry.variant = (registry.variant_name)(&entry.name);
+                }
+                entry.docs.push(doc);
+            }
+            None => entries.push(Entry {
+                value,
+                variant: (registry.variant_name)(name),
+                name: name.to_string(),
+                docs: vec![doc],
+            }),
+        }
+    }
+
+    for entry in &mut entries {
+        if let Some((_, rename)) = registry.renames.iter().find(|(v, _)| *v == entry.value) {
+            entry.variant = rename.to_string();
+        }
+        if let Some((_, note)) = registry.notes.iter().find(|(v, _)| *v == entry.value) {
+            entry.docs.push(note.to_string());
+        }
+    }
+    entries
+}
+
+fn generate(registry: &Registry, entries: &[Entry]) -> String {
+    let mut code = String::new();
+    let enum_name = registry
+        .header
+        .rsplit(' ')
+        .next()
+        .expect("The header ends with the name of the enum.");
+    if registry.fallback.is_some() {
+        code.push_str("repr_with_fallback! {\n");
+    }
+    writeln!(code, "{} {{", registry.header).unwrap();
+    for entry in entries {
+        for (i, doc) in entry.docs.iter().enumerate() {
+            if i > 0 {
+                code.push_str("///\n");
+            }
+            for line in wrap(doc) {
+                writeln!(code, "/// {line}").unwrap();
+            }
+        }
+        match registry.fallback {
+            Some(_) => writeln!(code, "{} = {},", entry.variant, entry.value).unwrap(),
+            None => writeln!(code, "{},", entry.variant).unwrap(),
+        }
+    }
+    if let Some(fallback) = registry.fallback {
+        code.push_str("/// A value that is unassigned or reserved, or that was assigned after this version of\n");
+        code.push_str("/// toluol-proto was released.\n");
+        writeln!(code, "{fallback},").unwrap();
+        code.push_str("}\n");
+    }
+    code.push_str("}\n\n");
+
+    writeln!(code, "impl {enum_name} {{").unwrap();
+    match registry.fallback {
+        Some(fallback) => {
+            let fallback = fallback.split('(').next().unwrap_or_default();
+            code.push_str(
+                "/// Returns the name of this value in the IANA registry, or [`None`] for values\n",
+            );
+            writeln!(
+                code,
+                "/// without a name (see [`{enum_name}::{fallback}`])."
+            )
+            .unwrap();
+            code.push_str("pub fn iana_name(&self) -> Option<&'static str> {\n");
+            code.push_str("match self {\n");
+            for entry in entries {
+                writeln!(code, "Self::{} => Some({:?}),", entry.variant, entry.name).unwrap();
+            }
+            writeln!(code, "Self::{fallback}(_) => None,").unwrap();
+            code.push_str("}\n}\n");
+        }
+        None => {
+            code.push_str("/// Returns the name of this value in the IANA registry.\n");
+            code.push_str("pub fn iana_name(&self) -> &'static str {\n");
+            code.push_str("match self {\n");
+            for entry in entries {
+                writeln!(code, "Self::{} => {:?},", entry.variant, entry.name).unwrap();
+            }
+            code.push_str("}\n}\n\n");
+
+            code.push_str("fn value(&self) -> u16 {\n");
+            code.push_str("match self {\n");
+            for entry in entries {
+                writeln!(code, "Self::{} => {},", entry.variant, entry.value).unwrap();
+            }
+            code.push_str("}\n}\n\n");
+
+            code.push_str("fn from_value(value: u16) -> Option<Self> {\n");
+            code.push_str("Some(match value {\n");
+            for entry in entries {
+                writeln!(code, "{} => Self::{},", entry.value, entry.variant).unwrap();
+            }
+            code.push_str("_ => return None,\n");
+            code.push_str("})\n}\n");
+        }
+    }
+    code.push_str("}\n");
+    code
+}
+
+/// Splits a line of a CSV file into its fields, which may be quoted (with `""` for a quote).
+fn parse_line(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+/// Formats the references of an entry (`[RFC8914][draft-...]`) as Markdown links, if possible.
+fn references(field: &str) -> Vec<String> {
+    field
+        .split(['[', ']'])
+        .filter(|r| !r.trim().is_empty())
+        .map(|r| {
+            if let Some(number) = r.strip_prefix("RFC").filter(|n| n.parse::<u16>().is_ok()) {
+                format!("[\\[RFC {number}\\]](https://www.rfc-editor.org/rfc/rfc{number})")
+            } else if r.starts_with("draft-") {
+                format!("[\\[{r}\\]](https://datatracker.ietf.org/doc/{r})")
+            } else if r.starts_with("http") {
+                format!("<{r}>")
+            } else {
+                format!("\\[{r}\\]")
+            }
+        })
+        .collect()
+}
+
+/// Wraps a doc comment so that the generated code stays readable.
+fn wrap(doc: &str) -> Vec<String> {
+    let mut lines = vec![String::new()];
+    for word in doc.split(' ') {
+        let line = lines.last_mut().unwrap();
+        if !line.is_empty() && line.len() + word.len() >= 96 {
+            lines.push(word.to_string());
+        } else {
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+    }
+    lines
+}
+
+/// `edns-tcp-keepalive` becomes `EdnsTcpKeepalive`, `Extended DNS Error` becomes
+/// `ExtendedDnsError`.
+fn camel_case(name: &str) -> String {
+    name.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let (first, rest) = word.split_at(1);
+            let rest = if rest.chars().any(|c| c.is_ascii_lowercase()) {
+                rest.to_string()
+            } else {
+                rest.to_ascii_lowercase()
+            };
+            first.to_ascii_uppercase() + &rest
+        })
+        .collect()
+}
+
+/// `NXDomain` becomes `NXDOMAIN`, `BADVERS/BADSIG` becomes `BADVERSBADSIG`, `SHA-256` becomes
+/// `SHA256`.
+fn upper_case(name: &str) -> String {
+    name.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// `ECC-GOST` becomes `ECC_GOST`.
+fn snake_mnemonic(mnemonic: &str) -> String {
+    mnemonic.replace('-', "_")
+}
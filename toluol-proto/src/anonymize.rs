@@ -0,0 +1,183 @@
+//! Redacting and pseudonymizing sensitive data in [`Message`]s, e.g. to share debug captures
+//! publicly.
+
+use sha2::{Digest, Sha256};
+
+use crate::error::EncodeError;
+use crate::rdata::opt::OptionCode;
+use crate::{Message, Name, Record};
+
+/// Describes which sensitive data to remove from a [`Message`], see [`Anonymizer::anonymize()`].
+///
+/// # Examples
+/// ```rust
+/// use toluol_proto::anonymize::Anonymizer;
+/// use toluol_proto::Name;
+///
+/// let anonymizer = Anonymizer {
+///     zones: vec![Name::from_ascii("corp.example").unwrap()],
+///     key: b"secret".to_vec(),
+///     ..Default::default()
+/// };
+///
+/// let mut name = Name::from_ascii("_ldap._tcp.dc1.corp.example").unwrap();
+/// anonymizer.pseudonymize_name(&mut name);
+/// assert!(name.to_string().starts_with("_ldap._tcp."));
+/// assert!(name.to_string().ends_with(".corp.example"));
+/// assert!(!name.to_string().contains("dc1"));
+///
+/// // the same label always gets the same pseudonym
+/// let mut other = Name::from_ascii("dc1.corp.example").unwrap();
+/// anonymizer.pseudonymize_name(&mut other);
+/// assert!(name.to_string().ends_with(&other.to_string()));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Anonymizer {
+    /// Whether to redact the address in EDNS Client Subnet options. The address family and the
+    /// prefix lengths are kept.
+    pub client_subnet: bool,
+    /// Whether to redact the client and server cookies in EDNS Cookie options. Their length is
+    /// kept.
+    pub cookies: bool,
+    /// Names at or below any of these names are pseudonymized: each label below the zone's name is
+    /// replaced by a label derived from a keyed hash of it. Labels starting with an underscore
+    /// (e.g. `_tcp`) and wildcard labels are kept, as they do not identify anything.
+    pub zones: Vec<Name>,
+    /// The key used for hashing labels. With the same key, a label always gets the same pseudonym,
+    /// so names can still be correlated across messages. The key should be secret, as the
+    /// pseudonyms of guessable labels can be reversed otherwise.
+    pub key: Vec<u8>,
+}
+
+impl Anonymizer {
+    /// Redacts and pseudonymizes the data selected in this `Anonymizer` in all sections of `msg`:
+    /// the names in questions, in record owners, and in the RDATA of record types that contain
+    /// names (e.g. [`CNAME`](crate::rdata::CNAME) or [`SOA`](crate::rdata::SOA)), as well as the
+    /// EDNS options.
+    ///
+    /// Note that this invalidates the signatures of changed records.
+    ///
+    /// Returns an error if a changed record's RDATA cannot be encoded anymore.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::anonymize::Anonymizer;
+    /// use toluol_proto::rdata::opt::OptionCode;
+    /// use toluol_proto::{EdnsConfig, HeaderFlags, Message, Name, Opcode, RecordType};
+    ///
+    /// let flags = HeaderFlags { aa: false, tc: false, rd: true, ra: false, ad: false, cd: false };
+    /// let edns = EdnsConfig {
+    ///     do_flag: false,
+    ///     bufsize: 1232,
+    ///     client_cookie: Some([42; 8]),
+    ///     client_subnet: None,
+    ///     edns_version: 0,
+    ///     extra_options: Vec::new(),
+    /// };
+    /// let name = Name::from_ascii("host.corp.example").unwrap();
+    /// let mut msg =
+    ///     Message::new_query(name, RecordType::A, Opcode::QUERY, flags, Some(edns)).unwrap();
+    ///
+    /// let anonymizer = Anonymizer {
+    ///     cookies: true,
+    ///     zones: vec![Name::from_ascii("corp.example").unwrap()],
+    ///     ..Default::default()
+    /// };
+    /// anonymizer.anonymize(&mut msg).unwrap();
+    ///
+    /// assert_eq!(msg.opt().unwrap().opt_rdata().get(OptionCode::Cookie), Some(&[0; 8][..]));
+    /// assert!(!msg.questions[0].qname.to_string().contains("host"));
+    /// ```
+    pub fn anonymize(&self, msg: &mut Message) -> Result<(), EncodeError> {
+        for question in &mut msg.questions {
+            self.pseudonymize_name(&mut question.qname);
+        }
+
+        for record in msg
+            .answers
+            .iter_mut()
+            .chain(msg.authoritative_answers.iter_mut())
+            .chain(msg.additional_answers.iter_mut())
+        {
+            match record {
+                Record::NONOPT(record) => {
+                    self.pseudonymize_name(&mut record.owner);
+                    let mut changed = false;
+                    for name in record.rdata.names_mut() {
+                        changed |= self.pseudonymize_name(name);
+                    }
+                    if changed {
+                        record.encoded_rdata = record.rdata.encode()?;
+                    }
+                }
+                Record::OPT(record) => {
+                    let mut changed = false;
+                    for (code, value) in &mut record.opt_rdata_mut().options {
+                        match code {
+                            OptionCode::ClientSubnet if self.client_subnet => {
+                                // family (2 bytes), source and scope prefix length (1 byte each)
+                                value.iter_mut().skip(4).for_each(|byte| *byte = 0);
+                                changed = true;
+                            }
+                            OptionCode::Cookie if self.cookies => {
+                                value.iter_mut().for_each(|byte| *byte = 0);
+                                changed = true;
+                            }
+                            _ => {}
+                        }
+                    }
+                    if changed {
+                        record.encoded_rdata = record.rdata.encode()?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Pseudonymizes `name` if it is at or below one of the names in [`Self::zones`] (see there
+    /// for details).
+    ///
+    /// Returns whether `name` was changed.
+    pub fn pseudonymize_name(&self, name: &mut Name) -> bool {
+        let zone_labels = match self.zones.iter().find(|zone| zone.zone_of(name)) {
+            Some(zone) => zone.label_count(),
+            None => return false,
+        };
+
+        let mut labels = Vec::new();
+        while name.label_count() > zone_labels {
+            labels.push(
+                name.pop_front_label()
+                    .expect("name has more labels than zone"),
+            );
+        }
+        let changed = labels.iter().any(|label| !keep_label(label));
+
+        for label in labels.iter().rev() {
+            let label = if keep_label(label) {
+                label.to_string()
+            } else {
+                self.pseudonym(label)
+            };
+            name.prepend_label(label).expect("labels are valid");
+        }
+        changed
+    }
+
+    /// Returns the pseudonym of `label`: the first eight bytes of the SHA-256 hash of the key and
+    /// the lowercased label, hex encoded.
+    fn pseudonym(&self, label: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update((self.key.len() as u64).to_be_bytes());
+        hasher.update(&self.key);
+        hasher.update(label.to_ascii_lowercase());
+        data_encoding::HEXLOWER.encode(&hasher.finalize()[..8])
+    }
+}
+
+/// Returns whether `label` is kept as is when pseudonymizing names.
+fn keep_label(label: &str) -> bool {
+    label == "*" || label.starts_with('_')
+}
@@ -0,0 +1,549 @@
+//! Implementations of [`Arbitrary`](::arbitrary::Arbitrary) for the protocol types, so fuzzers and
+//! property tests can generate random values.
+//!
+//! The generated values are valid, i.e. they can be encoded, and parsing the encoded bytes results
+//! in an equal value. In particular, generated [`Message`]s have consistent record counts and
+//! RCODEs, and never have the TC flag set.
+//!
+//! Only available with the `arbitrary` feature.
+//!
+//! # Examples
+//! ```rust
+//! use std::io::Cursor;
+//!
+//! use arbitrary::{Arbitrary, Unstructured};
+//! use toluol_proto::Message;
+//!
+//! let bytes: Vec<u8> = (0..=255).cycle().take(4096).collect();
+//! let msg = Message::arbitrary(&mut Unstructured::new(&bytes)).unwrap();
+//!
+//! let encoded = msg.encode().unwrap();
+//! assert_eq!(Message::parse(&mut Cursor::new(&encoded)).unwrap(), msg);
+//! ```
+
+use std::collections::BTreeSet;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use ::arbitrary::{Arbitrary, Result, Unstructured};
+use url::Url;
+
+use crate::rdata::opt::OptionCode;
+use crate::rdata::{
+    SvcParam, A, AAAA, CAA, CDNSKEY, CDS, CERT, CNAME, DNAME, DNSKEY, DS, HINFO, LOC, MX, NAPTR,
+    NS, NSEC, NSEC3, NSEC3PARAM, OPENPGPKEY, OPT, PTR, RP, RRSIG, SOA, SRV, SSHFP, SVCB, TLSA, TXT,
+};
+use crate::{
+    Class, Header, HeaderFlags, Message, Name, NonOptRecord, Opcode, OptFlags, OptRecord, Question,
+    RCode, Rdata, Record, RecordType,
+};
+
+/// The maximum number of labels of a generated name, to keep messages reasonably small.
+const MAX_LABELS: usize = 4;
+/// The maximum length of generated byte strings (keys, digests, signatures, ...).
+const MAX_BYTES: usize = 64;
+/// The maximum number of character strings in a generated TXT record.
+const MAX_STRINGS: usize = 4;
+
+/// The RCODEs that fit into the four bits of the header, i.e. can be used without EDNS.
+const HEADER_RCODES: [RCode; 12] = [
+    RCode::NOERROR,
+    RCode::FORMERR,
+    RCode::SERVFAIL,
+    RCode::NXDOMAIN,
+    RCode::NOTIMP,
+    RCode::REFUSED,
+    RCode::YXDOMAIN,
+    RCode::YXRRSET,
+    RCode::NXRRSET,
+    RCode::NOTAUTH,
+    RCode::NOTZONE,
+    RCode::DSOTYPENI,
+];
+
+/// The RCODEs that need EDNS.
+const EXTENDED_RCODES: [RCode; 8] = [
+    RCode::BADVERSBADSIG,
+    RCode::BADKEY,
+    RCode::BADTIME,
+    RCode::BADMODE,
+    RCode::BADNAME,
+    RCode::BADALG,
+    RCode::BADTRUNC,
+    RCode::BADCOOKIE,
+];
+
+impl<'a> Arbitrary<'a> for Name {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut name = Name::root();
+        // the length of the encoded name, starting with the root label
+        let mut len = 1;
+        for _ in 0..u.int_in_range(0..=MAX_LABELS)? {
+            let label = label(u)?;
+            if len + label.len() + 1 > 255 {
+                break;
+            }
+            len += label.len() + 1;
+            name.append_label(label)
+                .expect("generated labels are valid");
+        }
+        Ok(name)
+    }
+}
+
+/// Generates a label that is valid for [`Name::from_ascii()`].
+fn label(u: &mut Unstructured) -> Result<String> {
+    const EDGE: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789_";
+    const MIDDLE: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789_-";
+    let len = u.int_in_range(1..=63)?;
+    let mut label = String::with_capacity(len);
+    for i in 0..len {
+        let chars = if i == 0 || i == len - 1 { EDGE } else { MIDDLE };
+        label.push(*u.choose(chars)? as char);
+    }
+    Ok(label)
+}
+
+/// Generates a character string of printable ASCII characters.
+fn character_string(u: &mut Unstructured) -> Result<String> {
+    let len = u.int_in_range(0..=255)?;
+    (0..len)
+        .map(|_| Ok(u.int_in_range(b' '..=b'~')? as char))
+        .collect()
+}
+
+/// Generates a byte string of at most [`MAX_BYTES`] bytes.
+fn bytes(u: &mut Unstructured) -> Result<Vec<u8>> {
+    let len = u.int_in_range(0..=MAX_BYTES)?.min(u.len());
+    Ok(u.bytes(len)?.to_vec())
+}
+
+/// Generates a sorted list of distinct types, as in the type bitmaps of NSEC and NSEC3 records.
+fn type_bitmap(u: &mut Unstructured) -> Result<Vec<RecordType>> {
+    let types: BTreeSet<u16> = u.arbitrary_iter()?.take(16).collect::<Result<_>>()?;
+    Ok(types.into_iter().map(RecordType::from).collect())
+}
+
+/// Generates a salt as in NSEC3 and NSEC3PARAM records, where an empty salt is [`None`].
+fn salt(u: &mut Unstructured) -> Result<Option<Vec<u8>>> {
+    let salt = bytes(u)?;
+    Ok(if salt.is_empty() { None } else { Some(salt) })
+}
+
+impl<'a> Arbitrary<'a> for Opcode {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        u.choose(&[
+            Opcode::QUERY,
+            Opcode::IQUERY,
+            Opcode::STATUS,
+            Opcode::NOTIFY,
+            Opcode::UPDATE,
+            Opcode::DSO,
+        ])
+        .copied()
+    }
+}
+
+impl<'a> Arbitrary<'a> for RCode {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        if u.arbitrary()? {
+            u.choose(&HEADER_RCODES).copied()
+        } else {
+            u.choose(&EXTENDED_RCODES).copied()
+        }
+    }
+}
+
+impl<'a> Arbitrary<'a> for Class {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        u.choose(&[Class::IN, Class::CH, Class::HS, Class::NONE, Class::ANY])
+            .copied()
+    }
+}
+
+impl<'a> Arbitrary<'a> for RecordType {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(RecordType::from(u16::arbitrary(u)?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for HeaderFlags {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self {
+            aa: u.arbitrary()?,
+            tc: u.arbitrary()?,
+            rd: u.arbitrary()?,
+            ra: u.arbitrary()?,
+            ad: u.arbitrary()?,
+            cd: u.arbitrary()?,
+        })
+    }
+}
+
+/// Generates headers for queries and for responses without EDNS. The record counts are random,
+/// so these headers are only useful on their own; see [`Message`] for consistent headers.
+impl<'a> Arbitrary<'a> for Header {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let qr = u.arbitrary()?;
+        Ok(Self {
+            msg_id: u.arbitrary()?,
+            qr,
+            opcode: u.arbitrary()?,
+            flags: u.arbitrary()?,
+            rcode: if qr {
+                Some(*u.choose(&HEADER_RCODES)?)
+            } else {
+                None
+            },
+            qdcount: u.arbitrary()?,
+            ancount: u.arbitrary()?,
+            nscount: u.arbitrary()?,
+            arcount: u.arbitrary()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Question {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self::new(u.arbitrary()?, u.arbitrary()?, u.arbitrary()?))
+    }
+}
+
+/// Generates any variant except [`Rdata::OPT`], which only appears in [`OptRecord`]s.
+impl<'a> Arbitrary<'a> for Rdata {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(match u.int_in_range(0..=29)? {
+            0 => Rdata::A(A::from(Ipv4Addr::from(u32::arbitrary(u)?))),
+            1 => Rdata::NS(NS {
+                name: u.arbitrary()?,
+            }),
+            2 => Rdata::CNAME(CNAME {
+                cname: u.arbitrary()?,
+            }),
+            3 => Rdata::SOA(SOA {
+                mname: u.arbitrary()?,
+                rname: u.arbitrary()?,
+                serial: u.arbitrary()?,
+                refresh: u.arbitrary()?,
+                retry: u.arbitrary()?,
+                expire: u.arbitrary()?,
+                minimum: u.arbitrary()?,
+            }),
+            4 => Rdata::PTR(PTR {
+                location: u.arbitrary()?,
+            }),
+            5 => Rdata::HINFO(HINFO {
+                cpu: character_string(u)?,
+                os: character_string(u)?,
+            }),
+            6 => Rdata::MX(MX {
+                preference: u.arbitrary()?,
+                exchange: u.arbitrary()?,
+            }),
+            7 => Rdata::TXT(TXT {
+                text: (0..u.int_in_range(1..=MAX_STRINGS)?)
+                    .map(|_| character_string(u))
+                    .collect::<Result<_>>()?,
+            }),
+            8 => Rdata::RP(RP {
+                mbox: u.arbitrary()?,
+                txt: u.arbitrary()?,
+            }),
+            9 => Rdata::AAAA(AAAA::from(Ipv6Addr::from(u128::arbitrary(u)?))),
+            10 => {
+                // base and exponent of the size and precisions are between 0 and 9
+                let mut size = || Ok((u.int_in_range(0..=9)? << 4) | u.int_in_range(0..=9)?);
+                Rdata::LOC(LOC {
+                    size: size()?,
+                    horizontal_precision: size()?,
+                    vertical_precision: size()?,
+                    latitude: u.arbitrary()?,
+                    longitude: u.arbitrary()?,
+                    altitude: u.arbitrary()?,
+                })
+            }
+            11 => Rdata::SRV(SRV {
+                priority: u.arbitrary()?,
+                weight: u.arbitrary()?,
+                port: u.arbitrary()?,
+                target: u.arbitrary()?,
+            }),
+            12 => Rdata::NAPTR(NAPTR {
+                order: u.arbitrary()?,
+                preference: u.arbitrary()?,
+                flags: character_string(u)?,
+                services: character_string(u)?,
+                regexp: character_string(u)?,
+                replacement: u.arbitrary()?,
+            }),
+            13 => Rdata::CERT(CERT {
+                ctype: u16::arbitrary(u)?.into(),
+                key_tag: u.arbitrary()?,
+                algorithm: u8::arbitrary(u)?.into(),
+                data: bytes(u)?,
+            }),
+            14 => Rdata::DNAME(DNAME {
+                target: u.arbitrary()?,
+            }),
+            15 => Rdata::DS(DS {
+                key_tag: u.arbitrary()?,
+                algorithm: u8::arbitrary(u)?.into(),
+                digest_type: u8::arbitrary(u)?.into(),
+                digest: bytes(u)?,
+            }),
+            16 => Rdata::SSHFP(SSHFP {
+                algorithm: u8::arbitrary(u)?.into(),
+                fingerprint_type: u8::arbitrary(u)?.into(),
+                fingerprint: bytes(u)?,
+            }),
+            17 => Rdata::RRSIG(RRSIG {
+                type_covered: u.arbitrary()?,
+                algorithm: u8::arbitrary(u)?.into(),
+                labels: u.arbitrary()?,
+                original_ttl: u.arbitrary()?,
+                signature_expiration: u.arbitrary()?,
+                signature_inception: u.arbitrary()?,
+                key_tag: u.arbitrary()?,
+                signer_name: u.arbitrary()?,
+                signature: bytes(u)?,
+            }),
+            18 => Rdata::NSEC(NSEC {
+                next_domain_name: u.arbitrary()?,
+                types: type_bitmap(u)?,
+            }),
+            19 => Rdata::DNSKEY(DNSKEY {
+                zone: u.arbitrary()?,
+                revoked: u.arbitrary()?,
+                secure_entry_point: u.arbitrary()?,
+                algorithm: u8::arbitrary(u)?.into(),
+                key: bytes(u)?,
+            }),
+            20 => Rdata::NSEC3(NSEC3 {
+                hash_algorithm: u8::arbitrary(u)?.into(),
+                opt_out: u.arbitrary()?,
+                iterations: u.arbitrary()?,
+                salt: salt(u)?,
+                next_hashed_owner: bytes(u)?,
+                types: type_bitmap(u)?,
+            }),
+            21 => Rdata::NSEC3PARAM(NSEC3PARAM {
+                hash_algorithm: u8::arbitrary(u)?.into(),
+                flags: u.arbitrary()?,
+                iterations: u.arbitrary()?,
+                salt: salt(u)?,
+            }),
+            22 => Rdata::TLSA(TLSA {
+                cert_usage: u8::arbitrary(u)?.into(),
+                selector: u8::arbitrary(u)?.into(),
+                matching: u8::arbitrary(u)?.into(),
+                cert_data: bytes(u)?,
+            }),
+            23 => Rdata::OPENPGPKEY(OPENPGPKEY { key: bytes(u)? }),
+            24 => Rdata::CAA(caa(u)?),
+            25 => Rdata::CDS(CDS {
+                key_tag: u.arbitrary()?,
+                algorithm: u8::arbitrary(u)?.into(),
+                digest_type: u8::arbitrary(u)?.into(),
+                digest: bytes(u)?,
+            }),
+            26 => Rdata::CDNSKEY(CDNSKEY {
+                zone: u.arbitrary()?,
+                revoked: u.arbitrary()?,
+                secure_entry_point: u.arbitrary()?,
+                algorithm: u8::arbitrary(u)?.into(),
+                key: bytes(u)?,
+            }),
+            27 => Rdata::SVCB(svcb(u)?),
+            28 => Rdata::HTTPS(svcb(u)?),
+            _ => Rdata::Unknown(bytes(u)?),
+        })
+    }
+}
+
+/// Generates an SVCB record. ServiceMode records get a random selection of parameters, in the
+/// order of their keys.
+fn svcb(u: &mut Unstructured) -> Result<SVCB> {
+    let priority = u.arbitrary()?;
+    let mut params = Vec::new();
+    if priority != 0 {
+        if u.arbitrary()? {
+            let ids = (0..u.int_in_range(1..=3)?)
+                .map(|_| character_string(u))
+                .collect::<Result<_>>()?;
+            params.push(SvcParam::Alpn(ids));
+        }
+        if u.arbitrary()? {
+            params.push(SvcParam::NoDefaultAlpn);
+        }
+        if u.arbitrary()? {
+            params.push(SvcParam::Port(u.arbitrary()?));
+        }
+        if u.arbitrary()? {
+            let addresses = (0..u.int_in_range(1..=2)?)
+                .map(|_| Ok(Ipv4Addr::from(u32::arbitrary(u)?)))
+                .collect::<Result<_>>()?;
+            params.push(SvcParam::Ipv4Hint(addresses));
+        }
+        if u.arbitrary()? {
+            params.push(SvcParam::Ech(bytes(u)?));
+        }
+        if u.arbitrary()? {
+            let addresses = (0..u.int_in_range(1..=2)?)
+                .map(|_| Ok(Ipv6Addr::from(u128::arbitrary(u)?)))
+                .collect::<Result<_>>()?;
+            params.push(SvcParam::Ipv6Hint(addresses));
+        }
+        if u.arbitrary()? {
+            params.push(SvcParam::Unknown(u.int_in_range(65280..=65534)?, bytes(u)?));
+        }
+    }
+    Ok(SVCB {
+        priority,
+        target: u.arbitrary()?,
+        params,
+    })
+}
+
+/// Generates a CAA record with one of the well-known properties.
+fn caa(u: &mut Unstructured) -> Result<CAA> {
+    const PARAMETER_CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789-_.:/";
+    let issuer_critical = u.arbitrary()?;
+    if u.ratio(1, 3)? {
+        let host: Name = u.arbitrary()?;
+        let url = Url::parse(&format!("mailto:security@{}", host))
+            .expect("URLs with valid domain names are valid");
+        return Ok(CAA::iodef(issuer_critical, url));
+    }
+
+    // the value of the record is a single character string
+    let name: Option<Name> = u.arbitrary()?;
+    let mut len = name.as_ref().map_or(1, |name| name.to_string().len() + 2);
+    let mut parameters = Vec::new();
+    for _ in 0..u.int_in_range(0..=3)? {
+        let word = |u: &mut Unstructured, len| -> Result<String> {
+            (0..len)
+                .map(|_| Ok(*u.choose(PARAMETER_CHARS)? as char))
+                .collect()
+        };
+        let tag_len = u.int_in_range(1..=8)?;
+        let tag = word(u, tag_len)?;
+        let value_len = u.int_in_range(0..=16)?;
+        let value = word(u, value_len)?;
+        len += tag.len() + value.len() + 2;
+        if len > 255 {
+            break;
+        }
+        parameters.push((tag, value));
+    }
+    Ok(if u.arbitrary()? {
+        CAA::issue(issuer_critical, name, parameters)
+    } else {
+        CAA::issue_wild(issuer_critical, name, parameters)
+    })
+}
+
+impl<'a> Arbitrary<'a> for NonOptRecord {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let rdata: Rdata = u.arbitrary()?;
+        let unknown = matches!(rdata, Rdata::Unknown(_));
+        let mut record = NonOptRecord::new(u.arbitrary()?, u.arbitrary()?, u.arbitrary()?, rdata)
+            .expect("generated RDATA can be encoded");
+        if unknown {
+            // use a type from the private use range, which is never known
+            record.rtype = RecordType::from(u.int_in_range(65280..=65534)?);
+        }
+        Ok(record)
+    }
+}
+
+/// Generates an OPT record without an extended RCODE; see [`Message`] for consistent RCODEs.
+impl<'a> Arbitrary<'a> for OptRecord {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut options = Vec::new();
+        for _ in 0..u.int_in_range(0..=3)? {
+            let code = match u.int_in_range(0..=2)? {
+                0 => OptionCode::Cookie,
+                1 => OptionCode::Padding,
+                _ => OptionCode::from(u16::arbitrary(u)?),
+            };
+            options.push((code, bytes(u)?));
+        }
+        let rdata = Rdata::OPT(OPT { options });
+        Ok(Self {
+            owner: Name::root(),
+            payload_size: u.arbitrary()?,
+            rcode: Some(RCode::NOERROR),
+            edns_version: u.arbitrary()?,
+            flags: if u.arbitrary()? {
+                vec![OptFlags::DO]
+            } else {
+                Vec::new()
+            },
+            rdlength_warning: None,
+            encoded_rdata: rdata.encode().expect("generated OPT RDATA can be encoded"),
+            rdata,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Record {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(if u.ratio(1, 8)? {
+            Record::OPT(u.arbitrary()?)
+        } else {
+            Record::NONOPT(u.arbitrary()?)
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Message {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let qr: bool = u.arbitrary()?;
+        let mut opt: Option<OptRecord> = u.arbitrary()?;
+        let rcode = match (qr, &opt) {
+            (false, _) => None,
+            (true, Some(_)) => Some(u.arbitrary()?),
+            (true, None) => Some(*u.choose(&HEADER_RCODES)?),
+        };
+        if let Some(opt) = &mut opt {
+            // the OPT record holds the upper bits of the RCODE, so both must agree
+            opt.rcode = rcode;
+        }
+
+        let records = |u: &mut Unstructured<'a>, max| -> Result<Vec<Record>> {
+            (0..u.int_in_range(0..=max)?)
+                .map(|_| Ok(Record::NONOPT(u.arbitrary()?)))
+                .collect()
+        };
+        let questions = (0..u.int_in_range(0..=2)?)
+            .map(|_| u.arbitrary())
+            .collect::<Result<Vec<Question>>>()?;
+        let answers = records(u, 4)?;
+        let authoritative_answers = records(u, 2)?;
+        let mut additional_answers = records(u, 2)?;
+        if let Some(opt) = opt {
+            let idx = u.int_in_range(0..=additional_answers.len())?;
+            additional_answers.insert(idx, Record::OPT(opt));
+        }
+
+        let mut flags: HeaderFlags = u.arbitrary()?;
+        // truncated messages are rejected by `Message::parse()`
+        flags.tc = false;
+        Ok(Self {
+            header: Header {
+                msg_id: u.arbitrary()?,
+                qr,
+                opcode: u.arbitrary()?,
+                flags,
+                rcode,
+                qdcount: questions.len() as u16,
+                ancount: answers.len() as u16,
+                nscount: authoritative_answers.len() as u16,
+                arcount: additional_answers.len() as u16,
+            },
+            questions,
+            answers,
+            authoritative_answers,
+            additional_answers,
+        })
+    }
+}
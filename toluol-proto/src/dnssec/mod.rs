@@ -0,0 +1,798 @@
+//! DNSSEC validation.
+
+use std::cmp::min;
+use std::fmt::{self, Display};
+
+use sha2::{Digest, Sha256};
+
+use crate::error::DnssecError;
+use crate::rdata::dnskey::Algorithm;
+use crate::rdata::{RdataTrait, DNSKEY, RRSIG};
+use crate::{Class, Name, NonOptRecord, RecordType};
+
+pub mod policy;
+pub mod time;
+
+pub use policy::{Nsec3IterationPolicy, ValidationPolicy};
+use time::serial_lt;
+
+/// A set of resource records with the same owner name and [`RecordType`]. Used to validate records.
+#[derive(Clone, Debug)]
+pub struct RrSet {
+    records: Vec<NonOptRecord>,
+    record_type: RecordType,
+    class: Class,
+}
+
+impl RrSet {
+    /// Create a new `RrSet`.
+    ///
+    /// `records` must non be empty, and all records must have the same record type, owner name, and
+    /// class.
+    pub fn new(records: Vec<NonOptRecord>) -> Result<Self, DnssecError> {
+        if records.is_empty() {
+            return Err(DnssecError::EmptyRrset);
+        }
+
+        let record_type = records[0].rtype;
+        let owner = &records[0].owner;
+        let class = records[0].class;
+
+        if records
+            .iter()
+            .any(|rec| (rec.rtype != record_type) || (&rec.owner != owner) || (rec.class != class))
+        {
+            return Err(DnssecError::InvalidRrSet);
+        }
+
+        Ok(Self {
+            records,
+            record_type,
+            class,
+        })
+    }
+
+    /// Validates the record set with each record in `rrsigs` that covers it, using the records in
+    /// `dnskeys` whose key tag matches the RRSIG's, and returns the outcome for each of these
+    /// RRSIGs in the order in which they appear in `rrsigs`.
+    ///
+    /// The record set is authentic if any of the outcomes is `Ok(())`: zones commonly publish
+    /// multiple signatures for a record set, e.g. during an algorithm rollover. Records in `rrsigs`
+    /// that do not cover the set are ignored, so all RRSIGs of a message may be passed. If no
+    /// record in `rrsigs` covers the set, the returned `Vec` is empty.
+    ///
+    /// If `ignore_time` is true, the signature inception and expiration times are ignored.
+    ///
+    /// All records in the set and all covering RRSIGs are canonicalized. If the set is authentic,
+    /// the TTLs of its records and of the first valid RRSIG are updated as described for
+    /// [`validate_signature()`](Self::validate_signature()).
+    ///
+    /// All DNSKEYs are accepted, see [`validate_with()`](Self::validate_with()) to only accept
+    /// some of them.
+    pub fn validate(
+        &mut self,
+        rrsigs: &mut [NonOptRecord],
+        dnskeys: &[NonOptRecord],
+        ignore_time: bool,
+    ) -> Vec<SignatureValidation> {
+        self.validate_with(rrsigs, dnskeys, ignore_time, &ValidationPolicy::default())
+    }
+
+    /// Like [`validate()`](Self::validate()), but signatures by DNSKEYs that `policy` does not
+    /// accept fail with the error of [`ValidationPolicy::check_dnskey()`].
+    pub fn validate_with(
+        &mut self,
+        rrsigs: &mut [NonOptRecord],
+        dnskeys: &[NonOptRecord],
+        ignore_time: bool,
+        policy: &ValidationPolicy,
+    ) -> Vec<SignatureValidation> {
+        let rrset_received_ttl = self.received_ttl();
+        let mut new_ttl = None;
+        let mut outcomes = Vec::new();
+
+        for rrsig_record in rrsigs.iter_mut() {
+            if !self.is_covered_by(rrsig_record) {
+                continue;
+            }
+            let (key_tag, algorithm) = match rrsig_record.rdata.as_rrsig() {
+                Some(rrsig) => (rrsig.key_tag, rrsig.algorithm),
+                None => unreachable!("checked by is_covered_by()"),
+            };
+
+            let mut result = Err(DnssecError::NoMatchingDnskey);
+            let candidates: Vec<_> = dnskeys
+                .iter()
+                .filter(|rec| {
+                    rec.rdata
+                        .as_dnskey()
+                        .is_some_and(|dnskey| dnskey.key_tag() == key_tag)
+                })
+                .collect();
+            let mut keys_tried = 0;
+            let mut key_digest = None;
+            for dnskey_record in &candidates {
+                keys_tried += 1;
+                result = match dnskey_record
+                    .rdata
+                    .as_dnskey()
+                    .map(|key| policy.check_dnskey(key))
+                {
+                    Some(Err(e)) => Err(e),
+                    _ => self.verify(rrsig_record, dnskey_record, ignore_time, rrset_received_ttl),
+                };
+                if result.is_ok() {
+                    key_digest = dnskey_record.rdata.as_dnskey().map(DNSKEY::key_digest);
+                    break;
+                }
+            }
+
+            let result = result.map(|ttl| {
+                if new_ttl.is_none() {
+                    rrsig_record.ttl = ttl;
+                    new_ttl = Some(ttl);
+                }
+            });
+            outcomes.push(SignatureValidation {
+                key_tag,
+                algorithm,
+                result,
+                key_digest,
+                candidates: candidates.len(),
+                keys_tried,
+            });
+        }
+
+        if let Some(ttl) = new_ttl {
+            self.records.iter_mut().for_each(|rec| rec.ttl = ttl);
+        }
+        outcomes
+    }
+
+    /// Canonicalizes all records in the set and `rrsig_record`, validates the signature from
+    /// `rrsig_record` using the key from `dnskey_record`, and updates the TTL of all records in the
+    /// set and of `rrsig_record` according to the rules from RFC 4035, Section 5.3.3.
+    ///
+    /// If `ignore_time` is true, the signature inception and expiration times are ignored.
+    ///
+    /// If the signature is valid, `Ok(())` is returned. If it is invalid, an error is returned.
+    ///
+    /// To retrieve the validated and canonicalized records, use
+    /// [`into_records()`](Self::into_records()).
+    ///
+    /// The canonicalization of `rrsig_record` is always done, but its TTL is only updated if the
+    /// signature is valid.
+    pub fn validate_signature(
+        &mut self,
+        rrsig_record: &mut NonOptRecord,
+        dnskey_record: &NonOptRecord,
+        ignore_time: bool,
+    ) -> Result<(), DnssecError> {
+        let rrset_received_ttl = self.received_ttl();
+        let new_ttl = self.verify(rrsig_record, dnskey_record, ignore_time, rrset_received_ttl)?;
+
+        rrsig_record.ttl = new_ttl;
+        self.records.iter_mut().for_each(|rec| rec.ttl = new_ttl);
+
+        Ok(())
+    }
+
+    /// Canonicalizes all records in the set and `rrsig_record` and validates the signature from
+    /// `rrsig_record` using the key from `dnskey_record`.
+    ///
+    /// If the signature is valid, returns the TTL that the records in the set and `rrsig_record`
+    /// should be given, computed from `rrset_received_ttl` (the smallest TTL of the set as
+    /// received) according to the rules from RFC 4035, Section 5.3.3.
+    fn verify(
+        &mut self,
+        rrsig_record: &mut NonOptRecord,
+        dnskey_record: &NonOptRecord,
+        ignore_time: bool,
+        rrset_received_ttl: u32,
+    ) -> Result<u32, DnssecError> {
+        let (rrsig, dnskey) =
+            self.check_rrsig_and_dnskey(rrsig_record, dnskey_record, ignore_time)?;
+
+        rrsig.canonicalize();
+        let canonicalize_res: Result<Vec<_>, _> = self
+            .records
+            .iter_mut()
+            .map(|rec| rec.canonicalize(rrsig.labels, rrsig.original_ttl))
+            .collect();
+        canonicalize_res?;
+
+        // because of lifetime issues, we cannot just do
+        // `self.records.sort_unstable_by_key(|rec| &rec.encoded_rdata)`.
+        // the solution is to create a temporary array containing the encoded rdata slices, sort
+        // that and apply the same permutation to `self.records`.
+        let temp_rdata: Vec<_> = self.records.iter().map(|rec| &rec.encoded_rdata).collect();
+        let mut perm = permutation::sort(&temp_rdata);
+        perm.apply_slice_in_place(&mut self.records);
+
+        /*
+        From RFC 4034, Section 6.3:
+            RFC 2181 specifies that an RRset is not allowed to contain duplicate records (multiple
+            RRs with the same owner name, class, type, and RDATA). Therefore, if an implementation
+            detects duplicate RRs when putting the RRset in canonical form, it MUST treat this as a
+            protocol error. If the implementation chooses to handle this protocol error in the
+            spirit of the robustness principle (being liberal in what it accepts), it MUST remove
+            all but one of the duplicate RR(s) for the purposes of calculating the canonical form of
+            the RRset.
+        */
+
+        // for the same lifetime reasons as above, we can't use a reference to the encoded rdata as
+        // the key. instead, we compute the hash of the encoded rdata. this also removes all
+        // duplicates.
+        self.records
+            .dedup_by_key(|rec| Sha256::digest(&rec.encoded_rdata));
+
+        let mut data_to_be_signed = Vec::with_capacity(1024);
+        rrsig.encode_into_without_signature(&mut data_to_be_signed)?;
+
+        for record in &self.records {
+            record.encode_into(&mut data_to_be_signed)?;
+        }
+
+        dnskey.validate(&data_to_be_signed, &rrsig.signature)?;
+
+        /*
+        From RFC 4035, Section 5.3.3:
+            If the resolver accepts the RRset as authentic, the validator MUST set the TTL of the
+            RRSIG RR and each RR in the authenticated RRset to a value no greater than the minimum
+            of:
+            o  the RRset's TTL as received in the response;
+            o  the RRSIG RR's TTL as received in the response;
+            o  the value in the RRSIG RR's Original TTL field; and
+            o  the difference of the RRSIG RR's Signature Expiration time and the current time.
+        */
+        let sig_valid_duration = time::seconds_until(rrsig.signature_expiration, time::now());
+
+        let new_ttl = min(rrset_received_ttl, rrsig.original_ttl);
+        let new_ttl = min(new_ttl, sig_valid_duration);
+        let new_ttl = min(new_ttl, rrsig_record.ttl);
+
+        Ok(new_ttl)
+    }
+
+    /// Returns the smallest TTL of the records in the set.
+    fn received_ttl(&self) -> u32 {
+        self.records
+            .iter()
+            .map(|rec| rec.ttl)
+            .min()
+            .expect("Empty record set")
+    }
+
+    /// Returns whether `record` is an RRSIG record covering this set.
+    fn is_covered_by(&self, record: &NonOptRecord) -> bool {
+        record.owner == *self.owner()
+            && record.class == self.class
+            && record
+                .rdata
+                .as_rrsig()
+                .is_some_and(|rrsig| rrsig.type_covered == self.record_type)
+    }
+
+    /// Consumes the `Rrset` and returns the contained records.
+    pub fn into_records(self) -> Vec<NonOptRecord> {
+        self.records
+    }
+
+    /// Returns the contained records.
+    pub fn records(&self) -> &[NonOptRecord] {
+        &self.records
+    }
+
+    /// Returns the owner name of the records in the set.
+    pub fn owner(&self) -> &Name {
+        &self.records[0].owner
+    }
+
+    /// Returns the type of the records in the set.
+    pub fn record_type(&self) -> RecordType {
+        self.record_type
+    }
+
+    /// Returns the class of the records in the set.
+    pub fn class(&self) -> Class {
+        self.class
+    }
+
+    /// Checks that the given RRSIG and DNSKEY record are valid and match the record set as well as
+    /// each other.
+    ///
+    /// If `ignore_time` is true, the signature inception and expiration times are ignored.
+    ///
+    /// Returns the extracted RRSIG and DNSKEY RDATA.
+    fn check_rrsig_and_dnskey<'r, 'd>(
+        &self,
+        rrsig_record: &'r mut NonOptRecord,
+        dnskey_record: &'d NonOptRecord,
+        ignore_time: bool,
+    ) -> Result<(&'r mut RRSIG, &'d DNSKEY), DnssecError> {
+        if rrsig_record.rtype != RecordType::RRSIG {
+            return Err(DnssecError::NonRrsigRecordGiven);
+        }
+        let rrsig = match rrsig_record.rdata.as_mut_rrsig() {
+            Some(rrsig) => rrsig,
+            None => return Err(DnssecError::NonRrsigRecordGiven),
+        };
+
+        if dnskey_record.rtype != RecordType::DNSKEY {
+            return Err(DnssecError::NonDnskeyRecordGiven);
+        }
+        let dnskey = match dnskey_record.rdata.as_dnskey() {
+            Some(key) => key,
+            None => return Err(DnssecError::NonDnskeyRecordGiven),
+        };
+
+        if rrsig.type_covered != self.record_type {
+            return Err(DnssecError::RrsigDoesNotCoverType);
+        }
+
+        let owner = &self.records[0].owner;
+        if &rrsig_record.owner != owner {
+            return Err(DnssecError::RrsigHasDifferentOwner);
+        }
+
+        if rrsig_record.class != self.class {
+            return Err(DnssecError::RrsigHasDifferentClass);
+        }
+
+        if serial_lt(rrsig.signature_expiration, rrsig.signature_inception) {
+            return Err(DnssecError::RrsigExpirationBeforeInception);
+        }
+
+        if !ignore_time {
+            let now = time::now();
+            if serial_lt(now, rrsig.signature_inception) {
+                return Err(DnssecError::RrsigNotValidYet);
+            }
+            if serial_lt(rrsig.signature_expiration, now) {
+                return Err(DnssecError::RrsigExpired);
+            }
+        }
+
+        if !rrsig.signer_name.zone_of(owner) {
+            return Err(DnssecError::RrsigSignerNotInParentZone);
+        }
+        if rrsig.signer_name != dnskey_record.owner {
+            return Err(DnssecError::RrsigSignerDoesNotMatchDnskey);
+        }
+
+        if rrsig.key_tag != dnskey.key_tag() {
+            return Err(DnssecError::RrsigKeyTagDoesNotMatchDnskey);
+        }
+
+        if rrsig.algorithm != dnskey.algorithm {
+            return Err(DnssecError::RrsigAlgorithmDoesNotMatchDnskey);
+        }
+
+        if !dnskey.zone {
+            return Err(DnssecError::DnskeyNoZoneFlag);
+        }
+
+        if dnskey.revoked {
+            return Err(DnssecError::DnskeyRevoked);
+        }
+
+        Ok((rrsig, dnskey))
+    }
+}
+
+/// The outcome of validating a record set with one of its RRSIGs, see [`RrSet::validate()`].
+#[derive(Debug)]
+pub struct SignatureValidation {
+    /// The key tag of the DNSKEY the RRSIG refers to.
+    pub key_tag: u16,
+    /// The algorithm of the RRSIG.
+    pub algorithm: Algorithm,
+    /// `Ok(())` if the signature is valid, or why it is not.
+    pub result: Result<(), DnssecError>,
+    /// The [digest](DNSKEY::key_digest()) of the DNSKEY that verified the signature, if it is
+    /// valid. As several DNSKEYs may share a key tag, this tells which one it was.
+    pub key_digest: Option<[u8; 32]>,
+    /// The number of given DNSKEYs whose key tag matches the RRSIG's; more than one means that
+    /// key tags collide.
+    pub candidates: usize,
+    /// The number of these DNSKEYs that were tried until one verified the signature.
+    pub keys_tried: usize,
+}
+
+/// The outcome of validating a record set, see [`validate_rrsets()`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ValidationState {
+    /// The record set was validated with a signature from one of the given DNSKEYs.
+    Secure,
+    /// The record set has no signature, or only signatures by DNSKEYs that the
+    /// [`ValidationPolicy`] does not accept.
+    Insecure,
+    /// None of the record set's signatures could be validated, and at least one of them has a
+    /// matching DNSKEY.
+    Bogus,
+    /// The record set is signed, but none of the given DNSKEYs matches any of its signatures.
+    Indeterminate,
+}
+
+impl Display for ValidationState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self {
+            Self::Secure => "secure",
+            Self::Insecure => "insecure",
+            Self::Bogus => "bogus",
+            Self::Indeterminate => "indeterminate",
+        };
+        f.write_str(state)
+    }
+}
+
+/// The result of validating one record set, see [`validate_rrsets()`].
+#[derive(Debug)]
+pub struct RrSetValidation {
+    /// The owner name of the record set.
+    pub owner: Name,
+    /// The type of the record set.
+    pub rtype: RecordType,
+    /// The class of the record set.
+    pub class: Class,
+    /// The outcome of the validation.
+    pub state: ValidationState,
+    /// The outcome for each RRSIG covering the record set.
+    pub signatures: Vec<SignatureValidation>,
+    /// Problems with the record set that do not concern its signatures.
+    pub warnings: Vec<ValidationWarning>,
+}
+
+impl RrSetValidation {
+    /// Returns the key tag of the DNSKEY that validated the record set if it is secure, or else
+    /// the key tag of its first RRSIG, if it has one.
+    pub fn key_tag(&self) -> Option<u16> {
+        self.signatures
+            .iter()
+            .find(|sig| sig.result.is_ok())
+            .or_else(|| self.signatures.first())
+            .map(|sig| sig.key_tag)
+    }
+
+    /// Returns why the record set is [`ValidationState::Bogus`] or
+    /// [`ValidationState::Indeterminate`], i.e. the first error that is not
+    /// [`DnssecError::NoMatchingDnskey`], if any.
+    pub fn error(&self) -> Option<&DnssecError> {
+        if self.state == ValidationState::Secure {
+            return None;
+        }
+        let mut errors = self
+            .signatures
+            .iter()
+            .filter_map(|sig| sig.result.as_ref().err());
+        let first = errors.clone().next();
+        errors
+            .find(|e| !matches!(e, DnssecError::NoMatchingDnskey))
+            .or(first)
+    }
+}
+
+/// A problem with a record set that does not concern its signatures, see
+/// [`RrSetValidation::warnings`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ValidationWarning {
+    /// An NSEC3 record of the set uses more additional hash iterations than `limit`, the highest
+    /// threshold of the [`Nsec3IterationPolicy`] it exceeds.
+    Nsec3Iterations { iterations: u16, limit: u16 },
+}
+
+impl Display for ValidationWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Nsec3Iterations { iterations, limit } => write!(
+                f,
+                "NSEC3 uses {} additional hash iterations (more than {})",
+                iterations, limit
+            ),
+        }
+    }
+}
+
+/// Groups `records` into record sets and validates each set that is signed by one of the RRSIG
+/// records in `records`, using `dnskeys`.
+///
+/// The results are returned in the order in which the record sets first appear in `records`;
+/// RRSIG records are not validated themselves. As the records are copied, they are not changed,
+/// i.e. they are neither canonicalized nor are their TTLs updated. To validate owned records, e.g.
+/// from [`Message::take_rrsets()`](crate::Message::take_rrsets()), use [`validate_rrset()`].
+///
+/// The default [`ValidationPolicy`] is used, see [`validate_rrsets_with()`] to use another.
+///
+/// Note that the chain of trust of `dnskeys` is not checked, so [`ValidationState::Insecure`]
+/// only means that a record set is not signed, not that its zone is provably unsigned.
+pub fn validate_rrsets<'a>(
+    records: impl IntoIterator<Item = &'a NonOptRecord>,
+    dnskeys: &[NonOptRecord],
+) -> Vec<RrSetValidation> {
+    validate_rrsets_with(records, dnskeys, &ValidationPolicy::default())
+}
+
+/// Like [`validate_rrsets()`], but uses `policy`.
+pub fn validate_rrsets_with<'a>(
+    records: impl IntoIterator<Item = &'a NonOptRecord>,
+    dnskeys: &[NonOptRecord],
+    policy: &ValidationPolicy,
+) -> Vec<RrSetValidation> {
+    let (mut rrsigs, records): (Vec<_>, Vec<_>) = records
+        .into_iter()
+        .cloned()
+        .partition(|rec| rec.rtype == RecordType::RRSIG);
+
+    group_rrsets(records)
+        .iter_mut()
+        .map(|rrset| validate_rrset_with(rrset, &mut rrsigs, dnskeys, policy))
+        .collect()
+}
+
+/// Validates `rrset` using the records in `rrsigs` that cover it and the records in `dnskeys` whose
+/// key tag matches an RRSIG's. The set is secure if any of its signatures is valid.
+///
+/// The records of `rrset` and the RRSIGs may be canonicalized even if the set is not secure. If it
+/// is secure, their TTLs are updated, see [`RrSet::validate()`]. The default
+/// [`ValidationPolicy`] is used, see [`validate_rrset_with()`] to use another.
+///
+/// Note that the chain of trust of `dnskeys` is not checked, so [`ValidationState::Insecure`]
+/// only means that a record set is not signed, not that its zone is provably unsigned.
+pub fn validate_rrset(
+    rrset: &mut RrSet,
+    rrsigs: &mut [NonOptRecord],
+    dnskeys: &[NonOptRecord],
+) -> RrSetValidation {
+    validate_rrset_with(rrset, rrsigs, dnskeys, &ValidationPolicy::default())
+}
+
+/// Like [`validate_rrset()`], but uses `policy`: signatures by DNSKEYs it does not accept do not
+/// make the set secure (see [`ValidationPolicy`]), and an NSEC3 set is treated according to
+/// [`ValidationPolicy::nsec3`].
+pub fn validate_rrset_with(
+    rrset: &mut RrSet,
+    rrsigs: &mut [NonOptRecord],
+    dnskeys: &[NonOptRecord],
+    policy: &ValidationPolicy,
+) -> RrSetValidation {
+    let signatures = rrset.validate_with(rrsigs, dnskeys, false, policy);
+    let not_accepted = |sig: &SignatureValidation| {
+        matches!(
+            sig.result,
+            Err(DnssecError::AlgorithmNotAccepted(_) | DnssecError::RsaKeyTooShort { .. })
+        )
+    };
+
+    let state = if signatures.is_empty() {
+        ValidationState::Insecure
+    } else if signatures.iter().any(|sig| sig.result.is_ok()) {
+        ValidationState::Secure
+    } else if signatures
+        .iter()
+        .all(|sig| matches!(sig.result, Err(DnssecError::NoMatchingDnskey)))
+    {
+        ValidationState::Indeterminate
+    } else if !policy.reject_unaccepted
+        && signatures.iter().all(|sig| {
+            not_accepted(sig) || matches!(sig.result, Err(DnssecError::NoMatchingDnskey))
+        })
+    {
+        ValidationState::Insecure
+    } else {
+        ValidationState::Bogus
+    };
+    let (state, warning) = policy.nsec3.apply(rrset, state);
+
+    RrSetValidation {
+        owner: rrset.owner().clone(),
+        rtype: rrset.record_type,
+        class: rrset.class,
+        state,
+        signatures,
+        warnings: warning.into_iter().collect(),
+    }
+}
+
+/// Groups `records` into record sets, in the order in which the sets first appear in `records`.
+pub(crate) fn group_rrsets(records: impl IntoIterator<Item = NonOptRecord>) -> Vec<RrSet> {
+    let mut rrsets: Vec<RrSet> = Vec::new();
+    for record in records {
+        let rrset = rrsets.iter_mut().find(|rrset| {
+            rrset.record_type == record.rtype
+                && rrset.class == record.class
+                && rrset.owner() == &record.owner
+        });
+        match rrset {
+            Some(rrset) => rrset.records.push(record),
+            None => rrsets.push(RrSet {
+                record_type: record.rtype,
+                class: record.class,
+                records: vec![record],
+            }),
+        }
+    }
+    rrsets
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use data_encoding::BASE64;
+
+    use crate::rdata::dnskey::{Algorithm, DNSKEY};
+    use crate::rdata::nsec3::HashAlgorithm;
+    use crate::rdata::{A, NSEC3, RRSIG};
+    use crate::{Class, Name, NonOptRecord, RecordType};
+
+    use super::{
+        time, validate_rrsets, validate_rrsets_with, Nsec3IterationPolicy, RrSet, ValidationPolicy,
+        ValidationState, ValidationWarning,
+    };
+    use crate::error::DnssecError;
+
+    /// Returns the DNSKEY, A, and RRSIG records from the example in RFC 6605, Section 6.1.
+    fn rfc6605_example() -> (NonOptRecord, NonOptRecord, NonOptRecord) {
+        let example_net = Name::from_ascii("example.net").unwrap();
+        let www_example_net = Name::from_ascii("www.example.net").unwrap();
+
+        let dnskey = DNSKEY {
+            zone: true,
+            secure_entry_point: true,
+            revoked: false,
+            algorithm: Algorithm::ECDSAP256SHA256,
+            key: BASE64
+                .decode(
+                    b"GojIhhXUN/u4v54ZQqGSnyhWJwaubCvTmeexv7bR6edbkrSqQpF64cYbcB7wNcP+e+MAnLr+Wi9xMWyQLc8NAA==",
+                )
+                .unwrap(),
+        };
+        let dnskey_record = NonOptRecord::new(example_net, Class::IN, 3600, dnskey.into()).unwrap();
+
+        let a_record = NonOptRecord::new(
+            www_example_net.clone(),
+            Class::IN,
+            3600,
+            A {
+                address: Ipv4Addr::new(192, 0, 2, 1),
+            }
+            .into(),
+        )
+        .unwrap();
+
+        let signature_expiration = time::parse("20100909100439").unwrap();
+        let signature_inception = time::parse("20100812100439").unwrap();
+        let rrsig = RRSIG {
+            type_covered: RecordType::A,
+            algorithm: Algorithm::ECDSAP256SHA256,
+            labels: 3,
+            original_ttl: 3600,
+            signature_expiration,
+            signature_inception,
+            key_tag: 55648,
+            signer_name: Name::from_ascii("example.net").unwrap(),
+            signature: BASE64.decode(b"qx6wLYqmh+l9oCKTN6qIc+bw6ya+KJ8oMz0YP107epXAyGmt+3SNruPFKG7tZoLBLlUzGGus7ZwmwWep666VCw==").unwrap(),
+        };
+        let rrsig_record =
+            NonOptRecord::new(www_example_net, Class::IN, 3600, rrsig.into()).unwrap();
+
+        (dnskey_record, a_record, rrsig_record)
+    }
+
+    #[test]
+    fn validate_ecdsap256_sha256() {
+        let (dnskey_record, a_record, mut rrsig_record) = rfc6605_example();
+        let mut rr_set = RrSet::new(vec![a_record]).unwrap();
+        rr_set
+            .validate_signature(&mut rrsig_record, &dnskey_record, true)
+            .unwrap();
+    }
+
+    #[test]
+    fn validate_multiple_rrsigs() {
+        let (dnskey_record, a_record, rrsig_record) = rfc6605_example();
+        let mut rr_set = RrSet::new(vec![a_record]).unwrap();
+
+        // a signature by an unknown key, e.g. during a rollover, and one that covers another type
+        let mut unknown_key = rrsig_record.clone();
+        unknown_key.rdata.as_mut_rrsig().unwrap().key_tag = 1;
+        let mut other_type = rrsig_record.clone();
+        other_type.rdata.as_mut_rrsig().unwrap().type_covered = RecordType::AAAA;
+
+        let mut rrsigs = [unknown_key, rrsig_record, other_type];
+        let res = rr_set.validate(&mut rrsigs, &[dnskey_record], true);
+        assert_eq!(res.len(), 2);
+        assert_eq!(res[0].key_tag, 1);
+        assert!(matches!(res[0].result, Err(DnssecError::NoMatchingDnskey)));
+        assert_eq!(res[1].key_tag, 55648);
+        assert!(res[1].result.is_ok());
+    }
+
+    #[test]
+    fn validate_rrsets_states() {
+        let (dnskey_record, a_record, rrsig_record) = rfc6605_example();
+        let records = [a_record.clone(), rrsig_record];
+
+        // the example signature expired in 2010
+        let res = validate_rrsets(&records, &[dnskey_record]);
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].state, ValidationState::Bogus);
+        assert_eq!(res[0].key_tag(), Some(55648));
+        assert!(matches!(res[0].error(), Some(DnssecError::RrsigExpired)));
+
+        let res = validate_rrsets(&records, &[]);
+        assert_eq!(res[0].state, ValidationState::Indeterminate);
+
+        let res = validate_rrsets(&[a_record], &[]);
+        assert_eq!(res[0].state, ValidationState::Insecure);
+        assert_eq!(res[0].key_tag(), None);
+    }
+
+    #[test]
+    fn nsec3_iteration_policy() {
+        let nsec3 = NSEC3 {
+            hash_algorithm: HashAlgorithm::SHA1,
+            opt_out: false,
+            iterations: 10,
+            salt: None,
+            next_hashed_owner: vec![0; 20],
+            types: vec![RecordType::A],
+        };
+        let owner = Name::from_ascii("0p9mhaveqvm6t7vbl5lop2u3t2rp3tom.example").unwrap();
+        let record = NonOptRecord::new(owner, Class::IN, 3600, nsec3.into()).unwrap();
+
+        let res = validate_rrsets([&record], &[]);
+        assert_eq!(res[0].state, ValidationState::Insecure);
+        assert_eq!(
+            res[0].warnings,
+            [ValidationWarning::Nsec3Iterations {
+                iterations: 10,
+                limit: 0
+            }]
+        );
+
+        let policy = ValidationPolicy {
+            nsec3: Nsec3IterationPolicy {
+                warn_above: 10,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let res = validate_rrsets_with([&record], &[], &policy);
+        assert!(res[0].warnings.is_empty());
+    }
+
+    #[test]
+    fn validation_policy() {
+        let (dnskey_record, a_record, rrsig_record) = rfc6605_example();
+        let mut rr_set = RrSet::new(vec![a_record]).unwrap();
+        let policy = ValidationPolicy {
+            algorithms: Some(vec![Algorithm::ED25519]),
+            ..Default::default()
+        };
+        let res = rr_set.validate_with(&mut [rrsig_record], &[dnskey_record], true, &policy);
+        assert!(matches!(
+            res[0].result,
+            Err(DnssecError::AlgorithmNotAccepted(
+                Algorithm::ECDSAP256SHA256
+            ))
+        ));
+    }
+
+    #[test]
+    fn key_tag_collision() {
+        let (dnskey_record, a_record, rrsig_record) = rfc6605_example();
+        let mut rr_set = RrSet::new(vec![a_record]).unwrap();
+
+        // the same key under another name has the same key tag, but cannot verify the signature
+        let mut colliding = dnskey_record.clone();
+        colliding.owner = Name::from_ascii("example.org").unwrap();
+        let digest = dnskey_record.rdata.as_dnskey().unwrap().key_digest();
+
+        let res = rr_set.validate(&mut [rrsig_record], &[colliding, dnskey_record], true);
+        assert!(res[0].result.is_ok());
+        assert_eq!(res[0].candidates, 2);
+        assert_eq!(res[0].keys_tried, 2);
+        assert_eq!(res[0].key_digest, Some(digest));
+    }
+}
@@ -0,0 +1,219 @@
+//! Policies that decide which cryptography the DNSSEC validator accepts and how it treats
+//! expensive NSEC3 parameters, see [`ValidationPolicy`].
+
+use crate::error::DnssecError;
+use crate::rdata::dnskey::Algorithm;
+use crate::rdata::ds::DigestType;
+use crate::rdata::{DNSKEY, DS};
+
+use super::{RrSet, ValidationState, ValidationWarning};
+
+/// The algorithms that depend on SHA-1.
+const SHA1_ALGORITHMS: [Algorithm; 4] = [
+    Algorithm::DSA,
+    Algorithm::RSASHA1,
+    Algorithm::DSA_NSEC3_SHA1,
+    Algorithm::RSASHA1_NSEC3_SHA1,
+];
+
+/// Which algorithms, digest types, and keys the validator accepts, see
+/// [`validate_rrsets_with()`](super::validate_rrsets_with()).
+///
+/// Signatures made with keys the policy does not accept fail with
+/// [`DnssecError::AlgorithmNotAccepted`] or [`DnssecError::RsaKeyTooShort`]. A record set whose
+/// signatures all fail like this (or have no matching DNSKEY) is treated as insecure, like one
+/// signed only with unsupported algorithms
+/// ([RFC 4035, Section 5.2](https://www.rfc-editor.org/rfc/rfc4035#section-5.2)), unless
+/// [`reject_unaccepted`](Self::reject_unaccepted) is set.
+///
+/// The validator does not follow `DS` records itself; code that matches them against DNSKEYs
+/// should only follow those that pass [`check_ds()`](Self::check_ds()), which applies
+/// [`digest_types`](Self::digest_types).
+///
+/// The [default](Self::default()) is permissive and accepts everything toluol-proto implements;
+/// [`modern()`](Self::modern()) only accepts what is recommended today.
+///
+/// # Examples
+/// ```rust
+/// use toluol_proto::dnssec::ValidationPolicy;
+/// use toluol_proto::rdata::dnskey::Algorithm;
+/// use toluol_proto::rdata::ds::DigestType;
+///
+/// let policy = ValidationPolicy::modern();
+/// assert!(policy.accepts_algorithm(Algorithm::ECDSAP256SHA256));
+/// assert!(!policy.accepts_algorithm(Algorithm::RSASHA1));
+/// assert!(!policy.accepts_digest_type(DigestType::SHA1));
+/// assert!(ValidationPolicy::default().accepts_algorithm(Algorithm::RSASHA1));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationPolicy {
+    /// The algorithms whose keys and signatures are accepted, or [`None`] to accept all.
+    pub algorithms: Option<Vec<Algorithm>>,
+    /// The DS digest types that are accepted, or [`None`] to accept all.
+    pub digest_types: Option<Vec<DigestType>>,
+    /// The minimum size of accepted RSA keys in bits.
+    pub min_rsa_bits: usize,
+    /// Whether algorithms and digest types based on SHA-1 are accepted (if they are accepted by
+    /// [`algorithms`](Self::algorithms) and [`digest_types`](Self::digest_types) as well).
+    pub accept_sha1: bool,
+    /// Whether record sets that are only signed with keys the policy does not accept are
+    /// [`ValidationState::Bogus`] instead of [`ValidationState::Insecure`].
+    pub reject_unaccepted: bool,
+    /// How NSEC3 records with many hash iterations are treated.
+    pub nsec3: Nsec3IterationPolicy,
+}
+
+impl Default for ValidationPolicy {
+    /// Accepts all algorithms, digest types, and key sizes, and uses the default
+    /// [`Nsec3IterationPolicy`].
+    fn default() -> Self {
+        Self {
+            algorithms: None,
+            digest_types: None,
+            min_rsa_bits: 0,
+            accept_sha1: true,
+            reject_unaccepted: false,
+            nsec3: Nsec3IterationPolicy::default(),
+        }
+    }
+}
+
+impl ValidationPolicy {
+    /// Only accepts the algorithms and digest types that
+    /// [RFC 8624](https://www.rfc-editor.org/rfc/rfc8624#section-3) recommends for validation
+    /// without SHA-1 and GOST, and RSA keys of at least 2048 bits. Record sets that are only
+    /// signed otherwise are bogus.
+    pub fn modern() -> Self {
+        Self {
+            algorithms: Some(vec![
+                Algorithm::RSASHA256,
+                Algorithm::RSASHA512,
+                Algorithm::ECDSAP256SHA256,
+                Algorithm::ECDSAP384SHA384,
+                Algorithm::ED25519,
+                Algorithm::ED448,
+            ]),
+            digest_types: Some(vec![DigestType::SHA256, DigestType::SHA384]),
+            min_rsa_bits: 2048,
+            accept_sha1: false,
+            reject_unaccepted: true,
+            nsec3: Nsec3IterationPolicy::default(),
+        }
+    }
+
+    /// Returns whether the policy accepts keys and signatures with `algorithm`.
+    pub fn accepts_algorithm(&self, algorithm: Algorithm) -> bool {
+        (self.accept_sha1 || !SHA1_ALGORITHMS.contains(&algorithm))
+            && self
+                .algorithms
+                .as_ref()
+                .is_none_or(|algorithms| algorithms.contains(&algorithm))
+    }
+
+    /// Returns whether the policy accepts DS records with `digest_type`.
+    pub fn accepts_digest_type(&self, digest_type: DigestType) -> bool {
+        (self.accept_sha1 || digest_type != DigestType::SHA1)
+            && self
+                .digest_types
+                .as_ref()
+                .is_none_or(|digest_types| digest_types.contains(&digest_type))
+    }
+
+    /// Checks that the policy accepts the algorithm and, for RSA, the size of `dnskey`.
+    pub fn check_dnskey(&self, dnskey: &DNSKEY) -> Result<(), DnssecError> {
+        if !self.accepts_algorithm(dnskey.algorithm) {
+            return Err(DnssecError::AlgorithmNotAccepted(dnskey.algorithm));
+        }
+        match dnskey.rsa_key_bits() {
+            Some(bits) if bits < self.min_rsa_bits => Err(DnssecError::RsaKeyTooShort {
+                bits,
+                min: self.min_rsa_bits,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that the policy accepts the digest type and the algorithm of `ds`. To be followed in
+    /// a chain of trust, the DNSKEY it refers to must be accepted as well, see
+    /// [`check_dnskey()`](Self::check_dnskey()).
+    pub fn check_ds(&self, ds: &DS) -> Result<(), DnssecError> {
+        if !self.accepts_digest_type(ds.digest_type) {
+            return Err(DnssecError::DigestTypeNotAccepted(ds.digest_type));
+        }
+        if !self.accepts_algorithm(ds.algorithm) {
+            return Err(DnssecError::AlgorithmNotAccepted(ds.algorithm));
+        }
+        Ok(())
+    }
+}
+
+/// How NSEC3 records with many additional hash iterations are treated. As these are expensive to
+/// validate while adding little protection against zone enumeration,
+/// [RFC 9276](https://www.rfc-editor.org/rfc/rfc9276#section-3.2) recommends 0 iterations and
+/// allows validators to treat higher counts as insecure or bogus.
+///
+/// The thresholds are compared to the highest iteration count of an NSEC3 record set; record sets
+/// that are not NSEC3 are unaffected.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Nsec3IterationPolicy {
+    /// Record sets with more iterations get a [`ValidationWarning`].
+    pub warn_above: u16,
+    /// Secure record sets with more iterations are treated as [`ValidationState::Insecure`].
+    pub insecure_above: u16,
+    /// Secure record sets with more iterations are rejected as [`ValidationState::Bogus`].
+    pub bogus_above: u16,
+}
+
+impl Default for Nsec3IterationPolicy {
+    /// Warns about any iterations, treats more than 50 as insecure (like current versions of BIND
+    /// and Knot Resolver do) and more than 150 as bogus.
+    fn default() -> Self {
+        Self {
+            warn_above: 0,
+            insecure_above: 50,
+            bogus_above: 150,
+        }
+    }
+}
+
+impl Nsec3IterationPolicy {
+    /// Applies the policy to the `state` of `rrset`, returning the new state and the warning, if
+    /// any.
+    pub(super) fn apply(
+        &self,
+        rrset: &RrSet,
+        state: ValidationState,
+    ) -> (ValidationState, Option<ValidationWarning>) {
+        let iterations = rrset
+            .records()
+            .iter()
+            .filter_map(|rec| rec.rdata().as_nsec3())
+            .map(|nsec3| nsec3.iterations)
+            .max();
+        let iterations = match iterations {
+            Some(iterations) if iterations > self.warn_above => iterations,
+            _ => return (state, None),
+        };
+
+        let (state, limit) = if iterations > self.bogus_above {
+            (downgrade(state, ValidationState::Bogus), self.bogus_above)
+        } else if iterations > self.insecure_above {
+            (
+                downgrade(state, ValidationState::Insecure),
+                self.insecure_above,
+            )
+        } else {
+            (state, self.warn_above)
+        };
+        let warning = ValidationWarning::Nsec3Iterations { iterations, limit };
+        (state, Some(warning))
+    }
+}
+
+/// Returns `to` if `state` is [`ValidationState::Secure`], and `state` otherwise.
+fn downgrade(state: ValidationState, to: ValidationState) -> ValidationState {
+    match state {
+        ValidationState::Secure => to,
+        state => state,
+    }
+}
@@ -0,0 +1,113 @@
+//! Handling of the 32-bit timestamps used in [`RRSIG`](crate::rdata::RRSIG) records.
+//!
+//! The timestamps are seconds since 1 January 1970 00:00:00 UTC, modulo 2<sup>32</sup>. They must
+//! be compared using serial number arithmetic as defined in
+//! [RFC 1982](https://www.rfc-editor.org/rfc/rfc1982), so that they keep working after they wrap
+//! around in 2106. [\[RFC 4034, Section 3.1.5\]](https://www.rfc-editor.org/rfc/rfc4034#section-3.1.5)
+
+use std::cmp::Ordering;
+
+use chrono::{NaiveDateTime, TimeZone, Utc};
+
+use crate::error::ParseError;
+
+/// The format of timestamps in the presentation format, i.e. `YYYYMMDDHHmmSS` in UTC.
+const FORMAT: &str = "%Y%m%d%H%M%S";
+
+/// Returns the current time as a timestamp.
+pub fn now() -> u32 {
+    // truncating is intended, timestamps wrap around
+    Utc::now().timestamp() as u32
+}
+
+/// Compares the timestamps `t1` and `t2` using serial number arithmetic.
+///
+/// Returns [`None`] if the comparison is undefined, i.e. if the timestamps are exactly
+/// 2<sup>31</sup> seconds apart.
+///
+/// # Examples
+/// ```rust
+/// use std::cmp::Ordering;
+///
+/// use toluol_proto::dnssec::time::serial_cmp;
+///
+/// assert_eq!(serial_cmp(1, 2), Some(Ordering::Less));
+/// // u32::MAX is one second before 0 wraps around
+/// assert_eq!(serial_cmp(u32::MAX, 0), Some(Ordering::Less));
+/// assert_eq!(serial_cmp(0, 1 << 31), None);
+/// ```
+pub fn serial_cmp(t1: u32, t2: u32) -> Option<Ordering> {
+    match t2.wrapping_sub(t1) {
+        0 => Some(Ordering::Equal),
+        d if d < 1 << 31 => Some(Ordering::Less),
+        d if d > 1 << 31 => Some(Ordering::Greater),
+        _ => None,
+    }
+}
+
+/// Returns true iff the timestamp `t1` is less than the timestamp `t2` using serial number
+/// arithmetic. See [`serial_cmp()`].
+pub fn serial_lt(t1: u32, t2: u32) -> bool {
+    serial_cmp(t1, t2) == Some(Ordering::Less)
+}
+
+/// Returns the number of seconds from `now` until `timestamp`, or 0 if `timestamp` is not after
+/// `now`.
+pub fn seconds_until(timestamp: u32, now: u32) -> u32 {
+    if serial_lt(now, timestamp) {
+        timestamp.wrapping_sub(now)
+    } else {
+        0
+    }
+}
+
+/// Formats `timestamp` as `YYYYMMDDHHmmSS` in UTC, as in the presentation format of RRSIG
+/// records.
+///
+/// As the timestamp is interpreted as seconds since 1970, dates after the wrap-around in 2106 are
+/// shown as dates since 1970.
+///
+/// # Examples
+/// ```rust
+/// use toluol_proto::dnssec::time::format;
+///
+/// assert_eq!(format(0), "19700101000000");
+/// assert_eq!(format(u32::MAX), "21060207062815");
+/// ```
+pub fn format(timestamp: u32) -> String {
+    match Utc.timestamp_opt(timestamp as i64, 0).single() {
+        Some(time) => time.format(FORMAT).to_string(),
+        // unreachable, as all u32 values are in range
+        None => timestamp.to_string(),
+    }
+}
+
+/// Parses a timestamp in the presentation format of RRSIG records, which is either
+/// `YYYYMMDDHHmmSS` in UTC or the number of seconds since 1970.
+/// [\[RFC 4034, Section 3.2\]](https://www.rfc-editor.org/rfc/rfc4034#section-3.2)
+///
+/// Dates after the wrap-around in 2106 are taken modulo 2<sup>32</sup>.
+///
+/// # Examples
+/// ```rust
+/// use toluol_proto::dnssec::time::parse;
+///
+/// assert_eq!(parse("20100909100439").unwrap(), 1284026679);
+/// assert_eq!(parse("1284026679").unwrap(), 1284026679);
+/// assert_eq!(parse("21060207062816").unwrap(), 0);
+/// assert!(parse("2010-09-09").is_err());
+/// ```
+pub fn parse(timestamp: &str) -> Result<u32, ParseError> {
+    let invalid = || ParseError::InvalidTimestamp(timestamp.to_string());
+    if !timestamp.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    // a number of seconds has at most 10 digits, so there is no ambiguity
+    if timestamp.len() == 14 {
+        let time = NaiveDateTime::parse_from_str(timestamp, FORMAT).map_err(|_| invalid())?;
+        // truncating is intended, timestamps wrap around
+        Ok(time.timestamp() as u32)
+    } else {
+        timestamp.parse().map_err(|_| invalid())
+    }
+}
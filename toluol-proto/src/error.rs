@@ -2,6 +2,8 @@
 
 use thiserror::Error;
 
+use crate::rdata::dnskey::Algorithm;
+use crate::rdata::ds::DigestType;
 use crate::Name;
 
 /// High-level errors.
@@ -56,6 +58,9 @@ pub enum ParseError {
     #[error("Encountered name compression where it is explicitly prohibited.")]
     CompressionProhibited,
 
+    #[error("Invalid compression pointer: must point to an earlier name, points to offset {0}.")]
+    InvalidCompressionPointer(u16),
+
     #[error("Non-ASCII string in message: {0}.")]
     NonAsciiString(String),
 
@@ -77,6 +82,29 @@ pub enum ParseError {
     #[error("Invalid CAA parameter in value: {0}.")]
     InvalidCaaParameter(String),
 
+    #[error("Invalid SvcParam in SVCB/HTTPS record: {0}.")]
+    InvalidSvcParam(String),
+
+    #[error("Invalid timestamp: must be YYYYMMDDHHmmSS or a number of seconds, is {0}.")]
+    InvalidTimestamp(String),
+
+    #[error("Invalid E.164 number: must be + followed by up to 15 digits, is {0}.")]
+    InvalidE164Number(String),
+
+    #[error("Invalid email address: must be local-part@domain, is {0}.")]
+    InvalidEmailAddress(String),
+
+    #[error("Invalid prefix length: must be at most {1}, is {0}.")]
+    InvalidPrefixLength(u8, u8),
+
+    #[error("Invalid EDNS Client Subnet: {0}.")]
+    InvalidClientSubnet(String),
+
+    #[error(
+        "Invalid record type: must be a mnemonic like AAAA or TYPE followed by a number, is {0}."
+    )]
+    InvalidRecordType(String),
+
     #[error("IO error.")]
     IoError(#[from] std::io::Error),
 }
@@ -93,9 +121,15 @@ pub enum EncodeError {
     #[error("AA or RA flag set in a query.")]
     AaOrRaInQuery,
 
+    #[error("Too many questions: allowed are up to 65535, got {0}.")]
+    TooManyQuestions(usize),
+
     #[error("Tried to encode non-ASCII string: {0}.")]
     NonAsciiString(String),
 
+    #[error("RDATA too long: allowed are up to 65535 bytes, got {0}.")]
+    RdataTooLong(usize),
+
     #[error("IO error.")]
     IoError(#[from] std::io::Error),
 }
@@ -160,6 +194,20 @@ pub enum DnssecError {
     #[error("Unsupported DNSSEC algorithm.")]
     UnsupportedAlgorithm,
 
+    #[error("Unsupported DS digest type.")]
+    UnsupportedDigestType,
+
+    #[error("The DNSSEC algorithm {0:?} is not accepted by the validation policy.")]
+    AlgorithmNotAccepted(Algorithm),
+
+    #[error("The DS digest type {0:?} is not accepted by the validation policy.")]
+    DigestTypeNotAccepted(DigestType),
+
+    #[error(
+        "The RSA key has {bits} bits, fewer than the {min} bits the validation policy requires."
+    )]
+    RsaKeyTooShort { bits: usize, min: usize },
+
     #[error("Could not parse the DNSKEY public key data.")]
     ParseKey,
 
@@ -169,6 +217,9 @@ pub enum DnssecError {
     #[error("The signature is invalid.")]
     InvalidSignature,
 
+    #[error("No DNSKEY record matching the RRSIG was found.")]
+    NoMatchingDnskey,
+
     #[error("Encoding during validation failed.")]
     EncodingFailed(#[from] EncodeError),
 }
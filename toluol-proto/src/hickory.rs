@@ -0,0 +1,106 @@
+//! Conversions between this crate's types and the equivalent types of
+//! [`hickory-proto`](https://docs.rs/hickory-proto).
+//!
+//! This makes it possible to mix the two libraries, e.g. to use `hickory`'s resolver and display
+//! the results with [`Message::as_string()`]. All conversions go through the wire format, so
+//! anything that one of the libraries can't encode or parse can't be converted.
+//!
+//! Only available with the `hickory-interop` feature.
+//!
+//! # Examples
+//! ```rust
+//! use toluol_proto::{HeaderFlags, Message, Name, Opcode, RecordType};
+//!
+//! let flags = HeaderFlags { aa: false, tc: false, rd: true, ra: false, ad: false, cd: false };
+//! let name = Name::from_ascii("example.com").unwrap();
+//! let msg = Message::new_query(name, RecordType::A, Opcode::QUERY, flags, None).unwrap();
+//!
+//! let hickory_msg = hickory_proto::op::Message::try_from(&msg).unwrap();
+//! assert_eq!(hickory_msg.id(), msg.header.msg_id);
+//! assert_eq!(Message::try_from(&hickory_msg).unwrap(), msg);
+//!
+//! let name = hickory_proto::rr::Name::from_ascii("www.example.com.").unwrap();
+//! assert_eq!(Name::try_from(&name).unwrap(), Name::from_ascii("www.example.com").unwrap());
+//! ```
+
+use std::io::Cursor;
+
+use hickory_proto::error::ProtoError;
+use hickory_proto::op;
+use hickory_proto::rr;
+use hickory_proto::serialize::binary::{BinDecodable, BinEncodable};
+use thiserror::Error;
+
+use crate::error::{EncodeError, ParseError};
+use crate::name::Compression;
+use crate::reader::MessageReader;
+use crate::{Message, Name, Record};
+
+/// Errors that may arise when converting between this crate's and `hickory-proto`'s types.
+#[derive(Debug, Error)]
+pub enum HickoryError {
+    #[error("hickory-proto could not encode or parse the data: {0}")]
+    Hickory(#[from] ProtoError),
+
+    #[error("Error during parsing.")]
+    Parsing(#[from] ParseError),
+
+    #[error("Error during encoding.")]
+    Encoding(#[from] EncodeError),
+}
+
+impl TryFrom<&Message> for op::Message {
+    type Error = HickoryError;
+
+    fn try_from(msg: &Message) -> Result<Self, Self::Error> {
+        Ok(op::Message::from_vec(&msg.encode()?)?)
+    }
+}
+
+impl TryFrom<&op::Message> for Message {
+    type Error = HickoryError;
+
+    fn try_from(msg: &op::Message) -> Result<Self, Self::Error> {
+        let encoded = msg.to_vec()?;
+        Ok(Message::parse(&mut Cursor::new(&encoded))?)
+    }
+}
+
+impl TryFrom<&Record> for rr::Record {
+    type Error = HickoryError;
+
+    fn try_from(record: &Record) -> Result<Self, Self::Error> {
+        Ok(rr::Record::from_bytes(&record.encode()?)?)
+    }
+}
+
+impl TryFrom<&rr::Record> for Record {
+    type Error = HickoryError;
+
+    fn try_from(record: &rr::Record) -> Result<Self, Self::Error> {
+        let encoded = record.to_bytes()?;
+        Ok(Record::parse(&mut MessageReader::new(&encoded), None)?)
+    }
+}
+
+impl TryFrom<&Name> for rr::Name {
+    type Error = HickoryError;
+
+    fn try_from(name: &Name) -> Result<Self, Self::Error> {
+        let mut encoded = Vec::new();
+        name.encode_into(&mut encoded)?;
+        Ok(rr::Name::from_bytes(&encoded)?)
+    }
+}
+
+impl TryFrom<&rr::Name> for Name {
+    type Error = HickoryError;
+
+    fn try_from(name: &rr::Name) -> Result<Self, Self::Error> {
+        let encoded = name.to_bytes()?;
+        Ok(Name::parse(
+            &mut MessageReader::new(&encoded),
+            Compression::Prohibited,
+        )?)
+    }
+}
@@ -20,6 +20,9 @@
 //!         do_flag: false,
 //!         bufsize: 4096,
 //!         client_cookie: None,
+//!         client_subnet: None,
+//!         edns_version: 0,
+//!         extra_options: Vec::new(),
 //!     }),
 //! ).unwrap();
 //! let _encoded = msg.encode().unwrap();
@@ -28,6 +31,11 @@
 //! If you're also looking for utilities to actually send and receive DNS queries and responses,
 //! please take a look at [`toluol`].
 //!
+//! # Features
+//! The `presentation` feature (enabled by default) provides the padded and colourized text
+//! formatting of [`Message::as_string()`] and [`table::RecordTable`], and depends on `owo-colors`.
+//! Without it, records and the other types are still formatted plainly via [`Display`].
+//!
 //! # Usage note
 //! You can construct most structs directly, without using any `new()` method. In some cases, this
 //! can lead to inconsistencies, e.g. manually creating a [`Message`] where the record counts in the
@@ -40,38 +48,56 @@
 //!
 //! [`toluol`]: https://docs.rs/toluol
 
-use std::cmp::max;
-use std::collections::HashMap;
+use std::cmp::{min, Ordering};
 use std::fmt::{self, Display};
-use std::io::{Cursor, Read, Write};
+use std::io::{Cursor, ErrorKind, Write};
+use std::str::FromStr;
 
 use byteorder::{NetworkEndian, ReadBytesExt, WriteBytesExt};
+#[cfg(feature = "presentation")]
 use owo_colors::OwoColorize;
 use rand::Rng;
-use rdata::opt::OptionCode;
+use rdata::opt::{ClientSubnet, OptionCode};
 use repr_with_fallback::repr_with_fallback;
 #[cfg(feature = "serde")]
 use serde::Serialize;
 use strum_macros::EnumString;
 
 // TODO put the dnssec module behind a feature?
+pub mod anonymize;
+#[cfg(feature = "arbitrary")]
+pub mod arbitrary;
 pub mod dnssec;
 pub mod error;
+#[cfg(feature = "hickory-interop")]
+pub mod hickory;
 pub mod name;
 pub mod rdata;
+pub mod reader;
+pub mod registry;
+#[cfg(feature = "serde")]
+mod serialize;
+pub mod table;
+pub mod writer;
 
+use dnssec::RrSet;
 use error::{DnssecError, EncodeError, ParseError, ToluolError};
 use rdata::{RdataTrait, OPT};
+use reader::MessageReader;
+use writer::MessageWriter;
 
 pub use name::Name;
 pub use rdata::Rdata;
+use table::RecordOrder;
+#[cfg(feature = "presentation")]
+use table::RecordTable;
 
 /// Represents a DNS OpCode.
 ///
 /// See [here](https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#dns-parameters-5) for
 /// further information.
 #[cfg_attr(feature = "serde", derive(Serialize))]
-#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+#[derive(PartialEq, Eq, Copy, Clone, EnumString, Debug)]
 pub enum Opcode {
     QUERY,
     IQUERY,
@@ -81,37 +107,8 @@ pub enum Opcode {
     DSO,
 }
 
-/// Represents a DNS RCODE, including those introduced by EDNS.
-///
-/// See
-/// [here](https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#dns-parameters-6)
-/// for further information.
-#[cfg_attr(feature = "serde", derive(Serialize))]
-#[derive(PartialEq, Eq, Copy, Clone, Debug)]
-#[non_exhaustive]
-pub enum RCode {
-    NOERROR,
-    FORMERR,
-    SERVFAIL,
-    NXDOMAIN,
-    NOTIMP,
-    REFUSED,
-    YXDOMAIN,
-    YXRRSET,
-    NXRRSET,
-    NOTAUTH,
-    NOTZONE,
-    DSOTYPENI,
-    BADVERSBADSIG,
-    BADKEY,
-    BADTIME,
-    BADMODE,
-    BADNAME,
-    BADALG,
-    BADTRUNC,
-    BADCOOKIE,
-    // TODO Unknown(u16) ?
-}
+// generated by build.rs from iana/dns-parameters-6.csv
+include!(concat!(env!("OUT_DIR"), "/rcode.rs"));
 
 repr_with_fallback! {
     /// Represents a DNS TYPE.
@@ -121,8 +118,13 @@ repr_with_fallback! {
     /// This enum is non-exhaustive, see
     /// [here](https://en.wikipedia.org/wiki/List_of_DNS_record_types) for a more comprehensive
     /// overview.
+    ///
+    /// Types can be parsed from their mnemonic (case-insensitively), from the generic `TYPE###`
+    /// syntax of [RFC 3597](https://www.rfc-editor.org/rfc/rfc3597#section-5), and from the
+    /// mnemonics of some types without a variant of their own, like `SPF` (see
+    /// [`RecordType::from_str()`]).
     #[cfg_attr(feature = "serde", derive(Serialize))]
-    #[derive(PartialEq, Eq, Copy, Clone, EnumString, Debug)]
+    #[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
     #[non_exhaustive]
     pub enum RecordType {
         A = 1,
@@ -155,9 +157,11 @@ repr_with_fallback! {
         TLSA = 52,
         // TODO: SMIMEA (53)
         // TODO: HIP (55)
-        // TODO: CDNSKEY (60)
+        CDS = 59,
+        CDNSKEY = 60,
         OPENPGPKEY = 61,
-        // TODO: HTTPS (65)
+        SVCB = 64,
+        HTTPS = 65,
         // TODO: TKEY (249)
         // TODO: TSIG (250)
         CAA = 257,
@@ -173,7 +177,7 @@ repr_with_fallback! {
 ///
 /// See [RFC 1035](https://www.rfc-editor.org/rfc/rfc1035) for further information.
 #[cfg_attr(feature = "serde", derive(Serialize))]
-#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+#[derive(PartialEq, Eq, Hash, Copy, Clone, EnumString, Debug)]
 pub enum Class {
     IN,
     CH,
@@ -241,7 +245,7 @@ pub struct Header {
 ///
 /// See [RFC 1035](https://www.rfc-editor.org/rfc/rfc1035) for further information.
 #[cfg_attr(feature = "serde", derive(Serialize))]
-#[derive(PartialEq, Eq, Clone, Debug)]
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
 pub struct Question {
     /// The [`Name`] to query for.
     pub qname: Name,
@@ -286,13 +290,27 @@ pub struct EdnsConfig {
     ///
     /// See [RFC 7873](https://www.rfc-editor.org/rfc/rfc7873.html) for more.
     pub client_cookie: Option<[u8; 8]>,
+    /// May be [`None`] to indicate no client subnet should be sent.
+    ///
+    /// See [RFC 7871](https://www.rfc-editor.org/rfc/rfc7871.html) for more.
+    pub client_subnet: Option<ClientSubnet>,
+    /// The EDNS version that gets sent in the `OPT` record. Only version 0 is defined; servers
+    /// should answer other versions with [`RCode::BADVERSBADSIG`], which makes them useful for
+    /// conformance tests.
+    pub edns_version: u8,
+    /// Further options that get sent in the `OPT` record as they are, after the client cookie and
+    /// subnet, e.g. options that have no field here or malformed ones for testing servers.
+    pub extra_options: Vec<(OptionCode, Vec<u8>)>,
     // TODO: support padding?
 }
 
 /// The `OPT` variant of [`Record`].
 ///
 /// See [RFC 6891](https://www.rfc-editor.org/rfc/rfc6891#section-6) for further information.
-#[cfg_attr(feature = "serde", derive(Serialize))]
+///
+/// With the `serde` feature, this is serialized with all EDNS information flattened into one
+/// object: the owner, `"rtype": "OPT"`, the payload size, the full RCODE, the EDNS version, the
+/// flags, and the options as a list with their values decoded (see [`OPT`]).
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub struct OptRecord {
     /// Must be [`Name::root()`].
@@ -307,8 +325,9 @@ pub struct OptRecord {
     pub edns_version: u8,
     /// A list of [`OptFlags`] (may be empty).
     pub flags: Vec<OptFlags>,
+    /// How the RDLENGTH of the parsed record disagreed with its RDATA, if it did.
+    rdlength_warning: Option<RdlengthWarning>,
     // rdlength omitted as rdata knows its own length
-    #[cfg_attr(feature = "serde", serde(skip))]
     encoded_rdata: Vec<u8>, // needed for encoding
     rdata: Rdata, // this is of type Rdata and not OPT so that it nicely mirrors NonOptRecord
 }
@@ -327,6 +346,12 @@ pub struct NonOptRecord {
     pub class: Class,
     /// The amount of seconds this record may be cached for.
     pub ttl: u32,
+    /// The TTL the record had before it was rewritten with [`Self::set_ttl()`].
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    original_ttl: Option<u32>,
+    /// How the RDLENGTH of the parsed record disagreed with its RDATA, if it did.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    rdlength_warning: Option<RdlengthWarning>,
     // rdlength omitted as rdata knows its own length
     #[cfg_attr(feature = "serde", serde(skip))]
     encoded_rdata: Vec<u8>, // needed for encoding and DNSSEC
@@ -351,6 +376,141 @@ pub struct Message {
     pub additional_answers: Vec<Record>,
 }
 
+/// Describes how the RDLENGTH of a parsed record disagreed with its RDATA. See
+/// [`Record::rdlength_warning()`].
+///
+/// Some malformed servers send an RDLENGTH that is larger or smaller than the RDATA. Such records are
+/// parsed without reading past RDLENGTH, and the following records are parsed starting where
+/// RDLENGTH says the record ends, so that they are not affected.
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug)]
+pub enum RdlengthWarning {
+    /// RDLENGTH is the given number of bytes larger than the parsed RDATA. The additional bytes are
+    /// kept in the encoded RDATA, but are otherwise ignored.
+    TrailingBytes(u16),
+    /// The RDATA could not be parsed from RDLENGTH bytes, i.e. RDLENGTH is smaller than the RDATA.
+    /// The RDATA is kept unparsed as [`Rdata::Unknown`].
+    RdataTooLong,
+    /// RDLENGTH exceeds the end of the message by the given number of bytes. The RDATA is parsed
+    /// from the bytes up to the end of the message.
+    PastEndOfMessage(u16),
+}
+
+/// Describes something unusual about a message that is nevertheless well-formed enough to be
+/// parsed. See [`Message::warnings()`].
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum MessageWarning {
+    /// An OPT record was found in the answer section.
+    OptInAnswerSection,
+    /// An OPT record was found in the authoritative section.
+    OptInAuthoritativeSection,
+    /// The message contains the given number of OPT records instead of at most one (see
+    /// [RFC 6891, Section 6.1.1](https://www.rfc-editor.org/rfc/rfc6891#section-6.1.1)).
+    MultipleOpt(usize),
+    /// The RDLENGTH of the record with the given owner and type disagreed with its RDATA.
+    RdlengthMismatch(Name, RecordType, RdlengthWarning),
+}
+
+/// Describes how the owner name of a record breaks the naming convention of its type. See
+/// [`Record::check_owner_convention()`].
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum OwnerWarning {
+    /// The owner name of an SRV record does not start with `_service._proto` labels.
+    /// [\[RFC 2782\]](https://www.rfc-editor.org/rfc/rfc2782)
+    SrvWithoutServiceLabels,
+    /// The owner name of a TLSA record does not start with `_port._proto` labels, where the port
+    /// is a decimal number and the protocol is `tcp`, `udp`, or `sctp`.
+    /// [\[RFC 6698, Section 3\]](https://www.rfc-editor.org/rfc/rfc6698#section-3)
+    TlsaWithoutPortLabels,
+    /// A DMARC record (a TXT record starting with `v=DMARC1`) is not owned by a `_dmarc` name.
+    /// [\[RFC 7489, Section 6.1\]](https://www.rfc-editor.org/rfc/rfc7489#section-6.1)
+    DmarcWithoutDmarcLabel,
+}
+
+/// The encoded size of a [`Message`] in bytes, broken down by section and record. See
+/// [`Message::size_report()`].
+///
+/// As names are encoded without compression, the sizes may be larger than those of the message as
+/// it was received.
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct SizeReport {
+    /// The size of the header.
+    pub header: usize,
+    /// The size of the question section and of each question in it.
+    pub questions: SectionSize,
+    /// The size of the answer section and of each record in it.
+    pub answers: SectionSize,
+    /// The size of the authoritative section and of each record in it.
+    pub authoritative_answers: SectionSize,
+    /// The size of the additional section and of each record in it.
+    pub additional_answers: SectionSize,
+}
+
+/// The encoded size of one section of a [`Message`] in bytes. See [`SizeReport`].
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+pub struct SectionSize {
+    /// The size of the whole section.
+    pub total: usize,
+    /// The size of each entry of the section, in order.
+    pub entries: Vec<usize>,
+}
+
+/// Selects which parts of a [`Message`] are included in [`Message::as_string_with()`].
+#[cfg(feature = "presentation")]
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct DisplaySections {
+    /// The header, warnings, the OPT pseudosection, and the section names.
+    pub comments: bool,
+    /// The question section.
+    pub question: bool,
+    /// The answer section.
+    pub answer: bool,
+    /// The authoritative section.
+    pub authority: bool,
+    /// The additional section (except for OPT records, which are part of the comments).
+    pub additional: bool,
+    /// Not a part, but whether the records of each section are grouped by record set, with the
+    /// owner only printed once, see [`RecordTable::grouped_line()`].
+    pub group_rrsets: bool,
+    /// Not a part either, but whether internationalized names are shown in their Unicode form, see
+    /// [`RecordTable::unicode_names`].
+    pub unicode_names: bool,
+}
+
+#[cfg(feature = "presentation")]
+impl DisplaySections {
+    /// Selects all parts of a message.
+    pub const ALL: Self = Self {
+        comments: true,
+        question: true,
+        answer: true,
+        authority: true,
+        additional: true,
+        group_rrsets: false,
+        unicode_names: false,
+    };
+
+    /// Selects no part of a message.
+    pub const NONE: Self = Self {
+        comments: false,
+        question: false,
+        answer: false,
+        authority: false,
+        additional: false,
+        group_rrsets: false,
+        unicode_names: false,
+    };
+}
+
+#[cfg(feature = "presentation")]
+impl Default for DisplaySections {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
 impl Opcode {
     /// Encodes a `Opcode` as a byte.
     pub fn encode(&self) -> u8 {
@@ -393,28 +553,13 @@ impl RCode {
     /// the upper eight bits need to be encoded in an OPT record in the additional section of the
     /// DNS message.
     pub fn encode(&self) -> u8 {
-        match self {
-            RCode::NOERROR => 0,
-            RCode::FORMERR => 1,
-            RCode::SERVFAIL => 2,
-            RCode::NXDOMAIN => 3,
-            RCode::NOTIMP => 4,
-            RCode::REFUSED => 5,
-            RCode::YXDOMAIN => 6,
-            RCode::YXRRSET => 7,
-            RCode::NXRRSET => 8,
-            RCode::NOTAUTH => 9,
-            RCode::NOTZONE => 10,
-            RCode::DSOTYPENI => 11,
-            RCode::BADVERSBADSIG => 16 & 0b1111,
-            RCode::BADKEY => 17 & 0b1111,
-            RCode::BADTIME => 18 & 0b1111,
-            RCode::BADMODE => 19 & 0b1111,
-            RCode::BADNAME => 20 & 0b1111,
-            RCode::BADALG => 21 & 0b1111,
-            RCode::BADTRUNC => 22 & 0b1111,
-            RCode::BADCOOKIE => 23 & 0b1111,
-        }
+        (self.value() & 0b1111) as u8
+    }
+
+    /// Encodes the upper eight bits of an `RCode`, which are stored in the OPT record of the DNS
+    /// message (see [`Self::encode()`]).
+    pub fn encode_upper(&self) -> u8 {
+        (self.value() >> 4) as u8
     }
 
     /// Parses an encoded `RCode` from a twelve bit value. If EDNS is used, the upper eight bits
@@ -423,29 +568,7 @@ impl RCode {
     ///
     /// Returns an error if the given value does not represent a valid DNS RCODE.
     pub fn parse(val: u16) -> Result<RCode, ParseError> {
-        Ok(match val {
-            0 => RCode::NOERROR,
-            1 => RCode::FORMERR,
-            2 => RCode::SERVFAIL,
-            3 => RCode::NXDOMAIN,
-            4 => RCode::NOTIMP,
-            5 => RCode::REFUSED,
-            6 => RCode::YXDOMAIN,
-            7 => RCode::YXRRSET,
-            8 => RCode::NXRRSET,
-            9 => RCode::NOTAUTH,
-            10 => RCode::NOTZONE,
-            11 => RCode::DSOTYPENI,
-            16 => RCode::BADVERSBADSIG,
-            17 => RCode::BADKEY,
-            18 => RCode::BADTIME,
-            19 => RCode::BADMODE,
-            20 => RCode::BADNAME,
-            21 => RCode::BADALG,
-            22 => RCode::BADTRUNC,
-            23 => RCode::BADCOOKIE,
-            x => return Err(ParseError::InvalidRcode(x)),
-        })
+        RCode::from_value(val).ok_or(ParseError::InvalidRcode(val))
     }
 }
 
@@ -464,6 +587,110 @@ impl Display for RecordType {
     }
 }
 
+/// Mnemonics of assigned types (and query types) that have no [`RecordType`] variant, so that
+/// they can still be parsed.
+const RECORD_TYPE_ALIASES: &[(&str, u16)] = &[
+    ("NULL", 10),
+    ("WKS", 11),
+    ("MINFO", 14),
+    ("AFSDB", 18),
+    ("SIG", 24),
+    ("KEY", 25),
+    ("IPSECKEY", 45),
+    ("DHCID", 49),
+    ("SMIMEA", 53),
+    ("HIP", 55),
+    ("CSYNC", 62),
+    ("ZONEMD", 63),
+    ("SPF", 99),
+    ("EUI48", 108),
+    ("EUI64", 109),
+    ("TKEY", 249),
+    ("TSIG", 250),
+    ("IXFR", 251),
+    ("AXFR", 252),
+    ("ANY", 255),
+    ("URI", 256),
+    ("TA", 32768),
+    ("DLV", 32769),
+];
+
+impl FromStr for RecordType {
+    type Err = ParseError;
+
+    /// Parses a record type from its mnemonic, ignoring case, or from the generic `TYPE###`
+    /// syntax.
+    ///
+    /// Types without a variant, like `SPF` or `URI`, are parsed to [`RecordType::Unknown`] and
+    /// are therefore displayed in the generic syntax. (`SPF` records have the same RDATA as `TXT`
+    /// records, but remain a type of their own.) Generic syntax for a known type results in the
+    /// named variant.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::RecordType;
+    ///
+    /// assert_eq!("aaaa".parse::<RecordType>().unwrap(), RecordType::AAAA);
+    /// assert_eq!("TYPE28".parse::<RecordType>().unwrap(), RecordType::AAAA);
+    /// assert_eq!("type256".parse::<RecordType>().unwrap(), RecordType::Unknown(256));
+    /// assert_eq!("URI".parse::<RecordType>().unwrap(), RecordType::Unknown(256));
+    /// assert_eq!("https".parse::<RecordType>().unwrap(), RecordType::HTTPS);
+    /// assert_eq!("spf".parse::<RecordType>().unwrap().to_string(), "TYPE99");
+    /// assert!("TYPE65536".parse::<RecordType>().is_err());
+    /// assert!("Unknown".parse::<RecordType>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let upper = s.to_ascii_uppercase();
+        if let Some(number) = upper.strip_prefix("TYPE") {
+            if !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()) {
+                return match number.parse::<u16>() {
+                    Ok(number) => Ok(number.into()),
+                    Err(_) => Err(ParseError::InvalidRecordType(s.to_string())),
+                };
+            }
+        }
+        Ok(match upper.as_str() {
+            "A" => RecordType::A,
+            "NS" => RecordType::NS,
+            "CNAME" => RecordType::CNAME,
+            "SOA" => RecordType::SOA,
+            "PTR" => RecordType::PTR,
+            "HINFO" => RecordType::HINFO,
+            "MX" => RecordType::MX,
+            "TXT" => RecordType::TXT,
+            "RP" => RecordType::RP,
+            "AAAA" => RecordType::AAAA,
+            "LOC" => RecordType::LOC,
+            "SRV" => RecordType::SRV,
+            "NAPTR" => RecordType::NAPTR,
+            "CERT" => RecordType::CERT,
+            "DNAME" => RecordType::DNAME,
+            "OPT" => RecordType::OPT,
+            "DS" => RecordType::DS,
+            "SSHFP" => RecordType::SSHFP,
+            "RRSIG" => RecordType::RRSIG,
+            "NSEC" => RecordType::NSEC,
+            "DNSKEY" => RecordType::DNSKEY,
+            "NSEC3" => RecordType::NSEC3,
+            "NSEC3PARAM" => RecordType::NSEC3PARAM,
+            "TLSA" => RecordType::TLSA,
+            "CDS" => RecordType::CDS,
+            "CDNSKEY" => RecordType::CDNSKEY,
+            "OPENPGPKEY" => RecordType::OPENPGPKEY,
+            "SVCB" => RecordType::SVCB,
+            "HTTPS" => RecordType::HTTPS,
+            "CAA" => RecordType::CAA,
+            _ => match RECORD_TYPE_ALIASES
+                .iter()
+                .find(|(alias, _)| *alias == upper)
+            {
+                Some(&(_, number)) => number.into(),
+                None => return Err(ParseError::InvalidRecordType(s.to_string())),
+            },
+        })
+    }
+}
+
 impl Class {
     /// Encodes a `Class` as a two-byte value.
     pub fn encode(&self) -> u16 {
@@ -505,7 +732,7 @@ impl HeaderFlags {
             aa: (flags & (1 << 10)) != 0,
             tc: (flags & (1 << 9)) != 0,
             rd: (flags & (1 << 8)) != 0,
-            ra: (flags & (1 << 8)) != 0,
+            ra: (flags & (1 << 7)) != 0,
             ad: (flags & (1 << 5)) != 0,
             cd: (flags & (1 << 4)) != 0,
         }
@@ -617,7 +844,7 @@ impl Header {
     ///
     /// Returns an error if [`Opcode::parse()`], [`RCode::parse()`] or a method defined in
     /// [`byteorder::ReadBytesExt`] return an error.
-    pub fn parse(header: &mut Cursor<&[u8]>) -> Result<Self, ParseError> {
+    pub fn parse(header: &mut MessageReader) -> Result<Self, ParseError> {
         let msg_id = header.read_u16::<NetworkEndian>()?;
         let line_two = header.read_u16::<NetworkEndian>()?;
         let qr = (line_two & (1 << 15)) >> 15;
@@ -718,11 +945,27 @@ impl Question {
         Ok(())
     }
 
+    /// Returns the length of the encoded `Question` without encoding it, see
+    /// [`Name::encoded_len()`].
+    pub fn encoded_len(&self) -> usize {
+        // QTYPE and QCLASS
+        self.qname.encoded_len() + 4
+    }
+
+    /// Writes the `Question` into a message via `writer`, compressing the name if `writer` allows
+    /// it.
+    pub fn write(&self, writer: &mut MessageWriter) -> Result<(), EncodeError> {
+        writer.write_name(&self.qname, name::Compression::Allowed)?;
+        writer.write_u16::<NetworkEndian>(self.qtype.into())?;
+        writer.write_u16::<NetworkEndian>(self.qclass.encode())?;
+        Ok(())
+    }
+
     /// Parses an encoded `Question` from a series of bytes.
     ///
     /// Returns an error if [`Name::parse()`], [`Class::parse()`] or a method defined in
     /// [`byteorder::ReadBytesExt`] return an error.
-    pub fn parse(msg: &mut Cursor<&[u8]>) -> Result<Self, ParseError> {
+    pub fn parse(msg: &mut MessageReader) -> Result<Self, ParseError> {
         let qname = Name::parse(msg, name::Compression::Allowed)?;
         let qtype: RecordType = msg.read_u16::<NetworkEndian>()?.into();
         let qclass = Class::parse(msg.read_u16::<NetworkEndian>()?)?;
@@ -739,11 +982,12 @@ impl Question {
     ///
     /// If `output` is [`Some`] and the specified output stream supports colours, the output will be
     /// colourized.
+    #[cfg(feature = "presentation")]
     pub fn as_padded_string(&self, owner_len: usize, output: Option<owo_colors::Stream>) -> String {
         let mut res = String::new();
 
         let mut owner = self.qname.to_string();
-        while owner.len() < owner_len {
+        while owner.chars().count() < owner_len {
             owner.push(' ');
         }
 
@@ -788,12 +1032,52 @@ impl Record {
         }
     }
 
+    /// Writes the `Record` into a message via `writer`, compressing names if `writer` allows it.
+    ///
+    /// Returns an error if the RDATA could not be encoded or is longer than 65535 bytes.
+    pub fn write(&self, writer: &mut MessageWriter) -> Result<(), EncodeError> {
+        match self {
+            Record::NONOPT(nonopt) => nonopt.write(writer),
+            Record::OPT(opt) => opt.write(writer),
+        }
+    }
+
+    /// Returns the length of the encoded record without message compression, i.e. the length of
+    /// [`encode()`](Self::encode()), without encoding it.
+    ///
+    /// Returns an error in the same cases as [`write()`](Self::write()).
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::rdata::A;
+    /// use toluol_proto::{Class, Name, NonOptRecord, Rdata, Record};
+    ///
+    /// let owner = Name::from_ascii("example.com").unwrap();
+    /// let rdata = Rdata::A(A::from(std::net::Ipv4Addr::new(192, 0, 2, 1)));
+    /// let record = Record::NONOPT(NonOptRecord::new(owner, Class::IN, 300, rdata).unwrap());
+    /// assert_eq!(record.encoded_len().unwrap(), 13 + 10 + 4);
+    /// assert_eq!(record.encoded_len().unwrap(), record.encode().unwrap().len());
+    /// ```
+    pub fn encoded_len(&self) -> Result<usize, EncodeError> {
+        let mut writer = MessageWriter::size_only(name::Compression::Prohibited);
+        self.write(&mut writer)?;
+        Ok(writer.position())
+    }
+
+    /// Returns the owner name of the record.
+    fn owner(&self) -> &Name {
+        match self {
+            Record::NONOPT(nonopt) => &nonopt.owner,
+            Record::OPT(opt) => &opt.owner,
+        }
+    }
+
     /// Parses an encoded `Record` from a series of bytes.
     ///
     /// Returns an error if [`Name::parse()`], [`Class::parse()`],
     /// [`parse_rdata()`](Self::parse_rdata()) or a method defined in [`byteorder::ReadBytesExt`]
     /// return an error, or if an `OPT` record has a name other than `"."`.
-    pub fn parse(msg: &mut Cursor<&[u8]>, rcode: Option<RCode>) -> Result<Self, ParseError> {
+    pub fn parse(msg: &mut MessageReader, rcode: Option<RCode>) -> Result<Self, ParseError> {
         let owner = Name::parse(msg, name::Compression::Allowed)?;
         let atype: RecordType = msg.read_u16::<NetworkEndian>()?.into();
         if atype == RecordType::OPT {
@@ -802,19 +1086,16 @@ impl Record {
         let class = Class::parse(msg.read_u16::<NetworkEndian>()?)?;
         let ttl = msg.read_u32::<NetworkEndian>()?;
         let rdlength = msg.read_u16::<NetworkEndian>()?;
-
-        let mut encoded_rdata = vec![0; rdlength as usize];
-        let pos_rdata_start = msg.position();
-        msg.read_exact(&mut encoded_rdata)?;
-        // reset position to the start of rdata for parse_rdata()
-        msg.set_position(pos_rdata_start);
-        let rdata = Record::parse_rdata(&atype, msg, rdlength)?;
+        let (rdata, encoded_rdata, rdlength_warning) =
+            Record::parse_rdata_lenient(&atype, msg, rdlength)?;
 
         Ok(Record::NONOPT(NonOptRecord {
             owner,
             rtype: atype,
             class,
             ttl,
+            original_ttl: None,
+            rdlength_warning,
             encoded_rdata,
             rdata,
         }))
@@ -822,50 +1103,187 @@ impl Record {
 
     /// Parses encoded rdata into a vector of strings (canonical format).
     ///
-    /// `atype` is the type of the record containing the rdata. `msg` is the complete response
-    /// message, which is needed for message compression. `rdlength` is the length of the RDATA in
-    /// bytes.
+    /// `atype` is the type of the record containing the rdata. `rdata` is a reader for the RDATA,
+    /// see [`MessageReader::rdata_reader()`].
     ///
     /// Returns an error if any of the `parse_rdata()` methods in [`rdata`] or a method defined in
     /// [`byteorder::ReadBytesExt`] return an error.
-    pub fn parse_rdata(
+    pub fn parse_rdata(atype: &RecordType, rdata: &mut MessageReader) -> Result<Rdata, ParseError> {
+        match atype {
+            RecordType::A => rdata::A::parse_rdata(rdata),
+            RecordType::NS => rdata::NS::parse_rdata(rdata),
+            RecordType::CNAME => rdata::CNAME::parse_rdata(rdata),
+            RecordType::SOA => rdata::SOA::parse_rdata(rdata),
+            RecordType::PTR => rdata::PTR::parse_rdata(rdata),
+            RecordType::HINFO => rdata::HINFO::parse_rdata(rdata),
+            RecordType::MX => rdata::MX::parse_rdata(rdata),
+            RecordType::TXT => rdata::TXT::parse_rdata(rdata),
+            RecordType::RP => rdata::RP::parse_rdata(rdata),
+            RecordType::AAAA => rdata::AAAA::parse_rdata(rdata),
+            RecordType::LOC => rdata::LOC::parse_rdata(rdata),
+            RecordType::SRV => rdata::SRV::parse_rdata(rdata),
+            RecordType::NAPTR => rdata::NAPTR::parse_rdata(rdata),
+            RecordType::CERT => rdata::CERT::parse_rdata(rdata),
+            RecordType::DNAME => rdata::DNAME::parse_rdata(rdata),
+            RecordType::OPT => rdata::OPT::parse_rdata(rdata),
+            RecordType::DS => rdata::DS::parse_rdata(rdata),
+            RecordType::CDS => rdata::DS::parse(rdata).map(Rdata::CDS),
+            RecordType::SSHFP => rdata::SSHFP::parse_rdata(rdata),
+            RecordType::RRSIG => rdata::RRSIG::parse_rdata(rdata),
+            RecordType::NSEC => rdata::NSEC::parse_rdata(rdata),
+            RecordType::DNSKEY => rdata::DNSKEY::parse_rdata(rdata),
+            RecordType::CDNSKEY => rdata::DNSKEY::parse(rdata).map(Rdata::CDNSKEY),
+            RecordType::NSEC3 => rdata::NSEC3::parse_rdata(rdata),
+            RecordType::NSEC3PARAM => rdata::NSEC3PARAM::parse_rdata(rdata),
+            RecordType::TLSA => rdata::TLSA::parse_rdata(rdata),
+            RecordType::OPENPGPKEY => rdata::OPENPGPKEY::parse_rdata(rdata),
+            RecordType::SVCB => rdata::SVCB::parse_rdata(rdata),
+            RecordType::HTTPS => rdata::SVCB::parse(rdata).map(Rdata::HTTPS),
+            RecordType::CAA => rdata::CAA::parse_rdata(rdata),
+            RecordType::Unknown(_) => Ok(Rdata::Unknown(rdata.read_remaining().to_vec())),
+        }
+    }
+
+    /// Parses the RDATA like [`parse_rdata()`](Self::parse_rdata()), but copes with an `rdlength`
+    /// that disagrees with the RDATA (see [`RdlengthWarning`]): the RDATA is parsed without reading
+    /// past `rdlength` bytes, and afterwards, `msg` is positioned after these bytes.
+    ///
+    /// Returns the parsed and the encoded RDATA (with compressed names decompressed), as well as
+    /// how `rdlength` disagreed with the RDATA, if it did.
+    fn parse_rdata_lenient(
         atype: &RecordType,
-        msg: &mut Cursor<&[u8]>,
+        msg: &mut MessageReader,
         rdlength: u16,
-    ) -> Result<Rdata, ParseError> {
-        match atype {
-            RecordType::A => rdata::A::parse_rdata(msg, rdlength),
-            RecordType::NS => rdata::NS::parse_rdata(msg, rdlength),
-            RecordType::CNAME => rdata::CNAME::parse_rdata(msg, rdlength),
-            RecordType::SOA => rdata::SOA::parse_rdata(msg, rdlength),
-            RecordType::PTR => rdata::PTR::parse_rdata(msg, rdlength),
-            RecordType::HINFO => rdata::HINFO::parse_rdata(msg, rdlength),
-            RecordType::MX => rdata::MX::parse_rdata(msg, rdlength),
-            RecordType::TXT => rdata::TXT::parse_rdata(msg, rdlength),
-            RecordType::RP => rdata::RP::parse_rdata(msg, rdlength),
-            RecordType::AAAA => rdata::AAAA::parse_rdata(msg, rdlength),
-            RecordType::LOC => rdata::LOC::parse_rdata(msg, rdlength),
-            RecordType::SRV => rdata::SRV::parse_rdata(msg, rdlength),
-            RecordType::NAPTR => rdata::NAPTR::parse_rdata(msg, rdlength),
-            RecordType::CERT => rdata::CERT::parse_rdata(msg, rdlength),
-            RecordType::DNAME => rdata::DNAME::parse_rdata(msg, rdlength),
-            RecordType::OPT => rdata::OPT::parse_rdata(msg, rdlength),
-            RecordType::DS => rdata::DS::parse_rdata(msg, rdlength),
-            RecordType::SSHFP => rdata::SSHFP::parse_rdata(msg, rdlength),
-            RecordType::RRSIG => rdata::RRSIG::parse_rdata(msg, rdlength),
-            RecordType::NSEC => rdata::NSEC::parse_rdata(msg, rdlength),
-            RecordType::DNSKEY => rdata::DNSKEY::parse_rdata(msg, rdlength),
-            RecordType::NSEC3 => rdata::NSEC3::parse_rdata(msg, rdlength),
-            RecordType::NSEC3PARAM => rdata::NSEC3PARAM::parse_rdata(msg, rdlength),
-            RecordType::TLSA => rdata::TLSA::parse_rdata(msg, rdlength),
-            RecordType::OPENPGPKEY => rdata::OPENPGPKEY::parse_rdata(msg, rdlength),
-            RecordType::CAA => rdata::CAA::parse_rdata(msg, rdlength),
-            RecordType::Unknown(_) => {
-                let mut rdata = vec![0; rdlength as usize];
-                msg.read_exact(&mut rdata)?;
-                Ok(Rdata::Unknown(rdata))
+    ) -> Result<(Rdata, Vec<u8>, Option<RdlengthWarning>), ParseError> {
+        let available = msg.remaining_in_rdata();
+
+        let mut warning = None;
+        let len = if rdlength as usize > available {
+            warning = Some(RdlengthWarning::PastEndOfMessage(
+                rdlength - available as u16,
+            ));
+            available
+        } else {
+            rdlength as usize
+        };
+        let mut rdata_reader = msg.rdata_reader(len)?;
+        let mut encoded_rdata = rdata_reader.clone().read_remaining().to_vec();
+
+        let rdata = match Record::parse_rdata(atype, &mut rdata_reader) {
+            Ok(rdata) => {
+                let unread = rdata_reader.remaining_in_rdata();
+                if unread > 0 && warning.is_none() {
+                    warning = Some(RdlengthWarning::TrailingBytes(unread as u16));
+                }
+                rdata
+            }
+            Err(ParseError::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => {
+                warning = Some(RdlengthWarning::RdataTooLong);
+                Rdata::Unknown(encoded_rdata.clone())
+            }
+            Err(e) => return Err(e),
+        };
+
+        // compression pointers are only valid within the received message, so keep the RDATA
+        // without them
+        if warning.is_none() && rdata_reader.followed_pointer() {
+            if let Ok(decompressed) = rdata.encode() {
+                encoded_rdata = decompressed;
             }
         }
+
+        Ok((rdata, encoded_rdata, warning))
+    }
+
+    /// Writes the RDATA of a record that was possibly parsed by
+    /// [`parse_rdata_lenient()`](Self::parse_rdata_lenient()). If its RDLENGTH disagreed with the
+    /// RDATA, the RDATA is written as it was received, as `rdata` does not match it.
+    fn write_rdata_lenient(
+        writer: &mut MessageWriter,
+        rdata: &Rdata,
+        encoded_rdata: &[u8],
+        rdlength_warning: Option<RdlengthWarning>,
+    ) -> Result<(), EncodeError> {
+        if rdlength_warning.is_some() {
+            writer.write_all(encoded_rdata)?;
+            Ok(())
+        } else {
+            rdata.write(writer)
+        }
+    }
+
+    /// Returns how the RDLENGTH of the record disagreed with its RDATA when it was parsed, if it
+    /// did.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use std::io::Cursor;
+    /// use std::net::Ipv4Addr;
+    ///
+    /// use toluol_proto::rdata::A;
+    /// use toluol_proto::{
+    ///     Class, HeaderFlags, Message, Name, NonOptRecord, Opcode, RCode, RdlengthWarning, Record,
+    /// };
+    ///
+    /// let flags = HeaderFlags { aa: true, tc: false, rd: false, ra: false, ad: false, cd: false };
+    /// let record = |addr: [u8; 4]| {
+    ///     let owner = Name::from_ascii("example.com").unwrap();
+    ///     let rdata = A::from(Ipv4Addr::from(addr)).into();
+    ///     Record::NONOPT(NonOptRecord::new(owner, Class::IN, 60, rdata).unwrap())
+    /// };
+    /// let answers = vec![record([192, 0, 2, 1]), record([192, 0, 2, 2])];
+    /// let msg = Message::new_response(
+    ///     0, Opcode::QUERY, flags, RCode::NOERROR, vec![], [answers, vec![], vec![]],
+    /// );
+    ///
+    /// // header (12 bytes), owner (13 bytes), and type, class, and TTL (8 bytes) precede the
+    /// // first record's RDLENGTH, which is increased by two bytes that are then inserted
+    /// let mut encoded = msg.encode().unwrap();
+    /// encoded[34] += 2;
+    /// encoded.splice(39..39, [0, 0]);
+    ///
+    /// let parsed = Message::parse(&mut Cursor::new(&encoded)).unwrap();
+    /// assert_eq!(parsed.answers[0].rdlength_warning(), Some(RdlengthWarning::TrailingBytes(2)));
+    /// assert_eq!(parsed.answers[1].rdlength_warning(), None);
+    /// assert_eq!(parsed.answers[1].rdata(), msg.answers[1].rdata());
+    /// ```
+    pub fn rdlength_warning(&self) -> Option<RdlengthWarning> {
+        match self {
+            Self::OPT(opt) => opt.rdlength_warning,
+            Self::NONOPT(nonopt) => nonopt.rdlength_warning,
+        }
+    }
+
+    /// Checks whether the owner name follows the naming convention of the record's type, e.g.
+    /// `_service._proto` for SRV records, and returns the ways in which it does not. Records of
+    /// other types and OPT records never have warnings.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::rdata::SRV;
+    /// use toluol_proto::{Class, Name, NonOptRecord, OwnerWarning, Rdata, Record};
+    ///
+    /// let srv = |owner| {
+    ///     let rdata = Rdata::SRV(SRV {
+    ///         priority: 0,
+    ///         weight: 0,
+    ///         port: 5060,
+    ///         target: Name::from_ascii("sip.example.com").unwrap(),
+    ///     });
+    ///     let owner = Name::from_ascii(owner).unwrap();
+    ///     Record::NONOPT(NonOptRecord::new(owner, Class::IN, 300, rdata).unwrap())
+    /// };
+    /// assert!(srv("_sip._udp.example.com").check_owner_convention().is_empty());
+    /// assert_eq!(
+    ///     srv("sip.example.com").check_owner_convention(),
+    ///     [OwnerWarning::SrvWithoutServiceLabels]
+    /// );
+    /// ```
+    pub fn check_owner_convention(&self) -> Vec<OwnerWarning> {
+        match self {
+            Self::OPT(_) => Vec::new(),
+            Self::NONOPT(nonopt) => nonopt.check_owner_convention(),
+        }
     }
 
     /// Returns a reference to the inner [`OptRecord`]. [`None`] for the `NONOPT` variant.
@@ -885,19 +1303,15 @@ impl Record {
     }
 
     /// Returns the inner [`OptRecord`]. Panics if the variant is not `OPT`.
+    #[deprecated(since = "2.1.0", note = "use `OptRecord::try_from()` instead")]
     pub fn into_opt(self) -> OptRecord {
-        match self {
-            Self::OPT(opt) => opt,
-            Self::NONOPT(_) => panic!("Record::into_opt() called on NONOPT variant"),
-        }
+        OptRecord::try_from(self).expect("Record::into_opt() called on NONOPT variant")
     }
 
     /// Returns the inner [`NonOptRecord`]. Panics if the variant is not `NONOPT`.
+    #[deprecated(since = "2.1.0", note = "use `NonOptRecord::try_from()` instead")]
     pub fn into_nonopt(self) -> NonOptRecord {
-        match self {
-            Self::NONOPT(nonopt) => nonopt,
-            Self::OPT(_) => panic!("Record::into_nonopt() called on OPT variant"),
-        }
+        NonOptRecord::try_from(self).expect("Record::into_nonopt() called on OPT variant")
     }
 
     /// Returns a reference to the contained [`Rdata`].
@@ -915,6 +1329,14 @@ impl Record {
             Self::NONOPT(nonopt) => nonopt.rdata_mut(),
         }
     }
+
+    /// Sets the TTL of a `NONOPT` record, see [`NonOptRecord::set_ttl()`]. OPT records are left
+    /// unchanged, as their TTL field holds the extended RCODE and flags instead.
+    pub fn set_ttl(&mut self, ttl: u32) {
+        if let Self::NONOPT(nonopt) = self {
+            nonopt.set_ttl(ttl);
+        }
+    }
 }
 
 impl NonOptRecord {
@@ -934,6 +1356,8 @@ impl NonOptRecord {
             rtype,
             class,
             ttl,
+            original_ttl: None,
+            rdlength_warning: None,
             rdata,
             encoded_rdata,
         })
@@ -951,15 +1375,28 @@ impl NonOptRecord {
     /// The same as [`encode()`](Self::encode()), but encoded bytes are appended to the given writer
     /// instead of to a newly allocated one.
     pub fn encode_into(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
-        self.owner.encode_into(buf)?;
-        buf.write_u16::<NetworkEndian>(self.rtype.into())?;
-        buf.write_u16::<NetworkEndian>(self.class.encode())?;
-        buf.write_u32::<NetworkEndian>(self.ttl)?;
-        buf.write_u16::<NetworkEndian>(self.encoded_rdata.len() as u16)?;
-        buf.write_all(&self.encoded_rdata)?;
+        let mut writer = MessageWriter::new(name::Compression::Prohibited);
+        self.write(&mut writer)?;
+        buf.write_all(&writer.into_bytes())?;
         Ok(())
     }
 
+    /// See [`Record::write()`].
+    pub fn write(&self, writer: &mut MessageWriter) -> Result<(), EncodeError> {
+        writer.write_name(&self.owner, name::Compression::Allowed)?;
+        writer.write_u16::<NetworkEndian>(self.rtype.into())?;
+        writer.write_u16::<NetworkEndian>(self.class.encode())?;
+        writer.write_u32::<NetworkEndian>(self.ttl)?;
+        let rdlength = writer.reserve_rdlength()?;
+        Record::write_rdata_lenient(
+            writer,
+            &self.rdata,
+            &self.encoded_rdata,
+            self.rdlength_warning,
+        )?;
+        writer.patch_rdlength(rdlength)
+    }
+
     /// Ensures the record is in canonical format, as defined in
     /// [RFC 4034, Section 6.2](https://www.rfc-editor.org/rfc/rfc4034#section-6.2).
     ///
@@ -1017,6 +1454,145 @@ impl NonOptRecord {
         &mut self.rdata
     }
 
+    /// Returns a copy of the record for display, in which the owner and the names in the RDATA
+    /// (see [`Rdata::names_mut()`]) show internationalized labels in their Unicode form (see
+    /// [`Name::to_unicode_labels()`]). Other fields, e.g. the text of `TXT` records, are unchanged.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::rdata::{MX, TXT};
+    /// use toluol_proto::{Class, Name, NonOptRecord, Rdata};
+    ///
+    /// let owner = Name::from_ascii("xn--bcher-kva.example").unwrap();
+    /// let mx = Rdata::MX(MX { preference: 10, exchange: owner.clone() });
+    /// let record = NonOptRecord::new(owner.clone(), Class::IN, 300, mx).unwrap();
+    /// assert_eq!(
+    ///     record.to_unicode_names().to_string(),
+    ///     "bücher.example 300 MX 10 bücher.example"
+    /// );
+    ///
+    /// let txt = Rdata::TXT(TXT { text: vec!["xn--bcher-kva.example".to_string()] });
+    /// let record = NonOptRecord::new(owner, Class::IN, 300, txt).unwrap();
+    /// assert_eq!(
+    ///     record.to_unicode_names().to_string(),
+    ///     "bücher.example 300 TXT \"xn--bcher-kva.example\""
+    /// );
+    /// ```
+    pub fn to_unicode_names(&self) -> Self {
+        let mut record = self.clone();
+        record.owner = record.owner.to_unicode_labels();
+        for name in record.rdata.names_mut() {
+            *name = name.to_unicode_labels();
+        }
+        record
+    }
+
+    /// Sets the TTL of the record, remembering the TTL it had before the first change so that it
+    /// can still be displayed (see [`Self::original_ttl()`]).
+    ///
+    /// Setting [`Self::ttl`] directly does not remember the original TTL.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::rdata::A;
+    /// use toluol_proto::{Class, Name, NonOptRecord, Rdata};
+    ///
+    /// let rdata = Rdata::A(A::from(std::net::Ipv4Addr::new(192, 0, 2, 1)));
+    /// let owner = Name::from_ascii("example.com").unwrap();
+    /// let mut record = NonOptRecord::new(owner, Class::IN, 86400, rdata).unwrap();
+    /// record.set_ttl(3600);
+    /// record.set_ttl(60);
+    /// assert_eq!(record.ttl, 60);
+    /// assert_eq!(record.original_ttl(), Some(86400));
+    /// assert_eq!(record.to_string(), "example.com 60 A 192.0.2.1 ; original TTL 86400");
+    /// ```
+    pub fn set_ttl(&mut self, ttl: u32) {
+        if self.original_ttl.is_none() && ttl != self.ttl {
+            self.original_ttl = Some(self.ttl);
+        }
+        self.ttl = ttl;
+    }
+
+    /// Returns the TTL the record had before it was changed with [`Self::set_ttl()`], or [`None`]
+    /// if it was not changed.
+    pub fn original_ttl(&self) -> Option<u32> {
+        self.original_ttl
+    }
+
+    /// Returns whether `self` and `other` are the same member of a record set, i.e. whether they
+    /// have the same owner, type, class and RDATA. In contrast to `==`, the TTLs are ignored, so
+    /// this can be used to find duplicate records, e.g. in answers from different nameservers.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::rdata::A;
+    /// use toluol_proto::{Class, Name, NonOptRecord, Rdata};
+    ///
+    /// let owner = Name::from_ascii("example.com").unwrap();
+    /// let record = |ttl, address| {
+    ///     let rdata = Rdata::A(A::from(std::net::Ipv4Addr::from(address)));
+    ///     NonOptRecord::new(owner.clone(), Class::IN, ttl, rdata).unwrap()
+    /// };
+    /// let a = record(300, [192, 0, 2, 1]);
+    /// assert!(a.same_rrset_member(&record(60, [192, 0, 2, 1])));
+    /// assert_ne!(a, record(60, [192, 0, 2, 1]));
+    /// assert!(!a.same_rrset_member(&record(300, [192, 0, 2, 2])));
+    /// ```
+    pub fn same_rrset_member(&self, other: &Self) -> bool {
+        self.owner == other.owner
+            && self.rtype == other.rtype
+            && self.class == other.class
+            && self.rdata == other.rdata
+    }
+
+    /// See [`Record::check_owner_convention()`].
+    pub fn check_owner_convention(&self) -> Vec<OwnerWarning> {
+        let underscored = |index| {
+            self.owner
+                .label(index)
+                .and_then(|label| label.strip_prefix('_'))
+                .filter(|label| !label.is_empty())
+        };
+        let warning = match &self.rdata {
+            Rdata::SRV(_) => (underscored(0).is_none() || underscored(1).is_none())
+                .then_some(OwnerWarning::SrvWithoutServiceLabels),
+            Rdata::TLSA(_) => {
+                let port = underscored(0).and_then(|port| port.parse::<u16>().ok());
+                let proto = underscored(1).map(str::to_ascii_lowercase);
+                (port.is_none() || !matches!(proto.as_deref(), Some("tcp" | "udp" | "sctp")))
+                    .then_some(OwnerWarning::TlsaWithoutPortLabels)
+            }
+            Rdata::TXT(txt) => {
+                let text = txt.joined();
+                let is_dmarc = text
+                    .get(..8)
+                    .is_some_and(|version| version.eq_ignore_ascii_case("v=DMARC1"))
+                    && text[8..]
+                        .chars()
+                        .next()
+                        .is_none_or(|c| c == ' ' || c == ';');
+                let owner_is_dmarc = self
+                    .owner
+                    .label(0)
+                    .is_some_and(|label| label.eq_ignore_ascii_case("_dmarc"));
+                (is_dmarc && !owner_is_dmarc).then_some(OwnerWarning::DmarcWithoutDmarcLabel)
+            }
+            _ => None,
+        };
+        warning.into_iter().collect()
+    }
+
+    /// Returns the RDATA in canonical format (see [`Rdata::canonicalize()`]) and uncompressed wire
+    /// format, as used for the canonical ordering of records.
+    fn canonical_rdata(&self) -> Vec<u8> {
+        let mut rdata = self.rdata.clone();
+        rdata.canonicalize();
+        // can't fail for records that were parsed or created with `Self::new()`
+        rdata
+            .encode()
+            .unwrap_or_else(|_| self.encoded_rdata.clone())
+    }
+
     /// Returns a string representing the record in the format used in zone files, but without the
     /// redundant IN class and without trailing dots for domain names.
     ///
@@ -1029,6 +1605,9 @@ impl NonOptRecord {
     ///
     /// If `output` is [`Some`] and the specified output stream supports colours, the output will
     /// be colourized.
+    ///
+    /// If the TTL was changed with [`Self::set_ttl()`], the original TTL is appended as a comment.
+    #[cfg(feature = "presentation")]
     pub fn as_string(
         &self,
         separate_with_single_space: bool,
@@ -1036,9 +1615,33 @@ impl NonOptRecord {
         atype_len: Option<usize>,
         output: Option<owo_colors::Stream>,
     ) -> String {
-        let mut owner = self.owner.to_string();
+        self.padded_string(
+            separate_with_single_space,
+            owner_len,
+            atype_len,
+            output,
+            false,
+        )
+    }
+
+    /// Like [`Self::as_string()`], but if `elide_owner` is true, the owner is replaced by spaces
+    /// (so that continuation lines of a record set stay aligned).
+    #[cfg(feature = "presentation")]
+    pub(crate) fn padded_string(
+        &self,
+        separate_with_single_space: bool,
+        owner_len: Option<usize>,
+        atype_len: Option<usize>,
+        output: Option<owo_colors::Stream>,
+        elide_owner: bool,
+    ) -> String {
+        let mut owner = if elide_owner {
+            String::new()
+        } else {
+            self.owner.to_string()
+        };
         if let Some(len) = owner_len {
-            while owner.len() < len {
+            while owner.chars().count() < len {
                 owner.push(' ');
             }
         }
@@ -1055,17 +1658,88 @@ impl NonOptRecord {
             atype = atype.if_supports_color(stream, |s| s.purple()).to_string();
         }
 
-        if separate_with_single_space {
+        let mut res = if separate_with_single_space {
             format!("{} {} {} {}", owner, self.ttl, atype, self.rdata,)
         } else {
             format!("{}  {:>6}  {}  {}", owner, self.ttl, atype, &self.rdata,)
+        };
+        if let Some(ttl) = self.original_ttl {
+            let separator = if separate_with_single_space {
+                " "
+            } else {
+                "  "
+            };
+            res.push_str(&format!("{}; original TTL {}", separator, ttl));
         }
+        res
     }
 }
 
 impl Display for NonOptRecord {
+    /// Formats the record like [`as_string()`](Self::as_string()) with single spaces and neither
+    /// padding nor colours.
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.as_string(true, None, None, None))
+        write!(
+            f,
+            "{} {} {} {}",
+            self.owner, self.ttl, self.rtype, self.rdata
+        )?;
+        if let Some(ttl) = self.original_ttl {
+            write!(f, " ; original TTL {}", ttl)?;
+        }
+        Ok(())
+    }
+}
+
+impl Ord for NonOptRecord {
+    /// Orders records canonically: by owner name (see [`Name`]), class, type and then RDATA in
+    /// canonical wire format, as defined in
+    /// [RFC 4034, Section 6.3](https://www.rfc-editor.org/rfc/rfc4034#section-6.3). Records that
+    /// only differ in their TTLs are ordered by TTL.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::rdata::{A, NS};
+    /// use toluol_proto::{Class, Name, NonOptRecord, Rdata};
+    ///
+    /// let record = |owner, rdata| {
+    ///     NonOptRecord::new(Name::from_ascii(owner).unwrap(), Class::IN, 300, rdata).unwrap()
+    /// };
+    /// let a = |address| Rdata::A(A::from(std::net::Ipv4Addr::from(address)));
+    /// let ns = Rdata::NS(NS { name: Name::from_ascii("ns.example.com").unwrap() });
+    ///
+    /// let mut records = vec![
+    ///     record("www.example.com", a([192, 0, 2, 1])),
+    ///     record("example.com", ns),
+    ///     record("example.com", a([192, 0, 2, 2])),
+    ///     record("example.com", a([192, 0, 2, 1])),
+    /// ];
+    /// records.sort();
+    /// let records: Vec<_> = records.iter().map(|r| r.to_string()).collect();
+    /// assert_eq!(records, [
+    ///     "example.com 300 A 192.0.2.1",
+    ///     "example.com 300 A 192.0.2.2",
+    ///     "example.com 300 NS ns.example.com",
+    ///     "www.example.com 300 A 192.0.2.1",
+    /// ]);
+    /// ```
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.owner
+            .cmp(&other.owner)
+            .then_with(|| self.class.encode().cmp(&other.class.encode()))
+            .then_with(|| u16::from(self.rtype).cmp(&u16::from(other.rtype)))
+            .then_with(|| self.canonical_rdata().cmp(&other.canonical_rdata()))
+            .then_with(|| self.ttl.cmp(&other.ttl))
+            // only to stay consistent with `==`
+            .then_with(|| self.original_ttl.cmp(&other.original_ttl))
+            .then_with(|| self.rdlength_warning.cmp(&other.rdlength_warning))
+            .then_with(|| self.encoded_rdata.cmp(&other.encoded_rdata))
+    }
+}
+
+impl PartialOrd for NonOptRecord {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
@@ -1085,17 +1759,22 @@ impl OptRecord {
         if edns_config.do_flag {
             flags.push(OptFlags::DO);
         }
-        let mut options = HashMap::new();
+        let mut options = Vec::new();
+        if let Some(subnet) = edns_config.client_subnet {
+            options.push((OptionCode::ClientSubnet, subnet.encode()));
+        }
         if let Some(cookie) = edns_config.client_cookie {
-            options.insert(OptionCode::Cookie, cookie.to_vec());
+            options.push((OptionCode::Cookie, cookie.to_vec()));
         }
+        options.extend(edns_config.extra_options);
         let rdata = Rdata::OPT(OPT { options });
         Ok(Self {
             owner: Name::root(),
             payload_size: edns_config.bufsize,
             rcode,
-            edns_version: 0,
+            edns_version: edns_config.edns_version,
             flags,
+            rdlength_warning: None,
             encoded_rdata: rdata.encode()?,
             rdata,
         })
@@ -1113,21 +1792,33 @@ impl OptRecord {
     /// The same as [`encode()`](Self::encode()), but encoded bytes are appended to the given writer
     /// instead of to a newly allocated one.
     pub fn encode_into(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
-        self.owner.encode_into(buf)?;
-        buf.write_u16::<NetworkEndian>(RecordType::OPT.into())?;
-        buf.write_u16::<NetworkEndian>(self.payload_size)?;
+        let mut writer = MessageWriter::new(name::Compression::Prohibited);
+        self.write(&mut writer)?;
+        buf.write_all(&writer.into_bytes())?;
+        Ok(())
+    }
+
+    /// See [`Record::write()`].
+    pub fn write(&self, writer: &mut MessageWriter) -> Result<(), EncodeError> {
+        writer.write_name(&self.owner, name::Compression::Allowed)?;
+        writer.write_u16::<NetworkEndian>(RecordType::OPT.into())?;
+        writer.write_u16::<NetworkEndian>(self.payload_size)?;
         let rcode = self.rcode.unwrap_or(RCode::NOERROR);
-        let rcode = (((rcode.encode() as u16) & 0b111111110000) >> 4) as u8;
-        buf.write_u8(rcode)?;
-        buf.write_u8(self.edns_version)?;
+        writer.write_u8(rcode.encode_upper())?;
+        writer.write_u8(self.edns_version)?;
         if self.flags.contains(&OptFlags::DO) {
-            buf.write_u16::<NetworkEndian>(1 << 15)?;
+            writer.write_u16::<NetworkEndian>(1 << 15)?;
         } else {
-            buf.write_u16::<NetworkEndian>(0)?;
+            writer.write_u16::<NetworkEndian>(0)?;
         }
-        buf.write_u16::<NetworkEndian>(self.encoded_rdata.len() as u16)?;
-        buf.write_all(&self.encoded_rdata)?;
-        Ok(())
+        let rdlength = writer.reserve_rdlength()?;
+        Record::write_rdata_lenient(
+            writer,
+            &self.rdata,
+            &self.encoded_rdata,
+            self.rdlength_warning,
+        )?;
+        writer.patch_rdlength(rdlength)
     }
 
     /// Returns a string describing the `OPT` record, with the given `prefix` prepended to each
@@ -1135,6 +1826,7 @@ impl OptRecord {
     ///
     /// If `output` is [`Some`] and the specified output stream supports colours, the output will be
     /// colourized.
+    #[cfg(feature = "presentation")]
     pub fn as_padded_string(&self, prefix: &str, _output: Option<owo_colors::Stream>) -> String {
         let mut s = prefix.to_string();
 
@@ -1145,7 +1837,7 @@ impl OptRecord {
         if !self.opt_rdata().options.is_empty() {
             let options = self.rdata.to_string();
             let options_iter: Vec<_> = options.split(", ").collect();
-            let options_str = options_iter.join("\n");
+            let options_str = options_iter.join(&format!("\n{}", prefix));
             s.push('\n');
             s.push_str(prefix);
             s.push_str(&options_str);
@@ -1202,7 +1894,7 @@ impl OptRecord {
     ///
     /// See [`DnsRecord::parse()`] for further information.
     fn parse(
-        msg: &mut Cursor<&[u8]>,
+        msg: &mut MessageReader,
         owner: Name,
         rcode: Option<RCode>,
     ) -> Result<Record, ParseError> {
@@ -1212,15 +1904,11 @@ impl OptRecord {
 
         let payload_size = msg.read_u16::<NetworkEndian>()?;
         let ext_rcode = msg.read_u8()?;
-        let rcode = if rcode.is_some() {
-            match ext_rcode {
-                0 => rcode,
-                x => Some(RCode::parse(
-                    ((x as u16) << 4) + (rcode.unwrap().encode() as u16),
-                )?),
+        let rcode = match (rcode, ext_rcode) {
+            (Some(rcode), x) if x != 0 => {
+                Some(RCode::parse(((x as u16) << 4) + (rcode.encode() as u16))?)
             }
-        } else {
-            rcode
+            (rcode, _) => rcode,
         };
         let edns_version = msg.read_u8()?;
         let mut flags = vec![];
@@ -1230,12 +1918,12 @@ impl OptRecord {
         }
 
         let rdlength = msg.read_u16::<NetworkEndian>()?;
-        let mut encoded_rdata = vec![0; rdlength as usize];
-        let pos_rdata_start = msg.position();
-        msg.read_exact(&mut encoded_rdata)?;
-        // reset position to the start of rdata for parse_rdata()
-        msg.set_position(pos_rdata_start);
-        let rdata = Record::parse_rdata(&RecordType::OPT, msg, rdlength)?;
+        let (rdata, encoded_rdata, rdlength_warning) =
+            Record::parse_rdata_lenient(&RecordType::OPT, msg, rdlength)?;
+        if rdata.as_opt().is_none() {
+            // the OPT record is needed to interpret the message, so we can't do without its RDATA
+            return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into());
+        }
 
         Ok(Record::OPT(OptRecord {
             owner,
@@ -1243,6 +1931,7 @@ impl OptRecord {
             rcode,
             edns_version,
             flags,
+            rdlength_warning,
             encoded_rdata,
             rdata,
         }))
@@ -1255,6 +1944,112 @@ impl Display for OptRecord {
     }
 }
 
+#[cfg(feature = "serde")]
+impl Serialize for OptRecord {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        /// The options of the record, serialized like [`OPT::options`].
+        struct Options<'a>(&'a [(OptionCode, Vec<u8>)]);
+
+        impl Serialize for Options<'_> {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                rdata::opt::serialize_options(self.0, serializer)
+            }
+        }
+
+        let mut state = serializer.serialize_struct("OptRecord", 8)?;
+        state.serialize_field("owner", &self.owner)?;
+        state.serialize_field("rtype", &RecordType::OPT)?;
+        state.serialize_field("payload_size", &self.payload_size)?;
+        state.serialize_field("rcode", &self.rcode)?;
+        state.serialize_field("edns_version", &self.edns_version)?;
+        state.serialize_field("flags", &self.flags)?;
+        match &self.rdlength_warning {
+            Some(warning) => state.serialize_field("rdlength_warning", warning)?,
+            None => state.skip_field("rdlength_warning")?,
+        }
+        state.serialize_field("options", &Options(&self.opt_rdata().options))?;
+        state.end()
+    }
+}
+
+impl TryFrom<Record> for OptRecord {
+    /// The record that was given, as it is not an OPT record.
+    type Error = Record;
+
+    fn try_from(record: Record) -> Result<Self, Self::Error> {
+        match record {
+            Record::OPT(opt) => Ok(opt),
+            nonopt => Err(nonopt),
+        }
+    }
+}
+
+impl TryFrom<Record> for NonOptRecord {
+    /// The record that was given, as it is an OPT record.
+    type Error = Record;
+
+    fn try_from(record: Record) -> Result<Self, Self::Error> {
+        match record {
+            Record::NONOPT(nonopt) => Ok(nonopt),
+            opt => Err(opt),
+        }
+    }
+}
+
+impl SizeReport {
+    /// Returns the size of the whole message.
+    pub fn total(&self) -> usize {
+        self.header
+            + self.questions.total
+            + self.answers.total
+            + self.authoritative_answers.total
+            + self.additional_answers.total
+    }
+}
+
+impl Display for MessageWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OptInAnswerSection => write!(f, "OPT record in answer section"),
+            Self::OptInAuthoritativeSection => write!(f, "OPT record in authoritative section"),
+            Self::MultipleOpt(n) => write!(f, "{} OPT records instead of at most one", n),
+            Self::RdlengthMismatch(owner, rtype, warning) => {
+                write!(f, "{} record for {}: {}", rtype, owner, warning)
+            }
+        }
+    }
+}
+
+impl Display for OwnerWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SrvWithoutServiceLabels => {
+                write!(f, "SRV record not owned by a _service._proto name")
+            }
+            Self::TlsaWithoutPortLabels => {
+                write!(f, "TLSA record not owned by a _port._proto name")
+            }
+            Self::DmarcWithoutDmarcLabel => write!(f, "DMARC record not owned by a _dmarc name"),
+        }
+    }
+}
+
+impl Display for RdlengthWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TrailingBytes(n) => {
+                write!(f, "RDLENGTH is {} bytes larger than the RDATA", n)
+            }
+            Self::RdataTooLong => write!(f, "RDATA is larger than RDLENGTH and was not parsed"),
+            Self::PastEndOfMessage(n) => {
+                write!(f, "RDLENGTH exceeds the end of the message by {} bytes", n)
+            }
+        }
+    }
+}
+
 impl Display for Record {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -1264,6 +2059,94 @@ impl Display for Record {
     }
 }
 
+/// Formats the message as plain text without colours or padding, e.g. for logs and golden tests.
+/// Unlike [`Message::as_string()`], this format is stable: it only changes with the formats of
+/// the parts it consists of (e.g. [`NonOptRecord`]'s `Display` impl).
+///
+/// The format is line-based:
+/// - a comment line `;; ` with the header, see [`Header`]'s `Display` impl
+/// - for each OPT record (in any section), a comment line with its EDNS version, flags, and
+///   payload size, followed by a comment line with its options, if it has any
+/// - a comment line `;; warning: ` for each of [`Message::warnings()`]
+/// - the section markers `;; QUESTION`, `;; ANSWER`, `;; AUTHORITY`, and `;; ADDITIONAL`, each
+///   followed by one line per question or non-OPT record of the section (if any)
+///
+/// Questions are formatted as `<name> <type>`, followed by the class if it is not `IN`. Records
+/// are formatted like [`NonOptRecord`]'s `Display` impl, i.e. with single spaces and without the
+/// class. There is no trailing newline.
+///
+/// # Examples
+/// ```rust
+/// use toluol_proto::rdata::A;
+/// use toluol_proto::{Class, HeaderFlags, Message, Name, NonOptRecord, Opcode, Question, RCode};
+/// use toluol_proto::{Record, RecordType};
+///
+/// let name = Name::from_ascii("example.com").unwrap();
+/// let rdata = A { address: "192.0.2.1".parse().unwrap() }.into();
+/// let record = NonOptRecord::new(name.clone(), Class::IN, 300, rdata).unwrap();
+/// let flags = HeaderFlags { aa: true, tc: false, rd: true, ra: false, ad: false, cd: false };
+/// let msg = Message::new_response(
+///     4660,
+///     Opcode::QUERY,
+///     flags,
+///     RCode::NOERROR,
+///     vec![Question::new(name, RecordType::A, Class::IN)],
+///     [vec![Record::NONOPT(record)], Vec::new(), Vec::new()],
+/// );
+/// assert_eq!(
+///     msg.to_string(),
+///     ";; DNS Response (id: 4660, opcode: QUERY, rcode: NOERROR, flags: aa rd)
+/// ;; QUESTION
+/// example.com A
+/// ;; ANSWER
+/// example.com 300 A 192.0.2.1
+/// ;; AUTHORITY
+/// ;; ADDITIONAL"
+/// );
+/// ```
+impl Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, ";; {}", self.header)?;
+        let opts = [
+            &self.additional_answers,
+            &self.answers,
+            &self.authoritative_answers,
+        ]
+        .into_iter()
+        .flatten()
+        .filter_map(Record::as_opt);
+        for opt in opts {
+            write!(f, "\n;; {}", opt)?;
+            if !opt.opt_rdata().options.is_empty() {
+                write!(f, "\n;; {}", opt.opt_rdata())?;
+            }
+        }
+        for warning in self.warnings() {
+            write!(f, "\n;; warning: {}", warning)?;
+        }
+
+        f.write_str("\n;; QUESTION")?;
+        for question in &self.questions {
+            write!(f, "\n{} {}", question.qname, question.qtype)?;
+            if question.qclass != Class::IN {
+                write!(f, " {}", question.qclass)?;
+            }
+        }
+        let sections = [
+            ("ANSWER", &self.answers),
+            ("AUTHORITY", &self.authoritative_answers),
+            ("ADDITIONAL", &self.additional_answers),
+        ];
+        for (name, records) in sections {
+            write!(f, "\n;; {}", name)?;
+            for record in records.iter().filter_map(Record::as_nonopt) {
+                write!(f, "\n{}", record)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 impl Message {
     /// Creates a DNS query.
     ///
@@ -1279,14 +2162,67 @@ impl Message {
         opcode: Opcode,
         flags: HeaderFlags,
         edns: Option<EdnsConfig>,
+    ) -> Result<Self, EncodeError> {
+        Self::new_query_with_class(domain, qtype, Class::IN, opcode, flags, edns)
+    }
+
+    /// The same as [`new_query()`](Self::new_query()), but uses the given [`Class`] for the
+    /// question instead of [`Class::IN`].
+    ///
+    /// This is needed e.g. for `CH` queries like `version.bind`.
+    pub fn new_query_with_class(
+        domain: Name,
+        qtype: RecordType,
+        qclass: Class,
+        opcode: Opcode,
+        flags: HeaderFlags,
+        edns: Option<EdnsConfig>,
+    ) -> Result<Self, EncodeError> {
+        Self::new_query_multi(
+            vec![Question::new(domain, qtype, qclass)],
+            opcode,
+            flags,
+            edns,
+        )
+    }
+
+    /// The same as [`new_query()`](Self::new_query()), but with the given questions, which may
+    /// have any [`Class`]. The question count in the header is set accordingly.
+    ///
+    /// Most nameservers only answer queries with exactly one question, but some (e.g. for
+    /// `NOTIFY` or `UPDATE`) expect none.
+    ///
+    /// Returns an error if `aa` or `ra` are set in `flags`, or if there are more than 65535
+    /// questions.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::{Class, HeaderFlags, Message, Name, Opcode, Question, RecordType};
+    ///
+    /// let flags = HeaderFlags { aa: false, tc: false, rd: true, ra: false, ad: false, cd: false };
+    /// let questions = vec![
+    ///     Question::new(Name::from_ascii("version.bind").unwrap(), RecordType::TXT, Class::CH),
+    ///     Question::new(Name::from_ascii("hostname.bind").unwrap(), RecordType::TXT, Class::CH),
+    /// ];
+    /// let query = Message::new_query_multi(questions, Opcode::QUERY, flags, None).unwrap();
+    /// assert_eq!(query.header.qdcount, 2);
+    /// assert_eq!(query.questions[1].qclass, Class::CH);
+    /// ```
+    pub fn new_query_multi(
+        questions: Vec<Question>,
+        opcode: Opcode,
+        flags: HeaderFlags,
+        edns: Option<EdnsConfig>,
     ) -> Result<Self, EncodeError> {
         if flags.aa || flags.ra {
             return Err(EncodeError::AaOrRaInQuery);
         }
+        let qdcount = u16::try_from(questions.len())
+            .map_err(|_| EncodeError::TooManyQuestions(questions.len()))?;
 
         let msg_id = rand::thread_rng().gen_range(0..(1u32 << 16)) as u16;
 
-        let header = Header::new_query_header(msg_id, opcode, flags, edns.is_some(), 1)?;
+        let header = Header::new_query_header(msg_id, opcode, flags, edns.is_some(), qdcount)?;
 
         let mut additional_answers = Vec::new();
         if let Some(edns_config) = edns {
@@ -1295,7 +2231,7 @@ impl Message {
 
         Ok(Message {
             header,
-            questions: vec![Question::new(domain, qtype, Class::IN)],
+            questions,
             answers: Vec::new(),
             authoritative_answers: Vec::new(),
             additional_answers,
@@ -1348,29 +2284,200 @@ impl Message {
     /// The same as [`encode()`](Self::encode()), but encoded bytes are appended to the given writer
     /// instead of to a newly allocated one.
     pub fn encode_into(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
-        self.header.encode_into(buf)?;
+        let mut writer = MessageWriter::new(name::Compression::Prohibited);
+        self.write(&mut writer)?;
+        buf.write_all(&writer.into_bytes())?;
+        Ok(())
+    }
+
+    /// Writes the `Message` via `writer`, which should not have been written to yet. Names are
+    /// compressed if `writer` allows it.
+    ///
+    /// Returns an error if [`Header::encode_into()`], [`Question::write()`] or [`Record::write()`]
+    /// return an error.
+    pub fn write(&self, writer: &mut MessageWriter) -> Result<(), EncodeError> {
+        self.header.encode_into(writer)?;
         for question in &self.questions {
-            question.encode_into(buf)?;
+            question.write(writer)?;
         }
-        for record in &self.answers {
-            record.encode_into(buf)?;
+        for record in self
+            .answers
+            .iter()
+            .chain(&self.authoritative_answers)
+            .chain(&self.additional_answers)
+        {
+            record.write(writer)?;
         }
-        for record in &self.authoritative_answers {
-            record.encode_into(buf)?;
+
+        Ok(())
+    }
+
+    /// Returns the size of the encoded message without encoding it, with names compressed if
+    /// `compression` is [`Compression::Allowed`](name::Compression::Allowed).
+    ///
+    /// Returns an error in the same cases as [`write()`](Self::write()).
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::name::Compression;
+    /// use toluol_proto::rdata::CNAME;
+    /// use toluol_proto::{Class, HeaderFlags, Message, Name, NonOptRecord, Opcode, Rdata, Record};
+    /// use toluol_proto::RecordType;
+    ///
+    /// let flags = HeaderFlags { aa: false, tc: false, rd: true, ra: false, ad: false, cd: false };
+    /// let name = Name::from_ascii("www.example.com").unwrap();
+    /// let mut msg =
+    ///     Message::new_query(name.clone(), RecordType::A, Opcode::QUERY, flags, None).unwrap();
+    /// let cname = Rdata::CNAME(CNAME { cname: Name::from_ascii("example.com").unwrap() });
+    /// msg.answers.push(Record::NONOPT(NonOptRecord::new(name, Class::IN, 300, cname).unwrap()));
+    ///
+    /// let size = msg.encoded_size(Compression::Prohibited).unwrap();
+    /// assert_eq!(size, msg.encode().unwrap().len());
+    /// // both the owner and the CNAME target point to the question's name
+    /// assert_eq!(msg.encoded_size(Compression::Allowed).unwrap(), size - (17 - 2) - (13 - 2));
+    /// ```
+    pub fn encoded_size(&self, compression: name::Compression) -> Result<usize, EncodeError> {
+        let mut writer = MessageWriter::size_only(compression);
+        self.write(&mut writer)?;
+        Ok(writer.position())
+    }
+
+    /// Returns the size of the encoded message without encoding it and without allocating, e.g.
+    /// to check whether a response fits into a UDP payload before assembling it.
+    ///
+    /// With [`Compression::Prohibited`](name::Compression::Prohibited), this is the exact size.
+    /// With [`Compression::Allowed`](name::Compression::Allowed), it is an estimate that only
+    /// counts the owner names of records as compressed, and only as far as they share a suffix
+    /// with the name of the first question. It is never smaller than the exact size returned by
+    /// [`encoded_size()`](Self::encoded_size()), which needs to remember all names written.
+    ///
+    /// Returns an error in the same cases as [`write()`](Self::write()).
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::name::Compression;
+    /// use toluol_proto::rdata::A;
+    /// use toluol_proto::{Class, HeaderFlags, Message, Name, NonOptRecord, Opcode, Rdata, Record};
+    /// use toluol_proto::RecordType;
+    ///
+    /// let flags = HeaderFlags { aa: false, tc: false, rd: true, ra: false, ad: false, cd: false };
+    /// let name = Name::from_ascii("www.example.com").unwrap();
+    /// let mut msg =
+    ///     Message::new_query(name.clone(), RecordType::A, Opcode::QUERY, flags, None).unwrap();
+    /// let a = Rdata::A(A::from(std::net::Ipv4Addr::new(192, 0, 2, 1)));
+    /// msg.answers.push(Record::NONOPT(NonOptRecord::new(name, Class::IN, 300, a).unwrap()));
+    ///
+    /// let size = msg.encoded_len(Compression::Prohibited).unwrap();
+    /// assert_eq!(size, msg.encode().unwrap().len());
+    /// // the owner of the A record points to the question's name
+    /// let estimate = msg.encoded_len(Compression::Allowed).unwrap();
+    /// assert_eq!(estimate, size - (17 - 2));
+    /// assert_eq!(estimate, msg.encoded_size(Compression::Allowed).unwrap());
+    /// ```
+    pub fn encoded_len(&self, compression: name::Compression) -> Result<usize, EncodeError> {
+        // the header has a fixed size
+        let mut len = 12;
+        len += self
+            .questions
+            .iter()
+            .map(Question::encoded_len)
+            .sum::<usize>();
+        let qname = self.questions.first().map(|question| &question.qname);
+        for record in self
+            .answers
+            .iter()
+            .chain(&self.authoritative_answers)
+            .chain(&self.additional_answers)
+        {
+            len += record.encoded_len()?;
+            if let (name::Compression::Allowed, Some(qname)) = (compression, qname) {
+                let owner = record.owner();
+                len -= owner.encoded_len() - owner.compressed_len(qname);
+            }
         }
-        for record in &self.additional_answers {
-            record.encode_into(buf)?;
+        Ok(len)
+    }
+
+    /// Returns the encoded size of the message, broken down by section and record, e.g. to see
+    /// which records make a response exceed the UDP payload size.
+    ///
+    /// The sizes are those of [`encode()`](Self::encode()), which returns an error in the same
+    /// cases as this method.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::{EdnsConfig, HeaderFlags, Message, Name, Opcode, RecordType};
+    ///
+    /// let flags = HeaderFlags { aa: false, tc: false, rd: true, ra: false, ad: false, cd: false };
+    /// let name = Name::from_ascii("example.com").unwrap();
+    /// let msg = Message::new_query(name, RecordType::A, Opcode::QUERY, flags, None).unwrap();
+    ///
+    /// let report = msg.size_report().unwrap();
+    /// assert_eq!(report.header, 12);
+    /// assert_eq!(report.questions.entries, vec![17]);
+    /// assert_eq!(report.total(), msg.encode().unwrap().len());
+    /// ```
+    pub fn size_report(&self) -> Result<SizeReport, EncodeError> {
+        fn section_size<T>(
+            entries: &[T],
+            encode: impl Fn(&T) -> Result<Vec<u8>, EncodeError>,
+        ) -> Result<SectionSize, EncodeError> {
+            let entries = entries
+                .iter()
+                .map(|entry| encode(entry).map(|encoded| encoded.len()))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(SectionSize {
+                total: entries.iter().sum(),
+                entries,
+            })
         }
 
-        Ok(())
+        Ok(SizeReport {
+            header: self.header.encode()?.len(),
+            questions: section_size(&self.questions, Question::encode)?,
+            answers: section_size(&self.answers, Record::encode)?,
+            authoritative_answers: section_size(&self.authoritative_answers, Record::encode)?,
+            additional_answers: section_size(&self.additional_answers, Record::encode)?,
+        })
     }
 
-    /// Parses an encoded `Message` from a series of bytes.
+    /// Parses an encoded `Message` from a series of bytes, starting at `msg`'s current position.
+    /// Afterwards, `msg` is positioned after the message.
     ///
     /// Returns an error if [`Header::parse()`], [`Question::parse()`] or [`Record::parse()`] return
     /// an error or a truncated message is received.
     pub fn parse(msg: &mut Cursor<&[u8]>) -> Result<Self, ParseError> {
-        let mut header = Header::parse(msg)?;
+        let start = min(msg.position() as usize, msg.get_ref().len());
+        let mut reader = MessageReader::new(&msg.get_ref()[start..]);
+        let parsed = Message::parse_from(&mut reader)?;
+        msg.set_position((start + reader.position()) as u64);
+        Ok(parsed)
+    }
+
+    /// Parses an encoded `Message` from the beginning of `msg`, without the need to wrap it in a
+    /// [`Cursor`] like for [`parse()`](Self::parse()). Bytes after the message are ignored.
+    ///
+    /// This is the same as `Message::try_from(msg)`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::{HeaderFlags, Message, Name, Opcode, RecordType};
+    ///
+    /// let flags = HeaderFlags { aa: false, tc: false, rd: true, ra: false, ad: false, cd: false };
+    /// let name = Name::from_ascii("example.com").unwrap();
+    /// let msg = Message::new_query(name, RecordType::A, Opcode::QUERY, flags, None).unwrap();
+    ///
+    /// let encoded = Vec::<u8>::try_from(&msg).unwrap();
+    /// assert_eq!(Message::parse_bytes(&encoded).unwrap(), msg);
+    /// assert_eq!(Message::try_from(&encoded[..]).unwrap(), msg);
+    /// ```
+    pub fn parse_bytes(msg: &[u8]) -> Result<Self, ParseError> {
+        Message::parse_from(&mut MessageReader::new(msg))
+    }
+
+    /// Parses an encoded `Message` from `msg`, which must start at the beginning of the message.
+    fn parse_from(msg: &mut MessageReader) -> Result<Self, ParseError> {
+        let header = Header::parse(msg)?;
 
         if header.flags.tc {
             return Err(ParseError::TruncatedMessage);
@@ -1394,26 +2501,377 @@ impl Message {
             additional_answers = Message::parse_records(msg, arcount, header.rcode)?;
         }
 
-        for answer in &additional_answers {
-            if let Record::OPT(OptRecord { rcode, .. }) = answer {
-                header.rcode = *rcode;
-            }
-        }
-
-        Ok(Message {
+        let mut msg = Message {
             header,
             questions,
             answers,
             authoritative_answers,
             additional_answers,
-        })
+        };
+        if let Some(rcode) = msg.opt().map(|opt| opt.rcode) {
+            msg.header.rcode = rcode;
+        }
+
+        Ok(msg)
+    }
+
+    /// Returns the message's OPT record, if there is one.
+    ///
+    /// OPT records are expected in the additional section, but some middleboxes put them in other
+    /// sections. Therefore, if there is no OPT record in the additional section, the other
+    /// sections are searched as well. If there are multiple OPT records, the first one is returned.
+    pub fn opt(&self) -> Option<&OptRecord> {
+        self.additional_answers
+            .iter()
+            .chain(self.answers.iter())
+            .chain(self.authoritative_answers.iter())
+            .find_map(Record::as_opt)
+    }
+
+    /// Sorts the records of each section by `order`. OPT records are moved to the end of their
+    /// section.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::rdata::A;
+    /// use toluol_proto::table::RecordOrder;
+    /// use toluol_proto::{Class, HeaderFlags, Message, Name, NonOptRecord, Opcode, Question};
+    /// use toluol_proto::{RCode, Rdata, Record, RecordType};
+    ///
+    /// let name = Name::from_ascii("example.com").unwrap();
+    /// let record = |address: [u8; 4]| {
+    ///     let rdata = Rdata::A(A::from(std::net::Ipv4Addr::from(address)));
+    ///     Record::NONOPT(NonOptRecord::new(name.clone(), Class::IN, 300, rdata).unwrap())
+    /// };
+    /// let flags = HeaderFlags::from_flags(0);
+    /// let question = Question::new(name.clone(), RecordType::A, Class::IN);
+    /// let answers = vec![record([192, 0, 2, 2]), record([192, 0, 2, 1])];
+    /// let mut msg = Message::new_response(
+    ///     0, Opcode::QUERY, flags, RCode::NOERROR, vec![question], [answers, vec![], vec![]],
+    /// );
+    ///
+    /// msg.sort_records(RecordOrder::Rdata);
+    /// let first = msg.answers[0].as_nonopt().unwrap();
+    /// assert_eq!(first.rdata().to_string(), "192.0.2.1");
+    /// ```
+    pub fn sort_records(&mut self, order: RecordOrder) {
+        if order == RecordOrder::Original {
+            return;
+        }
+        for section in [
+            &mut self.answers,
+            &mut self.authoritative_answers,
+            &mut self.additional_answers,
+        ] {
+            section.sort_by(|a, b| match (a, b) {
+                (Record::NONOPT(a), Record::NONOPT(b)) => order.compare(a, b),
+                (Record::OPT(_), Record::NONOPT(_)) => Ordering::Greater,
+                (Record::NONOPT(_), Record::OPT(_)) => Ordering::Less,
+                (Record::OPT(_), Record::OPT(_)) => Ordering::Equal,
+            });
+        }
+    }
+
+    /// Returns a copy of the message for display, in which the questions and records show
+    /// internationalized names in their Unicode form, see [`NonOptRecord::to_unicode_names()`].
+    pub fn to_unicode_names(&self) -> Self {
+        let mut msg = self.clone();
+        for question in &mut msg.questions {
+            question.qname = question.qname.to_unicode_labels();
+        }
+        for record in msg
+            .answers
+            .iter_mut()
+            .chain(&mut msg.authoritative_answers)
+            .chain(&mut msg.additional_answers)
+        {
+            if let Record::NONOPT(record) = record {
+                *record = record.to_unicode_names();
+            }
+        }
+        msg
+    }
+
+    /// Limits the TTLs of all records (except for OPT records) to the range from `min` to `max`,
+    /// e.g. to apply a caching policy before forwarding the message. The original TTLs are kept
+    /// for display, see [`NonOptRecord::set_ttl()`].
+    ///
+    /// Panics if `min` is greater than `max`.
+    pub fn clamp_ttls(&mut self, min: u32, max: u32) {
+        assert!(min <= max, "minimum TTL is greater than maximum TTL");
+        for record in self
+            .answers
+            .iter_mut()
+            .chain(self.authoritative_answers.iter_mut())
+            .chain(self.additional_answers.iter_mut())
+        {
+            if let Record::NONOPT(nonopt) = record {
+                nonopt.set_ttl(nonopt.ttl.clamp(min, max));
+            }
+        }
+    }
+
+    /// Makes this (upstream) response a reply to `query`, which was received from a downstream
+    /// client: the message ID is set to the query's, and if the questions are the same (ignoring
+    /// case), they are replaced by the query's, so that the client gets the spelling of the names
+    /// it sent back (e.g. with DNS 0x20).
+    ///
+    /// Together with [`strip_opt()`](Self::strip_opt()), [`replace_opt()`](Self::replace_opt()),
+    /// [`retain_records()`](Self::retain_records()), [`clamp_ttls()`](Self::clamp_ttls()) and
+    /// [`encode_compressed()`](Self::encode_compressed()), this covers what DNS proxies usually do
+    /// to the responses they forward.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::rdata::{A, AAAA};
+    /// use toluol_proto::{Class, HeaderFlags, Message, Name, NonOptRecord, Opcode, Question};
+    /// use toluol_proto::{RCode, Rdata, Record, RecordType};
+    ///
+    /// let flags = HeaderFlags::from_flags(0);
+    /// let query = Message::new_query(
+    ///     Name::from_ascii("ExAmPlE.com").unwrap(), RecordType::A, Opcode::QUERY, flags, None,
+    /// ).unwrap();
+    ///
+    /// // the upstream response, e.g. from a cache
+    /// let name = Name::from_ascii("example.com").unwrap();
+    /// let record = |rdata| {
+    ///     Record::NONOPT(NonOptRecord::new(name.clone(), Class::IN, 86400, rdata).unwrap())
+    /// };
+    /// let a = Rdata::A(A::from(std::net::Ipv4Addr::new(192, 0, 2, 1)));
+    /// let aaaa = Rdata::AAAA(AAAA::from(std::net::Ipv6Addr::LOCALHOST));
+    /// let question = Question::new(name.clone(), RecordType::A, Class::IN);
+    /// let answers = vec![record(a), record(aaaa)];
+    /// let mut res = Message::new_response(
+    ///     1, Opcode::QUERY, flags, RCode::NOERROR, vec![question], [answers, vec![], vec![]],
+    /// );
+    ///
+    /// res.reply_to(&query);
+    /// res.retain_records(|rec| rec.rtype == RecordType::A);
+    /// res.clamp_ttls(0, 3600);
+    /// let encoded = res.encode_compressed().unwrap();
+    ///
+    /// let forwarded = Message::parse_bytes(&encoded).unwrap();
+    /// assert_eq!(forwarded.header.msg_id, query.header.msg_id);
+    /// assert_eq!(forwarded.questions[0].qname.to_string(), "ExAmPlE.com");
+    /// assert_eq!(forwarded.header.ancount, 1);
+    /// assert_eq!(forwarded.answers[0].as_nonopt().unwrap().ttl, 3600);
+    /// ```
+    pub fn reply_to(&mut self, query: &Message) {
+        self.header.msg_id = query.header.msg_id;
+        if self.questions == query.questions {
+            self.questions = query.questions.clone();
+        }
+    }
+
+    /// Removes all OPT records from all sections and returns the first one, if any. The header's
+    /// record counts are updated accordingly.
+    ///
+    /// Note that an extended RCODE (see [`OptRecord::rcode`]) cannot be represented without an
+    /// OPT record, so only its lower four bits are encoded in that case.
+    pub fn strip_opt(&mut self) -> Option<OptRecord> {
+        let mut first = None;
+        for section in [
+            &mut self.answers,
+            &mut self.authoritative_answers,
+            &mut self.additional_answers,
+        ] {
+            for record in std::mem::take(section) {
+                match record {
+                    Record::OPT(opt) => {
+                        first.get_or_insert(opt);
+                    }
+                    record => section.push(record),
+                }
+            }
+        }
+        self.update_counts();
+        first
+    }
+
+    /// Replaces all OPT records with `opt`, which is appended to the additional section, e.g. to
+    /// advertise the proxy's own payload size and options instead of the upstream server's. For
+    /// responses, the RCODE of `opt` is set to the message's, so that extended RCODEs are kept.
+    pub fn replace_opt(&mut self, mut opt: OptRecord) {
+        self.strip_opt();
+        if self.header.qr {
+            opt.rcode = self.header.rcode;
+        }
+        self.additional_answers.push(Record::OPT(opt));
+        self.update_counts();
+    }
+
+    /// Removes the non-OPT records for which `filter` returns false from all sections, e.g. to
+    /// filter out record types that downstream clients should not see. The header's record counts
+    /// are updated accordingly.
+    pub fn retain_records(&mut self, mut filter: impl FnMut(&NonOptRecord) -> bool) {
+        for section in [
+            &mut self.answers,
+            &mut self.authoritative_answers,
+            &mut self.additional_answers,
+        ] {
+            section.retain(|record| match record {
+                Record::NONOPT(nonopt) => filter(nonopt),
+                Record::OPT(_) => true,
+            });
+        }
+        self.update_counts();
+    }
+
+    /// Like [`encode()`](Self::encode()), but compresses names, as servers usually do to keep
+    /// responses small.
+    pub fn encode_compressed(&self) -> Result<Vec<u8>, EncodeError> {
+        let mut writer = MessageWriter::new(name::Compression::Allowed);
+        self.write(&mut writer)?;
+        Ok(writer.into_bytes())
+    }
+
+    /// Sets the header's record counts to the numbers of questions and records in the sections.
+    fn update_counts(&mut self) {
+        self.header.qdcount = self.questions.len() as u16;
+        self.header.ancount = self.answers.len() as u16;
+        self.header.nscount = self.authoritative_answers.len() as u16;
+        self.header.arcount = self.additional_answers.len() as u16;
+    }
+
+    /// Removes the non-OPT records for which `filter` returns true from all sections and returns
+    /// them grouped into record sets, in the order in which the sets first appear. The header's
+    /// record counts are updated accordingly.
+    ///
+    /// This allows validating the records (see [`dnssec::validate_rrset()`]) without copying them.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::rdata::A;
+    /// use toluol_proto::{Class, HeaderFlags, Message, Name, NonOptRecord, Opcode, Question};
+    /// use toluol_proto::{RCode, Rdata, Record, RecordType};
+    ///
+    /// let record = |name, address: [u8; 4]| {
+    ///     let rdata = Rdata::A(A::from(std::net::Ipv4Addr::from(address)));
+    ///     let name = Name::from_ascii(name).unwrap();
+    ///     Record::NONOPT(NonOptRecord::new(name, Class::IN, 300, rdata).unwrap())
+    /// };
+    /// let flags = HeaderFlags::from_flags(0);
+    /// let question = Question::new(Name::from_ascii("a.example").unwrap(), RecordType::A, Class::IN);
+    /// let answers = vec![
+    ///     record("a.example", [192, 0, 2, 1]),
+    ///     record("b.example", [192, 0, 2, 2]),
+    ///     record("a.example", [192, 0, 2, 3]),
+    /// ];
+    /// let mut msg = Message::new_response(
+    ///     0, Opcode::QUERY, flags, RCode::NOERROR, vec![question], [answers, vec![], vec![]],
+    /// );
+    ///
+    /// let a = Name::from_ascii("a.example").unwrap();
+    /// let rrsets = msg.take_rrsets(|rec| rec.owner == a);
+    /// assert_eq!(rrsets.len(), 1);
+    /// assert_eq!(rrsets[0].records().len(), 2);
+    /// assert_eq!(msg.answers.len(), 1);
+    /// assert_eq!(msg.header.ancount, 1);
+    /// ```
+    pub fn take_rrsets(&mut self, mut filter: impl FnMut(&NonOptRecord) -> bool) -> Vec<RrSet> {
+        let mut taken = Vec::new();
+        for section in [
+            &mut self.answers,
+            &mut self.authoritative_answers,
+            &mut self.additional_answers,
+        ] {
+            for record in std::mem::take(section) {
+                match record {
+                    Record::NONOPT(nonopt) if filter(&nonopt) => taken.push(nonopt),
+                    record => section.push(record),
+                }
+            }
+        }
+        self.update_counts();
+        dnssec::group_rrsets(taken)
+    }
+
+    /// Returns all records from all sections, except for OPT records.
+    pub fn nonopt_records(&self) -> impl Iterator<Item = &NonOptRecord> {
+        self.answers
+            .iter()
+            .chain(self.authoritative_answers.iter())
+            .chain(self.additional_answers.iter())
+            .filter_map(Record::as_nonopt)
+    }
+
+    /// Returns the ways in which the message deviates from what is expected of a well-formed
+    /// message, e.g. OPT records outside of the additional section.
+    pub fn warnings(&self) -> Vec<MessageWarning> {
+        let mut warnings = Vec::new();
+
+        if self.answers.iter().any(|rec| rec.as_opt().is_some()) {
+            warnings.push(MessageWarning::OptInAnswerSection);
+        }
+        if self
+            .authoritative_answers
+            .iter()
+            .any(|rec| rec.as_opt().is_some())
+        {
+            warnings.push(MessageWarning::OptInAuthoritativeSection);
+        }
+
+        let opt_count = self
+            .answers
+            .iter()
+            .chain(self.authoritative_answers.iter())
+            .chain(self.additional_answers.iter())
+            .filter(|rec| rec.as_opt().is_some())
+            .count();
+        if opt_count > 1 {
+            warnings.push(MessageWarning::MultipleOpt(opt_count));
+        }
+
+        for record in self
+            .answers
+            .iter()
+            .chain(self.authoritative_answers.iter())
+            .chain(self.additional_answers.iter())
+        {
+            if let Some(warning) = record.rdlength_warning() {
+                let (owner, rtype) = match record {
+                    Record::OPT(opt) => (opt.owner.clone(), RecordType::OPT),
+                    Record::NONOPT(nonopt) => (nonopt.owner.clone(), nonopt.rtype),
+                };
+                warnings.push(MessageWarning::RdlengthMismatch(owner, rtype, warning));
+            }
+        }
+
+        warnings
     }
 
     /// Returns a string verbosely describing the message (i.e. header and all the other sections).
     ///
     /// If `output` is [`Some`] and the specified output stream supports colours, the output will
     /// be colourized.
+    #[cfg(feature = "presentation")]
     pub fn as_string(&self, output: Option<owo_colors::Stream>) -> String {
+        self.as_string_with(output, DisplaySections::ALL)
+    }
+
+    /// Like [`as_string()`](Self::as_string()), but only describes the parts of the message
+    /// selected by `sections`. Without comments, the records are not indented and the sections
+    /// are not separated by empty lines, so that each line is a question or record.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::{DisplaySections, HeaderFlags, Message, Name, Opcode, RecordType};
+    ///
+    /// let flags = HeaderFlags { aa: false, tc: false, rd: true, ra: false, ad: false, cd: false };
+    /// let name = Name::from_ascii("example.com").unwrap();
+    /// let msg = Message::new_query(name, RecordType::A, Opcode::QUERY, flags, None).unwrap();
+    ///
+    /// let sections = DisplaySections { question: true, ..DisplaySections::NONE };
+    /// let text = msg.as_string_with(None, sections);
+    /// assert_eq!(text.lines().count(), 1);
+    /// assert!(text.starts_with("example.com ") && text.ends_with(" A"));
+    /// ```
+    #[cfg(feature = "presentation")]
+    pub fn as_string_with(
+        &self,
+        output: Option<owo_colors::Stream>,
+        sections: DisplaySections,
+    ) -> String {
         let section_name = |s: &str, o: Option<owo_colors::Stream>| {
             let mut s = s.to_string();
             if let Some(stream) = o {
@@ -1423,125 +2881,132 @@ impl Message {
         };
 
         let mut res = String::new();
+        let indent = if sections.comments { "\t" } else { "" };
 
-        let mut additional_answers = self.additional_answers.clone();
-        let mut opt_index = None;
-
-        let mut max_owner_len = 0;
-        let mut max_type_len = 0;
-
-        for q in &self.questions {
-            max_owner_len = max(max_owner_len, q.qname.string_len());
-            max_type_len = max(max_type_len, q.qtype.to_string().len());
-        }
-
-        let answers = [
+        let questions: &[Question] = if sections.question {
+            &self.questions
+        } else {
+            &[]
+        };
+        let record_sections = [
+            ("Answer Section:\n", &self.answers, sections.answer),
+            (
+                "Authoritative Section:\n",
+                &self.authoritative_answers,
+                sections.authority,
+            ),
+            (
+                "Additional Section:\n",
+                &self.additional_answers,
+                sections.additional,
+            ),
+        ]
+        .into_iter()
+        .filter(|(_, _, selected)| *selected)
+        .map(|(name, section, _)| {
+            let records = section.iter().filter_map(Record::as_nonopt);
+            let records = if sections.group_rrsets {
+                table::group_records(records)
+            } else {
+                records.collect()
+            };
+            (name, records)
+        })
+        .filter(|(_, records)| !records.is_empty())
+        .collect::<Vec<_>>();
+
+        let mut table = RecordTable::new(output);
+        table.unicode_names = sections.unicode_names;
+        table.fit_questions(questions);
+        table.fit_records(
+            record_sections
+                .iter()
+                .flat_map(|(_, records)| records)
+                .copied(),
+        );
+
+        // OPT records are printed in the OPT pseudosection, regardless of which section they were
+        // found in
+        let opts = [
+            &self.additional_answers,
             &self.answers,
             &self.authoritative_answers,
-            &self.additional_answers,
-        ];
-        let answers_iter = answers.iter().flat_map(|a| a.iter());
-        for (i, answer) in answers_iter.enumerate() {
-            match answer {
-                Record::OPT(_) => {
-                    // the iterator runs over self.answers, self.authoritative_answers and self.additional_answers,
-                    // but we want the index of answer with respect to self.additional_answers
-                    opt_index = Some(i - self.answers.len() - self.authoritative_answers.len());
-                }
-                Record::NONOPT(NonOptRecord {
-                    owner: name,
-                    rtype: atype,
-                    ..
-                }) => {
-                    max_owner_len = max(max_owner_len, name.string_len());
-                    max_type_len = max(max_type_len, atype.to_string().len());
+        ]
+        .into_iter()
+        .flatten()
+        .filter_map(Record::as_opt);
+
+        if sections.comments {
+            // Header
+            // TODO: coloured header output?
+            res.push_str(section_name("Header:\n\t", output).as_str());
+            res.push_str(format!("{}\n\n", self.header.info_str()).as_str());
+
+            let warnings = self.warnings();
+            if !warnings.is_empty() {
+                res.push_str(section_name("Warnings:\n", output).as_str());
+                for warning in warnings {
+                    res.push_str(format!("\t{}\n", warning).as_str());
                 }
+                res.push('\n');
             }
-        }
-
-        // Header
-        // TODO: coloured header output?
-        res.push_str(section_name("Header:\n\t", output).as_str());
-        res.push_str(format!("{}\n\n", self.header.info_str()).as_str());
 
-        // OPT Pseudosection (if present)
-        if let Some(idx) = opt_index {
-            let opt = additional_answers.remove(idx);
-            let opt = opt
-                .as_opt()
-                .expect("Calculated incorrect index for OPT record");
-            res.push_str(section_name("OPT Pseudosection:\n", output).as_str());
-            res.push_str(&opt.as_padded_string("\t", output));
-            res.push_str("\n\n");
+            // OPT Pseudosection (if present)
+            for opt in opts {
+                res.push_str(section_name("OPT Pseudosection:\n", output).as_str());
+                res.push_str(&opt.as_padded_string("\t", output));
+                res.push_str("\n\n");
+            }
         }
 
-        res.push_str(section_name("Question Section:\n", output).as_str());
-        for question in &self.questions {
-            res.push('\t');
-            // question doesn't need max_type_len because nothing gets printed after its qtype
-            res.push_str(question.as_padded_string(max_owner_len, output).as_str());
-            res.push('\n');
-        }
-        res.push('\n');
-
-        if !self.answers.is_empty() {
-            res.push_str(section_name("Answer Section:\n", output).as_str());
-            for answer in &self.answers {
-                res.push('\t');
-                res.push_str(
-                    answer
-                        .as_nonopt()
-                        .expect("Unexpected OPT record")
-                        .as_string(false, Some(max_owner_len), Some(max_type_len), output)
-                        .as_str(),
-                );
+        if sections.question {
+            if sections.comments {
+                res.push_str(section_name("Question Section:\n", output).as_str());
+            }
+            for question in questions {
+                res.push_str(indent);
+                res.push_str(table.question_line(question).as_str());
+                res.push('\n');
+            }
+            if sections.comments {
                 res.push('\n');
             }
-            res.push('\n');
-        }
-
-        if !self.authoritative_answers.is_empty() {
-            res.push_str(section_name("Authoritative Section:\n", output).as_str());
-            for answer in &self.authoritative_answers {
-                res.push('\t');
-                res.push_str(
-                    answer
-                        .as_nonopt()
-                        .expect("Unexpected OPT record")
-                        .as_string(false, Some(max_owner_len), Some(max_type_len), output)
-                        .as_str(),
-                );
+        }
+
+        for (name, records) in record_sections {
+            if sections.comments {
+                res.push_str(section_name(name, output).as_str());
+            }
+            let mut previous = None;
+            for record in records {
+                res.push_str(indent);
+                let line = if sections.group_rrsets {
+                    table.grouped_line(record, previous)
+                } else {
+                    table.record_line(record)
+                };
+                res.push_str(line.as_str());
+                previous = Some(record);
                 res.push('\n');
             }
-            res.push('\n');
-        }
-
-        if !additional_answers.is_empty() {
-            res.push_str(section_name("Additional Section:\n", output).as_str());
-            for answer in &additional_answers {
-                res.push('\t');
-                res.push_str(
-                    answer
-                        .as_nonopt()
-                        .expect("Unexpected OPT record")
-                        .as_string(false, Some(max_owner_len), Some(max_type_len), output)
-                        .as_str(),
-                );
+            if sections.comments {
                 res.push('\n');
             }
         }
 
         // remove trailing '\n's
-        while res.chars().nth(res.len() - 1).unwrap() == '\n' {
-            res.remove(res.len() - 1);
+        while res.ends_with('\n') {
+            res.pop();
         }
 
         res
     }
 
     /// Parses the question section of a DNS message.
-    fn parse_questions(msg: &mut Cursor<&[u8]>, qdcount: u16) -> Result<Vec<Question>, ParseError> {
-        let mut questions = Vec::with_capacity(qdcount as usize);
+    fn parse_questions(msg: &mut MessageReader, qdcount: u16) -> Result<Vec<Question>, ParseError> {
+        // a forged count must not cause a huge allocation; a question has at least 5 bytes
+        let capacity = (qdcount as usize).min(msg.remaining_in_rdata() / 5);
+        let mut questions = Vec::with_capacity(capacity);
         for _i in 0..qdcount {
             questions.push(Question::parse(msg)?);
         }
@@ -1551,11 +3016,13 @@ impl Message {
 
     /// Parses an answer section (i. e. answer, authoritative or additional) of a DNS message.
     fn parse_records(
-        msg: &mut Cursor<&[u8]>,
+        msg: &mut MessageReader,
         ancount: u16,
         rcode: Option<RCode>,
     ) -> Result<Vec<Record>, ParseError> {
-        let mut answers = Vec::with_capacity(ancount as usize);
+        // a forged count must not cause a huge allocation; a record has at least 11 bytes
+        let capacity = (ancount as usize).min(msg.remaining_in_rdata() / 11);
+        let mut answers = Vec::with_capacity(capacity);
         for _i in 0..ancount {
             answers.push(Record::parse(msg, rcode)?);
         }
@@ -1563,3 +3030,21 @@ impl Message {
         Ok(answers)
     }
 }
+
+impl TryFrom<&[u8]> for Message {
+    type Error = ParseError;
+
+    /// Parses an encoded `Message`, see [`Message::parse_bytes()`].
+    fn try_from(msg: &[u8]) -> Result<Self, Self::Error> {
+        Message::parse_bytes(msg)
+    }
+}
+
+impl TryFrom<&Message> for Vec<u8> {
+    type Error = EncodeError;
+
+    /// Encodes a `Message`, see [`Message::encode()`].
+    fn try_from(msg: &Message) -> Result<Self, Self::Error> {
+        msg.encode()
+    }
+}
@@ -1,14 +1,19 @@
 //! Definition and implementation of the [`Name`] type.
 
 use std::cmp::Ordering;
-use std::collections::VecDeque;
 use std::fmt::Display;
-use std::io::{Cursor, Seek, SeekFrom, Write};
-
-use byteorder::{ReadBytesExt, WriteBytesExt};
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::net::IpAddr;
+
+use byteorder::WriteBytesExt;
+use data_encoding::HEXLOWER;
+use sha2::{Digest, Sha256};
+use smallvec::SmallVec;
 use smartstring::SmartString;
 
 use crate::error::{EncodeError, ParseError};
+use crate::reader::MessageReader;
 
 #[cfg(feature = "serde")]
 use serde::Serialize;
@@ -25,9 +30,13 @@ use serde::Serialize;
 #[derive(Eq, Clone, Debug)]
 pub struct Name {
     // does not contain the root label, as that would be the empty string
-    labels: VecDeque<SmartString<smartstring::LazyCompact>>,
+    labels: Labels,
 }
 
+/// The labels of a [`Name`]. Most names have only a few labels, which are stored inline to avoid
+/// allocating when parsing messages.
+pub(crate) type Labels = SmallVec<[SmartString<smartstring::LazyCompact>; 4]>;
+
 /// Whether DNS message/name compression is allowed when parsing a [`Name`].
 ///
 /// For example, the [`RRSIG::signer_name`](crate::rdata::rrsig::RRSIG::signer_name) field must not
@@ -54,11 +63,12 @@ impl Name {
     /// ```
     pub fn root() -> Self {
         Self {
-            labels: VecDeque::new(),
+            labels: Labels::new(),
         }
     }
 
-    /// Parses a `Name` encoded as a DNS QNAME from the given cursor.
+    /// Parses a `Name` encoded as a DNS QNAME from the given reader, see
+    /// [`MessageReader::read_name()`].
     ///
     /// If `allow_compression` is true, message compression is supported.
     ///
@@ -68,54 +78,33 @@ impl Name {
     ///
     /// # Examples
     /// ```rust
-    /// use std::io::Cursor;
     /// use toluol_proto::name::{Compression, Name};
+    /// use toluol_proto::reader::MessageReader;
     ///
     /// // "sub.example.com" encoded via the "sub" label followed by a pointer to "example.com"
-    /// let bytes = b"\x07example\x03com\0\x03sub\xc0";
-    /// let mut cursor = Cursor::new(bytes as &[u8]);
-    /// let name = Name::parse(&mut cursor, Compression::Allowed);
+    /// let bytes = b"\x07example\x03com\0\x03sub\xc0\x00";
+    /// let mut reader = MessageReader::new(bytes);
+    /// let name = Name::parse(&mut reader, Compression::Allowed);
     /// assert_eq!(name.ok(), Some(Name::from_ascii("example.com").unwrap()));
     ///
-    /// let name = Name::parse(&mut cursor, Compression::Prohibited);
+    /// let mut sub = reader.clone();
+    /// let name = Name::parse(&mut sub, Compression::Allowed);
+    /// assert_eq!(name.ok(), Some(Name::from_ascii("sub.example.com").unwrap()));
+    ///
+    /// let name = Name::parse(&mut reader, Compression::Prohibited);
     /// assert!(name.is_err());
+    ///
+    /// // compression pointers must point backwards
+    /// let mut reader = MessageReader::new(b"\x03sub\xc0\x00");
+    /// assert!(Name::parse(&mut reader, Compression::Allowed).is_err());
     /// ```
-    pub fn parse(msg: &mut Cursor<&[u8]>, compression: Compression) -> Result<Self, ParseError> {
-        let mut labels = VecDeque::new();
-        let mut c = msg.read_u8()?; // length of next label
-
-        while c != 0 {
-            if (c & 0b11000000) != 0 {
-                if compression == Compression::Prohibited {
-                    return Err(ParseError::CompressionProhibited);
-                }
-
-                // after this comes a pointer for message compression
-                c &= 0b00111111; // erase upper two bits of c for offset calculation
-                let offset = ((c as u16) << 8) + (msg.read_u8()? as u16);
-                // save position after pointer
-                let pos_after_pointer = msg.position() as i64;
-                msg.seek(SeekFrom::Start(offset as u64))?;
-                // recursion is the easiest way to handle recursive message compression
-                // (i've seen that being used... looking at you, a.gtld-servers.net)
-                // TODO do this iteratively to avoid unnecessary allocations
-                labels.append(&mut Name::parse(msg, compression)?.labels);
-
-                // move cursor to byte after pointer
-                msg.seek(SeekFrom::Start(pos_after_pointer as u64))?;
-                return Ok(Name { labels });
-            } else if (c & 0b01000000) != 0 || (c & 0b10000000) != 0 {
-                return Err(ParseError::InvalidLabelType(c));
-            }
-            let mut label = SmartString::new();
-            for _ in 0..c {
-                label.push(msg.read_u8()? as char);
-            }
-            labels.push_back(label);
-            c = msg.read_u8()?;
-        }
+    pub fn parse(msg: &mut MessageReader, compression: Compression) -> Result<Self, ParseError> {
+        msg.read_name(compression)
+    }
 
-        Ok(Name { labels })
+    /// Constructs a `Name` from already validated labels.
+    pub(crate) fn from_labels(labels: Labels) -> Self {
+        Self { labels }
     }
 
     /// Constructs a `Name` from an ASCII domain string.
@@ -156,19 +145,19 @@ impl Name {
             return Ok(Self::root());
         }
 
-        if name.bytes().len() > 255 {
-            return Err(ParseError::NameTooLong(name.bytes().len()));
+        if name.len() > 255 {
+            return Err(ParseError::NameTooLong(name.len()));
         }
 
         let labels_iter = name.split('.');
-        let mut labels = VecDeque::new();
+        let mut labels = Labels::new();
         let mut root_label_found = false;
         for (idx, label) in labels_iter.enumerate() {
             if root_label_found {
                 return Err(ParseError::EmptyLabel);
             }
-            if label.bytes().len() > 63 {
-                return Err(ParseError::LabelTooLong(label.bytes().len()));
+            if label.len() > 63 {
+                return Err(ParseError::LabelTooLong(label.len()));
             }
             if label.is_empty() {
                 root_label_found = true;
@@ -179,13 +168,234 @@ impl Name {
                 if !is_valid_wildcard {
                     Name::check_label(label)?;
                 }
-                labels.push_back(label.into());
+                labels.push(label.into());
             }
         }
 
         Ok(Name { labels })
     }
 
+    /// Returns the domain name under `e164.arpa` for the given E.164 number, as used by ENUM (see
+    /// [RFC 6116, Section 2.4](https://www.rfc-editor.org/rfc/rfc6116#section-2.4)).
+    ///
+    /// The number must start with a `+`, followed by up to 15 digits. Hyphens, dots, and spaces
+    /// between the digits are ignored.
+    ///
+    /// Returns an error if the given string is not such a number.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::Name;
+    ///
+    /// let name = Name::from_e164("+44 1632 960083").unwrap();
+    /// assert_eq!(name.to_string(), "3.8.0.0.6.9.2.3.6.1.4.4.e164.arpa");
+    ///
+    /// assert!(Name::from_e164("441632960083").is_err());
+    /// assert!(Name::from_e164("+44a1632960083").is_err());
+    /// assert!(Name::from_e164("+").is_err());
+    /// ```
+    pub fn from_e164(number: impl AsRef<str>) -> Result<Self, ParseError> {
+        let number = number.as_ref();
+        let invalid = || ParseError::InvalidE164Number(number.to_string());
+
+        let digits = number.strip_prefix('+').ok_or_else(invalid)?;
+        if digits
+            .chars()
+            .any(|c| !c.is_ascii_digit() && !"-. ".contains(c))
+        {
+            return Err(invalid());
+        }
+        let mut labels: Labels = digits
+            .chars()
+            .filter(char::is_ascii_digit)
+            .rev()
+            .map(|digit| digit.to_string().into())
+            .collect();
+        if labels.is_empty() || labels.len() > 15 {
+            return Err(invalid());
+        }
+        labels.push("e164".into());
+        labels.push("arpa".into());
+
+        Ok(Name { labels })
+    }
+
+    /// Returns the name used for reverse lookups of `address`, i.e. the name under `in-addr.arpa`
+    /// or `ip6.arpa` whose `PTR` records map the address to host names.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::Name;
+    ///
+    /// let name = Name::from_ip("192.0.2.1".parse().unwrap());
+    /// assert_eq!(name.to_string(), "1.2.0.192.in-addr.arpa");
+    ///
+    /// let name = Name::from_ip("2001:db8::1".parse().unwrap());
+    /// assert_eq!(
+    ///     name.to_string(),
+    ///     "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa"
+    /// );
+    /// ```
+    pub fn from_ip(address: IpAddr) -> Self {
+        let mut labels: Labels = match address {
+            IpAddr::V4(address) => address
+                .octets()
+                .iter()
+                .rev()
+                .map(|octet| octet.to_string().into())
+                .collect(),
+            IpAddr::V6(address) => address
+                .octets()
+                .iter()
+                .rev()
+                .flat_map(|octet| [octet & 0xf, octet >> 4])
+                .map(|nibble| format!("{:x}", nibble).into())
+                .collect(),
+        };
+        match address {
+            IpAddr::V4(_) => labels.push("in-addr".into()),
+            IpAddr::V6(_) => labels.push("ip6".into()),
+        }
+        labels.push("arpa".into());
+        Name { labels }
+    }
+
+    /// Returns the reverse zone of the network with the first `prefix_len` bits of `address`,
+    /// i.e. the name under which the names returned by [`from_ip()`](Self::from_ip()) for all
+    /// addresses of the network are. As labels stand for octets (IPv4) or nibbles (IPv6), a
+    /// `prefix_len` that is not a multiple of 8 or 4, respectively, is rounded down.
+    ///
+    /// Returns an error if `prefix_len` is greater than 32 (IPv4) or 128 (IPv6).
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::Name;
+    ///
+    /// let zone = Name::reverse_zone("2001:db8::".parse().unwrap(), 32).unwrap();
+    /// assert_eq!(zone.to_string(), "8.b.d.0.1.0.0.2.ip6.arpa");
+    ///
+    /// let zone = Name::reverse_zone("192.0.2.0".parse().unwrap(), 26).unwrap();
+    /// assert_eq!(zone.to_string(), "2.0.192.in-addr.arpa");
+    ///
+    /// assert!(Name::reverse_zone("192.0.2.0".parse().unwrap(), 33).is_err());
+    /// ```
+    pub fn reverse_zone(address: IpAddr, prefix_len: u8) -> Result<Self, ParseError> {
+        let (max_len, bits_per_label) = match address {
+            IpAddr::V4(_) => (32, 8),
+            IpAddr::V6(_) => (128, 4),
+        };
+        if prefix_len > max_len {
+            return Err(ParseError::InvalidPrefixLength(prefix_len, max_len));
+        }
+
+        let mut name = Self::from_ip(address);
+        for _ in 0..(max_len - prefix_len).div_ceil(bits_per_label) {
+            name.labels.remove(0);
+        }
+        Ok(name)
+    }
+
+    /// Returns the owner name of the [`OPENPGPKEY`](crate::rdata::OPENPGPKEY) records for the
+    /// given email address (see [RFC 7929, Section 3](https://www.rfc-editor.org/rfc/rfc7929#section-3)):
+    /// the hex-encoded SHA-256 hash of the local part, truncated to 28 bytes, followed by
+    /// `_openpgpkey` and the domain.
+    ///
+    /// The local part is hashed as is; RFC 7929 leaves it to the caller whether to e.g. lowercase
+    /// it first.
+    ///
+    /// Returns an error if the address has no `@`, its local part is empty, or its domain is not
+    /// a valid name.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::Name;
+    ///
+    /// // the example from RFC 7929, Section 3
+    /// let name = Name::openpgpkey_for_email("hugh@example.com").unwrap();
+    /// assert_eq!(
+    ///     name.to_string(),
+    ///     "c93f1e400f26708f98cb19d936620da35eec8f72e57f9eec01c1afd6._openpgpkey.example.com"
+    /// );
+    ///
+    /// assert!(Name::openpgpkey_for_email("example.com").is_err());
+    /// assert!(Name::openpgpkey_for_email("@example.com").is_err());
+    /// ```
+    pub fn openpgpkey_for_email(email: impl AsRef<str>) -> Result<Self, ParseError> {
+        Self::for_email(email.as_ref(), "_openpgpkey")
+    }
+
+    /// Returns the owner name of the `SMIMEA` records for the given email address, which is formed
+    /// like [`openpgpkey_for_email()`](Self::openpgpkey_for_email()), but with `_smimecert` (see
+    /// [RFC 8162, Section 3](https://www.rfc-editor.org/rfc/rfc8162#section-3)).
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::Name;
+    ///
+    /// let name = Name::smimea_for_email("hugh@example.com").unwrap();
+    /// assert!(name.to_string().ends_with("._smimecert.example.com"));
+    /// ```
+    pub fn smimea_for_email(email: impl AsRef<str>) -> Result<Self, ParseError> {
+        Self::for_email(email.as_ref(), "_smimecert")
+    }
+
+    fn for_email(email: &str, service: &str) -> Result<Self, ParseError> {
+        let invalid = || ParseError::InvalidEmailAddress(email.to_string());
+        // the local part may contain an @ if it is quoted, the domain may not
+        let (local_part, domain) = email.rsplit_once('@').ok_or_else(invalid)?;
+        if local_part.is_empty() {
+            return Err(invalid());
+        }
+        let mut name = Self::from_ascii(domain).map_err(|_| invalid())?;
+        if name.is_root() {
+            return Err(invalid());
+        }
+
+        let hash = Sha256::digest(local_part.as_bytes());
+        name.prepend_label(service)?;
+        name.prepend_label(HEXLOWER.encode(&hash[..28]))?;
+        Ok(name)
+    }
+
+    /// Returns the length of this name encoded as a DNS QNAME without message compression, i.e. the
+    /// number of bytes [`Self::encode_into()`] writes, without encoding it.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::Name;
+    ///
+    /// assert_eq!(Name::from_ascii("example.com").unwrap().encoded_len(), 13);
+    /// assert_eq!(Name::root().encoded_len(), 1);
+    /// ```
+    pub fn encoded_len(&self) -> usize {
+        // each label has a length byte, and the root label is a single zero byte
+        self.labels
+            .iter()
+            .map(|label| label.len() + 1)
+            .sum::<usize>()
+            + 1
+    }
+
+    /// Returns the length of this name when it is written by a compressing
+    /// [`MessageWriter`](crate::writer::MessageWriter) after `previous`: the labels that are not
+    /// part of a suffix shared with `previous`, followed by a pointer to that suffix.
+    ///
+    /// Like the writer, this compares labels byte by byte, i.e. case-sensitively.
+    pub(crate) fn compressed_len(&self, previous: &Name) -> usize {
+        let shared = self
+            .labels
+            .iter()
+            .rev()
+            .zip(previous.labels.iter().rev())
+            .take_while(|(label, other)| label.as_bytes() == other.as_bytes())
+            .count();
+        if shared == 0 {
+            return self.encoded_len();
+        }
+        let unshared = &self.labels[..self.labels.len() - shared];
+        unshared.iter().map(|label| label.len() + 1).sum::<usize>() + 2
+    }
+
     /// Encodes this name as a DNS QNAME into the given buffer. Does not use message compression.
     ///
     /// Returns the number of bytes written on success.
@@ -206,7 +416,7 @@ impl Name {
         for label in &self.labels {
             buf.write_u8(label.len() as u8)?;
             buf.write_all(label.as_bytes())?;
-            bytes_written += 1 + label.as_bytes().len();
+            bytes_written += 1 + label.len();
         }
         buf.write_u8(0)?;
         Ok(bytes_written as u16 + 1)
@@ -247,7 +457,7 @@ impl Name {
     pub fn append_label(&mut self, label: impl AsRef<str>) -> Result<(), ParseError> {
         Name::check_label(label.as_ref())?;
         let label = SmartString::from(label.as_ref());
-        self.labels.push_back(label);
+        self.labels.push(label);
         Ok(())
     }
 
@@ -289,7 +499,7 @@ impl Name {
     /// ```
     pub fn prepend_label(&mut self, label: impl AsRef<str>) -> Result<(), ParseError> {
         Name::check_label(label.as_ref())?;
-        self.labels.push_front(label.as_ref().into());
+        self.labels.insert(0, label.as_ref().into());
         Ok(())
     }
 
@@ -311,7 +521,16 @@ impl Name {
     /// assert!(name.is_root());
     /// ```
     pub fn pop_front_label(&mut self) -> Option<SmartString<smartstring::LazyCompact>> {
-        self.labels.pop_front()
+        if self.labels.is_empty() {
+            None
+        } else {
+            Some(self.labels.remove(0))
+        }
+    }
+
+    /// Returns the label at `index`, counting from the left, if it exists.
+    pub(crate) fn label(&self, index: usize) -> Option<&str> {
+        self.labels.get(index).map(|label| label.as_str())
     }
 
     /// Removes and returns the last label of this `Name`, if it exists.
@@ -332,7 +551,7 @@ impl Name {
     /// assert!(name.is_root());
     /// ```
     pub fn pop_back_label(&mut self) -> Option<SmartString<smartstring::LazyCompact>> {
-        self.labels.pop_back()
+        self.labels.pop()
     }
 
     /// Prepends a wildcard label (`"*"`) to this `Name`.
@@ -355,7 +574,7 @@ impl Name {
     /// ```
     pub fn prepend_wildcard(&mut self) {
         if !self.is_wildcard() {
-            self.labels.push_front("*".into());
+            self.labels.insert(0, "*".into());
         }
     }
 
@@ -460,6 +679,83 @@ impl Name {
         }
     }
 
+    /// Returns true if any label of this `Name` is an IDNA A-label, i.e. starts with `xn--`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::Name;
+    ///
+    /// assert!(Name::from_ascii("xn--bcher-kva.example").unwrap().is_idn());
+    /// assert!(!Name::from_ascii("example.com").unwrap().is_idn());
+    /// ```
+    pub fn is_idn(&self) -> bool {
+        self.labels
+            .iter()
+            .any(|label| label.len() >= 4 && label[..4].eq_ignore_ascii_case("xn--"))
+    }
+
+    /// Returns the string representation of this `Name` with all IDNA A-labels (`xn--...`)
+    /// converted to their Unicode form, as described in
+    /// [RFC 5891](https://www.rfc-editor.org/rfc/rfc5891).
+    ///
+    /// If the name contains no A-labels or one of them is invalid, this is the same as the regular
+    /// string representation.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::Name;
+    ///
+    /// let name = Name::from_ascii("www.xn--bcher-kva.example").unwrap();
+    /// assert_eq!(name.to_unicode(), "www.bücher.example");
+    /// assert_eq!(Name::from_ascii("example.com").unwrap().to_unicode(), "example.com");
+    /// ```
+    pub fn to_unicode(&self) -> String {
+        let ace = self.to_string();
+        if !self.is_idn() {
+            return ace;
+        }
+        match idna::domain_to_unicode(&ace) {
+            (unicode, Ok(())) => unicode,
+            (_, Err(_)) => ace,
+        }
+    }
+
+    /// Returns a copy of this `Name` in which each IDNA A-label (`xn--...`) is replaced by its
+    /// Unicode form. Invalid A-labels are kept as they are.
+    ///
+    /// This is meant for displaying names, e.g. in records (see
+    /// [`NonOptRecord::to_unicode_names()`](crate::NonOptRecord::to_unicode_names())). The result
+    /// should not be queried for, as nameservers only know the A-labels.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::Name;
+    ///
+    /// let name = Name::from_ascii("www.xn--bcher-kva.example").unwrap();
+    /// let unicode = name.to_unicode_labels();
+    /// assert_eq!(unicode.to_string(), "www.bücher.example");
+    /// assert_eq!(unicode.label_count(), 3);
+    /// ```
+    pub fn to_unicode_labels(&self) -> Self {
+        let labels = self
+            .labels
+            .iter()
+            .map(|label| {
+                if !label
+                    .get(..4)
+                    .is_some_and(|p| p.eq_ignore_ascii_case("xn--"))
+                {
+                    return label.clone();
+                }
+                match idna::domain_to_unicode(label) {
+                    (unicode, Ok(())) => unicode.into(),
+                    (_, Err(_)) => label.clone(),
+                }
+            })
+            .collect();
+        Self { labels }
+    }
+
     /// Returns the length of the string returned if this `Name` is made into a [`String`].
     ///
     /// # Examples
@@ -508,7 +804,7 @@ impl Name {
     /// assert_eq!(Name::from_ascii("example.com").unwrap().is_wildcard(), false);
     /// ```
     pub fn is_wildcard(&self) -> bool {
-        if let Some(label) = self.labels.get(0) {
+        if let Some(label) = self.labels.first() {
             label == "*"
         } else {
             false
@@ -546,6 +842,31 @@ impl PartialEq for Name {
     }
 }
 
+impl Hash for Name {
+    /// Hashes the name consistently with its [`PartialEq`] impl, i.e. ignoring the case of ASCII
+    /// letters.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use std::collections::HashSet;
+    /// use toluol_proto::Name;
+    ///
+    /// let mut names = HashSet::new();
+    /// names.insert(Name::from_ascii("Example.COM").unwrap());
+    /// assert!(names.contains(&Name::from_ascii("example.com").unwrap()));
+    /// ```
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.labels.len());
+        for label in &self.labels {
+            // the length prefix keeps e.g. "ab.c" and "a.bc" apart
+            state.write_usize(label.len());
+            for b in label.bytes() {
+                state.write_u8(b.to_ascii_lowercase());
+            }
+        }
+    }
+}
+
 impl PartialOrd for Name {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
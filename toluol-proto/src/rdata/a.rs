@@ -7,6 +7,7 @@ use std::net::Ipv4Addr;
 use byteorder::ReadBytesExt;
 
 use crate::error::{EncodeError, ParseError};
+use crate::reader::MessageReader;
 
 use super::{Rdata, RdataTrait};
 
@@ -23,11 +24,14 @@ pub struct A {
     pub address: Ipv4Addr,
 }
 
+impl From<Ipv4Addr> for A {
+    fn from(address: Ipv4Addr) -> Self {
+        Self { address }
+    }
+}
+
 impl RdataTrait for A {
-    fn parse_rdata(
-        rdata: &mut std::io::Cursor<&[u8]>,
-        _rdlength: u16,
-    ) -> Result<Rdata, ParseError> {
+    fn parse_rdata(rdata: &mut MessageReader) -> Result<Rdata, ParseError> {
         let address = Ipv4Addr::new(
             rdata.read_u8()?,
             rdata.read_u8()?,
@@ -7,6 +7,7 @@ use std::net::Ipv6Addr;
 use byteorder::{NetworkEndian, ReadBytesExt};
 
 use crate::error::{EncodeError, ParseError};
+use crate::reader::MessageReader;
 
 use super::{Rdata, RdataTrait};
 
@@ -24,11 +25,14 @@ pub struct AAAA {
     pub address: Ipv6Addr,
 }
 
+impl From<Ipv6Addr> for AAAA {
+    fn from(address: Ipv6Addr) -> Self {
+        Self { address }
+    }
+}
+
 impl RdataTrait for AAAA {
-    fn parse_rdata(
-        rdata: &mut std::io::Cursor<&[u8]>,
-        _rdlength: u16,
-    ) -> Result<Rdata, ParseError> {
+    fn parse_rdata(rdata: &mut MessageReader) -> Result<Rdata, ParseError> {
         let address = Ipv6Addr::new(
             rdata.read_u16::<NetworkEndian>()?,
             rdata.read_u16::<NetworkEndian>()?,
@@ -1,13 +1,14 @@
 //! `CAA` RDATA definition.
 
 use std::fmt::Display;
-use std::io::{Read, Write};
+use std::io::Write;
 
 use byteorder::{ReadBytesExt, WriteBytesExt};
 use url::Url;
 
 use crate::error::{EncodeError, ParseError};
 use crate::name::Name;
+use crate::reader::MessageReader;
 
 use super::{encode_string_into, Rdata, RdataTrait};
 
@@ -92,19 +93,24 @@ impl Display for Property {
         match self {
             Self::Iodef => write!(f, "iodef"),
             Self::Issue => write!(f, "issue"),
-            Self::IssueWild => write!(f, "iodef"),
+            Self::IssueWild => write!(f, "issuewild"),
             Self::Unknown(unknown) => write!(f, "{}", unknown),
         }
     }
 }
 
 impl Value {
-    /// Encodes the `Value` into the given `buf`, preceded by the value length. No spaces are
-    /// written to `buf`.
+    /// Encodes the `Value` into the given `buf`. Unlike the tag, the value is not preceded by its
+    /// length, as it extends to the end of the RDATA.
     ///
     /// Returns the number of bytes written on success.
     pub(crate) fn encode_into(&self, buf: &mut impl Write) -> Result<u16, EncodeError> {
-        encode_string_into(self.to_string(), buf)
+        let value = self.to_string();
+        if !value.is_ascii() {
+            return Err(EncodeError::NonAsciiString(value));
+        }
+        buf.write_all(value.as_bytes())?;
+        Ok(value.len() as u16)
     }
 }
 
@@ -194,23 +200,18 @@ impl CAA {
 }
 
 impl RdataTrait for CAA {
-    fn parse_rdata(rdata: &mut std::io::Cursor<&[u8]>, rdlength: u16) -> Result<Rdata, ParseError> {
+    fn parse_rdata(rdata: &mut MessageReader) -> Result<Rdata, ParseError> {
         let flags = rdata.read_u8()?;
         let issuer_critical = (flags & (1 << 7)) != 0;
         let tag_length = rdata.read_u8()?;
-        let mut tag = vec![0; tag_length as usize];
-        rdata.read_exact(&mut tag)?;
-        // we already read: u8 (1) + u8 (1) + tag_length = 2 + tag_length bytes
-        let bytes_read = 2 + tag_length;
-        let value_length = rdlength - bytes_read as u16;
-        let mut value = vec![0; value_length as usize];
-        rdata.read_exact(&mut value)?;
+        let tag = rdata.read_bytes(tag_length as usize)?;
+        let value = rdata.read_remaining();
 
-        let tag = String::from_utf8_lossy(&tag);
+        let tag = String::from_utf8_lossy(tag);
         if !tag.is_ascii() {
             return Err(ParseError::NonAsciiCaa(tag.into_owned()));
         }
-        let value_cow = String::from_utf8_lossy(&value);
+        let value_cow = String::from_utf8_lossy(value);
         let tag = Property::from(&*tag);
         let caa = match &tag {
             Property::Unknown(_) => Self {
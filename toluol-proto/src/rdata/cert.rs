@@ -1,13 +1,14 @@
 //! `CERT` RDATA definition.
 
 use std::fmt::Display;
-use std::io::{Read, Write};
+use std::io::Write;
 
 use byteorder::{NetworkEndian, ReadBytesExt, WriteBytesExt};
 use data_encoding::BASE64;
 use repr_with_fallback::repr_with_fallback;
 
 use crate::error::{EncodeError, ParseError};
+use crate::reader::MessageReader;
 
 use super::dnskey::Algorithm;
 use super::{Rdata, RdataTrait};
@@ -64,17 +65,16 @@ pub struct CERT {
     /// The used cryptographic algorithm.
     pub algorithm: Algorithm,
     /// The binary certificate data.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::serialize::base64"))]
     pub data: Vec<u8>,
 }
 
 impl RdataTrait for CERT {
-    fn parse_rdata(rdata: &mut std::io::Cursor<&[u8]>, rdlength: u16) -> Result<Rdata, ParseError> {
+    fn parse_rdata(rdata: &mut MessageReader) -> Result<Rdata, ParseError> {
         let ctype: CertificateType = rdata.read_u16::<NetworkEndian>()?.into();
         let key_tag = rdata.read_u16::<NetworkEndian>()?;
         let algorithm: Algorithm = rdata.read_u8()?.into();
-        // we already read: u16 (2) + u16 (2) + u8 (1) = 5 bytes
-        let mut data = vec![0; (rdlength - 5) as usize];
-        rdata.read_exact(&mut data)?;
+        let data = rdata.read_remaining().to_vec();
 
         Ok(Rdata::CERT(Self {
             ctype,
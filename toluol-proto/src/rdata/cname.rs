@@ -5,6 +5,8 @@ use std::io::Write;
 
 use crate::error::{EncodeError, ParseError};
 use crate::name::{Compression, Name};
+use crate::reader::MessageReader;
+use crate::writer::MessageWriter;
 
 use super::{Rdata, RdataTrait};
 
@@ -21,11 +23,19 @@ pub struct CNAME {
     pub cname: Name,
 }
 
+impl CNAME {
+    /// Creates a new `CNAME` record pointing to the given canonical name.
+    ///
+    /// Returns an error if `cname` is not a valid domain name (see [`Name::from_ascii()`]).
+    pub fn new(cname: impl AsRef<str>) -> Result<Self, ParseError> {
+        Ok(Self {
+            cname: Name::from_ascii(cname)?,
+        })
+    }
+}
+
 impl RdataTrait for CNAME {
-    fn parse_rdata(
-        rdata: &mut std::io::Cursor<&[u8]>,
-        _rdlength: u16,
-    ) -> Result<Rdata, ParseError> {
+    fn parse_rdata(rdata: &mut MessageReader) -> Result<Rdata, ParseError> {
         Ok(Rdata::CNAME(Self {
             cname: Name::parse(rdata, Compression::Allowed)?,
         }))
@@ -35,9 +45,17 @@ impl RdataTrait for CNAME {
         self.cname.encode_into(buf)
     }
 
+    fn write_rdata(&self, writer: &mut MessageWriter) -> Result<(), EncodeError> {
+        writer.write_name(&self.cname, Compression::Allowed)
+    }
+
     fn canonicalize(&mut self) {
         self.cname.canonicalize();
     }
+
+    fn names_mut(&mut self) -> Vec<&mut Name> {
+        vec![&mut self.cname]
+    }
 }
 
 impl Display for CNAME {
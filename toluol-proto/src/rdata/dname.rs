@@ -5,6 +5,7 @@ use std::io::Write;
 
 use crate::error::{EncodeError, ParseError};
 use crate::name::{Compression, Name};
+use crate::reader::MessageReader;
 
 use super::{Rdata, RdataTrait};
 
@@ -26,10 +27,7 @@ pub struct DNAME {
 }
 
 impl RdataTrait for DNAME {
-    fn parse_rdata(
-        rdata: &mut std::io::Cursor<&[u8]>,
-        _rdlength: u16,
-    ) -> Result<Rdata, ParseError> {
+    fn parse_rdata(rdata: &mut MessageReader) -> Result<Rdata, ParseError> {
         Ok(Rdata::DNAME(Self {
             target: Name::parse(rdata, Compression::Allowed)?,
         }))
@@ -42,6 +40,10 @@ impl RdataTrait for DNAME {
     fn canonicalize(&mut self) {
         self.target.canonicalize();
     }
+
+    fn names_mut(&mut self) -> Vec<&mut Name> {
+        vec![&mut self.target]
+    }
 }
 
 impl Display for DNAME {
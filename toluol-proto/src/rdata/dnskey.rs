@@ -1,65 +1,26 @@
 //! `DNSKEY` RDATA definition.
 
-use std::{
-    fmt::Display,
-    io::{Read, Write},
-};
+use std::{fmt::Display, io::Write};
 
 use byteorder::{NetworkEndian, ReadBytesExt, WriteBytesExt};
 use data_encoding::BASE64;
 use ecdsa::signature::Verifier;
 use p256::ecdsa::{Signature, VerifyingKey};
 use repr_with_fallback::repr_with_fallback;
+use sha2::{Digest, Sha256, Sha384};
 
 use crate::error::{DnssecError, EncodeError, ParseError};
+use crate::reader::MessageReader;
+use crate::Name;
 
+use super::ds::{DigestType, DS};
 use super::{Rdata, RdataTrait};
 
 #[cfg(feature = "serde")]
 use serde::Serialize;
 
-repr_with_fallback! {
-    /// Algorithms for use in zone signing (see [`DNSKEY`]) and storing certificates in the DNS (see
-    /// [`CERT`](super::cert::CERT)).
-    ///
-    /// See <https://www.iana.org/assignments/dns-sec-alg-numbers/dns-sec-alg-numbers.xhtml> for the
-    /// official list.
-    #[cfg_attr(feature = "serde", derive(Serialize))]
-    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
-    #[non_exhaustive]
-    #[allow(non_camel_case_types)]
-    pub enum Algorithm {
-        /// DON'T USE THIS! DSA is not considered secure anymore, and this is only provided for
-        /// compatibility.
-        DSA = 3,
-        /// DON'T USE THIS! SHA1 is not considered secure anymore, and this is only provided for
-        /// compatibility.
-        RSASHA1 = 5,
-        /// DON'T USE THIS! DSA and SHA1 are not considered secure anymore, and this is only provided
-        /// for compatibility.
-        DSA_NSEC3_SHA1 = 6,
-        /// DON'T USE THIS! SHA1 is not considered secure anymore, and this is only provided for
-        /// compatibility.
-        RSASHA1_NSEC3_SHA1 = 7,
-        /// RSA with SHA256 [\[RFC 5702\]](https://www.rfc-editor.org/rfc/rfc5702)
-        RSASHA256 = 8,
-        /// RSA with SHA512 [\[RFC 5702\]](https://www.rfc-editor.org/rfc/rfc5702)
-        RSASHA512 = 10,
-        /// GOST R 34.10-2001 [\[RFC 5933\]](https://www.rfc-editor.org/rfc/rfc5933)
-        ECC_GOST = 12,
-        /// ECDSA Curve P-256 with SHA-256 [\[RFC 6605\]](https://www.rfc-editor.org/rfc/rfc6605)
-        ECDSAP256SHA256 = 13,
-        /// ECDSA Curve P-384 with SHA-384 [\[RFC 6605\]](https://www.rfc-editor.org/rfc/rfc6605)
-        ECDSAP384SHA384 = 14,
-        /// Ed25519 [\[RFC 8080\]](https://www.rfc-editor.org/rfc/rfc8080)
-        ED25519 = 15,
-        /// Ed448 [\[RFC 8080\]](https://www.rfc-editor.org/rfc/rfc8080)
-        ED448 = 16,
-        Unassigned(u8), // 0-2, 4, 9, 11, 17-255 (technically, some of these values have been assigned
-                        // to algorithms that cannot be used for zone signing or are Reserved/Private,
-                        // but we treat them as Unassigned)
-    }
-}
+// generated by build.rs from iana/dns-sec-alg-numbers-1.csv
+include!(concat!(env!("OUT_DIR"), "/algorithm.rs"));
 
 /// A record containing the public key used to sign record sets of the zone.
 /// [\[RFC 4034\]](https://www.rfc-editor.org/rfc/rfc4034)
@@ -81,10 +42,93 @@ pub struct DNSKEY {
     /// The public key's cryptographic algorithm and determines the format of
     /// [`Self::key`].
     pub algorithm: Algorithm,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::serialize::base64"))]
     pub key: Vec<u8>,
 }
 
+/// The child's copy of a [`DNSKEY`] record, which it publishes to ask the parent to create the
+/// delegation's [`DS`] records from it. [\[RFC 7344\]](https://www.rfc-editor.org/rfc/rfc7344)
+///
+/// A `CDNSKEY` record set consisting of [`DNSKEY::delete()`] only asks the parent to delete all
+/// [`DS`] records. [\[RFC 8078\]](https://www.rfc-editor.org/rfc/rfc8078)
+pub type CDNSKEY = DNSKEY;
+
 impl DNSKEY {
+    /// Returns the record that, published as the only [`CDNSKEY`] record, asks the parent to
+    /// delete the delegation's [`DS`] records (`0 3 0 AA==`, see
+    /// [RFC 8078, Section 4](https://www.rfc-editor.org/rfc/rfc8078#section-4)).
+    pub fn delete() -> Self {
+        Self {
+            zone: false,
+            revoked: false,
+            secure_entry_point: false,
+            algorithm: Algorithm::DELETE,
+            key: vec![0],
+        }
+    }
+
+    /// Returns whether this is the record returned by [`DNSKEY::delete()`].
+    pub fn is_delete(&self) -> bool {
+        *self == Self::delete()
+    }
+
+    /// Creates the [`DS`] record referring to this key, which is owned by `owner`, with a digest
+    /// of type `digest_type`.
+    ///
+    /// Returns an error if `digest_type` is not [`DigestType::SHA256`] or
+    /// [`DigestType::SHA384`]; `SHA1` must not be used for new `DS` records anymore
+    /// [\[RFC 8624\]](https://www.rfc-editor.org/rfc/rfc8624), and `GOST` is deprecated.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use data_encoding::{BASE64, HEXUPPER};
+    /// use toluol_proto::rdata::dnskey::{Algorithm, DNSKEY};
+    /// use toluol_proto::rdata::ds::DigestType;
+    /// use toluol_proto::Name;
+    ///
+    /// // the example from RFC 4509, Section 2.3
+    /// let dnskey = DNSKEY {
+    ///     zone: true,
+    ///     revoked: false,
+    ///     secure_entry_point: false,
+    ///     algorithm: Algorithm::RSASHA1,
+    ///     key: BASE64.decode(concat!(
+    ///         "AQOeiiR0GOMYkDshWoSKz9XzfwJr1AYtsmx3TGkJaNXVbfi/2pHm822aJ5iI9BMzNXxeYCmZ",
+    ///         "DRD99WYwYqUSdjMmmAphXdvxegXd/M5+X7OrzKBaMbCVdFLUUh6DhweJBjEVv5f2wwjM9Xzc",
+    ///         "nOf+EPbtG9DMBmADjFDc2w/rljwvFw==",
+    ///     ).as_bytes()).unwrap(),
+    /// };
+    /// let owner = Name::from_ascii("dskey.example.com").unwrap();
+    ///
+    /// let ds = dnskey.to_ds(&owner, DigestType::SHA256).unwrap();
+    /// assert_eq!(ds.key_tag, 60485);
+    /// assert_eq!(
+    ///     HEXUPPER.encode(&ds.digest),
+    ///     "D4B7D520E7BB5F0F67674A0CCEB1E3E0614B93C4F9E99B8383F6A1E4469DA50A",
+    /// );
+    /// assert!(ds.matches(&owner, &dnskey).unwrap());
+    /// ```
+    pub fn to_ds(&self, owner: &Name, digest_type: DigestType) -> Result<DS, DnssecError> {
+        // the digest is calculated over the canonical owner name and the RDATA
+        let mut owner = owner.clone();
+        owner.canonicalize();
+        let mut data = Vec::new();
+        owner.encode_into(&mut data)?;
+        self.encode_rdata_into(&mut data)?;
+
+        let digest = match digest_type {
+            DigestType::SHA256 => Sha256::digest(&data).to_vec(),
+            DigestType::SHA384 => Sha384::digest(&data).to_vec(),
+            _ => return Err(DnssecError::UnsupportedDigestType),
+        };
+        Ok(DS {
+            key_tag: self.key_tag(),
+            algorithm: self.algorithm,
+            digest_type,
+            digest,
+        })
+    }
+
     /// Calculates this key's key tag. Key tags are used in [`RRSIG`](super::RRSIG) and
     /// [`DS`](super::DS) records to "help select the corresponding `DNSKEY` record efficiently when
     /// more than one candidate `DNSKEY` record is available."
@@ -110,6 +154,62 @@ impl DNSKEY {
         (ac & 0xFFFF) as u16
     }
 
+    /// Returns the SHA-256 digest of the RDATA, which, unlike the [key tag](Self::key_tag()),
+    /// identifies the key: keys with the same key tag have different digests.
+    pub fn key_digest(&self) -> [u8; 32] {
+        let mut rdata = Vec::with_capacity(self.key.len() + 4);
+        self.encode_rdata_into(&mut rdata)
+            .expect("encoding DNSKEY into vector failed");
+        Sha256::digest(&rdata).into()
+    }
+
+    /// Returns the size of the modulus in bits if this is an RSA key, or [`None`] if it is not or
+    /// the key data is malformed (see [RFC 3110, Section 2](https://www.rfc-editor.org/rfc/rfc3110#section-2)).
+    ///
+    /// # Examples
+    /// ```rust
+    /// use data_encoding::BASE64;
+    /// use toluol_proto::rdata::dnskey::{Algorithm, DNSKEY};
+    ///
+    /// let mut dnskey = DNSKEY {
+    ///     zone: true,
+    ///     revoked: false,
+    ///     secure_entry_point: false,
+    ///     algorithm: Algorithm::RSASHA1,
+    ///     key: BASE64.decode(concat!(
+    ///         "AQOeiiR0GOMYkDshWoSKz9XzfwJr1AYtsmx3TGkJaNXVbfi/2pHm822aJ5iI9BMzNXxeYCmZ",
+    ///         "DRD99WYwYqUSdjMmmAphXdvxegXd/M5+X7OrzKBaMbCVdFLUUh6DhweJBjEVv5f2wwjM9Xzc",
+    ///         "nOf+EPbtG9DMBmADjFDc2w/rljwvFw==",
+    ///     ).as_bytes()).unwrap(),
+    /// };
+    /// assert_eq!(dnskey.rsa_key_bits(), Some(1024));
+    /// dnskey.algorithm = Algorithm::ED25519;
+    /// assert_eq!(dnskey.rsa_key_bits(), None);
+    /// ```
+    pub fn rsa_key_bits(&self) -> Option<usize> {
+        if !matches!(
+            self.algorithm,
+            Algorithm::RSASHA1
+                | Algorithm::RSASHA1_NSEC3_SHA1
+                | Algorithm::RSASHA256
+                | Algorithm::RSASHA512
+        ) {
+            return None;
+        }
+        // the exponent length is one byte, or, if that is zero, the following two bytes
+        let (exponent_len, offset) = match *self.key.first()? {
+            0 => (
+                u16::from_be_bytes([*self.key.get(1)?, *self.key.get(2)?]) as usize,
+                3,
+            ),
+            len => (len as usize, 1),
+        };
+        let modulus = self.key.get(offset + exponent_len..)?;
+        let first = modulus.iter().position(|&byte| byte != 0)?;
+        let leading_zeros = modulus[first].leading_zeros() as usize;
+        Some((modulus.len() - first) * 8 - leading_zeros)
+    }
+
     /// Validates the given signature of the specified data using the public key stored in this
     /// `DNSKEY`.
     ///
@@ -158,10 +258,8 @@ impl DNSKEY {
         let secure_entry_point = if self.secure_entry_point { 1 } else { 0 };
         zone | revoked | secure_entry_point
     }
-}
 
-impl RdataTrait for DNSKEY {
-    fn parse_rdata(rdata: &mut std::io::Cursor<&[u8]>, rdlength: u16) -> Result<Rdata, ParseError> {
+    pub(crate) fn parse(rdata: &mut MessageReader) -> Result<Self, ParseError> {
         let flags = rdata.read_u16::<NetworkEndian>()?;
         let zone = (flags & (1 << 8)) != 0;
         let revoked = (flags & (1 << 7)) != 0;
@@ -174,17 +272,21 @@ impl RdataTrait for DNSKEY {
 
         let algorithm: Algorithm = rdata.read_u8()?.into();
 
-        // we already read: u16 (2) + u8 (1) + u8 (1) = 4 bytes
-        let mut key = vec![0; (rdlength - 4) as usize];
-        rdata.read_exact(&mut key)?;
+        let key = rdata.read_remaining().to_vec();
 
-        Ok(Rdata::DNSKEY(Self {
+        Ok(Self {
             zone,
             revoked,
             secure_entry_point,
             algorithm,
             key,
-        }))
+        })
+    }
+}
+
+impl RdataTrait for DNSKEY {
+    fn parse_rdata(rdata: &mut MessageReader) -> Result<Rdata, ParseError> {
+        Self::parse(rdata).map(Rdata::DNSKEY)
     }
 
     fn encode_rdata_into(&self, buf: &mut impl Write) -> Result<u16, EncodeError> {
@@ -1,42 +1,25 @@
 //! `DS` RDATA definition.
 
 use std::fmt::Display;
-use std::io::{Read, Write};
+use std::io::Write;
 
 use byteorder::{NetworkEndian, ReadBytesExt, WriteBytesExt};
 use data_encoding::HEXUPPER;
 use repr_with_fallback::repr_with_fallback;
 
-use crate::error::{EncodeError, ParseError};
+use crate::error::{DnssecError, EncodeError, ParseError};
+use crate::reader::MessageReader;
+use crate::Name;
 
 use super::{Rdata, RdataTrait};
 
 #[cfg(feature = "serde")]
 use serde::Serialize;
 
-// import DNSKEY for easier rustdoc links
-#[allow(unused_imports)]
 use super::dnskey::{Algorithm, DNSKEY};
 
-repr_with_fallback! {
-    /// Digest algorithms for the [`DS`] record.
-    ///
-    /// See <https://www.iana.org/assignments/ds-rr-types/ds-rr-types.xhtml> for the official list.
-    #[cfg_attr(feature = "serde", derive(Serialize))]
-    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
-    #[non_exhaustive]
-    pub enum DigestType {
-        /// [RFC 3658](https://www.rfc-editor.org/rfc/rfc3658)
-        SHA1 = 1,
-        /// [RFC 4509](https://www.rfc-editor.org/rfc/rfc4509)
-        SHA256 = 2,
-        /// GOST R 34.11-94 [\[RFC 5933\]](https://www.rfc-editor.org/rfc/rfc5933)
-        GOST = 3,
-        /// [RFC 6605](https://www.rfc-editor.org/rfc/rfc6605)
-        SHA384 = 4,
-        Unassigned(u8), // 0, 5-255 (technically, 0 is Reserved, but we treat it as Unassigned)
-    }
-}
+// generated by build.rs from iana/ds-rr-types-1.csv
+include!(concat!(env!("OUT_DIR"), "/digest_type.rs"));
 
 /// A record referring to a [`DNSKEY`] record by storing the key tag, algorithm number, and a digest
 /// of the [`DNSKEY`] record. [\[RFC 4034\]](https://www.rfc-editor.org/rfc/rfc4034)
@@ -59,24 +42,64 @@ pub struct DS {
     /// The type of digest stored in [`Self::digest`].
     pub digest_type: DigestType,
     /// The digest of the corresponding [`DNSKEY`] record.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::serialize::base64"))]
     pub digest: Vec<u8>,
 }
 
-impl RdataTrait for DS {
-    fn parse_rdata(rdata: &mut std::io::Cursor<&[u8]>, rdlength: u16) -> Result<Rdata, ParseError> {
+/// The child's copy of a [`DS`] record, which it publishes to ask the parent to update the
+/// delegation's `DS` records. [\[RFC 7344\]](https://www.rfc-editor.org/rfc/rfc7344)
+///
+/// A `CDS` record set consisting of [`DS::delete()`] only asks the parent to delete all `DS`
+/// records. [\[RFC 8078\]](https://www.rfc-editor.org/rfc/rfc8078)
+pub type CDS = DS;
+
+impl DS {
+    /// Returns the record that, published as the only [`CDS`] record, asks the parent to delete
+    /// the delegation's `DS` records (`0 0 0 00`, see
+    /// [RFC 8078, Section 4](https://www.rfc-editor.org/rfc/rfc8078#section-4)).
+    pub fn delete() -> Self {
+        Self {
+            key_tag: 0,
+            algorithm: Algorithm::DELETE,
+            digest_type: DigestType::Unassigned(0),
+            digest: vec![0],
+        }
+    }
+
+    /// Returns whether this is the record returned by [`DS::delete()`].
+    pub fn is_delete(&self) -> bool {
+        *self == Self::delete()
+    }
+
+    /// Returns whether this record refers to `dnskey`, which is owned by `owner`, i.e. whether the
+    /// key tag, algorithm, and digest match.
+    ///
+    /// Returns an error if the digest type is not supported (see [`DNSKEY::to_ds()`]).
+    pub fn matches(&self, owner: &Name, dnskey: &DNSKEY) -> Result<bool, DnssecError> {
+        if self.key_tag != dnskey.key_tag() || self.algorithm != dnskey.algorithm {
+            return Ok(false);
+        }
+        Ok(dnskey.to_ds(owner, self.digest_type)?.digest == self.digest)
+    }
+
+    pub(crate) fn parse(rdata: &mut MessageReader) -> Result<Self, ParseError> {
         let key_tag = rdata.read_u16::<NetworkEndian>()?;
         let algorithm: Algorithm = rdata.read_u8()?.into();
         let digest_type: DigestType = rdata.read_u8()?.into();
-        // we already read: u16 (2) + u8 (1) + u8 (1) = 4 bytes
-        let mut digest = vec![0; (rdlength - 4) as usize];
-        rdata.read_exact(&mut digest)?;
+        let digest = rdata.read_remaining().to_vec();
 
-        Ok(Rdata::DS(Self {
+        Ok(Self {
             key_tag,
             algorithm,
             digest_type,
             digest,
-        }))
+        })
+    }
+}
+
+impl RdataTrait for DS {
+    fn parse_rdata(rdata: &mut MessageReader) -> Result<Rdata, ParseError> {
+        Self::parse(rdata).map(Rdata::DS)
     }
 
     fn encode_rdata_into(&self, buf: &mut impl Write) -> Result<u16, EncodeError> {
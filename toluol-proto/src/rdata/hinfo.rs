@@ -4,6 +4,7 @@ use std::fmt::Display;
 use std::io::Write;
 
 use crate::error::{EncodeError, ParseError};
+use crate::reader::MessageReader;
 
 use super::{encode_string_into, parse_string, Rdata, RdataTrait};
 
@@ -28,11 +29,18 @@ pub struct HINFO {
     pub os: String,
 }
 
+impl HINFO {
+    /// Creates a new `HINFO` record with the given CPU and operating system types.
+    pub fn new(cpu: impl Into<String>, os: impl Into<String>) -> Self {
+        Self {
+            cpu: cpu.into(),
+            os: os.into(),
+        }
+    }
+}
+
 impl RdataTrait for HINFO {
-    fn parse_rdata(
-        rdata: &mut std::io::Cursor<&[u8]>,
-        _rdlength: u16,
-    ) -> Result<Rdata, ParseError> {
+    fn parse_rdata(rdata: &mut MessageReader) -> Result<Rdata, ParseError> {
         let cpu = parse_string(rdata)?.0;
         let os = parse_string(rdata)?.0;
         Ok(Rdata::HINFO(Self { cpu, os }))
@@ -6,15 +6,18 @@ use std::io::Write;
 use byteorder::{NetworkEndian, ReadBytesExt, WriteBytesExt};
 
 use crate::error::{EncodeError, ParseError};
+use crate::reader::MessageReader;
 
 use super::{Rdata, RdataTrait};
 
 #[cfg(feature = "serde")]
-use serde::Serialize;
+use serde::ser::{Serialize, SerializeStruct, Serializer};
 
 /// A record carrying location information about hosts, networks, and subnets. This is experimental.
 /// [RFC 1876](https://www.rfc-editor.org/rfc/rfc1876)
-#[cfg_attr(feature = "serde", derive(Serialize))]
+///
+/// With the `serde` feature, this is serialized in human-readable units: latitude and longitude in
+/// decimal degrees (negative for south and west), and altitude, size, and precisions in meters.
 #[derive(PartialEq, Eq, Copy, Clone, Debug)]
 pub struct LOC {
     // the wire format also contains a "Version" field, but that must always be 0 (see RFC 1876)
@@ -44,10 +47,7 @@ pub struct LOC {
 }
 
 impl RdataTrait for LOC {
-    fn parse_rdata(
-        rdata: &mut std::io::Cursor<&[u8]>,
-        _rdlength: u16,
-    ) -> Result<Rdata, ParseError> {
+    fn parse_rdata(rdata: &mut MessageReader) -> Result<Rdata, ParseError> {
         let version = rdata.read_u8()?;
         if version != 0 {
             return Err(ParseError::InvalidLocVersion(version));
@@ -82,9 +82,10 @@ impl RdataTrait for LOC {
     }
 }
 
-fn decode_size(size: u8) -> u32 {
-    let decoded = ((size & 0xF0) >> 4) as u32; // base
-    decoded * 10u32.pow((size & 0x0F) as u32) // exponent
+fn decode_size(size: u8) -> u64 {
+    // 9e9 does not fit into a u32
+    let decoded = ((size & 0xF0) >> 4) as u64; // base
+    decoded * 10u64.pow((size & 0x0F) as u32) // exponent
 }
 
 fn decode_lat_long(mut val: u32) -> (u32, u32, u32, u32) {
@@ -100,6 +101,33 @@ fn decode_lat_long(mut val: u32) -> (u32, u32, u32, u32) {
     (deg, min, sec, secfrac)
 }
 
+/// Converts an encoded latitude or longitude to decimal degrees.
+#[cfg(feature = "serde")]
+fn lat_long_degrees(val: u32) -> f64 {
+    (val as i64 - (1i64 << 31)) as f64 / 3_600_000.0
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for LOC {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("LOC", 6)?;
+        state.serialize_field("latitude", &lat_long_degrees(self.latitude))?;
+        state.serialize_field("longitude", &lat_long_degrees(self.longitude))?;
+        state.serialize_field("altitude", &(self.altitude as f64 / 100.0 - 100_000.0))?;
+        // size and precisions are given in centimeters
+        state.serialize_field("size", &(decode_size(self.size) as f64 / 100.0))?;
+        state.serialize_field(
+            "horizontal_precision",
+            &(decode_size(self.horizontal_precision) as f64 / 100.0),
+        )?;
+        state.serialize_field(
+            "vertical_precision",
+            &(decode_size(self.vertical_precision) as f64 / 100.0),
+        )?;
+        state.end()
+    }
+}
+
 impl Display for LOC {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let size = decode_size(self.size);
@@ -137,9 +165,10 @@ impl Display for LOC {
             long_secfrac,
             east_west,
             altitude,
-            size as f64,
-            horizontal_precision as f64,
-            vertical_precision as f64
+            // size and precisions are given in centimeters
+            size as f64 / 100.0,
+            horizontal_precision as f64 / 100.0,
+            vertical_precision as f64 / 100.0
         )
     }
 }
@@ -1,13 +1,16 @@
 //! RDATA type definitions.
 
 use std::fmt::Display;
-use std::io::{Cursor, Read, Write};
+use std::io::Write;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
 use byteorder::ReadBytesExt;
 use data_encoding::HEXUPPER;
 
 use crate::error::{EncodeError, ParseError};
-use crate::RecordType;
+use crate::reader::MessageReader;
+use crate::writer::MessageWriter;
+use crate::{Name, RecordType};
 
 #[cfg(feature = "serde")]
 use serde::Serialize;
@@ -35,6 +38,7 @@ pub mod rrsig;
 pub mod soa;
 pub mod srv;
 pub mod sshfp;
+pub mod svcb;
 pub mod tlsa;
 pub mod txt;
 
@@ -44,8 +48,8 @@ pub use caa::CAA;
 pub use cert::CERT;
 pub use cname::CNAME;
 pub use dname::DNAME;
-pub use dnskey::DNSKEY;
-pub use ds::DS;
+pub use dnskey::{CDNSKEY, DNSKEY};
+pub use ds::{CDS, DS};
 pub use hinfo::HINFO;
 pub use loc::LOC;
 pub use mx::MX;
@@ -61,10 +65,10 @@ pub use rrsig::RRSIG;
 pub use soa::SOA;
 pub use srv::SRV;
 pub use sshfp::SSHFP;
+pub use svcb::{SvcParam, HTTPS, SVCB};
 pub use tlsa::TLSA;
 pub use txt::TXT;
 
-// TODO think about serde representation for nice JSON output
 /// The record data (RDATA) for a [`Record`][super::Record].
 #[cfg_attr(feature = "serde", derive(Serialize))]
 #[derive(PartialEq, Eq, Clone, Debug)]
@@ -94,32 +98,42 @@ pub enum Rdata {
     NSEC3(NSEC3),
     NSEC3PARAM(NSEC3PARAM),
     TLSA(TLSA),
+    CDS(CDS),
+    CDNSKEY(CDNSKEY),
     OPENPGPKEY(OPENPGPKEY),
+    SVCB(SVCB),
+    HTTPS(HTTPS),
     CAA(CAA),
 
     /// Unknown RDATA, containing the raw RDATA bytes.
-    Unknown(Vec<u8>),
+    Unknown(
+        #[cfg_attr(feature = "serde", serde(serialize_with = "crate::serialize::base64"))] Vec<u8>,
+    ),
 }
 
 /// A trait for working with the different RDATA variants.
 pub trait RdataTrait: Sized + Display {
-    /// Parses the RDATA from the encoded bytes, starting at `rdata`'s current position in the
-    /// slice.
+    /// Parses the RDATA from the encoded bytes, starting at `rdata`'s current position.
     ///
-    /// `rdata` is a [`Cursor`] wrapping the complete DNS message that contains the RDATA. It is
-    /// important that the slice contains the complete message, as this is needed for handling DNS
-    /// message compression: it is necessary to be able to jump to a specific byte position in the
-    /// message. This is also the reason for using [`Cursor`], as the [`Seek`][std::io::Seek] impl
-    /// provided by this type makes these jumps easier.
-    ///
-    /// `rdata` is the byte count of the encoded RDATA that will be parsed.
-    fn parse_rdata(rdata: &mut Cursor<&[u8]>, rdlength: u16) -> Result<Rdata, ParseError>;
+    /// `rdata` is a [`MessageReader`] for the RDATA (see [`MessageReader::rdata_reader()`]): the
+    /// RDATA spans its remaining bytes, but it still knows the complete DNS message that contains
+    /// the RDATA, which is needed for handling DNS message compression.
+    fn parse_rdata(rdata: &mut MessageReader) -> Result<Rdata, ParseError>;
 
     /// Encodes the RDATA into the given `buf` and returns the number of written bytes on success.
     ///
     /// If an error is returned, no guarantees for the state of `buf` are given.
     fn encode_rdata_into(&self, buf: &mut impl Write) -> Result<u16, EncodeError>;
 
+    /// Writes the RDATA into a message via `writer`.
+    ///
+    /// In contrast to [`encode_rdata_into()`](Self::encode_rdata_into()), names may be compressed
+    /// where [RFC 3597, Section 4](https://www.rfc-editor.org/rfc/rfc3597#section-4) allows it,
+    /// i.e. for [`NS`], [`CNAME`], [`SOA`], [`PTR`], and [`MX`].
+    fn write_rdata(&self, writer: &mut MessageWriter) -> Result<(), EncodeError> {
+        self.encode_rdata_into(writer).map(|_| ())
+    }
+
     /// Ensures the RDATA is in canonical format, as defined in
     /// [RFC 4034, Section 6.2](https://www.rfc-editor.org/rfc/rfc4034#section-6.2).
     ///
@@ -129,6 +143,12 @@ pub trait RdataTrait: Sized + Display {
     /// [`Name::canonicalize()`](crate::Name::canonicalize)).
     fn canonicalize(&mut self) {}
 
+    /// Returns all [`Name`](crate::Name)s contained within the RDATA, e.g. the target of a
+    /// [`CNAME`] or the exchange of an [`MX`] record.
+    fn names_mut(&mut self) -> Vec<&mut Name> {
+        Vec::new()
+    }
+
     /// Encodes the RDATA and returns the encoded bytes.
     fn encode(&self) -> Result<Vec<u8>, EncodeError> {
         let mut rdata = Vec::new();
@@ -203,7 +223,7 @@ macro_rules! impl_as_rtype {
 /// And this is how the [`Display`] impl for [`Rdata`] is done:
 /// ```ignore
 /// fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-///     match_rdata!(self, rdata, { write!(f, "{}", rdata) }, data, {
+///     match_rdata!(self, rdata, { Display::fmt(rdata, f) }, data, {
 ///         write!(f, "\\# {} {}", data.len(), HEXUPPER.encode(data))
 ///     })
 /// }
@@ -236,7 +256,11 @@ macro_rules! match_rdata {
             Rdata::NSEC3($inner) => $arm,
             Rdata::NSEC3PARAM($inner) => $arm,
             Rdata::TLSA($inner) => $arm,
+            Rdata::CDS($inner) => $arm,
+            Rdata::CDNSKEY($inner) => $arm,
             Rdata::OPENPGPKEY($inner) => $arm,
+            Rdata::SVCB($inner) => $arm,
+            Rdata::HTTPS($inner) => $arm,
             Rdata::CAA($inner) => $arm,
             Rdata::Unknown($inner_unknown) => $unknown_arm,
         }
@@ -249,6 +273,11 @@ impl Rdata {
         match_rdata!(self, rdata, { rdata.canonicalize() }, _rdata, {})
     }
 
+    /// See [`RdataTrait::names_mut()`].
+    pub fn names_mut(&mut self) -> Vec<&mut Name> {
+        match_rdata!(self, rdata, { rdata.names_mut() }, _rdata, { Vec::new() })
+    }
+
     /// See [`RdataTrait::encode()`].
     pub fn encode(&self) -> Result<Vec<u8>, EncodeError> {
         match_rdata!(self, rdata, { rdata.encode() }, unknown_rdata, {
@@ -270,6 +299,14 @@ impl Rdata {
         )
     }
 
+    /// See [`RdataTrait::write_rdata()`].
+    pub fn write(&self, writer: &mut MessageWriter) -> Result<(), EncodeError> {
+        match_rdata!(self, rdata, { rdata.write_rdata(writer) }, unknown_rdata, {
+            writer.write_all(unknown_rdata)?;
+            Ok(())
+        })
+    }
+
     /// Returns the [`RecordType`] that matches this `RDATA`.
     ///
     /// # Note
@@ -301,7 +338,11 @@ impl Rdata {
             Rdata::NSEC3(_) => RecordType::NSEC3,
             Rdata::NSEC3PARAM(_) => RecordType::NSEC3PARAM,
             Rdata::TLSA(_) => RecordType::TLSA,
+            Rdata::CDS(_) => RecordType::CDS,
+            Rdata::CDNSKEY(_) => RecordType::CDNSKEY,
             Rdata::OPENPGPKEY(_) => RecordType::OPENPGPKEY,
+            Rdata::SVCB(_) => RecordType::SVCB,
+            Rdata::HTTPS(_) => RecordType::HTTPS,
             Rdata::CAA(_) => RecordType::CAA,
             Rdata::Unknown(_) => RecordType::Unknown(0),
         }
@@ -331,7 +372,11 @@ impl Rdata {
     impl_as_rtype!(as_nsec3, as_mut_nsec3, NSEC3);
     impl_as_rtype!(as_nsec3param, as_mut_nsec3param, NSEC3PARAM);
     impl_as_rtype!(as_tlsa, as_mut_tlsa, TLSA);
+    impl_as_rtype!(as_cds, as_mut_cds, CDS);
+    impl_as_rtype!(as_cdnskey, as_mut_cdnskey, CDNSKEY);
     impl_as_rtype!(as_openpgpkey, as_mut_openpgpkey, OPENPGPKEY);
+    impl_as_rtype!(as_svcb, as_mut_svcb, SVCB);
+    impl_as_rtype!(as_https, as_mut_https, HTTPS);
     impl_as_rtype!(as_caa, as_mut_caa, CAA);
 }
 
@@ -360,11 +405,35 @@ impl_from_rtype!(NSEC3);
 impl_from_rtype!(NSEC3PARAM);
 impl_from_rtype!(TLSA);
 impl_from_rtype!(OPENPGPKEY);
+impl_from_rtype!(SVCB);
 impl_from_rtype!(CAA);
 
+impl From<Ipv4Addr> for Rdata {
+    fn from(address: Ipv4Addr) -> Self {
+        Self::A(address.into())
+    }
+}
+
+impl From<Ipv6Addr> for Rdata {
+    fn from(address: Ipv6Addr) -> Self {
+        Self::AAAA(address.into())
+    }
+}
+
+impl From<IpAddr> for Rdata {
+    /// Creates `A` RDATA for IPv4 addresses and `AAAA` RDATA for IPv6 addresses.
+    fn from(address: IpAddr) -> Self {
+        match address {
+            IpAddr::V4(address) => address.into(),
+            IpAddr::V6(address) => address.into(),
+        }
+    }
+}
+
 impl Display for Rdata {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match_rdata!(self, rdata, { write!(f, "{}", rdata) }, data, {
+        // forward the formatter, so flags like `{:#}` reach the inner RDATA
+        match_rdata!(self, rdata, { Display::fmt(rdata, f) }, data, {
             write!(f, "\\# {} {}", data.len(), HEXUPPER.encode(data))
         })
     }
@@ -375,14 +444,13 @@ impl Display for Rdata {
 ///
 /// Returns the parsed string and the number of bytes read.
 ///
-/// Returns an error if reading from the [`Cursor`] fails (i.e. unexpected EOF) or the read string
-/// was not all ASCII.
-pub fn parse_string(msg: &mut Cursor<&[u8]>) -> Result<(String, usize), ParseError> {
+/// Returns an error if reading from the [`MessageReader`] fails (i.e. unexpected EOF) or the read
+/// string was not all ASCII.
+pub fn parse_string(msg: &mut MessageReader) -> Result<(String, usize), ParseError> {
     let length = msg.read_u8()?;
-    let mut string = vec![0; length as usize];
-    msg.read_exact(&mut string)?;
+    let string = msg.read_bytes(length as usize)?;
 
-    let string = String::from_utf8_lossy(&string).into_owned();
+    let string = String::from_utf8_lossy(string).into_owned();
     if !string.is_ascii() {
         return Err(ParseError::NonAsciiString(string));
     }
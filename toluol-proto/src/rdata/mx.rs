@@ -7,6 +7,8 @@ use byteorder::{NetworkEndian, ReadBytesExt, WriteBytesExt};
 
 use crate::error::{EncodeError, ParseError};
 use crate::name::{Compression, Name};
+use crate::reader::MessageReader;
+use crate::writer::MessageWriter;
 
 use super::{Rdata, RdataTrait};
 
@@ -28,11 +30,20 @@ pub struct MX {
     pub exchange: Name,
 }
 
+impl MX {
+    /// Creates a new `MX` record with the given preference and mail exchange.
+    ///
+    /// Returns an error if `exchange` is not a valid domain name (see [`Name::from_ascii()`]).
+    pub fn new(preference: i16, exchange: impl AsRef<str>) -> Result<Self, ParseError> {
+        Ok(Self {
+            preference,
+            exchange: Name::from_ascii(exchange)?,
+        })
+    }
+}
+
 impl RdataTrait for MX {
-    fn parse_rdata(
-        rdata: &mut std::io::Cursor<&[u8]>,
-        _rdlength: u16,
-    ) -> Result<Rdata, ParseError> {
+    fn parse_rdata(rdata: &mut MessageReader) -> Result<Rdata, ParseError> {
         let preference = rdata.read_i16::<NetworkEndian>()?;
         let exchange = Name::parse(rdata, Compression::Allowed)?;
         Ok(Rdata::MX(Self {
@@ -48,9 +59,18 @@ impl RdataTrait for MX {
             .map(|bytes_written| bytes_written + 2)
     }
 
+    fn write_rdata(&self, writer: &mut MessageWriter) -> Result<(), EncodeError> {
+        writer.write_i16::<NetworkEndian>(self.preference)?;
+        writer.write_name(&self.exchange, Compression::Allowed)
+    }
+
     fn canonicalize(&mut self) {
         self.exchange.canonicalize();
     }
+
+    fn names_mut(&mut self) -> Vec<&mut Name> {
+        vec![&mut self.exchange]
+    }
 }
 
 impl Display for MX {
@@ -7,6 +7,7 @@ use byteorder::{NetworkEndian, ReadBytesExt, WriteBytesExt};
 
 use crate::error::{EncodeError, ParseError};
 use crate::name::{Compression, Name};
+use crate::reader::MessageReader;
 
 use super::{encode_string_into, parse_string, Rdata, RdataTrait};
 
@@ -46,10 +47,7 @@ pub struct NAPTR {
 }
 
 impl RdataTrait for NAPTR {
-    fn parse_rdata(
-        rdata: &mut std::io::Cursor<&[u8]>,
-        _rdlength: u16,
-    ) -> Result<Rdata, ParseError> {
+    fn parse_rdata(rdata: &mut MessageReader) -> Result<Rdata, ParseError> {
         let order = rdata.read_u16::<NetworkEndian>()?;
         let preference = rdata.read_u16::<NetworkEndian>()?;
         let flags = parse_string(rdata)?.0;
@@ -81,6 +79,10 @@ impl RdataTrait for NAPTR {
     fn canonicalize(&mut self) {
         self.replacement.canonicalize();
     }
+
+    fn names_mut(&mut self) -> Vec<&mut Name> {
+        vec![&mut self.replacement]
+    }
 }
 
 impl Display for NAPTR {
@@ -5,6 +5,8 @@ use std::io::Write;
 
 use crate::error::{EncodeError, ParseError};
 use crate::name::{Compression, Name};
+use crate::reader::MessageReader;
+use crate::writer::MessageWriter;
 
 use super::{Rdata, RdataTrait};
 
@@ -21,11 +23,19 @@ pub struct NS {
     pub name: Name,
 }
 
+impl NS {
+    /// Creates a new `NS` record pointing to the given name server.
+    ///
+    /// Returns an error if `name` is not a valid domain name (see [`Name::from_ascii()`]).
+    pub fn new(name: impl AsRef<str>) -> Result<Self, ParseError> {
+        Ok(Self {
+            name: Name::from_ascii(name)?,
+        })
+    }
+}
+
 impl RdataTrait for NS {
-    fn parse_rdata(
-        rdata: &mut std::io::Cursor<&[u8]>,
-        _rdlength: u16,
-    ) -> Result<Rdata, ParseError> {
+    fn parse_rdata(rdata: &mut MessageReader) -> Result<Rdata, ParseError> {
         Ok(Rdata::NS(Self {
             name: Name::parse(rdata, Compression::Allowed)?,
         }))
@@ -35,9 +45,17 @@ impl RdataTrait for NS {
         self.name.encode_into(buf)
     }
 
+    fn write_rdata(&self, writer: &mut MessageWriter) -> Result<(), EncodeError> {
+        writer.write_name(&self.name, Compression::Allowed)
+    }
+
     fn canonicalize(&mut self) {
         self.name.canonicalize();
     }
+
+    fn names_mut(&mut self) -> Vec<&mut Name> {
+        vec![&mut self.name]
+    }
 }
 
 impl Display for NS {
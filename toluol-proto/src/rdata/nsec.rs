@@ -2,12 +2,13 @@
 
 use std::collections::BTreeMap;
 use std::fmt::Display;
-use std::io::{Cursor, Write};
+use std::io::Write;
 
 use byteorder::{ReadBytesExt, WriteBytesExt};
 
 use crate::error::{EncodeError, ParseError};
 use crate::name::{Compression, Name};
+use crate::reader::MessageReader;
 use crate::RecordType;
 
 use super::{Rdata, RdataTrait};
@@ -46,20 +47,13 @@ pub struct NSEC {
 }
 
 impl NSEC {
-    /// Parses the type bitmap in the RDATA section of an NSEC or NSEC3 record.
-    ///
-    /// `bytes_read` is the count of the bytes already read from the rdata. `rdlength` is the total
-    /// length of the rdata.
+    /// Parses the type bitmap in the RDATA section of an NSEC or NSEC3 record, which spans the rest
+    /// of the RDATA.
     ///
     /// Returns an error if reading from `msg` fails.
-    pub fn parse_type_bitmap(
-        msg: &mut Cursor<&[u8]>,
-        bytes_read: u16,
-        rdlength: u16,
-    ) -> Result<Vec<RecordType>, ParseError> {
-        let mut len_read = bytes_read;
+    pub fn parse_type_bitmap(msg: &mut MessageReader) -> Result<Vec<RecordType>, ParseError> {
         let mut available_types = Vec::new();
-        while len_read < rdlength {
+        while msg.remaining_in_rdata() > 0 {
             let window_number = msg.read_u8()?;
             let bitmap_len = msg.read_u8()?;
             for i in 0..bitmap_len {
@@ -71,7 +65,6 @@ impl NSEC {
                     }
                 }
             }
-            len_read += (2 + bitmap_len) as u16;
         }
         Ok(available_types)
     }
@@ -125,16 +118,9 @@ impl NSEC {
 }
 
 impl RdataTrait for NSEC {
-    fn parse_rdata(rdata: &mut Cursor<&[u8]>, rdlength: u16) -> Result<Rdata, ParseError> {
-        // used to calculate how many bytes were read later on
-        let rdata_pos_before = rdata.position();
-
+    fn parse_rdata(rdata: &mut MessageReader) -> Result<Rdata, ParseError> {
         let next_domain_name = Name::parse(rdata, Compression::Prohibited)?;
-
-        let rdata_pos_after = rdata.position();
-        let bytes_read = (rdata_pos_after - rdata_pos_before) as u16;
-
-        let types = NSEC::parse_type_bitmap(rdata, bytes_read, rdlength)?;
+        let types = NSEC::parse_type_bitmap(rdata)?;
 
         Ok(Rdata::NSEC(Self {
             next_domain_name,
@@ -150,6 +136,10 @@ impl RdataTrait for NSEC {
     fn canonicalize(&mut self) {
         self.next_domain_name.canonicalize();
     }
+
+    fn names_mut(&mut self) -> Vec<&mut Name> {
+        vec![&mut self.next_domain_name]
+    }
 }
 
 impl Display for NSEC {
@@ -8,6 +8,7 @@ use data_encoding::{BASE32_DNSSEC, HEXUPPER};
 use repr_with_fallback::repr_with_fallback;
 
 use crate::error::{EncodeError, ParseError};
+use crate::reader::MessageReader;
 use crate::RecordType;
 
 use super::nsec::NSEC;
@@ -52,6 +53,10 @@ pub struct NSEC3 {
     /// defend against pre-calculated dictionary attacks.
     ///
     /// See [Section 5 of RFC 5155](https://www.rfc-editor.org/rfc/rfc5155#section-5) for details.
+    #[cfg_attr(
+        feature = "serde",
+        serde(serialize_with = "crate::serialize::option_base64")
+    )]
     pub salt: Option<Vec<u8>>,
     /// The next hashed owner name in hash order.
     ///
@@ -62,6 +67,7 @@ pub struct NSEC3 {
     ///
     /// This is not base32 encoded, unlike the owner name of the `NSEC3` record. It is the
     /// unmodified binary hash value. It does not include the name of the containing zone.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::serialize::base64"))]
     pub next_hashed_owner: Vec<u8>,
     /// The record set types that exist at the original owner name of the `NSEC3` record.
     pub types: Vec<RecordType>,
@@ -90,6 +96,10 @@ pub struct NSEC3PARAM {
     /// See [`NSEC3::iterations`].
     pub iterations: u16,
     /// See [`NSEC3::salt`].
+    #[cfg_attr(
+        feature = "serde",
+        serde(serialize_with = "crate::serialize::option_base64")
+    )]
     pub salt: Option<Vec<u8>>,
 }
 
@@ -104,7 +114,7 @@ impl NSEC3 {
 }
 
 impl RdataTrait for NSEC3 {
-    fn parse_rdata(rdata: &mut std::io::Cursor<&[u8]>, rdlength: u16) -> Result<Rdata, ParseError> {
+    fn parse_rdata(rdata: &mut MessageReader) -> Result<Rdata, ParseError> {
         let hash_algorithm: HashAlgorithm = rdata.read_u8()?.into();
         let flags = rdata.read_u8()?;
         let opt_out = (flags & 1) != 0;
@@ -118,11 +128,8 @@ impl RdataTrait for NSEC3 {
             None
         };
         let hash_length = rdata.read_u8()?;
-        let mut next_hashed_owner = vec![0; hash_length as usize];
-        rdata.read_exact(&mut next_hashed_owner)?;
-        // we already read: u8 (1) + u8 (1) + u16 (2) + u8 (1) + salt_length + u8 (1) + hash_length = 6 + salt_length + hash_length bytes
-        let bytes_read = 6 + salt_length as u16 + hash_length as u16;
-        let types = NSEC::parse_type_bitmap(rdata, bytes_read, rdlength)?;
+        let next_hashed_owner = rdata.read_bytes(hash_length as usize)?.to_vec();
+        let types = NSEC::parse_type_bitmap(rdata)?;
 
         Ok(Rdata::NSEC3(Self {
             hash_algorithm,
@@ -187,10 +194,7 @@ impl Display for NSEC3 {
 }
 
 impl RdataTrait for NSEC3PARAM {
-    fn parse_rdata(
-        rdata: &mut std::io::Cursor<&[u8]>,
-        _rdlength: u16,
-    ) -> Result<Rdata, ParseError> {
+    fn parse_rdata(rdata: &mut MessageReader) -> Result<Rdata, ParseError> {
         let hash_algorithm: HashAlgorithm = rdata.read_u8()?.into();
         let flags = rdata.read_u8()?;
         let iterations = rdata.read_u16::<NetworkEndian>()?;
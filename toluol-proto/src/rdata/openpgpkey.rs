@@ -1,9 +1,10 @@
 //! `OPENPGPKEY` RDATA definition.
 
 use std::fmt::Display;
-use std::io::{Read, Write};
+use std::io::Write;
 
 use crate::error::{EncodeError, ParseError};
+use crate::reader::MessageReader;
 use data_encoding::BASE64;
 
 use super::{Rdata, RdataTrait};
@@ -24,13 +25,61 @@ use serde::Serialize;
 pub struct OPENPGPKEY {
     /// A Transferable Public Key formatted as specified in
     /// [RFC 4880](https://www.rfc-editor.org/rfc/rfc4880).
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::serialize::base64"))]
     pub key: Vec<u8>,
 }
 
+impl OPENPGPKEY {
+    /// Returns the key in the ASCII armor format of
+    /// [RFC 4880, Section 6.2](https://www.rfc-editor.org/rfc/rfc4880#section-6.2), as expected
+    /// by e.g. `gpg --import`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::rdata::OPENPGPKEY;
+    ///
+    /// let openpgpkey = OPENPGPKEY { key: b"key".to_vec() };
+    /// assert_eq!(
+    ///     openpgpkey.to_armored(),
+    ///     "-----BEGIN PGP PUBLIC KEY BLOCK-----\n\na2V5\n=6BHJ\n-----END PGP PUBLIC KEY BLOCK-----\n"
+    /// );
+    /// ```
+    pub fn to_armored(&self) -> String {
+        let mut armored = "-----BEGIN PGP PUBLIC KEY BLOCK-----\n\n".to_string();
+        let encoded = BASE64.encode(&self.key);
+        // lines must not be longer than 76 characters; 64 is what GnuPG uses
+        for line in encoded.as_bytes().chunks(64) {
+            armored.push_str(std::str::from_utf8(line).expect("base64 is ASCII"));
+            armored.push('\n');
+        }
+        armored.push('=');
+        armored.push_str(&BASE64.encode(&crc24(&self.key).to_be_bytes()[1..]));
+        armored.push_str("\n-----END PGP PUBLIC KEY BLOCK-----\n");
+        armored
+    }
+}
+
+/// Computes the checksum of the ASCII armor, see
+/// [RFC 4880, Section 6.1](https://www.rfc-editor.org/rfc/rfc4880#section-6.1).
+fn crc24(data: &[u8]) -> u32 {
+    const CRC24_INIT: u32 = 0xB704CE;
+    const CRC24_POLY: u32 = 0x1864CFB;
+    let mut crc = CRC24_INIT;
+    for &byte in data {
+        crc ^= (byte as u32) << 16;
+        for _ in 0..8 {
+            crc <<= 1;
+            if crc & 0x1000000 != 0 {
+                crc ^= CRC24_POLY;
+            }
+        }
+    }
+    crc & 0xFFFFFF
+}
+
 impl RdataTrait for OPENPGPKEY {
-    fn parse_rdata(rdata: &mut std::io::Cursor<&[u8]>, rdlength: u16) -> Result<Rdata, ParseError> {
-        let mut key = vec![0; rdlength as usize];
-        rdata.read_exact(&mut key)?;
+    fn parse_rdata(rdata: &mut MessageReader) -> Result<Rdata, ParseError> {
+        let key = rdata.read_remaining().to_vec();
         Ok(Rdata::OPENPGPKEY(Self { key }))
     }
 
@@ -1,44 +1,36 @@
 //! `OPT` RDATA definition.
 
-use std::collections::HashMap;
 use std::fmt::Display;
-use std::io::{Read, Write};
+use std::io::Write;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
 
 use byteorder::{NetworkEndian, ReadBytesExt, WriteBytesExt};
 use repr_with_fallback::repr_with_fallback;
 
 use crate::error::{EncodeError, ParseError};
+use crate::reader::MessageReader;
 
 use super::{Rdata, RdataTrait};
 
 #[cfg(feature = "serde")]
-use serde::Serialize;
-
-repr_with_fallback! {
-    /// The type of option as per [the IANA assignment](
-    /// https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#dns-parameters-11).
-    #[cfg_attr(feature = "serde", derive(Serialize))]
-    #[derive(PartialEq, Eq, Copy, Clone, Debug, Hash)]
-    #[non_exhaustive]
-    pub enum OptionCode {
-        /// "A lightweight DNS transaction security mechanism that provides limited protection to
-        /// DNS servers and clients against a variety of increasingly common denial-of-service and
-        /// amplification/forgery or cache poisoning attacks by off-path attackers."
-        /// [\[RFC 7873\]](https://www.rfc-editor.org/rfc/rfc7873.html)
-        Cookie = 10,
-        /// "Allows DNS clients and servers to pad request and response messages by a variable
-        /// number of octets." [\[RFC 7830\]](https://www.rfc-editor.org/rfc/rfc7830.html)
-        Padding = 12,
-        Unknown(u16),
-    }
-}
+use crate::serialize::Base64;
+#[cfg(feature = "serde")]
+use serde::{ser::SerializeMap, Serialize, Serializer};
+
+// generated by build.rs from iana/dns-parameters-11.csv
+include!(concat!(env!("OUT_DIR"), "/option_code.rs"));
 
 impl OptionCode {
     fn format_rdata(&self, rdata: &[u8]) -> String {
         match self {
+            OptionCode::ClientSubnet => match ClientSubnet::parse(rdata) {
+                Ok(subnet) => subnet.to_string(),
+                Err(_) => data_encoding::HEXLOWER.encode(rdata),
+            },
             OptionCode::Cookie => data_encoding::HEXLOWER.encode(rdata),
             OptionCode::Padding => "<padding>".into(),
-            OptionCode::Unknown(_) => data_encoding::HEXLOWER.encode(rdata),
+            _ => data_encoding::HEXLOWER.encode(rdata),
         }
     }
 }
@@ -46,9 +38,14 @@ impl OptionCode {
 impl Display for OptionCode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            OptionCode::ClientSubnet => write!(f, "CLIENT-SUBNET"),
             OptionCode::Cookie => write!(f, "COOKIE"),
             OptionCode::Padding => write!(f, "PADDING"),
             OptionCode::Unknown(u) => write!(f, "CODE{u}"),
+            other => {
+                let name = other.iana_name().unwrap_or_default();
+                write!(f, "{}", name.to_uppercase().replace(' ', "-"))
+            }
         }
     }
 }
@@ -59,21 +56,244 @@ impl Display for OptionCode {
 #[cfg_attr(feature = "serde", derive(Serialize))]
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub struct OPT {
-    /// A map of different EDNS options and their respective values.
-    pub options: HashMap<OptionCode, Vec<u8>>,
+    /// The EDNS options and their respective values, in the order they appear in the message.
+    ///
+    /// The same option code may occur more than once, as some options (e.g. EDNS Key Tag) may be
+    /// repeated and the order of options can matter (e.g. padding should be the last option).
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_options"))]
+    pub options: Vec<(OptionCode, Vec<u8>)>,
+}
+
+impl OPT {
+    /// Returns the value of the first option with the given `code`, if there is one.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::rdata::opt::OptionCode;
+    /// use toluol_proto::rdata::OPT;
+    ///
+    /// let opt = OPT {
+    ///     options: vec![(OptionCode::Cookie, vec![1; 8]), (OptionCode::Padding, vec![0; 4])],
+    /// };
+    /// assert_eq!(opt.get(OptionCode::Padding), Some(&[0; 4][..]));
+    /// assert_eq!(opt.get(OptionCode::Nsid), None);
+    /// ```
+    pub fn get(&self, code: OptionCode) -> Option<&[u8]> {
+        self.options
+            .iter()
+            .find(|(option_code, _)| *option_code == code)
+            .map(|(_, value)| value.as_slice())
+    }
+}
+
+/// The value of an EDNS Client Subnet option, i.e. the network (prefix) a query originates from.
+/// [\[RFC 7871\]](https://www.rfc-editor.org/rfc/rfc7871.html)
+///
+/// # Examples
+/// ```rust
+/// use toluol_proto::rdata::opt::ClientSubnet;
+///
+/// let subnet: ClientSubnet = "192.0.2.77/24".parse().unwrap();
+/// assert_eq!(subnet.to_string(), "192.0.2.0/24/0");
+/// // family 1 (IPv4), source prefix length 24, scope prefix length 0, and three address bytes
+/// assert_eq!(subnet.encode(), [0, 1, 24, 0, 192, 0, 2]);
+/// assert_eq!(ClientSubnet::parse(&subnet.encode()).unwrap(), subnet);
+/// ```
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct ClientSubnet {
+    /// The network's address; all bits after [`Self::source_prefix_len`] are zero.
+    pub address: IpAddr,
+    /// The number of leading bits of [`Self::address`] that identify the network in the query.
+    pub source_prefix_len: u8,
+    /// The number of leading bits of [`Self::address`] the answer is valid for. Zero in queries.
+    pub scope_prefix_len: u8,
+}
+
+impl ClientSubnet {
+    /// Creates the option for a query, containing the first `source_prefix_len` bits of
+    /// `address`.
+    ///
+    /// Returns an error if `source_prefix_len` is longer than `address`.
+    pub fn new(address: IpAddr, source_prefix_len: u8) -> Result<Self, ParseError> {
+        let max_len = match address {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        if source_prefix_len > max_len {
+            return Err(ParseError::InvalidClientSubnet(format!(
+                "prefix length must be at most {}, is {}",
+                max_len, source_prefix_len
+            )));
+        }
+        Ok(Self {
+            address: mask(address, source_prefix_len),
+            source_prefix_len,
+            scope_prefix_len: 0,
+        })
+    }
+
+    /// Encodes the option's value. The address is truncated to the bytes covered by the source
+    /// prefix length.
+    pub fn encode(&self) -> Vec<u8> {
+        let (family, octets) = match self.address {
+            IpAddr::V4(address) => (1u16, address.octets().to_vec()),
+            IpAddr::V6(address) => (2u16, address.octets().to_vec()),
+        };
+        let mut value = family.to_be_bytes().to_vec();
+        value.push(self.source_prefix_len);
+        value.push(self.scope_prefix_len);
+        value.extend_from_slice(&octets[..(self.source_prefix_len as usize).div_ceil(8)]);
+        value
+    }
+
+    /// Parses the value of an EDNS Client Subnet option.
+    ///
+    /// Returns an error if the address family is unknown or the address does not match the source
+    /// prefix length.
+    pub fn parse(value: &[u8]) -> Result<Self, ParseError> {
+        let invalid = |reason: &str| ParseError::InvalidClientSubnet(reason.to_string());
+        if value.len() < 4 {
+            return Err(invalid("option is too short"));
+        }
+        let family = u16::from_be_bytes([value[0], value[1]]);
+        let (source_prefix_len, scope_prefix_len) = (value[2], value[3]);
+        let address = &value[4..];
+        if address.len() != (source_prefix_len as usize).div_ceil(8) {
+            return Err(invalid(
+                "address length does not match source prefix length",
+            ));
+        }
+        let address = match family {
+            1 if address.len() <= 4 => {
+                let mut octets = [0; 4];
+                octets[..address.len()].copy_from_slice(address);
+                IpAddr::V4(Ipv4Addr::from(octets))
+            }
+            2 if address.len() <= 16 => {
+                let mut octets = [0; 16];
+                octets[..address.len()].copy_from_slice(address);
+                IpAddr::V6(Ipv6Addr::from(octets))
+            }
+            1 | 2 => return Err(invalid("address is too long")),
+            _ => return Err(invalid("unknown address family")),
+        };
+        Ok(Self {
+            address,
+            source_prefix_len,
+            scope_prefix_len,
+        })
+    }
+}
+
+impl FromStr for ClientSubnet {
+    type Err = ParseError;
+
+    /// Parses a network like `192.0.2.0/24`. Without a prefix length, the complete address is
+    /// used.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ParseError::InvalidClientSubnet(s.to_string());
+        let (address, len) = match s.split_once('/') {
+            Some((address, len)) => (address, Some(len)),
+            None => (s, None),
+        };
+        let address = IpAddr::from_str(address).map_err(|_| invalid())?;
+        let len = match len {
+            Some(len) => len.parse().map_err(|_| invalid())?,
+            None if address.is_ipv4() => 32,
+            None => 128,
+        };
+        Self::new(address, len)
+    }
+}
+
+impl Display for ClientSubnet {
+    /// Formats the option like `dig` does, i.e. as `address/source/scope`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}/{}/{}",
+            self.address, self.source_prefix_len, self.scope_prefix_len
+        )
+    }
+}
+
+/// Returns the first `len` bits of `address`.
+fn mask(address: IpAddr, len: u8) -> IpAddr {
+    match address {
+        IpAddr::V4(address) => {
+            let mask = u32::MAX.checked_shl(32 - len as u32).unwrap_or(0);
+            IpAddr::V4((u32::from(address) & mask).into())
+        }
+        IpAddr::V6(address) => {
+            let mask = u128::MAX.checked_shl(128 - len as u32).unwrap_or(0);
+            IpAddr::V6((u128::from(address) & mask).into())
+        }
+    }
+}
+
+/// Serializes the options as a list, keeping their order, see [`SerializedOption`].
+#[cfg(feature = "serde")]
+pub(crate) fn serialize_options<S: Serializer>(
+    options: &[(OptionCode, Vec<u8>)],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(
+        options
+            .iter()
+            .map(|(code, value)| SerializedOption(*code, value)),
+    )
+}
+
+/// An EDNS option as serialized with serde: an object with the option's `code` and `name`, and its
+/// value decoded as far as toluol knows the option:
+/// - Client Subnet: `address`, `source_prefix_len`, and `scope_prefix_len`.
+/// - Cookie: the `client` and (if present) `server` cookies in hex.
+/// - Padding: the `length` of the padding.
+///
+/// The values of other options, and of malformed ones, are given as base64-tagged `data`.
+#[cfg(feature = "serde")]
+struct SerializedOption<'a>(OptionCode, &'a [u8]);
+
+#[cfg(feature = "serde")]
+impl Serialize for SerializedOption<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let SerializedOption(code, value) = *self;
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("code", &u16::from(code))?;
+        map.serialize_entry("name", &code.to_string())?;
+        match code {
+            OptionCode::ClientSubnet => match ClientSubnet::parse(value) {
+                Ok(subnet) => {
+                    map.serialize_entry("address", &subnet.address)?;
+                    map.serialize_entry("source_prefix_len", &subnet.source_prefix_len)?;
+                    map.serialize_entry("scope_prefix_len", &subnet.scope_prefix_len)?;
+                }
+                Err(_) => map.serialize_entry("data", &Base64(value))?,
+            },
+            // a client cookie has 8 bytes, a server cookie 8 to 32 bytes (RFC 7873, Section 4)
+            OptionCode::Cookie if value.len() == 8 || (16..=40).contains(&value.len()) => {
+                let (client, server) = value.split_at(8);
+                map.serialize_entry("client", &data_encoding::HEXLOWER.encode(client))?;
+                if !server.is_empty() {
+                    map.serialize_entry("server", &data_encoding::HEXLOWER.encode(server))?;
+                }
+            }
+            OptionCode::Padding => map.serialize_entry("length", &value.len())?,
+            _ => map.serialize_entry("data", &Base64(value))?,
+        }
+        map.end()
+    }
 }
 
 impl RdataTrait for OPT {
-    fn parse_rdata(rdata: &mut std::io::Cursor<&[u8]>, rdlength: u16) -> Result<Rdata, ParseError> {
-        let mut len = 0;
-        let mut options = HashMap::new();
-        while len < rdlength {
+    fn parse_rdata(rdata: &mut MessageReader) -> Result<Rdata, ParseError> {
+        let mut options = Vec::new();
+        while rdata.remaining_in_rdata() > 0 {
             let option_code = rdata.read_u16::<NetworkEndian>()?.into();
             let option_len = rdata.read_u16::<NetworkEndian>()?;
-            let mut option_value = vec![0; option_len as usize];
-            rdata.read_exact(&mut option_value)?;
-            options.insert(option_code, option_value);
-            len += option_len + 4;
+            let option_value = rdata.read_bytes(option_len as usize)?.to_vec();
+            options.push((option_code, option_value));
         }
         Ok(Rdata::OPT(Self { options }))
     }
@@ -5,6 +5,8 @@ use std::io::Write;
 
 use crate::error::{EncodeError, ParseError};
 use crate::name::{Compression, Name};
+use crate::reader::MessageReader;
+use crate::writer::MessageWriter;
 
 use super::{Rdata, RdataTrait};
 
@@ -26,11 +28,19 @@ pub struct PTR {
     pub location: Name,
 }
 
+impl PTR {
+    /// Creates a new `PTR` record pointing to the given location.
+    ///
+    /// Returns an error if `location` is not a valid domain name (see [`Name::from_ascii()`]).
+    pub fn new(location: impl AsRef<str>) -> Result<Self, ParseError> {
+        Ok(Self {
+            location: Name::from_ascii(location)?,
+        })
+    }
+}
+
 impl RdataTrait for PTR {
-    fn parse_rdata(
-        rdata: &mut std::io::Cursor<&[u8]>,
-        _rdlength: u16,
-    ) -> Result<Rdata, ParseError> {
+    fn parse_rdata(rdata: &mut MessageReader) -> Result<Rdata, ParseError> {
         Ok(Rdata::PTR(Self {
             location: Name::parse(rdata, Compression::Allowed)?,
         }))
@@ -40,9 +50,17 @@ impl RdataTrait for PTR {
         self.location.encode_into(buf)
     }
 
+    fn write_rdata(&self, writer: &mut MessageWriter) -> Result<(), EncodeError> {
+        writer.write_name(&self.location, Compression::Allowed)
+    }
+
     fn canonicalize(&mut self) {
         self.location.canonicalize();
     }
+
+    fn names_mut(&mut self) -> Vec<&mut Name> {
+        vec![&mut self.location]
+    }
 }
 
 impl Display for PTR {
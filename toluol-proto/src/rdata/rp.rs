@@ -5,6 +5,7 @@ use std::io::Write;
 
 use crate::error::{EncodeError, ParseError};
 use crate::name::{Compression, Name};
+use crate::reader::MessageReader;
 
 use super::{Rdata, RdataTrait};
 
@@ -24,11 +25,21 @@ pub struct RP {
     pub txt: Name,
 }
 
+impl RP {
+    /// Creates a new `RP` record with the given mailbox and `TXT` domain name.
+    ///
+    /// Returns an error if `mbox` or `txt` is not a valid domain name (see
+    /// [`Name::from_ascii()`]).
+    pub fn new(mbox: impl AsRef<str>, txt: impl AsRef<str>) -> Result<Self, ParseError> {
+        Ok(Self {
+            mbox: Name::from_ascii(mbox)?,
+            txt: Name::from_ascii(txt)?,
+        })
+    }
+}
+
 impl RdataTrait for RP {
-    fn parse_rdata(
-        rdata: &mut std::io::Cursor<&[u8]>,
-        _rdlength: u16,
-    ) -> Result<Rdata, ParseError> {
+    fn parse_rdata(rdata: &mut MessageReader) -> Result<Rdata, ParseError> {
         let mbox = Name::parse(rdata, Compression::Allowed)?;
         let txt = Name::parse(rdata, Compression::Allowed)?;
         Ok(Rdata::RP(Self { mbox, txt }))
@@ -42,6 +53,10 @@ impl RdataTrait for RP {
         self.mbox.canonicalize();
         self.txt.canonicalize();
     }
+
+    fn names_mut(&mut self) -> Vec<&mut Name> {
+        vec![&mut self.mbox, &mut self.txt]
+    }
 }
 
 impl Display for RP {
@@ -1,14 +1,15 @@
 //! `RRSIG` RDATA definition.
 
 use std::fmt::Display;
-use std::io::{Read, Write};
+use std::io::Write;
 
 use byteorder::{NetworkEndian, ReadBytesExt, WriteBytesExt};
-use chrono::{TimeZone, Utc};
 use data_encoding::BASE64;
 
+use crate::dnssec::time;
 use crate::error::{EncodeError, ParseError};
 use crate::name::{Compression, Name};
+use crate::reader::MessageReader;
 use crate::RecordType;
 
 use super::dnskey::Algorithm;
@@ -59,9 +60,17 @@ pub struct RRSIG {
     /// in [RFC 1982](https://www.rfc-editor.org/rfc/rfc1982). As a direct consequence, the values
     /// contained in these fields cannot refer to dates more than 68 years in either the past or the
     /// future.
+    #[cfg_attr(
+        feature = "serde",
+        serde(serialize_with = "crate::serialize::timestamp")
+    )]
     pub signature_expiration: u32,
     /// The start of the validity period for the signature. See [Self::signature_expiration] for
     /// details.
+    #[cfg_attr(
+        feature = "serde",
+        serde(serialize_with = "crate::serialize::timestamp")
+    )]
     pub signature_inception: u32,
     /// The key tag of the [`DNSKEY`] record that validates this signature.
     ///
@@ -80,6 +89,7 @@ pub struct RRSIG {
     /// [`Self::type_covered`].
     ///
     /// The format of this field depends on the algorithm in use.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::serialize::base64"))]
     pub signature: Vec<u8>,
 }
 
@@ -106,10 +116,7 @@ impl RRSIG {
 }
 
 impl RdataTrait for RRSIG {
-    fn parse_rdata(rdata: &mut std::io::Cursor<&[u8]>, rdlength: u16) -> Result<Rdata, ParseError> {
-        // used to calculate how many bytes were read later on
-        let rdata_pos_before = rdata.position();
-
+    fn parse_rdata(rdata: &mut MessageReader) -> Result<Rdata, ParseError> {
         let type_covered: RecordType = rdata.read_u16::<NetworkEndian>()?.into();
         let algorithm: Algorithm = rdata.read_u8()?.into();
         let labels = rdata.read_u8()?;
@@ -118,11 +125,7 @@ impl RdataTrait for RRSIG {
         let signature_inception = rdata.read_u32::<NetworkEndian>()?;
         let key_tag = rdata.read_u16::<NetworkEndian>()?;
         let signer_name = Name::parse(rdata, Compression::Prohibited)?;
-
-        let rdata_pos_after = rdata.position();
-        let bytes_read = (rdata_pos_after - rdata_pos_before) as usize;
-        let mut signature = vec![0; rdlength as usize - bytes_read];
-        rdata.read_exact(&mut signature)?;
+        let signature = rdata.read_remaining().to_vec();
 
         Ok(Rdata::RRSIG(Self {
             type_covered,
@@ -155,18 +158,54 @@ impl RdataTrait for RRSIG {
     fn canonicalize(&mut self) {
         self.signer_name.canonicalize();
     }
+
+    fn names_mut(&mut self) -> Vec<&mut Name> {
+        vec![&mut self.signer_name]
+    }
 }
 
+/// Displays the record in presentation format. The signature expiration and inception are shown as
+/// `YYYYMMDDHHmmSS`, or as seconds since 1970 with the alternate flag (`{:#}`).
+///
+/// # Examples
+/// ```rust
+/// use toluol_proto::rdata::dnskey::Algorithm;
+/// use toluol_proto::rdata::RRSIG;
+/// use toluol_proto::{Name, RecordType};
+///
+/// let rrsig = RRSIG {
+///     type_covered: RecordType::A,
+///     algorithm: Algorithm::ECDSAP256SHA256,
+///     labels: 2,
+///     original_ttl: 3600,
+///     signature_expiration: 1284026679,
+///     signature_inception: 1281607479,
+///     key_tag: 55648,
+///     signer_name: Name::from_ascii("example.net").unwrap(),
+///     signature: vec![],
+/// };
+/// assert_eq!(
+///     rrsig.to_string(),
+///     "A ECDSAP256SHA256 2 3600 20100909100439 20100812100439 55648 example.net "
+/// );
+/// assert_eq!(
+///     format!("{:#}", rrsig),
+///     "A ECDSAP256SHA256 2 3600 1284026679 1281607479 55648 example.net "
+/// );
+/// ```
 impl Display for RRSIG {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let signature_expiration = Utc
-            .timestamp(self.signature_expiration as i64, 0)
-            .format("%Y%m%d%H%M%S")
-            .to_string();
-        let signature_inception = Utc
-            .timestamp(self.signature_inception as i64, 0)
-            .format("%Y%m%d%H%M%S")
-            .to_string();
+        let (signature_expiration, signature_inception) = if f.alternate() {
+            (
+                self.signature_expiration.to_string(),
+                self.signature_inception.to_string(),
+            )
+        } else {
+            (
+                time::format(self.signature_expiration),
+                time::format(self.signature_inception),
+            )
+        };
         let signature = BASE64.encode(&self.signature);
         write!(
             f,
@@ -7,6 +7,8 @@ use byteorder::{NetworkEndian, ReadBytesExt, WriteBytesExt};
 
 use crate::error::{EncodeError, ParseError};
 use crate::name::{Compression, Name};
+use crate::reader::MessageReader;
+use crate::writer::MessageWriter;
 
 use super::{Rdata, RdataTrait};
 
@@ -42,10 +44,7 @@ pub struct SOA {
 }
 
 impl RdataTrait for SOA {
-    fn parse_rdata(
-        rdata: &mut std::io::Cursor<&[u8]>,
-        _rdlength: u16,
-    ) -> Result<Rdata, ParseError> {
+    fn parse_rdata(rdata: &mut MessageReader) -> Result<Rdata, ParseError> {
         let mname = Name::parse(rdata, Compression::Allowed)?;
         let rname = Name::parse(rdata, Compression::Allowed)?;
         let serial = rdata.read_u32::<NetworkEndian>()?;
@@ -77,10 +76,25 @@ impl RdataTrait for SOA {
         Ok(bytes_written + 4 + 4 + 4 + 4 + 4)
     }
 
+    fn write_rdata(&self, writer: &mut MessageWriter) -> Result<(), EncodeError> {
+        writer.write_name(&self.mname, Compression::Allowed)?;
+        writer.write_name(&self.rname, Compression::Allowed)?;
+        writer.write_u32::<NetworkEndian>(self.serial)?;
+        writer.write_u32::<NetworkEndian>(self.refresh)?;
+        writer.write_u32::<NetworkEndian>(self.retry)?;
+        writer.write_u32::<NetworkEndian>(self.expire)?;
+        writer.write_u32::<NetworkEndian>(self.minimum)?;
+        Ok(())
+    }
+
     fn canonicalize(&mut self) {
         self.mname.canonicalize();
         self.rname.canonicalize();
     }
+
+    fn names_mut(&mut self) -> Vec<&mut Name> {
+        vec![&mut self.mname, &mut self.rname]
+    }
 }
 
 impl Display for SOA {
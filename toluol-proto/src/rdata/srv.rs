@@ -7,6 +7,7 @@ use byteorder::{NetworkEndian, ReadBytesExt, WriteBytesExt};
 
 use crate::error::{EncodeError, ParseError};
 use crate::name::{Compression, Name};
+use crate::reader::MessageReader;
 
 use super::{Rdata, RdataTrait};
 
@@ -48,10 +49,7 @@ pub struct SRV {
 }
 
 impl RdataTrait for SRV {
-    fn parse_rdata(
-        rdata: &mut std::io::Cursor<&[u8]>,
-        _rdlength: u16,
-    ) -> Result<Rdata, ParseError> {
+    fn parse_rdata(rdata: &mut MessageReader) -> Result<Rdata, ParseError> {
         let priority = rdata.read_u16::<NetworkEndian>()?;
         let weight = rdata.read_u16::<NetworkEndian>()?;
         let port = rdata.read_u16::<NetworkEndian>()?;
@@ -76,6 +74,10 @@ impl RdataTrait for SRV {
     fn canonicalize(&mut self) {
         self.target.canonicalize();
     }
+
+    fn names_mut(&mut self) -> Vec<&mut Name> {
+        vec![&mut self.target]
+    }
 }
 
 impl Display for SRV {
@@ -1,13 +1,14 @@
 //! `SSHFP` RDATA definition.
 
 use std::fmt::Display;
-use std::io::{Read, Write};
+use std::io::Write;
 
 use byteorder::{ReadBytesExt, WriteBytesExt};
 use data_encoding::HEXUPPER;
 use repr_with_fallback::repr_with_fallback;
 
 use crate::error::{EncodeError, ParseError};
+use crate::reader::MessageReader;
 
 use super::{Rdata, RdataTrait};
 
@@ -60,16 +61,15 @@ pub struct SSHFP {
     pub fingerprint_type: FingerprintType,
     /// The fingerprint, calculated over the public key blob as described in
     /// [RFC 4253](https://www.rfc-editor.org/rfc/rfc4253).
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::serialize::base64"))]
     pub fingerprint: Vec<u8>,
 }
 
 impl RdataTrait for SSHFP {
-    fn parse_rdata(rdata: &mut std::io::Cursor<&[u8]>, rdlength: u16) -> Result<Rdata, ParseError> {
+    fn parse_rdata(rdata: &mut MessageReader) -> Result<Rdata, ParseError> {
         let algorithm: Algorithm = rdata.read_u8()?.into();
         let fingerprint_type: FingerprintType = rdata.read_u8()?.into();
-        // we already read: u8 (2) + u8 (1) = 2 bytes
-        let mut fingerprint = vec![0; (rdlength - 2) as usize];
-        rdata.read_exact(&mut fingerprint)?;
+        let fingerprint = rdata.read_remaining().to_vec();
 
         Ok(Rdata::SSHFP(Self {
             algorithm,
@@ -0,0 +1,330 @@
+//! `SVCB` and `HTTPS` RDATA definition.
+
+use std::fmt::Display;
+use std::io::Write;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use byteorder::{NetworkEndian, ReadBytesExt, WriteBytesExt};
+use data_encoding::BASE64;
+
+use crate::error::{EncodeError, ParseError};
+use crate::name::{Compression, Name};
+use crate::reader::MessageReader;
+
+use super::{encode_string_into, parse_string, Rdata, RdataTrait};
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// A service binding record, which tells clients where and how to connect to a service.
+/// [\[RFC 9460\]](https://www.rfc-editor.org/rfc/rfc9460)
+///
+/// A record with priority 0 is in *AliasMode*: similar to a `CNAME` record, but only for this
+/// service, it refers to [`Self::target`], whose `SVCB` records are to be used instead. All other
+/// records are in *ServiceMode* and describe an alternative endpoint of the service and the
+/// parameters to connect to it with.
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct SVCB {
+    /// 0 for AliasMode. Otherwise, the priority of the endpoint: clients SHOULD try endpoints with
+    /// lower values first.
+    pub priority: u16,
+    /// The name of the alias or the endpoint. The root name has a special meaning, see
+    /// [`Self::target_name()`]. Name compression is not to be used for this field.
+    pub target: Name,
+    /// The parameters of the endpoint, ordered by their keys. Empty in AliasMode.
+    pub params: Vec<SvcParam>,
+}
+
+/// The `SVCB` record for HTTPS origins, which additionally tells clients to only use HTTPS to
+/// connect to them. [\[RFC 9460, Section 9\]](https://www.rfc-editor.org/rfc/rfc9460#section-9)
+pub type HTTPS = SVCB;
+
+/// A parameter of a ServiceMode [`SVCB`] record, i.e. a key and its value.
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum SvcParam {
+    /// The keys of the parameters that clients MUST understand to use the endpoint.
+    Mandatory(Vec<u16>),
+    /// The protocol identifiers of the supported ALPN protocols, e.g. `h2` or `h3`.
+    Alpn(Vec<String>),
+    /// Whether the default protocol (`http/1.1` for `HTTPS` records) is not supported.
+    NoDefaultAlpn,
+    /// The port to connect to.
+    Port(u16),
+    /// IPv4 addresses of the target, which clients MAY use until their `A` query is answered.
+    Ipv4Hint(Vec<Ipv4Addr>),
+    /// The Encrypted ClientHello configuration list.
+    Ech(#[cfg_attr(feature = "serde", serde(serialize_with = "crate::serialize::base64"))] Vec<u8>),
+    /// IPv6 addresses of the target, which clients MAY use until their `AAAA` query is answered.
+    Ipv6Hint(Vec<Ipv6Addr>),
+    /// A parameter with another key, and its raw value.
+    Unknown(
+        u16,
+        #[cfg_attr(feature = "serde", serde(serialize_with = "crate::serialize::base64"))] Vec<u8>,
+    ),
+}
+
+impl SVCB {
+    /// Returns whether the record is in AliasMode, i.e. has priority 0.
+    pub fn is_alias_mode(&self) -> bool {
+        self.priority == 0
+    }
+
+    /// Returns the name the record refers to, given the `owner` of the record.
+    ///
+    /// A target of `.` stands for the owner itself in ServiceMode, and for "the service is not
+    /// available" in AliasMode, in which case [`None`] is returned.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::rdata::SVCB;
+    /// use toluol_proto::Name;
+    ///
+    /// let owner = Name::from_ascii("example.com").unwrap();
+    /// let service = SVCB { priority: 1, target: Name::root(), params: Vec::new() };
+    /// assert_eq!(service.target_name(&owner), Some(&owner));
+    ///
+    /// let alias = SVCB { priority: 0, target: Name::root(), params: Vec::new() };
+    /// assert_eq!(alias.target_name(&owner), None);
+    /// ```
+    pub fn target_name<'a>(&'a self, owner: &'a Name) -> Option<&'a Name> {
+        match (self.target.is_root(), self.is_alias_mode()) {
+            (false, _) => Some(&self.target),
+            (true, false) => Some(owner),
+            (true, true) => None,
+        }
+    }
+
+    /// Returns the addresses of the `ipv4hint` parameter, if any.
+    pub fn ipv4_hint(&self) -> &[Ipv4Addr] {
+        self.params
+            .iter()
+            .find_map(|param| match param {
+                SvcParam::Ipv4Hint(addresses) => Some(&addresses[..]),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns the addresses of the `ipv6hint` parameter, if any.
+    pub fn ipv6_hint(&self) -> &[Ipv6Addr] {
+        self.params
+            .iter()
+            .find_map(|param| match param {
+                SvcParam::Ipv6Hint(addresses) => Some(&addresses[..]),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn parse(rdata: &mut MessageReader) -> Result<Self, ParseError> {
+        let priority = rdata.read_u16::<NetworkEndian>()?;
+        let target = Name::parse(rdata, Compression::Prohibited)?;
+
+        let mut params: Vec<SvcParam> = Vec::new();
+        while rdata.remaining_in_rdata() > 0 {
+            let key = rdata.read_u16::<NetworkEndian>()?;
+            if let Some(previous) = params.last().filter(|previous| previous.key() >= key) {
+                return Err(ParseError::InvalidSvcParam(format!(
+                    "keys must be in increasing order, {} follows {}",
+                    SvcParam::key_name(key),
+                    SvcParam::key_name(previous.key())
+                )));
+            }
+            let len = rdata.read_u16::<NetworkEndian>()?;
+            let mut value = rdata.rdata_reader(len as usize)?;
+            params.push(SvcParam::parse(key, &mut value)?);
+        }
+
+        Ok(Self {
+            priority,
+            target,
+            params,
+        })
+    }
+}
+
+impl SvcParam {
+    /// Returns the key of the parameter.
+    pub fn key(&self) -> u16 {
+        match self {
+            SvcParam::Mandatory(_) => 0,
+            SvcParam::Alpn(_) => 1,
+            SvcParam::NoDefaultAlpn => 2,
+            SvcParam::Port(_) => 3,
+            SvcParam::Ipv4Hint(_) => 4,
+            SvcParam::Ech(_) => 5,
+            SvcParam::Ipv6Hint(_) => 6,
+            SvcParam::Unknown(key, _) => *key,
+        }
+    }
+
+    /// Returns the name of `key` in presentation format, e.g. `alpn` or `key65000`.
+    pub fn key_name(key: u16) -> String {
+        match key {
+            0 => "mandatory".to_string(),
+            1 => "alpn".to_string(),
+            2 => "no-default-alpn".to_string(),
+            3 => "port".to_string(),
+            4 => "ipv4hint".to_string(),
+            5 => "ech".to_string(),
+            6 => "ipv6hint".to_string(),
+            _ => format!("key{}", key),
+        }
+    }
+
+    /// Parses the parameter with the given `key` from its value, which spans all of `value`.
+    fn parse(key: u16, value: &mut MessageReader) -> Result<Self, ParseError> {
+        let len = value.remaining_in_rdata();
+        let param = match key {
+            0 if len > 0 && len.is_multiple_of(2) => SvcParam::Mandatory(
+                (0..len / 2)
+                    .map(|_| value.read_u16::<NetworkEndian>())
+                    .collect::<Result<_, _>>()?,
+            ),
+            1 if len > 0 => {
+                let mut ids = Vec::new();
+                while value.remaining_in_rdata() > 0 {
+                    ids.push(parse_string(value)?.0);
+                }
+                SvcParam::Alpn(ids)
+            }
+            2 if len == 0 => SvcParam::NoDefaultAlpn,
+            3 if len == 2 => SvcParam::Port(value.read_u16::<NetworkEndian>()?),
+            4 if len > 0 && len.is_multiple_of(4) => SvcParam::Ipv4Hint(
+                (0..len / 4)
+                    .map(|_| value.read_u32::<NetworkEndian>().map(Ipv4Addr::from))
+                    .collect::<Result<_, _>>()?,
+            ),
+            5 => SvcParam::Ech(value.read_remaining().to_vec()),
+            6 if len > 0 && len.is_multiple_of(16) => SvcParam::Ipv6Hint(
+                (0..len / 16)
+                    .map(|_| value.read_u128::<NetworkEndian>().map(Ipv6Addr::from))
+                    .collect::<Result<_, _>>()?,
+            ),
+            0..=6 => {
+                return Err(ParseError::InvalidSvcParam(format!(
+                    "invalid value for {} of {} bytes",
+                    Self::key_name(key),
+                    len
+                )))
+            }
+            _ => SvcParam::Unknown(key, value.read_remaining().to_vec()),
+        };
+        Ok(param)
+    }
+
+    /// Encodes the value of the parameter, without key and length.
+    fn encode_value(&self) -> Result<Vec<u8>, EncodeError> {
+        let mut value = Vec::new();
+        match self {
+            SvcParam::Mandatory(keys) => {
+                for key in keys {
+                    value.write_u16::<NetworkEndian>(*key)?;
+                }
+            }
+            SvcParam::Alpn(ids) => {
+                for id in ids {
+                    encode_string_into(id, &mut value)?;
+                }
+            }
+            SvcParam::NoDefaultAlpn => {}
+            SvcParam::Port(port) => value.write_u16::<NetworkEndian>(*port)?,
+            SvcParam::Ipv4Hint(addresses) => {
+                for address in addresses {
+                    value.write_all(&address.octets())?;
+                }
+            }
+            SvcParam::Ipv6Hint(addresses) => {
+                for address in addresses {
+                    value.write_all(&address.octets())?;
+                }
+            }
+            SvcParam::Ech(bytes) | SvcParam::Unknown(_, bytes) => value.write_all(bytes)?,
+        }
+        Ok(value)
+    }
+}
+
+impl RdataTrait for SVCB {
+    fn parse_rdata(rdata: &mut MessageReader) -> Result<Rdata, ParseError> {
+        Self::parse(rdata).map(Rdata::SVCB)
+    }
+
+    fn encode_rdata_into(&self, buf: &mut impl Write) -> Result<u16, EncodeError> {
+        buf.write_u16::<NetworkEndian>(self.priority)?;
+        let mut len = 2 + self.target.encode_into(buf)? as usize;
+        for param in &self.params {
+            let value = param.encode_value()?;
+            buf.write_u16::<NetworkEndian>(param.key())?;
+            buf.write_u16::<NetworkEndian>(value.len() as u16)?;
+            buf.write_all(&value)?;
+            len += 2 + 2 + value.len();
+        }
+        if len > u16::MAX as usize {
+            return Err(EncodeError::RdataTooLong(len));
+        }
+        Ok(len as u16)
+    }
+
+    fn names_mut(&mut self) -> Vec<&mut Name> {
+        vec![&mut self.target]
+    }
+}
+
+impl Display for SVCB {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.priority, self.target)?;
+        for param in &self.params {
+            write!(f, " {}", param)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for SvcParam {
+    /// Formats the parameter in presentation format, e.g. `alpn=h2,h3` or `ipv4hint=192.0.2.1`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let key = Self::key_name(self.key());
+        let value = match self {
+            SvcParam::Mandatory(keys) => {
+                let keys: Vec<_> = keys.iter().map(|&key| Self::key_name(key)).collect();
+                keys.join(",")
+            }
+            SvcParam::Alpn(ids) => {
+                // commas separate the IDs, so they (and the escape character) must be escaped
+                let ids: Vec<_> = ids
+                    .iter()
+                    .map(|id| id.replace('\\', "\\\\").replace(',', "\\,"))
+                    .collect();
+                ids.join(",")
+            }
+            SvcParam::NoDefaultAlpn => return write!(f, "{}", key),
+            SvcParam::Port(port) => port.to_string(),
+            SvcParam::Ipv4Hint(addresses) => {
+                let addresses: Vec<_> = addresses.iter().map(Ipv4Addr::to_string).collect();
+                addresses.join(",")
+            }
+            SvcParam::Ech(bytes) => BASE64.encode(bytes),
+            SvcParam::Ipv6Hint(addresses) => {
+                let addresses: Vec<_> = addresses.iter().map(Ipv6Addr::to_string).collect();
+                addresses.join(",")
+            }
+            SvcParam::Unknown(_, bytes) if bytes.is_empty() => return write!(f, "{}", key),
+            SvcParam::Unknown(_, bytes) => {
+                // the value is quoted, with all bytes except for printable ASCII escaped
+                let value: String = bytes
+                    .iter()
+                    .map(|&b| match b {
+                        b'"' | b'\\' => format!("\\{}", b as char),
+                        b' '..=b'~' => (b as char).to_string(),
+                        _ => format!("\\{:03}", b),
+                    })
+                    .collect();
+                format!("\"{}\"", value)
+            }
+        };
+        write!(f, "{}={}", key, value)
+    }
+}
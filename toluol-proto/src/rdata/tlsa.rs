@@ -1,13 +1,14 @@
 //! `TLSA` RDATA definition.
 
 use std::fmt::Display;
-use std::io::{Read, Write};
+use std::io::Write;
 
 use byteorder::{ReadBytesExt, WriteBytesExt};
 use data_encoding::HEXUPPER;
 use repr_with_fallback::repr_with_fallback;
 
 use crate::error::{EncodeError, ParseError};
+use crate::reader::MessageReader;
 
 use super::{Rdata, RdataTrait};
 
@@ -93,17 +94,16 @@ pub struct TLSA {
     /// the full certificate or its SubjectPublicKeyInfo, depending on the selector), or the hash of
     /// the raw data. The data refers to the certificate in the association, not to the TLS ASN.1
     /// Certificate object.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::serialize::base64"))]
     pub cert_data: Vec<u8>,
 }
 
 impl RdataTrait for TLSA {
-    fn parse_rdata(rdata: &mut std::io::Cursor<&[u8]>, rdlength: u16) -> Result<Rdata, ParseError> {
+    fn parse_rdata(rdata: &mut MessageReader) -> Result<Rdata, ParseError> {
         let cert_usage: CertUsage = rdata.read_u8()?.into();
         let selector: Selector = rdata.read_u8()?.into();
         let matching: Matching = rdata.read_u8()?.into();
-        // we already read: u8 (1) + u8 (1) + u8 (1) = 3 bytes
-        let mut cert_data = vec![0; (rdlength - 3) as usize];
-        rdata.read_exact(&mut cert_data)?;
+        let cert_data = rdata.read_remaining().to_vec();
 
         Ok(Rdata::TLSA(Self {
             cert_usage,
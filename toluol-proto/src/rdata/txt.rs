@@ -4,12 +4,16 @@ use std::fmt::Display;
 use std::io::Write;
 
 use crate::error::{EncodeError, ParseError};
+use crate::reader::MessageReader;
 
 use super::{encode_string_into, parse_string, Rdata, RdataTrait};
 
 #[cfg(feature = "serde")]
 use serde::Serialize;
 
+/// The maximum length of a single character string, in bytes.
+const MAX_STRING_LEN: usize = 255;
+
 /// A record containing text strings. [\[RFC 1035\]](https://www.rfc-editor.org/rfc/rfc1035)
 ///
 /// `TXT` records are used to hold descriptive text. The semantics of the text depends on the
@@ -21,17 +25,106 @@ pub struct TXT {
     pub text: Vec<String>,
 }
 
+impl TXT {
+    /// Creates a new `TXT` record containing the given strings.
+    ///
+    /// As a single character string in the wire format is at most 255 bytes long, strings longer
+    /// than that are split into multiple consecutive strings, as is common e.g. for DKIM keys.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::rdata::TXT;
+    ///
+    /// let txt = TXT::from_strings(["v=spf1 -all"]);
+    /// assert_eq!(txt.text, vec!["v=spf1 -all"]);
+    ///
+    /// let txt = TXT::from_strings(["a".repeat(300)]);
+    /// assert_eq!(txt.text, vec!["a".repeat(255), "a".repeat(45)]);
+    /// ```
+    pub fn from_strings(strings: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        let mut text = Vec::new();
+        for string in strings {
+            let mut string = string.as_ref();
+            loop {
+                // don't split inside of a multi-byte character; non-ASCII strings can't be encoded
+                // anyway, but this way we at least don't panic
+                let mut len = string.len().min(MAX_STRING_LEN);
+                while !string.is_char_boundary(len) {
+                    len -= 1;
+                }
+                let (chunk, rest) = string.split_at(len);
+                text.push(chunk.to_string());
+                if rest.is_empty() {
+                    break;
+                }
+                string = rest;
+            }
+        }
+        Self { text }
+    }
+
+    /// Returns the strings of this record concatenated without any separator.
+    ///
+    /// This is how long values that are split into multiple strings (e.g. SPF records or DKIM keys)
+    /// are meant to be interpreted. [\[RFC 7208, Section 3.3\]](https://www.rfc-editor.org/rfc/rfc7208#section-3.3)
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::rdata::TXT;
+    ///
+    /// let txt = TXT::from_strings(["v=spf1 include:example.com", " -all"]);
+    /// assert_eq!(txt.joined(), "v=spf1 include:example.com -all");
+    /// ```
+    pub fn joined(&self) -> String {
+        self.text.concat()
+    }
+
+    /// Interprets this record as a list of `tag=value` pairs separated by semicolons, as used by
+    /// DKIM, DMARC, MTA-STS, and TLSRPT records.
+    /// [\[RFC 6376, Section 3.2\]](https://www.rfc-editor.org/rfc/rfc6376#section-3.2)
+    ///
+    /// Whitespace around tags and values is removed. Returns [`None`] if the record is not a valid
+    /// tag list.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::rdata::TXT;
+    ///
+    /// let txt = TXT::from_strings(["v=DMARC1; p=reject; rua=mailto:dmarc@example.com;"]);
+    /// let tags = txt.tag_list().unwrap();
+    /// assert_eq!(tags[0], ("v".to_string(), "DMARC1".to_string()));
+    /// assert_eq!(tags[1], ("p".to_string(), "reject".to_string()));
+    /// assert_eq!(tags.len(), 3);
+    ///
+    /// assert_eq!(TXT::from_strings(["v=spf1 -all; no tag"]).tag_list(), None);
+    /// ```
+    pub fn tag_list(&self) -> Option<Vec<(String, String)>> {
+        let joined = self.joined();
+        let mut tags = Vec::new();
+        for spec in joined.split(';') {
+            let spec = spec.trim();
+            // a trailing semicolon is allowed
+            if spec.is_empty() {
+                continue;
+            }
+            let (tag, value) = spec.split_once('=')?;
+            let tag = tag.trim();
+            if tag.is_empty() || !tag.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return None;
+            }
+            tags.push((tag.to_string(), value.trim().to_string()));
+        }
+        Some(tags)
+    }
+}
+
 impl RdataTrait for TXT {
-    fn parse_rdata(rdata: &mut std::io::Cursor<&[u8]>, rdlength: u16) -> Result<Rdata, ParseError> {
-        let rdlength = rdlength as usize;
+    fn parse_rdata(rdata: &mut MessageReader) -> Result<Rdata, ParseError> {
         let mut text = Vec::new();
-        let mut bytes_read = 0;
 
         // according to RFC1035, it is possible that one TXT entry holds multiple character strings
-        while bytes_read < rdlength {
-            let (s, len) = parse_string(rdata)?;
-            bytes_read += len; // also count the length byte before the actual string
-            text.push(s);
+        while rdata.remaining_in_rdata() > 0 {
+            text.push(parse_string(rdata)?.0);
         }
 
         Ok(Rdata::TXT(Self { text }))
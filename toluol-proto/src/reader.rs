@@ -0,0 +1,190 @@
+//! Definition and implementation of the [`MessageReader`] type.
+
+use std::io::{self, ErrorKind, Read};
+
+use smartstring::SmartString;
+
+use crate::error::ParseError;
+use crate::name::{Compression, Labels, Name};
+
+/// Reads the parts of an encoded DNS message, e.g. [`Name`]s or
+/// [`Rdata`](crate::rdata::Rdata).
+///
+/// In contrast to a [`Cursor`](std::io::Cursor), a `MessageReader` knows the bounds of the complete
+/// message as well as those of the part it is reading, e.g. the RDATA of a record (see
+/// [`rdata_reader()`](Self::rdata_reader())): it never reads past the part's end, but it can still
+/// follow compression pointers to names elsewhere in the message. Compression pointers must point
+/// backwards, i.e. to a name earlier in the message, so that parsing always terminates.
+///
+/// Numbers can be read via [`Read`], e.g. with [`byteorder::ReadBytesExt`]. Reading past the end
+/// of the part results in an [`ErrorKind::UnexpectedEof`] error.
+///
+/// # Examples
+/// ```rust
+/// use byteorder::{NetworkEndian, ReadBytesExt};
+/// use toluol_proto::name::Compression;
+/// use toluol_proto::reader::MessageReader;
+/// use toluol_proto::Name;
+///
+/// // "example.com", followed by RDATA with a preference and "mail" + a pointer to "example.com"
+/// let msg = b"\x07example\x03com\0\x00\x0a\x04mail\xc0\x00";
+/// let mut reader = MessageReader::new(msg);
+/// assert_eq!(reader.read_name(Compression::Allowed).unwrap().to_string(), "example.com");
+///
+/// let mut rdata = reader.rdata_reader(9).unwrap();
+/// assert_eq!(rdata.read_u16::<NetworkEndian>().unwrap(), 10);
+/// let exchange = rdata.read_name(Compression::Allowed).unwrap();
+/// assert_eq!(exchange, Name::from_ascii("mail.example.com").unwrap());
+/// assert_eq!(rdata.remaining_in_rdata(), 0);
+/// assert!(rdata.read_u8().is_err());
+/// ```
+#[derive(Clone, Debug)]
+pub struct MessageReader<'a> {
+    /// The complete message.
+    msg: &'a [u8],
+    /// The position of the next byte to read.
+    pos: usize,
+    /// The end of the part that is being read.
+    end: usize,
+    /// Whether a compression pointer was followed while reading the part.
+    followed_pointer: bool,
+}
+
+impl<'a> MessageReader<'a> {
+    /// Creates a `MessageReader` for the complete message `msg`, starting at its first byte.
+    pub fn new(msg: &'a [u8]) -> Self {
+        Self {
+            msg,
+            pos: 0,
+            end: msg.len(),
+            followed_pointer: false,
+        }
+    }
+
+    /// Returns the position of the next byte to read, relative to the start of the message.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Returns the number of bytes left in the part that is being read, i.e. in the RDATA for
+    /// readers returned by [`rdata_reader()`](Self::rdata_reader()).
+    pub fn remaining_in_rdata(&self) -> usize {
+        self.end - self.pos
+    }
+
+    /// Returns a reader for the next `len` bytes, e.g. the RDATA of a record, and moves this
+    /// reader past them.
+    ///
+    /// The returned reader only reads these bytes, except for following compression pointers.
+    ///
+    /// Returns an error if fewer than `len` bytes are left.
+    pub fn rdata_reader(&mut self, len: usize) -> Result<MessageReader<'a>, ParseError> {
+        if len > self.remaining_in_rdata() {
+            return Err(eof());
+        }
+        let reader = Self {
+            msg: self.msg,
+            pos: self.pos,
+            end: self.pos + len,
+            followed_pointer: false,
+        };
+        self.pos += len;
+        Ok(reader)
+    }
+
+    /// Returns whether a compression pointer was followed while reading names.
+    pub(crate) fn followed_pointer(&self) -> bool {
+        self.followed_pointer
+    }
+
+    /// Returns the next `len` bytes without copying them.
+    ///
+    /// Returns an error if fewer than `len` bytes are left.
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], ParseError> {
+        if len > self.remaining_in_rdata() {
+            return Err(eof());
+        }
+        let bytes = &self.msg[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    /// Returns all bytes left in the part that is being read without copying them.
+    pub fn read_remaining(&mut self) -> &'a [u8] {
+        let bytes = &self.msg[self.pos..self.end];
+        self.pos = self.end;
+        bytes
+    }
+
+    /// Parses a `Name` encoded as a DNS QNAME, following compression pointers if `compression` is
+    /// [`Compression::Allowed`].
+    ///
+    /// Returns an error if the name is compressed although `compression` is
+    /// [`Compression::Prohibited`], if a compression pointer does not point backwards, or if the
+    /// name is truncated.
+    pub fn read_name(&mut self, compression: Compression) -> Result<Name, ParseError> {
+        let mut labels = Labels::new();
+        // where to read the next length byte, and up to where
+        let mut pos = self.pos;
+        let mut end = self.end;
+        // compression pointers must point before the start of the labels that are being read
+        let mut segment_start = self.pos;
+        // where this reader continues after the name, once a pointer was followed
+        let mut pos_after_pointer = None;
+
+        loop {
+            let c = *self.msg[..end].get(pos).ok_or_else(eof)?; // length of next label
+            pos += 1;
+
+            if c == 0 {
+                break;
+            } else if (c & 0b11000000) == 0b11000000 {
+                if compression == Compression::Prohibited {
+                    return Err(ParseError::CompressionProhibited);
+                }
+
+                // erase upper two bits of c for offset calculation
+                let low = *self.msg[..end].get(pos).ok_or_else(eof)?;
+                pos += 1;
+                let offset = (((c & 0b00111111) as usize) << 8) + low as usize;
+                if offset >= segment_start {
+                    return Err(ParseError::InvalidCompressionPointer(offset as u16));
+                }
+
+                pos_after_pointer.get_or_insert(pos);
+                pos = offset;
+                segment_start = offset;
+                end = self.msg.len();
+            } else if (c & 0b11000000) != 0 {
+                return Err(ParseError::InvalidLabelType(c));
+            } else {
+                let label = self.msg[..end].get(pos..pos + c as usize).ok_or_else(eof)?;
+                pos += c as usize;
+                // bytes outside of ASCII are interpreted as Latin-1
+                let label = match std::str::from_utf8(label) {
+                    Ok(label) if label.is_ascii() => SmartString::from(label),
+                    _ => label.iter().map(|&b| b as char).collect(),
+                };
+                labels.push(label);
+            }
+        }
+
+        self.followed_pointer |= pos_after_pointer.is_some();
+        self.pos = pos_after_pointer.unwrap_or(pos);
+        Ok(Name::from_labels(labels))
+    }
+}
+
+impl Read for MessageReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.remaining_in_rdata());
+        buf[..len].copy_from_slice(&self.msg[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+/// Returns the error for reading past the end of the part that is being read.
+fn eof() -> ParseError {
+    io::Error::from(ErrorKind::UnexpectedEof).into()
+}
@@ -0,0 +1,325 @@
+//! Metadata about the record types toluol knows, e.g. for explaining them to people who are
+//! learning DNS. See [`RecordType::info()`].
+
+use crate::RecordType;
+
+/// Metadata about a [`RecordType`].
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct TypeInfo {
+    pub rtype: RecordType,
+    /// A one-line, plain-English explanation of what records of this type are for.
+    pub summary: &'static str,
+    /// The number of the RFC that defines the type.
+    pub rfc: u16,
+    /// The notable fields of the RDATA, in presentation order.
+    pub fields: &'static [FieldInfo],
+}
+
+/// Metadata about a field of the RDATA of a [`RecordType`], see [`TypeInfo`].
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct FieldInfo {
+    /// The name of the field as used in RFCs.
+    pub name: &'static str,
+    /// A one-line, plain-English explanation of the field.
+    pub summary: &'static str,
+}
+
+macro_rules! fields {
+    ($($name:literal => $summary:literal),* $(,)?) => {
+        &[$(FieldInfo { name: $name, summary: $summary }),*]
+    };
+}
+
+/// The metadata of all known record types.
+pub const TYPES: &[TypeInfo] = &[
+    TypeInfo {
+        rtype: RecordType::A,
+        summary: "IPv4 address of the name",
+        rfc: 1035,
+        fields: &[],
+    },
+    TypeInfo {
+        rtype: RecordType::NS,
+        summary: "authoritative nameserver of the zone",
+        rfc: 1035,
+        fields: &[],
+    },
+    TypeInfo {
+        rtype: RecordType::CNAME,
+        summary: "alias: the name is another name for the canonical name given here",
+        rfc: 1035,
+        fields: &[],
+    },
+    TypeInfo {
+        rtype: RecordType::SOA,
+        summary: "start of authority: administrative information about the zone",
+        rfc: 1035,
+        fields: fields! {
+            "mname" => "primary nameserver of the zone",
+            "rname" => "email address of the zone's administrator, with the @ replaced by a dot",
+            "serial" => "version number of the zone, increased on every change",
+            "refresh" => "seconds after which secondary nameservers check for a new serial",
+            "retry" => "seconds after which secondaries retry a failed refresh",
+            "expire" => "seconds after which secondaries stop answering if refreshes keep failing",
+            "minimum" => "seconds for which resolvers cache negative answers",
+        },
+    },
+    TypeInfo {
+        rtype: RecordType::PTR,
+        summary: "pointer to another name, e.g. the host name of an IP address (reverse DNS)",
+        rfc: 1035,
+        fields: &[],
+    },
+    TypeInfo {
+        rtype: RecordType::HINFO,
+        summary: "host information: CPU and operating system (mostly obsolete)",
+        rfc: 1035,
+        fields: fields! {
+            "cpu" => "CPU type of the host",
+            "os" => "operating system of the host",
+        },
+    },
+    TypeInfo {
+        rtype: RecordType::MX,
+        summary: "mail exchanger: a server that accepts email for the name",
+        rfc: 1035,
+        fields: fields! {
+            "preference" => "lower values are tried first",
+            "exchange" => "host name of the mail server",
+        },
+    },
+    TypeInfo {
+        rtype: RecordType::TXT,
+        summary: "free-form text, e.g. for SPF policies or domain ownership verification",
+        rfc: 1035,
+        fields: &[],
+    },
+    TypeInfo {
+        rtype: RecordType::RP,
+        summary: "responsible person for the name",
+        rfc: 1183,
+        fields: fields! {
+            "mbox" => "email address of the person, with the @ replaced by a dot",
+            "txt" => "name of TXT records with further information",
+        },
+    },
+    TypeInfo {
+        rtype: RecordType::AAAA,
+        summary: "IPv6 address of the name",
+        rfc: 3596,
+        fields: &[],
+    },
+    TypeInfo {
+        rtype: RecordType::LOC,
+        summary: "geographical location of the name",
+        rfc: 1876,
+        fields: fields! {
+            "size" => "diameter of a sphere enclosing the entity",
+            "latitude" => "latitude of the entity's center",
+            "longitude" => "longitude of the entity's center",
+            "altitude" => "altitude of the entity's center",
+        },
+    },
+    TypeInfo {
+        rtype: RecordType::SRV,
+        summary: "location of a service: which host and port to connect to",
+        rfc: 2782,
+        fields: fields! {
+            "priority" => "lower values are tried first",
+            "weight" => "share of connections among targets with the same priority",
+            "port" => "port the service listens on",
+            "target" => "host name of the server",
+        },
+    },
+    TypeInfo {
+        rtype: RecordType::NAPTR,
+        summary: "naming authority pointer: rewrite rules, e.g. from phone numbers to URIs",
+        rfc: 3403,
+        fields: fields! {
+            "order" => "lower values must be processed first",
+            "preference" => "lower values are preferred among records with the same order",
+            "flags" => "how to continue after this record",
+            "services" => "protocols and services the result is for",
+            "regexp" => "substitution applied to the original string",
+            "replacement" => "next name to look up",
+        },
+    },
+    TypeInfo {
+        rtype: RecordType::CERT,
+        summary: "certificate or certificate revocation list",
+        rfc: 4398,
+        fields: fields! {
+            "type" => "kind of certificate, e.g. PKIX or PGP",
+            "key tag" => "identifies the key of the certificate",
+            "algorithm" => "algorithm of the key",
+        },
+    },
+    TypeInfo {
+        rtype: RecordType::DNAME,
+        summary: "alias for a whole subtree: names below the name map to names below the target",
+        rfc: 6672,
+        fields: &[],
+    },
+    TypeInfo {
+        rtype: RecordType::OPT,
+        summary: "EDNS pseudo-record with options for this message, not part of any zone",
+        rfc: 6891,
+        fields: &[],
+    },
+    TypeInfo {
+        rtype: RecordType::DS,
+        summary:
+            "delegation signer: hash of a child zone's DNSKEY, linking the DNSSEC chain of trust",
+        rfc: 4034,
+        fields: fields! {
+            "key tag" => "identifies the DNSKEY the hash is for",
+            "algorithm" => "algorithm of that DNSKEY",
+            "digest type" => "hash function used, e.g. 2 for SHA-256",
+            "digest" => "hash of the child zone's DNSKEY",
+        },
+    },
+    TypeInfo {
+        rtype: RecordType::SSHFP,
+        summary: "fingerprint of an SSH host key, for verifying it without trust on first use",
+        rfc: 4255,
+        fields: fields! {
+            "algorithm" => "algorithm of the host key",
+            "fingerprint type" => "hash function used for the fingerprint",
+        },
+    },
+    TypeInfo {
+        rtype: RecordType::RRSIG,
+        summary: "DNSSEC signature over the records of one type for the name",
+        rfc: 4034,
+        fields: fields! {
+            "type covered" => "type of the records that are signed",
+            "algorithm" => "algorithm of the signature",
+            "labels" => "number of labels of the signed name, revealing wildcards",
+            "original TTL" => "TTL of the records when they were signed",
+            "expiration" => "time after which the signature is invalid",
+            "inception" => "time before which the signature is invalid",
+            "key tag" => "identifies the DNSKEY that made the signature",
+            "signer" => "zone whose DNSKEY made the signature",
+        },
+    },
+    TypeInfo {
+        rtype: RecordType::NSEC,
+        summary:
+            "authenticated denial of existence: the next name in the zone and the types of this one",
+        rfc: 4034,
+        fields: fields! {
+            "next domain name" => "next name in the zone; no names exist in between",
+            "types" => "record types that exist for the name",
+        },
+    },
+    TypeInfo {
+        rtype: RecordType::DNSKEY,
+        summary: "public key of a zone, used to verify its DNSSEC signatures",
+        rfc: 4034,
+        fields: fields! {
+            "flags" => "256 for a zone signing key, 257 for a key signing key",
+            "protocol" => "always 3",
+            "algorithm" => "algorithm of the key, e.g. 13 for ECDSA P-256 with SHA-256",
+        },
+    },
+    TypeInfo {
+        rtype: RecordType::NSEC3,
+        summary: "hashed authenticated denial of existence: like NSEC, but for hashed names",
+        rfc: 5155,
+        fields: fields! {
+            "hash algorithm" => "hash function used for the names",
+            "flags" => "1 if unsigned delegations may be skipped (opt-out)",
+            "iterations" => "additional hash iterations, should be 0",
+            "salt" => "value appended to names before hashing, should be empty",
+            "next hashed owner" => "next hashed name in the zone",
+            "types" => "record types that exist for the name",
+        },
+    },
+    TypeInfo {
+        rtype: RecordType::NSEC3PARAM,
+        summary: "NSEC3 parameters the zone's nameservers use to compute hashed names",
+        rfc: 5155,
+        fields: fields! {
+            "hash algorithm" => "hash function used for the names",
+            "iterations" => "additional hash iterations, should be 0",
+            "salt" => "value appended to names before hashing, should be empty",
+        },
+    },
+    TypeInfo {
+        rtype: RecordType::TLSA,
+        summary: "TLS certificate association (DANE): which certificate a TLS service must present",
+        rfc: 6698,
+        fields: fields! {
+            "usage" => "how the certificate is checked, e.g. 3 for the server's own certificate",
+            "selector" => "0 for the full certificate, 1 for its public key only",
+            "matching type" => "0 for the exact data, 1 for SHA-256, 2 for SHA-512",
+        },
+    },
+    TypeInfo {
+        rtype: RecordType::CDS,
+        summary: "child DS: the DS record the zone wants its parent to publish",
+        rfc: 7344,
+        fields: &[],
+    },
+    TypeInfo {
+        rtype: RecordType::CDNSKEY,
+        summary: "child DNSKEY: the key the zone wants its parent to publish a DS record for",
+        rfc: 7344,
+        fields: &[],
+    },
+    TypeInfo {
+        rtype: RecordType::OPENPGPKEY,
+        summary: "OpenPGP public key of an email address",
+        rfc: 7929,
+        fields: &[],
+    },
+    TypeInfo {
+        rtype: RecordType::SVCB,
+        summary: "service binding: which endpoints provide a service, and how to connect to them",
+        rfc: 9460,
+        fields: fields! {
+            "priority" => "0 for an alias to the target, otherwise lower values are tried first",
+            "target" => "name of the alias or the endpoint, . for the owner itself",
+            "params" => "connection parameters, e.g. alpn, port, ipv4hint, or ipv6hint",
+        },
+    },
+    TypeInfo {
+        rtype: RecordType::HTTPS,
+        summary: "service binding for HTTPS: which endpoints serve a website, and how to connect",
+        rfc: 9460,
+        fields: fields! {
+            "priority" => "0 for an alias to the target, otherwise lower values are tried first",
+            "target" => "name of the alias or the endpoint, . for the owner itself",
+            "params" => "connection parameters, e.g. alpn, port, ipv4hint, or ipv6hint",
+        },
+    },
+    TypeInfo {
+        rtype: RecordType::CAA,
+        summary:
+            "certification authority authorization: which CAs may issue certificates for the name",
+        rfc: 8659,
+        fields: fields! {
+            "flags" => "128 if CAs must understand the tag to issue certificates",
+            "tag" => "kind of property, e.g. issue, issuewild, or iodef",
+            "value" => "value of the property, e.g. the CA's domain",
+        },
+    },
+];
+
+impl RecordType {
+    /// Returns the metadata of the type, or [`None`] for unknown types.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::RecordType;
+    ///
+    /// let info = RecordType::SOA.info().unwrap();
+    /// assert_eq!(info.rfc, 1035);
+    /// let serial = info.fields.iter().find(|field| field.name == "serial").unwrap();
+    /// assert_eq!(serial.summary, "version number of the zone, increased on every change");
+    /// assert!(RecordType::Unknown(65280).info().is_none());
+    /// ```
+    pub fn info(&self) -> Option<&'static TypeInfo> {
+        TYPES.iter().find(|info| info.rtype == *self)
+    }
+}
@@ -0,0 +1,44 @@
+//! Helpers for the serde representation of RDATA fields that have no natural JSON equivalent.
+//!
+//! Binary data is serialized as an object like `{"base64": "AAEC"}`, so that it is neither confused
+//! with text nor bloated into an array of numbers. Timestamps are serialized as ISO 8601 strings in
+//! UTC.
+
+use chrono::{SecondsFormat, TimeZone, Utc};
+use data_encoding::BASE64;
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+
+/// Binary data that serializes to `{"base64": "..."}`.
+pub(crate) struct Base64<'a>(pub(crate) &'a [u8]);
+
+impl Serialize for Base64<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry("base64", &BASE64.encode(self.0))?;
+        map.end()
+    }
+}
+
+/// Serializes `bytes` as [`Base64`].
+pub(crate) fn base64<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    Base64(bytes).serialize(serializer)
+}
+
+/// Serializes `bytes` as [`Base64`], or as `null` if there are none.
+pub(crate) fn option_base64<S: Serializer>(
+    bytes: &Option<Vec<u8>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    bytes.as_deref().map(Base64).serialize(serializer)
+}
+
+/// Serializes a timestamp in seconds since 1970 (like the ones in `RRSIG` records) as an ISO 8601
+/// string, e.g. `2022-09-18T12:00:00Z`.
+pub(crate) fn timestamp<S: Serializer>(timestamp: &u32, serializer: S) -> Result<S::Ok, S::Error> {
+    match Utc.timestamp_opt(*timestamp as i64, 0).single() {
+        Some(time) => serializer.serialize_str(&time.to_rfc3339_opts(SecondsFormat::Secs, true)),
+        // unreachable, as all u32 values are in range
+        None => serializer.serialize_u32(*timestamp),
+    }
+}
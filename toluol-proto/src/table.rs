@@ -0,0 +1,249 @@
+//! Rendering records as aligned lines, like in the sections of [`Message::as_string()`](
+//! crate::Message::as_string()), and ordering them for display.
+//!
+//! [`RecordTable`] requires the `presentation` feature.
+
+#[cfg(feature = "presentation")]
+use std::cmp::max;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use strum_macros::EnumString;
+
+use crate::NonOptRecord;
+#[cfg(feature = "presentation")]
+use crate::{Name, Question};
+#[cfg(feature = "presentation")]
+use std::borrow::Cow;
+
+/// Renders records (and questions) as lines whose columns are aligned, by padding the owner and
+/// type columns.
+///
+/// The column widths are computed from the records passed to [`Self::fit_records()`] and
+/// [`Self::fit_questions()`], and can be overridden by setting [`Self::owner_width`] and
+/// [`Self::type_width`] directly.
+///
+/// # Examples
+/// ```rust
+/// use toluol_proto::rdata::A;
+/// use toluol_proto::table::RecordTable;
+/// use toluol_proto::{Class, Name, NonOptRecord, Rdata};
+///
+/// let record = |name, ttl, address: [u8; 4]| {
+///     let rdata = Rdata::A(A::from(std::net::Ipv4Addr::from(address)));
+///     NonOptRecord::new(Name::from_ascii(name).unwrap(), Class::IN, ttl, rdata).unwrap()
+/// };
+/// let records = [
+///     record("example.com", 3600, [192, 0, 2, 1]),
+///     record("www.example.com", 60, [192, 0, 2, 2]),
+/// ];
+///
+/// let mut table = RecordTable::new(None);
+/// table.fit_records(&records);
+/// let lines: Vec<_> = records.iter().map(|r| table.record_line(r)).collect();
+/// assert_eq!(lines[0], "example.com        3600  A  192.0.2.1");
+/// assert_eq!(lines[1], "www.example.com      60  A  192.0.2.2");
+///
+/// // internationalized names can be shown in Unicode form
+/// let records = [
+///     record("xn--bcher-kva.example", 300, [192, 0, 2, 3]),
+///     record("www.example", 300, [192, 0, 2, 4]),
+/// ];
+/// let mut table = RecordTable::new(None);
+/// table.unicode_names = true;
+/// table.fit_records(&records);
+/// assert_eq!(table.record_line(&records[0]), "bücher.example     300  A  192.0.2.3");
+/// assert_eq!(table.record_line(&records[1]), "www.example        300  A  192.0.2.4");
+/// ```
+#[cfg(feature = "presentation")]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RecordTable {
+    /// The width of the owner column.
+    pub owner_width: usize,
+    /// The width of the type column.
+    pub type_width: usize,
+    /// If [`Some`] and the specified output stream supports colours, the lines are colourized.
+    pub output: Option<owo_colors::Stream>,
+    /// Whether names (owners and names in the RDATA, but not e.g. the text of `TXT` records) are
+    /// shown with their internationalized labels in Unicode form, see
+    /// [`NonOptRecord::to_unicode_names()`]. The columns are padded by the number of characters.
+    pub unicode_names: bool,
+}
+
+#[cfg(feature = "presentation")]
+impl RecordTable {
+    /// Creates a table with empty columns, i.e. without padding.
+    pub fn new(output: Option<owo_colors::Stream>) -> Self {
+        Self {
+            owner_width: 0,
+            type_width: 0,
+            output,
+            unicode_names: false,
+        }
+    }
+
+    /// Widens the columns to fit `records`.
+    pub fn fit_records<'a>(&mut self, records: impl IntoIterator<Item = &'a NonOptRecord>) {
+        for record in records {
+            self.owner_width = max(self.owner_width, self.name_width(&record.owner));
+            self.type_width = max(self.type_width, record.rtype.to_string().len());
+        }
+    }
+
+    /// Widens the columns to fit `questions`.
+    pub fn fit_questions<'a>(&mut self, questions: impl IntoIterator<Item = &'a Question>) {
+        for question in questions {
+            self.owner_width = max(self.owner_width, self.name_width(&question.qname));
+            self.type_width = max(self.type_width, question.qtype.to_string().len());
+        }
+    }
+
+    /// Returns the width of `name` in the owner column.
+    fn name_width(&self, name: &Name) -> usize {
+        if self.unicode_names && name.is_idn() {
+            name.to_unicode_labels().to_string().chars().count()
+        } else {
+            name.string_len()
+        }
+    }
+
+    /// Returns `record` as it is displayed, see [`Self::unicode_names`].
+    fn displayed<'a>(&self, record: &'a NonOptRecord) -> Cow<'a, NonOptRecord> {
+        if self.unicode_names {
+            Cow::Owned(record.to_unicode_names())
+        } else {
+            Cow::Borrowed(record)
+        }
+    }
+
+    /// Renders `record` as a line, see [`NonOptRecord::as_string()`].
+    pub fn record_line(&self, record: &NonOptRecord) -> String {
+        self.displayed(record).as_string(
+            false,
+            Some(self.owner_width),
+            Some(self.type_width),
+            self.output,
+        )
+    }
+
+    /// Renders `record` like [`Self::record_line()`], but with a blank owner column if `previous`
+    /// (the record on the line before) has the same owner, like in zone files. Use
+    /// [`group_records()`] first so that the records of a record set are adjacent.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol_proto::rdata::TXT;
+    /// use toluol_proto::table::{group_records, RecordTable};
+    /// use toluol_proto::{Class, Name, NonOptRecord, Rdata};
+    ///
+    /// let record = |name, text: &str| {
+    ///     let rdata = Rdata::TXT(TXT { text: vec![text.to_string()] });
+    ///     NonOptRecord::new(Name::from_ascii(name).unwrap(), Class::IN, 300, rdata).unwrap()
+    /// };
+    /// let records = [
+    ///     record("example.com", "a"),
+    ///     record("www.example.com", "b"),
+    ///     record("example.com", "c"),
+    /// ];
+    ///
+    /// let mut table = RecordTable::new(None);
+    /// table.fit_records(&records);
+    /// let mut previous = None;
+    /// let mut lines = Vec::new();
+    /// for record in group_records(&records) {
+    ///     lines.push(table.grouped_line(record, previous));
+    ///     previous = Some(record);
+    /// }
+    /// assert_eq!(lines[0], "example.com         300  TXT  \"a\"");
+    /// assert_eq!(lines[1], "                    300  TXT  \"c\"");
+    /// assert_eq!(lines[2], "www.example.com     300  TXT  \"b\"");
+    /// ```
+    pub fn grouped_line(&self, record: &NonOptRecord, previous: Option<&NonOptRecord>) -> String {
+        self.displayed(record).padded_string(
+            false,
+            Some(self.owner_width),
+            Some(self.type_width),
+            self.output,
+            previous.is_some_and(|p| p.owner == record.owner),
+        )
+    }
+
+    /// Renders `question` as a line, see [`Question::as_padded_string()`].
+    pub fn question_line(&self, question: &Question) -> String {
+        if self.unicode_names {
+            let mut question = question.clone();
+            question.qname = question.qname.to_unicode_labels();
+            return question.as_padded_string(self.owner_width, self.output);
+        }
+        question.as_padded_string(self.owner_width, self.output)
+    }
+}
+
+/// Groups `records` by record set (i.e. by owner, class, and type), keeping the record sets in the
+/// order of their first record and the records of each set in their original order.
+pub fn group_records<'a>(
+    records: impl IntoIterator<Item = &'a NonOptRecord>,
+) -> Vec<&'a NonOptRecord> {
+    let mut groups: Vec<Vec<&NonOptRecord>> = Vec::new();
+    let mut indices = HashMap::new();
+    for record in records {
+        let key = (&record.owner, record.class, record.rtype);
+        let i = *indices.entry(key).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[i].push(record);
+    }
+    groups.into_iter().flatten().collect()
+}
+
+/// The order in which records are displayed, see [`Message::sort_records()`](
+/// crate::Message::sort_records()).
+///
+/// Sorting makes the output of repeated queries comparable, as nameservers often rotate the
+/// records of a record set.
+///
+/// # Examples
+/// ```rust
+/// use std::str::FromStr;
+///
+/// use toluol_proto::table::RecordOrder;
+///
+/// assert_eq!(RecordOrder::from_str("owner").unwrap(), RecordOrder::Owner);
+/// assert_eq!(RecordOrder::default(), RecordOrder::Original);
+/// ```
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum RecordOrder {
+    /// The order in which the records appear in the message.
+    #[default]
+    Original,
+    /// By owner name in canonical order (see [`Name`](crate::Name)), then by type, then by RDATA.
+    Owner,
+    /// By type, then by owner name and RDATA.
+    Type,
+    /// By RDATA, keeping the original order of records with the same RDATA.
+    Rdata,
+}
+
+impl RecordOrder {
+    /// Compares two records according to this order. Records are equal in [`Self::Original`]
+    /// order.
+    ///
+    /// RDATA is compared in its uncompressed wire format.
+    pub fn compare(self, a: &NonOptRecord, b: &NonOptRecord) -> Ordering {
+        let owner = || a.owner.cmp(&b.owner);
+        let rtype = || u16::from(a.rtype).cmp(&u16::from(b.rtype));
+        let rdata = || match (a.rdata.encode(), b.rdata.encode()) {
+            (Ok(a), Ok(b)) => a.cmp(&b),
+            // can't happen for parsed records
+            _ => Ordering::Equal,
+        };
+        match self {
+            Self::Original => Ordering::Equal,
+            Self::Owner => owner().then_with(rtype).then_with(rdata),
+            Self::Type => rtype().then_with(owner).then_with(rdata),
+            Self::Rdata => rdata(),
+        }
+    }
+}
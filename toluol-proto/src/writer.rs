@@ -0,0 +1,173 @@
+//! Definition and implementation of the [`MessageWriter`] type.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use byteorder::{NetworkEndian, WriteBytesExt};
+
+use crate::error::EncodeError;
+use crate::name::{Compression, Name};
+
+/// Compression pointers have 14 bits for the offset.
+const MAX_POINTER_OFFSET: usize = 0x3fff;
+
+/// Writes the parts of an encoded DNS message, e.g. [`Name`]s or
+/// [`Rdata`](crate::rdata::Rdata).
+///
+/// A `MessageWriter` remembers where it wrote which names, so that later names can be compressed
+/// by pointing to them (if created with [`Compression::Allowed`]). The RDLENGTH of a record can be
+/// reserved before writing the RDATA and patched afterwards (see
+/// [`reserve_rdlength()`](Self::reserve_rdlength())), so the RDATA does not need to be encoded
+/// separately first.
+///
+/// A `MessageWriter` created with [`size_only()`](Self::size_only()) does not store any bytes,
+/// but only counts them, e.g. to check whether a message fits into a UDP payload.
+///
+/// Numbers can be written via [`Write`], e.g. with [`byteorder::WriteBytesExt`].
+///
+/// # Examples
+/// ```rust
+/// use byteorder::{NetworkEndian, WriteBytesExt};
+/// use toluol_proto::name::Compression;
+/// use toluol_proto::writer::MessageWriter;
+/// use toluol_proto::Name;
+///
+/// let mut writer = MessageWriter::new(Compression::Allowed);
+/// writer.write_name(&Name::from_ascii("example.com").unwrap(), Compression::Allowed).unwrap();
+///
+/// // RDATA with a preference and "mail" + a pointer to "example.com"
+/// let rdlength = writer.reserve_rdlength().unwrap();
+/// writer.write_u16::<NetworkEndian>(10).unwrap();
+/// let exchange = Name::from_ascii("mail.example.com").unwrap();
+/// writer.write_name(&exchange, Compression::Allowed).unwrap();
+/// writer.patch_rdlength(rdlength).unwrap();
+///
+/// assert_eq!(writer.into_bytes(), b"\x07example\x03com\0\x00\x09\x00\x0a\x04mail\xc0\x00");
+/// ```
+#[derive(Clone, Debug)]
+pub struct MessageWriter {
+    /// The bytes written so far; stays empty for writers that only count bytes.
+    buf: Vec<u8>,
+    /// The number of bytes written so far.
+    len: usize,
+    /// Whether to store the written bytes.
+    emit: bool,
+    /// Whether names may be compressed.
+    compression: Compression,
+    /// The offsets of the names written so far, keyed by their encoding. Contains every suffix of
+    /// these names as well.
+    names: HashMap<Vec<u8>, u16>,
+}
+
+impl MessageWriter {
+    /// Creates a `MessageWriter` for a new message, which compresses names if `compression` is
+    /// [`Compression::Allowed`].
+    pub fn new(compression: Compression) -> Self {
+        Self {
+            buf: Vec::new(),
+            len: 0,
+            emit: true,
+            compression,
+            names: HashMap::new(),
+        }
+    }
+
+    /// Creates a `MessageWriter` that does not store any bytes, but only computes the size of the
+    /// message (see [`position()`](Self::position())).
+    pub fn size_only(compression: Compression) -> Self {
+        Self {
+            emit: false,
+            ..Self::new(compression)
+        }
+    }
+
+    /// Returns the number of bytes written so far, i.e. the position of the next byte relative to
+    /// the start of the message.
+    pub fn position(&self) -> usize {
+        self.len
+    }
+
+    /// Returns the bytes written so far. Always empty for writers created with
+    /// [`size_only()`](Self::size_only()).
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+
+    /// Encodes `name` as a DNS QNAME. If `compression` as well as the writer allow it, the name is
+    /// compressed by pointing to a name (or a suffix of one) written before.
+    ///
+    /// Returns an error if writing fails.
+    pub fn write_name(&mut self, name: &Name, compression: Compression) -> Result<(), EncodeError> {
+        if self.compression == Compression::Prohibited {
+            // nothing to remember, so the name can be written without encoding it separately
+            name.encode_into(self)?;
+            return Ok(());
+        }
+
+        let mut encoded = Vec::new();
+        name.encode_into(&mut encoded)?;
+
+        // every label starts a suffix of the name; the last byte is the root label
+        let mut start = 0;
+        while start < encoded.len() - 1 {
+            if self.compression == Compression::Allowed {
+                let suffix = &encoded[start..];
+                match self.names.get(suffix) {
+                    Some(&offset) if compression == Compression::Allowed => {
+                        self.write_u16::<NetworkEndian>(0b11000000_00000000 | offset)?;
+                        return Ok(());
+                    }
+                    Some(_) => {}
+                    None if self.len <= MAX_POINTER_OFFSET => {
+                        self.names.insert(suffix.to_vec(), self.len as u16);
+                    }
+                    None => {}
+                }
+            }
+
+            let label_end = start + 1 + encoded[start] as usize;
+            self.write_all(&encoded[start..label_end])?;
+            start = label_end;
+        }
+        self.write_u8(0)?;
+
+        Ok(())
+    }
+
+    /// Writes a placeholder for a record's RDLENGTH and returns its position, which must be passed
+    /// to [`patch_rdlength()`](Self::patch_rdlength()) once the RDATA has been written.
+    ///
+    /// Returns an error if writing fails.
+    pub fn reserve_rdlength(&mut self) -> Result<usize, EncodeError> {
+        let position = self.len;
+        self.write_u16::<NetworkEndian>(0)?;
+        Ok(position)
+    }
+
+    /// Sets the RDLENGTH reserved at `position` (see
+    /// [`reserve_rdlength()`](Self::reserve_rdlength())) to the number of bytes written since.
+    ///
+    /// Returns an error if more than 65535 bytes were written since.
+    pub fn patch_rdlength(&mut self, position: usize) -> Result<(), EncodeError> {
+        let rdlength = self.len - position - 2;
+        let rdlength = u16::try_from(rdlength).map_err(|_| EncodeError::RdataTooLong(rdlength))?;
+        if self.emit {
+            self.buf[position..position + 2].copy_from_slice(&rdlength.to_be_bytes());
+        }
+        Ok(())
+    }
+}
+
+impl Write for MessageWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.emit {
+            self.buf.extend_from_slice(buf);
+        }
+        self.len += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,140 @@
+//! Round-trip and golden-file tests for the wire messages in `testdata/`.
+//!
+//! Each `<case>.hex` file contains a hex-encoded DNS message. For each of them, the message must
+//! survive parse → encode → parse unchanged, the RDATA of its OPT record must be re-encoded
+//! byte-identically, its `Display` output must match `<case>.plain`, and its presentation format
+//! (and JSON representation, with the `serde` feature) must match `<case>.txt` (and
+//! `<case>.json`).
+//!
+//! Run the tests with `UPDATE_GOLDEN=1` to (re)create the golden files after an intended change of
+//! the output, and review the diff before committing.
+
+use std::env;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use toluol_proto::Message;
+
+fn testdata() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata")
+}
+
+/// Returns the names of all cases, sorted.
+fn cases() -> Vec<String> {
+    let mut cases: Vec<_> = fs::read_dir(testdata())
+        .expect("testdata directory is missing")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "hex"))
+        .map(|path| path.file_stem().unwrap().to_string_lossy().into_owned())
+        .collect();
+    cases.sort();
+    assert!(!cases.is_empty(), "testdata directory contains no cases");
+    cases
+}
+
+fn parse_case(case: &str) -> Message {
+    let hex = fs::read_to_string(testdata().join(format!("{}.hex", case))).unwrap();
+    let wire = data_encoding::HEXLOWER_PERMISSIVE
+        .decode(hex.trim().as_bytes())
+        .unwrap_or_else(|e| panic!("{}: invalid hex: {}", case, e));
+    Message::parse(&mut Cursor::new(&wire[..]))
+        .unwrap_or_else(|e| panic!("{}: could not parse message: {}", case, e))
+}
+
+/// Compares `actual` with the golden file `<case>.<ext>`, or overwrites the golden file if
+/// `UPDATE_GOLDEN` is set. Returns a description of the mismatch, if any.
+fn check_golden(case: &str, ext: &str, actual: &str) -> Option<String> {
+    let path = testdata().join(format!("{}.{}", case, ext));
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, actual).unwrap();
+        return None;
+    }
+    match fs::read_to_string(&path) {
+        Ok(expected) if expected == actual => None,
+        Ok(expected) => Some(format!(
+            "{} differs from {}:\n--- expected\n{}\n--- actual\n{}",
+            case,
+            path.display(),
+            expected,
+            actual
+        )),
+        Err(e) => Some(format!(
+            "{}: could not read {}: {}",
+            case,
+            path.display(),
+            e
+        )),
+    }
+}
+
+#[test]
+fn round_trip() {
+    for case in cases() {
+        let msg = parse_case(&case);
+        let encoded = msg
+            .encode()
+            .unwrap_or_else(|e| panic!("{}: could not encode message: {}", case, e));
+        let reparsed = Message::parse(&mut Cursor::new(&encoded[..]))
+            .unwrap_or_else(|e| panic!("{}: could not parse encoded message: {}", case, e));
+        assert_eq!(msg, reparsed, "{}: message changed after round trip", case);
+        assert_eq!(
+            encoded,
+            reparsed.encode().unwrap(),
+            "{}: encoding is not stable",
+            case
+        );
+        // EDNS options must be kept as they are, including their order and repeated options
+        if let Some(opt) = msg.opt() {
+            // the encoded record contains the RDATA as it was parsed, after the root name (1
+            // byte), type, class, TTL, and RDLENGTH (10 bytes)
+            assert_eq!(
+                opt.encode().unwrap()[11..],
+                opt.rdata().encode().unwrap(),
+                "{}: OPT RDATA changed after round trip",
+                case
+            );
+        }
+    }
+}
+
+#[test]
+fn golden_display() {
+    let mismatches: Vec<_> = cases()
+        .iter()
+        .filter_map(|case| {
+            let text = format!("{}\n", parse_case(case));
+            check_golden(case, "plain", &text)
+        })
+        .collect();
+    assert!(mismatches.is_empty(), "{}", mismatches.join("\n\n"));
+}
+
+#[cfg(feature = "presentation")]
+#[test]
+fn golden_presentation_format() {
+    let mismatches: Vec<_> = cases()
+        .iter()
+        .filter_map(|case| {
+            let text = format!("{}\n", parse_case(case).as_string(None));
+            check_golden(case, "txt", &text)
+        })
+        .collect();
+    assert!(mismatches.is_empty(), "{}", mismatches.join("\n\n"));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn golden_json() {
+    let mismatches: Vec<_> = cases()
+        .iter()
+        .filter_map(|case| {
+            let json = format!(
+                "{}\n",
+                serde_json::to_string_pretty(&parse_case(case)).unwrap()
+            );
+            check_golden(case, "json", &json)
+        })
+        .collect();
+    assert!(mismatches.is_empty(), "{}", mismatches.join("\n\n"));
+}
@@ -0,0 +1,109 @@
+//! Property tests for encoding/parsing and displaying/parsing the protocol types, using the
+//! generators from the `arbitrary` module.
+//!
+//! Only run with the `arbitrary` feature.
+
+#![cfg(feature = "arbitrary")]
+
+use std::fmt::{Debug, Display};
+use std::io::Cursor;
+use std::str::FromStr;
+
+use arbitrary::{Arbitrary, Unstructured};
+use proptest::prelude::*;
+use toluol_proto::reader::MessageReader;
+use toluol_proto::{Class, Header, Message, Name, Opcode, Question, RCode, Record, RecordType};
+
+/// Generates a `T` from random bytes.
+fn arb<T: for<'a> Arbitrary<'a> + Debug>() -> impl Strategy<Value = T> {
+    prop::collection::vec(any::<u8>(), 0..4096)
+        .prop_map(|bytes| T::arbitrary(&mut Unstructured::new(&bytes)).unwrap())
+}
+
+/// Asserts that `value` is unchanged after displaying and parsing it.
+fn assert_display_round_trip<T>(value: T)
+where
+    T: Display + FromStr + PartialEq + Debug,
+    T::Err: Debug,
+{
+    assert_eq!(value.to_string().parse::<T>().unwrap(), value);
+}
+
+#[test]
+fn record_type() {
+    // there are few enough types to check all of them
+    for rtype in (0..=u16::MAX).map(RecordType::from) {
+        // unknown types are displayed in the format of RFC 3597, which can't be parsed
+        if !matches!(rtype, RecordType::Unknown(_)) {
+            assert_display_round_trip(rtype);
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn message(msg in arb::<Message>()) {
+        let encoded = msg.encode().unwrap();
+        let parsed = Message::parse(&mut Cursor::new(&encoded[..])).unwrap();
+        prop_assert_eq!(&parsed, &msg);
+        prop_assert_eq!(parsed.encode().unwrap(), encoded);
+        // displaying must not panic
+        #[cfg(feature = "presentation")]
+        msg.as_string(None);
+    }
+
+    #[test]
+    fn header(header in arb::<Header>()) {
+        let encoded = header.encode().unwrap();
+        prop_assert_eq!(Header::parse(&mut MessageReader::new(&encoded)).unwrap(), header);
+    }
+
+    #[test]
+    fn question(question in arb::<Question>()) {
+        let encoded = question.encode().unwrap();
+        let parsed = Question::parse(&mut MessageReader::new(&encoded)).unwrap();
+        prop_assert_eq!(parsed, question);
+    }
+
+    #[test]
+    fn record(record in arb::<Record>()) {
+        let encoded = record.encode().unwrap();
+        // generated OPT records have no extended RCODE
+        let parsed = Record::parse(&mut MessageReader::new(&encoded), Some(RCode::NOERROR)).unwrap();
+        prop_assert_eq!(parsed, record);
+    }
+
+    #[test]
+    fn opt_rdata(
+        options in prop::collection::vec(
+            (any::<u16>(), prop::collection::vec(any::<u8>(), 0..16)),
+            0..8,
+        ),
+    ) {
+        // the order of the options and repeated options must be kept
+        let mut encoded = Vec::new();
+        for (code, value) in options {
+            encoded.extend(code.to_be_bytes());
+            encoded.extend((value.len() as u16).to_be_bytes());
+            encoded.extend(value);
+        }
+        let mut reader = MessageReader::new(&encoded);
+        let rdata = Record::parse_rdata(&RecordType::OPT, &mut reader).unwrap();
+        prop_assert_eq!(rdata.encode().unwrap(), encoded);
+    }
+
+    #[test]
+    fn name(name in arb::<Name>()) {
+        prop_assert_eq!(Name::from_ascii(name.to_string()).unwrap(), name);
+    }
+
+    #[test]
+    fn class(class in arb::<Class>()) {
+        assert_display_round_trip(class);
+    }
+
+    #[test]
+    fn opcode(opcode in arb::<Opcode>()) {
+        assert_display_round_trip(opcode);
+    }
+}
@@ -0,0 +1,306 @@
+//! A small HTTP API that resolves names with a [`Resolver`], so that other tools and dashboards
+//! can use toluol as a resolution service (`serve-api`), see [`ApiServer`].
+
+use serde_json::{json, Value};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use toluol_proto::dnssec::{ValidationPolicy, ValidationState};
+use toluol_proto::{Message, Name, RCode, Record, RecordType};
+use url::Url;
+
+use crate::cache::{AnswerCache, CacheKey};
+use crate::error::Result;
+use crate::resolver::Resolver;
+use crate::util::validate_answer;
+
+/// How long a client may take to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The maximum size of a request (the request line and the headers), in bytes.
+const MAX_REQUEST_SIZE: u64 = 8192;
+
+/// Answers `GET /resolve?name=<name>&type=<type>` requests with the answer of its resolver as
+/// JSON. The type defaults to `A`.
+///
+/// Answers are cached for as long as their TTLs allow (see [`AnswerCache`]). With
+/// [`Self::with_validation()`], the answers are validated with DNSSEC, using the DNSKEYs cached by
+/// the resolver (see [`Resolver::dnskeys()`]); the resolver's queries must then set the DO bit,
+/// e.g. with [`QueryMetadata::fetch_dnssec`](crate::QueryMetadata::fetch_dnssec).
+///
+/// A successful response looks like this, with `dnssec` and `rrsets` only if the answers are
+/// validated:
+///
+/// ```json
+/// {
+///   "name": "example.com",
+///   "type": "A",
+///   "rcode": "NOERROR",
+///   "nameserver": "192.0.2.53",
+///   "cached": true,
+///   "age": 12,
+///   "answers": [{ "name": "example.com", "type": "A", "ttl": 288, "data": "192.0.2.1" }],
+///   "dnssec": "secure",
+///   "rrsets": [{ "name": "example.com", "type": "A", "state": "secure" }]
+/// }
+/// ```
+///
+/// `dnssec` is the worst state of the validated record sets (`insecure` if there are none). As
+/// with `+validate`, the chain of trust of the DNSKEYs is not checked. Errors are returned as
+/// `{ "error": "<message>" }` with status 400 for invalid requests and 502 if the name could not
+/// be resolved.
+#[derive(Debug)]
+pub struct ApiServer {
+    resolver: Resolver,
+    cache: AnswerCache,
+    policy: Option<ValidationPolicy>,
+}
+
+impl ApiServer {
+    /// Creates a server that resolves names with `resolver`.
+    pub fn new(resolver: Resolver) -> Self {
+        Self {
+            resolver,
+            cache: AnswerCache::default(),
+            policy: None,
+        }
+    }
+
+    /// Validates the answers with DNSSEC according to `policy`, and includes the results in the
+    /// responses.
+    pub fn with_validation(mut self, policy: ValidationPolicy) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// Returns the cache of the answers.
+    pub fn cache(&self) -> &AnswerCache {
+        &self.cache
+    }
+
+    /// Answers the requests of the clients connecting to `listener`, each connection in its own
+    /// thread. Only returns if `listener` does.
+    pub fn serve(self: Arc<Self>, listener: TcpListener) {
+        // errors (e.g. too many open files) only affect a single connection
+        for stream in listener.incoming().flatten() {
+            let server = self.clone();
+            thread::spawn(move || server.handle_connection(stream));
+        }
+    }
+
+    /// Answers the request `target` (the path and query of the URL), and returns the HTTP status
+    /// code and the body of the response.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol::api::ApiServer;
+    /// use toluol::resolver::{Resolver, ResolverConfig};
+    /// # use std::time::Duration;
+    /// # use toluol::net::TcpOptions;
+    /// # use toluol::{ConnectionType, QueryMetadata};
+    /// # use toluol_proto::{Class, Name, Opcode, RecordType};
+    /// # let metadata = QueryMetadata {
+    /// #     name: Name::root(),
+    /// #     qtype: RecordType::A,
+    /// #     qclass: Class::IN,
+    /// #     opcode: Opcode::QUERY,
+    /// #     nameserver: "192.0.2.1".into(),
+    /// #     port: 53,
+    /// #     connection_type: ConnectionType::Udp,
+    /// #     fetch_dnssec: false,
+    /// #     validate_dnssec: false,
+    /// #     dnssec_ok: None,
+    /// #     checking_disabled: true,
+    /// #     edns: true,
+    /// #     edns_version: 0,
+    /// #     edns_options: Vec::new(),
+    /// #     client_cookie: None,
+    /// #     client_subnet: None,
+    /// #     check_source: true,
+    /// #     timeout: Duration::from_secs(1),
+    /// #     retransmissions: 0,
+    /// #     reuse_udp_sockets: false,
+    /// #     tcp_options: TcpOptions::default(),
+    /// #     doh_json_fallback: false,
+    /// #     query_log: None,
+    /// # };
+    ///
+    /// let resolver = Resolver::new(ResolverConfig::default(), metadata, 1232);
+    /// let server = ApiServer::new(resolver);
+    /// let (status, body) = server.handle("/resolve?type=A");
+    /// assert_eq!(status, 400);
+    /// assert_eq!(body["error"], "Missing parameter: name.");
+    /// assert_eq!(server.handle("/").0, 404);
+    /// ```
+    pub fn handle(&self, target: &str) -> (u16, Value) {
+        let url = match Url::parse("http://localhost").and_then(|base| base.join(target)) {
+            Ok(url) => url,
+            Err(e) => return error(400, format!("Invalid URL: {}.", e)),
+        };
+        if url.path() != "/resolve" {
+            return error(404, "Not found.");
+        }
+
+        let param = |key: &str| {
+            url.query_pairs()
+                .find(|(k, _)| k == key)
+                .map(|(_, value)| value.into_owned())
+        };
+        let name = match param("name").map(|name| Name::from_ascii(&name)) {
+            Some(Ok(name)) => name,
+            Some(Err(e)) => return error(400, format!("Invalid name: {}.", e)),
+            None => return error(400, "Missing parameter: name."),
+        };
+        let qtype = match param("type").map(|qtype| RecordType::from_str(&qtype)) {
+            Some(Ok(qtype)) => qtype,
+            Some(Err(_)) => return error(400, "Invalid type."),
+            None => RecordType::A,
+        };
+
+        match self.resolve(&name, qtype) {
+            Ok(value) => (200, value),
+            Err(e) => error(502, format!("{:#}", e)),
+        }
+    }
+
+    /// Resolves `name` (from the cache, if possible) and describes the answer as JSON.
+    fn resolve(&self, name: &Name, qtype: RecordType) -> Result<Value> {
+        let mut metadata = self.resolver.metadata().clone();
+        metadata.name = name.clone();
+        metadata.qtype = qtype;
+        metadata.nameserver = self.resolver.config().nameservers.join(",");
+        let key = CacheKey::from_metadata(&metadata);
+
+        let (answer, age) = match self.cache.get(&key, Instant::now()) {
+            Some((answer, age)) => (answer, Some(age)),
+            None => {
+                let answer = self.resolver.query(name, qtype)?;
+                self.cache.insert(key, answer.clone(), Instant::now());
+                (answer, None)
+            }
+        };
+        let (nameserver, res, _, _) = &answer;
+
+        let mut value = json!({
+            "name": name.to_string(),
+            "type": qtype.to_string(),
+            "rcode": rcode(res).to_string(),
+            "nameserver": nameserver.ip.map(|ip| ip.to_string()),
+            "cached": age.is_some(),
+            "age": age.unwrap_or_default().as_secs(),
+            "answers": res
+                .answers
+                .iter()
+                .filter_map(Record::as_nonopt)
+                .map(|record| json!({
+                    "name": record.owner.to_string(),
+                    "type": record.rtype.to_string(),
+                    "ttl": record.ttl,
+                    "data": record.rdata().to_string(),
+                }))
+                .collect::<Vec<_>>(),
+        });
+        if let Some(policy) = &self.policy {
+            let (state, rrsets) = self.validate(name, res, policy)?;
+            value["dnssec"] = state.to_string().into();
+            value["rrsets"] = rrsets;
+        }
+        Ok(value)
+    }
+
+    /// Validates `res` with the DNSKEYs of the zone of `name` (or of the closest parent zone that
+    /// has DNSKEYs), and returns the worst state and the results for each record set.
+    fn validate(
+        &self,
+        name: &Name,
+        res: &Message,
+        policy: &ValidationPolicy,
+    ) -> Result<(ValidationState, Value)> {
+        let mut zone = name.clone();
+        let dnskeys = loop {
+            let dnskeys = self.resolver.dnskeys(&zone)?;
+            if !dnskeys.records.is_empty() || zone.is_root() {
+                break dnskeys.records;
+            }
+            zone.pop_front_label();
+        };
+
+        let validations = validate_answer(res, &dnskeys, policy);
+        let severity = |state: ValidationState| match state {
+            ValidationState::Secure => 0,
+            ValidationState::Insecure => 1,
+            ValidationState::Indeterminate => 2,
+            ValidationState::Bogus => 3,
+        };
+        let state = validations
+            .iter()
+            .map(|validation| validation.state)
+            .max_by_key(|&state| severity(state))
+            .unwrap_or(ValidationState::Insecure);
+        let rrsets = validations
+            .iter()
+            .map(|validation| {
+                json!({
+                    "name": validation.owner.to_string(),
+                    "type": validation.rtype.to_string(),
+                    "state": validation.state.to_string(),
+                })
+            })
+            .collect();
+        Ok((state, rrsets))
+    }
+
+    /// Reads a request from `stream` and writes the response.
+    fn handle_connection(&self, stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+        let mut reader = BufReader::new((&stream).take(MAX_REQUEST_SIZE));
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        // the headers don't matter
+        let mut line = String::new();
+        while reader.read_line(&mut line)? > 0 && !line.trim_end().is_empty() {
+            line.clear();
+        }
+
+        let (status, body) = match request_line.split_whitespace().collect::<Vec<_>>()[..] {
+            ["GET", target, _] => self.handle(target),
+            [_, _, _] => error(405, "Only GET requests are supported."),
+            _ => error(400, "Invalid request."),
+        };
+        let body = body.to_string();
+        let reason = match status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            _ => "Bad Gateway",
+        };
+        write!(
+            &stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+             Connection: close\r\n\r\n{}",
+            status,
+            reason,
+            body.len(),
+            body
+        )?;
+        (&stream).flush()
+    }
+}
+
+/// Returns the response for an error with the HTTP status code `status`.
+fn error(status: u16, message: impl Into<String>) -> (u16, Value) {
+    (status, json!({ "error": message.into() }))
+}
+
+/// Returns the response code of `res`, including the extended bits from its OPT record.
+fn rcode(res: &Message) -> RCode {
+    match res.opt() {
+        Some(opt) => opt.rcode,
+        None => res.header.rcode,
+    }
+    .unwrap_or(RCode::NOERROR)
+}
@@ -1,200 +1,1218 @@
 //! CLI argument definition and parsing.
 
+use std::cell::Cell;
 use std::env;
+use std::io::{self, Read};
 use std::net::IpAddr;
+#[cfg(feature = "json")]
+use std::net::SocketAddr;
+use std::panic;
 use std::process;
 use std::str::FromStr;
+use std::sync::Arc;
 
+use data_encoding::HEXLOWER_PERMISSIVE;
 use owo_colors::OwoColorize;
+use toluol::dns64::{Nat64Prefix, IPV4ONLY_NAME};
+#[cfg(feature = "geoip")]
+use toluol::geoip::GeoIp;
+#[cfg(feature = "json")]
+use toluol::hook::Hook;
+use toluol::hosts::HOSTS_FILE;
+use toluol::net::{TcpOptions, DEFAULT_UDP_RETRANSMISSIONS, DEFAULT_UDP_TIMEOUT};
+use toluol::probe::ProbeSpec;
+use toluol::querylog::QueryLog;
+use toluol::raw::MessageTemplate;
+use toluol::survey;
+use toluol::sweep::Network;
+use toluol::util::DEFAULT_BUFSIZE;
 use toluol::{ConnectionType, QueryMetadata};
-use toluol_proto::{Name, RecordType};
+use toluol_proto::dnssec::{Nsec3IterationPolicy, ValidationPolicy};
+use toluol_proto::rdata::opt::{ClientSubnet, OptionCode};
+use toluol_proto::table::RecordOrder;
+use toluol_proto::{Class, DisplaySections, Name, Opcode, RecordType};
 
 #[derive(Clone, Debug)]
 pub struct Args {
+    pub command: Command,
     pub nameserver: String,
     pub name: Name,
-    pub qtype: RecordType,
+    /// The record types to query for; never empty.
+    pub qtypes: Vec<RecordType>,
+    pub qclass: Class,
+    pub opcode: Opcode,
     pub verbose: bool,
     #[cfg(feature = "json")]
     pub json: bool,
     pub print_meta: bool,
+    /// Whether to print the records of the answers as CSV or TSV (`+csv`, `+tsv`) instead.
+    pub rows: Option<RowFormat>,
+    /// The sections of responses (and whether the comments) that are displayed in text output.
+    pub sections: DisplaySections,
     pub pad_answers: bool,
+    /// Whether to explain the types of the displayed records in plain language.
+    pub explain: bool,
+    pub idn_out: bool,
     pub fetch_dnssec: bool,
     pub validate_dnssec: bool,
+    /// The DO bit given with `+[no]dobit`, if any.
+    pub dnssec_ok: Option<bool>,
+    pub checking_disabled: bool,
+    /// Which cryptography `+validate` accepts and how it treats NSEC3 records with many hash
+    /// iterations.
+    pub validation_policy: ValidationPolicy,
     pub iterative: bool,
     pub connection_type: ConnectionType,
     pub port: u16,
     pub cookie: bool,
+    pub check_source: bool,
+    /// Whether UDP queries reuse sockets connected to the same nameserver, given with
+    /// `+reuse-udp`.
+    pub reuse_udp_sockets: bool,
+    /// The socket options given with `+nodelay`, `+tfo`, `+reuseaddr`, `+sndbuf`, and `+rcvbuf`.
+    pub tcp_options: TcpOptions,
+    /// The UDP payload size advertised with EDNS, which is also the size of the largest answer
+    /// that is accepted.
+    pub bufsize: u16,
+    /// Whether to use EDNS, i.e. to send an OPT record.
+    pub edns: bool,
+    /// The EDNS version to send.
+    pub edns_version: u8,
+    /// The raw EDNS options given with `+ednsopt`.
+    pub edns_options: Vec<(OptionCode, Vec<u8>)>,
+    /// Whether to use the system's nameservers and resolver options from `/etc/resolv.conf`.
+    pub system: bool,
+    /// The hosts files to consult before sending queries, in order; only used with `system`.
+    pub hosts_files: Vec<String>,
+    /// The file to load nameserver statistics from and save them to; only used with `system` and
+    /// `iterative`.
+    pub stats_file: Option<String>,
+    /// The order in which the records of responses are displayed.
+    pub sort: RecordOrder,
+    /// The log each query and its response are appended to, if any.
+    pub query_log: Option<Arc<QueryLog>>,
+    #[cfg(all(feature = "http", feature = "json"))]
+    pub rdap: bool,
+    /// Whether to use the JSON API of a DoH server that rejects the wire format.
+    #[cfg(all(feature = "http", feature = "json"))]
+    pub doh_json_fallback: bool,
+    /// The file to write the trace of an iterative query to as JSON, if any.
+    #[cfg(feature = "json")]
+    pub trace_json: Option<String>,
+    /// The command that is run with the JSON result of each query, if any.
+    #[cfg(feature = "json")]
+    pub hook: Option<Hook>,
+    /// The GeoIP databases used to annotate addresses in verbose and JSON output, if any.
+    #[cfg(feature = "geoip")]
+    pub geoip: Option<Arc<GeoIp>>,
+    /// The NAT64 prefixes with which synthesized `AAAA` records are recognized; always contains
+    /// the Well-Known Prefix first.
+    pub dns64_prefixes: Vec<Nat64Prefix>,
+    /// The EDNS Client Subnets given with `--subnet`; at most one unless the command is
+    /// [`Command::SplitHorizon`].
+    pub subnets: Vec<ClientSubnet>,
+    /// The number of queries per second sent by [`Command::PtrSweep`] (always limited) and
+    /// [`Command::Probe`] (only if given with `--rate`).
+    pub sweep_rate: Option<u32>,
+    /// The number of addresses per network looked up by [`Command::PtrSweep`]; larger networks
+    /// are sampled.
+    pub sweep_sample: usize,
+    /// Whether [`Command::PtrSweep`] checks that the host names map back to the addresses.
+    pub sweep_confirm: bool,
+}
+
+/// How `+csv` and `+tsv` print records, see [`toluol::resolver::RecordRow`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RowFormat {
+    Csv,
+    Tsv,
+}
+
+/// What toluol should do.
+#[derive(Clone, Debug)]
+pub enum Command {
+    /// Send a query and display the response (the default).
+    Query,
+    /// Decode the given wire format DNS message and display it.
+    Decode(Vec<u8>),
+    /// Send the query to each of the given nameservers and check whether they block the domain.
+    /// If the list is empty, a list of known filtering resolvers is used.
+    Blocklist(Vec<String>),
+    /// Find and evaluate the CAA records that apply to the domain.
+    CaaCheck,
+    /// Check the `CDS` and `CDNSKEY` records of the zone against the parent's `DS` records and
+    /// its `DNSKEY`s, and tell what an automated parent would do.
+    CdsCheck,
+    /// Fetch and check the mail security records (MX, SPF, DMARC, MTA-STS, TLSRPT) of the domain.
+    MailAudit,
+    /// Resolve the given E.164 number (without separators) to URIs with ENUM. The domain is the
+    /// number's domain under `e164.arpa`.
+    Enum(String),
+    /// Fetch the OpenPGP keys of the given email address from its `OPENPGPKEY` records. The
+    /// domain is the address's name under `_openpgpkey`.
+    PgpKey(String),
+    /// Look up the `PTR` records of the addresses in the given networks.
+    PtrSweep(Vec<Network>),
+    /// Send the queries of the given spec, expanded for each combination of the values of their
+    /// variables, and display the answers by substitution, see [`toluol::probe`].
+    Probe(ProbeSpec),
+    /// Compare the `A` and `AAAA` records of the domain to detect DNS64 and infer the NAT64
+    /// prefix the nameserver uses.
+    Dns64Check,
+    /// Send the query with different client subnets and transports to the recursive and the
+    /// authoritative nameservers and compare the answers.
+    SplitHorizon,
+    /// Send probe queries to the nameserver and report signs of interference by middleboxes on
+    /// the path to it.
+    MiddleboxCheck,
+    /// Send the message described by the template, which may be malformed on purpose, and display
+    /// the raw response.
+    Raw(MessageTemplate),
+    /// Query for each of the given record types and report which of them exist for the domain.
+    Survey(Vec<RecordType>),
+    /// Send the query again whenever a record of the answer expires, and count down the remaining
+    /// TTL of each record in the meantime.
+    Watch,
+    /// Answer HTTP requests on the given address with the results of queries as JSON, see
+    /// [`toluol::api::ApiServer`].
+    #[cfg(feature = "json")]
+    ServeApi(SocketAddr),
+    /// Read commands and queries from stdin and run them one after the other, with the given
+    /// arguments (without `-i`) as the defaults for each query.
+    Interactive(Vec<String>),
 }
 
-enum ConsumeNext {
-    Port,
+/// A single command line argument, classified by its prefix.
+enum RawArg<'a> {
+    /// `@nameserver`
+    Nameserver(&'a str),
+    /// `+flag` or `+flag=value`
+    Flag(&'a str, Option<&'a str>),
+    /// `-o`, `--option`, or `--option=value`
+    Opt(&'a str, Option<&'a str>),
+    /// Anything else, e.g. domain names and record types.
+    Positional(&'a str),
 }
 
+impl<'a> RawArg<'a> {
+    fn classify(arg: &'a str) -> Self {
+        if let Some(ns) = arg.strip_prefix('@') {
+            Self::Nameserver(ns)
+        } else if let Some(flag) = arg.strip_prefix('+') {
+            match flag.split_once('=') {
+                Some((flag, value)) => Self::Flag(flag, Some(value)),
+                None => Self::Flag(flag, None),
+            }
+        } else if let Some(option) = arg.strip_prefix("--") {
+            match option.split_once('=') {
+                Some((option, value)) => Self::Opt(option, Some(value)),
+                None => Self::Opt(option, None),
+            }
+        } else if arg.len() > 1 && arg.starts_with('-') {
+            Self::Opt(&arg[1..], None)
+        } else {
+            Self::Positional(arg)
+        }
+    }
+}
+
+/// The names of all subcommands. A subcommand must be the first positional argument.
+const SUBCOMMANDS: [&str; 15] = [
+    "decode",
+    "blocklist",
+    "caa-check",
+    "cds-check",
+    "mail-audit",
+    "middlebox-check",
+    "enum",
+    "pgpkey",
+    "probe",
+    "ptr-sweep",
+    "raw",
+    "serve-api",
+    "split-horizon",
+    "survey",
+    "watch",
+];
+
 const DEFAULT_NAMESERVER: &str = "ordns.he.net";
 const DEFAULT_URL: &str = "example.com.";
 const DEFAULT_QTYPE: RecordType = RecordType::AAAA;
+/// The default address the serve-api subcommand listens on.
+#[cfg(feature = "json")]
+const DEFAULT_API_ADDRESS: &str = "127.0.0.1:8053";
+/// The default number of queries per second for the ptr-sweep subcommand.
+const DEFAULT_SWEEP_RATE: u32 = 20;
+/// The default number of addresses per network looked up by the ptr-sweep subcommand.
+const DEFAULT_SWEEP_SAMPLE: usize = 256;
+/// The record types queried for with `+all-common`.
+const COMMON_QTYPES: [RecordType; 7] = [
+    RecordType::A,
+    RecordType::AAAA,
+    RecordType::MX,
+    RecordType::TXT,
+    RecordType::NS,
+    RecordType::SOA,
+    RecordType::CAA,
+];
+
+thread_local! {
+    /// Whether arguments are parsed with [`Args::try_parse_from()`], i.e. whether [`exit()`]
+    /// must unwind instead of exiting.
+    static TRYING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// The payload [`exit()`] unwinds with while [`TRYING`].
+struct Exit;
 
 impl Args {
     pub fn parse() -> Self {
         // skip executable name
-        let args: Vec<String> = env::args().skip(1).collect();
+        Self::parse_from(env::args().skip(1).collect())
+    }
+
+    /// Parses `args` like [`Self::parse()`], but returns [`None`] instead of exiting if they are
+    /// invalid (after printing why) or if they only asked for the help or version. This is used
+    /// for the lines of an interactive session.
+    pub fn try_parse_from(args: Vec<String>) -> Option<Self> {
+        TRYING.set(true);
+        let res = panic::catch_unwind(|| Self::parse_from(args));
+        TRYING.set(false);
+        match res {
+            Ok(args) => Some(args),
+            Err(payload) if payload.is::<Exit>() => None,
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+
+    fn parse_from(args: Vec<String>) -> Self {
+        let interactive_args: Vec<String> = args
+            .iter()
+            .filter(|arg| !matches!(arg.as_str(), "-i" | "--interactive"))
+            .cloned()
+            .collect();
 
         let mut nameserver = DEFAULT_NAMESERVER.into();
         let mut name = DEFAULT_URL.into();
-        let mut qtype = DEFAULT_QTYPE;
+        let mut qtypes = Vec::new();
+        let mut all_common = false;
+        let mut all_types = false;
+        let mut qclass = Class::IN;
+        let mut opcode = Opcode::QUERY;
         let mut verbose = false;
         #[cfg(feature = "json")]
         let mut json = false;
         let mut print_meta = true;
+        let mut rows = None;
+        let mut sections = DisplaySections::ALL;
         let mut pad_answers = true;
+        let mut group_rrsets = false;
+        let mut explain = false;
+        let mut idn_out = false;
         let mut fetch_dnssec = false;
         let mut validate_dnssec = false;
+        let mut dnssec_ok = None;
+        let mut checking_disabled = true;
+        let mut nsec3_policy = Nsec3IterationPolicy::default();
+        let mut validation_policy = None;
         let mut iterative = false;
         let mut connection_type = ConnectionType::Udp;
         let mut port = None;
         let mut cookie = false;
+        let mut bufsize = None;
+        let mut edns = true;
+        let mut edns_version = None;
+        let mut edns_options = Vec::new();
+        let mut check_source = true;
+        let mut reuse_udp_sockets = false;
+        let mut tcp_options = TcpOptions::default();
+        let mut system = false;
+        let mut hosts_files = Vec::new();
+        let mut stats_file = None;
+        let mut query_log_file = None;
+        let mut sort = RecordOrder::Original;
+        #[cfg(all(feature = "http", feature = "json"))]
+        let mut rdap = false;
+        #[cfg(all(feature = "http", feature = "json"))]
+        let mut doh_json_fallback = false;
+        #[cfg(feature = "json")]
+        let mut trace_json = None;
+        #[cfg(feature = "json")]
+        let mut hook = None;
+        #[cfg(feature = "json")]
+        let mut listen = None;
+        #[cfg(feature = "geoip")]
+        let mut geoip_dbs = Vec::new();
+        let mut dns64_prefixes = vec![Nat64Prefix::WELL_KNOWN];
+        let mut subnets = Vec::new();
+        let mut sweep_rate = None;
+        let mut sweep_sample = None;
+        let mut sweep_confirm = false;
+
+        let mut command = Command::Query;
+        let mut parse_stdin = false;
+        let mut interactive = false;
+        let mut dns64_check = false;
+        // all nameservers given, for the blocklist subcommand
+        let mut nameservers = Vec::new();
 
         // TODO infer that this a reverse query if the only CLI argument is an IPv4/IPv6 address?
         let mut reverse = false;
-        let mut consume_next = None;
-
-        for arg in args {
-            if let Some(to_consume) = &consume_next {
-                match to_consume {
-                    ConsumeNext::Port => match arg.parse::<u16>() {
-                        Ok(val) => port = Some(val),
-                        Err(_) => err(format!("Invalid port: {}.", arg)),
-                    },
+        let mut seen_positional = false;
+        let mut name_given = false;
+        // positional arguments that may be names or record types, classified after all arguments
+        // were seen
+        let mut bare_words = Vec::new();
+        let mut types_selected = false;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match RawArg::classify(arg) {
+                RawArg::Nameserver(ns) => {
+                    nameserver = ns.to_string();
+                    nameservers.push(nameserver.clone());
                 }
-                consume_next = None;
-            } else if let Some(ns) = arg.strip_prefix('@') {
-                // nameserver
-                nameserver = ns.to_string();
-            } else if let Some(flag) = arg.strip_prefix('+') {
-                // flags
-                match flag {
-                    "verbose" => {
-                        verbose = true;
-                    }
-                    #[cfg(feature = "json")]
-                    "json" => {
-                        json = true;
-                    }
-                    "no-meta" => {
-                        print_meta = false;
-                    }
-                    "no-padding" => {
-                        pad_answers = false;
-                    }
-                    "do" => {
-                        fetch_dnssec = true;
-                    }
-                    "validate" => {
-                        fetch_dnssec = true;
-                        validate_dnssec = true;
-                    }
-                    "trace" => {
-                        iterative = true;
-                    }
-                    "cookie" => {
-                        cookie = true;
-                    }
-                    "tcp" => {
-                        connection_type = ConnectionType::Tcp;
-                    }
-                    #[cfg(feature = "tls")]
-                    "dot" | "tls" => {
-                        connection_type = ConnectionType::Tls;
-                    }
-                    #[cfg(feature = "http")]
-                    "doh" | "https" | "https-post" => {
-                        connection_type = ConnectionType::HttpsPost;
-                    }
-                    #[cfg(feature = "http")]
-                    "https-get" => {
-                        connection_type = ConnectionType::HttpsGet;
-                    }
-                    #[cfg(feature = "http")]
-                    "http" | "http-post" => {
-                        connection_type = ConnectionType::HttpPost;
+                RawArg::Flag("bufsize", value) => {
+                    let value = value
+                        .unwrap_or_else(|| err("Flag +bufsize needs a value, e.g. +bufsize=1232."));
+                    bufsize = match value.parse() {
+                        Ok(size) if size >= 512 => Some(size),
+                        _ => err(format!(
+                            "Invalid EDNS buffer size: {}; must be between 512 and 65535.",
+                            value
+                        )),
+                    };
+                }
+                RawArg::Flag(flag @ ("sndbuf" | "rcvbuf"), value) => {
+                    let value = value.unwrap_or_else(|| {
+                        err(format!(
+                            "Flag +{} needs a value, e.g. +{}=65536.",
+                            flag, flag
+                        ))
+                    });
+                    let size = match value.parse() {
+                        Ok(size) if size > 0 => Some(size),
+                        _ => err(format!("Invalid socket buffer size: {}.", value)),
+                    };
+                    if flag == "sndbuf" {
+                        tcp_options.send_buffer_size = size;
+                    } else {
+                        tcp_options.recv_buffer_size = size;
                     }
-                    #[cfg(feature = "http")]
-                    "http-get" => {
-                        connection_type = ConnectionType::HttpGet;
+                }
+                RawArg::Flag("ednsver", value) => {
+                    let value = value
+                        .unwrap_or_else(|| err("Flag +ednsver needs a value, e.g. +ednsver=1."));
+                    edns_version = match value.parse() {
+                        Ok(version) => Some(version),
+                        Err(_) => err(format!(
+                            "Invalid EDNS version: {}; must be between 0 and 255.",
+                            value
+                        )),
+                    };
+                }
+                RawArg::Flag("ednsopt", value) => {
+                    let value = value.unwrap_or_else(|| {
+                        err("Flag +ednsopt needs a value, e.g. +ednsopt=65001:cafe.")
+                    });
+                    let (code, data) = value.split_once(':').unwrap_or((value, ""));
+                    let code = match code.parse::<u16>() {
+                        Ok(code) => OptionCode::from(code),
+                        Err(_) => err(format!("Invalid EDNS option code: {}.", code)),
+                    };
+                    let data = match HEXLOWER_PERMISSIVE.decode(data.as_bytes()) {
+                        Ok(data) => data,
+                        Err(e) => err(format!("Invalid EDNS option value: {}.", e)),
+                    };
+                    edns_options.push((code, data));
+                }
+                RawArg::Flag(flag, value) => {
+                    // flags that don't take a value
+                    if let Some(value) = value {
+                        err(format!(
+                            "Flag +{} does not take a value, got: {}.",
+                            flag, value
+                        ));
                     }
-                    x => {
-                        err(format!("Invalid flag: +{}.", x));
+                    match flag {
+                        "verbose" => {
+                            verbose = true;
+                        }
+                        #[cfg(feature = "json")]
+                        "json" => {
+                            json = true;
+                        }
+                        "no-meta" => {
+                            print_meta = false;
+                        }
+                        "csv" => {
+                            rows = Some(RowFormat::Csv);
+                        }
+                        "tsv" => {
+                            rows = Some(RowFormat::Tsv);
+                        }
+                        "no-padding" => {
+                            pad_answers = false;
+                        }
+                        "confirm" => {
+                            sweep_confirm = true;
+                        }
+                        "group" => {
+                            group_rrsets = true;
+                        }
+                        // dig-style section selection; later flags override earlier ones
+                        "noall" => {
+                            sections = DisplaySections::NONE;
+                            print_meta = false;
+                        }
+                        "comments" | "nocomments" => {
+                            sections.comments = flag == "comments";
+                        }
+                        "question" | "noquestion" => {
+                            sections.question = flag == "question";
+                        }
+                        "answer" | "noanswer" => {
+                            sections.answer = flag == "answer";
+                        }
+                        "authority" | "noauthority" => {
+                            sections.authority = flag == "authority";
+                        }
+                        "additional" | "noadditional" => {
+                            sections.additional = flag == "additional";
+                        }
+                        "idn-out" => {
+                            idn_out = true;
+                        }
+                        "do" => {
+                            fetch_dnssec = true;
+                        }
+                        "validate" => {
+                            fetch_dnssec = true;
+                            validate_dnssec = true;
+                        }
+                        "dobit" | "nodobit" => {
+                            dnssec_ok = Some(flag == "dobit");
+                        }
+                        "cdflag" | "nocdflag" => {
+                            checking_disabled = flag == "cdflag";
+                        }
+                        "trace" => {
+                            iterative = true;
+                        }
+                        "all-common" => {
+                            all_common = true;
+                        }
+                        "all-types" => {
+                            all_types = true;
+                        }
+                        "dns64-check" => {
+                            dns64_check = true;
+                        }
+                        "explain" => {
+                            explain = true;
+                        }
+                        "cookie" => {
+                            cookie = true;
+                        }
+                        "noedns" => {
+                            edns = false;
+                        }
+                        "no-source-check" => {
+                            check_source = false;
+                        }
+                        "reuse-udp" => {
+                            reuse_udp_sockets = true;
+                        }
+                        "nodelay" => {
+                            tcp_options.nodelay = true;
+                        }
+                        "tfo" => {
+                            tcp_options.fast_open = true;
+                        }
+                        "reuseaddr" => {
+                            tcp_options.reuse_address = true;
+                        }
+                        "system" => {
+                            system = true;
+                        }
+                        // the system's hosts file takes precedence over the ones given with --hosts
+                        "hosts" if !hosts_files.iter().any(|f| f == HOSTS_FILE) => {
+                            hosts_files.insert(0, HOSTS_FILE.to_string());
+                        }
+                        "hosts" => {}
+                        #[cfg(all(feature = "http", feature = "json"))]
+                        "rdap" => {
+                            rdap = true;
+                        }
+                        #[cfg(all(feature = "http", feature = "json"))]
+                        "json-fallback" => {
+                            doh_json_fallback = true;
+                        }
+                        "tcp" => {
+                            connection_type = ConnectionType::Tcp;
+                        }
+                        #[cfg(feature = "tls")]
+                        "dot" | "tls" => {
+                            connection_type = ConnectionType::Tls;
+                        }
+                        #[cfg(feature = "http")]
+                        "doh" | "https" | "https-post" => {
+                            connection_type = ConnectionType::HttpsPost;
+                        }
+                        #[cfg(feature = "http")]
+                        "https-get" => {
+                            connection_type = ConnectionType::HttpsGet;
+                        }
+                        #[cfg(feature = "http")]
+                        "http" | "http-post" => {
+                            connection_type = ConnectionType::HttpPost;
+                        }
+                        #[cfg(feature = "http")]
+                        "http-get" => {
+                            connection_type = ConnectionType::HttpGet;
+                        }
+                        x => {
+                            err(format!("Invalid flag: +{}.", x));
+                        }
                     }
                 }
-            } else if let Some(option) = arg.strip_prefix('-') {
-                // options
-                match option {
-                    "h" | "-help" => {
-                        print_help();
-                        process::exit(0);
-                    }
-                    "V" | "-version" => {
-                        print_version();
-                        process::exit(0);
+                RawArg::Opt(option, value) => {
+                    // options that take a value; if it was not given as `--option=value`, the next
+                    // argument is the value
+                    let mut value = || match value {
+                        Some(value) => value,
+                        None => match args.next() {
+                            Some(value) => value.as_str(),
+                            None => err(format!("Missing value for option {}.", arg)),
+                        },
+                    };
+                    match option {
+                        "h" | "help" => {
+                            print_help();
+                            exit(0);
+                        }
+                        "V" | "version" => {
+                            print_version();
+                            exit(0);
+                        }
+                        "i" | "interactive" => {
+                            interactive = true;
+                        }
+                        "x" => {
+                            reverse = true;
+                        }
+                        "parse-stdin" => {
+                            parse_stdin = true;
+                        }
+                        "p" | "port" => {
+                            let val = value();
+                            match val.parse::<u16>() {
+                                Ok(val) => port = Some(val),
+                                Err(_) => err(format!("Invalid port: {}.", val)),
+                            }
+                        }
+                        "opcode" => {
+                            let val = value();
+                            match Opcode::from_str(&val.to_uppercase()) {
+                                Ok(val) => opcode = val,
+                                Err(_) => err(format!("Invalid opcode: {}.", val)),
+                            }
+                        }
+                        #[cfg(feature = "geoip")]
+                        "geoip" => {
+                            geoip_dbs.push(value().to_string());
+                        }
+                        "hosts" => {
+                            hosts_files.push(value().to_string());
+                        }
+                        "stats-file" => {
+                            stats_file = Some(value().to_string());
+                        }
+                        "query-log" => {
+                            query_log_file = Some(value().to_string());
+                        }
+                        "dnssec-policy" => {
+                            let val = value();
+                            validation_policy = match val {
+                                "modern" => Some(ValidationPolicy::modern()),
+                                "permissive" => Some(ValidationPolicy::default()),
+                                _ => err(format!("Invalid DNSSEC validation policy: {}.", val)),
+                            };
+                        }
+                        "nsec3-iterations" => {
+                            let val = value();
+                            let limits: Vec<_> =
+                                val.split(',').map(|limit| limit.parse::<u16>()).collect();
+                            match limits[..] {
+                                [Ok(warn_above), Ok(insecure_above), Ok(bogus_above)] => {
+                                    nsec3_policy = Nsec3IterationPolicy {
+                                        warn_above,
+                                        insecure_above,
+                                        bogus_above,
+                                    };
+                                }
+                                _ => err(format!("Invalid NSEC3 iteration limits: {}.", val)),
+                            }
+                        }
+                        #[cfg(feature = "json")]
+                        "trace-json" => {
+                            trace_json = Some(value().to_string());
+                        }
+                        #[cfg(feature = "json")]
+                        "hook" => {
+                            hook = Some(Hook::new(value()));
+                        }
+                        #[cfg(feature = "json")]
+                        "listen" => {
+                            let val = value();
+                            match SocketAddr::from_str(val) {
+                                Ok(addr) => listen = Some(addr),
+                                Err(_) => err(format!("Invalid address to listen on: {}.", val)),
+                            }
+                        }
+                        "subnet" => {
+                            let val = value();
+                            match ClientSubnet::from_str(val) {
+                                Ok(subnet) => subnets.push(subnet),
+                                Err(e) => err(e.to_string()),
+                            }
+                        }
+                        "rate" => {
+                            let val = value();
+                            match val.parse::<u32>() {
+                                Ok(val) if val > 0 => sweep_rate = Some(val),
+                                _ => err(format!("Invalid rate: {}.", val)),
+                            }
+                        }
+                        "sample" => {
+                            let val = value();
+                            match val.parse::<usize>() {
+                                Ok(val) if val > 0 => sweep_sample = Some(val),
+                                _ => err(format!("Invalid sample size: {}.", val)),
+                            }
+                        }
+                        "dns64-prefix" => match Nat64Prefix::from_str(value()) {
+                            Ok(prefix) if !dns64_prefixes.contains(&prefix) => {
+                                dns64_prefixes.push(prefix)
+                            }
+                            Ok(_) => {}
+                            Err(e) => err(format!("{:#}", e)),
+                        },
+                        "c" | "class" => qclass = parse_class(value()),
+                        "t" | "type" => {
+                            let t = parse_type(value());
+                            if !qtypes.contains(&t) {
+                                qtypes.push(t);
+                            }
+                            types_selected = true;
+                        }
+                        "sort" => {
+                            let val = value();
+                            match RecordOrder::from_str(&val.to_lowercase()) {
+                                Ok(val) => sort = val,
+                                Err(_) => err(format!("Invalid sort order: {}.", val)),
+                            }
+                        }
+                        _ => {
+                            err(format!("Invalid option: {}.", arg));
+                        }
                     }
-                    "p" | "-port" => {
-                        consume_next = Some(ConsumeNext::Port);
+                }
+                RawArg::Positional(arg) => {
+                    let is_first_positional = !seen_positional;
+                    seen_positional = true;
+
+                    if let Command::Raw(template) = &mut command {
+                        if let Err(e) = template.set(arg) {
+                            err(format!("{:#}", e));
+                        }
+                        continue;
                     }
-                    "x" => {
-                        reverse = true;
+
+                    if is_first_positional && SUBCOMMANDS.contains(&arg) {
+                        command = match arg {
+                            "decode" => {
+                                let hex = match args.next() {
+                                    Some(hex) => hex,
+                                    None => err("Missing message to decode."),
+                                };
+                                match HEXLOWER_PERMISSIVE.decode(hex.as_bytes()) {
+                                    Ok(msg) => Command::Decode(msg),
+                                    Err(e) => err(format!("Invalid hex string: {}.", e)),
+                                }
+                            }
+                            "blocklist" => Command::Blocklist(Vec::new()),
+                            "caa-check" => Command::CaaCheck,
+                            "cds-check" => Command::CdsCheck,
+                            "mail-audit" => Command::MailAudit,
+                            "split-horizon" => Command::SplitHorizon,
+                            "middlebox-check" => Command::MiddleboxCheck,
+                            "raw" => Command::Raw(MessageTemplate::default()),
+                            "survey" => Command::Survey(Vec::new()),
+                            "watch" => Command::Watch,
+                            #[cfg(feature = "json")]
+                            "serve-api" => Command::ServeApi(DEFAULT_API_ADDRESS.parse().unwrap()),
+                            #[cfg(not(feature = "json"))]
+                            "serve-api" => {
+                                err("The serve-api subcommand requires the json feature.")
+                            }
+                            "enum" => {
+                                let number = match args.next() {
+                                    Some(number) => number,
+                                    None => err("Missing E.164 number to resolve."),
+                                };
+                                if let Err(e) = Name::from_e164(number) {
+                                    err(e.to_string());
+                                }
+                                // the application unique string is the number without separators
+                                let digits: String =
+                                    number.chars().filter(char::is_ascii_digit).collect();
+                                Command::Enum(format!("+{}", digits))
+                            }
+                            "pgpkey" => {
+                                let email = match args.next() {
+                                    Some(email) => email,
+                                    None => err("Missing email address to fetch the key for."),
+                                };
+                                if let Err(e) = Name::openpgpkey_for_email(email) {
+                                    err(e.to_string());
+                                }
+                                Command::PgpKey(email.to_string())
+                            }
+                            "ptr-sweep" => {
+                                let networks = match args.next() {
+                                    Some(networks) => networks,
+                                    None => err("Missing networks to sweep."),
+                                };
+                                let networks = if networks == "-" {
+                                    read_stdin_networks()
+                                } else {
+                                    networks.split(',').map(str::to_string).collect()
+                                };
+                                let networks = networks
+                                    .iter()
+                                    .map(|network| match Network::from_str(network.trim()) {
+                                        Ok(network) => network,
+                                        Err(e) => err(format!("{:#}", e)),
+                                    })
+                                    .collect();
+                                Command::PtrSweep(networks)
+                            }
+                            "probe" => {
+                                let spec = match args.next().map(String::as_str) {
+                                    Some("-") => read_stdin_spec(),
+                                    Some(path) => ProbeSpec::from_file(path),
+                                    None => err("Missing query spec to probe with."),
+                                };
+                                match spec {
+                                    Ok(spec) => Command::Probe(spec),
+                                    Err(e) => err(format!("{:#}", e)),
+                                }
+                            }
+                            _ => unreachable!(),
+                        };
+                        continue;
                     }
-                    x => {
-                        err(format!("Invalid option: -{}.", x));
+
+                    if let Some(val) = arg.strip_prefix("type=") {
+                        let t = parse_type(val);
+                        if !qtypes.contains(&t) {
+                            qtypes.push(t);
+                        }
+                        types_selected = true;
+                    } else if let Some(val) = arg.strip_prefix("class=") {
+                        qclass = parse_class(val);
+                    } else {
+                        bare_words.push(arg);
                     }
                 }
-            } else {
-                match RecordType::from_str(&arg.to_uppercase()) {
+            }
+        }
+
+        if types_selected {
+            // with explicit record types, names like "ns" or "any" are names
+            match bare_words[..] {
+                [] => {}
+                [word] => {
+                    name = word.to_string();
+                    name_given = true;
+                }
+                _ => err("Cannot give more than one domain name with -t or type=."),
+            }
+        } else {
+            let mut ambiguous = Vec::new();
+            for word in bare_words {
+                match RecordType::from_str(word) {
                     Ok(t) => {
-                        qtype = t;
+                        if !qtypes.contains(&t) {
+                            qtypes.push(t);
+                        }
+                        ambiguous.push(word);
                     }
                     Err(_) => {
                         // use URL as fallback
-                        name = arg;
+                        name = word.to_string();
+                        name_given = true;
                     }
                 }
             }
+            if !name_given && matches!(command, Command::Query) {
+                for word in ambiguous {
+                    eprintln!(
+                        "Warning: took {} as a record type, not as a domain name; use -t to select \
+                         the type explicitly, e.g. toluol {} -t A.",
+                        word, word
+                    );
+                }
+            }
+        }
+
+        if parse_stdin {
+            if !matches!(command, Command::Query) {
+                err("Cannot use --parse-stdin with subcommands.");
+            }
+            if interactive || TRYING.get() {
+                err("Cannot use --parse-stdin in interactive mode.");
+            }
+            command = Command::Decode(read_stdin_message());
+        }
+
+        if dns64_check {
+            if !matches!(command, Command::Query) {
+                err("Cannot use +dns64-check with subcommands.");
+            }
+            if !qtypes.is_empty() || all_common {
+                err("Cannot give query types with +dns64-check.");
+            }
+            if iterative {
+                err("Cannot use both +dns64-check and +trace.");
+            }
+            if system {
+                err("Cannot use both +dns64-check and +system.");
+            }
+            if reverse {
+                err("Cannot use both +dns64-check and -x.");
+            }
+            command = Command::Dns64Check;
+            if !name_given {
+                name = IPV4ONLY_NAME.to_string();
+            }
+        }
+
+        if let Command::Blocklist(ref mut list) = command {
+            *list = nameservers.clone();
+            if iterative {
+                err("Cannot use +trace with the blocklist subcommand.");
+            }
+        }
+
+        let subcommand = match command {
+            Command::CaaCheck => Some("caa-check"),
+            Command::CdsCheck => Some("cds-check"),
+            Command::MailAudit => Some("mail-audit"),
+            Command::MiddleboxCheck => Some("middlebox-check"),
+            Command::Enum(_) => Some("enum"),
+            Command::PgpKey(_) => Some("pgpkey"),
+            Command::PtrSweep(_) => Some("ptr-sweep"),
+            Command::Probe(_) => Some("probe"),
+            Command::Raw(_) => Some("raw"),
+            #[cfg(feature = "json")]
+            Command::ServeApi(_) => Some("serve-api"),
+            _ => None,
+        };
+        if let Some(subcommand) = subcommand {
+            if !qtypes.is_empty() || all_common {
+                err(format!(
+                    "Cannot give query types for the {} subcommand.",
+                    subcommand
+                ));
+            }
+            if iterative {
+                err(format!(
+                    "Cannot use +trace with the {} subcommand.",
+                    subcommand
+                ));
+            }
+        }
+
+        let qtypes_given = !qtypes.is_empty() || all_common;
+        if all_common {
+            for t in COMMON_QTYPES {
+                if !qtypes.contains(&t) {
+                    qtypes.push(t);
+                }
+            }
+        }
+        if let Command::Survey(ref mut types) = command {
+            *types = if all_types {
+                survey::assigned_types()
+            } else if qtypes_given {
+                qtypes.clone()
+            } else {
+                survey::DEFAULT_TYPES.to_vec()
+            };
+            if iterative {
+                err("Cannot use +trace with the survey subcommand.");
+            }
+            if ![ConnectionType::Udp, ConnectionType::Tcp].contains(&connection_type) {
+                err("Cannot use DoT/DoH with the survey subcommand.");
+            }
+        } else if all_types {
+            err("Cannot use +all-types except for the survey subcommand.");
+        }
+        if qtypes.is_empty() {
+            qtypes.push(DEFAULT_QTYPE);
+        }
+
+        if matches!(command, Command::Blocklist(_)) && qtypes.len() > 1 {
+            err("Cannot use multiple query types with the blocklist subcommand.");
+        }
+
+        if let Command::SplitHorizon = command {
+            if qtypes.len() > 1 {
+                err("Cannot use multiple query types with the split-horizon subcommand.");
+            }
+            if iterative {
+                err("Cannot use +trace with the split-horizon subcommand.");
+            }
+        } else if subnets.len() > 1 {
+            err("Cannot give multiple subnets except for the split-horizon subcommand.");
+        }
+
+        if let Command::Watch = command {
+            if qtypes.len() > 1 {
+                err("Cannot use multiple query types with the watch subcommand.");
+            }
+            if iterative {
+                err("Cannot use +trace with the watch subcommand.");
+            }
+        }
+
+        if iterative && qtypes.len() > 1 {
+            err("Cannot use +trace with multiple query types.");
+        }
+        #[cfg(feature = "json")]
+        if trace_json.is_some() && !iterative {
+            err("Cannot use --trace-json without +trace.");
+        }
+        #[cfg(feature = "json")]
+        if hook.is_some() && !matches!(command, Command::Query) {
+            err("Cannot use --hook with subcommands.");
+        }
+        #[cfg(feature = "json")]
+        match (&mut command, listen) {
+            (Command::ServeApi(addr), Some(listen)) => *addr = listen,
+            (_, Some(_)) => err("Cannot use --listen except for the serve-api subcommand."),
+            _ => {}
+        }
+        // the API server resolves names with the system's resolver configuration, too
+        #[cfg(feature = "json")]
+        let serves_api = matches!(command, Command::ServeApi(_));
+        #[cfg(not(feature = "json"))]
+        let serves_api = false;
+
+        if stats_file.is_some() && !system && !iterative {
+            err("Cannot use --stats-file without +system or +trace.");
+        }
+        if nsec3_policy != Nsec3IterationPolicy::default() && !validate_dnssec {
+            err("Cannot use --nsec3-iterations without +validate.");
+        }
+        if validation_policy.is_some() && !validate_dnssec && !matches!(command, Command::CdsCheck)
+        {
+            err("Cannot use --dnssec-policy without +validate or cds-check.");
+        }
+        let validation_policy = ValidationPolicy {
+            nsec3: nsec3_policy,
+            ..validation_policy.unwrap_or_default()
+        };
+        if !hosts_files.is_empty() && !system {
+            err("Cannot use +hosts or --hosts without +system.");
+        }
+        if system {
+            if !nameservers.is_empty() {
+                err("Cannot use both +system and an explicit nameserver.");
+            }
+            if iterative {
+                err("Cannot use both +system and +trace.");
+            }
+            if !matches!(command, Command::Query) && !serves_api {
+                err("Cannot use +system with subcommands.");
+            }
+            if ![ConnectionType::Udp, ConnectionType::Tcp].contains(&connection_type) {
+                err("Cannot use +system with DoT/DoH.");
+            }
+        }
+
+        #[cfg(all(feature = "http", feature = "json"))]
+        if rdap && iterative {
+            err("Cannot use both +rdap and +trace.");
+        }
+        #[cfg(all(feature = "http", feature = "json"))]
+        if rdap && json {
+            err("Cannot use both +rdap and +json.");
+        }
+        #[cfg(all(feature = "http", feature = "json"))]
+        if doh_json_fallback
+            && ![
+                ConnectionType::HttpGet,
+                ConnectionType::HttpPost,
+                ConnectionType::HttpsGet,
+                ConnectionType::HttpsPost,
+            ]
+            .contains(&connection_type)
+        {
+            err("Cannot use +json-fallback without DoH.");
+        }
+
+        #[cfg(feature = "http")]
+        if tcp_options != TcpOptions::default()
+            && [
+                ConnectionType::HttpGet,
+                ConnectionType::HttpPost,
+                ConnectionType::HttpsGet,
+                ConnectionType::HttpsPost,
+            ]
+            .contains(&connection_type)
+        {
+            err("Cannot use +nodelay, +tfo, +reuseaddr, +sndbuf, or +rcvbuf with DoH.");
+        }
+
+        #[cfg(feature = "json")]
+        if explain && json {
+            err("Cannot use both +explain and +json.");
         }
 
+        if rows.is_some() {
+            if !matches!(command, Command::Query) {
+                err("Cannot use +csv or +tsv with subcommands.");
+            }
+            if verbose || explain || iterative || validate_dnssec {
+                err("Cannot use +csv or +tsv with +verbose, +explain, +trace, or +validate.");
+            }
+            #[cfg(feature = "json")]
+            if json || hook.is_some() {
+                err("Cannot use +csv or +tsv with +json or --hook.");
+            }
+            #[cfg(all(feature = "http", feature = "json"))]
+            if rdap {
+                err("Cannot use +csv or +tsv with +rdap.");
+            }
+        }
         if verbose && !pad_answers {
             err("Cannot use both +verbose and +no-padding.");
         }
+        if group_rrsets && !pad_answers {
+            err("Cannot use both +group and +no-padding.");
+        }
+        // set here so that +noall doesn't reset them
+        sections.group_rrsets = group_rrsets;
+        sections.unicode_names = idn_out;
+
+        // all of these are sent in the OPT record
+        if !edns && bufsize.is_some() {
+            err("Cannot use both +bufsize and +noedns.");
+        }
+        if !edns && (fetch_dnssec || dnssec_ok.is_some()) {
+            err("Cannot use +do, +validate, or +[no]dobit with +noedns.");
+        }
+        if !edns && cookie {
+            err("Cannot use both +cookie and +noedns.");
+        }
+        if !edns && !subnets.is_empty() {
+            err("Cannot use --subnet with +noedns.");
+        }
+        if !edns && (edns_version.is_some() || !edns_options.is_empty()) {
+            err("Cannot use +ednsver or +ednsopt with +noedns.");
+        }
+
+        if reverse && matches!(command, Command::Enum(_)) {
+            err("Cannot use -x with the enum subcommand.");
+        }
+        if reverse && matches!(command, Command::PgpKey(_)) {
+            err("Cannot use -x with the pgpkey subcommand.");
+        }
+        if reverse && matches!(command, Command::PtrSweep(_)) {
+            err("Cannot use -x with the ptr-sweep subcommand.");
+        }
+        if reverse && matches!(command, Command::Probe(_)) {
+            err("Cannot use -x with the probe subcommand.");
+        }
+        if let Command::MiddleboxCheck = command {
+            if ![ConnectionType::Udp, ConnectionType::Tcp].contains(&connection_type) {
+                err("Cannot use DoT/DoH with the middlebox-check subcommand.");
+            }
+            if reverse {
+                err("Cannot use -x with the middlebox-check subcommand.");
+            }
+        }
+        if let Command::Raw(_) = command {
+            if reverse {
+                err("Cannot use -x with the raw subcommand.");
+            }
+            // the message is sent exactly as given, so these would have no effect
+            if fetch_dnssec
+                || dnssec_ok.is_some()
+                || !checking_disabled
+                || cookie
+                || !subnets.is_empty()
+                || edns_version.is_some()
+                || !edns_options.is_empty()
+            {
+                err("Cannot use +do, +validate, +[no]dobit, +nocdflag, +cookie, --subnet, +ednsver, or +ednsopt with the raw subcommand; give the header bits with flags= and an OPT record with ar= instead.");
+            }
+        }
+        if (sweep_sample.is_some() || sweep_confirm) && !matches!(command, Command::PtrSweep(_)) {
+            err("Cannot use --sample or +confirm except for the ptr-sweep subcommand.");
+        }
+        if sweep_rate.is_some() && !matches!(command, Command::PtrSweep(_) | Command::Probe(_)) {
+            err("Cannot use --rate except for the ptr-sweep and probe subcommands.");
+        }
 
         if reverse {
-            match IpAddr::from_str(name.as_str()) {
-                Err(_) => {
-                    err(format!(
-                        "Expected IP address for reverse lookup, but got: {}.",
-                        name
-                    ));
-                }
-                Ok(IpAddr::V4(addr)) => {
-                    let octets = addr.octets();
-                    name = format!(
-                        "{}.{}.{}.{}.in-addr.arpa",
-                        octets[3], octets[2], octets[1], octets[0]
-                    );
-                }
-                Ok(IpAddr::V6(addr)) => {
-                    name = String::with_capacity(72);
-                    for s in addr.segments().iter().rev() {
-                        for c in format!("{:04x}", s).chars().rev() {
-                            name.push(c);
-                            name.push('.');
-                        }
+            // a network (e.g. 2001:db8::/32) is the shorthand for its reverse zone
+            let (address, prefix_len) = match name.split_once('/') {
+                Some((address, prefix_len)) => match prefix_len.parse() {
+                    Ok(prefix_len) => (address, Some(prefix_len)),
+                    Err(_) => err(format!("Invalid prefix length: {}.", prefix_len)),
+                },
+                None => (name.as_str(), None),
+            };
+            let address = match IpAddr::from_str(address) {
+                Ok(address) => address,
+                Err(_) => err(format!(
+                    "Expected IP address or network for reverse lookup, but got: {}.",
+                    name
+                )),
+            };
+            name = match prefix_len {
+                Some(prefix_len) => {
+                    if !qtypes_given {
+                        qtypes = vec![RecordType::NS];
+                    }
+                    match Name::reverse_zone(address, prefix_len) {
+                        Ok(zone) => zone.to_string(),
+                        Err(e) => err(e.to_string()),
                     }
-                    name.push_str("ip6.arpa");
                 }
-            }
-            qtype = RecordType::PTR;
+                None => {
+                    qtypes = vec![RecordType::PTR];
+                    Name::from_ip(address).to_string()
+                }
+            };
         }
 
-        let name = match Name::from_ascii(name) {
+        let name = match &command {
+            Command::Enum(number) => {
+                qtypes = vec![RecordType::NAPTR];
+                Name::from_e164(number)
+            }
+            Command::PgpKey(email) => {
+                qtypes = vec![RecordType::OPENPGPKEY];
+                Name::openpgpkey_for_email(email)
+            }
+            _ => Name::from_ascii(name),
+        };
+        let name = match name {
             Ok(name) => name,
             Err(e) => err(e.to_string()),
         };
@@ -218,10 +1236,11 @@ impl Args {
             .contains(&connection_type);
         }
 
+        if ns_must_be_hostname && webpki::DnsNameRef::try_from_ascii_str(&nameserver).is_err() {
+            err("The nameserver must be a valid hostname (not an IP address) for DoT/DoH.");
+        }
+        #[cfg(any(feature = "tls", feature = "http"))]
         if ns_must_be_hostname {
-            if webpki::DnsNameRef::try_from_ascii_str(&nameserver).is_err() {
-                err("The nameserver must be a valid hostname (not an IP address) for DoT/DoH.");
-            }
             #[cfg(feature = "tls")]
             if (connection_type == ConnectionType::Tls) && port.is_none() {
                 port = Some(853);
@@ -238,21 +1257,89 @@ impl Args {
             }
         }
 
-        Self {
-            nameserver,
-            name,
-            qtype,
-            verbose,
-            #[cfg(feature = "json")]
-            json,
-            print_meta,
+        if interactive {
+            if !matches!(command, Command::Query) {
+                err("Cannot use -i with subcommands.");
+            }
+            if TRYING.get() {
+                err("Already in interactive mode.");
+            }
+            command = Command::Interactive(interactive_args);
+        }
+
+        // ptr-sweep always limits its rate, probe only if asked to
+        let sweep_rate = match command {
+            Command::PtrSweep(_) => Some(sweep_rate.unwrap_or(DEFAULT_SWEEP_RATE)),
+            _ => sweep_rate,
+        };
+
+        let query_log = query_log_file.map(|path| match QueryLog::open(path) {
+            Ok(log) => Arc::new(log),
+            Err(e) => err(format!("{:#}", e)),
+        });
+
+        #[cfg(feature = "geoip")]
+        let geoip = if geoip_dbs.is_empty() {
+            None
+        } else {
+            match GeoIp::open(&geoip_dbs) {
+                Ok(geoip) => Some(Arc::new(geoip)),
+                Err(e) => err(format!("{:#}", e)),
+            }
+        };
+
+        Self {
+            command,
+            nameserver,
+            name,
+            qtypes,
+            qclass,
+            opcode,
+            verbose,
+            #[cfg(feature = "json")]
+            json,
+            print_meta,
+            rows,
+            sections,
             pad_answers,
+            explain,
+            idn_out,
             fetch_dnssec,
             validate_dnssec,
+            dnssec_ok,
+            checking_disabled,
+            validation_policy,
             iterative,
             connection_type,
             port: port.unwrap_or(53),
             cookie,
+            bufsize: bufsize.unwrap_or(DEFAULT_BUFSIZE),
+            edns,
+            edns_version: edns_version.unwrap_or(0),
+            edns_options,
+            check_source,
+            reuse_udp_sockets,
+            tcp_options,
+            system,
+            hosts_files,
+            stats_file,
+            sort,
+            query_log,
+            #[cfg(all(feature = "http", feature = "json"))]
+            rdap,
+            #[cfg(all(feature = "http", feature = "json"))]
+            doh_json_fallback,
+            #[cfg(feature = "json")]
+            trace_json,
+            #[cfg(feature = "json")]
+            hook,
+            #[cfg(feature = "geoip")]
+            geoip,
+            dns64_prefixes,
+            subnets,
+            sweep_rate,
+            sweep_sample: sweep_sample.unwrap_or(DEFAULT_SWEEP_SAMPLE),
+            sweep_confirm,
         }
     }
 }
@@ -267,13 +1354,35 @@ impl From<Args> for QueryMetadata {
         };
         Self {
             name: args.name,
-            qtype: args.qtype,
+            qtype: args.qtypes[0],
+            qclass: args.qclass,
+            opcode: args.opcode,
             nameserver: args.nameserver,
             port: args.port,
             connection_type: args.connection_type,
             fetch_dnssec: args.fetch_dnssec,
             validate_dnssec: args.validate_dnssec,
+            dnssec_ok: args.dnssec_ok,
+            checking_disabled: args.checking_disabled,
+            edns: args.edns,
+            edns_version: args.edns_version,
+            edns_options: args.edns_options,
             client_cookie,
+            // the split-horizon subcommand sends each subnet separately
+            client_subnet: match args.command {
+                Command::SplitHorizon => None,
+                _ => args.subnets.first().copied(),
+            },
+            check_source: args.check_source,
+            timeout: DEFAULT_UDP_TIMEOUT,
+            retransmissions: DEFAULT_UDP_RETRANSMISSIONS,
+            reuse_udp_sockets: args.reuse_udp_sockets,
+            tcp_options: args.tcp_options,
+            #[cfg(all(feature = "http", feature = "json"))]
+            doh_json_fallback: args.doh_json_fallback,
+            #[cfg(not(all(feature = "http", feature = "json")))]
+            doh_json_fallback: false,
+            query_log: args.query_log,
         }
     }
 }
@@ -308,7 +1417,128 @@ fn print_help() {
     let output = owo_colors::Stream::Stdout;
     print!("{}", "Usage:".if_supports_color(output, |s| s.purple()));
     println!(
-        "\ttoluol [@{}] [{}] [{}] [{}] [{}]",
+        "\ttoluol [@{}] [{}] [{}...] [{}] [{}]",
+        var!("nameserver"),
+        var!("domain"),
+        var!("q-type"),
+        var!("options"),
+        var!("flags")
+    );
+    println!(
+        "\ttoluol decode <{}> [{}] [{}]",
+        var!("message"),
+        var!("options"),
+        var!("flags")
+    );
+    println!(
+        "\ttoluol --parse-stdin [{}] [{}]",
+        var!("options"),
+        var!("flags")
+    );
+    println!(
+        "\ttoluol blocklist [@{}...] [{}] [{}] [{}] [{}]",
+        var!("nameserver"),
+        var!("domain"),
+        var!("q-type"),
+        var!("options"),
+        var!("flags")
+    );
+    println!(
+        "\ttoluol caa-check [@{}] [{}] [{}] [{}]",
+        var!("nameserver"),
+        var!("domain"),
+        var!("options"),
+        var!("flags")
+    );
+    println!(
+        "\ttoluol cds-check [@{}] [{}] [{}] [{}]",
+        var!("nameserver"),
+        var!("domain"),
+        var!("options"),
+        var!("flags")
+    );
+    println!(
+        "\ttoluol mail-audit [@{}] [{}] [{}] [{}]",
+        var!("nameserver"),
+        var!("domain"),
+        var!("options"),
+        var!("flags")
+    );
+    println!(
+        "\ttoluol middlebox-check [@{}] [{}] [{}] [{}]",
+        var!("nameserver"),
+        var!("domain"),
+        var!("options"),
+        var!("flags")
+    );
+    println!(
+        "\ttoluol split-horizon [@{}] [{}] [{}] [{}] [{}]",
+        var!("nameserver"),
+        var!("domain"),
+        var!("q-type"),
+        var!("options"),
+        var!("flags")
+    );
+    println!(
+        "\ttoluol enum <{}> [@{}] [{}] [{}]",
+        var!("number"),
+        var!("nameserver"),
+        var!("options"),
+        var!("flags")
+    );
+    println!(
+        "\ttoluol pgpkey <{}> [@{}] [{}] [{}]",
+        var!("email"),
+        var!("nameserver"),
+        var!("options"),
+        var!("flags")
+    );
+    println!(
+        "\ttoluol raw [{}...] [@{}] [{}] [{}]",
+        var!("field"),
+        var!("nameserver"),
+        var!("options"),
+        var!("flags")
+    );
+    println!(
+        "\ttoluol ptr-sweep <{}> [@{}] [{}] [{}]",
+        var!("networks"),
+        var!("nameserver"),
+        var!("options"),
+        var!("flags")
+    );
+    println!(
+        "\ttoluol probe <{}> [@{}] [{}] [{}]",
+        var!("spec"),
+        var!("nameserver"),
+        var!("options"),
+        var!("flags")
+    );
+    println!(
+        "\ttoluol survey [@{}] [{}] [{}...] [{}] [{}]",
+        var!("nameserver"),
+        var!("domain"),
+        var!("q-type"),
+        var!("options"),
+        var!("flags")
+    );
+    println!(
+        "\ttoluol watch [@{}] [{}] [{}] [{}] [{}]",
+        var!("nameserver"),
+        var!("domain"),
+        var!("q-type"),
+        var!("options"),
+        var!("flags")
+    );
+    #[cfg(feature = "json")]
+    println!(
+        "\ttoluol serve-api [@{}] [{}] [{}]",
+        var!("nameserver"),
+        var!("options"),
+        var!("flags")
+    );
+    println!(
+        "\ttoluol -i [@{}] [{}] [{}] [{}] [{}]",
         var!("nameserver"),
         var!("domain"),
         var!("q-type"),
@@ -320,7 +1550,7 @@ fn print_help() {
     println!("{}", "Where:".if_supports_color(output, |s| s.purple()));
 
     println!(
-        "\t{} is the IP address or hostname of a DNS nameserver",
+        "\t{} is the IP address or hostname of a DNS nameserver; IPv6 addresses may have a\n\tscope ID, e.g. fe80::1%eth0",
         var!("nameserver")
     );
     println!();
@@ -329,16 +1559,213 @@ fn print_help() {
     println!();
 
     println!(
-        "\t{} is the record type you want (e.g. AAAA, A, TXT, MX, SOA, ...)",
+        "\t{} is the record type you want (e.g. AAAA, A, TXT, MX, SOA, ..., or TYPE65 for any type number; case-insensitive);\n\tif multiple types are given, one query per type is sent",
         var!("q-type")
     );
     println!();
 
+    println!(
+        "\t{} is a DNS message in wire format, given as a hex string; with --parse-stdin,\n\tthe message is read from stdin as binary instead (optionally with the two-byte\n\tlength prefix used with TCP)",
+        var!("message")
+    );
+    println!();
+
+    println!(
+        "\tblocklist sends the query to each given {} (or to a list of known filtering\n\tresolvers if none is given) and reports which of them block the {}",
+        var!("nameserver"),
+        var!("domain")
+    );
+    println!();
+
+    println!(
+        "\tcaa-check finds the CAA records that apply to {} (RFC 8659) and shows which\n\tCAs may issue certificates for it",
+        var!("domain")
+    );
+    println!();
+
+    println!(
+        "\tcds-check compares the CDS and CDNSKEY records of the zone {} with its DNSKEYs\n\tand the DS records at the parent, and shows what an automated parent would do\n\t(RFC 7344, RFC 8078)",
+        var!("domain")
+    );
+    println!();
+
+    println!(
+        "\tmail-audit checks the MX, SPF, DMARC, MTA-STS, and TLSRPT records of {}",
+        var!("domain")
+    );
+    println!();
+
+    println!(
+        "\tsplit-horizon sends the query with and without client subnets (see --subnet),\n\tover UDP and TCP, to the {} and the authoritative nameservers of {},\n\tand shows which answers differ",
+        var!("nameserver"),
+        var!("domain")
+    );
+    println!();
+
+    println!(
+        "\tmiddlebox-check sends probe queries to the {} over UDP and TCP and reports\n\tlikely interference by middleboxes on the way: differing answers, blocked TCP,\n\tstripped EDNS, dropped large responses, rewritten case of {}, and\n\tfiltered DNSSEC records",
+        var!("nameserver"),
+        var!("domain")
+    );
+    println!();
+
+    println!(
+        "\tenum resolves the E.164 {} (e.g. +441632960083) to URIs with ENUM (RFC 6116)",
+        var!("number")
+    );
+    println!();
+
+    println!(
+        "\tpgpkey fetches the OpenPGP keys of the {} (e.g. hugh@example.com) from its\n\tOPENPGPKEY records (RFC 7929) and prints them ASCII-armored, e.g. for gpg --import",
+        var!("email")
+    );
+    println!();
+
+    println!(
+        "\tptr-sweep looks up the PTR records of the addresses in the comma-separated\n\t{} (e.g. 192.0.2.0/28,2001:db8::/64, or - to read one per line from stdin)\n\twith a limited rate (see --rate), each address once; large networks are sampled\n\tby their lowest addresses (see --sample); +confirm checks that the host names\n\tmap back to the addresses",
+        var!("networks")
+    );
+    println!();
+
+    println!(
+        "\tprobe sends the queries of the query {} file (or - to read it from stdin), e.g.
+	\"$label = www, mail\" and \"{{label}}.probe.example TXT\", once for each value of
+	their variables (\"$n = 1..10\" is a range), and prints the answers by substitution;
+	--rate limits the number of queries per second",
+        var!("spec")
+    );
+    println!();
+
+    #[cfg(feature = "json")]
+    {
+        println!(
+            "\tserve-api answers HTTP requests like GET /resolve?name=example.com&type=A with the\n\tanswer of the {} (or of the system's nameservers with +system) as JSON; the\n\tanswers are cached, and validated with +validate (see --listen)",
+            var!("nameserver")
+        );
+        println!();
+    }
+
+    println!(
+        "\tsurvey queries for each {} (by default, the types toluol knows and a few other\n\tcommon ones; see +all-types) and reports which types exist for {}, like the\n\ttype bitmap of an NSEC record",
+        var!("q-type"),
+        var!("domain")
+    );
+    println!();
+
+    println!(
+        "\twatch sends the query again whenever a record of the answer expires and, on a\n\tterminal, counts down the remaining TTL of each record in the meantime; this shows\n\thow long the {} caches the records (stop with Ctrl-C)",
+        var!("nameserver")
+    );
+    println!();
+
+    println!(
+        "\traw sends a message built from the {}s exactly as given, which may be malformed\n\ton purpose, and prints the response even if it cannot be parsed; a {} is one of\n\tid=<id>, flags=<flag>,..., opcode=<opcode>, rcode=<rcode>, qdcount=<count>\n\t(likewise ancount, nscount, arcount), q=<name>:<type>[:<class>],\n\tan=<name>:<type>:<class>:<ttl>:<hex rdata>[:<rdlength>] (likewise ns, ar),\n\tand trailing=<hex>; names may be given as hex:<wire format>",
+        var!("field"),
+        var!("field")
+    );
+    println!();
+
     println!("\t{} is one or more of the following:", var!("options"));
     printopt!("-h | --help", "print this help message");
     printopt!("-V | --version", "print the version of toluol");
     printopt!("-p | --port <port>", "use the given port number");
+    println!("\t\toptions taking a value may also be given as --option=value");
+    printopt!(
+        "-i | --interactive",
+        "read queries from a prompt, with the other arguments as defaults"
+    );
+    println!("\t\ttype help at the prompt for the available commands");
     printopt!("-x", "shortcut for reverse lookup");
+    println!(
+        "\t\twith a network (e.g. -x 2001:db8::/32), its reverse zone is queried (NS by default)"
+    );
+    printopt!(
+        "--parse-stdin",
+        "display a binary wire format message from stdin, like decode"
+    );
+    printopt!(
+        "--opcode <opcode>",
+        "use the given opcode, e.g. STATUS or NOTIFY; default QUERY"
+    );
+    printopt!(
+        "-t | --type <q-type>",
+        "query for the given type; may be repeated, also as type=<q-type>"
+    );
+    println!("\t\twith it, all other arguments that look like types are domain names instead");
+    printopt!(
+        "-c | --class <class>",
+        "use the given class, e.g. CH or ANY; default IN; also as class=<class>"
+    );
+    printopt!(
+        "--hosts <path>",
+        "look up names in the given hosts file first; requires +system"
+    );
+    printopt!(
+        "--sort <order>",
+        "sort records by owner, type, or rdata; default original"
+    );
+    printopt!(
+        "--stats-file <path>",
+        "with +system or +trace, prefer fast nameservers using the statistics in the given file, \
+        and update it"
+    );
+    printopt!(
+        "--query-log <path>",
+        "append each query and its response to the given file, like BIND's query log"
+    );
+    printopt!(
+        "--dnssec-policy <policy>",
+        "with +validate or cds-check, accept the algorithms, digest types, and key sizes of the \
+        given policy: permissive (everything, the default) or modern (RFC 8624 without SHA-1, RSA \
+        keys of at least 2048 bits, and rejecting anything else)"
+    );
+    printopt!(
+        "--nsec3-iterations <warn>,<insecure>,<bogus>",
+        "with +validate, warn about NSEC3 records with more hash iterations than the first limit, \
+        and treat secure ones with more than the second and third as insecure and bogus; \
+        default 0,50,150"
+    );
+    #[cfg(feature = "json")]
+    printopt!(
+        "--trace-json <path>",
+        "with +trace, also write the delegation path as JSON to the given file"
+    );
+    #[cfg(feature = "json")]
+    printopt!(
+        "--hook <command>",
+        "after each query, run the given shell command with the result as JSON on its standard \
+        input, and the name and type in TOLUOL_NAME and TOLUOL_TYPE"
+    );
+    #[cfg(feature = "geoip")]
+    printopt!(
+        "--geoip <path>",
+        "annotate addresses using the given MaxMind database; may be repeated"
+    );
+    printopt!(
+        "--subnet <prefix>",
+        "send the given EDNS Client Subnet, e.g. 192.0.2.0/24"
+    );
+    println!(
+        "\t\tmay be repeated for split-horizon, which uses a network per continent by default"
+    );
+    printopt!(
+        "--rate <n>",
+        "send at most n queries per second with ptr-sweep (default: 20) or probe"
+    );
+    printopt!(
+        "--sample <n>",
+        "look up at most n addresses per network with ptr-sweep (default: 256)"
+    );
+    #[cfg(feature = "json")]
+    printopt!(
+        "--listen <address>",
+        "listen on the given address and port with serve-api (default: 127.0.0.1:8053)"
+    );
+    printopt!(
+        "--dns64-prefix <prefix>",
+        "also treat AAAA records in the given NAT64 prefix as synthesized"
+    );
+    println!("\t\tmay be repeated; 64:ff9b::/96 is always used, e.g. --dns64-prefix 2001:db8::/96");
     println!();
     println!("\t{} is one or more of the following:", var!("flags"));
     printflag!(
@@ -355,10 +1782,123 @@ fn print_help() {
         "+no-padding",
         "don't pad output; cannot be used with +verbose"
     );
+    printflag!(
+        "+group",
+        "group records by record set and print each owner only once"
+    );
+    printflag!(
+        "+noall",
+        "don't print any section, comments, or query metadata"
+    );
+    printflag!(
+        "+[no]question",
+        "(don't) print the question section with +verbose"
+    );
+    printflag!("+[no]answer", "(don't) print the answer section");
+    printflag!("+[no]authority", "(don't) print the authoritative section");
+    printflag!("+[no]additional", "(don't) print the additional section");
+    printflag!(
+        "+[no]comments",
+        "(don't) print the header, OPT, section names, and annotations"
+    );
+    println!("\t\tlater flags override earlier ones, e.g. +noall +answer; not used with +json");
+    printflag!(
+        "+confirm",
+        "with ptr-sweep, check that the host names map back to the addresses"
+    );
+    printflag!(
+        "+csv",
+        "print the records of the answer as CSV: owner,ttl,class,type,rdata,server,rtt (ms)"
+    );
+    printflag!("+tsv", "the same as +csv, but with tabs as separators");
+    printflag!(
+        "+explain",
+        "explain the types of the records and their fields in plain English"
+    );
+    printflag!(
+        "+idn-out",
+        "show internationalized names (xn--...) in their Unicode form"
+    );
     printflag!("+do", "fetch DNSSEC records");
     printflag!("+validate", "validate DNSSEC records; implies +do");
+    printflag!(
+        "+[no]dobit",
+        "set (clear) the DO bit, regardless of +do and +validate"
+    );
+    printflag!(
+        "+[no]cdflag",
+        "set (clear) the CD bit, which disables validation by the resolver; set by default"
+    );
     printflag!("+trace", "query iteratively, starting from a root server");
+    printflag!(
+        "+all-common",
+        "query for A, AAAA, MX, TXT, NS, SOA, and CAA records"
+    );
+    printflag!(
+        "+all-types",
+        "survey all record types assigned by IANA; requires survey"
+    );
+    printflag!(
+        "+dns64-check",
+        "compare A and AAAA records to infer the NAT64 prefix"
+    );
+    println!("\t\tthe domain defaults to {} (RFC 7050)", IPV4ONLY_NAME);
     printflag!("+cookie", "send a random DNS client cookie to the server");
+    printflag!(
+        "+bufsize=<size>",
+        format!(
+            "advertise this EDNS UDP payload size; default {}",
+            DEFAULT_BUFSIZE
+        )
+    );
+    printflag!(
+        "+noedns",
+        "send queries without EDNS, i.e. without an OPT record"
+    );
+    printflag!("+ednsver=<version>", "send this EDNS version; default 0");
+    printflag!(
+        "+ednsopt=<code>[:<hex>]",
+        "send an EDNS option with the given value; may be repeated"
+    );
+    printflag!(
+        "+no-source-check",
+        "accept UDP answers from any address, not just the server's"
+    );
+    printflag!(
+        "+reuse-udp",
+        "reuse UDP sockets for queries to the same server, e.g. with +trace"
+    );
+    printflag!(
+        "+nodelay",
+        "disable Nagle's algorithm (TCP_NODELAY) for TCP and DoT"
+    );
+    printflag!(
+        "+tfo",
+        "use TCP Fast Open for TCP and DoT, if the system supports it"
+    );
+    printflag!("+reuseaddr", "set SO_REUSEADDR on TCP and DoT sockets");
+    printflag!(
+        "+sndbuf=<bytes>",
+        "use this socket send buffer size for TCP and DoT"
+    );
+    printflag!(
+        "+rcvbuf=<bytes>",
+        "use this socket receive buffer size for TCP and DoT"
+    );
+    println!("\t\tall but +nodelay only have an effect on Linux");
+    printflag!(
+        "+system",
+        "use the nameservers and options from /etc/resolv.conf"
+    );
+    printflag!(
+        "+hosts",
+        "look up names in /etc/hosts first; requires +system"
+    );
+    #[cfg(all(feature = "http", feature = "json"))]
+    printflag!(
+        "+rdap",
+        "look up registration info for the domain and addresses"
+    );
     printflag!("+tcp", "use TCP instead of UDP");
     #[cfg(feature = "tls")]
     {
@@ -374,10 +1914,15 @@ fn print_help() {
         printflag!("+http", "use DNS over HTTP, with POST");
         printflag!("+http-post", "use DNS over HTTP, with POST");
         printflag!("+http-get", "use DNS over HTTP, with GET");
+        #[cfg(feature = "json")]
+        printflag!(
+            "+json-fallback",
+            "use the JSON API if the DoH server rejects the wire format"
+        );
     }
     println!();
 
-    println!("Note: the order of the arguments does not matter.");
+    println!("Note: the order of the arguments does not matter, except that a subcommand such as\n`decode` must come before the domain and q-types.");
     println!();
 
     println!(
@@ -397,11 +1942,88 @@ fn print_help() {
     );
 }
 
+/// Reads a wire format DNS message from stdin.
+///
+/// If the input starts with a two-byte length prefix that matches the length of the rest, as in
+/// captures of DNS over TCP or TLS, the prefix is removed. This misdetects messages whose ID happens
+/// to equal their length minus two, which is unlikely enough.
+/// Parses a record type given with `-t` or `type=`, or exits.
+fn parse_type(val: &str) -> RecordType {
+    match RecordType::from_str(val) {
+        Ok(t) => t,
+        Err(_) => err(format!("Invalid record type: {}.", val)),
+    }
+}
+
+/// Parses a class given with `-c` or `class=`, or exits.
+fn parse_class(val: &str) -> Class {
+    match Class::from_str(&val.to_uppercase()) {
+        Ok(val) => val,
+        Err(_) => err(format!("Invalid class: {}.", val)),
+    }
+}
+
+/// Reads the addresses or networks for the ptr-sweep subcommand from stdin, one per line. Empty
+/// lines and lines starting with `#` are skipped.
+fn read_stdin_networks() -> Vec<String> {
+    if TRYING.get() {
+        err("Cannot read networks from stdin in interactive mode.");
+    }
+    let mut input = String::new();
+    if let Err(e) = io::stdin().read_to_string(&mut input) {
+        err(format!("Could not read networks from stdin: {}.", e));
+    }
+    let networks: Vec<_> = input
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect();
+    if networks.is_empty() {
+        err("No networks on stdin.");
+    }
+    networks
+}
+
+/// Reads the query spec for the probe subcommand from stdin.
+fn read_stdin_spec() -> toluol::error::Result<ProbeSpec> {
+    if TRYING.get() {
+        err("Cannot read the query spec from stdin in interactive mode.");
+    }
+    let mut input = String::new();
+    if let Err(e) = io::stdin().read_to_string(&mut input) {
+        err(format!("Could not read the query spec from stdin: {}.", e));
+    }
+    input.parse()
+}
+
+fn read_stdin_message() -> Vec<u8> {
+    let mut msg = Vec::new();
+    if let Err(e) = io::stdin().read_to_end(&mut msg) {
+        err(format!("Could not read message from stdin: {}.", e));
+    }
+    if msg.is_empty() {
+        err("No message on stdin.");
+    }
+    if msg.len() >= 2 && u16::from_be_bytes([msg[0], msg[1]]) as usize == msg.len() - 2 {
+        msg.drain(..2);
+    }
+    msg
+}
+
 fn print_version() {
     println!("toluol v{}", env!("CARGO_PKG_VERSION"));
 }
 
 fn err(msg: impl AsRef<str>) -> ! {
     eprintln!("{}", msg.as_ref());
-    process::exit(1)
+    exit(1)
+}
+
+/// Exits with `code`, or, while parsing with [`Args::try_parse_from()`], returns to it.
+fn exit(code: i32) -> ! {
+    if TRYING.get() {
+        panic::resume_unwind(Box::new(Exit));
+    }
+    process::exit(code)
 }
@@ -0,0 +1,80 @@
+//! Checking whether filtering resolvers block a domain.
+
+use std::fmt::Display;
+use std::net::IpAddr;
+
+use toluol_proto::{Message, RCode, Rdata};
+
+/// Public resolvers that are known to filter malicious or otherwise unwanted domains, together
+/// with a human readable name.
+pub const FILTERING_RESOLVERS: [(&str, &str); 8] = [
+    ("Quad9", "9.9.9.9"),
+    ("Cloudflare (malware)", "1.1.1.2"),
+    ("Cloudflare (family)", "1.1.1.3"),
+    ("AdGuard DNS", "94.140.14.14"),
+    ("AdGuard DNS (family)", "94.140.14.15"),
+    ("CleanBrowsing (security)", "185.228.168.9"),
+    ("CleanBrowsing (family)", "185.228.168.168"),
+    ("OpenDNS FamilyShield", "208.67.222.123"),
+];
+
+/// How a resolver answered a query for a possibly blocked domain.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// The resolver answered normally.
+    Clean,
+    /// The resolver claimed the domain does not exist.
+    NxDomain,
+    /// The resolver answered with an unspecified address, i.e. `0.0.0.0` or `::`.
+    NullAddress,
+    /// The resolver refused to answer.
+    Refused,
+}
+
+impl Verdict {
+    /// Classifies the response `res` of a resolver.
+    ///
+    /// Note that a domain that actually does not exist is indistinguishable from a domain that
+    /// is blocked with NXDOMAIN, so it is a good idea to compare with an unfiltered resolver.
+    pub fn classify(res: &Message) -> Self {
+        let rcode = match res.opt() {
+            Some(opt) => opt.rcode,
+            None => res.header.rcode,
+        };
+        match rcode {
+            Some(RCode::NXDOMAIN) => return Self::NxDomain,
+            Some(RCode::REFUSED) => return Self::Refused,
+            _ => {}
+        }
+
+        let null_address = res.nonopt_records().any(|rec| {
+            let ip: IpAddr = match rec.rdata() {
+                Rdata::A(a) => a.address.into(),
+                Rdata::AAAA(aaaa) => aaaa.address.into(),
+                _ => return false,
+            };
+            ip.is_unspecified()
+        });
+        if null_address {
+            Self::NullAddress
+        } else {
+            Self::Clean
+        }
+    }
+
+    /// Returns true if the verdict means that the domain is blocked.
+    pub fn is_blocked(&self) -> bool {
+        *self != Self::Clean
+    }
+}
+
+impl Display for Verdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Clean => write!(f, "clean"),
+            Self::NxDomain => write!(f, "blocked (NXDOMAIN)"),
+            Self::NullAddress => write!(f, "blocked (null address)"),
+            Self::Refused => write!(f, "blocked (REFUSED)"),
+        }
+    }
+}
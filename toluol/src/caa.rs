@@ -0,0 +1,178 @@
+//! Evaluating the CAA policy for a domain name, as described in
+//! [RFC 8659](https://www.rfc-editor.org/rfc/rfc8659).
+
+use std::fmt::Display;
+use toluol_proto::rdata::caa::{Property, Value};
+use toluol_proto::rdata::CAA;
+use toluol_proto::{Name, RCode, Rdata, RecordType};
+use url::Url;
+
+use crate::error::{bail, Context, Result};
+use crate::util::query_single;
+use crate::QueryMetadata;
+
+/// The CAA records that apply to a domain name.
+#[derive(Clone, Debug)]
+pub struct CaaPolicy {
+    /// The domain name the policy was evaluated for.
+    pub name: Name,
+    /// The closest ancestor of [`Self::name`] (or the name itself) that has CAA records, if there
+    /// is any.
+    pub relevant_domain: Option<Name>,
+    /// The CAA records of [`Self::relevant_domain`] (the "relevant resource record set").
+    pub records: Vec<CAA>,
+}
+
+/// Which CAs may issue certificates for a domain name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Authorization {
+    /// Any CA may issue certificates.
+    AnyCa,
+    /// No CA may issue certificates.
+    NoCa,
+    /// Only the CAs with the given issuer domain names may issue certificates.
+    Cas(Vec<Name>),
+}
+
+impl CaaPolicy {
+    /// Returns which CAs may issue certificates for [`Self::name`], or for names matched by the
+    /// wildcard `*.name` if `wildcard` is true.
+    ///
+    /// For wildcard certificates, `issuewild` records take precedence over `issue` records if
+    /// there are any. Unknown properties marked as critical forbid any issuance.
+    pub fn authorization(&self, wildcard: bool) -> Authorization {
+        if !self.unknown_critical().is_empty() {
+            return Authorization::NoCa;
+        }
+
+        let has_tag = |tag: &Property| self.records.iter().any(|caa| caa.tag() == tag);
+        let tag = if wildcard && has_tag(&Property::IssueWild) {
+            Property::IssueWild
+        } else {
+            Property::Issue
+        };
+        if !has_tag(&tag) {
+            return Authorization::AnyCa;
+        }
+
+        let mut cas = Vec::new();
+        for caa in self.records.iter().filter(|caa| caa.tag() == &tag) {
+            if let Value::Issuer {
+                name: Some(name), ..
+            } = caa.value()
+            {
+                if !cas.contains(name) {
+                    cas.push(name.clone());
+                }
+            }
+        }
+        if cas.is_empty() {
+            Authorization::NoCa
+        } else {
+            Authorization::Cas(cas)
+        }
+    }
+
+    /// Returns true if the CA with the issuer domain name `ca` (e.g. `letsencrypt.org`) may issue
+    /// certificates for [`Self::name`] (or `*.name` if `wildcard` is true).
+    pub fn may_issue(&self, ca: &Name, wildcard: bool) -> bool {
+        match self.authorization(wildcard) {
+            Authorization::AnyCa => true,
+            Authorization::NoCa => false,
+            Authorization::Cas(cas) => cas.contains(ca),
+        }
+    }
+
+    /// Returns the URLs that CAs may report invalid certificate requests to.
+    pub fn iodef_urls(&self) -> Vec<&Url> {
+        self.records
+            .iter()
+            .filter_map(|caa| match caa.value() {
+                Value::IodefUrl(url) => Some(url),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the records with unknown properties that are marked as critical. If there are any,
+    /// no CA may issue certificates.
+    pub fn unknown_critical(&self) -> Vec<&CAA> {
+        self.records
+            .iter()
+            .filter(|caa| caa.issuer_critical && matches!(caa.tag(), Property::Unknown(_)))
+            .collect()
+    }
+}
+
+impl Display for Authorization {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::AnyCa => write!(f, "any CA"),
+            Self::NoCa => write!(f, "no CA"),
+            Self::Cas(cas) => {
+                let cas: Vec<_> = cas.iter().map(|ca| ca.to_string()).collect();
+                write!(f, "{}", cas.join(", "))
+            }
+        }
+    }
+}
+
+/// Finds the CAA policy for `name`.
+///
+/// Starting at `name`, CAA records are queried for `name` and each of its ancestors (excluding the
+/// root) until a non-empty set of CAA records is found. Aliases are followed by the resolver. A
+/// leading wildcard label of `name` is ignored.
+///
+/// As CAs must not issue certificates if a lookup fails, an error is returned in that case.
+/// `metadata.name` and `metadata.qtype` are ignored.
+pub fn find_policy(name: &Name, metadata: &QueryMetadata, bufsize: u16) -> Result<CaaPolicy> {
+    let mut name = name.clone();
+    if name.is_wildcard() {
+        name.pop_front_label();
+    }
+
+    let mut metadata = metadata.clone();
+    metadata.name = name.clone();
+    metadata.qtype = RecordType::CAA;
+    while !metadata.name.is_root() {
+        let (_, res, _, _) = query_single(&metadata, bufsize).transport(format!(
+            "Could not look up CAA records for {}.",
+            metadata.name
+        ))?;
+        let rcode = match res.opt() {
+            Some(opt) => opt.rcode,
+            None => res.header.rcode,
+        };
+        match rcode.unwrap_or(RCode::NOERROR) {
+            RCode::NOERROR | RCode::NXDOMAIN => {}
+            rcode => bail!(
+                transport,
+                "Looking up CAA records for {} failed with {}, so no CA may issue certificates.",
+                metadata.name,
+                rcode
+            ),
+        }
+
+        let records: Vec<_> = res
+            .nonopt_records()
+            .filter_map(|record| match record.rdata() {
+                Rdata::CAA(caa) => Some(caa.clone()),
+                _ => None,
+            })
+            .collect();
+        if !records.is_empty() {
+            return Ok(CaaPolicy {
+                name,
+                relevant_domain: Some(metadata.name),
+                records,
+            });
+        }
+        metadata.name.pop_front_label();
+    }
+
+    Ok(CaaPolicy {
+        name,
+        relevant_domain: None,
+        records: Vec::new(),
+    })
+}
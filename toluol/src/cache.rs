@@ -0,0 +1,392 @@
+//! Caching answers for as long as their TTLs allow, see [`AnswerCache`], and the DNSKEYs of
+//! zones, see [`DnskeyCache`].
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use toluol_proto::dnssec::{validate_rrsets, ValidationState};
+use toluol_proto::rdata::opt::ClientSubnet;
+use toluol_proto::{Class, Message, Name, NonOptRecord, Opcode, RCode, RecordType};
+
+use crate::error::Result;
+use crate::util::Answer;
+use crate::QueryMetadata;
+
+/// The maximum number of answers an [`AnswerCache`] keeps by default.
+pub const DEFAULT_CACHE_SIZE: usize = 1024;
+
+/// What an answer depends on: the question, the nameserver it was sent to, and the parts of the
+/// query that may change the answer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CacheKey {
+    pub name: Name,
+    pub qtype: RecordType,
+    pub qclass: Class,
+    pub opcode: Opcode,
+    pub nameserver: String,
+    pub port: u16,
+    /// The DO bit of the query.
+    pub dnssec_ok: bool,
+    pub checking_disabled: bool,
+    pub edns: bool,
+    pub client_subnet: Option<ClientSubnet>,
+}
+
+impl CacheKey {
+    /// Returns the key for answers to the query described by `metadata`.
+    pub fn from_metadata(metadata: &QueryMetadata) -> Self {
+        Self {
+            name: metadata.name.clone(),
+            qtype: metadata.qtype,
+            qclass: metadata.qclass,
+            opcode: metadata.opcode,
+            nameserver: metadata.nameserver.clone(),
+            port: metadata.port,
+            dnssec_ok: metadata.edns && metadata.dnssec_ok.unwrap_or(metadata.fetch_dnssec),
+            checking_disabled: metadata.checking_disabled,
+            edns: metadata.edns,
+            client_subnet: metadata.client_subnet,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    key: CacheKey,
+    answer: Answer,
+    received: Instant,
+    expires: Instant,
+}
+
+/// Answers kept until their TTL elapses, like a caching resolver would keep them.
+///
+/// Only `NOERROR` and `NXDOMAIN` answers are cached, for the smallest TTL of their records (see
+/// [`cache_ttl()`]). When an answer is taken from the cache, the TTLs of its records are reduced
+/// by the time it spent in the cache, so that they count down as they would at a resolver.
+///
+/// # Examples
+/// ```rust
+/// use std::time::{Duration, Instant, SystemTime};
+/// use toluol::cache::{AnswerCache, CacheKey};
+/// use toluol::net::{Nameserver, QueryTiming};
+/// use toluol_proto::rdata::A;
+/// use toluol_proto::{Class, HeaderFlags, Message, Name, NonOptRecord, Opcode, Question, RCode};
+/// use toluol_proto::{Record, RecordType};
+///
+/// let name = Name::from_ascii("example.com").unwrap();
+/// let rdata = A { address: "192.0.2.1".parse().unwrap() }.into();
+/// let record = NonOptRecord::new(name.clone(), Class::IN, 300, rdata).unwrap();
+/// let flags = HeaderFlags { aa: false, tc: false, rd: true, ra: true, ad: false, cd: false };
+/// let message = Message::new_response(
+///     1,
+///     Opcode::QUERY,
+///     flags,
+///     RCode::NOERROR,
+///     vec![Question::new(name.clone(), RecordType::A, Class::IN)],
+///     [vec![Record::NONOPT(record)], Vec::new(), Vec::new()],
+/// );
+/// let nameserver = Nameserver {
+///     hostname: None,
+///     ip: Some("192.0.2.53".parse().unwrap()),
+///     scope_id: None,
+///     port: 53,
+/// };
+/// let timing = QueryTiming {
+///     sent: SystemTime::now(),
+///     received: SystemTime::now(),
+///     elapsed: Duration::ZERO,
+///     handshake: None,
+///     http_cache: None,
+///     retransmissions: 0,
+///     resolved_addrs: Vec::new(),
+///     tcp_fallback: None,
+///     attempts: Vec::new(),
+/// };
+///
+/// let key = CacheKey {
+///     name,
+///     qtype: RecordType::A,
+///     qclass: Class::IN,
+///     opcode: Opcode::QUERY,
+///     nameserver: "192.0.2.53".into(),
+///     port: 53,
+///     dnssec_ok: false,
+///     checking_disabled: true,
+///     edns: true,
+///     client_subnet: None,
+/// };
+/// let cache = AnswerCache::default();
+/// let now = Instant::now();
+/// assert!(cache.insert(key.clone(), (nameserver, message, 0, timing), now));
+///
+/// let (answer, age) = cache.get(&key, now + Duration::from_secs(100)).unwrap();
+/// assert_eq!(age, Duration::from_secs(100));
+/// assert_eq!(answer.1.answers[0].as_nonopt().unwrap().ttl, 200);
+/// assert!(cache.get(&key, now + Duration::from_secs(300)).is_none());
+/// ```
+#[derive(Debug)]
+pub struct AnswerCache {
+    entries: Mutex<Vec<Entry>>,
+    capacity: usize,
+}
+
+impl Default for AnswerCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CACHE_SIZE)
+    }
+}
+
+impl AnswerCache {
+    /// Creates a cache that keeps at most `capacity` answers. If it is full, expired answers are
+    /// removed first, then those that expire soonest.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(Vec::new()),
+            capacity,
+        }
+    }
+
+    /// Returns the cached answer for `key` if it has not expired at `now`, with its TTLs reduced
+    /// accordingly, and how long ago it was received.
+    pub fn get(&self, key: &CacheKey, now: Instant) -> Option<(Answer, Duration)> {
+        let entries = self.entries.lock().expect("Answer cache lock is poisoned.");
+        let entry = entries
+            .iter()
+            .find(|entry| entry.key == *key && entry.expires > now)?;
+        let age = now.saturating_duration_since(entry.received);
+        let mut answer = entry.answer.clone();
+        age_records(&mut answer.1, age);
+        Some((answer, age))
+    }
+
+    /// Keeps `answer`, which was received at `received`, for queries with `key`, if it may be
+    /// cached (see [`cache_ttl()`]). Returns whether it was kept.
+    ///
+    /// For DNS over HTTP(S), the HTTP caching headers apply as well (see
+    /// [`HttpCaching`](crate::net::HttpCaching)): the answer is not kept if it must not be cached,
+    /// it is kept for at most its `max-age`, and its `Age` counts as time it already spent in the
+    /// cache.
+    pub fn insert(&self, key: CacheKey, answer: Answer, received: Instant) -> bool {
+        let Some(mut ttl) = cache_ttl(&answer.1) else {
+            return false;
+        };
+        let mut received = received;
+        if let Some(http) = answer.3.http_cache {
+            if http.no_store {
+                return false;
+            }
+            if let Some(max_age) = http.max_age {
+                ttl = ttl.min(Duration::from_secs(max_age.into()));
+            }
+            let age = Duration::from_secs(http.age.into());
+            if ttl <= age {
+                return false;
+            }
+            received = received.checked_sub(age).unwrap_or(received);
+        }
+        let mut entries = self.entries.lock().expect("Answer cache lock is poisoned.");
+        entries.retain(|entry| entry.key != key && entry.expires > received);
+        if entries.len() >= self.capacity {
+            if let Some(i) = (0..entries.len()).min_by_key(|&i| entries[i].expires) {
+                entries.remove(i);
+            }
+        }
+        if self.capacity > 0 {
+            entries.push(Entry {
+                key,
+                answer,
+                received,
+                expires: received + ttl,
+            });
+        }
+        self.capacity > 0
+    }
+
+    /// Returns the number of cached answers, including expired ones that were not removed yet.
+    pub fn len(&self) -> usize {
+        self.entries
+            .lock()
+            .expect("Answer cache lock is poisoned.")
+            .len()
+    }
+
+    /// Returns whether no answers are cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes all answers.
+    pub fn clear(&self) {
+        self.entries
+            .lock()
+            .expect("Answer cache lock is poisoned.")
+            .clear();
+    }
+}
+
+/// How long a zone without DNSKEYs is remembered by a [`DnskeyCache`], as there is no TTL.
+pub const NO_DNSKEYS_TTL: Duration = Duration::from_secs(300);
+
+/// The DNSKEYs of a zone kept by a [`DnskeyCache`].
+#[derive(Clone, Debug)]
+pub struct CachedDnskeys {
+    /// The DNSKEY records and the RRSIGs covering them, as returned by
+    /// [`get_dnskeys()`](crate::util::get_dnskeys()).
+    pub records: Vec<NonOptRecord>,
+    /// The result of validating the DNSKEY record set with its own keys. This does not check the
+    /// chain of trust, but a set that is not [`ValidationState::Secure`] was either not signed or
+    /// altered.
+    pub state: ValidationState,
+    expires: Instant,
+}
+
+/// The DNSKEYs of zones, kept for the smallest TTL of their records so that validating several
+/// answers from the same zone (or tracing several queries through the same zones) only fetches
+/// them once.
+///
+/// # Examples
+/// ```rust
+/// use std::time::{Duration, Instant};
+/// use toluol::cache::DnskeyCache;
+/// use toluol_proto::dnssec::ValidationState;
+/// use toluol_proto::Name;
+///
+/// let cache = DnskeyCache::default();
+/// let zone = Name::from_ascii("example.com").unwrap();
+/// let now = Instant::now();
+/// let mut fetches = 0;
+/// for _ in 0..2 {
+///     let keys = cache
+///         .get_or_fetch(&zone, now, || {
+///             fetches += 1;
+///             Ok(Vec::new())
+///         })
+///         .unwrap();
+///     assert!(keys.records.is_empty());
+///     assert_eq!(keys.state, ValidationState::Insecure);
+/// }
+/// assert_eq!(fetches, 1);
+/// assert!(cache.get(&zone, now + Duration::from_secs(3600)).is_none());
+/// ```
+#[derive(Debug, Default)]
+pub struct DnskeyCache {
+    entries: Mutex<BTreeMap<Name, CachedDnskeys>>,
+}
+
+impl DnskeyCache {
+    /// Creates an empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the cached DNSKEYs of `zone` if they have not expired at `now`. Their TTLs are not
+    /// reduced, as that would invalidate their signatures.
+    pub fn get(&self, zone: &Name, now: Instant) -> Option<CachedDnskeys> {
+        let entries = self.entries.lock().expect("DNSKEY cache lock is poisoned.");
+        entries
+            .get(zone)
+            .filter(|entry| entry.expires > now)
+            .cloned()
+    }
+
+    /// Keeps `records` (DNSKEYs and RRSIGs, possibly none) as the DNSKEYs of `zone`, received at
+    /// `received`, after validating them with themselves. Returns the kept entry.
+    pub fn insert(
+        &self,
+        zone: Name,
+        records: Vec<NonOptRecord>,
+        received: Instant,
+    ) -> CachedDnskeys {
+        let state = validate_rrsets(&records, &records)
+            .into_iter()
+            .find(|validation| validation.rtype == RecordType::DNSKEY)
+            .map_or(ValidationState::Insecure, |validation| validation.state);
+        let ttl = records
+            .iter()
+            .map(|record| Duration::from_secs(record.ttl.into()))
+            .min()
+            .unwrap_or(NO_DNSKEYS_TTL);
+        let entry = CachedDnskeys {
+            records,
+            state,
+            expires: received + ttl,
+        };
+        let mut entries = self.entries.lock().expect("DNSKEY cache lock is poisoned.");
+        entries.retain(|_, entry| entry.expires > received);
+        entries.insert(zone, entry.clone());
+        entry
+    }
+
+    /// Returns the cached DNSKEYs of `zone`, or calls `fetch` to get them and keeps them.
+    ///
+    /// The cache is not locked while `fetch` runs, so concurrent calls for the same zone may both
+    /// fetch the DNSKEYs.
+    pub fn get_or_fetch(
+        &self,
+        zone: &Name,
+        now: Instant,
+        fetch: impl FnOnce() -> Result<Vec<NonOptRecord>>,
+    ) -> Result<CachedDnskeys> {
+        if let Some(entry) = self.get(zone, now) {
+            return Ok(entry);
+        }
+        let records = fetch()?;
+        Ok(self.insert(zone.clone(), records, Instant::now()))
+    }
+
+    /// Returns the number of zones whose DNSKEYs are cached, including expired ones that were not
+    /// removed yet.
+    pub fn len(&self) -> usize {
+        self.entries
+            .lock()
+            .expect("DNSKEY cache lock is poisoned.")
+            .len()
+    }
+
+    /// Returns whether no DNSKEYs are cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes all DNSKEYs.
+    pub fn clear(&self) {
+        self.entries
+            .lock()
+            .expect("DNSKEY cache lock is poisoned.")
+            .clear();
+    }
+}
+
+/// Returns how long `message` may be cached: the smallest TTL of its answer and authority
+/// records, or [`None`] if it must not be cached because it is neither a `NOERROR` nor an
+/// `NXDOMAIN` answer, is truncated, or contains no such records.
+pub fn cache_ttl(message: &Message) -> Option<Duration> {
+    let rcode = message.header.rcode;
+    if !matches!(rcode, Some(RCode::NOERROR | RCode::NXDOMAIN)) || message.header.flags.tc {
+        return None;
+    }
+    message
+        .answers
+        .iter()
+        .chain(&message.authoritative_answers)
+        .filter_map(|record| record.as_nonopt())
+        .map(|record| record.ttl)
+        .min()
+        .filter(|&ttl| ttl > 0)
+        .map(|ttl| Duration::from_secs(ttl.into()))
+}
+
+/// Reduces the TTLs of the records of `message` by `age`.
+fn age_records(message: &mut Message, age: Duration) {
+    let age = age.as_secs().min(u32::MAX.into()) as u32;
+    for record in message
+        .answers
+        .iter_mut()
+        .chain(&mut message.authoritative_answers)
+        .chain(&mut message.additional_answers)
+    {
+        if let Some(ttl) = record.as_nonopt().map(|record| record.ttl) {
+            record.set_ttl(ttl.saturating_sub(age));
+        }
+    }
+}
@@ -0,0 +1,450 @@
+//! Checking the `CDS` and `CDNSKEY` records a child zone publishes to have its parent update the
+//! delegation's `DS` records automatically ([RFC 7344](https://www.rfc-editor.org/rfc/rfc7344),
+//! [RFC 8078](https://www.rfc-editor.org/rfc/rfc8078)), e.g. when the zone is served by multiple
+//! signers ([RFC 8901](https://www.rfc-editor.org/rfc/rfc8901)).
+
+use std::fmt::Display;
+use std::thread;
+use toluol_proto::dnssec::{validate_rrsets_with, ValidationPolicy, ValidationState};
+use toluol_proto::error::DnssecError;
+use toluol_proto::rdata::ds::DigestType;
+use toluol_proto::rdata::{CDNSKEY, CDS, DNSKEY, DS};
+use toluol_proto::{Name, NonOptRecord, RCode, Rdata, RecordType};
+
+use crate::error::{bail, Result};
+use crate::util::{lookup, query_multiple};
+use crate::{ConnectionType, QueryMetadata};
+
+/// The record types queried from each of the child's nameservers.
+const CHILD_QTYPES: [RecordType; 3] = [RecordType::CDS, RecordType::CDNSKEY, RecordType::DNSKEY];
+
+/// The `CDS`, `CDNSKEY`, and `DNSKEY` records served by one of the child's nameservers, together
+/// with their `RRSIG`s.
+#[derive(Clone, Debug)]
+pub struct ChildRecords {
+    pub records: Vec<NonOptRecord>,
+}
+
+impl ChildRecords {
+    /// Returns the `CDS` records.
+    pub fn cds(&self) -> Vec<&CDS> {
+        self.records
+            .iter()
+            .filter_map(|record| record.rdata().as_cds())
+            .collect()
+    }
+
+    /// Returns the `CDNSKEY` records.
+    pub fn cdnskey(&self) -> Vec<&CDNSKEY> {
+        self.records
+            .iter()
+            .filter_map(|record| record.rdata().as_cdnskey())
+            .collect()
+    }
+
+    /// Returns the `DNSKEY` records.
+    pub fn dnskeys(&self) -> Vec<&DNSKEY> {
+        self.records
+            .iter()
+            .filter_map(|record| record.rdata().as_dnskey())
+            .collect()
+    }
+
+    /// Returns whether the `CDS` and `CDNSKEY` records are the same as in `other`.
+    fn same_cds(&self, other: &Self) -> bool {
+        let set = |records: &Self| {
+            let mut set: Vec<_> = records
+                .records
+                .iter()
+                .filter(|record| matches!(record.rtype, RecordType::CDS | RecordType::CDNSKEY))
+                .filter_map(|record| Some((u16::from(record.rtype), record.rdata().encode().ok()?)))
+                .collect();
+            set.sort();
+            set.dedup();
+            set
+        };
+        set(self) == set(other)
+    }
+}
+
+/// What a parent that follows RFC 7344 and RFC 8078 would do with the delegation's `DS` records.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParentAction {
+    /// Nothing, as the child publishes neither `CDS` nor `CDNSKEY` records.
+    None,
+    /// Nothing, as the `DS` records already match the `CDS`/`CDNSKEY` records.
+    Unchanged,
+    /// Add these `DS` records to the so far insecure delegation. Parents usually require further
+    /// proof before doing so (see RFC 8078, Section 3).
+    Add(Vec<DS>),
+    /// Add and remove these `DS` records, e.g. for a key rollover.
+    Roll { add: Vec<DS>, remove: Vec<DS> },
+    /// Remove all `DS` records, making the delegation insecure.
+    Delete,
+}
+
+/// A reason for a parent to ignore the child's `CDS`/`CDNSKEY` records.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Problem {
+    /// This nameserver of the child could not be queried.
+    ServerFailed(Name),
+    /// The child's nameservers do not all serve the same `CDS` and `CDNSKEY` records.
+    Inconsistent,
+    /// The `CDS` and `CDNSKEY` records do not describe the same keys.
+    CdsCdnskeyMismatch,
+    /// A delete record is published together with other records of the same type.
+    MixedDelete(RecordType),
+    /// This `CDS` record does not refer to any of the child's `DNSKEY`s.
+    CdsWithoutDnskey(DS),
+    /// This `CDNSKEY` record is none of the child's `DNSKEY`s.
+    CdnskeyWithoutDnskey(DNSKEY),
+    /// The record set of this type is not signed by a key that the current `DS` records (or, if
+    /// there are none, the new ones) refer to.
+    NotSignedByTrustedKey(RecordType),
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ServerFailed(nameserver) => write!(f, "{} could not be queried", nameserver),
+            Self::Inconsistent => write!(f, "the nameservers serve different CDS/CDNSKEY records"),
+            Self::CdsCdnskeyMismatch => {
+                write!(f, "the CDS and CDNSKEY records describe different keys")
+            }
+            Self::MixedDelete(rtype) => {
+                write!(
+                    f,
+                    "the {} delete record is not the only {} record",
+                    rtype, rtype
+                )
+            }
+            Self::CdsWithoutDnskey(ds) => {
+                write!(f, "CDS {} does not refer to any DNSKEY of the zone", ds)
+            }
+            Self::CdnskeyWithoutDnskey(dnskey) => write!(
+                f,
+                "CDNSKEY with key tag {} is no DNSKEY of the zone",
+                dnskey.key_tag()
+            ),
+            Self::NotSignedByTrustedKey(rtype) => write!(
+                f,
+                "the {} records are not signed by a key the DS records refer to",
+                rtype
+            ),
+        }
+    }
+}
+
+/// The result of [`check()`].
+#[derive(Debug)]
+pub struct CdsCheck {
+    /// The child zone.
+    pub zone: Name,
+    /// The `DS` records currently published by the parent.
+    pub ds: Vec<DS>,
+    /// The records served by each of the child's nameservers.
+    pub servers: Vec<(Name, Result<ChildRecords>)>,
+    /// What the parent would do if there were no [`Self::problems`].
+    pub action: ParentAction,
+    /// The reasons for the parent to ignore the `CDS`/`CDNSKEY` records; if there are any, it
+    /// does not change the `DS` records at all.
+    pub problems: Vec<Problem>,
+}
+
+impl CdsCheck {
+    /// Returns what the parent would actually do, i.e. [`ParentAction::Unchanged`] if there are
+    /// problems.
+    pub fn effective_action(&self) -> ParentAction {
+        if self.problems.is_empty() {
+            self.action.clone()
+        } else {
+            ParentAction::Unchanged
+        }
+    }
+}
+
+/// Queries the `DS` records of the zone in `metadata` via the nameserver in `metadata` and its
+/// `CDS`, `CDNSKEY`, and `DNSKEY` records from each of its authoritative nameservers, and
+/// evaluates them like an automated parent that follows `policy` would (see [`evaluate()`]).
+///
+/// Returns an error if the `DS` or `NS` records of the zone cannot be looked up.
+pub fn check(
+    metadata: &QueryMetadata,
+    bufsize: u16,
+    policy: &ValidationPolicy,
+) -> Result<CdsCheck> {
+    let zone = metadata.name.clone();
+    let ds: Vec<_> = lookup(&zone, RecordType::DS, metadata, bufsize)?
+        .into_iter()
+        .filter_map(|rdata| match rdata {
+            Rdata::DS(ds) => Some(ds),
+            _ => None,
+        })
+        .collect();
+    let nameservers: Vec<_> = lookup(&zone, RecordType::NS, metadata, bufsize)?
+        .into_iter()
+        .filter_map(|rdata| match rdata {
+            Rdata::NS(ns) => Some(ns.name),
+            _ => None,
+        })
+        .collect();
+    if nameservers.is_empty() {
+        bail!(
+            configuration,
+            "{} has no NS records, so it is no zone.",
+            zone
+        );
+    }
+
+    let servers: Vec<_> = thread::scope(|s| {
+        let handles: Vec<_> = nameservers
+            .into_iter()
+            .map(|nameserver| {
+                s.spawn(move || {
+                    let records = query_child(metadata, &nameserver, bufsize);
+                    (nameserver, records)
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("Query thread panicked."))
+            .collect()
+    });
+
+    let (action, problems) = evaluate(&zone, &ds, &servers, policy);
+    Ok(CdsCheck {
+        zone,
+        ds,
+        servers,
+        action,
+        problems,
+    })
+}
+
+/// Evaluates the `CDS`/`CDNSKEY` records served by the child's nameservers against the parent's
+/// `DS` records as described in RFC 7344, Section 4.1, and RFC 8078:
+/// - all nameservers must serve the same records, and `CDS` and `CDNSKEY` records must agree,
+/// - the records must refer to the zone's `DNSKEY`s, and
+/// - they must be signed by a key referred to by the current `DS` records (or, when securing an
+///   insecure delegation, by the new ones).
+///
+/// Only `DS` and `CDS` records whose digest type and algorithm `policy` accepts make a key
+/// trusted, and the signatures are validated according to `policy`.
+///
+/// Returns what the parent would do and why it would refuse to.
+pub fn evaluate(
+    zone: &Name,
+    ds: &[DS],
+    servers: &[(Name, Result<ChildRecords>)],
+    policy: &ValidationPolicy,
+) -> (ParentAction, Vec<Problem>) {
+    let mut problems: Vec<_> = servers
+        .iter()
+        .filter(|(_, records)| records.is_err())
+        .map(|(nameserver, _)| Problem::ServerFailed(nameserver.clone()))
+        .collect();
+    let answered: Vec<_> = servers
+        .iter()
+        .filter_map(|(_, records)| records.as_ref().ok())
+        .collect();
+    let child = match answered.first() {
+        Some(child) => *child,
+        None => return (ParentAction::None, problems),
+    };
+    if answered.iter().any(|other| !child.same_cds(other)) {
+        problems.push(Problem::Inconsistent);
+    }
+
+    let cds = child.cds();
+    let cdnskey = child.cdnskey();
+    let dnskeys = child.dnskeys();
+    if cds.is_empty() && cdnskey.is_empty() {
+        return (ParentAction::None, problems);
+    }
+
+    // RFC 8078, Section 4: a delete record must be the only one of its type, and if both types are
+    // published, both must ask for deletion
+    let cds_delete = cds.iter().any(|ds| ds.is_delete());
+    let cdnskey_delete = cdnskey.iter().any(|key| key.is_delete());
+    if cds_delete && cds.len() > 1 {
+        problems.push(Problem::MixedDelete(RecordType::CDS));
+    }
+    if cdnskey_delete && cdnskey.len() > 1 {
+        problems.push(Problem::MixedDelete(RecordType::CDNSKEY));
+    }
+    if cds_delete || cdnskey_delete {
+        if (!cds.is_empty() && !cds_delete) || (!cdnskey.is_empty() && !cdnskey_delete) {
+            problems.push(Problem::CdsCdnskeyMismatch);
+        }
+        check_signatures(zone, ds, &[], child, policy, &mut problems);
+        let action = if ds.is_empty() {
+            ParentAction::Unchanged
+        } else {
+            ParentAction::Delete
+        };
+        return (action, problems);
+    }
+
+    for cds in &cds {
+        if !dnskeys.iter().any(|key| refers_to(cds, zone, key)) {
+            problems.push(Problem::CdsWithoutDnskey((*cds).clone()));
+        }
+    }
+    for cdnskey in &cdnskey {
+        if !dnskeys.contains(cdnskey) {
+            problems.push(Problem::CdnskeyWithoutDnskey((*cdnskey).clone()));
+        }
+    }
+    if !cds.is_empty()
+        && !cdnskey.is_empty()
+        && (cds
+            .iter()
+            .any(|ds| !cdnskey.iter().any(|key| refers_to(ds, zone, key)))
+            || cdnskey
+                .iter()
+                .any(|key| !cds.iter().any(|ds| refers_to(ds, zone, key))))
+    {
+        problems.push(Problem::CdsCdnskeyMismatch);
+    }
+
+    // the parent uses the CDS records as they are, or computes DS records from the CDNSKEY
+    // records with a digest type of its choice
+    let (add, remove): (Vec<DS>, Vec<DS>) = if !cds.is_empty() {
+        (
+            cds.iter()
+                .filter(|new| !ds.contains(new))
+                .map(|new| (*new).clone())
+                .collect(),
+            ds.iter()
+                .filter(|old| !cds.contains(old))
+                .cloned()
+                .collect(),
+        )
+    } else {
+        (
+            cdnskey
+                .iter()
+                .filter(|key| !ds.iter().any(|old| refers_to(old, zone, key)))
+                .filter_map(|key| key.to_ds(zone, DigestType::SHA256).ok())
+                .collect(),
+            ds.iter()
+                .filter(|old| !cdnskey.iter().any(|key| refers_to(old, zone, key)))
+                .cloned()
+                .collect(),
+        )
+    };
+
+    let new_keys: Vec<_> = dnskeys
+        .iter()
+        .filter(|key| {
+            cds.iter().any(|ds| trusted_by(ds, zone, key, policy)) || cdnskey.contains(key)
+        })
+        .copied()
+        .collect();
+    check_signatures(zone, ds, &new_keys, child, policy, &mut problems);
+
+    let action = if add.is_empty() && remove.is_empty() {
+        ParentAction::Unchanged
+    } else if ds.is_empty() {
+        ParentAction::Add(add)
+    } else {
+        ParentAction::Roll { add, remove }
+    };
+    (action, problems)
+}
+
+/// Sends the queries for [`CHILD_QTYPES`] to `nameserver`.
+fn query_child(metadata: &QueryMetadata, nameserver: &Name, bufsize: u16) -> Result<ChildRecords> {
+    let mut metadata = metadata.clone();
+    metadata.nameserver = nameserver.to_string();
+    metadata.port = 53;
+    if metadata.connection_type != ConnectionType::Tcp {
+        metadata.connection_type = ConnectionType::Udp;
+    }
+    metadata.fetch_dnssec = true;
+    metadata.client_subnet = None;
+
+    let mut records = Vec::new();
+    for (qtype, answer) in query_multiple(&metadata, &CHILD_QTYPES, bufsize) {
+        let (_, res, _, _) = answer?;
+        let rcode = match res.opt() {
+            Some(opt) => opt.rcode,
+            None => res.header.rcode,
+        };
+        match rcode.unwrap_or(RCode::NOERROR) {
+            RCode::NOERROR => {}
+            rcode => bail!(
+                transport,
+                "Querying {} records failed with {}.",
+                qtype,
+                rcode
+            ),
+        }
+        records.extend(
+            res.answers
+                .into_iter()
+                .filter_map(|record| NonOptRecord::try_from(record).ok())
+                .filter(|record| {
+                    record.rtype == qtype
+                        || record.rdata().as_rrsig().map(|rrsig| rrsig.type_covered) == Some(qtype)
+                }),
+        );
+    }
+    Ok(ChildRecords { records })
+}
+
+/// Checks that the `CDS` and `CDNSKEY` record sets of `child` are signed by a `DNSKEY` that one of
+/// the current `DS` records that `policy` accepts refers to, or, if there are none, by one of
+/// `new_keys`.
+fn check_signatures(
+    zone: &Name,
+    ds: &[DS],
+    new_keys: &[&DNSKEY],
+    child: &ChildRecords,
+    policy: &ValidationPolicy,
+    problems: &mut Vec<Problem>,
+) {
+    let trusted: Vec<_> = child
+        .records
+        .iter()
+        .filter(|record| match record.rdata().as_dnskey() {
+            Some(key) if ds.is_empty() => new_keys.contains(&key),
+            Some(key) => ds.iter().any(|ds| trusted_by(ds, zone, key, policy)),
+            None => false,
+        })
+        .cloned()
+        .collect();
+    let records = child
+        .records
+        .iter()
+        .filter(|record| record.rtype != RecordType::DNSKEY);
+    for validation in validate_rrsets_with(records, &trusted, policy) {
+        if validation.rtype == RecordType::DNSKEY {
+            continue;
+        }
+        // signatures with algorithms that toluol cannot validate yet are accepted, as long as
+        // the key is a trusted one
+        let signed = validation.state == ValidationState::Secure
+            || validation
+                .signatures
+                .iter()
+                .any(|sig| matches!(sig.result, Err(DnssecError::UnsupportedAlgorithm)));
+        if !signed {
+            problems.push(Problem::NotSignedByTrustedKey(validation.rtype));
+        }
+    }
+}
+
+/// Returns whether `ds` refers to `dnskey`. If the digest cannot be checked because its type is
+/// not supported, the key tag and algorithm must match only.
+fn refers_to(ds: &DS, zone: &Name, dnskey: &DNSKEY) -> bool {
+    // `DS::matches()` only fails after the key tag and algorithm matched
+    ds.matches(zone, dnskey).unwrap_or(true)
+}
+
+/// Returns whether `ds` refers to `dnskey` (see [`refers_to()`]) and `policy` accepts `ds`, so
+/// that `dnskey` is trusted.
+fn trusted_by(ds: &DS, zone: &Name, dnskey: &DNSKEY, policy: &ValidationPolicy) -> bool {
+    policy.check_ds(ds).is_ok() && refers_to(ds, zone, dnskey)
+}
@@ -0,0 +1,355 @@
+//! Resolving service URIs with the Dynamic Delegation Discovery System (DDDS), using NAPTR
+//! records.
+//! [\[RFC 3402\]](https://www.rfc-editor.org/rfc/rfc3402)
+//! [\[RFC 3403\]](https://www.rfc-editor.org/rfc/rfc3403)
+//!
+//! This is e.g. used by ENUM ([RFC 6116](https://www.rfc-editor.org/rfc/rfc6116)) to map phone
+//! numbers to URIs, and by SIP ([RFC 3263](https://www.rfc-editor.org/rfc/rfc3263)) to find the
+//! servers of a domain.
+
+use regex::RegexBuilder;
+use std::fmt::Display;
+use std::net::IpAddr;
+use toluol_proto::rdata::{NAPTR, SRV};
+use toluol_proto::{Name, Rdata, RecordType};
+
+use crate::error::{bail, Context, Result};
+use crate::util::lookup;
+use crate::QueryMetadata;
+
+/// The maximum number of non-terminal rules that are followed, to prevent loops.
+const MAX_REWRITES: usize = 10;
+
+/// The maximum size of a compiled NAPTR regular expression, so that malicious records can't use
+/// up too much memory.
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+/// What a terminal rule resolved to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Terminal {
+    /// The rule has the `U` flag, i.e. its output is a URI.
+    Uri(String),
+    /// The rule has the `S` flag, i.e. its output is a name with SRV records. Contains the SRV
+    /// records, each with the addresses of its target.
+    Srv {
+        name: Name,
+        records: Vec<(SRV, Vec<IpAddr>)>,
+    },
+    /// The rule has the `A` flag, i.e. its output is a name with A/AAAA records.
+    Address { name: Name, addresses: Vec<IpAddr> },
+    /// The rule has the `P` flag, i.e. the rest of the resolution is application specific.
+    /// Contains the rule's output.
+    Protocol(String),
+}
+
+/// A terminal rule and what it resolved to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Resolution {
+    /// The NAPTR record containing the rule.
+    pub rule: NAPTR,
+    pub terminal: Terminal,
+}
+
+/// The flags of a rule.
+enum Flag {
+    NonTerminal,
+    Terminal(char),
+    /// Rules with unknown flags must be ignored.
+    Unknown,
+}
+
+impl Display for Terminal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let addresses = |addresses: &[IpAddr]| {
+            let addresses: Vec<_> = addresses.iter().map(IpAddr::to_string).collect();
+            addresses.join(", ")
+        };
+        match self {
+            Self::Uri(uri) => write!(f, "{}", uri),
+            Self::Srv { name, records } => {
+                let records: Vec<_> = records
+                    .iter()
+                    .map(|(srv, addrs)| format!("{} ({})", srv, addresses(addrs)))
+                    .collect();
+                write!(f, "{} SRV {}", name, records.join("; "))
+            }
+            Self::Address {
+                name,
+                addresses: addrs,
+            } => write!(f, "{} {}", name, addresses(addrs)),
+            Self::Protocol(output) => write!(f, "{} (protocol specific)", output),
+        }
+    }
+}
+
+/// Runs the DDDS algorithm for the application unique string `aus`, starting with the NAPTR
+/// records of `key`.
+///
+/// Only terminal rules whose services are accepted by `accept_service` are used. Non-terminal
+/// rules are followed (regardless of their services), and the terminal rules of `S` and `A` type
+/// are resolved to the SRV records and addresses they point to.
+///
+/// The results are ordered by the rules' order and preference. As soon as rules with one order
+/// value produced results, rules with higher order values are ignored.
+///
+/// `metadata.name` and `metadata.qtype` are ignored.
+pub fn resolve(
+    aus: &str,
+    key: &Name,
+    accept_service: impl Fn(&str) -> bool,
+    metadata: &QueryMetadata,
+    bufsize: u16,
+) -> Result<Vec<Resolution>> {
+    resolve_key(aus, key, &accept_service, metadata, bufsize, 0)
+}
+
+fn resolve_key(
+    aus: &str,
+    key: &Name,
+    accept_service: &dyn Fn(&str) -> bool,
+    metadata: &QueryMetadata,
+    bufsize: u16,
+    rewrites: usize,
+) -> Result<Vec<Resolution>> {
+    if rewrites > MAX_REWRITES {
+        bail!(
+            parse,
+            "Followed more than {} non-terminal NAPTR rules, there is probably a loop.",
+            MAX_REWRITES
+        );
+    }
+
+    let mut rules: Vec<_> = lookup(key, RecordType::NAPTR, metadata, bufsize)?
+        .into_iter()
+        .filter_map(|rdata| match rdata {
+            Rdata::NAPTR(naptr) => Some(naptr),
+            _ => None,
+        })
+        .collect();
+    rules.sort_by_key(|rule| (rule.order, rule.preference));
+
+    let mut res = Vec::new();
+    let mut matched_order = None;
+    for rule in rules {
+        let order = rule.order;
+        if matched_order.is_some_and(|matched| matched != order) {
+            break;
+        }
+        let flag = flag(&rule.flags);
+        match flag {
+            Flag::Unknown => continue,
+            Flag::Terminal(_) if !accept_service(&rule.services) => continue,
+            _ => {}
+        }
+        let output = match rewrite(&rule, aus)? {
+            Some(output) => output,
+            None => continue,
+        };
+
+        let len = res.len();
+        match flag {
+            Flag::Unknown => unreachable!("rules with unknown flags are skipped"),
+            Flag::NonTerminal => {
+                let next = output_name(&output)?;
+                res.extend(resolve_key(
+                    aus,
+                    &next,
+                    accept_service,
+                    metadata,
+                    bufsize,
+                    rewrites + 1,
+                )?);
+            }
+            Flag::Terminal(flag) => {
+                let terminal = match flag {
+                    'U' => Terminal::Uri(output),
+                    'S' => {
+                        let name = output_name(&output)?;
+                        let records = lookup_srv(&name, metadata, bufsize)?;
+                        Terminal::Srv { name, records }
+                    }
+                    'A' => {
+                        let name = output_name(&output)?;
+                        let addresses = lookup_addresses(&name, metadata, bufsize)?;
+                        Terminal::Address { name, addresses }
+                    }
+                    _ => Terminal::Protocol(output),
+                };
+                res.push(Resolution { rule, terminal });
+            }
+        }
+        if res.len() > len {
+            matched_order = Some(order);
+        }
+    }
+    Ok(res)
+}
+
+/// Parses the flags of a rule. The flags `S`, `A`, `U`, and `P` are terminal and mutually
+/// exclusive. [\[RFC 3404, Section 4.3\]](https://www.rfc-editor.org/rfc/rfc3404#section-4.3)
+fn flag(flags: &str) -> Flag {
+    let flags = flags.to_ascii_uppercase();
+    match flags.as_bytes() {
+        [] => Flag::NonTerminal,
+        [flag @ (b'S' | b'A' | b'U' | b'P')] => Flag::Terminal(char::from(*flag)),
+        _ => Flag::Unknown,
+    }
+}
+
+/// Returns the output of `rule` for the application unique string `aus`, i.e. the result of
+/// applying its regular expression or its replacement. Returns [`None`] if the regular expression
+/// doesn't match.
+fn rewrite(rule: &NAPTR, aus: &str) -> Result<Option<String>> {
+    if rule.regexp.is_empty() {
+        Ok(Some(rule.replacement.to_string()))
+    } else {
+        apply_regexp(&rule.regexp, aus)
+    }
+}
+
+/// Parses the output of a rule as the next name to look up.
+fn output_name(output: &str) -> Result<Name> {
+    Name::from_ascii(output).parse(format!("Invalid name in NAPTR rule: {}", output))
+}
+
+/// Applies a NAPTR substitution expression of the form `!ere!replacement!flags` to `input`.
+///
+/// The first character is the delimiter, which can be escaped with a backslash inside the regular
+/// expression and the replacement. `\1` to `\9` in the replacement refer to the groups matched by
+/// the regular expression. The only flag is `i`, which makes the match case-insensitive.
+/// [\[RFC 3402, Section 3.2\]](https://www.rfc-editor.org/rfc/rfc3402#section-3.2)
+///
+/// The regular expression is evaluated in linear time, so malicious records can't make it take
+/// arbitrarily long.
+///
+/// Returns the replacement (with the groups substituted) if the regular expression matches
+/// `input`, and [`None`] otherwise.
+///
+/// # Examples
+/// ```rust
+/// use toluol::ddds::apply_regexp;
+///
+/// let uri = apply_regexp("!^.*$!sip:info@example.com!", "+441632960083").unwrap();
+/// assert_eq!(uri.as_deref(), Some("sip:info@example.com"));
+///
+/// let uri = apply_regexp(r"!^\+44(.*)$!tel:0\1!", "+441632960083").unwrap();
+/// assert_eq!(uri.as_deref(), Some("tel:01632960083"));
+///
+/// assert_eq!(apply_regexp(r"!^\+49!x!", "+441632960083").unwrap(), None);
+/// ```
+pub fn apply_regexp(regexp: &str, input: &str) -> Result<Option<String>> {
+    let mut chars = regexp.chars();
+    let delim = chars.next().parse("Empty NAPTR regular expression.")?;
+    if delim.is_ascii_digit() || delim == '\\' || delim == 'i' {
+        bail!(
+            parse,
+            "Invalid delimiter in NAPTR regular expression: {}",
+            regexp
+        );
+    }
+
+    let mut parts = vec![String::new()];
+    let mut escaped = false;
+    for c in chars {
+        let part = parts.last_mut().expect("parts is never empty");
+        if escaped {
+            // escaped delimiters become literal, other escapes are kept for the regex engine or
+            // the replacement
+            if c != delim {
+                part.push('\\');
+            }
+            part.push(c);
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == delim {
+            parts.push(String::new());
+        } else {
+            part.push(c);
+        }
+    }
+    let (ere, replacement, flags) = match &parts[..] {
+        [ere, replacement, flags] if !escaped => (ere, replacement, flags),
+        _ => bail!(parse, "Invalid NAPTR regular expression: {}", regexp),
+    };
+    if !flags.is_empty() && flags != "i" {
+        bail!(
+            parse,
+            "Invalid flags in NAPTR regular expression: {}",
+            regexp
+        );
+    }
+
+    let regex = RegexBuilder::new(ere)
+        .case_insensitive(flags == "i")
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+        .parse(format!("Invalid NAPTR regular expression: {}", regexp))?;
+    let captures = match regex.captures(input) {
+        Some(captures) => captures,
+        None => return Ok(None),
+    };
+
+    let mut res = String::new();
+    let mut replacement = replacement.chars();
+    while let Some(c) = replacement.next() {
+        if c != '\\' {
+            res.push(c);
+            continue;
+        }
+        match replacement.next() {
+            Some(digit @ '1'..='9') => {
+                let group = digit as usize - '0' as usize;
+                res.push_str(captures.get(group).map_or("", |m| m.as_str()));
+            }
+            Some(c) => res.push(c),
+            None => res.push('\\'),
+        }
+    }
+    Ok(Some(res))
+}
+
+/// Looks up the SRV records of `name` and the addresses of their targets.
+fn lookup_srv(
+    name: &Name,
+    metadata: &QueryMetadata,
+    bufsize: u16,
+) -> Result<Vec<(SRV, Vec<IpAddr>)>> {
+    let mut records: Vec<_> = lookup(name, RecordType::SRV, metadata, bufsize)?
+        .into_iter()
+        .filter_map(|rdata| match rdata {
+            Rdata::SRV(srv) => Some(srv),
+            _ => None,
+        })
+        .collect();
+    records.sort_by_key(|srv| (srv.priority, u16::MAX - srv.weight));
+
+    let mut res = Vec::with_capacity(records.len());
+    for srv in records {
+        // a target of "." means that the service is not available
+        let addresses = if srv.target.is_root() {
+            Vec::new()
+        } else {
+            lookup_addresses(&srv.target, metadata, bufsize)?
+        };
+        res.push((srv, addresses));
+    }
+    Ok(res)
+}
+
+/// Looks up the A and AAAA records of `name`.
+fn lookup_addresses(name: &Name, metadata: &QueryMetadata, bufsize: u16) -> Result<Vec<IpAddr>> {
+    let mut res = Vec::new();
+    for qtype in [RecordType::A, RecordType::AAAA] {
+        res.extend(
+            lookup(name, qtype, metadata, bufsize)?
+                .into_iter()
+                .filter_map(|rdata| match rdata {
+                    Rdata::A(a) => Some(IpAddr::V4(a.address)),
+                    Rdata::AAAA(aaaa) => Some(IpAddr::V6(aaaa.address)),
+                    _ => None,
+                }),
+        );
+    }
+    Ok(res)
+}
@@ -0,0 +1,250 @@
+//! Recognizing addresses synthesized by DNS64 resolvers and inferring the NAT64 prefix they use, as
+//! described in [RFC 6052](https://www.rfc-editor.org/rfc/rfc6052) and
+//! [RFC 7050](https://www.rfc-editor.org/rfc/rfc7050).
+
+use std::fmt::Display;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use toluol_proto::{Name, Rdata, Record, RecordType};
+
+use crate::error::{bail, Context, Error, Result};
+use crate::util::query_single;
+use crate::QueryMetadata;
+
+/// The lengths a NAT64 prefix may have, see RFC 6052, Section 2.2.
+pub const PREFIX_LENGTHS: [u8; 6] = [96, 64, 56, 48, 40, 32];
+
+/// The name that is well-known to only have `A` records, so that its `AAAA` records are always
+/// synthesized by DNS64 resolvers (see RFC 7050, Section 2.2).
+pub const IPV4ONLY_NAME: &str = "ipv4only.arpa";
+
+/// A NAT64 prefix, i.e. an IPv6 prefix into which IPv4 addresses are embedded.
+///
+/// # Examples
+/// ```rust
+/// use std::net::{Ipv4Addr, Ipv6Addr};
+/// use toluol::dns64::Nat64Prefix;
+///
+/// let prefix: Nat64Prefix = "2001:db8:64::/48".parse().unwrap();
+/// let ipv4 = Ipv4Addr::new(192, 0, 2, 33);
+/// let synthesized = prefix.synthesize(ipv4);
+/// // the bits 64 to 71 are skipped
+/// assert_eq!(synthesized, "2001:db8:64:c000:2:2100::".parse::<Ipv6Addr>().unwrap());
+/// assert_eq!(prefix.extract(synthesized), Some(ipv4));
+///
+/// let wkp = Nat64Prefix::WELL_KNOWN;
+/// assert_eq!(wkp.synthesize(ipv4), "64:ff9b::c000:221".parse::<Ipv6Addr>().unwrap());
+/// assert_eq!(wkp.extract(synthesized), None);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Nat64Prefix {
+    prefix: Ipv6Addr,
+    len: u8,
+}
+
+impl Nat64Prefix {
+    /// The Well-Known Prefix `64:ff9b::/96` (see RFC 6052, Section 2.1).
+    pub const WELL_KNOWN: Self = Self {
+        prefix: Ipv6Addr::new(0x64, 0xff9b, 0, 0, 0, 0, 0, 0),
+        len: 96,
+    };
+
+    /// Creates a NAT64 prefix from the first `len` bits of `prefix`.
+    ///
+    /// Returns an error if `len` is not one of [`PREFIX_LENGTHS`].
+    pub fn new(prefix: Ipv6Addr, len: u8) -> Result<Self> {
+        if !PREFIX_LENGTHS.contains(&len) {
+            bail!(
+                configuration,
+                "Invalid NAT64 prefix length: must be 32, 40, 48, 56, 64, or 96, got {}.",
+                len
+            );
+        }
+        Ok(Self {
+            prefix: mask(prefix, len),
+            len,
+        })
+    }
+
+    /// Returns the prefix, with all bits after the prefix length set to zero.
+    pub fn prefix(&self) -> Ipv6Addr {
+        self.prefix
+    }
+
+    /// Returns the length of the prefix in bits.
+    pub fn prefix_len(&self) -> u8 {
+        self.len
+    }
+
+    /// Returns the IPv6 address a DNS64 resolver synthesizes from `address` with this prefix.
+    pub fn synthesize(&self, address: Ipv4Addr) -> Ipv6Addr {
+        let mut octets = self.prefix.octets();
+        for (i, octet) in embedded_octets(self.len).zip(address.octets()) {
+            octets[i] = octet;
+        }
+        octets.into()
+    }
+
+    /// Returns the IPv4 address embedded in `address`, or [`None`] if `address` is not within this
+    /// prefix. This reverses [`synthesize()`](Self::synthesize()).
+    pub fn extract(&self, address: Ipv6Addr) -> Option<Ipv4Addr> {
+        if mask(address, self.len) != self.prefix {
+            return None;
+        }
+        let octets = address.octets();
+        // the bits 64 to 71 must be zero (unless they are part of a /96 prefix)
+        if self.len < 96 && octets[8] != 0 {
+            return None;
+        }
+        let mut ipv4 = [0; 4];
+        for (octet, i) in ipv4.iter_mut().zip(embedded_octets(self.len)) {
+            *octet = octets[i];
+        }
+        Some(ipv4.into())
+    }
+}
+
+impl FromStr for Nat64Prefix {
+    type Err = Error;
+
+    /// Parses a prefix like `64:ff9b::/96`.
+    fn from_str(s: &str) -> Result<Self> {
+        let (prefix, len) = s.split_once('/').configuration(format!(
+            "Invalid NAT64 prefix: missing prefix length in {}.",
+            s
+        ))?;
+        let prefix = prefix.parse().configuration(format!(
+            "Invalid NAT64 prefix: {} is no IPv6 address.",
+            prefix
+        ))?;
+        let len = len
+            .parse()
+            .configuration(format!("Invalid NAT64 prefix length: {}.", len))?;
+        Self::new(prefix, len)
+    }
+}
+
+impl Display for Nat64Prefix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.prefix, self.len)
+    }
+}
+
+/// Returns the IPv4 address embedded in `address` and the prefix it was embedded with, if
+/// `address` is within one of `prefixes`, i.e. if it was presumably synthesized by a DNS64
+/// resolver.
+pub fn unsynthesize(
+    address: Ipv6Addr,
+    prefixes: &[Nat64Prefix],
+) -> Option<(Ipv4Addr, Nat64Prefix)> {
+    prefixes
+        .iter()
+        .find_map(|prefix| prefix.extract(address).map(|ipv4| (ipv4, *prefix)))
+}
+
+/// Infers the NAT64 prefixes with which the addresses in `aaaa` were synthesized from the ones in
+/// `a`, similar to RFC 7050, Section 3, but for any name.
+///
+/// # Examples
+/// ```rust
+/// use toluol::dns64::{infer_prefixes, Nat64Prefix};
+///
+/// let a = ["192.0.0.170".parse().unwrap(), "192.0.0.171".parse().unwrap()];
+/// let aaaa = ["2001:db8:64::c000:aa".parse().unwrap(), "2001:db8::1".parse().unwrap()];
+/// assert_eq!(infer_prefixes(&a, &aaaa), vec!["2001:db8:64::/96".parse().unwrap()]);
+/// ```
+pub fn infer_prefixes(a: &[Ipv4Addr], aaaa: &[Ipv6Addr]) -> Vec<Nat64Prefix> {
+    let mut prefixes = Vec::new();
+    for &address in aaaa {
+        let prefix = PREFIX_LENGTHS
+            .iter()
+            .filter_map(|&len| Nat64Prefix::new(address, len).ok())
+            .find(|prefix| {
+                prefix
+                    .extract(address)
+                    .is_some_and(|ipv4| a.contains(&ipv4))
+            });
+        if let Some(prefix) = prefix {
+            if !prefixes.contains(&prefix) {
+                prefixes.push(prefix);
+            }
+        }
+    }
+    prefixes
+}
+
+/// The result of comparing the `A` and `AAAA` records of a name, see [`check()`].
+#[derive(Clone, Debug)]
+pub struct Dns64Check {
+    /// The name whose records were compared.
+    pub name: Name,
+    /// The addresses from the `A` records.
+    pub a: Vec<Ipv4Addr>,
+    /// The addresses from the `AAAA` records.
+    pub aaaa: Vec<Ipv6Addr>,
+    /// The NAT64 prefixes inferred from the addresses; empty if the nameserver does not seem to
+    /// use DNS64 (or the name has `AAAA` records of its own).
+    pub prefixes: Vec<Nat64Prefix>,
+}
+
+/// Queries the `A` and `AAAA` records of the name in `metadata` and infers the NAT64 prefixes the
+/// nameserver uses to synthesize `AAAA` records.
+///
+/// DNS64 resolvers only synthesize `AAAA` records for names without any, so the name should not
+/// have `AAAA` records, e.g. [`IPV4ONLY_NAME`].
+pub fn check(metadata: &QueryMetadata, bufsize: u16) -> Result<Dns64Check> {
+    let mut metadata = metadata.clone();
+    metadata.qtype = RecordType::A;
+    let a = answer_addresses(&metadata, bufsize)?
+        .into_iter()
+        .filter_map(|ip| match ip {
+            IpAddr::V4(ip) => Some(ip),
+            IpAddr::V6(_) => None,
+        })
+        .collect::<Vec<_>>();
+    metadata.qtype = RecordType::AAAA;
+    let aaaa = answer_addresses(&metadata, bufsize)?
+        .into_iter()
+        .filter_map(|ip| match ip {
+            IpAddr::V4(_) => None,
+            IpAddr::V6(ip) => Some(ip),
+        })
+        .collect::<Vec<_>>();
+
+    let prefixes = infer_prefixes(&a, &aaaa);
+    Ok(Dns64Check {
+        name: metadata.name,
+        a,
+        aaaa,
+        prefixes,
+    })
+}
+
+/// Sends the query specified by `metadata` and returns the addresses in the answer section.
+fn answer_addresses(metadata: &QueryMetadata, bufsize: u16) -> Result<Vec<IpAddr>> {
+    let (_, res, _, _) = query_single(metadata, bufsize).transport(format!(
+        "Could not look up {} records for {}.",
+        metadata.qtype, metadata.name
+    ))?;
+    Ok(res
+        .answers
+        .iter()
+        .filter_map(Record::as_nonopt)
+        .filter_map(|record| match record.rdata() {
+            Rdata::A(a) => Some(a.address.into()),
+            Rdata::AAAA(aaaa) => Some(aaaa.address.into()),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Returns the first `len` bits of `address`.
+fn mask(address: Ipv6Addr, len: u8) -> Ipv6Addr {
+    (u128::from(address) & (u128::MAX << (128 - len as u32))).into()
+}
+
+/// Returns the indices of the octets of an IPv6 address an IPv4 address is embedded in with a
+/// prefix of length `len`, skipping the octet with the bits 64 to 71.
+fn embedded_octets(len: u8) -> impl Iterator<Item = usize> {
+    (len as usize / 8..16).filter(|&i| i != 8).take(4)
+}
@@ -0,0 +1,355 @@
+//! Conversion of DNS responses in JSON format into [`Message`]s.
+//!
+//! Some DNS over HTTPS endpoints only answer queries in JSON (`application/dns-json`) instead of the
+//! wire format. Two formats are understood:
+//!
+//! - the one used by the JSON APIs of Google and Cloudflare, with members like `Status`,
+//!   `Question` and `Answer`, where the record data is given in presentation format as `data`;
+//! - the one from [RFC 8427](https://www.rfc-editor.org/rfc/rfc8427), with members like `RCODE`,
+//!   `questionRRs` and `answerRRs`, where the record data is given as `RDATAHEX` or in
+//!   presentation format as `rdata<TYPE>` (e.g. `rdataA`).
+//!
+//! Record data in presentation format can only be converted for the types `A`, `AAAA`, `NS`,
+//! `CNAME`, `PTR`, `DNAME`, `MX`, `TXT`, `SOA` and `SRV`, or if it is given in the generic format
+//! from [RFC 3597](https://www.rfc-editor.org/rfc/rfc3597) (`\# <length> <hex>`).
+
+use data_encoding::HEXUPPER_PERMISSIVE;
+use serde_json::Value;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use toluol_proto::rdata::{CNAME, DNAME, MX, NS, PTR, SOA, SRV, TXT};
+use toluol_proto::{HeaderFlags, Message, Name, Opcode, Rdata, RecordType};
+
+use crate::error::{bail, Context, Error, Result};
+
+/// Converts the JSON response `json` into a [`Message`] with the message ID `msg_id`, as the JSON
+/// formats don't necessarily include the ID of the query.
+///
+/// Returns an error if `json` is not in one of the formats described in the [module
+/// documentation](self), or if it contains record data that can't be converted.
+///
+/// # Examples
+/// ```rust
+/// use toluol::dns_json::parse_response;
+/// use toluol_proto::RecordType;
+///
+/// let json = r#"{
+///     "Status": 0, "TC": false, "RD": true, "RA": true, "AD": false, "CD": false,
+///     "Question": [{"name": "example.com.", "type": 1}],
+///     "Answer": [{"name": "example.com.", "type": 1, "TTL": 3600, "data": "93.184.216.34"}]
+/// }"#;
+/// let msg = parse_response(json, 0x1234).unwrap();
+/// assert_eq!(msg.header.msg_id, 0x1234);
+/// assert_eq!(msg.questions[0].qtype, RecordType::A);
+/// assert_eq!(msg.answers[0].as_nonopt().unwrap().rdata().to_string(), "93.184.216.34");
+/// ```
+pub fn parse_response(json: &str, msg_id: u16) -> Result<Message> {
+    let res: Value = serde_json::from_str(json).parse("Could not parse the JSON response.")?;
+    if !res.is_object() {
+        bail!(parse, "The JSON response is not an object.");
+    }
+    // RFC 8427 uses `RCODE`, the JSON APIs use `Status`
+    let rfc8427 = res.get("RCODE").is_some();
+
+    let flag = |name: &str| match &res[name] {
+        Value::Bool(b) => *b,
+        value => value.as_u64().is_some_and(|v| v != 0),
+    };
+    let flags = HeaderFlags {
+        aa: flag("AA"),
+        tc: flag("TC"),
+        rd: flag("RD"),
+        ra: flag("RA"),
+        ad: flag("AD"),
+        cd: flag("CD"),
+    };
+    let (rcode, opcode) = if rfc8427 {
+        (
+            number(&res["RCODE"], "RCODE")?,
+            res["Opcode"].as_u64().unwrap_or(0),
+        )
+    } else {
+        (number(&res["Status"], "Status")?, 0)
+    };
+    let opcode = Opcode::parse(opcode as u8).parse("Invalid opcode in the JSON response.")?;
+    if rcode > 0xf {
+        // extended RCODEs would need an OPT record, which the JSON formats don't describe
+        bail!(
+            parse,
+            "The JSON response has an extended RCODE ({}).",
+            rcode
+        );
+    }
+
+    let questions = if rfc8427 {
+        match res.get("questionRRs") {
+            Some(questions) => section(questions, "questionRRs")?.to_vec(),
+            None if res.get("QNAME").is_some() => vec![res.clone()],
+            None => Vec::new(),
+        }
+    } else {
+        optional_section(&res, "Question")?.to_vec()
+    };
+    let sections: [&[Value]; 3] = if rfc8427 {
+        [
+            optional_section(&res, "answerRRs")?,
+            optional_section(&res, "authorityRRs")?,
+            optional_section(&res, "additionalRRs")?,
+        ]
+    } else {
+        [
+            optional_section(&res, "Answer")?,
+            optional_section(&res, "Authority")?,
+            optional_section(&res, "Additional")?,
+        ]
+    };
+
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&msg_id.to_be_bytes());
+    buf.extend_from_slice(
+        &((1 << 15) | (u16::from(opcode.encode()) << 11) | flags.as_flags() | rcode).to_be_bytes(),
+    );
+    buf.extend_from_slice(&(questions.len() as u16).to_be_bytes());
+    for section in sections {
+        buf.extend_from_slice(&(section.len() as u16).to_be_bytes());
+    }
+
+    for question in &questions {
+        let (name, qtype, qclass) = if rfc8427 {
+            (&question["QNAME"], &question["QTYPE"], &question["QCLASS"])
+        } else {
+            (&question["name"], &question["type"], &question["class"])
+        };
+        encode_name(name, &mut buf)?;
+        buf.extend_from_slice(&number(qtype, "question type")?.to_be_bytes());
+        buf.extend_from_slice(&(qclass.as_u64().unwrap_or(1) as u16).to_be_bytes());
+    }
+    for record in sections.into_iter().flatten() {
+        if rfc8427 {
+            encode_rfc8427_record(record, &mut buf)?;
+        } else {
+            encode_record(record, &mut buf)?;
+        }
+    }
+
+    Message::parse_bytes(&buf).parse("Could not convert the JSON response into a DNS message.")
+}
+
+/// Returns the array `section`, or an error naming the section if it is not an array.
+fn section<'a>(section: &'a Value, name: &str) -> Result<&'a [Value]> {
+    match section.as_array() {
+        Some(records) => Ok(records),
+        None => bail!(parse, "{} in the JSON response is not an array.", name),
+    }
+}
+
+/// Returns the array `res[name]`, which may be missing.
+fn optional_section<'a>(res: &'a Value, name: &str) -> Result<&'a [Value]> {
+    match res.get(name) {
+        Some(records) => section(records, name),
+        None => Ok(&[]),
+    }
+}
+
+/// Returns `value` as a 16-bit number, or an error describing `what` it should have been.
+fn number(value: &Value, what: &str) -> Result<u16> {
+    match value.as_u64() {
+        Some(n) if n <= u16::MAX as u64 => Ok(n as u16),
+        _ => bail!(parse, "Invalid {} in the JSON response: {}.", what, value),
+    }
+}
+
+/// Encodes the domain name `name` given as a JSON string into `buf`.
+fn encode_name(name: &Value, buf: &mut Vec<u8>) -> Result<()> {
+    let name = match name.as_str() {
+        Some(name) => name,
+        None => bail!(parse, "Invalid name in the JSON response: {}.", name),
+    };
+    Name::from_ascii(name)
+        .parse(format!("Invalid name in the JSON response: {}.", name))?
+        .encode_into(buf)
+        .parse(format!("Could not encode the name {}.", name))?;
+    Ok(())
+}
+
+/// Encodes a record in the format of the JSON APIs into `buf`.
+fn encode_record(record: &Value, buf: &mut Vec<u8>) -> Result<()> {
+    let rtype = number(&record["type"], "record type")?;
+    let rdata = match record["data"].as_str() {
+        Some(data) => parse_rdata(rtype, data)?,
+        None => bail!(
+            parse,
+            "Record without data in the JSON response: {}.",
+            record
+        ),
+    };
+    encode_name(&record["name"], buf)?;
+    buf.extend_from_slice(&rtype.to_be_bytes());
+    // the JSON APIs only support class IN
+    buf.extend_from_slice(&1u16.to_be_bytes());
+    buf.extend_from_slice(&(record["TTL"].as_u64().unwrap_or(0) as u32).to_be_bytes());
+    encode_rdata(&rdata, buf)
+}
+
+/// Encodes a record in the format of RFC 8427 into `buf`.
+fn encode_rfc8427_record(record: &Value, buf: &mut Vec<u8>) -> Result<()> {
+    let rtype = number(&record["TYPE"], "record type")?;
+    let rdata = if let Some(hex) = record["RDATAHEX"].as_str() {
+        HEXUPPER_PERMISSIVE
+            .decode(hex.as_bytes())
+            .parse(format!("Invalid RDATAHEX in the JSON response: {}.", hex))?
+    } else {
+        let key = format!("rdata{}", RecordType::from(rtype));
+        match record[&key].as_str() {
+            Some(data) => parse_rdata(rtype, data)?,
+            None => bail!(
+                parse,
+                "Record without data in the JSON response: {}.",
+                record
+            ),
+        }
+    };
+    encode_name(&record["NAME"], buf)?;
+    buf.extend_from_slice(&rtype.to_be_bytes());
+    buf.extend_from_slice(&(record["CLASS"].as_u64().unwrap_or(1) as u16).to_be_bytes());
+    buf.extend_from_slice(&(record["TTL"].as_u64().unwrap_or(0) as u32).to_be_bytes());
+    encode_rdata(&rdata, buf)
+}
+
+/// Encodes the RDLENGTH and the RDATA `rdata` into `buf`.
+fn encode_rdata(rdata: &[u8], buf: &mut Vec<u8>) -> Result<()> {
+    if rdata.len() > u16::MAX as usize {
+        bail!(parse, "Record data in the JSON response is too long.");
+    }
+    buf.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+    buf.extend_from_slice(rdata);
+    Ok(())
+}
+
+/// Converts the record data `data` of type `rtype` from presentation format into wire format.
+fn parse_rdata(rtype: u16, data: &str) -> Result<Vec<u8>> {
+    let rtype = RecordType::from(rtype);
+    let invalid = || {
+        format!(
+            "Invalid {} record data in the JSON response: {}.",
+            rtype, data
+        )
+    };
+    let fields: Vec<&str> = data.split_whitespace().collect();
+
+    if fields.first() == Some(&"\\#") {
+        let len: usize = fields
+            .get(1)
+            .and_then(|l| l.parse().ok())
+            .parse(invalid())?;
+        let rdata = HEXUPPER_PERMISSIVE
+            .decode(fields[2..].concat().as_bytes())
+            .parse(invalid())?;
+        if rdata.len() != len {
+            return Err(Error::parse(invalid()));
+        }
+        return Ok(rdata);
+    }
+
+    let name = |i: usize| -> Result<Name> {
+        let field = fields.get(i).parse(invalid())?;
+        Name::from_ascii(field).parse(invalid())
+    };
+    let int = |i: usize| -> Result<u32> {
+        let field = fields.get(i).parse(invalid())?;
+        field.parse().parse(invalid())
+    };
+    let short = |i: usize| -> Result<u16> {
+        let field = fields.get(i).parse(invalid())?;
+        field.parse().parse(invalid())
+    };
+
+    let rdata = match rtype {
+        RecordType::A => Rdata::from(data.trim().parse::<Ipv4Addr>().parse(invalid())?),
+        RecordType::AAAA => Rdata::from(data.trim().parse::<Ipv6Addr>().parse(invalid())?),
+        RecordType::NS => NS { name: name(0)? }.into(),
+        RecordType::CNAME => CNAME { cname: name(0)? }.into(),
+        RecordType::PTR => PTR { location: name(0)? }.into(),
+        RecordType::DNAME => DNAME { target: name(0)? }.into(),
+        RecordType::MX => MX {
+            preference: short(0)? as i16,
+            exchange: name(1)?,
+        }
+        .into(),
+        RecordType::TXT => TXT {
+            text: parse_character_strings(data).parse(invalid())?,
+        }
+        .into(),
+        RecordType::SOA => SOA {
+            mname: name(0)?,
+            rname: name(1)?,
+            serial: int(2)?,
+            refresh: int(3)?,
+            retry: int(4)?,
+            expire: int(5)?,
+            minimum: int(6)?,
+        }
+        .into(),
+        RecordType::SRV => SRV {
+            priority: short(0)?,
+            weight: short(1)?,
+            port: short(2)?,
+            target: name(3)?,
+        }
+        .into(),
+        _ => bail!(
+            parse,
+            "Converting {} record data from the JSON response is not supported: {}.",
+            rtype,
+            data
+        ),
+    };
+    rdata.encode().parse(invalid())
+}
+
+/// Splits `data` into character strings, which are either quoted (and may then contain escaped
+/// characters) or separated by whitespace.
+///
+/// Returns [`None`] if a quoted string is not terminated or an escape sequence is invalid.
+fn parse_character_strings(data: &str) -> Option<Vec<String>> {
+    let mut strings = Vec::new();
+    let mut chars = data.trim().chars();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        let quoted = c == '"';
+        let mut string = Vec::new();
+        let mut c = if quoted { chars.next()? } else { c };
+        loop {
+            match c {
+                '"' if quoted => break,
+                c if c.is_whitespace() && !quoted => break,
+                '\\' => {
+                    let escaped = chars.next()?;
+                    if let Some(d) = escaped.to_digit(10) {
+                        // `\DDD` is a decimal byte value
+                        let mut byte = d;
+                        for _ in 0..2 {
+                            byte = byte * 10 + chars.next()?.to_digit(10)?;
+                        }
+                        string.push(u8::try_from(byte).ok()?);
+                    } else {
+                        let mut buf = [0; 4];
+                        string.extend_from_slice(escaped.encode_utf8(&mut buf).as_bytes());
+                    }
+                }
+                c => {
+                    let mut buf = [0; 4];
+                    string.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+            }
+            c = match chars.next() {
+                Some(c) => c,
+                None if quoted => return None,
+                None => break,
+            };
+        }
+        strings.push(String::from_utf8_lossy(&string).into_owned());
+    }
+    Some(strings)
+}
@@ -0,0 +1,221 @@
+//! The error type of the library, see [`Error`].
+
+use std::fmt::Display;
+
+use crate::net::{NetworkError, OversizedAnswer};
+
+/// The underlying error that caused an [`Error`].
+pub type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// A [`Result`](std::result::Result) with [`Error`] as the default error type.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// The errors that may arise when using toluol.
+///
+/// Most variants consist of a message that says what failed and the error that caused it, if
+/// any, which is available via [`std::error::Error::source()`]. The causes may be [`Error`]s
+/// themselves, e.g. if retrying a query via TCP failed after an oversized UDP answer.
+///
+/// The alternate format (`{:#}`) includes the messages of all sources, separated by colons.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Sending a query or receiving its answer failed, e.g. because no TLS connection could be
+    /// established or the HTTP request was unsuccessful.
+    Transport {
+        message: String,
+        source: Option<BoxError>,
+    },
+
+    /// Sending a query or receiving its answer via UDP or TCP failed, see [`NetworkError`].
+    Network(NetworkError),
+
+    /// The nameserver sent a larger UDP answer than advertised, see [`OversizedAnswer`].
+    OversizedAnswer(OversizedAnswer),
+
+    /// A message or other data could not be parsed or encoded, e.g. because the nameserver sent a
+    /// malformed answer.
+    Parse {
+        message: String,
+        source: Option<BoxError>,
+    },
+
+    /// DNSSEC validation failed, or the records needed for it could not be obtained.
+    Validation {
+        message: String,
+        source: Option<BoxError>,
+    },
+
+    /// The configuration or input is invalid, e.g. an unknown network interface, an unreadable
+    /// file, or an unsupported combination of options.
+    Configuration {
+        message: String,
+        source: Option<BoxError>,
+    },
+}
+
+impl Error {
+    /// Creates an [`Error::Transport`] without a source.
+    pub fn transport(message: impl Display) -> Self {
+        Self::Transport {
+            message: message.to_string(),
+            source: None,
+        }
+    }
+
+    /// Creates an [`Error::Parse`] without a source.
+    pub fn parse(message: impl Display) -> Self {
+        Self::Parse {
+            message: message.to_string(),
+            source: None,
+        }
+    }
+
+    /// Creates an [`Error::Validation`] without a source.
+    pub fn validation(message: impl Display) -> Self {
+        Self::Validation {
+            message: message.to_string(),
+            source: None,
+        }
+    }
+
+    /// Creates an [`Error::Configuration`] without a source.
+    pub fn configuration(message: impl Display) -> Self {
+        Self::Configuration {
+            message: message.to_string(),
+            source: None,
+        }
+    }
+
+    /// Returns an iterator over this error and its sources, starting with this error.
+    pub fn chain(&self) -> impl Iterator<Item = &(dyn std::error::Error + 'static)> {
+        std::iter::successors(Some(self as &(dyn std::error::Error + 'static)), |e| {
+            e.source()
+        })
+    }
+
+    /// Returns the innermost error that caused this one, which is the error itself if it has no
+    /// source.
+    pub fn root_cause(&self) -> &(dyn std::error::Error + 'static) {
+        self.chain()
+            .last()
+            .expect("the chain contains at least this error")
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Network(e) => Display::fmt(e, f)?,
+            Self::OversizedAnswer(e) => Display::fmt(e, f)?,
+            Self::Transport { message, .. }
+            | Self::Parse { message, .. }
+            | Self::Validation { message, .. }
+            | Self::Configuration { message, .. } => f.write_str(message)?,
+        }
+        if f.alternate() {
+            for source in self.chain().skip(1) {
+                write!(f, ": {}", source)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Network(e) => e.source(),
+            Self::OversizedAnswer(e) => e.source(),
+            Self::Transport { source, .. }
+            | Self::Parse { source, .. }
+            | Self::Validation { source, .. }
+            | Self::Configuration { source, .. } => source.as_deref().map(|e| e as _),
+        }
+    }
+}
+
+impl From<NetworkError> for Error {
+    fn from(e: NetworkError) -> Self {
+        Self::Network(e)
+    }
+}
+
+impl From<OversizedAnswer> for Error {
+    fn from(e: OversizedAnswer) -> Self {
+        Self::OversizedAnswer(e)
+    }
+}
+
+/// Returns early with an [`Error`] of the given kind (`transport`, `parse`, `validation`, or
+/// `configuration`), formatting the message like [`format!`].
+macro_rules! bail {
+    ($kind:ident, $($arg:tt)+) => {
+        return Err($crate::error::Error::$kind(format!($($arg)+)))
+    };
+}
+pub(crate) use bail;
+
+/// Converts other errors (and [`None`]) into an [`Error`] of a particular kind with a message that
+/// says what failed, keeping the original error as the source.
+pub trait Context<T> {
+    /// Converts the error into an [`Error::Transport`].
+    fn transport(self, message: impl Display) -> Result<T>;
+
+    /// Converts the error into an [`Error::Parse`].
+    fn parse(self, message: impl Display) -> Result<T>;
+
+    /// Converts the error into an [`Error::Validation`].
+    fn validation(self, message: impl Display) -> Result<T>;
+
+    /// Converts the error into an [`Error::Configuration`].
+    fn configuration(self, message: impl Display) -> Result<T>;
+}
+
+impl<T, E: Into<BoxError>> Context<T> for std::result::Result<T, E> {
+    fn transport(self, message: impl Display) -> Result<T> {
+        self.map_err(|e| Error::Transport {
+            message: message.to_string(),
+            source: Some(e.into()),
+        })
+    }
+
+    fn parse(self, message: impl Display) -> Result<T> {
+        self.map_err(|e| Error::Parse {
+            message: message.to_string(),
+            source: Some(e.into()),
+        })
+    }
+
+    fn validation(self, message: impl Display) -> Result<T> {
+        self.map_err(|e| Error::Validation {
+            message: message.to_string(),
+            source: Some(e.into()),
+        })
+    }
+
+    fn configuration(self, message: impl Display) -> Result<T> {
+        self.map_err(|e| Error::Configuration {
+            message: message.to_string(),
+            source: Some(e.into()),
+        })
+    }
+}
+
+impl<T> Context<T> for Option<T> {
+    fn transport(self, message: impl Display) -> Result<T> {
+        self.ok_or_else(|| Error::transport(message))
+    }
+
+    fn parse(self, message: impl Display) -> Result<T> {
+        self.ok_or_else(|| Error::parse(message))
+    }
+
+    fn validation(self, message: impl Display) -> Result<T> {
+        self.ok_or_else(|| Error::validation(message))
+    }
+
+    fn configuration(self, message: impl Display) -> Result<T> {
+        self.ok_or_else(|| Error::configuration(message))
+    }
+}
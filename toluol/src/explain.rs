@@ -0,0 +1,78 @@
+//! Plain-language explanations of record types and their notable fields, for people who use
+//! toluol to learn DNS.
+//!
+//! The explanations come from an [`Explainer`]; [`English`] uses the
+//! [registry](toluol_proto::registry) of toluol-proto. Other languages (or more detailed
+//! explanations) can be plugged in by implementing [`Explainer`].
+
+use toluol_proto::{Message, RecordType};
+
+/// The explanation of a record type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Explanation {
+    /// A one-line explanation of what records of the type are for.
+    pub summary: String,
+    /// The notable fields of the RDATA and a one-line explanation of each.
+    pub fields: Vec<(String, String)>,
+}
+
+/// Explains record types, e.g. in a certain language.
+pub trait Explainer {
+    /// Returns the explanation of `rtype`, or [`None`] if there is none.
+    fn explain(&self, rtype: RecordType) -> Option<Explanation>;
+}
+
+/// Explains record types in English, using the descriptions of
+/// [`RecordType::info()`](toluol_proto::RecordType::info()).
+#[derive(Copy, Clone, Debug, Default)]
+pub struct English;
+
+impl Explainer for English {
+    fn explain(&self, rtype: RecordType) -> Option<Explanation> {
+        let info = rtype.info()?;
+        Some(Explanation {
+            summary: info.summary.to_string(),
+            fields: info
+                .fields
+                .iter()
+                .map(|field| (field.name.to_string(), field.summary.to_string()))
+                .collect(),
+        })
+    }
+}
+
+/// Returns the explanations of the types of all records in `msg`, in the order the types first
+/// occur. Types without an explanation are left out.
+///
+/// # Examples
+/// ```rust
+/// use toluol::explain::{explain_message, English};
+/// use toluol_proto::{HeaderFlags, Message, Name, Opcode, RecordType};
+///
+/// let flags = HeaderFlags { aa: false, tc: false, rd: true, ra: false, ad: false, cd: false };
+/// let name = Name::from_ascii("example.com").unwrap();
+/// let msg = Message::new_query(name, RecordType::SOA, Opcode::QUERY, flags, None).unwrap();
+/// // the question is not explained, only the records
+/// assert!(explain_message(&msg, &English).is_empty());
+/// ```
+pub fn explain_message(msg: &Message, explainer: &dyn Explainer) -> Vec<(RecordType, Explanation)> {
+    let mut types = Vec::new();
+    let records = msg
+        .answers
+        .iter()
+        .chain(&msg.authoritative_answers)
+        .chain(&msg.additional_answers);
+    for record in records {
+        let rtype = match record.as_nonopt() {
+            Some(record) => record.rtype,
+            None => RecordType::OPT,
+        };
+        if !types.contains(&rtype) {
+            types.push(rtype);
+        }
+    }
+    types
+        .into_iter()
+        .filter_map(|rtype| Some((rtype, explainer.explain(rtype)?)))
+        .collect()
+}
@@ -0,0 +1,93 @@
+//! GeoIP annotation of IP addresses using MaxMind databases (`geoip` feature).
+
+use maxminddb::{geoip2, Reader};
+use std::fmt::Display;
+use std::net::IpAddr;
+use std::path::Path;
+
+use crate::error::{Context, Result};
+
+/// A set of MaxMind databases, e.g. GeoLite2-Country and GeoLite2-ASN.
+#[derive(Debug)]
+pub struct GeoIp {
+    readers: Vec<Reader<Vec<u8>>>,
+}
+
+/// The information found for an IP address. Each field is [`None`] if none of the databases
+/// contained it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GeoInfo {
+    /// ISO 3166-1 alpha-2 country code.
+    pub country: Option<String>,
+    /// Number of the autonomous system the address belongs to.
+    pub asn: Option<u32>,
+    /// Name of the organization operating the autonomous system.
+    pub as_org: Option<String>,
+}
+
+impl GeoIp {
+    /// Opens the MaxMind databases at the given paths.
+    pub fn open(paths: &[impl AsRef<Path>]) -> Result<Self> {
+        let readers = paths
+            .iter()
+            .map(|path| {
+                Reader::open_readfile(path).configuration(format!(
+                    "Could not open GeoIP database {}.",
+                    path.as_ref().display()
+                ))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { readers })
+    }
+
+    /// Looks up `ip` in all databases and returns the combined information.
+    ///
+    /// Each database is searched for country and AS information, so it doesn't matter which kind
+    /// of database was given.
+    pub fn lookup(&self, ip: IpAddr) -> GeoInfo {
+        let mut info = GeoInfo::default();
+        for reader in &self.readers {
+            if info.country.is_none() {
+                info.country = reader
+                    .lookup::<geoip2::Country>(ip)
+                    .ok()
+                    .and_then(|res| res.country)
+                    .and_then(|country| country.iso_code)
+                    .map(str::to_string);
+            }
+            if info.asn.is_none() {
+                if let Ok(asn) = reader.lookup::<geoip2::Asn>(ip) {
+                    info.asn = asn.autonomous_system_number;
+                    info.as_org = asn.autonomous_system_organization.map(str::to_string);
+                }
+            }
+        }
+        info
+    }
+}
+
+impl GeoInfo {
+    /// Returns true if nothing was found.
+    pub fn is_empty(&self) -> bool {
+        self.country.is_none() && self.asn.is_none()
+    }
+}
+
+impl Display for GeoInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(country) = &self.country {
+            parts.push(country.clone());
+        }
+        match (self.asn, &self.as_org) {
+            (Some(asn), Some(org)) => parts.push(format!("AS{} {}", asn, org)),
+            (Some(asn), None) => parts.push(format!("AS{}", asn)),
+            _ => {}
+        }
+        if parts.is_empty() {
+            write!(f, "unknown")
+        } else {
+            write!(f, "{}", parts.join(", "))
+        }
+    }
+}
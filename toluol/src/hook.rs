@@ -0,0 +1,88 @@
+//! Running a command after each query, so that answers can be processed further (e.g. for
+//! alerting or storage) without changing toluol, see [`Hook`].
+
+use std::io::{ErrorKind, Write};
+use std::process::{Command, Stdio};
+use toluol_proto::Message;
+
+use crate::error::{bail, Context, Result};
+
+/// A shell command that is run with the result of a query.
+///
+/// The command gets the result as JSON on its standard input, and the name and type that were
+/// queried for in the environment variables `TOLUOL_NAME` and `TOLUOL_TYPE`. Its standard output
+/// and standard error are those of toluol.
+///
+/// # Examples
+/// ```rust
+/// use toluol::hook::Hook;
+/// use toluol_proto::{HeaderFlags, Message, Name, Opcode, RecordType};
+///
+/// let flags = HeaderFlags { aa: false, tc: false, rd: true, ra: false, ad: false, cd: false };
+/// let query = Message::new_query(Name::root(), RecordType::NS, Opcode::QUERY, flags, None).unwrap();
+/// let result = serde_json::json!({ "answers": [] });
+/// assert!(Hook::new("exit 0").run(&query, &result).is_ok());
+/// assert!(Hook::new("exit 3").run(&query, &result).is_err());
+/// ```
+#[derive(Clone, Debug)]
+pub struct Hook {
+    command: String,
+}
+
+impl Hook {
+    /// Creates a hook that runs `command` with the system's shell (`sh` or `cmd`).
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+        }
+    }
+
+    /// Returns the command the hook runs.
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    /// Runs the command for the answer `res`, whose JSON representation is `result`, and waits
+    /// until it exits. Commands that don't read their standard input are fine.
+    ///
+    /// Returns an error if the command cannot be started or does not exit successfully.
+    pub fn run(&self, res: &Message, result: &serde_json::Value) -> Result<()> {
+        let (shell, flag) = if cfg!(windows) {
+            ("cmd", "/C")
+        } else {
+            ("sh", "-c")
+        };
+        let mut command = Command::new(shell);
+        command.arg(flag).arg(&self.command).stdin(Stdio::piped());
+        if let Some(question) = res.questions.first() {
+            command
+                .env("TOLUOL_NAME", question.qname.to_string())
+                .env("TOLUOL_TYPE", question.qtype.to_string());
+        }
+        let mut child = command
+            .spawn()
+            .configuration(format!("Could not run hook {}.", self.command))?;
+
+        let mut stdin = child.stdin.take().expect("stdin of hook was not piped");
+        let written = serde_json::to_writer(&mut stdin, result)
+            .map_err(std::io::Error::from)
+            .and_then(|_| stdin.write_all(b"\n"));
+        drop(stdin);
+        match written {
+            Err(e) if e.kind() != ErrorKind::BrokenPipe => {
+                // don't leave the command behind
+                let _ = child.wait();
+                return Err(e).configuration(format!("Could not write to hook {}.", self.command));
+            }
+            _ => {}
+        }
+
+        let status = child
+            .wait()
+            .configuration(format!("Could not wait for hook {}.", self.command))?;
+        if !status.success() {
+            bail!(configuration, "Hook {} failed: {}.", self.command, status);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,155 @@
+//! Looking up names and addresses in hosts files (see `hosts(5)`), like the system's resolver does
+//! before sending queries to a nameserver.
+
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use toluol_proto::rdata::{A, AAAA, PTR};
+use toluol_proto::{Class, Name, NonOptRecord, Rdata, RecordType};
+
+use crate::error::{Context, Result};
+
+/// The path of the system's hosts file.
+pub const HOSTS_FILE: &str = "/etc/hosts";
+
+/// The TTL of records synthesized from hosts files. As hosts files may change at any time, the
+/// records must not be cached.
+pub const HOSTS_TTL: u32 = 0;
+
+/// The entries of one or more hosts files.
+#[derive(Clone, Debug, Default)]
+pub struct Hosts {
+    entries: Vec<Entry>,
+}
+
+#[derive(Clone, Debug)]
+struct Entry {
+    address: IpAddr,
+    /// The canonical name, followed by the aliases.
+    names: Vec<Name>,
+    /// The file the entry was read from.
+    path: PathBuf,
+}
+
+impl Hosts {
+    /// Reads the system's hosts file, [`HOSTS_FILE`].
+    pub fn system() -> Result<Self> {
+        Self::from_file(HOSTS_FILE)
+    }
+
+    /// Reads the hosts file at `path`.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let mut hosts = Self::default();
+        hosts.add_file(path)?;
+        Ok(hosts)
+    }
+
+    /// Reads the hosts file at `path` and adds its entries. Entries that were added before take
+    /// precedence.
+    pub fn add_file(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let hosts = fs::read_to_string(path)
+            .configuration(format!("Could not read {}.", path.display()))?;
+        self.add(&hosts, path);
+        Ok(())
+    }
+
+    /// Parses `hosts`, which must have the format of `hosts(5)`, and adds its entries as if they
+    /// were read from `path`. Entries that were added before take precedence.
+    ///
+    /// Like the system's resolver, invalid lines and names are ignored.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use toluol::hosts::Hosts;
+    /// use toluol_proto::{Name, RecordType};
+    ///
+    /// let mut hosts = Hosts::default();
+    /// hosts.add("192.0.2.1 www.example.com www # the web server", "hosts");
+    ///
+    /// let name = Name::from_ascii("www.example.com").unwrap();
+    /// assert_eq!(hosts.lookup(&name, RecordType::A).len(), 1);
+    /// assert!(hosts.lookup(&name, RecordType::AAAA).is_empty());
+    ///
+    /// let reverse = Name::from_ascii("1.2.0.192.in-addr.arpa").unwrap();
+    /// assert_eq!(hosts.lookup(&reverse, RecordType::PTR)[0].rdata().to_string(), "www.example.com");
+    /// ```
+    pub fn add(&mut self, hosts: &str, path: impl AsRef<Path>) {
+        for line in hosts.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut words = line.split_whitespace();
+            // IPv6 addresses may have a scope ID (e.g. `fe80::1%eth0`), which we can't represent
+            let address = match words
+                .next()
+                .map(|a| a.split('%').next().unwrap_or(a).parse())
+            {
+                Some(Ok(address)) => address,
+                _ => continue,
+            };
+            let names: Vec<_> = words.filter_map(|n| Name::from_ascii(n).ok()).collect();
+            if !names.is_empty() {
+                self.entries.push(Entry {
+                    address,
+                    names,
+                    path: path.as_ref().to_path_buf(),
+                });
+            }
+        }
+    }
+
+    /// Returns true if there are no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the records of type `qtype` for `name` that the hosts files define.
+    ///
+    /// For A and AAAA records, these are the addresses of all entries containing `name`. For PTR
+    /// records, `name` must be a reverse name (in `in-addr.arpa` or `ip6.arpa`), and the record
+    /// points to the canonical name of the first entry with that address. For other types, there
+    /// are never any records.
+    pub fn lookup(&self, name: &Name, qtype: RecordType) -> Vec<NonOptRecord> {
+        self.lookup_with_path(name, qtype)
+            .map(|(records, _)| records)
+            .unwrap_or_default()
+    }
+
+    /// Like [`Self::lookup()`], but also returns the path of the file the (first) record came
+    /// from. Returns [`None`] if there are no records.
+    pub fn lookup_with_path(
+        &self,
+        name: &Name,
+        qtype: RecordType,
+    ) -> Option<(Vec<NonOptRecord>, &Path)> {
+        let record = |rdata| NonOptRecord::new(name.clone(), Class::IN, HOSTS_TTL, rdata).ok();
+        let (records, path): (Vec<_>, Vec<_>) = match qtype {
+            RecordType::A | RecordType::AAAA => self
+                .entries
+                .iter()
+                .filter(|entry| entry.names.contains(name))
+                .filter_map(|entry| {
+                    let rdata = match (entry.address, qtype) {
+                        (IpAddr::V4(address), RecordType::A) => Rdata::A(A::from(address)),
+                        (IpAddr::V6(address), RecordType::AAAA) => Rdata::AAAA(AAAA::from(address)),
+                        _ => return None,
+                    };
+                    Some((record(rdata)?, entry.path.as_path()))
+                })
+                .unzip(),
+            RecordType::PTR => self
+                .entries
+                .iter()
+                .find(|entry| &Name::from_ip(entry.address) == name)
+                .and_then(|entry| {
+                    let rdata = Rdata::PTR(PTR {
+                        location: entry.names[0].clone(),
+                    });
+                    Some((record(rdata)?, entry.path.as_path()))
+                })
+                .into_iter()
+                .unzip(),
+            _ => return None,
+        };
+        Some((records, path.first().copied()?))
+    }
+}
@@ -1,15 +1,24 @@
 //! Code for iterative DNS queries (+trace mode).
 
-use crate::net::Nameserver;
-use crate::util::{get_dnskeys, prepare_query, send_query};
+use crate::cache::DnskeyCache;
+use crate::error::{bail, Context, Result};
+use crate::net::{Nameserver, QueryTiming};
+use crate::stats::ServerStats;
+use crate::util::{get_dnskeys_cached, prepare_and_send_query, use_tcp_for_large_query};
 use crate::QueryMetadata;
-use anyhow::{Context, Result};
 use lazy_static::lazy_static;
-use rand::seq::IteratorRandom;
+use rand::seq::SliceRandom;
 use std::io::Cursor;
+use std::iter::zip;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::mpsc;
+use std::thread;
 use std::time::Duration;
-use toluol_proto::{EdnsConfig, Message, Name, NonOptRecord, OptRecord, Record, RecordType};
+use toluol_proto::dnssec::{validate_rrsets_with, ValidationPolicy, ValidationState};
+use toluol_proto::{
+    Class, EdnsConfig, Message, Name, NonOptRecord, Opcode, OptRecord, RCode, Rdata, Record,
+    RecordType,
+};
 
 /// Contains the following information for an answer received from a nameserver:
 /// - the zone that the nameserver is authoritative for
@@ -17,11 +26,89 @@ use toluol_proto::{EdnsConfig, Message, Name, NonOptRecord, OptRecord, Record, R
 /// - received answer
 /// - number of received bytes
 /// - duration of the query
-pub type Answer = (Name, Nameserver, Message, u16, Duration);
+pub type Answer = (Name, Nameserver, Message, u16, QueryTiming);
 
 /// Contains a list of all DNSKEY records, including their RRSIG records, for a list of zones.
 pub type DnsKeys = Vec<Vec<NonOptRecord>>;
 
+/// The delegation path of an iterative query, with one step for each query that was sent, so
+/// that it can be analyzed programmatically. See [`query()`].
+///
+/// Queries for the addresses of nameservers without glue are not part of the trace.
+#[derive(Clone, Debug)]
+pub struct Trace {
+    /// A random ID that identifies the iterative query, e.g. to correlate it with logs.
+    pub id: u64,
+    /// The queries that were sent, in order.
+    pub steps: Vec<TraceStep>,
+}
+
+/// A single query of an iterative query, see [`Trace`].
+#[derive(Clone, Debug)]
+pub struct TraceStep {
+    /// The zone that the queried nameserver is authoritative for.
+    pub zone: Name,
+    /// The queried nameserver whose answer was used.
+    pub server: Nameserver,
+    /// The message ID of the answer.
+    pub msg_id: u16,
+    /// The time between sending the query and receiving the answer.
+    pub rtt: Duration,
+    /// The response code of the answer.
+    pub rcode: RCode,
+    /// Where the answer referred to, or [`None`] if it was no referral.
+    pub referral: Option<Referral>,
+    /// The other nameservers of the zone that were tried, but whose answers were not used.
+    pub skipped: Vec<SkippedServer>,
+}
+
+/// A nameserver whose answer was not used, see [`TraceStep::skipped`].
+#[derive(Clone, Debug)]
+pub struct SkippedServer {
+    pub server: Nameserver,
+    /// Why the answer was not used, e.g. a timeout or SERVFAIL, or [`None`] if another
+    /// nameserver's answer arrived first.
+    pub reason: Option<String>,
+}
+
+/// The delegation found in an answer, see [`TraceStep`].
+#[derive(Clone, Debug)]
+pub struct Referral {
+    /// The zone that is delegated.
+    pub zone: Name,
+    /// The nameserver of `zone` that was queried next.
+    pub nameserver: Name,
+    /// The address of `nameserver`, taken from glue records or looked up separately, or [`None`]
+    /// if it could not be looked up.
+    pub address: Option<IpAddr>,
+    /// Whether the delegation is secured with DNSSEC, or [`None`] if the query was not validated
+    /// (see [`QueryMetadata::validate_dnssec`]).
+    pub security: Option<DelegationSecurity>,
+}
+
+/// Whether a delegation links the chain of trust from the parent zone to the child zone, see
+/// [`Referral::security`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DelegationSecurity {
+    /// [`ValidationState::Secure`] if the parent's `DS` records are signed by the parent's
+    /// DNSKEYs and one of them refers to a DNSKEY of the child that signs the child's DNSKEYs,
+    /// [`ValidationState::Insecure`] if the parent has no `DS` records for the child, and
+    /// otherwise the state of the `DS` records or [`ValidationState::Bogus`].
+    ///
+    /// `DS` records whose digest type or algorithm the [`ValidationPolicy`] does not accept are
+    /// ignored. If it accepts none of them, the delegation is insecure, or bogus if the policy
+    /// sets [`ValidationPolicy::reject_unaccepted`].
+    ///
+    /// The absence of `DS` records is not proven with NSEC or NSEC3 records.
+    pub state: ValidationState,
+    /// Why the delegation has this state, e.g. which of the child's DNSKEYs the `DS` records
+    /// refer to.
+    pub reason: String,
+}
+
+/// The maximum number of nameservers of a zone that are queried concurrently, see [`query()`].
+pub const MAX_CONCURRENT_NAMESERVERS: usize = 2;
+
 lazy_static! {
     /// IPv6 addresses of the root servers ({a,b,c,d,e,f,g,h,i,j,k,l,m}.root-servers.net).
     static ref ROOT_IPV6: Vec<Nameserver> = {
@@ -45,6 +132,7 @@ lazy_static! {
             root_servers.push(Nameserver {
                 ip: Some(IpAddr::V6(root_server_ips[i])),
                 hostname: Some(format!("{}.root-servers.net.", prefix)),
+                scope_id: None,
                 port: 53,
             });
         }
@@ -73,6 +161,7 @@ lazy_static! {
             root_servers.push(Nameserver {
                 ip: Some(IpAddr::V4(root_server_ips[i])),
                 hostname: Some(format!("{}.root-servers.net.", prefix)),
+                scope_id: None,
                 port: 53,
             });
         }
@@ -80,74 +169,153 @@ lazy_static! {
     };
 }
 
-/// Performs an iterative query for the information specified in `args`, starting at one of the
-/// root servers. If `args.verify_dnssec` is true, also returns the DNSKEYs of all queried zones
+/// Performs an iterative query for the information specified in `args`, starting at the root
+/// servers. If `args.verify_dnssec` is true, also returns the DNSKEYs of all queried zones
 /// (including the root zone) and their RRSIGs.
-pub fn query(metadata: &QueryMetadata) -> Result<(Vec<Answer>, Option<DnsKeys>)> {
-    // idea: first try an IPv6 nameserver, if that fails, try again with IPv4.
+///
+/// For each zone, up to [`MAX_CONCURRENT_NAMESERVERS`] of its nameservers are queried
+/// concurrently, preferring those that answered quickly before, and the first usable answer is
+/// taken. If none of them gives one, the next nameservers are tried, so that a single lame or
+/// unreachable nameserver does not fail the query.
+///
+/// Also returns the [`Trace`] of the query.
+pub fn query(metadata: &QueryMetadata) -> Result<(Vec<Answer>, Option<DnsKeys>, Trace)> {
+    query_with_stats(metadata, &ServerStats::new())
+}
 
-    let mut rng = rand::thread_rng();
-    let nameserver = ROOT_IPV6
-        .iter()
-        .choose(&mut rng)
-        .expect("No hardcoded IPv6 root servers");
-    let res = resolve(metadata, nameserver.clone()).map(|res| (res.1, res.2));
+/// The same as [`query()`], but prefers nameservers with a low smoothed RTT in `stats`, and
+/// records all answers and timeouts in `stats`.
+pub fn query_with_stats(
+    metadata: &QueryMetadata,
+    stats: &ServerStats,
+) -> Result<(Vec<Answer>, Option<DnsKeys>, Trace)> {
+    query_with_caches(
+        metadata,
+        stats,
+        &DnskeyCache::new(),
+        &ValidationPolicy::default(),
+    )
+}
+
+/// The same as [`query_with_stats()`], but takes the DNSKEYs of the queried zones from `dnskeys`
+/// if it has them, and otherwise keeps them there, so that they are only fetched once for several
+/// queries.
+///
+/// The security of the delegations in the [`Trace`] is checked according to `policy`, see
+/// [`DelegationSecurity`].
+pub fn query_with_caches(
+    metadata: &QueryMetadata,
+    stats: &ServerStats,
+    dnskeys: &DnskeyCache,
+    policy: &ValidationPolicy,
+) -> Result<(Vec<Answer>, Option<DnsKeys>, Trace)> {
+    // idea: first try the IPv6 root servers, if that fails, try again with IPv4.
+
+    let id = rand::random();
+    let to_result = |(_, replies, dnskeys, steps)| (replies, dnskeys, Trace { id, steps });
+    let res = resolve(metadata, &ROOT_IPV6, stats, dnskeys, policy).map(to_result);
     if res.is_ok() {
         return res;
     }
 
-    let nameserver = ROOT_IPV4
-        .iter()
-        .choose(&mut rng)
-        .expect("No hardcoded IPv4 root servers");
-    resolve(metadata, nameserver.clone())
-        .map(|res| (res.1, res.2))
-        .context("Could not perform iterative query.")
+    resolve(metadata, &ROOT_IPV4, stats, dnskeys, policy)
+        .map(to_result)
+        .transport("Could not perform iterative query.")
 }
 
-/// Iteratively queries for the information specified in `args`, starting with `args.nameserver`
-/// as the first nameserver. Returns a tuple of the query result (may be the empty string if the
-/// requested record doesn't exist) and the same information that [`query()`] returns.
+/// The result of [`resolve()`]: the query result (an OPT record if the requested record doesn't
+/// exist), the answers and DNSKEYs that [`query()`] returns, and the steps of the trace.
+type Resolution = (Record, Vec<Answer>, Option<DnsKeys>, Vec<TraceStep>);
+
+/// Iteratively queries for the information specified in `args`, starting with `roots` as the
+/// nameservers of the root zone. All of `roots` must have IP addresses of the same version; only
+/// nameserver addresses of that version are used. The delegations are checked according to
+/// `policy`.
 fn resolve(
     metadata: &QueryMetadata,
-    mut nameserver: Nameserver,
-) -> Result<(Record, Vec<Answer>, Option<DnsKeys>)> {
+    roots: &[Nameserver],
+    stats: &ServerStats,
+    dnskey_cache: &DnskeyCache,
+    policy: &ValidationPolicy,
+) -> Result<Resolution> {
     let bufsize = 4096;
     let mut replies = Vec::new();
     let mut dnskeys = Vec::new();
-    // store root nameserver for later
-    let root_server = nameserver.clone();
-    let use_ipv6 = matches!(root_server.ip, Some(IpAddr::V6(_)));
+    let mut steps: Vec<TraceStep> = Vec::new();
+    let use_ipv6 = matches!(roots.first().and_then(|ns| ns.ip), Some(IpAddr::V6(_)));
     let mut current_queried_zone = Name::root();
+    let mut candidates: Vec<_> = roots
+        .iter()
+        .map(|nameserver| Candidate {
+            name: Name::from_ascii(nameserver.hostname.as_deref().unwrap_or_default())
+                .expect("Invalid hardcoded root server name"),
+            nameserver: nameserver.clone(),
+        })
+        .collect();
+    // the zone the last answer referred to, until the referral's nameserver that answered is known
+    let mut pending_referral: Option<Name> = None;
 
     // loop structure inspired by https://jvns.ca/blog/2022/02/01/a-dns-resolver-in-80-lines-of-go
     loop {
+        let mut skipped = Vec::new();
+        let (name, nameserver, reply, bytes_recvd, timing) = query_zone(
+            metadata,
+            bufsize,
+            &current_queried_zone,
+            &mut candidates,
+            roots,
+            stats,
+            &mut skipped,
+        )?;
+
         if metadata.validate_dnssec {
             dnskeys.push(
-                get_dnskeys(
+                get_dnskeys_cached(
                     current_queried_zone.clone(),
                     nameserver.clone(),
                     metadata.clone(),
+                    dnskey_cache,
                 )
-                .context(format!(
+                .validation(format!(
                     "Could not get DNSKEYs for the {} zone.",
                     current_queried_zone
                 ))?,
             );
         }
 
-        let query = prepare_query(metadata, bufsize)?;
-        let (reply, bytes_recvd, elapsed) =
-            send_query(metadata.connection_type, bufsize, &mut nameserver, &query)?;
-        let reply = Message::parse(&mut Cursor::new(&reply)).context("Could not parse answer.")?;
+        if let Some(zone) = pending_referral.take() {
+            // the referral is the last reply, and the DNSKEYs of both zones were just fetched
+            let security = match (replies.last(), &dnskeys[..]) {
+                (Some((_, _, parent_reply, _, _)), [.., parent_keys, child_keys]) => Some(
+                    check_delegation(&zone, parent_reply, parent_keys, child_keys, policy),
+                ),
+                _ => None,
+            };
+            if let Some(step) = steps.last_mut() {
+                step.referral = Some(Referral {
+                    zone,
+                    nameserver: name,
+                    address: nameserver.ip,
+                    security,
+                });
+            }
+        }
 
-        // push now because nameserver may be changed later
+        steps.push(TraceStep {
+            zone: current_queried_zone.clone(),
+            server: nameserver.clone(),
+            msg_id: reply.header.msg_id,
+            rtt: timing.elapsed,
+            rcode: rcode_of(&reply),
+            referral: None,
+            skipped,
+        });
         replies.push((
             current_queried_zone.clone(),
-            nameserver.clone(),
+            nameserver,
             reply.clone(),
             bytes_recvd,
-            elapsed,
+            timing,
         ));
 
         // TODO what about CNAMEs/DNAMEs?
@@ -159,52 +327,11 @@ fn resolve(
                 None
             };
             // TODO remove clone
-            break Ok((answer.clone(), replies, dnskeys));
-        } else if let Some((zone, hostname, ip)) = find_glue(use_ipv6, &reply) {
-            nameserver.ip = Some(ip);
-            nameserver.hostname = Some(hostname.to_string());
+            break Ok((answer.clone(), replies, dnskeys, steps));
+        } else if let Some((zone, referred)) = find_referral(use_ipv6, &reply) {
             current_queried_zone = zone.clone();
-        } else if let Some((ns_hostname, zone)) = select_ns(&reply) {
-            let mut args2 = metadata.clone();
-
-            // if root_server contains an IPv6 address and we've made it this far, we can assume
-            // that IPv6 works. therefore first query for the nameserver's IPv6 address, and only
-            // if there is no AAAA record, query for the IPv4 address
-            args2.qtype = if use_ipv6 {
-                RecordType::AAAA
-            } else {
-                RecordType::A
-            };
-            args2.name = ns_hostname.clone();
-            nameserver.hostname = Some(ns_hostname.to_string());
-            current_queried_zone = zone.clone();
-
-            let mut res = resolve(&args2, root_server.clone());
-            if res.is_err() && use_ipv6 {
-                args2.qtype = RecordType::A;
-                res = resolve(&args2, root_server.clone());
-            }
-            let ip = res.ok().and_then(|(rec, _, _)| {
-                rec.as_nonopt().map(|nonopt| {
-                    if use_ipv6 {
-                        nonopt
-                            .rdata()
-                            .as_aaaa()
-                            .expect("queried for AAAA, but didn't get AAAA")
-                            .address
-                            .into()
-                    } else {
-                        nonopt
-                            .rdata()
-                            .as_a()
-                            .expect("queried for A, but didn't get A")
-                            .address
-                            .into()
-                    }
-                })
-            });
-
-            nameserver.ip = ip;
+            candidates = referred;
+            pending_referral = Some(zone);
         } else {
             let dnskeys = if metadata.fetch_dnssec {
                 Some(dnskeys)
@@ -220,17 +347,329 @@ fn resolve(
                             bufsize: 4096,
                             do_flag: false,
                             client_cookie: None,
+                            client_subnet: None,
+                            edns_version: 0,
+                            extra_options: Vec::new(),
                         },
                     )
                     .expect("couldn't create OPT record"),
                 ),
                 replies,
                 dnskeys,
+                steps,
             ));
         }
     }
 }
 
+/// Checks whether the `DS` records for `zone` in `parent_reply` (a referral) are signed by
+/// `parent_keys` and refer to one of `child_keys` that signs them, see [`DelegationSecurity`].
+/// Only the DS records that `policy` accepts are followed.
+fn check_delegation(
+    zone: &Name,
+    parent_reply: &Message,
+    parent_keys: &[NonOptRecord],
+    child_keys: &[NonOptRecord],
+    policy: &ValidationPolicy,
+) -> DelegationSecurity {
+    let result = |state, reason: String| DelegationSecurity { state, reason };
+
+    let ds_records: Vec<_> = parent_reply
+        .authoritative_answers
+        .iter()
+        .filter_map(Record::as_nonopt)
+        .filter(|rec| {
+            &rec.owner == zone
+                && (rec.rtype == RecordType::DS
+                    || rec.rdata().as_rrsig().map(|rrsig| rrsig.type_covered)
+                        == Some(RecordType::DS))
+        })
+        .collect();
+    let ds: Vec<_> = ds_records
+        .iter()
+        .filter_map(|rec| rec.rdata().as_ds())
+        .collect();
+    if ds.is_empty() {
+        return result(ValidationState::Insecure, "no DS records".into());
+    }
+
+    let ds_state = validate_rrsets_with(ds_records.iter().copied(), parent_keys, policy)
+        .first()
+        .map_or(ValidationState::Insecure, |validation| validation.state);
+    if ds_state != ValidationState::Secure {
+        return result(ds_state, format!("the DS records are {}", ds_state));
+    }
+
+    let mut rejected = None;
+    let ds: Vec<_> = ds
+        .into_iter()
+        .filter(|ds| match policy.check_ds(ds) {
+            Ok(()) => true,
+            Err(e) => {
+                rejected = Some(e);
+                false
+            }
+        })
+        .collect();
+    if let Some(e) = rejected.filter(|_| ds.is_empty()) {
+        let state = if policy.reject_unaccepted {
+            ValidationState::Bogus
+        } else {
+            ValidationState::Insecure
+        };
+        return result(state, format!("no DS record is accepted ({})", e));
+    }
+
+    let mut unsupported = false;
+    let matching: Vec<_> = child_keys
+        .iter()
+        .filter(|rec| match rec.rdata().as_dnskey() {
+            Some(key) => ds.iter().any(|ds| match ds.matches(zone, key) {
+                Ok(matches) => matches,
+                Err(_) => {
+                    unsupported = true;
+                    false
+                }
+            }),
+            None => false,
+        })
+        .cloned()
+        .collect();
+    if matching.is_empty() {
+        return if unsupported {
+            result(
+                ValidationState::Indeterminate,
+                "the digest types of the DS records are not supported".into(),
+            )
+        } else {
+            result(
+                ValidationState::Bogus,
+                "no DNSKEY matches the DS records".into(),
+            )
+        };
+    }
+
+    let tags: Vec<_> = matching
+        .iter()
+        .filter_map(|rec| rec.rdata().as_dnskey())
+        .map(|key| key.key_tag().to_string())
+        .collect();
+    let tags = tags.join(", ");
+    let signed = validate_rrsets_with(child_keys, &matching, policy)
+        .iter()
+        .any(|validation| {
+            validation.rtype == RecordType::DNSKEY && validation.state == ValidationState::Secure
+        });
+    if signed {
+        result(
+            ValidationState::Secure,
+            format!("DS matches DNSKEY {}", tags),
+        )
+    } else {
+        result(
+            ValidationState::Bogus,
+            format!("the DNSKEYs are not signed by DNSKEY {}", tags),
+        )
+    }
+}
+
+/// A nameserver that may be queried for a zone during an iterative query.
+struct Candidate {
+    name: Name,
+    nameserver: Nameserver,
+}
+
+/// The answer that was used for a zone during an iterative query: the name of the nameserver that
+/// sent it, the nameserver, the answer, the number of received bytes, and timing information.
+type ZoneAnswer = (Name, Nameserver, Message, u16, QueryTiming);
+
+/// Queries the nameservers of `zone` in `candidates` until one of them gives a usable answer,
+/// [`MAX_CONCURRENT_NAMESERVERS`] at a time, and returns that answer. Queried candidates are
+/// removed from `candidates`, and the ones whose answer was not used are added to `skipped`.
+///
+/// Candidates with a low smoothed RTT in `stats` are queried first. The addresses of candidates without glue are only looked up
+/// (starting at `roots`) once all other candidates failed.
+fn query_zone(
+    metadata: &QueryMetadata,
+    bufsize: u16,
+    zone: &Name,
+    candidates: &mut Vec<Candidate>,
+    roots: &[Nameserver],
+    stats: &ServerStats,
+    skipped: &mut Vec<SkippedServer>,
+) -> Result<ZoneAnswer> {
+    // shuffle first so that candidates with the same RTT are queried in random order
+    candidates.shuffle(&mut rand::thread_rng());
+    loop {
+        candidates.sort_by_key(|candidate| match candidate.nameserver.ip {
+            Some(ip) => (false, stats.srtt(ip)),
+            None => (true, Duration::ZERO),
+        });
+
+        let with_address = candidates
+            .iter()
+            .take(MAX_CONCURRENT_NAMESERVERS)
+            .take_while(|candidate| candidate.nameserver.ip.is_some())
+            .count();
+        if with_address > 0 {
+            let batch = candidates.drain(..with_address).collect();
+            if let Some(answer) = query_concurrently(metadata, bufsize, batch, stats, skipped) {
+                return Ok(answer);
+            }
+            continue;
+        }
+
+        let candidate = match candidates.pop() {
+            Some(candidate) => candidate,
+            None => {
+                let reasons: Vec<_> = skipped
+                    .iter()
+                    .filter_map(|skipped| {
+                        let reason = skipped.reason.as_ref()?;
+                        Some(format!("{}: {}", skipped.server, reason))
+                    })
+                    .collect();
+                bail!(
+                    transport,
+                    "None of the nameservers of {} gave a usable answer.\n{}",
+                    zone,
+                    reasons.join("\n")
+                )
+            }
+        };
+        let use_ipv6 = matches!(roots.first().and_then(|ns| ns.ip), Some(IpAddr::V6(_)));
+        match lookup_address(metadata, &candidate.name, use_ipv6, roots, stats) {
+            Some(ip) => {
+                let mut nameserver = candidate.nameserver;
+                nameserver.ip = Some(ip);
+                candidates.push(Candidate {
+                    name: candidate.name,
+                    nameserver,
+                });
+            }
+            None => skipped.push(SkippedServer {
+                server: candidate.nameserver,
+                reason: Some("Could not look up the nameserver's address.".into()),
+            }),
+        }
+    }
+}
+
+/// Sends the query specified by `metadata` to each of `nameservers` concurrently and returns the
+/// first usable answer, i.e. one without SERVFAIL, NOTIMP, or REFUSED, or [`None`] if there is
+/// none. All answers and timeouts are recorded in `stats`.
+///
+/// The nameservers whose answer was not used are added to `skipped`. Queries that are still in
+/// flight when a usable answer arrives are not waited for.
+fn query_concurrently(
+    metadata: &QueryMetadata,
+    bufsize: u16,
+    candidates: Vec<Candidate>,
+    stats: &ServerStats,
+    skipped: &mut Vec<SkippedServer>,
+) -> Option<ZoneAnswer> {
+    let (tx, rx) = mpsc::channel();
+    for (i, candidate) in candidates.iter().enumerate() {
+        let tx = tx.clone();
+        let mut metadata = metadata.clone();
+        let mut nameserver = candidate.nameserver.clone();
+        if let Some(ip) = nameserver.ip {
+            use_tcp_for_large_query(&mut metadata, bufsize, stats.max_query_size(ip));
+        }
+        thread::spawn(move || {
+            let res = prepare_and_send_query(&metadata, bufsize, &mut nameserver).and_then(
+                |(reply, bytes_recvd, timing)| {
+                    let reply = Message::parse(&mut Cursor::new(&reply))
+                        .parse("Could not parse answer.")?;
+                    Ok((reply, bytes_recvd, timing))
+                },
+            );
+            // the receiver is gone if another nameserver's answer was used already
+            let _ = tx.send((i, nameserver, res));
+        });
+    }
+    drop(tx);
+
+    let mut pending = vec![true; candidates.len()];
+    for (i, nameserver, res) in rx {
+        pending[i] = false;
+        let ip = candidates[i]
+            .nameserver
+            .ip
+            .expect("Nameserver without address was queried.");
+        let reason = match res {
+            Ok((reply, bytes_recvd, timing)) => {
+                stats.record_answer(ip, timing.elapsed, &reply);
+                let rcode = rcode_of(&reply);
+                if ![RCode::SERVFAIL, RCode::NOTIMP, RCode::REFUSED].contains(&rcode) {
+                    skipped.extend(zip(&candidates, &pending).filter(|(_, p)| **p).map(
+                        |(candidate, _)| SkippedServer {
+                            server: candidate.nameserver.clone(),
+                            reason: None,
+                        },
+                    ));
+                    let name = candidates[i].name.clone();
+                    return Some((name, nameserver, reply, bytes_recvd, timing));
+                }
+                format!("The nameserver answered with {}.", rcode)
+            }
+            Err(e) => {
+                stats.record_timeout(ip, metadata.timeout);
+                format!("{:#}", e)
+            }
+        };
+        skipped.push(SkippedServer {
+            server: nameserver,
+            reason: Some(reason),
+        });
+    }
+    None
+}
+
+/// Looks up the address of the nameserver `name` iteratively, starting at `roots`. If `use_ipv6`
+/// is true, looks up its IPv6 address first and its IPv4 address if there is none.
+fn lookup_address(
+    metadata: &QueryMetadata,
+    name: &Name,
+    use_ipv6: bool,
+    roots: &[Nameserver],
+    stats: &ServerStats,
+) -> Option<IpAddr> {
+    let mut metadata = metadata.clone();
+    metadata.name = name.clone();
+    metadata.qclass = Class::IN;
+    metadata.opcode = Opcode::QUERY;
+    metadata.validate_dnssec = false;
+
+    let qtypes: &[_] = if use_ipv6 {
+        &[RecordType::AAAA, RecordType::A]
+    } else {
+        &[RecordType::A]
+    };
+    qtypes.iter().find_map(|&qtype| {
+        metadata.qtype = qtype;
+        // no DNSKEYs are fetched without validation
+        resolve(
+            &metadata,
+            roots,
+            stats,
+            &DnskeyCache::new(),
+            &ValidationPolicy::default(),
+        )
+        .ok()
+        .and_then(|(rec, _, _, _)| rec.as_nonopt().and_then(address_of))
+    })
+}
+
+/// Returns the response code of `reply`, including the extended bits from its OPT record.
+fn rcode_of(reply: &Message) -> RCode {
+    let rcode = match reply.opt() {
+        Some(opt) => opt.rcode,
+        None => reply.header.rcode,
+    };
+    rcode.unwrap_or(RCode::NOERROR)
+}
+
 fn find_answer<'a>(metadata: &QueryMetadata, reply: &'a Message) -> Option<&'a Record> {
     reply.answers.iter().find(|rec| {
         let rec = rec.as_nonopt();
@@ -242,79 +681,60 @@ fn find_answer<'a>(metadata: &QueryMetadata, reply: &'a Message) -> Option<&'a R
     })
 }
 
-/// returns (zone name, nameserver hostname, nameserver ip)
-fn find_glue(prefer_ipv6: bool, reply: &Message) -> Option<(&Name, &Name, IpAddr)> {
-    // stores nameservers and which zones they are responsible for
-    let nameservers: Vec<_> = filter_ns(reply)
-        .into_iter()
-        .map(|rec| {
-            let name = &rec
-                .rdata()
-                .as_ns()
-                .expect("NS record had non-NS RDATA")
-                .name;
-            (name, &rec.owner)
-        })
-        .collect();
-    let find_glue_with_type = |typ: RecordType| {
-        reply
-            .additional_answers
-            .iter()
-            .find(|rec| {
-                let rec = rec.as_nonopt();
-                if let Some(nonopt) = rec {
-                    (nonopt.rtype == typ) & nameservers.iter().any(|(ns, _)| *ns == &nonopt.owner)
-                } else {
-                    false
-                }
-            })
-            .and_then(|rec| {
-                rec.as_nonopt().map(|nonopt| {
-                    let zone = nameservers
-                        .iter()
-                        .find(|(ns, _)| *ns == &nonopt.owner)
-                        .unwrap()
-                        .1;
-                    let ip: IpAddr = match typ {
-                        RecordType::A => nonopt
-                            .rdata()
-                            .as_a()
-                            .expect("A record has non-A RDATA")
-                            .address
-                            .into(),
-                        RecordType::AAAA => nonopt
-                            .rdata()
-                            .as_aaaa()
-                            .expect("AAAA record has non-AAAA RDATA")
-                            .address
-                            .into(),
-                        _ => {
-                            unreachable!("tried to find glue record with type other than AAAA or A")
-                        }
-                    };
-                    (zone, &nonopt.owner, ip)
-                })
-            })
-    };
-    if prefer_ipv6 {
-        // look for an IPv6 glue record and return it immediately if we find one. if we don't find
-        // one, look for an IPv4 glue record afterwards
-        return find_glue_with_type(RecordType::AAAA)
-            .or_else(|| find_glue_with_type(RecordType::A));
+/// Returns the zone that `reply` refers to and its nameservers as candidates, with their address
+/// taken from the glue records if there are any, or [`None`] if `reply` is no referral.
+///
+/// If `prefer_ipv6` is true, IPv6 glue is used if available and IPv4 glue otherwise; if not, only
+/// IPv4 glue is used.
+fn find_referral(prefer_ipv6: bool, reply: &Message) -> Option<(Name, Vec<Candidate>)> {
+    let ns_records = filter_ns(reply);
+    let zone = &ns_records.first()?.owner;
+    let mut candidates: Vec<Candidate> = Vec::new();
+    for ns in ns_records
+        .iter()
+        .filter(|rec| &rec.owner == zone)
+        .filter_map(|rec| rec.rdata().as_ns())
+    {
+        if candidates.iter().any(|candidate| candidate.name == ns.name) {
+            continue;
+        }
+        let glue = if prefer_ipv6 {
+            find_glue(&ns.name, RecordType::AAAA, reply)
+                .or_else(|| find_glue(&ns.name, RecordType::A, reply))
+        } else {
+            find_glue(&ns.name, RecordType::A, reply)
+        };
+        candidates.push(Candidate {
+            name: ns.name.clone(),
+            nameserver: Nameserver {
+                hostname: Some(ns.name.to_string()),
+                ip: glue,
+                scope_id: None,
+                port: 53,
+            },
+        });
     }
-    find_glue_with_type(RecordType::A)
+    Some((zone.clone(), candidates))
 }
 
-/// randomly chooses one of the nameservers from the authoritative section and returns its hostname
-/// and the zone name
-fn select_ns(reply: &Message) -> Option<(&Name, &Name)> {
-    filter_ns(reply)
-        .into_iter()
-        .choose(&mut rand::thread_rng())
-        .map(|rec| {
-            let name = &rec.rdata().as_ns().unwrap().name;
-            (name, &rec.owner)
-        })
+/// Returns the address of the first glue record of type `rtype` for the nameserver `name` in the
+/// additional section of `reply`.
+fn find_glue(name: &Name, rtype: RecordType, reply: &Message) -> Option<IpAddr> {
+    reply
+        .additional_answers
+        .iter()
+        .filter_map(Record::as_nonopt)
+        .filter(|rec| rec.rtype == rtype && &rec.owner == name)
+        .find_map(address_of)
+}
+
+/// returns the address contained in an A or AAAA record
+fn address_of(rec: &NonOptRecord) -> Option<IpAddr> {
+    match rec.rdata() {
+        Rdata::A(a) => Some(a.address.into()),
+        Rdata::AAAA(aaaa) => Some(aaaa.address.into()),
+        _ => None,
+    }
 }
 
 /// returns all NS records from the authoritative section
@@ -333,3 +753,133 @@ fn filter_ns(reply: &Message) -> Vec<&NonOptRecord> {
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use p256::ecdsa::signature::Signer;
+    use p256::ecdsa::{SigningKey, VerifyingKey};
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use toluol_proto::dnssec::{ValidationPolicy, ValidationState};
+    use toluol_proto::rdata::dnskey::{Algorithm, DNSKEY};
+    use toluol_proto::rdata::ds::{DigestType, DS};
+    use toluol_proto::rdata::RRSIG;
+    use toluol_proto::Record;
+    use toluol_proto::{Class, HeaderFlags, Message, Name, NonOptRecord, Opcode, RCode, Rdata};
+
+    use super::check_delegation;
+
+    fn signing_key(n: u8) -> SigningKey {
+        SigningKey::from_bytes(&[n; 32]).unwrap()
+    }
+
+    fn dnskey(key: &SigningKey) -> DNSKEY {
+        let point = VerifyingKey::from(key).to_encoded_point(false);
+        DNSKEY {
+            zone: true,
+            revoked: false,
+            secure_entry_point: true,
+            algorithm: Algorithm::ECDSAP256SHA256,
+            key: point.as_bytes()[1..].to_vec(),
+        }
+    }
+
+    fn record(owner: &Name, rdata: Rdata) -> NonOptRecord {
+        NonOptRecord::new(owner.clone(), Class::IN, 3600, rdata).unwrap()
+    }
+
+    /// Returns `rdata` as a record of `owner` and an RRSIG covering it, made with `key` of the
+    /// zone `signer`.
+    fn signed(owner: &Name, rdata: Rdata, signer: &Name, key: &SigningKey) -> [NonOptRecord; 2] {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as u32;
+        let covered = record(owner, rdata);
+        let mut rrsig = RRSIG {
+            type_covered: covered.rtype,
+            algorithm: Algorithm::ECDSAP256SHA256,
+            labels: owner.label_count(),
+            original_ttl: covered.ttl,
+            signature_expiration: now + 86400,
+            signature_inception: now - 3600,
+            key_tag: dnskey(key).key_tag(),
+            signer_name: signer.clone(),
+            signature: Vec::new(),
+        };
+        let mut data = Rdata::RRSIG(rrsig.clone()).encode().unwrap();
+        data.extend(covered.encode().unwrap());
+        let signature: p256::ecdsa::Signature = key.sign(&data);
+        rrsig.signature = signature.as_ref().to_vec();
+        [covered, record(owner, Rdata::RRSIG(rrsig))]
+    }
+
+    /// Returns the state of the delegation from `example` to `child.example` with `ds` according
+    /// to `policy`. The DS and DNSKEY records are correctly signed.
+    fn delegation_state(
+        ds: impl Fn(&DNSKEY, &Name) -> DS,
+        policy: ValidationPolicy,
+    ) -> ValidationState {
+        let parent = Name::from_ascii("example").unwrap();
+        let zone = Name::from_ascii("child.example").unwrap();
+        let (parent_key, child_key) = (signing_key(1), signing_key(2));
+
+        let ds = ds(&dnskey(&child_key), &zone);
+        let referral = Message::new_response(
+            0,
+            Opcode::QUERY,
+            HeaderFlags {
+                aa: false,
+                tc: false,
+                rd: false,
+                ra: false,
+                ad: false,
+                cd: false,
+            },
+            RCode::NOERROR,
+            Vec::new(),
+            [
+                Vec::new(),
+                signed(&zone, Rdata::DS(ds), &parent, &parent_key)
+                    .map(Record::NONOPT)
+                    .to_vec(),
+                Vec::new(),
+            ],
+        );
+        let parent_keys = [record(&parent, Rdata::DNSKEY(dnskey(&parent_key)))];
+        let child_keys = signed(&zone, Rdata::DNSKEY(dnskey(&child_key)), &zone, &child_key);
+        check_delegation(&zone, &referral, &parent_keys, &child_keys, &policy).state
+    }
+
+    #[test]
+    fn delegation_follows_policy() {
+        let sha256 = |key: &DNSKEY, zone: &Name| key.to_ds(zone, DigestType::SHA256).unwrap();
+        // toluol-proto cannot compute SHA-1 digests, so the digest does not need to be correct
+        let sha1 = |key: &DNSKEY, _: &Name| DS {
+            key_tag: key.key_tag(),
+            algorithm: key.algorithm,
+            digest_type: DigestType::SHA1,
+            digest: vec![0; 20],
+        };
+        let without_sha1 = || ValidationPolicy {
+            accept_sha1: false,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            delegation_state(sha256, ValidationPolicy::modern()),
+            ValidationState::Secure
+        );
+        assert_eq!(
+            delegation_state(sha1, ValidationPolicy::default()),
+            ValidationState::Indeterminate
+        );
+        assert_eq!(
+            delegation_state(sha1, without_sha1()),
+            ValidationState::Insecure
+        );
+        assert_eq!(
+            delegation_state(sha1, ValidationPolicy::modern()),
+            ValidationState::Bogus
+        );
+    }
+}
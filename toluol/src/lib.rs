@@ -1,8 +1,52 @@
-use toluol_proto::{Name, RecordType};
+use std::fmt::Display;
+use std::sync::Arc;
+use std::time::Duration;
+use toluol_proto::rdata::opt::{ClientSubnet, OptionCode};
+use toluol_proto::{Class, Name, Opcode, RecordType};
 
+use crate::querylog::QueryLog;
+
+pub use crate::error::Error;
+
+#[cfg(feature = "json")]
+pub mod api;
+pub mod blocklist;
+pub mod caa;
+pub mod cache;
+pub mod cds;
+pub mod ddds;
+pub mod dns64;
+#[cfg(feature = "json")]
+pub mod dns_json;
+pub mod error;
+pub mod explain;
+#[cfg(feature = "geoip")]
+pub mod geoip;
+#[cfg(feature = "json")]
+pub mod hook;
+pub mod hosts;
 pub mod iter;
+pub mod mail;
+pub mod middlebox;
+pub mod mux;
 pub mod net;
+pub mod probe;
+pub mod querylog;
+pub mod ratelimit;
+pub mod raw;
+#[cfg(all(feature = "http", feature = "json"))]
+pub mod rdap;
+pub mod resolver;
+pub mod session;
+pub mod split;
+pub mod stats;
+pub mod stream;
+pub mod survey;
+pub mod sweep;
+#[cfg(feature = "test-server")]
+pub mod test_server;
 pub mod util;
+pub mod watch;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum ConnectionType {
@@ -20,14 +64,87 @@ pub enum ConnectionType {
     HttpsPost,
 }
 
+impl ConnectionType {
+    /// Returns whether this is DNS over HTTP or HTTPS.
+    pub fn is_http(self) -> bool {
+        match self {
+            Self::Udp | Self::Tcp => false,
+            #[cfg(feature = "tls")]
+            Self::Tls => false,
+            #[cfg(feature = "http")]
+            Self::HttpGet | Self::HttpPost | Self::HttpsGet | Self::HttpsPost => true,
+        }
+    }
+}
+
+impl Display for ConnectionType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Udp => write!(f, "UDP"),
+            Self::Tcp => write!(f, "TCP"),
+            #[cfg(feature = "tls")]
+            Self::Tls => write!(f, "DoT"),
+            #[cfg(feature = "http")]
+            Self::HttpGet => write!(f, "HTTP (GET)"),
+            #[cfg(feature = "http")]
+            Self::HttpPost => write!(f, "HTTP (POST)"),
+            #[cfg(feature = "http")]
+            Self::HttpsGet => write!(f, "DoH (GET)"),
+            #[cfg(feature = "http")]
+            Self::HttpsPost => write!(f, "DoH (POST)"),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct QueryMetadata {
     pub name: Name,
     pub qtype: RecordType,
+    pub qclass: Class,
+    pub opcode: Opcode,
     pub nameserver: String,
     pub port: u16,
     pub connection_type: ConnectionType,
+    /// Whether to request DNSSEC records, i.e. `RRSIG`s for the answers and, when querying
+    /// iteratively, the `DNSKEY`s of the zones. This sets the DO bit, unless [`Self::dnssec_ok`]
+    /// says otherwise.
     pub fetch_dnssec: bool,
     pub validate_dnssec: bool,
+    /// The DO bit to send instead of the one implied by [`Self::fetch_dnssec`], e.g. to reproduce
+    /// how a resolver treats such combinations. Only sent with EDNS.
+    pub dnssec_ok: Option<bool>,
+    /// Whether to set the CD (checking disabled) bit, which asks a validating resolver to answer
+    /// even if validation fails. Normally set, as toluol can validate answers itself, see
+    /// [RFC 6840, Section 5.9](https://www.rfc-editor.org/rfc/rfc6840#section-5.9).
+    pub checking_disabled: bool,
+    /// Whether to send an OPT record, i.e. to use EDNS. Without it, the DO bit, the client cookie,
+    /// and the client subnet are not sent.
+    pub edns: bool,
+    /// The EDNS version to send, normally 0.
+    pub edns_version: u8,
+    /// Further EDNS options to send as they are, e.g. for testing servers.
+    pub edns_options: Vec<(OptionCode, Vec<u8>)>,
     pub client_cookie: Option<[u8; 8]>,
+    /// The EDNS Client Subnet to send, if any.
+    pub client_subnet: Option<ClientSubnet>,
+    /// Whether to ignore UDP answers that don't come from the queried nameserver.
+    pub check_source: bool,
+    /// How long to wait for an answer to a UDP query before sending it again.
+    pub timeout: Duration,
+    /// How often to send a UDP query again if no answer arrives in time.
+    pub retransmissions: u32,
+    /// Whether UDP queries reuse a socket connected to the same nameserver by an earlier query,
+    /// see [`UdpOptions::reuse_socket`](net::UdpOptions::reuse_socket). This is useful for
+    /// iterative queries, which query the same nameservers (e.g. of the root and TLD zones) again
+    /// and again.
+    pub reuse_udp_sockets: bool,
+    /// The socket options of the connections of queries via TCP and TLS, including those sent
+    /// again via TCP because the UDP answer was truncated.
+    pub tcp_options: net::TcpOptions,
+    /// Whether to query a DNS over HTTP(S) endpoint again via its JSON API if it rejects the wire
+    /// format with status 406 or 415, see [`send_query_http_json()`](net::send_query_http_json()).
+    /// Only used with the `http` and `json` features.
+    pub doh_json_fallback: bool,
+    /// The log each query and its response are appended to, if any.
+    pub query_log: Option<Arc<QueryLog>>,
 }
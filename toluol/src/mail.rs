@@ -0,0 +1,661 @@
+//! Auditing the mail security records of a domain (`mail-audit`).
+//!
+//! The following records are checked:
+//! - MX records
+//! - SPF ([RFC 7208](https://www.rfc-editor.org/rfc/rfc7208))
+//! - DMARC ([RFC 7489](https://www.rfc-editor.org/rfc/rfc7489))
+//! - MTA-STS ([RFC 8461](https://www.rfc-editor.org/rfc/rfc8461)), including the policy if the
+//!   `http` feature is enabled
+//! - SMTP TLS reporting ([RFC 8460](https://www.rfc-editor.org/rfc/rfc8460))
+
+use std::fmt::Display;
+use toluol_proto::rdata::{MX, TXT};
+use toluol_proto::{Class, Name, NonOptRecord, Rdata, RecordType};
+
+use crate::error::{Context, Result};
+use crate::util::lookup;
+use crate::QueryMetadata;
+
+/// The maximum number of DNS lookups an SPF record may cause.
+/// [\[RFC 7208, Section 4.6.4\]](https://www.rfc-editor.org/rfc/rfc7208#section-4.6.4)
+const SPF_MAX_LOOKUPS: usize = 10;
+
+/// How severe a [`Finding`] is.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Something is missing or misconfigured so that it doesn't work or weakens security.
+    Error,
+    /// Something could be improved.
+    Warning,
+    /// Something noteworthy that is not a problem.
+    Info,
+}
+
+/// A single result of the audit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// The mail security records of a domain and the problems found with them.
+#[derive(Clone, Debug)]
+pub struct MailAudit {
+    pub domain: Name,
+    pub mx: Vec<MX>,
+    /// TXT records of the domain that start with `v=spf1`.
+    pub spf: Vec<String>,
+    /// TXT records of `_dmarc.<domain>` that start with `v=DMARC1`.
+    pub dmarc: Vec<String>,
+    /// TXT records of `_mta-sts.<domain>` that start with `v=STSv1`.
+    pub mta_sts: Vec<String>,
+    /// The MTA-STS policy, if there is an MTA-STS record and it could be fetched.
+    pub mta_sts_policy: Option<String>,
+    /// TXT records of `_smtp._tls.<domain>` that start with `v=TLSRPTv1`.
+    pub tlsrpt: Vec<String>,
+    /// All findings, sorted by severity.
+    pub findings: Vec<Finding>,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Warning => write!(f, "warning"),
+            Self::Info => write!(f, "info"),
+        }
+    }
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
+
+/// Fetches and checks the mail security records of `domain`.
+///
+/// `metadata.name` and `metadata.qtype` are ignored.
+pub fn audit(domain: &Name, metadata: &QueryMetadata, bufsize: u16) -> Result<MailAudit> {
+    let subdomain = |label: &str| -> Result<Name> {
+        let mut name = domain.clone();
+        name.prepend_label(label)
+            .parse(format!("Could not create the name {}.{}.", label, domain))?;
+        Ok(name)
+    };
+    let mut tlsrpt_name = subdomain("_tls")?;
+    tlsrpt_name
+        .prepend_label("_smtp")
+        .parse(format!("Could not create the name _smtp._tls.{}.", domain))?;
+
+    let mx: Vec<_> = lookup(domain, RecordType::MX, metadata, bufsize)?
+        .into_iter()
+        .filter_map(|rdata| match rdata {
+            Rdata::MX(mx) => Some(mx),
+            _ => None,
+        })
+        .collect();
+    let apex_txt = lookup_all_txt(domain, metadata, bufsize)?;
+    let spf = filter_txt(&apex_txt, "v=spf1");
+    let dmarc = lookup_txt(&subdomain("_dmarc")?, "v=DMARC1", metadata, bufsize)?;
+    let mta_sts = lookup_txt(&subdomain("_mta-sts")?, "v=STSv1", metadata, bufsize)?;
+    let tlsrpt = lookup_txt(&tlsrpt_name, "v=TLSRPTv1", metadata, bufsize)?;
+
+    let mut findings = Vec::new();
+    check_mx(&mx, &mut findings);
+    let accepts_mail = !is_null_mx(&mx);
+    check_spf(&spf, &mut findings);
+    check_owner_conventions(domain, &apex_txt, &mut findings);
+    check_dmarc(&dmarc, &mut findings);
+    let mta_sts_policy = check_mta_sts(domain, &mta_sts, &mx, accepts_mail, &mut findings);
+    check_tlsrpt(&tlsrpt, &mta_sts, accepts_mail, &mut findings);
+    findings.sort_by_key(|finding| finding.severity);
+
+    Ok(MailAudit {
+        domain: domain.clone(),
+        mx,
+        spf: spf.iter().map(TXT::joined).collect(),
+        dmarc: dmarc.iter().map(TXT::joined).collect(),
+        mta_sts: mta_sts.iter().map(TXT::joined).collect(),
+        mta_sts_policy,
+        tlsrpt: tlsrpt.iter().map(TXT::joined).collect(),
+        findings,
+    })
+}
+
+/// Queries the TXT records of `name` and returns those that start with `version` (ignoring case).
+fn lookup_txt(
+    name: &Name,
+    version: &str,
+    metadata: &QueryMetadata,
+    bufsize: u16,
+) -> Result<Vec<TXT>> {
+    Ok(filter_txt(
+        &lookup_all_txt(name, metadata, bufsize)?,
+        version,
+    ))
+}
+
+/// Queries the TXT records of `name`.
+fn lookup_all_txt(name: &Name, metadata: &QueryMetadata, bufsize: u16) -> Result<Vec<TXT>> {
+    Ok(lookup(name, RecordType::TXT, metadata, bufsize)?
+        .into_iter()
+        .filter_map(|rdata| match rdata {
+            Rdata::TXT(txt) => Some(txt),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Returns the records of `txt` that start with `version` (ignoring case), followed by a space, a
+/// semicolon, or nothing.
+fn filter_txt(txt: &[TXT], version: &str) -> Vec<TXT> {
+    txt.iter()
+        .filter(|txt| {
+            let text = txt.joined();
+            let prefix = text.get(..version.len()).unwrap_or_default();
+            prefix.eq_ignore_ascii_case(version)
+                && text[version.len()..]
+                    .chars()
+                    .next()
+                    .is_none_or(|c| c == ' ' || c == ';')
+        })
+        .cloned()
+        .collect()
+}
+
+/// Adds a finding for each TXT record of `domain` that belongs elsewhere by convention, e.g. a
+/// DMARC record that was published at the domain instead of at `_dmarc.<domain>`.
+fn check_owner_conventions(domain: &Name, txt: &[TXT], findings: &mut Vec<Finding>) {
+    for txt in txt {
+        let record = match NonOptRecord::new(domain.clone(), Class::IN, 0, Rdata::TXT(txt.clone()))
+        {
+            Ok(record) => record,
+            Err(_) => continue,
+        };
+        for warning in record.check_owner_convention() {
+            finding(
+                findings,
+                Severity::Error,
+                format!(
+                    "{} at {}; receivers ignore it there (\"{}\").",
+                    warning,
+                    domain,
+                    txt.joined()
+                ),
+            );
+        }
+    }
+}
+
+/// Returns true if `mx` is a "null MX", i.e. the domain doesn't accept mail.
+/// [\[RFC 7505\]](https://www.rfc-editor.org/rfc/rfc7505)
+fn is_null_mx(mx: &[MX]) -> bool {
+    mx.len() == 1 && mx[0].exchange.is_root()
+}
+
+fn finding(findings: &mut Vec<Finding>, severity: Severity, message: impl Into<String>) {
+    findings.push(Finding {
+        severity,
+        message: message.into(),
+    });
+}
+
+fn check_mx(mx: &[MX], findings: &mut Vec<Finding>) {
+    if mx.is_empty() {
+        finding(
+            findings,
+            Severity::Warning,
+            "No MX records; mail is delivered to the domain's A/AAAA records. Publish a null MX \
+             record (\"0 .\") if the domain does not accept mail.",
+        );
+    } else if is_null_mx(mx) {
+        finding(
+            findings,
+            Severity::Info,
+            "The domain does not accept mail (null MX record).",
+        );
+    } else if mx.iter().any(|mx| mx.exchange.is_root()) {
+        finding(
+            findings,
+            Severity::Error,
+            "A null MX record must be the only MX record.",
+        );
+    }
+}
+
+fn check_spf(spf: &[TXT], findings: &mut Vec<Finding>) {
+    let spf = match spf {
+        [] => {
+            finding(
+                findings,
+                Severity::Error,
+                "No SPF record; anyone may send mail in the domain's name.",
+            );
+            return;
+        }
+        [spf] => spf.joined(),
+        _ => {
+            finding(
+                findings,
+                Severity::Error,
+                "Multiple SPF records; receivers treat this as a permanent error.",
+            );
+            return;
+        }
+    };
+
+    let terms: Vec<_> = spf.split_ascii_whitespace().skip(1).collect();
+    let mut lookups = 0;
+    let mut all = None;
+    let mut redirect = false;
+    for term in &terms {
+        let term = term.to_ascii_lowercase();
+        let mechanism = term.trim_start_matches(['+', '-', '~', '?']);
+        let name = mechanism.split([':', '/', '=']).next().unwrap_or_default();
+        match name {
+            "include" | "a" | "mx" | "exists" => lookups += 1,
+            "ptr" => {
+                lookups += 1;
+                finding(
+                    findings,
+                    Severity::Warning,
+                    "The SPF record uses the \"ptr\" mechanism, which should not be used.",
+                );
+            }
+            "redirect" => {
+                lookups += 1;
+                redirect = true;
+            }
+            "all" => all = term.chars().next(),
+            _ => {}
+        }
+    }
+
+    if lookups > SPF_MAX_LOOKUPS {
+        finding(
+            findings,
+            Severity::Error,
+            format!(
+                "The SPF record needs at least {} DNS lookups, but at most {} are allowed.",
+                lookups, SPF_MAX_LOOKUPS
+            ),
+        );
+    }
+    match all {
+        Some('+') | Some('a') => finding(
+            findings,
+            Severity::Error,
+            "The SPF record ends with \"+all\", which allows anyone to send mail.",
+        ),
+        Some('?') => finding(
+            findings,
+            Severity::Warning,
+            "The SPF record ends with \"?all\", which does not restrict senders.",
+        ),
+        Some('~') => finding(
+            findings,
+            Severity::Info,
+            "The SPF record ends with \"~all\" (soft fail).",
+        ),
+        Some(_) => {}
+        None if !redirect => finding(
+            findings,
+            Severity::Warning,
+            "The SPF record has neither an \"all\" mechanism nor a redirect, so unlisted senders \
+             are not rejected.",
+        ),
+        None => {}
+    }
+}
+
+fn check_dmarc(dmarc: &[TXT], findings: &mut Vec<Finding>) {
+    let dmarc = match dmarc {
+        [] => {
+            finding(findings, Severity::Error, "No DMARC record.");
+            return;
+        }
+        [dmarc] => dmarc,
+        _ => {
+            finding(
+                findings,
+                Severity::Error,
+                "Multiple DMARC records; receivers ignore all of them.",
+            );
+            return;
+        }
+    };
+    let tags = match dmarc.tag_list() {
+        Some(tags) => tags,
+        None => {
+            finding(findings, Severity::Error, "The DMARC record is malformed.");
+            return;
+        }
+    };
+    let tag = |name: &str| {
+        tags.iter()
+            .find(|(tag, _)| tag.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.to_ascii_lowercase())
+    };
+
+    match tag("p").as_deref() {
+        None => finding(
+            findings,
+            Severity::Error,
+            "The DMARC record has no policy (\"p\" tag).",
+        ),
+        Some("none") => finding(
+            findings,
+            Severity::Warning,
+            "The DMARC policy is \"none\", so failing mail is only reported, not rejected.",
+        ),
+        Some("quarantine") | Some("reject") => {}
+        Some(p) => finding(
+            findings,
+            Severity::Error,
+            format!("The DMARC policy \"{}\" is invalid.", p),
+        ),
+    }
+    if let Some(pct) = tag("pct") {
+        if pct != "100" {
+            finding(
+                findings,
+                Severity::Warning,
+                format!("The DMARC policy only applies to {}% of mail.", pct),
+            );
+        }
+    }
+    if tag("rua").is_none() {
+        finding(
+            findings,
+            Severity::Warning,
+            "The DMARC record has no \"rua\" tag, so no aggregate reports are sent.",
+        );
+    }
+}
+
+/// Checks the MTA-STS record and, if possible, the policy. Returns the policy if it was fetched.
+#[cfg_attr(not(feature = "http"), allow(unused_variables))]
+fn check_mta_sts(
+    domain: &Name,
+    mta_sts: &[TXT],
+    mx: &[MX],
+    accepts_mail: bool,
+    findings: &mut Vec<Finding>,
+) -> Option<String> {
+    let record = match mta_sts {
+        [] => {
+            if accepts_mail {
+                finding(
+                    findings,
+                    Severity::Info,
+                    "No MTA-STS record; senders may deliver mail without TLS.",
+                );
+            }
+            return None;
+        }
+        [record] => record,
+        _ => {
+            finding(
+                findings,
+                Severity::Error,
+                "Multiple MTA-STS records; senders ignore all of them.",
+            );
+            return None;
+        }
+    };
+    let has_id = record.tag_list().is_some_and(|tags| {
+        tags.iter()
+            .any(|(tag, value)| tag == "id" && !value.is_empty())
+    });
+    if !has_id {
+        finding(
+            findings,
+            Severity::Error,
+            "The MTA-STS record has no \"id\" tag.",
+        );
+    }
+
+    #[cfg(feature = "http")]
+    {
+        let domain = domain.to_string();
+        let url = format!(
+            "https://mta-sts.{}/.well-known/mta-sts.txt",
+            domain.trim_end_matches('.')
+        );
+        let policy = ureq::get(&url)
+            .timeout(std::time::Duration::from_secs(10))
+            .call()
+            .map_err(crate::error::BoxError::from)
+            .and_then(|response| Ok(response.into_string()?));
+        match policy {
+            Ok(policy) => {
+                check_mta_sts_policy(&policy, mx, findings);
+                Some(policy)
+            }
+            Err(e) => {
+                finding(
+                    findings,
+                    Severity::Error,
+                    format!("Could not fetch the MTA-STS policy: {}", e),
+                );
+                None
+            }
+        }
+    }
+
+    #[cfg(not(feature = "http"))]
+    None
+}
+
+/// Checks the MTA-STS policy `policy` ([RFC 8461, Section 3.2](https://www.rfc-editor.org/rfc/rfc8461#section-3.2)).
+#[cfg(feature = "http")]
+fn check_mta_sts_policy(policy: &str, mx: &[MX], findings: &mut Vec<Finding>) {
+    let fields: Vec<_> = policy
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key.trim(), value.trim()))
+        .collect();
+    let field = |name: &str| fields.iter().find(|(key, _)| *key == name).map(|f| f.1);
+
+    if field("version") != Some("STSv1") {
+        finding(
+            findings,
+            Severity::Error,
+            "The MTA-STS policy has no \"version: STSv1\" field.",
+        );
+    }
+    match field("mode") {
+        Some("enforce") => {}
+        Some("testing") => finding(
+            findings,
+            Severity::Warning,
+            "The MTA-STS policy is in testing mode, so it is not enforced.",
+        ),
+        Some("none") => finding(
+            findings,
+            Severity::Warning,
+            "The MTA-STS policy mode is \"none\".",
+        ),
+        _ => finding(
+            findings,
+            Severity::Error,
+            "The MTA-STS policy has no valid \"mode\" field.",
+        ),
+    }
+    if field("max_age")
+        .and_then(|max_age| max_age.parse::<u32>().ok())
+        .is_none()
+    {
+        finding(
+            findings,
+            Severity::Error,
+            "The MTA-STS policy has no valid \"max_age\" field.",
+        );
+    }
+
+    let patterns: Vec<_> = fields
+        .iter()
+        .filter(|(key, _)| *key == "mx")
+        .map(|(_, pattern)| pattern.to_ascii_lowercase())
+        .collect();
+    for mx in mx.iter().filter(|mx| !mx.exchange.is_root()) {
+        let host = mx.exchange.to_string().to_ascii_lowercase();
+        let host = host.trim_end_matches('.');
+        let matches = patterns
+            .iter()
+            .any(|pattern| match pattern.strip_prefix("*.") {
+                // a wildcard matches exactly one label
+                Some(suffix) => host
+                    .split_once('.')
+                    .is_some_and(|(_, parent)| parent == suffix),
+                None => pattern == host,
+            });
+        if !matches {
+            finding(
+                findings,
+                Severity::Error,
+                format!("The MX host {} is not listed in the MTA-STS policy.", host),
+            );
+        }
+    }
+}
+
+fn check_tlsrpt(tlsrpt: &[TXT], mta_sts: &[TXT], accepts_mail: bool, findings: &mut Vec<Finding>) {
+    match tlsrpt {
+        [] => {
+            if accepts_mail {
+                let severity = if mta_sts.is_empty() {
+                    Severity::Info
+                } else {
+                    Severity::Warning
+                };
+                finding(
+                    findings,
+                    severity,
+                    "No TLSRPT record, so no reports about TLS failures are sent.",
+                );
+            }
+        }
+        [record] => {
+            let has_rua = record.tag_list().is_some_and(|tags| {
+                tags.iter()
+                    .any(|(tag, value)| tag == "rua" && !value.is_empty())
+            });
+            if !has_rua {
+                finding(
+                    findings,
+                    Severity::Error,
+                    "The TLSRPT record has no \"rua\" tag.",
+                );
+            }
+        }
+        _ => finding(
+            findings,
+            Severity::Error,
+            "Multiple TLSRPT records; senders ignore all of them.",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use toluol_proto::rdata::TXT;
+
+    use super::{check_dmarc, check_spf, Finding, Severity};
+
+    fn txt(text: &str) -> Vec<TXT> {
+        vec![TXT {
+            text: vec![text.to_string()],
+        }]
+    }
+
+    /// Returns the severities of the findings for the SPF record `spf`.
+    fn spf_findings(spf: &str) -> Vec<Severity> {
+        let mut findings = Vec::new();
+        check_spf(&txt(spf), &mut findings);
+        findings.iter().map(|f| f.severity).collect()
+    }
+
+    fn dmarc_findings(dmarc: &str) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        check_dmarc(&txt(dmarc), &mut findings);
+        findings
+    }
+
+    #[test]
+    fn spf_all_qualifiers() {
+        assert_eq!(spf_findings("v=spf1 mx -all"), []);
+        assert_eq!(spf_findings("v=spf1 mx ~all"), [Severity::Info]);
+        assert_eq!(spf_findings("v=spf1 mx ?all"), [Severity::Warning]);
+        assert_eq!(spf_findings("v=spf1 mx +all"), [Severity::Error]);
+        assert_eq!(spf_findings("v=spf1 mx all"), [Severity::Error]);
+        assert_eq!(spf_findings("v=spf1 mx"), [Severity::Warning]);
+        assert_eq!(spf_findings("v=spf1 redirect=_spf.example.com"), []);
+    }
+
+    #[test]
+    fn spf_lookup_limit() {
+        let includes: Vec<_> = (0..9)
+            .map(|i| format!("include:_spf{}.example.com", i))
+            .collect();
+        let spf = format!("v=spf1 {} -all", includes.join(" "));
+        assert_eq!(spf_findings(&spf), []);
+        // "a" and "mx" need lookups as well, but "ip4" doesn't
+        let spf = format!("v=spf1 {} ip4:192.0.2.1 a -all", includes.join(" "));
+        assert_eq!(spf_findings(&spf), []);
+        let spf = format!("v=spf1 {} a mx -all", includes.join(" "));
+        assert_eq!(spf_findings(&spf), [Severity::Error]);
+    }
+
+    #[test]
+    fn dmarc_policy() {
+        let rua = "rua=mailto:dmarc@example.com";
+        assert_eq!(dmarc_findings(&format!("v=DMARC1; p=reject; {}", rua)), []);
+        assert_eq!(
+            dmarc_findings(&format!("v=DMARC1; p=Quarantine; pct=100; {}", rua)),
+            []
+        );
+
+        let findings = dmarc_findings(&format!("v=DMARC1; p=none; {}", rua));
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, Severity::Warning);
+        let findings = dmarc_findings(&format!("v=DMARC1; p=reject; pct=20; {}", rua));
+        assert_eq!(findings.len(), 1);
+        assert!(findings[0].message.contains("20%"));
+
+        for dmarc in ["v=DMARC1; p=block", "v=DMARC1; sp=reject"] {
+            let findings = dmarc_findings(&format!("{}; {}", dmarc, rua));
+            assert_eq!(findings.len(), 1);
+            assert_eq!(findings[0].severity, Severity::Error);
+        }
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn mta_sts_policy_mx_patterns() {
+        use super::check_mta_sts_policy;
+        use toluol_proto::rdata::MX;
+        use toluol_proto::Name;
+
+        let policy = "version: STSv1\r\nmode: enforce\r\nmx: mail.example.com\r\n\
+                      mx: *.example.net\r\nmax_age: 86400\r\n";
+        let unlisted = |hosts: &[&str]| {
+            let mx: Vec<_> = hosts
+                .iter()
+                .map(|host| MX {
+                    preference: 10,
+                    exchange: Name::from_ascii(host).unwrap(),
+                })
+                .collect();
+            let mut findings = Vec::new();
+            check_mta_sts_policy(policy, &mx, &mut findings);
+            findings.len()
+        };
+        assert_eq!(unlisted(&["mail.example.com", "mx1.example.net"]), 0);
+        assert_eq!(unlisted(&["MAIL.example.com"]), 0);
+        // a wildcard matches exactly one label
+        assert_eq!(unlisted(&["example.net", "a.b.example.net"]), 2);
+        assert_eq!(unlisted(&["mail.example.org"]), 1);
+    }
+}
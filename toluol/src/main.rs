@@ -1,18 +1,59 @@
-use std::cmp::max;
-use std::io::Cursor;
+use std::borrow::Cow;
+use std::io::{self, Cursor, IsTerminal};
 use std::iter::zip;
-use std::time::Duration;
+use std::net::{Ipv4Addr, Ipv6Addr};
+#[cfg(feature = "json")]
+use std::net::{SocketAddr, TcpListener};
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Result};
+use data_encoding::HEXLOWER;
 use owo_colors::{OwoColorize, Style};
-use toluol::net::Nameserver;
-use toluol::util::{get_dnskeys, prepare_query, send_query};
+#[cfg(feature = "json")]
+use toluol::api::ApiServer;
+use toluol::blocklist::{Verdict, FILTERING_RESOLVERS};
+use toluol::caa::{find_policy, Authorization};
+use toluol::cache::DnskeyCache;
+use toluol::cds::{self, ParentAction};
+use toluol::ddds;
+#[cfg(feature = "json")]
+use toluol::ddds::Terminal;
+use toluol::dns64::{self, Nat64Prefix};
+use toluol::explain::{explain_message, English};
+use toluol::hosts::Hosts;
+use toluol::mail::{audit, Severity};
+use toluol::middlebox::{self, Outcome};
+use toluol::net::{Nameserver, QueryTiming};
+use toluol::probe::{self, ProbeSpec};
+use toluol::ratelimit::{Rate, RateLimit};
+use toluol::raw::MessageTemplate;
+use toluol::resolver::{Lookup, Resolver, ResolverConfig};
+use toluol::session::Session;
+use toluol::split;
+use toluol::stats::ServerStats;
+use toluol::survey;
+use toluol::sweep::{self, Network};
+use toluol::util::{
+    get_dnskeys_cached, query_multiple, query_nameservers, query_single, send_query,
+    validate_answer,
+};
+use toluol::watch::Watch;
 use toluol::QueryMetadata;
-use toluol_proto::{dnssec::RrSet, Message, NonOptRecord, RCode, Record, RecordType};
+use toluol_proto::dnssec::{RrSetValidation, ValidationPolicy, ValidationState};
+use toluol_proto::rdata::DS;
+use toluol_proto::table::{self, RecordOrder, RecordTable};
+#[cfg(feature = "json")]
+use toluol_proto::NonOptRecord;
+use toluol_proto::{Message, Name, RCode, Rdata, Record, RecordType, SizeReport};
 
 mod args;
+mod repl;
 
-use args::Args;
+use args::{Args, Command, RowFormat};
 
 // TODO
 // - better docs (examples!)
@@ -23,59 +64,1771 @@ use args::Args;
 // - add new flag to only print the RDATA of the answer (re-use +short as that is free after implementing above point?)
 // - better README
 // - AXFR support
-// - use resolv-conf (Linux) and ipconfig (Windows) crates to query the system's configured nameservers
+// - use the ipconfig crate to query the system's configured nameservers on Windows (+system)
 
-fn main() -> Result<()> {
-    let bufsize = 4096; // seems reasonable
+fn main() {
+    if let Err(e) = run() {
+        print_error(&e);
+        process::exit(1);
+    }
+}
+
+/// Prints `e` with its causes, and a hint and diagnostics if there are any.
+fn print_error(e: &anyhow::Error) {
+    eprintln!("Error: {:?}", e);
+    let toluol_error = e
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<toluol::Error>());
+    if let Some(hint) = toluol_error.and_then(error_hint) {
+        eprintln!();
+        eprintln!("{}", hint);
+    }
+    if let Some(toluol::Error::Network(e)) = toluol_error {
+        eprintln!();
+        eprintln!("Network diagnostics:");
+        for (description, value) in e.diagnostics() {
+            eprintln!("\t{:<16}{}", format!("{}:", description), value);
+        }
+    }
+}
+
+/// Returns a hint on what to do about `e`, if there is anything the user can do.
+fn error_hint(e: &toluol::Error) -> Option<&'static str> {
+    match e {
+        toluol::Error::Network(_) | toluol::Error::Transport { .. } => Some(
+            "The nameserver could not be reached or did not answer. Check the nameserver's \
+             address and port, or try another connection type (e.g. +tcp).",
+        ),
+        toluol::Error::OversizedAnswer(_) => Some(
+            "The nameserver ignores the advertised UDP payload size. Use +tcp to get the \
+             complete answer.",
+        ),
+        toluol::Error::Parse { .. } => Some(
+            "The data could not be parsed. If the nameserver's answer is malformed, try +tcp or \
+             another nameserver to rule out a broken middlebox.",
+        ),
+        toluol::Error::Validation { .. } => Some(
+            "DNSSEC validation could not be performed. Query without +validate to see the \
+             answer anyway.",
+        ),
+        toluol::Error::Configuration { .. } => {
+            Some("The configuration or input is invalid. Use --help to see the available options.")
+        }
+        _ => None,
+    }
+}
+
+fn run() -> Result<()> {
     let args = Args::parse();
+    if let Command::Interactive(defaults) = &args.command {
+        return repl::run(defaults);
+    }
+    run_args(&args, None)
+}
+
+/// Runs what `args` say. In an interactive session, single queries are sent via `session`, which
+/// keeps connections open and caches the answers.
+fn run_args(args: &Args, session: Option<&Session>) -> Result<()> {
+    let bufsize = args.bufsize;
     let query_metadata: QueryMetadata = args.clone().into();
-    let data = prepare_query(&query_metadata, bufsize)?;
-    let mut nameserver = Nameserver::from_metadata(&query_metadata);
 
-    if args.iterative {
-        do_and_display_iterative_query(&args, &query_metadata)?;
-        return Ok(());
+    if let Command::Decode(msg) = &args.command {
+        // there is no query metadata for a decoded message, so it is always printed verbosely
+        let mut res = Message::parse(&mut Cursor::new(&msg[..]))?;
+        res.sort_records(args.sort);
+        #[cfg(feature = "json")]
+        if args.json {
+            let value = serde_json::to_value(idn_message(&res, args)).unwrap();
+            println!("{}", serde_json::to_string_pretty(&value).unwrap());
+            return Ok(());
+        }
+        let output = res.as_string_with(Some(owo_colors::Stream::Stdout), args.sections);
+        if !output.is_empty() {
+            println!("{}", output);
+        }
+        return Ok(());
+    }
+
+    if let Command::Blocklist(nameservers) = &args.command {
+        do_and_display_blocklist_check(args, &query_metadata, nameservers, bufsize);
+        return Ok(());
+    }
+
+    if let Command::CaaCheck = &args.command {
+        return do_and_display_caa_check(args, &query_metadata, bufsize);
+    }
+
+    if let Command::CdsCheck = &args.command {
+        return do_and_display_cds_check(args, &query_metadata, bufsize);
+    }
+
+    if let Command::Dns64Check = &args.command {
+        return do_and_display_dns64_check(args, &query_metadata, bufsize);
+    }
+
+    if let Command::SplitHorizon = &args.command {
+        return do_and_display_split_horizon(args, &query_metadata, bufsize);
+    }
+
+    if let Command::MailAudit = &args.command {
+        return do_and_display_mail_audit(args, &query_metadata, bufsize);
+    }
+
+    if let Command::MiddleboxCheck = &args.command {
+        return do_and_display_middlebox_check(args, &query_metadata, bufsize);
+    }
+
+    if let Command::Enum(number) = &args.command {
+        return do_and_display_enum(args, &query_metadata, number, bufsize);
+    }
+
+    if let Command::PgpKey(email) = &args.command {
+        return do_and_display_pgpkey(args, &query_metadata, email, bufsize);
+    }
+
+    if let Command::PtrSweep(networks) = &args.command {
+        return do_and_display_ptr_sweep(args, &query_metadata, networks, bufsize);
+    }
+
+    if let Command::Probe(spec) = &args.command {
+        return do_and_display_probe(args, &query_metadata, spec, bufsize);
+    }
+
+    if let Command::Raw(template) = &args.command {
+        return do_and_display_raw(args, &query_metadata, template, bufsize);
+    }
+
+    if let Command::Survey(types) = &args.command {
+        return do_and_display_survey(args, &query_metadata, types, bufsize);
+    }
+
+    if let Command::Watch = &args.command {
+        return do_and_display_watch(args, &query_metadata, bufsize);
+    }
+
+    #[cfg(feature = "json")]
+    if let Command::ServeApi(address) = &args.command {
+        return serve_api(args, &query_metadata, *address, bufsize);
+    }
+
+    let server_stats = match &args.stats_file {
+        Some(path) => Arc::new(ServerStats::load_or_default(path)?),
+        None => Arc::default(),
+    };
+
+    // DNSKEYs are fetched once per zone, even for several record types or levels of a trace
+    let dnskey_cache = session.map_or_else(Arc::default, |s| s.dnskey_cache().clone());
+
+    if args.iterative {
+        let res =
+            do_and_display_iterative_query(args, &query_metadata, &server_stats, &dnskey_cache);
+        save_server_stats(args, &server_stats)?;
+        return res;
+    }
+
+    let resolver = if args.system {
+        let config = ResolverConfig::system()?;
+        let mut resolver = Resolver::new(config, query_metadata.clone(), bufsize)
+            .with_dnskey_cache(dnskey_cache.clone());
+        if args.stats_file.is_some() {
+            resolver = resolver.with_server_stats(server_stats.clone());
+        }
+        if !args.hosts_files.is_empty() {
+            let mut hosts = Hosts::default();
+            for path in &args.hosts_files {
+                hosts.add_file(path)?;
+            }
+            resolver = resolver.with_hosts(hosts);
+        }
+        Some(resolver)
+    } else {
+        None
+    };
+
+    if args.qtypes.len() > 1 {
+        do_and_display_multiple_queries(
+            args,
+            &query_metadata,
+            resolver.as_ref(),
+            &dnskey_cache,
+            bufsize,
+        )?;
+        save_server_stats(args, &server_stats)?;
+        return Ok(());
+    }
+
+    let lookup = match &resolver {
+        Some(resolver) => {
+            let lookup = resolver.lookup(&query_metadata.name, query_metadata.qtype);
+            save_server_stats(args, &server_stats)?;
+            lookup?
+        }
+        None => match session {
+            Some(session) => {
+                let (answer, age) = session.query(&query_metadata, bufsize)?;
+                if let Some(age) = age {
+                    eprintln!("(cached answer, received {} s ago)", age.as_secs());
+                }
+                Lookup::Nameserver(answer)
+            }
+            None => Lookup::Nameserver(query_single(&query_metadata, bufsize)?),
+        },
+    };
+    if let Some(format) = args.rows {
+        display_rows(&lookup, format);
+        return Ok(());
+    }
+    match lookup {
+        Lookup::Nameserver((nameserver, res, bytes_recvd, timing)) => {
+            let validations = if args.validate_dnssec {
+                Some(fetch_dnskeys_and_validate(
+                    &res,
+                    &query_metadata,
+                    &nameserver,
+                    &args.validation_policy,
+                    &dnskey_cache,
+                )?)
+            } else {
+                None
+            };
+            display_result(
+                &res,
+                args,
+                &nameserver,
+                bytes_recvd,
+                &timing,
+                validations.as_deref(),
+            );
+
+            #[cfg(all(feature = "http", feature = "json"))]
+            if args.rdap {
+                display_rdap(&query_metadata.name, &answer_addresses(&res));
+            }
+        }
+        // there is nothing to validate for records from a hosts file
+        Lookup::Hosts { path, message } => {
+            display_hosts_result(&message, args, &path);
+
+            #[cfg(all(feature = "http", feature = "json"))]
+            if args.rdap {
+                display_rdap(&query_metadata.name, &answer_addresses(&message));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn do_and_display_multiple_queries(
+    args: &Args,
+    metadata: &QueryMetadata,
+    resolver: Option<&Resolver>,
+    dnskey_cache: &DnskeyCache,
+    bufsize: u16,
+) -> Result<()> {
+    let output = owo_colors::Stream::Stdout;
+    let headline_style = owo_colors::style().bold().blue();
+    let err_style = Style::new().bold().red();
+    let answers = match resolver {
+        Some(resolver) => resolver.lookup_multiple(&metadata.name, &args.qtypes),
+        None => query_multiple(metadata, &args.qtypes, bufsize)
+            .into_iter()
+            .map(|(qtype, answer)| (qtype, answer.map(Lookup::Nameserver)))
+            .collect(),
+    };
+
+    if let Some(format) = args.rows {
+        for (qtype, answer) in answers {
+            match answer {
+                Ok(lookup) => display_rows(&lookup, format),
+                Err(e) => eprintln!("{} query failed: {:#}", qtype, e),
+            }
+        }
+        return Ok(());
+    }
+
+    #[cfg(feature = "json")]
+    if args.json {
+        let mut results = serde_json::Map::new();
+        for (qtype, answer) in answers {
+            let result = match answer {
+                Ok(Lookup::Nameserver((nameserver, res, _, timing))) => {
+                    let value = json_value(&res, args, &nameserver, &timing, None);
+                    run_hook(&res, &value, args);
+                    value
+                }
+                Ok(Lookup::Hosts { path, message }) => {
+                    let value = hosts_json_value(&message, args, &path);
+                    run_hook(&message, &value, args);
+                    value
+                }
+                Err(e) => match &e {
+                    toluol::Error::Network(network_error) => serde_json::json!({
+                        "error": format!("{:#}", e),
+                        "diagnostics": network_error
+                            .diagnostics()
+                            .into_iter()
+                            .map(|(description, value)| {
+                                (description.to_lowercase().replace(' ', "_"), value.into())
+                            })
+                            .collect::<serde_json::Map<_, _>>(),
+                    }),
+                    _ => serde_json::json!({ "error": format!("{:#}", e) }),
+                },
+            };
+            results.insert(qtype.to_string(), result);
+        }
+        println!("{}", serde_json::to_string_pretty(&results).unwrap());
+        return Ok(());
+    }
+
+    #[cfg(all(feature = "http", feature = "json"))]
+    let mut addresses = Vec::new();
+
+    for (i, (qtype, answer)) in answers.into_iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!(
+            "{}",
+            format!("{} records:", qtype)
+                .if_supports_color(output, |text| text.style(headline_style))
+        );
+        match answer {
+            Ok(Lookup::Nameserver((nameserver, res, bytes_recvd, timing))) => {
+                let validations = if args.validate_dnssec {
+                    let mut metadata = metadata.clone();
+                    metadata.qtype = qtype;
+                    Some(fetch_dnskeys_and_validate(
+                        &res,
+                        &metadata,
+                        &nameserver,
+                        &args.validation_policy,
+                        dnskey_cache,
+                    )?)
+                } else {
+                    None
+                };
+                display_result(
+                    &res,
+                    args,
+                    &nameserver,
+                    bytes_recvd,
+                    &timing,
+                    validations.as_deref(),
+                );
+                #[cfg(all(feature = "http", feature = "json"))]
+                addresses.extend(answer_addresses(&res));
+            }
+            Ok(Lookup::Hosts { path, message }) => {
+                display_hosts_result(&message, args, &path);
+                #[cfg(all(feature = "http", feature = "json"))]
+                addresses.extend(answer_addresses(&message));
+            }
+            Err(e) => {
+                let err = format!("Query failed: {:#}", e);
+                println!("{}", err.if_supports_color(output, |s| s.style(err_style)));
+                if let (true, toluol::Error::Network(e)) = (args.verbose, &e) {
+                    for (description, value) in e.diagnostics() {
+                        println!("\t{:<16}{}", format!("{}:", description), value);
+                    }
+                }
+            }
+        }
+    }
+
+    #[cfg(all(feature = "http", feature = "json"))]
+    if args.rdap {
+        display_rdap(&metadata.name, &addresses);
+    }
+
+    Ok(())
+}
+
+/// Sends the query to each of `nameservers` (or the known filtering resolvers if it is empty) and
+/// displays which of them block the queried name.
+fn do_and_display_blocklist_check(
+    args: &Args,
+    metadata: &QueryMetadata,
+    nameservers: &[String],
+    bufsize: u16,
+) {
+    let output = owo_colors::Stream::Stdout;
+
+    let (labels, nameservers): (Vec<_>, Vec<_>) = if nameservers.is_empty() {
+        FILTERING_RESOLVERS
+            .iter()
+            .map(|(label, ns)| (label.to_string(), ns.to_string()))
+            .unzip()
+    } else {
+        nameservers
+            .iter()
+            .map(|ns| (ns.clone(), ns.clone()))
+            .unzip()
+    };
+    let answers = query_nameservers(metadata, &nameservers, bufsize);
+    let results: Vec<_> = zip(labels, answers)
+        .map(|(label, (ns, answer))| {
+            let verdict = answer.map(|(_, res, _, _)| Verdict::classify(&res));
+            (label, ns, verdict)
+        })
+        .collect();
+
+    #[cfg(feature = "json")]
+    if args.json {
+        let results: Vec<_> = results
+            .iter()
+            .map(|(label, ns, verdict)| match verdict {
+                Ok(verdict) => serde_json::json!({
+                    "resolver": label,
+                    "nameserver": ns,
+                    "verdict": verdict.to_string(),
+                    "blocked": verdict.is_blocked(),
+                }),
+                Err(e) => serde_json::json!({
+                    "resolver": label,
+                    "nameserver": ns,
+                    "error": format!("{:#}", e),
+                }),
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&results).unwrap());
+        return;
+    }
+
+    let label_len = results
+        .iter()
+        .map(|(label, _, _)| label.len())
+        .max()
+        .unwrap_or(0);
+    let mut blocked_by = Vec::new();
+    for (label, _, verdict) in &results {
+        let (text, style) = match verdict {
+            Ok(verdict) if verdict.is_blocked() => {
+                blocked_by.push(label.as_str());
+                (verdict.to_string(), Style::new().red())
+            }
+            Ok(verdict) => (verdict.to_string(), Style::new().green()),
+            Err(e) => (format!("query failed: {:#}", e), Style::new().yellow()),
+        };
+        println!(
+            "{:<width$}  {}",
+            label,
+            text.if_supports_color(output, |s| s.style(style)),
+            width = label_len
+        );
+    }
+
+    if args.print_meta {
+        println!();
+        let answered = results.iter().filter(|(_, _, v)| v.is_ok()).count();
+        if blocked_by.is_empty() {
+            println!(
+                "{} is not blocked by any of the {} resolvers that answered.",
+                metadata.name, answered
+            );
+        } else {
+            println!(
+                "{} is blocked by {} of the {} resolvers that answered: {}",
+                metadata.name,
+                blocked_by.len(),
+                answered,
+                blocked_by.join(", ")
+            );
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "json"), allow(unused_variables))]
+fn do_and_display_caa_check(args: &Args, metadata: &QueryMetadata, bufsize: u16) -> Result<()> {
+    let output = owo_colors::Stream::Stdout;
+    let policy = find_policy(&metadata.name, metadata, bufsize)?;
+
+    #[cfg(feature = "json")]
+    if args.json {
+        let authorization = |wildcard| match policy.authorization(wildcard) {
+            Authorization::AnyCa => serde_json::json!("any"),
+            Authorization::NoCa => serde_json::json!([]),
+            Authorization::Cas(cas) => {
+                serde_json::json!(cas.iter().map(|ca| ca.to_string()).collect::<Vec<_>>())
+            }
+        };
+        let value = serde_json::json!({
+            "name": policy.name.to_string(),
+            "relevant_domain": policy.relevant_domain.as_ref().map(|d| d.to_string()),
+            "records": policy.records.iter().map(|caa| caa.to_string()).collect::<Vec<_>>(),
+            "issue": authorization(false),
+            "issuewild": authorization(true),
+            "iodef": policy.iodef_urls().iter().map(|url| url.to_string()).collect::<Vec<_>>(),
+        });
+        println!("{}", serde_json::to_string_pretty(&value).unwrap());
+        return Ok(());
+    }
+
+    match &policy.relevant_domain {
+        Some(domain) => {
+            println!("CAA records of {}:", domain);
+            for caa in &policy.records {
+                println!("\t{}", caa);
+            }
+        }
+        None => println!(
+            "Neither {} nor any of its parents have CAA records.",
+            policy.name
+        ),
+    }
+    println!();
+
+    let style = |authorization: &Authorization| match authorization {
+        Authorization::AnyCa => Style::new().yellow(),
+        Authorization::NoCa => Style::new().red(),
+        Authorization::Cas(_) => Style::new().green(),
+    };
+    let issue = policy.authorization(false);
+    let issuewild = policy.authorization(true);
+    println!(
+        "Certificates for {} may be issued by: {}",
+        policy.name,
+        issue
+            .to_string()
+            .if_supports_color(output, |s| s.style(style(&issue)))
+    );
+    println!(
+        "Wildcard certificates for *.{} may be issued by: {}",
+        policy.name,
+        issuewild
+            .to_string()
+            .if_supports_color(output, |s| s.style(style(&issuewild)))
+    );
+    for caa in policy.unknown_critical() {
+        let warning = format!(
+            "Warning: the unknown property {} is marked as critical, so no CA may issue.",
+            caa.tag()
+        );
+        println!("{}", warning.if_supports_color(output, |s| s.yellow()));
+    }
+    let iodef_urls = policy.iodef_urls();
+    if !iodef_urls.is_empty() {
+        let urls: Vec<_> = iodef_urls.iter().map(|url| url.to_string()).collect();
+        println!("Violations may be reported to: {}", urls.join(", "));
+    }
+
+    Ok(())
+}
+
+/// Compares the `A` and `AAAA` records of the queried name and displays the NAT64 prefixes the
+/// nameserver seems to synthesize `AAAA` records with.
+#[cfg_attr(not(feature = "json"), allow(unused_variables))]
+fn do_and_display_dns64_check(args: &Args, metadata: &QueryMetadata, bufsize: u16) -> Result<()> {
+    let output = owo_colors::Stream::Stdout;
+    let check = dns64::check(metadata, bufsize)?;
+
+    #[cfg(feature = "json")]
+    if args.json {
+        let value = serde_json::json!({
+            "name": check.name.to_string(),
+            "nameserver": metadata.nameserver,
+            "a": check.a.iter().map(|ip| ip.to_string()).collect::<Vec<_>>(),
+            "aaaa": check.aaaa.iter().map(|ip| ip.to_string()).collect::<Vec<_>>(),
+            "prefixes": check.prefixes.iter().map(|p| p.to_string()).collect::<Vec<_>>(),
+        });
+        println!("{}", serde_json::to_string_pretty(&value).unwrap());
+        return Ok(());
+    }
+
+    println!("A records of {}:", check.name);
+    for ip in &check.a {
+        println!("\t{}", ip);
+    }
+    println!("AAAA records of {}:", check.name);
+    for ip in &check.aaaa {
+        println!("\t{}", ip);
+    }
+    println!();
+
+    if check.prefixes.is_empty() {
+        let text = if check.a.is_empty() {
+            format!(
+                "{} has no A records, so DNS64 cannot be detected.",
+                check.name
+            )
+        } else {
+            format!(
+                "{} does not seem to synthesize AAAA records (no DNS64).",
+                metadata.nameserver
+            )
+        };
+        println!("{}", text.if_supports_color(output, |s| s.yellow()));
+    } else {
+        let prefixes: Vec<_> = check.prefixes.iter().map(|p| p.to_string()).collect();
+        println!(
+            "{} synthesizes AAAA records with the NAT64 prefix {}",
+            metadata.nameserver,
+            prefixes.join(", ").if_supports_color(output, |s| s.green())
+        );
+        if check.prefixes.iter().any(|p| *p != Nat64Prefix::WELL_KNOWN) {
+            println!(
+                "Pass the prefix with --dns64-prefix to recognize synthesized AAAA records in other queries."
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Probes the queried name for split-horizon or geo-dependent answers and displays which probes
+/// got which answers.
+#[cfg_attr(not(feature = "json"), allow(unused_variables))]
+fn do_and_display_split_horizon(args: &Args, metadata: &QueryMetadata, bufsize: u16) -> Result<()> {
+    let output = owo_colors::Stream::Stdout;
+    let headline_style = owo_colors::style().bold().blue();
+    let subnets = if args.subnets.is_empty() {
+        split::default_subnets()
+    } else {
+        args.subnets.clone()
+    };
+    let report = split::probe(metadata, &subnets, bufsize)?;
+    let answers = report.distinct_answers();
+
+    #[cfg(feature = "json")]
+    if args.json {
+        let probes: Vec<_> = report
+            .results
+            .iter()
+            .enumerate()
+            .map(|(i, result)| {
+                // the index into "answers"
+                let answer = answers.iter().position(|(_, indices)| indices.contains(&i));
+                serde_json::json!({
+                    "probe": result.probe.to_string(),
+                    "answer": answer,
+                    "ecs_scope": result.client_subnet.map(|subnet| subnet.scope_prefix_len),
+                    "error": result.answer.as_ref().err().map(|e| format!("{:#}", e)),
+                })
+            })
+            .collect();
+        let value = serde_json::json!({
+            "name": report.name.to_string(),
+            "type": report.qtype.to_string(),
+            "zone": report.zone.as_ref().map(|zone| zone.to_string()),
+            "differs": report.differs(),
+            "answers": answers.iter().map(|(answer, _)| serde_json::json!({
+                "rcode": answer.rcode.to_string(),
+                "records": answer.records.iter()
+                    .map(|rdata| format!("{} {}", rdata.rtype(), rdata))
+                    .collect::<Vec<_>>(),
+            })).collect::<Vec<_>>(),
+            "probes": probes,
+        });
+        println!("{}", serde_json::to_string_pretty(&value).unwrap());
+        return Ok(());
+    }
+
+    for (i, (answer, indices)) in answers.iter().enumerate() {
+        let headline = format!(
+            "Answer {} ({}, {} of {} probes):",
+            i + 1,
+            answer.rcode,
+            indices.len(),
+            report.results.len()
+        );
+        println!(
+            "{}",
+            headline.if_supports_color(output, |s| s.style(headline_style))
+        );
+        if answer.records.is_empty() {
+            println!("\t<empty>");
+        }
+        for rdata in &answer.records {
+            println!("\t{} {}", rdata.rtype(), idn_rdata(rdata, args));
+        }
+        println!("  returned by:");
+        for &i in indices {
+            let result = &report.results[i];
+            match result.client_subnet {
+                Some(subnet) if result.probe.client_subnet.is_some() => {
+                    println!("\t{} (scope /{})", result.probe, subnet.scope_prefix_len)
+                }
+                _ => println!("\t{}", result.probe),
+            }
+        }
+        println!();
+    }
+
+    let failed: Vec<_> = report
+        .results
+        .iter()
+        .filter_map(|result| result.answer.as_ref().err().map(|e| (&result.probe, e)))
+        .collect();
+    if !failed.is_empty() {
+        println!(
+            "{}",
+            "Failed probes:".if_supports_color(output, |s| s.style(headline_style))
+        );
+        for (probe, e) in failed {
+            let text = format!("{}: {:#}", probe, e);
+            println!("\t{}", text.if_supports_color(output, |s| s.yellow()));
+        }
+        println!();
+    }
+
+    if report.zone.is_none() {
+        let text = format!(
+            "Could not determine the zone of {}, so no authoritative nameservers were probed.",
+            report.name
+        );
+        println!("{}", text.if_supports_color(output, |s| s.yellow()));
+    }
+    if report.differs() {
+        let text = format!(
+            "The answers for {} {} differ, so it seems to have split-horizon or geo-dependent answers.",
+            report.name, report.qtype
+        );
+        println!("{}", text.if_supports_color(output, |s| s.red()));
+    } else if answers.is_empty() {
+        println!(
+            "{}",
+            "All probes failed.".if_supports_color(output, |s| s.red())
+        );
+    } else {
+        let text = format!(
+            "All probes got the same answer for {} {}.",
+            report.name, report.qtype
+        );
+        println!("{}", text.if_supports_color(output, |s| s.green()));
+    }
+
+    Ok(())
+}
+
+/// Checks the `CDS` and `CDNSKEY` records of the queried zone and displays what an automated
+/// parent would do with its `DS` records.
+fn do_and_display_cds_check(args: &Args, metadata: &QueryMetadata, bufsize: u16) -> Result<()> {
+    let output = owo_colors::Stream::Stdout;
+    let headline_style = owo_colors::style().bold().blue();
+    let report = cds::check(metadata, bufsize, &args.validation_policy)?;
+    let to_strings = |ds: &[DS]| ds.iter().map(|ds| ds.to_string()).collect::<Vec<_>>();
+
+    #[cfg(feature = "json")]
+    if args.json {
+        let servers: Vec<_> = report
+            .servers
+            .iter()
+            .map(|(nameserver, records)| match records {
+                Ok(records) => serde_json::json!({
+                    "nameserver": nameserver.to_string(),
+                    "cds": records.cds().iter().map(|ds| ds.to_string()).collect::<Vec<_>>(),
+                    "cdnskey": records.cdnskey().iter().map(|key| key.to_string()).collect::<Vec<_>>(),
+                }),
+                Err(e) => serde_json::json!({
+                    "nameserver": nameserver.to_string(),
+                    "error": format!("{:#}", e),
+                }),
+            })
+            .collect();
+        let action = match &report.action {
+            ParentAction::None => serde_json::json!({ "type": "none" }),
+            ParentAction::Unchanged => serde_json::json!({ "type": "unchanged" }),
+            ParentAction::Add(add) => serde_json::json!({ "type": "add", "add": to_strings(add) }),
+            ParentAction::Roll { add, remove } => serde_json::json!({
+                "type": "roll",
+                "add": to_strings(add),
+                "remove": to_strings(remove),
+            }),
+            ParentAction::Delete => serde_json::json!({ "type": "delete" }),
+        };
+        let value = serde_json::json!({
+            "zone": report.zone.to_string(),
+            "ds": to_strings(&report.ds),
+            "servers": servers,
+            "action": action,
+            "accepted": report.problems.is_empty(),
+            "problems": report.problems.iter().map(|p| p.to_string()).collect::<Vec<_>>(),
+        });
+        println!("{}", serde_json::to_string_pretty(&value).unwrap());
+        return Ok(());
+    }
+
+    let headline = format!("DS records of {} at the parent:", report.zone);
+    println!(
+        "{}",
+        headline.if_supports_color(output, |s| s.style(headline_style))
+    );
+    if report.ds.is_empty() {
+        println!("\t<none>");
+    }
+    for ds in &report.ds {
+        println!("\t{}", ds);
+    }
+    println!();
+
+    println!(
+        "{}",
+        "CDS and CDNSKEY records per nameserver:"
+            .if_supports_color(output, |s| s.style(headline_style))
+    );
+    for (nameserver, records) in &report.servers {
+        match records {
+            Ok(records) => {
+                println!("  {}:", idn_name(nameserver, args));
+                let (cds, cdnskey) = (records.cds(), records.cdnskey());
+                if cds.is_empty() && cdnskey.is_empty() {
+                    println!("\t<none>");
+                }
+                for ds in cds {
+                    println!("\tCDS {}", ds);
+                }
+                for key in cdnskey {
+                    println!("\tCDNSKEY {} (key tag {})", key, key.key_tag());
+                }
+            }
+            Err(e) => {
+                let text = format!("{}: {:#}", idn_name(nameserver, args), e);
+                println!("  {}", text.if_supports_color(output, |s| s.yellow()));
+            }
+        }
+    }
+    println!();
+
+    if !report.problems.is_empty() {
+        println!(
+            "{}",
+            "Problems:".if_supports_color(output, |s| s.style(headline_style))
+        );
+        for problem in &report.problems {
+            println!("\t{}", problem.if_supports_color(output, |s| s.yellow()));
+        }
+        println!();
+    }
+
+    let (text, style) = match &report.action {
+        ParentAction::None => (
+            format!("{} publishes no CDS or CDNSKEY records.", report.zone),
+            Style::new(),
+        ),
+        ParentAction::Unchanged => (
+            "The DS records already match the CDS/CDNSKEY records.".to_string(),
+            Style::new().green(),
+        ),
+        ParentAction::Add(add) => (
+            format!(
+                "The delegation is insecure; the parent would add (usually after further checks):\n\t{}",
+                to_strings(add).join("\n\t")
+            ),
+            Style::new().yellow(),
+        ),
+        ParentAction::Roll { add, remove } => {
+            let mut text = "The parent would update the DS records:".to_string();
+            for ds in add {
+                text.push_str(&format!("\n\tadd    {}", ds));
+            }
+            for ds in remove {
+                text.push_str(&format!("\n\tremove {}", ds));
+            }
+            (text, Style::new().yellow())
+        }
+        ParentAction::Delete => (
+            "The parent would delete all DS records, making the delegation insecure.".to_string(),
+            Style::new().yellow(),
+        ),
+    };
+    if report.problems.is_empty() || report.action == ParentAction::None {
+        println!("{}", text.if_supports_color(output, |s| s.style(style)));
+    } else {
+        println!(
+            "{}",
+            "Because of the problems, an automated parent would ignore the CDS/CDNSKEY records. Otherwise:"
+                .if_supports_color(output, |s| s.red())
+        );
+        println!("{}", text);
+    }
+
+    Ok(())
+}
+
+#[cfg_attr(not(feature = "json"), allow(unused_variables))]
+fn do_and_display_mail_audit(args: &Args, metadata: &QueryMetadata, bufsize: u16) -> Result<()> {
+    let output = owo_colors::Stream::Stdout;
+    let headline_style = owo_colors::style().bold().blue();
+    let report = audit(&metadata.name, metadata, bufsize)?;
+
+    #[cfg(feature = "json")]
+    if args.json {
+        let findings: Vec<_> = report
+            .findings
+            .iter()
+            .map(|finding| {
+                serde_json::json!({
+                    "severity": finding.severity.to_string(),
+                    "message": finding.message,
+                })
+            })
+            .collect();
+        let value = serde_json::json!({
+            "domain": report.domain.to_string(),
+            "mx": report.mx.iter().map(|mx| mx.to_string()).collect::<Vec<_>>(),
+            "spf": report.spf,
+            "dmarc": report.dmarc,
+            "mta_sts": report.mta_sts,
+            "mta_sts_policy": report.mta_sts_policy,
+            "tlsrpt": report.tlsrpt,
+            "findings": findings,
+        });
+        println!("{}", serde_json::to_string_pretty(&value).unwrap());
+        return Ok(());
+    }
+
+    let mx: Vec<_> = report.mx.iter().map(|mx| mx.to_string()).collect();
+    let sections = [
+        ("MX", &mx),
+        ("SPF", &report.spf),
+        ("DMARC", &report.dmarc),
+        ("MTA-STS", &report.mta_sts),
+        ("TLSRPT", &report.tlsrpt),
+    ];
+    for (title, records) in sections {
+        println!(
+            "{}",
+            format!("{}:", title).if_supports_color(output, |text| text.style(headline_style))
+        );
+        if records.is_empty() {
+            println!("\t<none>");
+        }
+        for record in records {
+            println!("\t{}", record);
+        }
+    }
+    if let Some(policy) = &report.mta_sts_policy {
+        println!(
+            "{}",
+            "MTA-STS policy:".if_supports_color(output, |text| text.style(headline_style))
+        );
+        for line in policy.lines() {
+            println!("\t{}", line);
+        }
+    }
+
+    println!();
+    if report.findings.is_empty() {
+        println!(
+            "{}",
+            "No problems found.".if_supports_color(output, |s| s.green())
+        );
+    }
+    for finding in &report.findings {
+        let style = match finding.severity {
+            Severity::Error => Style::new().red(),
+            Severity::Warning => Style::new().yellow(),
+            Severity::Info => Style::new().green(),
+        };
+        println!(
+            "{}: {}",
+            finding
+                .severity
+                .to_string()
+                .if_supports_color(output, |s| s.style(style)),
+            finding.message
+        );
+    }
+
+    Ok(())
+}
+
+/// Resolves the E.164 `number` to URIs with ENUM, i.e. with DDDS starting at `metadata.name`.
+#[cfg_attr(not(feature = "json"), allow(unused_variables))]
+fn do_and_display_enum(
+    args: &Args,
+    metadata: &QueryMetadata,
+    number: &str,
+    bufsize: u16,
+) -> Result<()> {
+    let output = owo_colors::Stream::Stdout;
+    // ENUM services are of the form "E2U+<type>[:<subtype>]" (RFC 6116, Section 3.4.3)
+    let is_enum_service = |services: &str| {
+        services
+            .get(..4)
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case("E2U+"))
+    };
+    let resolutions = ddds::resolve(number, &metadata.name, is_enum_service, metadata, bufsize)?;
+
+    #[cfg(feature = "json")]
+    if args.json {
+        let results: Vec<_> = resolutions
+            .iter()
+            .map(|resolution| {
+                let uri = match &resolution.terminal {
+                    Terminal::Uri(uri) => Some(uri),
+                    _ => None,
+                };
+                serde_json::json!({
+                    "order": resolution.rule.order,
+                    "preference": resolution.rule.preference,
+                    "services": resolution.rule.services,
+                    "uri": uri,
+                    "result": resolution.terminal.to_string(),
+                })
+            })
+            .collect();
+        let value = serde_json::json!({
+            "number": number,
+            "domain": metadata.name.to_string(),
+            "results": results,
+        });
+        println!("{}", serde_json::to_string_pretty(&value).unwrap());
+        return Ok(());
+    }
+
+    if resolutions.is_empty() {
+        println!("No ENUM records found for {} ({}).", number, metadata.name);
+        return Ok(());
+    }
+    println!("ENUM records for {} ({}):", number, metadata.name);
+    let services_len = resolutions
+        .iter()
+        .map(|resolution| resolution.rule.services.len())
+        .max()
+        .unwrap_or_default();
+    for resolution in &resolutions {
+        println!(
+            "\t{:>5} {:>5}  {}  {}",
+            resolution.rule.order,
+            resolution.rule.preference,
+            format!("{:<width$}", resolution.rule.services, width = services_len)
+                .if_supports_color(output, |s| s.purple()),
+            resolution.terminal,
+        );
+    }
+
+    Ok(())
+}
+
+/// Fetches the `OPENPGPKEY` records of an email address and prints the keys ASCII-armored.
+#[cfg_attr(not(feature = "json"), allow(unused_variables))]
+fn do_and_display_pgpkey(
+    args: &Args,
+    metadata: &QueryMetadata,
+    email: &str,
+    bufsize: u16,
+) -> Result<()> {
+    let output = owo_colors::Stream::Stdout;
+    let (_, res, _, _) = query_single(metadata, bufsize)?;
+    let rcode = match res.opt() {
+        Some(opt) => opt.rcode,
+        None => res.header.rcode,
+    };
+    let rcode = rcode.unwrap_or(RCode::NOERROR);
+    if ![RCode::NOERROR, RCode::NXDOMAIN].contains(&rcode) {
+        bail!(
+            "Looking up the OPENPGPKEY records for {} failed with {}.",
+            email,
+            rcode
+        );
+    }
+    let keys: Vec<_> = res
+        .answers
+        .iter()
+        .filter_map(Record::as_nonopt)
+        .filter_map(|record| record.rdata().as_openpgpkey())
+        .collect();
+    // RFC 7929 requires the records to be validated with DNSSEC, which only the nameserver did if
+    // it set the AD flag
+    let authenticated = res.header.flags.ad;
+
+    #[cfg(feature = "json")]
+    if args.json {
+        let value = serde_json::json!({
+            "email": email,
+            "domain": metadata.name.to_string(),
+            "authenticated": authenticated,
+            "keys": keys.iter().map(|key| key.to_armored()).collect::<Vec<_>>(),
+        });
+        println!("{}", serde_json::to_string_pretty(&value).unwrap());
+        return Ok(());
+    }
+
+    if keys.is_empty() {
+        println!(
+            "No OPENPGPKEY records found for {} ({}).",
+            email, metadata.name
+        );
+        return Ok(());
+    }
+    for key in keys {
+        print!("{}", key.to_armored());
+    }
+    if !authenticated {
+        let text = format!(
+            "Warning: the nameserver did not authenticate the records with DNSSEC, so the keys for {} may be forged.",
+            email
+        );
+        println!("{}", text.if_supports_color(output, |s| s.yellow()));
+    }
+
+    Ok(())
+}
+
+/// Probes the nameserver for middlebox interference and displays the result of each check.
+#[cfg_attr(not(feature = "json"), allow(unused_variables))]
+fn do_and_display_middlebox_check(
+    args: &Args,
+    metadata: &QueryMetadata,
+    bufsize: u16,
+) -> Result<()> {
+    let output = owo_colors::Stream::Stdout;
+    let report = middlebox::detect(metadata, bufsize)?;
+
+    #[cfg(feature = "json")]
+    if args.json {
+        let checks: Vec<_> = report
+            .results
+            .iter()
+            .map(|result| {
+                let (outcome, interference) = match result.outcome {
+                    Outcome::Passed => ("passed", None),
+                    Outcome::Detected(interference) => ("detected", Some(interference.to_string())),
+                    Outcome::Inconclusive => ("inconclusive", None),
+                };
+                serde_json::json!({
+                    "check": result.check.to_string(),
+                    "outcome": outcome,
+                    "interference": interference,
+                    "detail": result.detail,
+                })
+            })
+            .collect();
+        let value = serde_json::json!({
+            "nameserver": report.nameserver,
+            "checks": checks,
+        });
+        println!("{}", serde_json::to_string_pretty(&value).unwrap());
+        return Ok(());
+    }
+
+    let width = report
+        .results
+        .iter()
+        .map(|result| result.check.to_string().len())
+        .max()
+        .unwrap_or(0);
+    for result in &report.results {
+        let (verdict, style) = match result.outcome {
+            Outcome::Passed => ("ok".to_string(), Style::new().green()),
+            Outcome::Detected(interference) => (interference.to_string(), Style::new().red()),
+            Outcome::Inconclusive => ("inconclusive".to_string(), Style::new().yellow()),
+        };
+        println!(
+            "{:<width$}  {} ({})",
+            result.check.to_string(),
+            verdict.if_supports_color(output, |s| s.style(style)),
+            result.detail,
+            width = width
+        );
+    }
+
+    if args.print_meta {
+        println!();
+        let interference = report.interference();
+        if interference.is_empty() {
+            println!(
+                "No signs of middlebox interference on the path to {}.",
+                report.nameserver
+            );
+        } else {
+            println!(
+                "Likely middlebox interference on the path to {}: {}.",
+                report.nameserver,
+                interference
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Sends the message described by `template` and displays the response, which is printed as hex if
+/// it cannot be parsed.
+fn do_and_display_raw(
+    args: &Args,
+    metadata: &QueryMetadata,
+    template: &MessageTemplate,
+    bufsize: u16,
+) -> Result<()> {
+    let output = owo_colors::Stream::Stdout;
+    let query = template.encode();
+    let mut nameserver = Nameserver::from_metadata(metadata)?;
+    let (res, len, timing) = send_query(metadata, bufsize, &mut nameserver, &query)?;
+    let res = &res[..len as usize];
+    let parsed = Message::parse(&mut Cursor::new(res));
+
+    #[cfg(feature = "json")]
+    if args.json {
+        let mut value = serde_json::json!({
+            "query": HEXLOWER.encode(&query),
+            "response": HEXLOWER.encode(res),
+        });
+        match parsed {
+            Ok(mut msg) => {
+                msg.sort_records(args.sort);
+                value["parsed"] = serde_json::to_value(idn_message(&msg, args)).unwrap();
+            }
+            Err(e) => value["parse_error"] = format!("{:#}", e).into(),
+        }
+        println!("{}", serde_json::to_string_pretty(&value).unwrap());
+        return Ok(());
+    }
+
+    match parsed {
+        Ok(mut msg) => {
+            msg.sort_records(args.sort);
+            let text = msg.as_string_with(Some(output), args.sections);
+            if !text.is_empty() {
+                println!("{}", text);
+            }
+        }
+        Err(e) => {
+            let text = format!("Could not parse the response: {:#}", e);
+            println!("{}", text.if_supports_color(output, |s| s.yellow()));
+            println!("{}", HEXLOWER.encode(res));
+        }
+    }
+
+    if args.print_meta {
+        println!();
+        println!(
+            "Sent {} bytes and received {} bytes from {} in {} ms.",
+            query.len(),
+            len,
+            nameserver,
+            timing.elapsed.as_millis()
+        );
+    }
+
+    Ok(())
+}
+
+/// Looks up the `PTR` records of the addresses in `networks` and displays the host names found.
+#[cfg_attr(not(feature = "json"), allow(unused_variables))]
+fn do_and_display_ptr_sweep(
+    args: &Args,
+    metadata: &QueryMetadata,
+    networks: &[Network],
+    bufsize: u16,
+) -> Result<()> {
+    let output = owo_colors::Stream::Stdout;
+    let (addresses, sampled) = sweep::select_addresses(networks, args.sweep_sample);
+    let resolver = Resolver::new(nameserver_config(metadata), metadata.clone(), bufsize)
+        .with_rate_limit(RateLimit {
+            global: args.sweep_rate.map(Rate::per_second),
+            ..Default::default()
+        });
+    let results = sweep::reverse_lookup(&resolver, &addresses, args.sweep_confirm);
+
+    #[cfg(feature = "json")]
+    if args.json {
+        let results: Vec<_> = results
+            .iter()
+            .map(|result| match &result.names {
+                Ok(names) => {
+                    let mut value = serde_json::json!({
+                        "address": result.address.to_string(),
+                        "names": names.iter().map(|host| host.name.to_string()).collect::<Vec<_>>(),
+                    });
+                    if args.sweep_confirm {
+                        value["confirmed"] = names
+                            .iter()
+                            .filter(|host| host.confirmed == Some(true))
+                            .map(|host| host.name.to_string())
+                            .collect::<Vec<_>>()
+                            .into();
+                    }
+                    value
+                }
+                Err(e) => serde_json::json!({
+                    "address": result.address.to_string(),
+                    "error": format!("{:#}", e),
+                }),
+            })
+            .collect();
+        let value = serde_json::json!({
+            "networks": networks.iter().map(|network| network.to_string()).collect::<Vec<_>>(),
+            "sampled": sampled.iter().map(|network| network.to_string()).collect::<Vec<_>>(),
+            "results": results,
+        });
+        println!("{}", serde_json::to_string_pretty(&value).unwrap());
+        return Ok(());
+    }
+
+    let address_len = results
+        .iter()
+        .map(|result| result.address.to_string().len())
+        .max()
+        .unwrap_or_default();
+    let mut found = 0;
+    let mut failed = 0;
+    for result in &results {
+        let address = format!("{:<width$}", result.address, width = address_len);
+        match &result.names {
+            Ok(names) if names.is_empty() => {}
+            Ok(names) => {
+                found += 1;
+                let names: Vec<_> = names
+                    .iter()
+                    .map(|host| {
+                        let name = idn_name(&host.name, args);
+                        match host.confirmed {
+                            Some(true) => format!("{} (confirmed)", name),
+                            Some(false) => format!("{} (not confirmed)", name),
+                            None if args.sweep_confirm => format!("{} (unknown)", name),
+                            None => name,
+                        }
+                    })
+                    .collect();
+                println!(
+                    "{}  {}",
+                    address.if_supports_color(output, |s| s.purple()),
+                    names.join(", ")
+                );
+            }
+            Err(e) => {
+                failed += 1;
+                let text = format!("{}  {:#}", address, e);
+                println!("{}", text.if_supports_color(output, |s| s.yellow()));
+            }
+        }
+    }
+
+    if !results.is_empty() {
+        println!();
+    }
+    for network in &sampled {
+        let text = format!(
+            "{} has more than {} addresses, so only its lowest {} addresses were looked up (see --sample).",
+            network, args.sweep_sample, args.sweep_sample
+        );
+        println!("{}", text.if_supports_color(output, |s| s.yellow()));
+    }
+    println!(
+        "{} of {} addresses have PTR records; {} lookups failed.",
+        found,
+        results.len(),
+        failed
+    );
+
+    Ok(())
+}
+
+/// Sends the queries of `spec` for each substitution, and displays the answers by substitution.
+fn do_and_display_probe(
+    args: &Args,
+    metadata: &QueryMetadata,
+    spec: &ProbeSpec,
+    bufsize: u16,
+) -> Result<()> {
+    let output = owo_colors::Stream::Stdout;
+    let probes = spec.expand()?;
+    let resolver = Resolver::new(nameserver_config(metadata), metadata.clone(), bufsize)
+        .with_rate_limit(RateLimit {
+            global: args.sweep_rate.map(Rate::per_second),
+            ..Default::default()
+        });
+    let results = probe::run(&resolver, probes);
+    let rcode = |res: &Message| {
+        match res.opt() {
+            Some(opt) => opt.rcode,
+            None => res.header.rcode,
+        }
+        .unwrap_or(RCode::NOERROR)
+    };
+
+    #[cfg(feature = "json")]
+    if args.json {
+        let results: Vec<_> = results
+            .iter()
+            .map(|result| {
+                let substitution: serde_json::Map<_, _> = result
+                    .probe
+                    .substitution
+                    .iter()
+                    .map(|(variable, value)| (variable.clone(), value.clone().into()))
+                    .collect();
+                let mut value = serde_json::json!({
+                    "substitution": substitution,
+                    "name": result.probe.name.to_string(),
+                    "type": result.probe.qtype.to_string(),
+                });
+                match &result.answer {
+                    Ok(lookup) => {
+                        let rows = lookup.rows();
+                        value["rcode"] = rcode(lookup.message()).to_string().into();
+                        value["answers"] = rows
+                            .iter()
+                            .map(|row| {
+                                serde_json::json!({
+                                    "name": row.owner.to_string(),
+                                    "type": row.rtype.to_string(),
+                                    "ttl": row.ttl,
+                                    "data": row.rdata,
+                                })
+                            })
+                            .collect::<Vec<_>>()
+                            .into();
+                        if let Lookup::Nameserver((_, _, _, timing)) = lookup {
+                            value["rtt_ms"] = (timing.elapsed.as_secs_f64() * 1000.0).into();
+                        }
+                    }
+                    Err(e) => value["error"] = format!("{:#}", e).into(),
+                }
+                value
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&results).unwrap());
+        return Ok(());
+    }
+
+    let substitutions: Vec<_> = results
+        .iter()
+        .map(|result| result.probe.substitution_string())
+        .collect();
+    let width = substitutions
+        .iter()
+        .map(String::len)
+        .max()
+        .unwrap_or_default();
+    let mut answered = 0;
+    let mut failed = 0;
+    for (result, substitution) in results.iter().zip(&substitutions) {
+        let substitution = format!("{:<width$}", substitution, width = width);
+        let query = format!(
+            "{} {}",
+            idn_name(&result.probe.name, args),
+            result.probe.qtype
+        );
+        match &result.answer {
+            Ok(lookup) => {
+                let res = lookup.message();
+                let records: Vec<_> = lookup
+                    .rows()
+                    .into_iter()
+                    .filter(|row| row.rtype == result.probe.qtype)
+                    .map(|row| format!("{} (TTL {})", row.rdata, row.ttl))
+                    .collect();
+                let answer = if records.is_empty() {
+                    rcode(res).to_string()
+                } else {
+                    answered += 1;
+                    records.join(", ")
+                };
+                println!(
+                    "{}  {}  {}",
+                    substitution.if_supports_color(output, |s| s.purple()),
+                    query,
+                    answer
+                );
+            }
+            Err(e) => {
+                failed += 1;
+                let text = format!("{}  {}  {:#}", substitution, query, e);
+                println!("{}", text.if_supports_color(output, |s| s.yellow()));
+            }
+        }
+    }
+
+    if args.print_meta {
+        if !results.is_empty() {
+            println!();
+        }
+        println!(
+            "{} of {} queries were answered with records; {} queries failed.",
+            answered,
+            results.len(),
+            failed
+        );
+    }
+
+    Ok(())
+}
+
+/// Queries for each of `types` and displays which of them exist for the queried name.
+fn do_and_display_survey(
+    args: &Args,
+    metadata: &QueryMetadata,
+    types: &[RecordType],
+    bufsize: u16,
+) -> Result<()> {
+    let output = owo_colors::Stream::Stdout;
+    let survey = survey::survey(metadata, types, bufsize)?;
+
+    #[cfg(feature = "json")]
+    if args.json {
+        let present: Vec<_> = survey
+            .present
+            .iter()
+            .map(
+                |(rtype, count)| serde_json::json!({ "type": rtype.to_string(), "records": count }),
+            )
+            .collect();
+        let failed: Vec<_> = survey
+            .failed
+            .iter()
+            .map(|(rtype, e)| serde_json::json!({ "type": rtype.to_string(), "error": e }))
+            .collect();
+        let value = serde_json::json!({
+            "name": survey.name.to_string(),
+            "queried": survey.queried,
+            "nxdomain": survey.nxdomain,
+            "present": present,
+            "failed": failed,
+        });
+        println!("{}", serde_json::to_string_pretty(&value).unwrap());
+        return Ok(());
+    }
+
+    let name = idn_name(&survey.name, args);
+    if survey.nxdomain && survey.present.is_empty() {
+        println!("{} does not exist (NXDOMAIN).", name);
+        return Ok(());
+    }
+
+    let width = survey
+        .present
+        .iter()
+        .map(|(rtype, _)| rtype.to_string().len())
+        .chain(
+            survey
+                .failed
+                .iter()
+                .map(|(rtype, _)| rtype.to_string().len()),
+        )
+        .max()
+        .unwrap_or_default();
+    for (rtype, count) in &survey.present {
+        let rtype = format!("{:<width$}", rtype.to_string(), width = width);
+        let records = if *count == 1 { "record" } else { "records" };
+        println!(
+            "{}  {} {}",
+            rtype.if_supports_color(output, |s| s.purple()),
+            count,
+            records
+        );
+    }
+    for (rtype, e) in &survey.failed {
+        let text = format!("{:<width$}  {}", rtype.to_string(), e, width = width);
+        println!("{}", text.if_supports_color(output, |s| s.yellow()));
+    }
+
+    if args.print_meta {
+        if !survey.present.is_empty() || !survey.failed.is_empty() {
+            println!();
+        }
+        println!(
+            "{} of {} record types exist for {}; {} queries failed.",
+            survey.present.len(),
+            survey.queried,
+            name,
+            survey.failed.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// Answers HTTP requests on `address` with the answers of the nameserver (or of the system's
+/// nameservers with +system) as JSON until toluol is stopped, see [`ApiServer`].
+#[cfg(feature = "json")]
+fn serve_api(
+    args: &Args,
+    metadata: &QueryMetadata,
+    address: SocketAddr,
+    bufsize: u16,
+) -> Result<()> {
+    let config = if args.system {
+        ResolverConfig::system()?
+    } else {
+        nameserver_config(metadata)
+    };
+    let resolver = Resolver::new(config, metadata.clone(), bufsize);
+    let mut server = ApiServer::new(resolver);
+    if args.validate_dnssec {
+        server = server.with_validation(args.validation_policy.clone());
+    }
+
+    let listener = TcpListener::bind(address)?;
+    eprintln!("Listening on http://{}/resolve", listener.local_addr()?);
+    Arc::new(server).serve(listener);
+    Ok(())
+}
+
+/// The time after which the watch subcommand sends a failed query again.
+const WATCH_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Sends the query again whenever a record of the previous answer expires, see [`Watch`].
+///
+/// On a terminal, the screen is redrawn every second with the remaining TTL of each record.
+/// Otherwise, each answer is printed once (as a line of JSON with +json).
+fn do_and_display_watch(args: &Args, metadata: &QueryMetadata, bufsize: u16) -> Result<()> {
+    #[cfg(feature = "json")]
+    let json = args.json;
+    #[cfg(not(feature = "json"))]
+    let json = false;
+    let interactive = io::stdout().is_terminal() && !json;
+    let mut watch = Watch::default();
+    let mut nameserver = None;
+    loop {
+        let (next, error) = match query_single(metadata, bufsize) {
+            Ok((ns, res, _, _timing)) => {
+                watch.update(&res, Instant::now());
+                #[cfg(feature = "json")]
+                if args.json {
+                    let value = json_value(&res, args, &ns, &_timing, None);
+                    run_hook(&res, &value, args);
+                    println!("{}", serde_json::to_string(&value).unwrap());
+                }
+                nameserver = Some(ns);
+                let next = watch.next_query().expect("an answer was received");
+                (next, None)
+            }
+            // there is nothing to watch if not even the first query succeeds
+            Err(e) if watch.updates() == 0 => return Err(e.into()),
+            Err(e) => (Instant::now() + WATCH_RETRY_INTERVAL, Some(e)),
+        };
+        let nameserver = nameserver.as_ref().expect("an answer was received");
+
+        if !interactive {
+            if !json {
+                if watch.updates() > 1 || error.is_some() {
+                    println!();
+                }
+                display_watch(args, &watch, nameserver, next, error.as_ref());
+            }
+            thread::sleep(next.saturating_duration_since(Instant::now()));
+            continue;
+        }
+        loop {
+            let now = Instant::now();
+            // clear the screen and move the cursor to the top left corner
+            print!("\x1b[2J\x1b[H");
+            display_watch(args, &watch, nameserver, next, error.as_ref());
+            if now >= next {
+                break;
+            }
+            thread::sleep((next - now).min(Duration::from_secs(1)));
+        }
+    }
+}
+
+/// Displays the records of `watch` with their remaining TTL, and when the query is sent again.
+fn display_watch(
+    args: &Args,
+    watch: &Watch,
+    nameserver: &Nameserver,
+    next: Instant,
+    error: Option<&toluol::Error>,
+) {
+    let output = owo_colors::Stream::Stdout;
+    let now = Instant::now();
+    let question = format!("{} {}", idn_name(&args.name, args), args.qtypes[0]);
+    let headline = format!(
+        "{} via {}: answer {}, next query in {}s",
+        question,
+        nameserver,
+        watch.updates(),
+        next.saturating_duration_since(now).as_secs()
+    );
+    println!(
+        "{}",
+        headline.if_supports_color(output, |s| s.style(owo_colors::style().bold().blue()))
+    );
+    if let Some(e) = error {
+        let text = format!("Query failed: {:#}", e);
+        println!("{}", text.if_supports_color(output, |s| s.yellow()));
+    }
+
+    let records: Vec<_> = watch.records().iter().map(|r| r.at(now)).collect();
+    if records.is_empty() {
+        println!("No records.");
+        return;
+    }
+    let owner_len = records.iter().map(|r| r.owner.to_string().len()).max();
+    let type_len = records.iter().map(|r| r.rtype.to_string().len()).max();
+    for record in &records {
+        println!(
+            "{}",
+            record.as_string(false, owner_len, type_len, Some(output))
+        );
     }
+}
 
-    let (answer, bytes_recvd, elapsed) =
-        send_query(args.connection_type, bufsize, &mut nameserver, &data)?;
+/// Fetches the DNSKEYs for the queried name's zone (or the closest parent zone that has DNSKEYs)
+/// and uses them to validate `res`, see [`validate_answer()`]. The DNSKEYs are taken from `cache`
+/// if it has them.
+fn fetch_dnskeys_and_validate(
+    res: &Message,
+    metadata: &QueryMetadata,
+    nameserver: &Nameserver,
+    policy: &ValidationPolicy,
+    cache: &DnskeyCache,
+) -> Result<Vec<RrSetValidation>> {
+    let mut zone = metadata.name.clone();
+    let dnskeys = loop {
+        let dnskeys =
+            get_dnskeys_cached(zone.clone(), nameserver.clone(), metadata.clone(), cache)?;
+        if !dnskeys.is_empty() {
+            break dnskeys;
+        }
 
-    let res = Message::parse(&mut Cursor::new(&answer)).context("Could not parse answer.")?;
-    display_result(&res, &args, &nameserver, bytes_recvd, &elapsed);
+        // try the parent zone's DNSKEYs
+        // TODO figure out when to stop (e.g. we should not try to validate www.example.com with
+        // the com DNSKEYs if example.com has no keys)
+        if zone.is_root() {
+            break Vec::new();
+        }
+        zone.pop_front_label();
+    };
+    Ok(validate_answer(res, &dnskeys, policy))
+}
 
-    if args.validate_dnssec {
-        let mut zone = args.name.clone();
-        let dnskeys = loop {
-            let dnskeys = get_dnskeys(zone.clone(), nameserver.clone(), query_metadata.clone())?;
-            if !dnskeys.is_empty() {
-                break dnskeys;
-            }
-
-            // try the parent zone's DNSKEYs
-            // TODO figure out when to stop (e.g. we should not try to validate www.example.com with
-            // the com DNSKEYs if example.com has no keys)
-            if zone.is_root() {
-                // this ensures consistent error message styling
-                validate_result(res, &[], &args);
-                return Ok(());
-            }
-            zone.pop_front_label();
-        };
-        validate_result(res, &dnskeys, &args);
+/// Returns the configuration of a resolver that sends each query once to the nameserver in
+/// `metadata`.
+fn nameserver_config(metadata: &QueryMetadata) -> ResolverConfig {
+    ResolverConfig {
+        nameservers: vec![metadata.nameserver.clone()],
+        timeout: metadata.timeout,
+        attempts: 1,
+        ..ResolverConfig::default()
     }
+}
 
-    Ok(())
+/// Saves `stats` to the file given with `--stats-file`, if any.
+fn save_server_stats(args: &Args, stats: &ServerStats) -> Result<()> {
+    match &args.stats_file {
+        Some(path) => Ok(stats.save(path)?),
+        None => Ok(()),
+    }
 }
 
-fn do_and_display_iterative_query(args: &Args, metadata: &QueryMetadata) -> Result<()> {
+fn do_and_display_iterative_query(
+    args: &Args,
+    metadata: &QueryMetadata,
+    stats: &ServerStats,
+    dnskey_cache: &DnskeyCache,
+) -> Result<()> {
     let headline_style = owo_colors::style().bold().blue();
-    let (answers, dnskeys) = toluol::iter::query(metadata)?;
+    let (answers, dnskeys, trace) =
+        toluol::iter::query_with_caches(metadata, stats, dnskey_cache, &args.validation_policy)?;
+    #[cfg(feature = "json")]
+    let json = args.json;
+    #[cfg(not(feature = "json"))]
+    let json = false;
     let dnskeys = match dnskeys {
         None => vec![None; answers.len()],
         Some(dnskeys) => dnskeys.into_iter().map(Some).collect(),
     };
-    for (i, (answer, dnskeys)) in zip(answers, dnskeys).enumerate() {
-        let (zone, nameserver, answer, bytes_recvd, elapsed) = answer;
+    for (i, ((answer, dnskeys), step)) in zip(zip(answers, dnskeys), &trace.steps).enumerate() {
+        let (zone, nameserver, answer, bytes_recvd, timing) = answer;
         if i > 0 {
             println!();
         }
@@ -90,93 +1843,239 @@ fn do_and_display_iterative_query(args: &Args, metadata: &QueryMetadata) -> Resu
                 .if_supports_color(owo_colors::Stream::Stdout, |text| text
                     .style(headline_style))
         );
-        display_result(&answer, args, &nameserver, bytes_recvd, &elapsed);
+        for skipped in &step.skipped {
+            if let Some(reason) = &skipped.reason {
+                let text = format!("skipped {}: {}", skipped.server, reason);
+                println!(
+                    "{}",
+                    text.if_supports_color(owo_colors::Stream::Stdout, |s| s.yellow())
+                );
+            }
+        }
+        // the DS records of referrals are checked with the delegation below
+        let validations = (args.validate_dnssec && !answer.answers.is_empty()).then(|| {
+            validate_answer(
+                &answer,
+                &dnskeys.unwrap_or_default(),
+                &args.validation_policy,
+            )
+        });
+        display_result(
+            &answer,
+            args,
+            &nameserver,
+            bytes_recvd,
+            &timing,
+            validations.as_deref(),
+        );
 
-        // TODO for every answer except the last the DS record and its RRSIG are in the authoritative section
-        if args.validate_dnssec && !answer.answers.is_empty() {
-            let dnskeys = dnskeys.unwrap();
-            validate_result(answer, &dnskeys, args);
+        let referral = step.referral.as_ref().filter(|_| !json);
+        if let Some((referral, security)) =
+            referral.and_then(|r| r.security.as_ref().map(|security| (r, security)))
+        {
+            println!(
+                "delegation to {}: {} ({})",
+                idn_name(&referral.zone, args),
+                security
+                    .state
+                    .to_string()
+                    .if_supports_color(owo_colors::Stream::Stdout, |s| s
+                        .style(validation_state_style(security.state))),
+                security.reason
+            );
         }
     }
+
+    #[cfg(feature = "json")]
+    if let Some(path) = &args.trace_json {
+        use anyhow::Context;
+        let value = trace_json(&trace);
+        std::fs::write(path, serde_json::to_string_pretty(&value).unwrap())
+            .with_context(|| format!("Could not write the trace to {}.", path))?;
+    }
+
+    if args.print_meta && !json {
+        let total: Duration = trace.steps.iter().map(|step| step.rtt).sum();
+        println!();
+        println!(
+            "trace {:016x}: {} queries in {} ms",
+            trace.id,
+            trace.steps.len(),
+            total.as_millis()
+        );
+    }
     Ok(())
 }
 
+/// Prints the records of the answer of `lookup` as CSV or TSV, see [`Lookup::rows()`].
+fn display_rows(lookup: &Lookup, format: RowFormat) {
+    for row in lookup.rows() {
+        match format {
+            RowFormat::Csv => println!("{}", row.to_csv()),
+            RowFormat::Tsv => println!("{}", row.to_tsv()),
+        }
+    }
+}
+
 fn display_result(
     res: &Message,
     args: &Args,
     nameserver: &Nameserver,
     bytes_recvd: u16,
-    elapsed: &Duration,
+    timing: &QueryTiming,
+    validations: Option<&[RrSetValidation]>,
 ) {
     let output = owo_colors::Stream::Stdout;
+    let res = &*sorted(res, args);
 
-    if args.verbose {
-        #[cfg(feature = "json")]
+    #[cfg(feature = "json")]
+    if args.json || args.hook.is_some() {
+        let value = json_value(res, args, nameserver, timing, validations);
+        run_hook(res, &value, args);
         if args.json {
-            println!("{}", serde_json::to_string_pretty(&res).unwrap());
+            println!("{}", serde_json::to_string_pretty(&value).unwrap());
             return;
         }
+    }
 
-        println!("{}", res.as_string(Some(output)));
+    if args.verbose {
+        let text = res.as_string_with(Some(output), args.sections);
+        if !text.is_empty() {
+            println!("{}", text);
+        }
 
-        if args.print_meta {
+        if let Some(validations) = validations {
             println!();
             println!(
                 "{}",
-                "Query metadata:".if_supports_color(output, |s| s.yellow())
+                "DNSSEC validation:".if_supports_color(output, |s| s.yellow())
             );
-            println!("\tTime:        {} ms", elapsed.as_millis());
-            println!("\tReply size:  {} bytes", bytes_recvd);
-            println!("\tServer:      {}", nameserver);
+            for validation in validations {
+                println!(
+                    "\t{} {} {}",
+                    idn_name(&validation.owner, args),
+                    validation.rtype,
+                    validation_annotation(validation)
+                );
+                // with multiple signatures or colliding key tags, list each signature's outcome
+                // (and which key verified it) for diagnostics
+                if validation.signatures.len() > 1
+                    || validation.signatures.iter().any(|sig| sig.candidates > 1)
+                {
+                    for sig in &validation.signatures {
+                        let mut outcome = match &sig.result {
+                            Ok(()) => "valid".to_string(),
+                            Err(e) => e.to_string(),
+                        };
+                        if let Some(digest) = sig.key_digest {
+                            outcome += &format!(" with key {}", HEXLOWER.encode(&digest));
+                        }
+                        if sig.candidates > 1 {
+                            outcome += &format!(
+                                " ({} of {} keys with this tag tried)",
+                                sig.keys_tried, sig.candidates
+                            );
+                        }
+                        println!(
+                            "\t\tkey tag {} ({:?}): {}",
+                            sig.key_tag, sig.algorithm, outcome
+                        );
+                    }
+                }
+            }
         }
-        return;
-    }
-
-    let all_answers: Vec<_> = res
-        .answers
-        .iter()
-        .chain(res.authoritative_answers.iter())
-        .chain(res.additional_answers.iter())
-        // don't print OPT records
-        .filter_map(|record| record.as_nonopt())
-        .collect();
-
-    #[cfg(feature = "json")]
-    if args.json {
-        println!("{}", serde_json::to_string_pretty(&all_answers).unwrap());
-        return;
-    }
 
-    if all_answers.is_empty() {
-        println!("<empty response>");
-    } else if !args.pad_answers {
-        for answer in &all_answers {
-            println!("{}", answer.as_string(true, None, None, Some(output)));
+        let synthesized = synthesized_addresses(res, args);
+        if !synthesized.is_empty() {
+            println!();
+            println!("{}", "DNS64:".if_supports_color(output, |s| s.yellow()));
+            for (ipv6, ipv4, prefix) in synthesized {
+                println!("\t{}  synthesized from {} with {}", ipv6, ipv4, prefix);
+            }
         }
-    } else {
-        let (mut max_owner_len, mut max_type_len) = (0, 0);
-        for answer in &all_answers {
-            max_owner_len = max(max_owner_len, answer.owner.string_len());
-            max_type_len = max(max_type_len, answer.rtype.to_string().len());
+
+        #[cfg(feature = "geoip")]
+        if let Some(geoip) = &args.geoip {
+            let addresses = answer_addresses(res);
+            if !addresses.is_empty() {
+                println!();
+                println!("{}", "GeoIP:".if_supports_color(output, |s| s.yellow()));
+                for ip in addresses {
+                    println!("\t{}  {}", ip, geoip.lookup(ip));
+                }
+            }
         }
-        for answer in &all_answers {
+
+        display_explanations(res, args);
+
+        if args.print_meta {
+            println!();
             println!(
                 "{}",
-                answer.as_string(false, Some(max_owner_len), Some(max_type_len), Some(output))
+                "Query metadata:".if_supports_color(output, |s| s.yellow())
+            );
+            println!("\tTime:        {} ms", timing.elapsed.as_millis());
+            if let Some(handshake) = timing.handshake {
+                println!("\tHandshake:   {} ms", handshake.as_millis());
+            }
+            println!("\tSent at:     {:.6} (Unix time)", unix_time(timing.sent));
+            println!(
+                "\tReceived at: {:.6} (Unix time)",
+                unix_time(timing.received)
             );
+            println!("\tRetransmits: {}", timing.retransmissions);
+            if let Some(reason) = &timing.tcp_fallback {
+                println!("\tVia TCP:     {}", reason);
+            }
+            if let Some(transport) = timing.transport_summary() {
+                println!("\tTransport:   {}", transport);
+            }
+            if timing.attempts.len() > 1 {
+                for (i, attempt) in timing.attempts.iter().enumerate() {
+                    let label = if i == 0 { "Attempts:" } else { "" };
+                    print!(
+                        "\t{:<12} {}. {} in {} ms",
+                        label,
+                        i + 1,
+                        attempt.transport,
+                        attempt.elapsed.as_millis()
+                    );
+                    match &attempt.failure {
+                        Some(failure) => println!(" ({})", failure),
+                        None => println!(),
+                    }
+                }
+            }
+            println!("\tReply size:  {} bytes", bytes_recvd);
+            println!("\tServer:      {}", nameserver);
+            if !timing.resolved_addrs.is_empty() {
+                let addrs: Vec<_> = timing
+                    .resolved_addrs
+                    .iter()
+                    .map(|addr| addr.to_string())
+                    .collect();
+                println!("\tResolved to: {}", addrs.join(", "));
+            }
+            #[cfg(feature = "geoip")]
+            if let (Some(geoip), Some(ip)) = (&args.geoip, nameserver.ip) {
+                println!("\tServer geo:  {}", geoip.lookup(ip));
+            }
+
+            if let Ok(report) = res.size_report() {
+                println!();
+                display_size_report(res, &report, args);
+            }
         }
+        return;
     }
 
+    display_records(res, args, validations.unwrap_or_default());
+    display_explanations(res, args);
+
     if args.print_meta {
-        let rcode = if let Some(opt) = res
-            .additional_answers
-            .iter()
-            .filter_map(|rec| rec.as_opt())
-            .next()
-        {
-            opt.rcode
-        } else {
-            res.header.rcode
+        let rcode = match res.opt() {
+            Some(opt) => opt.rcode,
+            None => res.header.rcode,
         };
         let rcode = rcode.unwrap_or(RCode::NOERROR);
         let style = if rcode == RCode::NOERROR {
@@ -186,113 +2085,626 @@ fn display_result(
         };
 
         println!();
+        for warning in res.warnings() {
+            let warning = format!("Warning: {}", warning);
+            println!("{}", warning.if_supports_color(output, |s| s.yellow()));
+        }
+        if let Some(reason) = &timing.tcp_fallback {
+            let warning = format!("Warning: {} Sent the query again via TCP.", reason);
+            println!("{}", warning.if_supports_color(output, |s| s.yellow()));
+        }
+        // the transport is only worth mentioning if the query had to be sent more than once
+        let attempts = match (timing.attempts.len(), timing.transport_summary()) {
+            (n, Some(transport)) if n > 1 => {
+                let times: Vec<_> = timing
+                    .attempts
+                    .iter()
+                    .map(|a| format!("{} {} ms", a.transport, a.elapsed.as_millis()))
+                    .collect();
+                format!(" via {} ({} attempts: {})", transport, n, times.join(", "))
+            }
+            _ => String::new(),
+        };
+        let handshake = match timing.handshake {
+            Some(handshake) => format!(" (+{} ms handshake)", handshake.as_millis()),
+            None => String::new(),
+        };
         println!(
-            "{} from {} in {} ms",
+            "{} from {} in {} ms{}{}",
             rcode
                 .to_string()
                 .if_supports_color(output, |s| s.style(style)),
             nameserver,
-            elapsed.as_millis()
+            timing.elapsed.as_millis(),
+            handshake,
+            attempts
         );
     }
 }
 
-fn validate_result(mut answer: Message, dnskeys: &[NonOptRecord], args: &Args) {
+/// Displays the encoded size of `res` per section, along with the largest record of each section.
+fn display_size_report(res: &Message, report: &SizeReport, args: &Args) {
     let output = owo_colors::Stream::Stdout;
-    let err_style = Style::new().bold().red();
-    let ok_style = Style::new().bold().green();
-
-    if dnskeys.is_empty() {
-        let err = format!(
-            "The {} record(s) could not be verified: no DNSKEY record found.",
-            args.qtype
-        );
-        println!("{}", err.if_supports_color(output, |s| s.style(err_style)));
-        return;
-    }
+    println!(
+        "{}",
+        "Message size (without compression):".if_supports_color(output, |s| s.yellow())
+    );
+    println!("\t{:<12} {} bytes", "Header:", report.header);
+    println!("\t{:<12} {} bytes", "Question:", report.questions.total);
 
-    // Vec::drain_filter() is still unstable, so we roll our own thing
-    let mut idx = 0;
-    let mut rrsig_records = Vec::new();
-    let mut rrset_records = Vec::new();
-    while idx < answer.answers.len() {
-        if let Record::NONOPT(nonopt) = &answer.answers[idx] {
-            if nonopt.rtype == RecordType::RRSIG {
-                rrsig_records.push(answer.answers.swap_remove(idx).into_nonopt());
-                continue;
-            } else if nonopt.rtype == args.qtype {
-                rrset_records.push(answer.answers.swap_remove(idx).into_nonopt());
-                continue;
+    let sections = [
+        ("Answer:", &report.answers, &res.answers),
+        (
+            "Authority:",
+            &report.authoritative_answers,
+            &res.authoritative_answers,
+        ),
+        (
+            "Additional:",
+            &report.additional_answers,
+            &res.additional_answers,
+        ),
+    ];
+    for (title, size, records) in sections {
+        let largest = zip(&size.entries, records).max_by_key(|(size, _)| **size);
+        match largest {
+            Some((largest, record)) => {
+                let record = match record {
+                    Record::NONOPT(rec) => {
+                        format!("{} {}", idn_name(&rec.owner, args), rec.rtype)
+                    }
+                    Record::OPT(_) => "OPT".to_string(),
+                };
+                let plural = if records.len() == 1 { "" } else { "s" };
+                println!(
+                    "\t{:<12} {} bytes ({} record{}, largest {} bytes: {})",
+                    title,
+                    size.total,
+                    records.len(),
+                    plural,
+                    largest,
+                    record
+                );
             }
+            None => println!("\t{:<12} {} bytes", title, size.total),
         }
-        idx += 1;
     }
+    println!("\t{:<12} {} bytes", "Total:", report.total());
+}
 
-    let mut rrset = match RrSet::new(rrset_records) {
-        Ok(rrset) => rrset,
-        Err(e) => {
-            let err = format!("The {} record(s) could not be verified: {}", args.qtype, e);
-            println!("{}", err.if_supports_color(output, |s| s.style(err_style)));
+/// Displays an answer that was synthesized from the hosts file at `path`.
+fn display_hosts_result(res: &Message, args: &Args, path: &Path) {
+    let output = owo_colors::Stream::Stdout;
+    let res = &*sorted(res, args);
+
+    #[cfg(feature = "json")]
+    if args.json || args.hook.is_some() {
+        let value = hosts_json_value(res, args, path);
+        run_hook(res, &value, args);
+        if args.json {
+            println!("{}", serde_json::to_string_pretty(&value).unwrap());
             return;
         }
-    };
+    }
 
-    let rrsig = rrsig_records.into_iter().find(|rec| {
-        rec.rdata()
-            .as_rrsig()
-            .expect("RRSIG record has non-RRSIG RDATA.")
-            .type_covered
-            == args.qtype
-    });
-    let mut rrsig = match rrsig {
-        Some(rrsig) => rrsig,
-        None => {
-            let err = format!(
-                "The {} record(s) could not be verified: no RRSIG record found.",
-                args.qtype
+    if args.verbose {
+        let text = res.as_string_with(Some(output), args.sections);
+        if !text.is_empty() {
+            println!("{}", text);
+        }
+        if args.print_meta {
+            println!();
+            println!(
+                "{}",
+                "Query metadata:".if_supports_color(output, |s| s.yellow())
             );
-            println!("{}", err.if_supports_color(output, |s| s.style(err_style)));
-            return;
+            println!("\tSource:      hosts file {}", path.display());
         }
-    };
+        return;
+    }
 
-    let dnskey_candidates: Vec<_> = dnskeys
+    display_records(res, args, &[]);
+
+    if args.print_meta {
+        println!();
+        println!(
+            "{} from hosts file {}",
+            RCode::NOERROR
+                .to_string()
+                .if_supports_color(output, |s| s.green()),
+            path.display()
+        );
+    }
+}
+
+/// Displays a plain-language explanation of each record type in `res` if `+explain` was given.
+fn display_explanations(res: &Message, args: &Args) {
+    let output = owo_colors::Stream::Stdout;
+    let explanations = explain_message(res, &English);
+    if !args.explain || explanations.is_empty() {
+        return;
+    }
+    println!();
+    println!(
+        "{}",
+        "Explanations:".if_supports_color(output, |s| s.yellow())
+    );
+    for (rtype, explanation) in explanations {
+        println!("\t{:<10} {}", rtype.to_string(), explanation.summary);
+        for (field, summary) in explanation.fields {
+            println!("\t{:<10}   {}: {}", "", field, summary);
+        }
+    }
+}
+
+/// Returns `res` with its records in the order given by `--sort`, only copying it if needed.
+fn sorted<'a>(res: &'a Message, args: &Args) -> Cow<'a, Message> {
+    if args.sort == RecordOrder::Original {
+        return Cow::Borrowed(res);
+    }
+    let mut res = res.clone();
+    res.sort_records(args.sort);
+    Cow::Owned(res)
+}
+
+/// Displays the non-OPT records of `res` in the sections selected by `args` in the short (i.e.
+/// non-verbose) format.
+///
+/// Unless comments are disabled, the first record of each record set in `validations` is annotated
+/// with its validation result, and `AAAA` records that were presumably synthesized by DNS64 with
+/// the IPv4 address they were synthesized from.
+fn display_records(res: &Message, args: &Args, validations: &[RrSetValidation]) {
+    let output = owo_colors::Stream::Stdout;
+    let all_answers: Vec<_> = [
+        (&res.answers, args.sections.answer),
+        (&res.authoritative_answers, args.sections.authority),
+        (&res.additional_answers, args.sections.additional),
+    ]
+    .into_iter()
+    .filter(|(_, selected)| *selected)
+    .flat_map(|(section, _)| {
+        let records = section.iter().filter_map(Record::as_nonopt);
+        if args.sections.group_rrsets {
+            table::group_records(records)
+        } else {
+            records.collect()
+        }
+    })
+    .collect();
+
+    if all_answers.is_empty() {
+        if args.sections.comments {
+            println!("<empty response>");
+        }
+        return;
+    }
+
+    let mut table = RecordTable::new(Some(output));
+    table.unicode_names = args.idn_out;
+    if args.pad_answers {
+        table.fit_records(all_answers.iter().copied());
+    }
+    let mut annotated = vec![false; validations.len()];
+    let mut previous = None;
+    for &answer in &all_answers {
+        let mut line = if args.sections.group_rrsets {
+            table.grouped_line(answer, previous)
+        } else if args.pad_answers {
+            table.record_line(answer)
+        } else if args.idn_out {
+            answer
+                .to_unicode_names()
+                .as_string(true, None, None, Some(output))
+        } else {
+            answer.as_string(true, None, None, Some(output))
+        };
+        let validation = validations.iter().position(|v| {
+            v.owner == answer.owner && v.rtype == answer.rtype && v.class == answer.class
+        });
+        let mut annotations = Vec::new();
+        if let Some(i) = validation.filter(|&i| args.sections.comments && !annotated[i]) {
+            annotated[i] = true;
+            annotations.push(validation_annotation(&validations[i]));
+        }
+        if let Rdata::AAAA(aaaa) = answer.rdata() {
+            if let Some((ipv4, _)) = dns64::unsynthesize(aaaa.address, &args.dns64_prefixes) {
+                if args.sections.comments {
+                    annotations.push(format!("DNS64 from {}", ipv4));
+                }
+            }
+        }
+        if !annotations.is_empty() {
+            line = format!("{}  ; {}", line, annotations.join(", "));
+        }
+        println!("{}", line);
+        previous = Some(answer);
+    }
+}
+
+/// Returns the addresses of the `AAAA` records in `res` that are within one of the NAT64 prefixes
+/// given in `args`, along with the IPv4 addresses they were presumably synthesized from and the
+/// prefixes they are within.
+fn synthesized_addresses(res: &Message, args: &Args) -> Vec<(Ipv6Addr, Ipv4Addr, Nat64Prefix)> {
+    res.nonopt_records()
+        .filter_map(|record| match record.rdata() {
+            Rdata::AAAA(aaaa) => dns64::unsynthesize(aaaa.address, &args.dns64_prefixes)
+                .map(|(ipv4, prefix)| (aaaa.address, ipv4, prefix)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns a (colourized) description of the validation result of a record set, e.g.
+/// `secure (key tag 12345)`.
+fn validation_annotation(validation: &RrSetValidation) -> String {
+    let output = owo_colors::Stream::Stdout;
+    let style = validation_state_style(validation.state);
+    let mut res = validation
+        .state
+        .to_string()
+        .if_supports_color(output, |s| s.style(style))
+        .to_string();
+    if let Some(key_tag) = validation.key_tag() {
+        res.push_str(&format!(" (key tag {})", key_tag));
+    }
+    let problems: Vec<_> = validation
+        .error()
+        .map(ToString::to_string)
+        .into_iter()
+        .chain(validation.warnings.iter().map(ToString::to_string))
+        .collect();
+    if !problems.is_empty() {
+        res.push_str(&format!(": {}", problems.join("; ")));
+    }
+    res
+}
+
+/// Returns the style in which `state` is displayed.
+fn validation_state_style(state: ValidationState) -> Style {
+    match state {
+        ValidationState::Secure => Style::new().bold().green(),
+        ValidationState::Bogus => Style::new().bold().red(),
+        ValidationState::Insecure | ValidationState::Indeterminate => Style::new().yellow(),
+    }
+}
+
+/// Returns `name` with internationalized labels in their Unicode form if `+idn-out` was given.
+fn idn_name(name: &Name, args: &Args) -> String {
+    if args.idn_out {
+        name.to_unicode_labels().to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Returns `rdata` with the internationalized names it contains in their Unicode form if
+/// `+idn-out` was given, see [`Rdata::names_mut()`].
+fn idn_rdata<'a>(rdata: &'a Rdata, args: &Args) -> Cow<'a, Rdata> {
+    if !args.idn_out {
+        return Cow::Borrowed(rdata);
+    }
+    let mut rdata = rdata.clone();
+    for name in rdata.names_mut() {
+        *name = name.to_unicode_labels();
+    }
+    Cow::Owned(rdata)
+}
+
+/// Returns `res` with internationalized names in their Unicode form if `+idn-out` was given, see
+/// [`Message::to_unicode_names()`].
+#[cfg(feature = "json")]
+fn idn_message<'a>(res: &'a Message, args: &Args) -> Cow<'a, Message> {
+    if args.idn_out {
+        Cow::Owned(res.to_unicode_names())
+    } else {
+        Cow::Borrowed(res)
+    }
+}
+
+/// Returns all records from all sections of `res`, except for OPT records.
+#[cfg(feature = "json")]
+fn nonopt_records(res: &Message) -> Vec<&NonOptRecord> {
+    res.nonopt_records().collect()
+}
+
+/// Returns the JSON representation of `trace` that is written when `--trace-json` is given.
+#[cfg(feature = "json")]
+fn trace_json(trace: &toluol::iter::Trace) -> serde_json::Value {
+    let steps: Vec<_> = trace
+        .steps
         .iter()
-        .filter(|rec| {
-            // TODO what to do with the RRSIGs here?
-            if rec.rtype != RecordType::DNSKEY {
-                return false;
-            }
-            let rrsig_keytag = rrsig.rdata().as_rrsig().unwrap().key_tag;
-            let rdata = rec
-                .rdata()
-                .as_dnskey()
-                .expect("DNSKEY record has non-DNSKEY RDATA.");
-            rdata.key_tag() == rrsig_keytag
+        .map(|step| {
+            let referral = step.referral.as_ref().map(|referral| {
+                serde_json::json!({
+                    "zone": referral.zone.to_string(),
+                    "nameserver": referral.nameserver.to_string(),
+                    "address": referral.address.map(|ip| ip.to_string()),
+                    "dnssec": referral.security.as_ref().map(|security| serde_json::json!({
+                        "state": security.state.to_string(),
+                        "reason": security.reason,
+                    })),
+                })
+            });
+            let skipped: Vec<_> = step
+                .skipped
+                .iter()
+                .map(|skipped| {
+                    serde_json::json!({
+                        "server": skipped.server.hostname,
+                        "address": skipped.server.ip.map(|ip| ip.to_string()),
+                        "reason": skipped.reason,
+                    })
+                })
+                .collect();
+            serde_json::json!({
+                "zone": step.zone.to_string(),
+                "server": step.server.hostname,
+                "address": step.server.ip.map(|ip| ip.to_string()),
+                "port": step.server.port,
+                "msg_id": step.msg_id,
+                "rtt_ms": step.rtt.as_secs_f64() * 1000.0,
+                "rcode": step.rcode.to_string(),
+                "referral": referral,
+                "skipped": skipped,
+            })
         })
         .collect();
+    serde_json::json!({
+        "id": format!("{:016x}", trace.id),
+        "steps": steps,
+    })
+}
 
-    let mut err = None;
-    for dnskey in dnskey_candidates {
-        match rrset.validate(&mut rrsig, dnskey, false) {
-            Ok(()) => {
-                let msg = format!(
-                    "The {} record(s) have been validated using the RRSIG record.",
-                    args.qtype
-                );
-                println!("{}", msg.if_supports_color(output, |s| s.style(ok_style)));
-                return;
+/// Returns the number of seconds between the Unix epoch and `time`.
+fn unix_time(time: SystemTime) -> f64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or_default()
+}
+
+/// Returns the JSON representation of `res` that is printed when `+json` is given.
+///
+/// If `+verbose` is given (and `+nometa` is not) or GeoIP databases were given, the representation
+/// is wrapped in an object that also contains the query's timing information and the GeoIP
+/// information for all addresses in `res` and for the nameserver, respectively. The same goes for
+/// the DNSSEC validation results, if given, and the `AAAA` records presumably synthesized by
+/// DNS64.
+#[cfg(feature = "json")]
+#[cfg_attr(not(feature = "geoip"), allow(unused_variables))]
+fn json_value(
+    res: &Message,
+    args: &Args,
+    nameserver: &Nameserver,
+    timing: &QueryTiming,
+    validations: Option<&[RrSetValidation]>,
+) -> serde_json::Value {
+    let displayed = idn_message(res, args);
+    let value = if args.verbose {
+        serde_json::to_value(&displayed).unwrap()
+    } else {
+        serde_json::to_value(nonopt_records(&displayed)).unwrap()
+    };
+    let mut wrapped = serde_json::Map::new();
+
+    if args.verbose && args.print_meta {
+        if let Ok(report) = res.size_report() {
+            wrapped.insert("size".into(), serde_json::to_value(report).unwrap());
+        }
+        let resolved_addrs: Vec<_> = timing
+            .resolved_addrs
+            .iter()
+            .map(|addr| addr.to_string())
+            .collect();
+        let attempts: Vec<_> = timing
+            .attempts
+            .iter()
+            .map(|attempt| {
+                serde_json::json!({
+                    "transport": attempt.transport.to_string(),
+                    "elapsed_ms": attempt.elapsed.as_secs_f64() * 1000.0,
+                    "failure": attempt.failure,
+                })
+            })
+            .collect();
+        wrapped.insert(
+            "timing".into(),
+            serde_json::json!({
+                "sent": unix_time(timing.sent),
+                "received": unix_time(timing.received),
+                "elapsed_ms": timing.elapsed.as_secs_f64() * 1000.0,
+                "handshake_ms": timing.handshake.map(|h| h.as_secs_f64() * 1000.0),
+                "retransmissions": timing.retransmissions,
+                "resolved_addrs": resolved_addrs,
+                "tcp_fallback": timing.tcp_fallback,
+                "transport": timing.transport_summary(),
+                "attempts": attempts,
+            }),
+        );
+    }
+
+    #[cfg(feature = "geoip")]
+    if let Some(geoip) = &args.geoip {
+        let mut addresses = answer_addresses(res);
+        if let Some(ip) = nameserver.ip {
+            if !addresses.contains(&ip) {
+                addresses.push(ip);
             }
-            Err(e) => err = Some(e),
+        }
+        let geo: serde_json::Map<_, _> = addresses
+            .into_iter()
+            .map(|ip| {
+                let info = geoip.lookup(ip);
+                let info = serde_json::json!({
+                    "country": info.country,
+                    "asn": info.asn,
+                    "as_org": info.as_org,
+                });
+                (ip.to_string(), info)
+            })
+            .collect();
+        wrapped.insert("geoip".into(), geo.into());
+    }
+
+    if let Some(validations) = validations {
+        let validations: Vec<_> = validations
+            .iter()
+            .map(|validation| {
+                let signatures: Vec<_> = validation
+                    .signatures
+                    .iter()
+                    .map(|sig| {
+                        serde_json::json!({
+                            "key_tag": sig.key_tag,
+                            "algorithm": format!("{:?}", sig.algorithm),
+                            "error": sig.result.as_ref().err().map(|e| e.to_string()),
+                            "key_digest": sig.key_digest.map(|digest| HEXLOWER.encode(&digest)),
+                            "candidates": sig.candidates,
+                            "keys_tried": sig.keys_tried,
+                        })
+                    })
+                    .collect();
+                serde_json::json!({
+                    "owner": validation.owner.to_string(),
+                    "type": validation.rtype.to_string(),
+                    "state": validation.state.to_string(),
+                    "key_tag": validation.key_tag(),
+                    "error": validation.error().map(|e| e.to_string()),
+                    "warnings": validation
+                        .warnings
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>(),
+                    "signatures": signatures,
+                })
+            })
+            .collect();
+        wrapped.insert("dnssec".into(), validations.into());
+    }
+
+    let synthesized: Vec<_> = synthesized_addresses(res, args)
+        .into_iter()
+        .map(|(ipv6, ipv4, prefix)| {
+            serde_json::json!({
+                "address": ipv6.to_string(),
+                "ipv4": ipv4.to_string(),
+                "prefix": prefix.to_string(),
+            })
+        })
+        .collect();
+    if !synthesized.is_empty() {
+        wrapped.insert("dns64".into(), synthesized.into());
+    }
+
+    if wrapped.is_empty() {
+        return value;
+    }
+    wrapped.insert("answer".into(), value);
+    wrapped.into()
+}
+
+/// Like [`json_value()`], for an answer that was synthesized from the hosts file at `path`.
+/// Runs the hook given with `--hook`, if any, with `value`, the JSON result for `res`. A failing
+/// hook does not stop toluol; the error is printed to stderr instead.
+#[cfg(feature = "json")]
+fn run_hook(res: &Message, value: &serde_json::Value, args: &Args) {
+    if let Some(hook) = &args.hook {
+        if let Err(e) = hook.run(res, value) {
+            let warning = format!("Warning: {:#}", e);
+            eprintln!(
+                "{}",
+                warning.if_supports_color(owo_colors::Stream::Stderr, |s| s.yellow())
+            );
         }
     }
+}
+
+#[cfg(feature = "json")]
+fn hosts_json_value(res: &Message, args: &Args, path: &Path) -> serde_json::Value {
+    let displayed = idn_message(res, args);
+    let value = if args.verbose {
+        serde_json::to_value(&displayed).unwrap()
+    } else {
+        serde_json::to_value(nonopt_records(&displayed)).unwrap()
+    };
+    serde_json::json!({
+        "hosts_file": path.display().to_string(),
+        "answer": value,
+    })
+}
+
+/// Returns the addresses contained in the A and AAAA records of `res`, without duplicates.
+#[cfg(any(feature = "geoip", all(feature = "http", feature = "json")))]
+fn answer_addresses(res: &Message) -> Vec<std::net::IpAddr> {
+    let mut addresses = Vec::new();
+    for record in res.nonopt_records() {
+        let ip = match record.rdata() {
+            Rdata::A(a) => a.address.into(),
+            Rdata::AAAA(aaaa) => aaaa.address.into(),
+            _ => continue,
+        };
+        if !addresses.contains(&ip) {
+            addresses.push(ip);
+        }
+    }
+    addresses
+}
+
+/// Looks up and displays the registration information for `name` and `addresses` via RDAP.
+#[cfg(all(feature = "http", feature = "json"))]
+fn display_rdap(name: &toluol_proto::Name, addresses: &[std::net::IpAddr]) {
+    use toluol::rdap::{lookup_domain, lookup_ip};
+
+    let output = owo_colors::Stream::Stdout;
+    let headline_style = owo_colors::style().bold().blue();
+    let err_style = Style::new().bold().red();
 
-    // if we haven't returned early, that means validation did not succeed and we should have an
-    // error
-    let err = format!(
-        "The {} record(s) could not be verified: {}",
-        args.qtype,
-        err.unwrap()
+    println!();
+    println!(
+        "{}",
+        "RDAP:".if_supports_color(output, |text| text.style(headline_style))
     );
-    println!("{}", err.if_supports_color(output, |s| s.style(err_style)));
+
+    match lookup_domain(name) {
+        Ok(info) => {
+            println!("\tDomain:       {}", info.domain);
+            if let Some(registrar) = info.registrar {
+                println!("\tRegistrar:    {}", registrar);
+            }
+            if let Some(registration) = info.registration {
+                println!("\tRegistered:   {}", registration);
+            }
+            if let Some(expiration) = info.expiration {
+                println!("\tExpires:      {}", expiration);
+            }
+            if !info.nameservers.is_empty() {
+                println!("\tNameservers:  {}", info.nameservers.join(", "));
+            }
+        }
+        Err(e) => {
+            let err = format!("\tDomain lookup failed: {:#}", e);
+            println!("{}", err.if_supports_color(output, |s| s.style(err_style)));
+        }
+    }
+
+    let mut seen = Vec::new();
+    for &ip in addresses {
+        if seen.contains(&ip) {
+            continue;
+        }
+        seen.push(ip);
+
+        match lookup_ip(ip) {
+            Ok(info) => {
+                let parts: Vec<_> = [info.name.or(info.handle), info.country, info.org]
+                    .into_iter()
+                    .flatten()
+                    .collect();
+                println!("\t{}  {}", ip, parts.join(", "));
+            }
+            Err(e) => {
+                let err = format!("\t{}  lookup failed: {:#}", ip, e);
+                println!("{}", err.if_supports_color(output, |s| s.style(err_style)));
+            }
+        }
+    }
 }
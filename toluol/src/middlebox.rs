@@ -0,0 +1,368 @@
+//! Detecting interference by middleboxes (e.g. firewalls, home routers, or transparent DNS
+//! proxies) on the path between the client and a recursive nameserver: a battery of probe queries
+//! is sent and the answers are checked for the traces such devices typically leave, see
+//! [`detect()`].
+
+use std::fmt::Display;
+use std::thread;
+use toluol_proto::error::ParseError;
+use toluol_proto::{Message, Name, Rdata, RecordType};
+
+use crate::error::{Context, Error, Result};
+use crate::split::AnswerSummary;
+use crate::util::query_single;
+use crate::{ConnectionType, QueryMetadata};
+
+/// A kind of interference that a middlebox likely causes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Interference {
+    /// The answers over UDP and TCP differ, e.g. because a proxy intercepts and rewrites UDP
+    /// queries only.
+    AnswersDiffer,
+    /// Queries over TCP fail while queries over UDP work.
+    TcpBlocked,
+    /// Responses to queries with EDNS do not contain an `OPT` record, i.e. EDNS is stripped.
+    EdnsStripped,
+    /// Large responses over UDP do not arrive while they do over TCP, e.g. because fragments or
+    /// datagrams larger than 512 bytes are dropped.
+    LargeResponsesDropped,
+    /// The case of the question name is not preserved (the "0x20" bits of
+    /// [draft-vixie-dnsext-dns0x20](https://datatracker.ietf.org/doc/html/draft-vixie-dnsext-dns0x20)),
+    /// i.e. the query was answered by something that rewrites names.
+    CaseNotPreserved,
+    /// Responses to queries with the DO bit contain no `RRSIG` records, i.e. DNSSEC records are
+    /// filtered.
+    DnssecStripped,
+}
+
+impl Display for Interference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            Self::AnswersDiffer => "answers over UDP and TCP differ",
+            Self::TcpBlocked => "DNS over TCP is blocked",
+            Self::EdnsStripped => "EDNS is stripped",
+            Self::LargeResponsesDropped => "large UDP responses are dropped",
+            Self::CaseNotPreserved => "the case of question names is not preserved",
+            Self::DnssecStripped => "DNSSEC records are filtered",
+        };
+        f.write_str(text)
+    }
+}
+
+/// A check performed by [`detect()`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Check {
+    /// Compares the answers over UDP and TCP.
+    Transport,
+    /// Checks that the response to a query with EDNS contains an `OPT` record.
+    Edns,
+    /// Queries the `DNSKEY` records of the root zone with the DO bit over UDP, whose response is
+    /// larger than 512 bytes.
+    LargeResponse,
+    /// Sends a question name with randomized case and checks that the response repeats it
+    /// exactly.
+    CasePreservation,
+    /// Queries the `SOA` record of the root zone with the DO bit and checks that the response
+    /// contains its `RRSIG`.
+    Dnssec,
+}
+
+impl Display for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            Self::Transport => "UDP vs. TCP",
+            Self::Edns => "EDNS",
+            Self::LargeResponse => "large responses",
+            Self::CasePreservation => "0x20 case preservation",
+            Self::Dnssec => "DNSSEC records",
+        };
+        f.write_str(text)
+    }
+}
+
+/// The outcome of a [`Check`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// There are no signs of interference.
+    Passed,
+    /// Interference of the given kind is likely.
+    Detected(Interference),
+    /// The check could not tell, e.g. because a query failed for unrelated reasons.
+    Inconclusive,
+}
+
+/// The result of a single [`Check`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CheckResult {
+    pub check: Check,
+    pub outcome: Outcome,
+    /// What was observed, for humans.
+    pub detail: String,
+}
+
+/// The result of [`detect()`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MiddleboxReport {
+    pub nameserver: String,
+    pub results: Vec<CheckResult>,
+}
+
+impl MiddleboxReport {
+    /// Returns the kinds of interference that were detected.
+    pub fn interference(&self) -> Vec<Interference> {
+        self.results
+            .iter()
+            .filter_map(|result| match result.outcome {
+                Outcome::Detected(interference) => Some(interference),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Sends probe queries to the recursive nameserver in `metadata` (over UDP and TCP, regardless of
+/// the connection type in `metadata`) concurrently and checks their answers for signs of
+/// middlebox interference, see [`Check`]. The name and type in `metadata` are used for the
+/// transport and case checks; the other checks query the root zone.
+///
+/// Returns an error if the nameserver does not answer the plain query over UDP at all, as no
+/// check can be performed then.
+pub fn detect(metadata: &QueryMetadata, bufsize: u16) -> Result<MiddleboxReport> {
+    let mut base = metadata.clone();
+    base.connection_type = ConnectionType::Udp;
+    base.edns = true;
+    base.fetch_dnssec = false;
+    base.validate_dnssec = false;
+    base.dnssec_ok = None;
+    base.checking_disabled = true;
+
+    let mut tcp = base.clone();
+    tcp.connection_type = ConnectionType::Tcp;
+    let mut large = base.clone();
+    large.name = Name::root();
+    large.qtype = RecordType::DNSKEY;
+    large.fetch_dnssec = true;
+    let mut large_tcp = large.clone();
+    large_tcp.connection_type = ConnectionType::Tcp;
+    let mut mixed_case = base.clone();
+    mixed_case.name = randomize_case(&base.name);
+    let mut dnssec = base.clone();
+    dnssec.name = Name::root();
+    dnssec.qtype = RecordType::SOA;
+    dnssec.fetch_dnssec = true;
+
+    let probes = [base, tcp, large, large_tcp, mixed_case, dnssec];
+    let mut results = thread::scope(|s| {
+        let handles: Vec<_> = probes
+            .iter()
+            .map(|metadata| {
+                s.spawn(move || query_single(metadata, bufsize).map(|(_, res, len, _)| (res, len)))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("Query thread panicked."))
+            .collect::<Vec<_>>()
+    })
+    .into_iter();
+    let mut next = || results.next().expect("there is a result for each probe");
+    let (udp, tcp, large, large_tcp, mixed_case, dnssec) =
+        (next(), next(), next(), next(), next(), next());
+
+    let (udp, _) = udp.transport(format!(
+        "The nameserver {} did not answer over UDP.",
+        metadata.nameserver
+    ))?;
+    let results = vec![
+        check_transport(&udp, tcp.map(|(res, _)| res)),
+        check_edns(&udp),
+        check_large_response(large, large_tcp),
+        check_case(&probes[4].name, mixed_case.map(|(res, _)| res)),
+        check_dnssec(dnssec.map(|(res, _)| res)),
+    ];
+
+    Ok(MiddleboxReport {
+        nameserver: metadata.nameserver.clone(),
+        results,
+    })
+}
+
+fn check_transport(udp: &Message, tcp: Result<Message>) -> CheckResult {
+    let check = Check::Transport;
+    let tcp = match tcp {
+        Ok(tcp) => tcp,
+        Err(e) => {
+            return CheckResult {
+                check,
+                outcome: Outcome::Detected(Interference::TcpBlocked),
+                detail: format!("the query over TCP failed: {:#}", e),
+            }
+        }
+    };
+    let (udp, tcp) = (AnswerSummary::new(udp), AnswerSummary::new(&tcp));
+    if udp == tcp {
+        return CheckResult {
+            check,
+            outcome: Outcome::Passed,
+            detail: "the answers over UDP and TCP are the same".to_string(),
+        };
+    }
+    let describe = |answer: &AnswerSummary| {
+        if answer.records.is_empty() {
+            answer.rcode.to_string()
+        } else {
+            let records: Vec<_> = answer
+                .records
+                .iter()
+                .map(|rdata| format!("{} {}", rdata.rtype(), rdata))
+                .collect();
+            records.join(", ")
+        }
+    };
+    CheckResult {
+        check,
+        outcome: Outcome::Detected(Interference::AnswersDiffer),
+        detail: format!("UDP: {}; TCP: {}", describe(&udp), describe(&tcp)),
+    }
+}
+
+fn check_edns(udp: &Message) -> CheckResult {
+    match udp.opt() {
+        Some(opt) => CheckResult {
+            check: Check::Edns,
+            outcome: Outcome::Passed,
+            detail: format!(
+                "the response has EDNS version {} and a payload size of {}",
+                opt.edns_version, opt.payload_size
+            ),
+        },
+        None => CheckResult {
+            check: Check::Edns,
+            outcome: Outcome::Detected(Interference::EdnsStripped),
+            detail: "the response to a query with EDNS has no OPT record".to_string(),
+        },
+    }
+}
+
+fn check_large_response(udp: Result<(Message, u16)>, tcp: Result<(Message, u16)>) -> CheckResult {
+    let check = Check::LargeResponse;
+    // truncated responses are rejected by the parser
+    let truncated = |e: &Error| {
+        e.chain().any(|cause| {
+            matches!(
+                cause.downcast_ref::<ParseError>(),
+                Some(ParseError::TruncatedMessage)
+            )
+        })
+    };
+    match (udp, tcp) {
+        (Err(e), _) if truncated(&e) => CheckResult {
+            check,
+            outcome: Outcome::Passed,
+            detail: "the response was truncated, so the nameserver limits UDP responses itself"
+                .to_string(),
+        },
+        (Ok((_, len)), _) if len <= 512 => CheckResult {
+            check,
+            outcome: Outcome::Inconclusive,
+            detail: format!("the response over UDP was only {} bytes long", len),
+        },
+        (Ok((_, len)), _) => CheckResult {
+            check,
+            outcome: Outcome::Passed,
+            detail: format!("a response of {} bytes arrived over UDP", len),
+        },
+        (Err(e), Ok((_, len))) => CheckResult {
+            check,
+            outcome: Outcome::Detected(Interference::LargeResponsesDropped),
+            detail: format!(
+                "the query over UDP failed ({:#}), but a response of {} bytes arrived over TCP",
+                e, len
+            ),
+        },
+        (Err(e), Err(_)) => CheckResult {
+            check,
+            outcome: Outcome::Inconclusive,
+            detail: format!("the query failed over both UDP and TCP: {:#}", e),
+        },
+    }
+}
+
+fn check_case(sent: &Name, res: Result<Message>) -> CheckResult {
+    let check = Check::CasePreservation;
+    let received = res.and_then(|res| {
+        res.questions
+            .first()
+            .map(|question| question.qname.to_string())
+            .parse("the response has no question")
+    });
+    let sent = sent.to_string();
+    match received {
+        Ok(received) if received == sent => CheckResult {
+            check,
+            outcome: Outcome::Passed,
+            detail: format!("{} was repeated exactly", sent),
+        },
+        Ok(received) => CheckResult {
+            check,
+            outcome: Outcome::Detected(Interference::CaseNotPreserved),
+            detail: format!("sent {}, but the response has {}", sent, received),
+        },
+        Err(e) => CheckResult {
+            check,
+            outcome: Outcome::Inconclusive,
+            detail: format!("{:#}", e),
+        },
+    }
+}
+
+fn check_dnssec(res: Result<Message>) -> CheckResult {
+    let check = Check::Dnssec;
+    let res = match res {
+        Ok(res) => res,
+        Err(e) => {
+            return CheckResult {
+                check,
+                outcome: Outcome::Inconclusive,
+                detail: format!("the query failed: {:#}", e),
+            }
+        }
+    };
+    let has_rrsig = res
+        .answers
+        .iter()
+        .filter_map(|record| record.as_nonopt())
+        .any(|record| matches!(record.rdata(), Rdata::RRSIG(_)));
+    if has_rrsig {
+        CheckResult {
+            check,
+            outcome: Outcome::Passed,
+            detail: "the root SOA record came with its RRSIG".to_string(),
+        }
+    } else {
+        CheckResult {
+            check,
+            outcome: Outcome::Detected(Interference::DnssecStripped),
+            detail: "the root SOA record came without an RRSIG despite the DO bit".to_string(),
+        }
+    }
+}
+
+/// Returns `name` with the case of its letters chosen randomly, but not all lowercase.
+fn randomize_case(name: &Name) -> Name {
+    let mut text: Vec<u8> = name
+        .to_string()
+        .bytes()
+        .map(|c| match rand::random() {
+            true => c.to_ascii_uppercase(),
+            false => c.to_ascii_lowercase(),
+        })
+        .collect();
+    if !text.iter().any(u8::is_ascii_uppercase) {
+        if let Some(c) = text.iter_mut().find(|c| c.is_ascii_alphabetic()) {
+            c.make_ascii_uppercase();
+        }
+    }
+    let text = String::from_utf8(text).expect("only ASCII letters were changed");
+    Name::from_ascii(text).unwrap_or_else(|_| name.clone())
+}
@@ -0,0 +1,309 @@
+//! Multiplexing many concurrent queries over a single UDP socket or TCP connection.
+//!
+//! A [`Multiplexer`] keeps track of the outstanding queries by their message ID and question and
+//! dispatches each answer to the caller waiting for it, so thousands of queries can be in flight at
+//! once without opening a socket for each of them. Answers that don't match an outstanding query
+//! (e.g. late answers to queries that already timed out) are dropped.
+//!
+//! Truncated answers are passed on with only their header and question, so the caller can see the
+//! TC flag and send the query again via TCP.
+
+use crate::error::{Context, Error, Result};
+use crate::net::Nameserver;
+use crate::util::prepare_query;
+use crate::QueryMetadata;
+use byteorder::{NetworkEndian, WriteBytesExt};
+use std::collections::HashMap;
+use std::io::{self, Cursor, Read, Write};
+use std::net::{Shutdown, TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use toluol_proto::reader::MessageReader;
+use toluol_proto::{Header, Message, Question};
+
+/// How often the receiving thread checks whether the multiplexer was dropped.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Sends queries to a single nameserver over one UDP socket or TCP connection.
+///
+/// [`Multiplexer::query()`] may be called from many threads at once.
+#[derive(Debug)]
+pub struct Multiplexer {
+    shared: Arc<Shared>,
+    writer: Writer,
+    receiver: Option<JoinHandle<()>>,
+}
+
+#[derive(Debug)]
+enum Writer {
+    Udp(UdpSocket),
+    Tcp(Mutex<TcpStream>),
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    /// The outstanding queries, by message ID. There may be multiple queries with the same ID as
+    /// long as their questions differ.
+    pending: Mutex<HashMap<u16, Vec<Pending>>>,
+    stop: AtomicBool,
+}
+
+#[derive(Debug)]
+struct Pending {
+    question: Option<Question>,
+    answer: Sender<Message>,
+}
+
+impl Multiplexer {
+    /// Creates a multiplexer that sends queries to `nameserver` via UDP.
+    pub fn udp(nameserver: &Nameserver) -> Result<Self> {
+        let addr = nameserver
+            .to_socket_addrs()
+            .transport("Could not get socket address for nameserver.")?
+            .next()
+            .transport("Could not get socket address for nameserver.")?;
+        let bind_addr = if addr.is_ipv4() { "0.0.0.0" } else { "::" };
+        let socket = UdpSocket::bind((bind_addr, 0)).transport("Could not create UDP socket.")?;
+        socket
+            .connect(addr)
+            .transport(format!("Could not connect to {} via UDP.", nameserver))?;
+        socket
+            .set_read_timeout(Some(POLL_INTERVAL))
+            .transport("Could not set UDP socket read timeout.")?;
+
+        let reader = socket
+            .try_clone()
+            .transport("Could not clone UDP socket.")?;
+        let shared = Arc::new(Shared::default());
+        let receiver = {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || receive_udp(reader, &shared))
+        };
+        Ok(Self {
+            shared,
+            writer: Writer::Udp(socket),
+            receiver: Some(receiver),
+        })
+    }
+
+    /// Creates a multiplexer that sends queries to `nameserver` over a single TCP connection, which
+    /// is kept open until the multiplexer is dropped.
+    pub fn tcp(nameserver: &Nameserver) -> Result<Self> {
+        let addr = nameserver
+            .to_socket_addrs()
+            .transport("Could not get socket address for nameserver.")?
+            .next()
+            .transport("Could not get socket address for nameserver.")?;
+        let stream =
+            TcpStream::connect_timeout(&addr, Duration::from_secs(10)).transport(format!(
+                "Could not connect to {} via TCP, is the server running?",
+                nameserver
+            ))?;
+        stream
+            .set_write_timeout(Some(Duration::new(2, 0)))
+            .transport("Could not set TCP stream write timeout.")?;
+
+        let reader = stream
+            .try_clone()
+            .transport("Could not clone TCP stream.")?;
+        let shared = Arc::new(Shared::default());
+        let receiver = {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || receive_tcp(reader, &shared))
+        };
+        Ok(Self {
+            shared,
+            writer: Writer::Tcp(Mutex::new(stream)),
+            receiver: Some(receiver),
+        })
+    }
+
+    /// Sends the query specified by `metadata` and waits up to `timeout` for the answer.
+    ///
+    /// The query is not retransmitted, so with UDP, a lost query or answer results in a timeout. If
+    /// the answer is truncated, the returned message has the TC flag set and contains only the
+    /// header and the question.
+    ///
+    /// `metadata.nameserver`, `metadata.port`, and `metadata.connection_type` are ignored, as
+    /// they were given when creating the multiplexer.
+    pub fn query(
+        &self,
+        metadata: &QueryMetadata,
+        bufsize: u16,
+        timeout: Duration,
+    ) -> Result<Message> {
+        let mut query = prepare_query(metadata, bufsize)?;
+        let question = Message::parse(&mut Cursor::new(&query))
+            .parse("Could not parse query.")?
+            .questions
+            .into_iter()
+            .next();
+
+        let (sender, answer) = mpsc::channel();
+        let id = {
+            let mut pending = self.shared.pending.lock().unwrap();
+            // the ID must be unique among the outstanding queries with the same question, or we
+            // couldn't tell which query an answer belongs to
+            let id = loop {
+                let id: u16 = rand::random();
+                let in_use = pending
+                    .get(&id)
+                    .is_some_and(|queries| queries.iter().any(|q| q.question == question));
+                if !in_use {
+                    break id;
+                }
+            };
+            pending.entry(id).or_default().push(Pending {
+                question: question.clone(),
+                answer: sender,
+            });
+            id
+        };
+        query[..2].copy_from_slice(&id.to_be_bytes());
+
+        let res = self.send(&query).and_then(|()| {
+            answer.recv_timeout(timeout).map_err(|e| match e {
+                RecvTimeoutError::Timeout => {
+                    Error::transport("The nameserver did not reply in time.")
+                }
+                RecvTimeoutError::Disconnected => {
+                    Error::transport("The connection to the nameserver was closed.")
+                }
+            })
+        });
+        if res.is_err() {
+            self.shared.take(id, &question);
+        }
+        res
+    }
+
+    fn send(&self, query: &[u8]) -> Result<()> {
+        match &self.writer {
+            Writer::Udp(socket) => {
+                socket
+                    .send(query)
+                    .transport("Could not send data to nameserver.")?;
+            }
+            Writer::Tcp(stream) => {
+                let mut msg = Vec::with_capacity(query.len() + 2);
+                msg.write_u16::<NetworkEndian>(query.len() as u16)
+                    .expect("Writing to a Vec cannot fail.");
+                msg.extend_from_slice(query);
+                stream
+                    .lock()
+                    .unwrap()
+                    .write_all(&msg)
+                    .transport("Could not write data to TCP stream.")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Multiplexer {
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::Relaxed);
+        if let Writer::Tcp(stream) = &self.writer {
+            // unblocks the receiving thread
+            let _ = stream.lock().unwrap().shutdown(Shutdown::Both);
+        }
+        if let Some(receiver) = self.receiver.take() {
+            let _ = receiver.join();
+        }
+    }
+}
+
+impl Shared {
+    /// Removes the outstanding query with the given ID and question and returns the channel its
+    /// answer should be sent to.
+    fn take(&self, id: u16, question: &Option<Question>) -> Option<Sender<Message>> {
+        let mut pending = self.pending.lock().unwrap();
+        let queries = pending.get_mut(&id)?;
+        let idx = queries.iter().position(|q| &q.question == question)?;
+        let query = queries.swap_remove(idx);
+        if queries.is_empty() {
+            pending.remove(&id);
+        }
+        Some(query.answer)
+    }
+
+    /// Passes the answer `data` to the query waiting for it. Answers that can't be parsed or don't
+    /// belong to an outstanding query are dropped.
+    fn dispatch(&self, data: &[u8]) {
+        let answer = match Message::parse(&mut Cursor::new(data)) {
+            Ok(answer) => answer,
+            Err(_) => match parse_truncated(data) {
+                Some(answer) => answer,
+                None => return,
+            },
+        };
+        let question = answer.questions.first().cloned();
+        if let Some(sender) = self.take(answer.header.msg_id, &question) {
+            // the caller may have given up already, which is fine
+            let _ = sender.send(answer);
+        }
+    }
+
+    /// Wakes up all waiting callers by dropping their channels.
+    fn disconnect(&self) {
+        self.pending.lock().unwrap().clear();
+    }
+}
+
+/// Parses the header and question of a truncated answer, whose records may be incomplete.
+fn parse_truncated(data: &[u8]) -> Option<Message> {
+    let mut reader = MessageReader::new(data);
+    let header = Header::parse(&mut reader).ok()?;
+    if !header.flags.tc {
+        return None;
+    }
+    let questions = match header.qdcount {
+        0 => Vec::new(),
+        _ => vec![Question::parse(&mut reader).ok()?],
+    };
+    Some(Message {
+        header,
+        questions,
+        answers: Vec::new(),
+        authoritative_answers: Vec::new(),
+        additional_answers: Vec::new(),
+    })
+}
+
+fn receive_udp(socket: UdpSocket, shared: &Shared) {
+    let mut buf = vec![0; u16::MAX as usize];
+    while !shared.stop.load(Ordering::Relaxed) {
+        match socket.recv(&mut buf) {
+            Ok(len) => shared.dispatch(&buf[..len]),
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) => {}
+            // e.g. ICMP port unreachable; the affected queries will time out
+            Err(_) => {}
+        }
+    }
+    shared.disconnect();
+}
+
+fn receive_tcp(mut stream: TcpStream, shared: &Shared) {
+    // see RFC 1035, Section 4.2.2 for the message format
+    let mut read_message = || -> io::Result<Vec<u8>> {
+        let mut len = [0; 2];
+        stream.read_exact(&mut len)?;
+        let mut buf = vec![0; u16::from_be_bytes(len) as usize];
+        stream.read_exact(&mut buf)?;
+        Ok(buf)
+    };
+    while !shared.stop.load(Ordering::Relaxed) {
+        match read_message() {
+            Ok(msg) => shared.dispatch(&msg),
+            Err(_) => break,
+        }
+    }
+    shared.disconnect();
+}
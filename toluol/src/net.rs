@@ -1,18 +1,22 @@
 //! Network-related code, i.e. actually sending queries and receiving answers.
 
-use crate::QueryMetadata;
-use anyhow::{anyhow, bail, Context, Result};
+use crate::error::{bail, Context, Error, Result};
+use crate::stream::{ResponseStream, StreamEnd};
+use crate::{ConnectionType, QueryMetadata};
 use byteorder::{NetworkEndian, WriteBytesExt};
-use std::fmt::Display;
+use std::fmt::{Debug, Display};
 use std::io::{self, Read, Write};
-use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
-use std::time::{Duration, Instant};
+use std::net::{IpAddr, Ipv6Addr, SocketAddr, SocketAddrV6, TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
 
 #[cfg(feature = "tls")]
 use std::{convert::TryInto, sync::Arc};
 
 #[cfg(feature = "http")]
-use {crate::ConnectionType, data_encoding::BASE64URL_NOPAD};
+use data_encoding::BASE64URL_NOPAD;
+#[cfg(all(feature = "http", feature = "json"))]
+use toluol_proto::{Message, OptFlags};
 
 /// Contains all info needed to connect to a nameserver.
 #[derive(Clone, Debug)]
@@ -21,14 +25,419 @@ pub struct Nameserver {
     pub hostname: Option<String>,
     /// Nameserver's IP address. If this is [`None`], `nameserver_hostname` must be [`Some`].
     pub ip: Option<IpAddr>,
+    /// Scope ID (i.e. interface index) of the nameserver's IPv6 address. This is needed for
+    /// link-local addresses (e.g. `fe80::1%eth0`) and ignored for IPv4 addresses.
+    pub scope_id: Option<u32>,
     /// Nameserver's port.
     pub port: u16,
 }
 
+/// Timing information for a query, returned by the `send_query_*()` functions.
+#[derive(Clone, Debug)]
+pub struct QueryTiming {
+    /// When the query was (first) sent.
+    pub sent: SystemTime,
+    /// When the answer was received completely.
+    pub received: SystemTime,
+    /// Time between sending the query for the first time and receiving the answer. This is
+    /// measured with a monotonic clock and thus more accurate than `received - sent`.
+    pub elapsed: Duration,
+    /// Time it took to establish the connection before the query was sent: the TCP handshake,
+    /// and for DNS over TLS the TLS handshake as well. It is not part of `elapsed`. [`None`] if
+    /// no connection had to be established, e.g. for UDP or a reused connection, or if the
+    /// transport doesn't report it (DNS over HTTP(S)).
+    pub handshake: Option<Duration>,
+    /// The HTTP caching headers of the response for DNS over HTTP(S), [`None`] for the other
+    /// transports.
+    pub http_cache: Option<HttpCaching>,
+    /// How often the query was sent again because no answer arrived in time. This is only ever
+    /// non-zero for UDP.
+    pub retransmissions: u32,
+    /// The socket addresses the nameserver resolved to. For DNS over HTTP(S), this is empty, as
+    /// the HTTP client resolves the nameserver's hostname itself.
+    pub resolved_addrs: Vec<SocketAddr>,
+    /// Why the query was sent again via TCP after the answer via UDP was unusable, if it was. The
+    /// other fields describe the query via TCP then.
+    pub tcp_fallback: Option<String>,
+    /// Every transmission of the query in order, including retransmissions and fallbacks to
+    /// another transport. The last attempt is the one that was answered. This may be empty for
+    /// transports that don't report their attempts.
+    pub attempts: Vec<QueryAttempt>,
+}
+
+/// How a DNS over HTTP(S) response may be cached, from its `Cache-Control` and `Age` headers
+/// (see [RFC 8484, Section 5.1](https://www.rfc-editor.org/rfc/rfc8484#section-5.1)).
+///
+/// # Examples
+/// ```rust
+/// use toluol::net::HttpCaching;
+///
+/// let caching = HttpCaching::from_headers(Some("public, max-age=60"), Some("20"));
+/// assert_eq!(caching.max_age, Some(60));
+/// assert_eq!(caching.age, 20);
+/// assert!(!caching.no_store);
+/// assert!(HttpCaching::from_headers(Some("no-cache"), None).no_store);
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct HttpCaching {
+    /// The `max-age` directive: how many seconds after it was generated the response may be
+    /// used. [`None`] if it was not given, in which case only the TTLs of the records apply.
+    pub max_age: Option<u32>,
+    /// The `Age` header: how many seconds ago the response was generated, i.e. how long it was
+    /// kept by HTTP caches. The TTLs of the records are as old as well.
+    pub age: u32,
+    /// Whether the response must not be cached (`no-store`) or must not be used without
+    /// revalidation (`no-cache`), which toluol can't do.
+    pub no_store: bool,
+}
+
+impl HttpCaching {
+    /// Parses the values of the `Cache-Control` and `Age` headers, if the response had them.
+    /// Unknown and invalid directives are ignored.
+    pub fn from_headers(cache_control: Option<&str>, age: Option<&str>) -> Self {
+        let mut caching = Self {
+            age: age
+                .and_then(|age| age.trim().parse().ok())
+                .unwrap_or_default(),
+            ..Self::default()
+        };
+        for directive in cache_control.unwrap_or_default().split(',') {
+            let (name, value) = match directive.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+                None => (directive.trim(), None),
+            };
+            match (name.to_ascii_lowercase().as_str(), value) {
+                ("no-store" | "no-cache", _) => caching.no_store = true,
+                ("max-age", Some(value)) => {
+                    if let Ok(secs) = value.parse() {
+                        caching.max_age = Some(secs);
+                    }
+                }
+                _ => {}
+            }
+        }
+        caching
+    }
+
+    /// Returns the caching headers of `response`.
+    #[cfg(feature = "http")]
+    fn from_response(response: &ureq::Response) -> Self {
+        Self::from_headers(response.header("Cache-Control"), response.header("Age"))
+    }
+}
+
+impl QueryTiming {
+    /// Returns the transport that delivered the answer, i.e. the one of the last attempt.
+    pub fn transport(&self) -> Option<ConnectionType> {
+        self.attempts.last().map(|attempt| attempt.transport)
+    }
+
+    /// Describes the transports that were used, e.g. `UDP` or `UDP→TCP fallback`, or returns
+    /// [`None`] if there are no [`attempts`](Self::attempts).
+    pub fn transport_summary(&self) -> Option<String> {
+        let mut transports: Vec<ConnectionType> = Vec::new();
+        for attempt in &self.attempts {
+            if transports.last() != Some(&attempt.transport) {
+                transports.push(attempt.transport);
+            }
+        }
+        let summary: Vec<_> = transports.iter().map(|t| t.to_string()).collect();
+        match summary.len() {
+            0 => None,
+            1 => Some(summary.join("")),
+            _ => Some(format!("{} fallback", summary.join("→"))),
+        }
+    }
+}
+
+/// A single transmission of a query, see [`QueryTiming::attempts`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueryAttempt {
+    /// The transport the query was sent with.
+    pub transport: ConnectionType,
+    /// Time between sending the query and receiving the answer or giving up on it.
+    pub elapsed: Duration,
+    /// Why this attempt did not lead to the answer, or [`None`] if it did.
+    pub failure: Option<String>,
+}
+
+/// How often a UDP query is sent again by default if no answer arrives in time.
+pub const DEFAULT_UDP_RETRANSMISSIONS: u32 = 0;
+
+/// How long to wait for an answer to a UDP query by default before sending it again or giving up.
+pub const DEFAULT_UDP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Options for sending queries via UDP, see [`send_query_udp()`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct UdpOptions {
+    /// Whether to ignore datagrams that don't come from (one of the addresses of) the nameserver.
+    pub check_source: bool,
+    /// How long to wait for an answer before sending the query again.
+    pub timeout: Duration,
+    /// How often to send the query again if no answer arrives in time.
+    pub retransmissions: u32,
+    /// Whether to reuse a connected socket from an earlier query to the same nameserver, and to
+    /// keep the socket for later queries. Only used if `check_source` is true.
+    ///
+    /// This saves creating a socket (and an ephemeral port) for each query when many queries are
+    /// sent to the same nameservers, e.g. for iterative queries, but sends them all from the same
+    /// source port, which makes spoofed answers easier to guess.
+    pub reuse_socket: bool,
+}
+
+impl UdpOptions {
+    /// Returns the UDP options given by `metadata`.
+    pub fn from_metadata(metadata: &QueryMetadata) -> Self {
+        Self {
+            check_source: metadata.check_source,
+            timeout: metadata.timeout,
+            retransmissions: metadata.retransmissions,
+            reuse_socket: metadata.reuse_udp_sockets,
+        }
+    }
+}
+
+/// Options for the connections of queries via TCP and TLS, see [`send_query_tcp()`].
+///
+/// The defaults leave everything to the operating system. Except for `nodelay`, the options are
+/// only supported on Linux and ignored elsewhere.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TcpOptions {
+    /// Whether to disable Nagle's algorithm (`TCP_NODELAY`), so that small writes, e.g. those of
+    /// the TLS handshake, are sent without waiting for earlier ones to be acknowledged.
+    pub nodelay: bool,
+    /// Whether to use TCP Fast Open ([RFC 7413](https://www.rfc-editor.org/rfc/rfc7413)), which
+    /// sends the query along with the SYN if the nameserver handed out a cookie in an earlier
+    /// connection. The connection is then only established when the query is sent, so the
+    /// [handshake time](QueryTiming::handshake) is (close to) zero and the query RTT includes it.
+    pub fast_open: bool,
+    /// Whether to set `SO_REUSEADDR` on the socket.
+    pub reuse_address: bool,
+    /// The size of the socket's send buffer (`SO_SNDBUF`), if not the system's default.
+    pub send_buffer_size: Option<usize>,
+    /// The size of the socket's receive buffer (`SO_RCVBUF`), if not the system's default.
+    pub recv_buffer_size: Option<usize>,
+}
+
+impl TcpOptions {
+    /// Returns whether any option must be set before connecting, which needs a socket that is
+    /// created by hand rather than by [`TcpStream::connect_timeout()`].
+    fn needs_socket_options(&self) -> bool {
+        self.fast_open
+            || self.reuse_address
+            || self.send_buffer_size.is_some()
+            || self.recv_buffer_size.is_some()
+    }
+}
+
+/// The maximum number of sockets kept in [`UDP_SOCKETS`].
+const UDP_SOCKET_CACHE_SIZE: usize = 16;
+
+/// A connected UDP socket with the addresses and the scope ID of the nameserver it is connected to.
+type CachedUdpSocket = (Vec<SocketAddr>, Option<u32>, UdpSocket);
+
+/// Connected UDP sockets kept for reuse (see [`UdpOptions::reuse_socket`]). The least recently
+/// returned socket comes first.
+static UDP_SOCKETS: Mutex<Vec<CachedUdpSocket>> = Mutex::new(Vec::new());
+
+/// Removes a socket connected to the nameserver with `addrs` and `scope_id` from [`UDP_SOCKETS`]
+/// and returns it, if there is one. While a socket is in use, it is not in the cache, so
+/// concurrent queries never share a socket.
+fn take_cached_udp_socket(addrs: &[SocketAddr], scope_id: Option<u32>) -> Option<UdpSocket> {
+    let mut sockets = UDP_SOCKETS
+        .lock()
+        .expect("UDP socket cache lock is poisoned.");
+    let i = sockets
+        .iter()
+        .position(|(cached_addrs, cached_scope_id, _)| {
+            cached_addrs == addrs && *cached_scope_id == scope_id
+        })?;
+    Some(sockets.remove(i).2)
+}
+
+/// Returns `socket`, which is connected to the nameserver with `addrs` and `scope_id`, to
+/// [`UDP_SOCKETS`], dropping the least recently returned socket if the cache is full.
+fn cache_udp_socket(addrs: Vec<SocketAddr>, scope_id: Option<u32>, socket: UdpSocket) {
+    let mut sockets = UDP_SOCKETS
+        .lock()
+        .expect("UDP socket cache lock is poisoned.");
+    if sockets.len() >= UDP_SOCKET_CACHE_SIZE {
+        sockets.remove(0);
+    }
+    sockets.push((addrs, scope_id, socket));
+}
+
+/// Sends encoded queries to nameservers and receives the answers.
+///
+/// By default, toluol creates a new socket from the standard library for each query (see
+/// [`StdTransport`]). Implementing this trait allows embedders to send queries some other way, e.g.
+/// via sockets from a VPN library, via io_uring, or not at all in tests. A transport is used with
+/// [`send_query_via()`](crate::util::send_query_via()),
+/// [`query_single_via()`](crate::util::query_single_via()), and
+/// [`Resolver::with_transport()`](crate::resolver::Resolver::with_transport()).
+pub trait RawTransport: Debug + Send + Sync {
+    /// Sends the encoded query `data` to `nameserver` and returns the answer, the number of
+    /// received bytes, and timing information.
+    ///
+    /// `metadata` describes how to send the query, e.g. its connection type and UDP timeout. The
+    /// answer must not be longer than `bufsize` bytes. If the transport knows which address the
+    /// answer came from, it should set `nameserver.ip` accordingly.
+    fn send_query(
+        &self,
+        metadata: &QueryMetadata,
+        bufsize: u16,
+        nameserver: &mut Nameserver,
+        data: &[u8],
+    ) -> Result<(Vec<u8>, u16, QueryTiming)>;
+
+    /// The same as [`Self::send_query()`], but if the transport sends the query more than once,
+    /// each transmission sends the query returned by `make_query`, which is called with the number
+    /// of the retransmission (0 for the first transmission). See [`send_query_udp_retrying()`] for
+    /// why this matters.
+    ///
+    /// The default implementation sends the first query via [`Self::send_query()`].
+    fn send_query_retrying(
+        &self,
+        metadata: &QueryMetadata,
+        bufsize: u16,
+        nameserver: &mut Nameserver,
+        make_query: &mut dyn FnMut(u32) -> Result<Vec<u8>>,
+    ) -> Result<(Vec<u8>, u16, QueryTiming)> {
+        let data = make_query(0)?;
+        self.send_query(metadata, bufsize, nameserver, &data)
+    }
+
+    /// Sends the encoded query `data` to `nameserver` and returns the answers as a stream, for
+    /// queries whose response spans several messages, e.g. zone transfers. `end` determines when
+    /// the stream ends.
+    ///
+    /// The default implementation returns an error, as most transports only support a single
+    /// answer.
+    fn send_query_stream(
+        &self,
+        metadata: &QueryMetadata,
+        bufsize: u16,
+        nameserver: &mut Nameserver,
+        data: &[u8],
+        end: StreamEnd,
+    ) -> Result<ResponseStream> {
+        let _ = (metadata, bufsize, nameserver, data, end);
+        bail!(
+            transport,
+            "{:?} does not support responses that span several messages.",
+            self
+        )
+    }
+}
+
+/// The default [`RawTransport`], which sends each query via a new socket from the standard library
+/// (or via `ureq` for DNS over HTTP(S)), using the connection type given by the metadata.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct StdTransport;
+
+impl RawTransport for StdTransport {
+    fn send_query(
+        &self,
+        metadata: &QueryMetadata,
+        bufsize: u16,
+        nameserver: &mut Nameserver,
+        data: &[u8],
+    ) -> Result<(Vec<u8>, u16, QueryTiming)> {
+        match metadata.connection_type {
+            ConnectionType::Udp => {
+                let options = UdpOptions::from_metadata(metadata);
+                let res = send_query_udp(nameserver, bufsize, data, &options);
+                fall_back_to_tcp(res, nameserver, data, &metadata.tcp_options)
+            }
+            ConnectionType::Tcp => send_query_tcp(nameserver, data, &metadata.tcp_options),
+            #[cfg(feature = "tls")]
+            ConnectionType::Tls => send_query_tls(nameserver, data, &metadata.tcp_options),
+            #[cfg(feature = "http")]
+            ConnectionType::HttpGet
+            | ConnectionType::HttpPost
+            | ConnectionType::HttpsGet
+            | ConnectionType::HttpsPost => {
+                #[cfg(feature = "json")]
+                let before = Instant::now();
+                let res = send_query_http(nameserver, metadata.connection_type, bufsize, data);
+                #[cfg(feature = "json")]
+                if metadata.doh_json_fallback && is_wire_format_rejected(&res) {
+                    let rejected = QueryAttempt {
+                        transport: metadata.connection_type,
+                        elapsed: before.elapsed(),
+                        failure: res.as_ref().err().map(|e| e.root_cause().to_string()),
+                    };
+                    let (answer, len, mut timing) =
+                        send_query_http_json(nameserver, metadata.connection_type, data)?;
+                    timing.attempts.insert(0, rejected);
+                    return Ok((answer, len, timing));
+                }
+                res
+            }
+        }
+    }
+
+    fn send_query_retrying(
+        &self,
+        metadata: &QueryMetadata,
+        bufsize: u16,
+        nameserver: &mut Nameserver,
+        make_query: &mut dyn FnMut(u32) -> Result<Vec<u8>>,
+    ) -> Result<(Vec<u8>, u16, QueryTiming)> {
+        if metadata.connection_type != ConnectionType::Udp {
+            let data = make_query(0)?;
+            return self.send_query(metadata, bufsize, nameserver, &data);
+        }
+        let options = UdpOptions::from_metadata(metadata);
+        let mut last_query = Vec::new();
+        let make_query = |retransmission| {
+            let query = make_query(retransmission)?;
+            last_query.clone_from(&query);
+            Ok(query)
+        };
+        let res = send_query_udp_retrying(nameserver, bufsize, make_query, &options);
+        fall_back_to_tcp(res, nameserver, &last_query, &metadata.tcp_options)
+    }
+
+    fn send_query_stream(
+        &self,
+        metadata: &QueryMetadata,
+        bufsize: u16,
+        nameserver: &mut Nameserver,
+        data: &[u8],
+        end: StreamEnd,
+    ) -> Result<ResponseStream> {
+        match metadata.connection_type {
+            ConnectionType::Udp => {
+                ResponseStream::udp(nameserver, bufsize, data, end, metadata.timeout)
+            }
+            ConnectionType::Tcp => ResponseStream::tcp(nameserver, data, end, metadata.timeout),
+            #[allow(unreachable_patterns)]
+            connection_type => bail!(
+                transport,
+                "Responses that span several messages are not supported via {}.",
+                connection_type
+            ),
+        }
+    }
+}
+
 impl Nameserver {
     /// Use the information from `metadata` to create a `Nameserver`.
-    pub fn from_metadata(metadata: &QueryMetadata) -> Self {
-        let ip = metadata.nameserver.parse().ok();
+    ///
+    /// IPv6 addresses may be followed by a scope ID, which is either an interface name or index,
+    /// e.g. `fe80::1%eth0` or `fe80::1%2`.
+    pub fn from_metadata(metadata: &QueryMetadata) -> Result<Self> {
+        let (ip, scope_id) = match metadata.nameserver.split_once('%') {
+            Some((addr, scope)) => {
+                let ip: Ipv6Addr = addr.parse().configuration(format!(
+                    "A scope ID can only be given for IPv6 addresses, got: {}",
+                    metadata.nameserver
+                ))?;
+                (Some(IpAddr::V6(ip)), Some(parse_scope_id(scope)?))
+            }
+            None => (metadata.nameserver.parse().ok(), None),
+        };
         let hostname = if ip.is_some() {
             // TODO: this might be suboptimal, e.g. for TLS certificates, the cert hostname might be 1.1.1.1
             // use webpki::DnsNameRef to validate? (note: that crate currently does not support IP addresses)
@@ -37,22 +446,73 @@ impl Nameserver {
             Some(metadata.nameserver.clone())
         };
 
-        Self {
+        Ok(Self {
             ip,
             hostname,
+            scope_id,
             port: metadata.port,
+        })
+    }
+
+    /// Returns all socket addresses of this nameserver, resolving its hostname if necessary.
+    pub(crate) fn resolve(&self) -> Result<Vec<SocketAddr>> {
+        let addrs: Vec<_> = self
+            .to_socket_addrs()
+            .transport("Could not get socket address for nameserver.")?
+            .collect();
+        if addrs.is_empty() {
+            bail!(transport, "Could not get socket address for nameserver.");
+        }
+        Ok(addrs)
+    }
+
+    /// Returns the address a socket used to connect to this nameserver should be bound to, if it
+    /// is known.
+    fn bind_addr(&self) -> Option<SocketAddr> {
+        match self.ip? {
+            IpAddr::V6(_) => Some(
+                SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, 0, 0, self.scope_id.unwrap_or(0)).into(),
+            ),
+            IpAddr::V4(_) => Some(([0, 0, 0, 0], 0).into()),
         }
     }
 }
 
+/// Parses an IPv6 scope ID, which is either a numeric interface index or an interface name.
+fn parse_scope_id(scope: &str) -> Result<u32> {
+    if let Ok(idx) = scope.parse() {
+        return Ok(idx);
+    }
+
+    #[cfg(unix)]
+    {
+        let name = std::ffi::CString::new(scope)
+            .configuration(format!("Invalid network interface name: {}", scope))?;
+        // SAFETY: `name` is a valid, NUL-terminated C string that outlives the call
+        let idx = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        if idx == 0 {
+            bail!(configuration, "Unknown network interface: {}", scope);
+        }
+        Ok(idx)
+    }
+
+    #[cfg(not(unix))]
+    bail!(
+        configuration,
+        "Invalid scope ID (must be an interface index): {}",
+        scope
+    )
+}
+
 impl Display for Nameserver {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let ip = self.ip.map(|ip| {
             // if ip is an IPv6 address, check if it is a mapped IPv4 adress. if yes, display the
             // IPv4 address
             match ip {
-                IpAddr::V6(ipv6) => match ipv6.to_ipv4() {
-                    Some(ipv4) if ipv4.to_ipv6_mapped() == ipv6 => ipv4.to_string(),
+                IpAddr::V6(ipv6) => match (ipv6.to_ipv4(), self.scope_id) {
+                    (Some(ipv4), _) if ipv4.to_ipv6_mapped() == ipv6 => ipv4.to_string(),
+                    (_, Some(scope_id)) => format!("[{}%{}]", ipv6, scope_id),
                     _ => format!("[{}]", ipv6),
                 },
                 _ => ip.to_string(),
@@ -79,55 +539,459 @@ impl ToSocketAddrs for Nameserver {
     type Iter = std::vec::IntoIter<SocketAddr>;
     fn to_socket_addrs(&self) -> std::io::Result<Self::Iter> {
         if let Some(ip) = self.ip {
-            Ok(vec![(ip, self.port).into()].into_iter())
+            let addr = match ip {
+                IpAddr::V6(ip) => {
+                    SocketAddrV6::new(ip, self.port, 0, self.scope_id.unwrap_or(0)).into()
+                }
+                IpAddr::V4(ip) => (ip, self.port).into(),
+            };
+            Ok(vec![addr].into_iter())
         } else if let Some(hostname) = &self.hostname {
             (hostname.as_str(), self.port).to_socket_addrs()
         } else {
-            Err(io::Error::new(
-                io::ErrorKind::Other,
-                anyhow!("Nameserver has neither IP nor hostname"),
-            ))
+            Err(io::Error::other("Nameserver has neither IP nor hostname"))
+        }
+    }
+}
+
+/// The error returned by [`send_query_udp()`] if the nameserver ignored the advertised UDP payload
+/// size and sent a larger answer. Such answers are not used, as they may have been cut off on the
+/// way; [`StdTransport`] sends the query again via TCP instead.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OversizedAnswer {
+    /// The size of the answer in bytes.
+    pub size: usize,
+    /// The advertised UDP payload size.
+    pub bufsize: u16,
+    /// The transmissions via UDP, the last of which was answered with the oversized answer.
+    pub attempts: Vec<QueryAttempt>,
+}
+
+impl Display for OversizedAnswer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The nameserver sent a {}-byte answer via UDP, although only {} bytes were advertised.",
+            self.size, self.bufsize
+        )
+    }
+}
+
+impl std::error::Error for OversizedAnswer {}
+
+/// The step of exchanging messages with a nameserver that failed, see [`NetworkError`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum NetworkErrorKind {
+    /// Creating the socket failed.
+    CreateSocket,
+    /// Connecting to the nameserver failed, e.g. as there is no route to it.
+    Connect,
+    /// Sending the query failed.
+    Send,
+    /// Receiving the answer failed.
+    Receive,
+    /// The nameserver did not answer in time.
+    Timeout,
+    /// Nothing is listening on the nameserver's port, which was reported via ICMP.
+    PortUnreachable,
+}
+
+/// The error returned by [`send_query_udp()`] and [`send_query_tcp()`] if the query could not be
+/// sent or its answer could not be received, with details about the socket to find out why, e.g.
+/// when another tool can reach the nameserver.
+///
+/// It is wrapped in [`Error::Network`].
+#[derive(Debug)]
+pub struct NetworkError {
+    pub kind: NetworkErrorKind,
+    /// [`ConnectionType::Udp`] or [`ConnectionType::Tcp`].
+    pub transport: ConnectionType,
+    /// The nameserver, as displayed by [`Nameserver`].
+    pub nameserver: String,
+    /// The address of the nameserver the socket sent to or connected to, if it got that far.
+    pub remote_addr: Option<SocketAddr>,
+    /// The local address of the socket, if it was created. For connected sockets, this contains
+    /// the source address the OS selected for reaching the nameserver.
+    pub local_addr: Option<SocketAddr>,
+    /// Whether an IPv6 socket was used or tried. For nameservers given by hostname, toluol tries
+    /// IPv6 first and falls back to IPv4.
+    pub ipv6_attempted: bool,
+    /// The error reported by the OS, if any.
+    pub source: Option<io::Error>,
+}
+
+impl NetworkError {
+    /// Returns what is known about the socket as pairs of a description and a value, e.g. for
+    /// displaying it in a bug report.
+    pub fn diagnostics(&self) -> Vec<(&'static str, String)> {
+        let unknown = || "unknown".to_string();
+        let mut diagnostics = vec![
+            ("Transport", self.transport.to_string()),
+            ("Nameserver", self.nameserver.clone()),
+            (
+                "Remote address",
+                self.remote_addr
+                    .map_or_else(unknown, |addr| addr.to_string()),
+            ),
+            (
+                "Local address",
+                self.local_addr
+                    .map_or_else(unknown, |addr| addr.to_string()),
+            ),
+            (
+                "IPv6 attempted",
+                if self.ipv6_attempted { "yes" } else { "no" }.to_string(),
+            ),
+        ];
+        if let Some(e) = &self.source {
+            diagnostics.push(("OS error", format!("{:?}: {}", e.kind(), e)));
+        }
+        diagnostics
+    }
+}
+
+impl Display for NetworkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            NetworkErrorKind::CreateSocket => {
+                write!(f, "Could not create {} socket.", self.transport)
+            }
+            NetworkErrorKind::Connect if self.transport == ConnectionType::Tcp => write!(
+                f,
+                "Could not connect to {} via TCP, is the server running?",
+                self.nameserver
+            ),
+            NetworkErrorKind::Connect => write!(
+                f,
+                "Could not connect to {} via {}.",
+                self.nameserver, self.transport
+            ),
+            NetworkErrorKind::Send => write!(f, "Could not send data to nameserver."),
+            NetworkErrorKind::Receive => write!(f, "Could not receive data from nameserver."),
+            NetworkErrorKind::Timeout => write!(f, "The nameserver did not reply in time."),
+            NetworkErrorKind::PortUnreachable => write!(
+                f,
+                "The nameserver is not listening: {} rejected the query (ICMP port unreachable).",
+                self.nameserver
+            ),
         }
     }
 }
 
+impl std::error::Error for NetworkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|e| e as &(dyn std::error::Error + 'static))
+    }
+}
+
+/// What is known about a socket for a [`NetworkError`].
+struct SocketInfo {
+    nameserver: String,
+    transport: ConnectionType,
+    remote_addr: Option<SocketAddr>,
+    local_addr: Option<SocketAddr>,
+    ipv6_attempted: bool,
+}
+
+impl SocketInfo {
+    fn new(nameserver: &Nameserver, transport: ConnectionType) -> Self {
+        Self {
+            nameserver: nameserver.to_string(),
+            transport,
+            remote_addr: None,
+            local_addr: None,
+            ipv6_attempted: false,
+        }
+    }
+
+    /// Notes the local address of the socket, if it could be determined.
+    fn with_local_addr(mut self, local_addr: io::Result<SocketAddr>) -> Self {
+        if let Ok(addr) = local_addr {
+            self.ipv6_attempted |= addr.is_ipv6();
+            self.local_addr = Some(addr);
+        }
+        self
+    }
+
+    fn error(&self, kind: NetworkErrorKind, source: Option<io::Error>) -> NetworkError {
+        NetworkError {
+            kind,
+            transport: self.transport,
+            nameserver: self.nameserver.clone(),
+            remote_addr: self.remote_addr,
+            local_addr: self.local_addr,
+            ipv6_attempted: self.ipv6_attempted,
+            source,
+        }
+    }
+}
+
+/// If `res` failed with [`OversizedAnswer`], sends `data` to `nameserver` again via TCP and notes
+/// the reason in [`QueryTiming::tcp_fallback`].
+fn fall_back_to_tcp(
+    res: Result<(Vec<u8>, u16, QueryTiming)>,
+    nameserver: &mut Nameserver,
+    data: &[u8],
+    options: &TcpOptions,
+) -> Result<(Vec<u8>, u16, QueryTiming)> {
+    let oversized = match res {
+        Err(Error::OversizedAnswer(oversized)) => oversized,
+        res => return res,
+    };
+    let (answer, len, mut timing) = send_query_tcp(nameserver, data, options)
+        .transport(format!("{} Retrying via TCP failed.", oversized))?;
+    timing.tcp_fallback = Some(oversized.to_string());
+    timing.attempts.splice(0..0, oversized.attempts);
+    Ok((answer, len, timing))
+}
+
+/// Sends `data` to `nameserver` via UDP and returns the answer, the number of received bytes, and
+/// timing information.
+///
+/// Returns an [`OversizedAnswer`] error if the answer is larger than `bufsize`, which should be
+/// the UDP payload size advertised in `data`.
+///
+/// If no answer arrives within `options.timeout`, the same query is sent again (up to
+/// `options.retransmissions` times). Prefer [`send_query_udp_retrying()`], which uses a new
+/// message ID for each transmission.
+///
+/// Datagrams whose message ID differs from the one of `data` are ignored.
+///
+/// If `options.check_source` is true, datagrams that don't come from (one of the addresses of)
+/// `nameserver` are ignored. Otherwise, the first datagram that arrives is taken as the answer,
+/// which is needed for servers that reply from a different address than the one the query was
+/// sent to.
 pub fn send_query_udp(
     nameserver: &mut Nameserver,
     bufsize: u16,
     data: &[u8],
-) -> Result<(Vec<u8>, u16, Duration)> {
-    let socket = create_and_connect_udp_socket(nameserver)?;
-    let mut res = vec![0; bufsize as usize]; // the query sets this as max size
+    options: &UdpOptions,
+) -> Result<(Vec<u8>, u16, QueryTiming)> {
+    send_query_udp_retrying(nameserver, bufsize, |_| Ok(data.to_vec()), options)
+}
 
-    socket
-        .set_write_timeout(Some(Duration::new(2, 0)))
-        .context("Could not set UDP socket write timeout.")?;
-    socket
-        .set_read_timeout(Some(Duration::new(10, 0)))
-        .context("Could not set UDP socket read timeout.")?;
+/// The same as [`send_query_udp()`], but each transmission sends the query returned by
+/// `make_query`, which is called with the number of the retransmission (0 for the first
+/// transmission).
+///
+/// `make_query` should return a query with a new message ID each time (see
+/// [`prepare_query()`](crate::util::prepare_query())). Only an answer with the ID of the most
+/// recently sent query is accepted, so answers to earlier transmissions are rejected. This keeps an
+/// attacker from gaining more chances to guess the ID of a spoofed answer through retransmissions,
+/// which all stay open until an answer arrives.
+pub fn send_query_udp_retrying(
+    nameserver: &mut Nameserver,
+    bufsize: u16,
+    mut make_query: impl FnMut(u32) -> Result<Vec<u8>>,
+    options: &UdpOptions,
+) -> Result<(Vec<u8>, u16, QueryTiming)> {
+    let check_source = options.check_source;
+    let resolved_addrs = nameserver.resolve()?;
+    let reuse_socket = check_source && options.reuse_socket;
+    let cached = if reuse_socket {
+        take_cached_udp_socket(&resolved_addrs, nameserver.scope_id)
+    } else {
+        None
+    };
+    // a connected socket only receives datagrams from the nameserver and also receives ICMP errors,
+    // so we only leave the socket unconnected if we want to accept answers from anywhere
+    let (socket, target) = if let Some(socket) = cached {
+        (socket, None)
+    } else if check_source {
+        (
+            create_and_connect_udp_socket(nameserver, &resolved_addrs)?,
+            None,
+        )
+    } else {
+        let target = resolved_addrs[0];
+        (create_udp_socket_for(nameserver, target)?, Some(target))
+    };
+    let mut info =
+        SocketInfo::new(nameserver, ConnectionType::Udp).with_local_addr(socket.local_addr());
+    info.remote_addr = target.or_else(|| socket.peer_addr().ok());
+    // the query sets bufsize as max size, but some nameservers ignore it, so we receive as much as
+    // possible to detect this instead of silently cutting off the answer
+    let mut res = vec![0; u16::MAX as usize];
 
     socket
-        .connect(nameserver as &Nameserver)
-        .context(format!("Could not connect to {} via UDP.", nameserver))?;
+        .set_write_timeout(Some(Duration::new(2, 0)))
+        .transport("Could not set UDP socket write timeout.")?;
 
+    let sent = SystemTime::now();
     let before = Instant::now();
-    socket
-        .send(data)
-        .context("Could not send data to nameserver.")?;
+    let mut retransmissions = 0;
+    let mut attempts = Vec::new();
+    let (bytes_recvd, remote_addr) = loop {
+        let data = make_query(retransmissions)?;
+        let attempt_start = Instant::now();
+        match target {
+            Some(target) => socket.send_to(&data, target),
+            None => socket.send(&data),
+        }
+        .map_err(|e| info.error(NetworkErrorKind::Send, Some(e)))?;
 
-    let (bytes_recvd, remote_addr) = socket
-        .recv_from(&mut res)
-        .context("The nameserver did not reply in time.")?;
+        let expected_sources = check_source.then_some(&resolved_addrs[..]);
+        let expected_id = message_id(&data);
+        match recv_udp_answer(
+            &socket,
+            &mut res,
+            options.timeout,
+            expected_sources,
+            expected_id,
+        ) {
+            Ok(Some(recvd)) => {
+                attempts.push(QueryAttempt {
+                    transport: ConnectionType::Udp,
+                    elapsed: attempt_start.elapsed(),
+                    failure: None,
+                });
+                break recvd;
+            }
+            Ok(None) if retransmissions < options.retransmissions => {
+                attempts.push(QueryAttempt {
+                    transport: ConnectionType::Udp,
+                    elapsed: attempt_start.elapsed(),
+                    failure: Some("no answer in time".to_string()),
+                });
+                retransmissions += 1;
+            }
+            Ok(None) => return Err(info.error(NetworkErrorKind::Timeout, None).into()),
+            Err(e) if is_port_unreachable(&e) => {
+                return Err(info
+                    .error(NetworkErrorKind::PortUnreachable, Some(e))
+                    .into())
+            }
+            Err(e) => return Err(info.error(NetworkErrorKind::Receive, Some(e)).into()),
+        }
+    };
     let elapsed = before.elapsed();
+    let received = SystemTime::now();
 
     nameserver.ip = Some(remote_addr.ip());
+    if reuse_socket {
+        // late answers to this query that arrive on the socket are ignored by later queries, as
+        // their message IDs differ
+        cache_udp_socket(resolved_addrs.clone(), nameserver.scope_id, socket);
+    }
 
+    if bytes_recvd > bufsize as usize {
+        if let Some(attempt) = attempts.last_mut() {
+            attempt.failure = Some(format!("{}-byte answer was too large", bytes_recvd));
+        }
+        return Err(OversizedAnswer {
+            size: bytes_recvd,
+            bufsize,
+            attempts,
+        }
+        .into());
+    }
     res.resize(bytes_recvd, 0);
 
-    Ok((res, bytes_recvd as u16, elapsed))
+    let timing = QueryTiming {
+        sent,
+        received,
+        elapsed,
+        handshake: None,
+        http_cache: None,
+        retransmissions,
+        resolved_addrs,
+        tcp_fallback: None,
+        attempts,
+    };
+    Ok((res, bytes_recvd as u16, timing))
+}
+
+/// Returns the message ID of the encoded message `data`, or [`None`] if it is too short.
+fn message_id(data: &[u8]) -> Option<u16> {
+    Some(u16::from_be_bytes([*data.first()?, *data.get(1)?]))
+}
+
+/// Waits up to `timeout` for a datagram and returns its size and source address, or [`None`] if
+/// nothing arrived in time.
+///
+/// If `expected_sources` is given, datagrams from other addresses are ignored. If `expected_id` is
+/// given, datagrams with another message ID are ignored.
+fn recv_udp_answer(
+    socket: &UdpSocket,
+    buf: &mut [u8],
+    timeout: Duration,
+    expected_sources: Option<&[SocketAddr]>,
+    expected_id: Option<u16>,
+) -> io::Result<Option<(usize, SocketAddr)>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(None);
+        }
+        socket.set_read_timeout(Some(remaining))?;
+
+        match socket.recv_from(buf) {
+            Ok((bytes_recvd, source)) => {
+                let expected = expected_sources.is_none_or(|addrs| {
+                    addrs.iter().any(|addr| {
+                        addr.ip().to_canonical() == source.ip().to_canonical()
+                            && addr.port() == source.port()
+                    })
+                });
+                let expected = expected
+                    && expected_id.is_none_or(|id| message_id(&buf[..bytes_recvd]) == Some(id));
+                if expected {
+                    return Ok(Some((bytes_recvd, source)));
+                }
+            }
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                return Ok(None)
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Returns true if `e` was caused by an ICMP port unreachable message, i.e. nothing is listening on
+/// the nameserver's port. Linux reports this as `ECONNREFUSED`, Windows as `WSAECONNRESET`.
+fn is_port_unreachable(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::ConnectionRefused | io::ErrorKind::ConnectionReset
+    )
+}
+
+/// Creates an unconnected UDP socket that can send to `target`.
+pub(crate) fn create_udp_socket_for(
+    nameserver: &Nameserver,
+    target: SocketAddr,
+) -> Result<UdpSocket> {
+    let bind_addr: SocketAddr = match target {
+        SocketAddr::V6(_) => SocketAddrV6::new(
+            Ipv6Addr::UNSPECIFIED,
+            0,
+            0,
+            nameserver.scope_id.unwrap_or(0),
+        )
+        .into(),
+        SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+    };
+    UdpSocket::bind(bind_addr).map_err(|e| {
+        let mut info = SocketInfo::new(nameserver, ConnectionType::Udp);
+        info.remote_addr = Some(target);
+        info.ipv6_attempted = target.is_ipv6();
+        info.error(NetworkErrorKind::CreateSocket, Some(e)).into()
+    })
 }
 
-fn create_and_connect_udp_socket(nameserver: &Nameserver) -> Result<UdpSocket> {
+fn create_and_connect_udp_socket(
+    nameserver: &Nameserver,
+    addrs: &[SocketAddr],
+) -> Result<UdpSocket, NetworkError> {
     // on windows, binding a UDP socket to :: and trying to connect to an IPv4 address or a hostname
     // on a machine that has no IPv6 internet connection gives this helpful error message:
     // "The system detected an invalid pointer address in attempting to use a pointer argument in a
@@ -136,97 +1000,279 @@ fn create_and_connect_udp_socket(nameserver: &Nameserver) -> Result<UdpSocket> {
     // we only have a hostname, we try IPv6 first and try again with IPv4 if that first try fails.
     // this is (to my knowledge) not necessary on linux, but it won't hurt to do this regardless of
     // which OS we're running on.
-    if let Some(ip_addr) = nameserver.ip {
-        let bind_addr = if ip_addr.is_ipv6() { "::" } else { "0.0.0.0" };
-        UdpSocket::bind((bind_addr, 0)).context("Could not create UDP socket.")
+    let bind_addrs: Vec<SocketAddr> = match nameserver.bind_addr() {
+        Some(bind_addr) => vec![bind_addr],
+        None => vec![(Ipv6Addr::UNSPECIFIED, 0).into(), ([0, 0, 0, 0], 0).into()],
+    };
+    let mut info = SocketInfo::new(nameserver, ConnectionType::Udp);
+    if let [addr] = addrs {
+        info.remote_addr = Some(*addr);
+    }
+    let mut err = None;
+    for bind_addr in bind_addrs {
+        info.ipv6_attempted |= bind_addr.is_ipv6();
+        let socket = UdpSocket::bind(bind_addr)
+            .map_err(|e| info.error(NetworkErrorKind::CreateSocket, Some(e)))?;
+        info = info.with_local_addr(socket.local_addr());
+        match socket.connect(addrs) {
+            Ok(()) => return Ok(socket),
+            Err(e) => err = Some(e),
+        }
+    }
+
+    Err(info.error(NetworkErrorKind::Connect, err))
+}
+
+/// Opens a TCP connection to `addr` with `options`, waiting at most `timeout` for it to be
+/// established.
+pub(crate) fn connect_tcp(
+    addr: &SocketAddr,
+    timeout: Duration,
+    options: &TcpOptions,
+) -> io::Result<TcpStream> {
+    #[cfg(target_os = "linux")]
+    let stream = if options.needs_socket_options() {
+        connect_tcp_with_socket_options(addr, timeout, options)?
     } else {
-        let mut err = None;
-        for bind_addr in ["::", "0.0.0.0"] {
-            let socket = UdpSocket::bind((bind_addr, 0)).context("Could not create UDP socket.")?;
-            match socket.connect(nameserver as &Nameserver) {
-                Ok(()) => return Ok(socket),
-                Err(e) => err = Some(e),
-            }
+        TcpStream::connect_timeout(addr, timeout)?
+    };
+    #[cfg(not(target_os = "linux"))]
+    let stream = TcpStream::connect_timeout(addr, timeout)?;
+
+    if options.nodelay {
+        stream.set_nodelay(true)?;
+    }
+    Ok(stream)
+}
+
+/// Like [`TcpStream::connect_timeout()`], but sets the options of `options` that only have an
+/// effect if they are set before connecting.
+#[cfg(target_os = "linux")]
+fn connect_tcp_with_socket_options(
+    addr: &SocketAddr,
+    timeout: Duration,
+    options: &TcpOptions,
+) -> io::Result<TcpStream> {
+    use std::mem::size_of;
+    use std::os::unix::io::FromRawFd;
+
+    let family = match addr {
+        SocketAddr::V4(_) => libc::AF_INET,
+        SocketAddr::V6(_) => libc::AF_INET6,
+    };
+    // SAFETY: socket() has no preconditions; its result is checked below
+    let fd = unsafe {
+        libc::socket(
+            family,
+            libc::SOCK_STREAM | libc::SOCK_CLOEXEC | libc::SOCK_NONBLOCK,
+            0,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `fd` is a new socket that nothing else owns, so the stream may close it
+    let stream = unsafe { TcpStream::from_raw_fd(fd) };
+
+    let set_option = |level, name, value: libc::c_int| {
+        // SAFETY: all of these options take a c_int, and `value` outlives the call
+        let res = unsafe {
+            libc::setsockopt(
+                fd,
+                level,
+                name,
+                &value as *const libc::c_int as *const libc::c_void,
+                size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+        if res < 0 {
+            return Err(io::Error::last_os_error());
         }
+        Ok(())
+    };
+    let buffer_size = |size: usize| size.try_into().unwrap_or(libc::c_int::MAX);
+    if options.reuse_address {
+        set_option(libc::SOL_SOCKET, libc::SO_REUSEADDR, 1)?;
+    }
+    if let Some(size) = options.send_buffer_size {
+        set_option(libc::SOL_SOCKET, libc::SO_SNDBUF, buffer_size(size))?;
+    }
+    if let Some(size) = options.recv_buffer_size {
+        set_option(libc::SOL_SOCKET, libc::SO_RCVBUF, buffer_size(size))?;
+    }
+    if options.fast_open {
+        // connect() then returns at once, and the SYN is sent with the first write
+        set_option(libc::IPPROTO_TCP, libc::TCP_FASTOPEN_CONNECT, 1)?;
+    }
 
-        Err(err.unwrap()).context(format!("Could not connect to {} via UDP.", nameserver))
+    let res = match addr {
+        SocketAddr::V4(addr) => {
+            let sockaddr = libc::sockaddr_in {
+                sin_family: libc::AF_INET as libc::sa_family_t,
+                sin_port: addr.port().to_be(),
+                sin_addr: libc::in_addr {
+                    s_addr: u32::from_ne_bytes(addr.ip().octets()),
+                },
+                sin_zero: [0; 8],
+            };
+            // SAFETY: `sockaddr` is a valid sockaddr_in of the given length
+            unsafe {
+                libc::connect(
+                    fd,
+                    &sockaddr as *const libc::sockaddr_in as *const libc::sockaddr,
+                    size_of::<libc::sockaddr_in>() as libc::socklen_t,
+                )
+            }
+        }
+        SocketAddr::V6(addr) => {
+            let sockaddr = libc::sockaddr_in6 {
+                sin6_family: libc::AF_INET6 as libc::sa_family_t,
+                sin6_port: addr.port().to_be(),
+                sin6_flowinfo: addr.flowinfo(),
+                sin6_addr: libc::in6_addr {
+                    s6_addr: addr.ip().octets(),
+                },
+                sin6_scope_id: addr.scope_id(),
+            };
+            // SAFETY: `sockaddr` is a valid sockaddr_in6 of the given length
+            unsafe {
+                libc::connect(
+                    fd,
+                    &sockaddr as *const libc::sockaddr_in6 as *const libc::sockaddr,
+                    size_of::<libc::sockaddr_in6>() as libc::socklen_t,
+                )
+            }
+        }
+    };
+    if res < 0 {
+        let e = io::Error::last_os_error();
+        if e.raw_os_error() != Some(libc::EINPROGRESS) {
+            return Err(e);
+        }
+        let mut pollfd = libc::pollfd {
+            fd,
+            events: libc::POLLOUT,
+            revents: 0,
+        };
+        let timeout = timeout.as_millis().try_into().unwrap_or(libc::c_int::MAX);
+        // SAFETY: `pollfd` is a single valid pollfd
+        match unsafe { libc::poll(&mut pollfd, 1, timeout) } {
+            0 => return Err(io::ErrorKind::TimedOut.into()),
+            n if n < 0 => return Err(io::Error::last_os_error()),
+            _ => {}
+        }
+        if let Some(e) = stream.take_error()? {
+            return Err(e);
+        }
     }
+    stream.set_nonblocking(false)?;
+    Ok(stream)
 }
 
+/// Sends `data` to `nameserver` via TCP, with a new connection that uses `options`, and returns
+/// the answer, the number of received bytes, and timing information.
+///
+/// Messages whose ID differs from the one of `data` are skipped.
 pub fn send_query_tcp(
     nameserver: &mut Nameserver,
-    bufsize: u16,
     data: &[u8],
-) -> Result<(Vec<u8>, u16, Duration)> {
-    let nameserver_socketaddr = nameserver
-        .to_socket_addrs()
-        .context("Could not get socket address for nameserver.")?
-        .next()
-        .ok_or_else(|| anyhow!("Could not get socket address for nameserver."))?;
-    let mut socket = TcpStream::connect_timeout(&nameserver_socketaddr, Duration::from_secs(10))
-        .context(format!(
-            "Could not connect to {} via TCP, is the server running?",
-            nameserver
-        ))?;
+    options: &TcpOptions,
+) -> Result<(Vec<u8>, u16, QueryTiming)> {
+    let resolved_addrs = nameserver.resolve()?;
+    let mut info = SocketInfo::new(nameserver, ConnectionType::Tcp);
+    info.remote_addr = Some(resolved_addrs[0]);
+    info.ipv6_attempted = resolved_addrs[0].is_ipv6();
+    let before_connect = Instant::now();
+    let mut socket = connect_tcp(&resolved_addrs[0], Duration::from_secs(10), options)
+        .map_err(|e| info.error(NetworkErrorKind::Connect, Some(e)))?;
+    let handshake = before_connect.elapsed();
+    let info = info.with_local_addr(socket.local_addr());
 
-    let peer_addr = socket
-        .peer_addr()
-        .context("Could not get peer address of TCP socket.")?;
-    nameserver.ip = Some(peer_addr.ip());
+    // with TCP Fast Open, the socket is not connected yet, so its peer address is unknown
+    nameserver.ip = Some(resolved_addrs[0].ip());
 
     socket
         .set_write_timeout(Some(Duration::new(2, 0)))
-        .context("Could not set TCP stream write timeout.")?;
+        .transport("Could not set TCP stream write timeout.")?;
     socket
         .set_read_timeout(Some(Duration::new(10, 0)))
-        .context("Could not set TCP stream read timeout.")?;
+        .transport("Could not set TCP stream read timeout.")?;
 
     let mut msg = Vec::with_capacity(data.len() + 2);
-    msg.write_u16::<NetworkEndian>(data.len() as u16)?;
+    msg.write_u16::<NetworkEndian>(data.len() as u16)
+        .expect("Writing to a Vec cannot fail.");
     msg.extend_from_slice(data);
 
+    let sent = SystemTime::now();
     let before = Instant::now();
     socket
         .write_all(&msg)
-        .context("Could not write data to TCP stream.")?;
-
-    // we can't use socket.read_to_end() because we would have to wait for the read timout to elapse
-    // before getting an EOF from the socket. therefore we roll our own implementation which stops reading
-    // from the socket as soon as the received number of bytes is equal to the message length given by
-    // the first two bytes of the message (plus two, because the message length does not count the two
-    // bytes at the start; see RFC 1035, Section 4.2.2)
-    let mut offset = 0;
-    // the query sets this as max size
-    let mut res = vec![0; bufsize as usize];
-    while (offset < 2) || (offset - 2 < u16::from_be_bytes([res[0], res[1]]) as usize) {
-        offset += socket
-            .read(&mut res[offset..])
-            .context("Could not read from TCP stream.")?;
-    }
+        .map_err(|e| info.error(NetworkErrorKind::Send, Some(e)))?;
 
-    let elapsed = before.elapsed();
-    socket.shutdown(std::net::Shutdown::Both)?;
+    let mut read_exact = |buf: &mut [u8]| {
+        socket.read_exact(buf).map_err(|e| {
+            let (kind, e) = match e.kind() {
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => {
+                    (NetworkErrorKind::Timeout, e)
+                }
+                io::ErrorKind::UnexpectedEof => (
+                    NetworkErrorKind::Receive,
+                    io::Error::new(
+                        e.kind(),
+                        "the nameserver closed the connection before the answer was complete",
+                    ),
+                ),
+                _ => (NetworkErrorKind::Receive, e),
+            };
+            info.error(kind, Some(e))
+        })
+    };
+    let expected_id = message_id(data);
+    let res = loop {
+        // each message is preceded by its length, see RFC 1035, Section 4.2.2
+        let mut len = [0; 2];
+        read_exact(&mut len)?;
+        let mut res = vec![0; u16::from_be_bytes(len).into()];
+        read_exact(&mut res)?;
+        // like with UDP, messages with another ID (e.g. spoofed ones) are not the answer
+        if message_id(&res) == expected_id {
+            break res;
+        }
+    };
 
-    let bytes_recvd = u16::from_be_bytes([res[0], res[1]]);
-    res = res.into_iter().skip(2).collect();
-    if bytes_recvd as usize != offset - 2 {
-        bail!(
-            "Received {} bytes, but TCP message says {} bytes were sent.",
-            offset - 2,
-            bytes_recvd
-        );
-    }
-    // this will always shrink res
-    res.resize(bytes_recvd as usize, 0);
+    let elapsed = before.elapsed();
+    let received = SystemTime::now();
+    socket
+        .shutdown(std::net::Shutdown::Both)
+        .transport("Could not shut down TCP stream.")?;
+    let bytes_recvd = res.len() as u16;
 
-    Ok((res, bytes_recvd, elapsed))
+    let timing = QueryTiming {
+        sent,
+        received,
+        elapsed,
+        handshake: Some(handshake),
+        http_cache: None,
+        retransmissions: 0,
+        resolved_addrs,
+        tcp_fallback: None,
+        attempts: vec![QueryAttempt {
+            transport: ConnectionType::Tcp,
+            elapsed,
+            failure: None,
+        }],
+    };
+    Ok((res, bytes_recvd, timing))
 }
 
+/// Sends `data` to `nameserver` via TLS, with a new TCP connection that uses `options`, and
+/// returns the answer, the number of received bytes, and timing information.
 #[cfg(feature = "tls")]
 pub fn send_query_tls(
     nameserver: &mut Nameserver,
     data: &[u8],
-) -> Result<(Vec<u8>, u16, Duration)> {
+    options: &TcpOptions,
+) -> Result<(Vec<u8>, u16, QueryTiming)> {
     let mut root_store = rustls::RootCertStore::empty();
     root_store.add_server_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.0.iter().map(|ta| {
         rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
@@ -246,43 +1292,48 @@ pub fn send_query_tls(
         .expect("The argument parser failed to ensure the DoT nameserver is given as a hostname")
         .as_str()
         .try_into()
-        .context("Invalid nameserver hostname.")?;
+        .transport("Invalid nameserver hostname.")?;
     let mut session = rustls::ClientConnection::new(Arc::new(config), nameserver_tlsname)
-        .context("Could not create TLS connection.")?;
-
-    let nameserver_socketaddr = nameserver
-        .to_socket_addrs()
-        .context("Could not get socket address for nameserver.")?
-        .next()
-        .ok_or_else(|| anyhow!("Could not get socket address for nameserver."))?;
-    let mut socket = TcpStream::connect_timeout(&nameserver_socketaddr, Duration::from_secs(10))
-        .context(format!(
+        .transport("Could not create TLS connection.")?;
+
+    let resolved_addrs = nameserver.resolve()?;
+    let before_connect = Instant::now();
+    let mut socket =
+        connect_tcp(&resolved_addrs[0], Duration::from_secs(10), options).transport(format!(
             "Failed to connect to {}, is the server configured to use DNS over TLS?",
             nameserver
         ))?;
 
-    let peer_addr = socket
-        .peer_addr()
-        .context("Could not get peer address of TCP socket.")?;
-    nameserver.ip = Some(peer_addr.ip());
+    // with TCP Fast Open, the socket is not connected yet, so its peer address is unknown
+    nameserver.ip = Some(resolved_addrs[0].ip());
 
     socket
         .set_write_timeout(Some(Duration::new(2, 0)))
-        .context("Could not set TLS/TCP stream write timeout.")?;
+        .transport("Could not set TLS/TCP stream write timeout.")?;
     socket
         .set_read_timeout(Some(Duration::new(10, 0)))
-        .context("Could not set TLS/TCP stream read timeout.")?;
+        .transport("Could not set TLS/TCP stream read timeout.")?;
+
+    // complete the handshake first, so that it can be timed separately from the query
+    while session.is_handshaking() {
+        session
+            .complete_io(&mut socket)
+            .transport("TLS handshake failed.")?;
+    }
+    let handshake = before_connect.elapsed();
 
     let mut plaintext = Vec::new();
     let mut msg = Vec::with_capacity(data.len() + 2);
-    msg.write_u16::<NetworkEndian>(data.len() as u16)?;
+    msg.write_u16::<NetworkEndian>(data.len() as u16)
+        .expect("Writing to a Vec cannot fail.");
     msg.extend_from_slice(data);
 
+    let sent = SystemTime::now();
     let before = Instant::now();
     session
         .writer()
         .write_all(&msg)
-        .context("Could not write to TLS socket.")?;
+        .transport("Could not write to TLS socket.")?;
 
     while (plaintext.len() < 2)
         || plaintext.len() - 2 < u16::from_be_bytes([plaintext[0], plaintext[1]]) as usize
@@ -290,25 +1341,26 @@ pub fn send_query_tls(
         if session.wants_write() {
             session
                 .write_tls(&mut socket)
-                .context("Could not write TLS packets to TCP stream.")?;
+                .transport("Could not write TLS packets to TCP stream.")?;
         }
 
         if session.wants_read() {
             session
                 .read_tls(&mut socket)
-                .context("Could not read TLS packets from TCP stream.")?;
+                .transport("Could not read TLS packets from TCP stream.")?;
             session
                 .process_new_packets()
-                .context("Could not process new TLS packets.")?;
+                .transport("Could not process new TLS packets.")?;
             // Ignore WouldBlock errors
             match session.reader().read_to_end(&mut plaintext) {
                 Ok(_) => (),
                 Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => (),
-                Err(e) => Err(e).context("Could not read from TLS socket.")?,
+                Err(e) => Err(e).transport("Could not read from TLS socket.")?,
             }
         }
     }
     let elapsed = before.elapsed();
+    let received = SystemTime::now();
 
     session.send_close_notify();
 
@@ -317,29 +1369,39 @@ pub fn send_query_tls(
     plaintext = plaintext.into_iter().skip(2).collect();
     if bytes_recvd != plaintext.len() as u16 {
         bail!(
+            transport,
             "Received {} bytes, but TCP message says {} were sent.",
             bytes_recvd,
             plaintext.len()
         )
     }
 
-    Ok((plaintext, bytes_recvd, elapsed))
+    let timing = QueryTiming {
+        sent,
+        received,
+        elapsed,
+        handshake: Some(handshake),
+        http_cache: None,
+        retransmissions: 0,
+        resolved_addrs,
+        tcp_fallback: None,
+        attempts: vec![QueryAttempt {
+            transport: ConnectionType::Tls,
+            elapsed,
+            failure: None,
+        }],
+    };
+    Ok((plaintext, bytes_recvd, timing))
 }
 
+/// Returns the URL of the DNS over HTTP(S) endpoint of `nameserver`.
 #[cfg(feature = "http")]
-pub fn send_query_http(
-    nameserver: &mut Nameserver,
-    connection_type: ConnectionType,
-    bufsize: u16,
-    data: &[u8],
-) -> Result<(Vec<u8>, u16, Duration)> {
-    let mut res = Vec::with_capacity(bufsize as usize); // the query sets this as max size
-
+fn http_url(nameserver: &Nameserver, connection_type: ConnectionType) -> String {
     let nameserver_hostname = nameserver
         .hostname
         .as_ref()
         .expect("The argument parser failed to ensure the DoT nameserver is given as a hostname");
-    let addr = match connection_type {
+    match connection_type {
         ConnectionType::HttpGet | ConnectionType::HttpPost => {
             format!(
                 "http://{}:{}/dns-query",
@@ -353,13 +1415,27 @@ pub fn send_query_http(
             )
         }
         _ => unreachable!(),
-    };
+    }
+}
+
+#[cfg(feature = "http")]
+pub fn send_query_http(
+    nameserver: &mut Nameserver,
+    connection_type: ConnectionType,
+    bufsize: u16,
+    data: &[u8],
+) -> Result<(Vec<u8>, u16, QueryTiming)> {
+    let mut res = Vec::with_capacity(bufsize as usize); // the query sets this as max size
+
+    let addr = http_url(nameserver, connection_type);
     let b64 = BASE64URL_NOPAD.encode(data);
+    let sent = SystemTime::now();
     let before = Instant::now();
 
     let response = match connection_type {
         ConnectionType::HttpPost | ConnectionType::HttpsPost => ureq::post(&addr)
             .set("Content-Type", "application/dns-message")
+            .set("Accept", "application/dns-message")
             .send_bytes(data),
         ConnectionType::HttpGet | ConnectionType::HttpsGet => ureq::get(&addr)
             .set("Accept", "application/dns-message")
@@ -367,23 +1443,127 @@ pub fn send_query_http(
             .call(),
         _ => unreachable!(),
     }
-    .context("HTTP(S) request unsuccessful.")?;
+    .transport("HTTP(S) request unsuccessful.")?;
 
     let elapsed = before.elapsed();
+    let received = SystemTime::now();
     // for 404 the above ? already returns an Err...
     if response.status() != 200 {
-        bail!("HTTP(S) response code not 200.")
+        bail!(transport, "HTTP(S) response code not 200.")
     }
 
     // TODO Response::remote_addr() will be added in ureq 2.6.0
     // nameserver.ip = response.remote_addr().map(|s| s.ip());
+    let http_cache = HttpCaching::from_response(&response);
 
     let bytes_recvd = response
         .into_reader()
         .read_to_end(&mut res)
-        .context("Could not read the HTTP(S) response.")?;
+        .transport("Could not read the HTTP(S) response.")?;
 
     res.resize(bytes_recvd, 0);
 
-    Ok((res, bytes_recvd as u16, elapsed))
+    let timing = QueryTiming {
+        sent,
+        received,
+        elapsed,
+        handshake: None,
+        http_cache: Some(http_cache),
+        retransmissions: 0,
+        resolved_addrs: Vec::new(),
+        tcp_fallback: None,
+        attempts: vec![QueryAttempt {
+            transport: connection_type,
+            elapsed,
+            failure: None,
+        }],
+    };
+    Ok((res, bytes_recvd as u16, timing))
+}
+
+/// Returns whether `res` failed because the DNS over HTTP(S) endpoint does not accept the wire
+/// format, i.e. answered with status 406 (Not Acceptable) or 415 (Unsupported Media Type).
+#[cfg(all(feature = "http", feature = "json"))]
+fn is_wire_format_rejected<T>(res: &Result<T>) -> bool {
+    match res {
+        Err(Error::Transport {
+            source: Some(source),
+            ..
+        }) => matches!(
+            source.downcast_ref::<ureq::Error>(),
+            Some(ureq::Error::Status(406 | 415, _))
+        ),
+        _ => false,
+    }
+}
+
+/// Sends the query `data` to the JSON API (`application/dns-json`) of the DNS over HTTP(S)
+/// endpoint of `nameserver` and converts the answer into the wire format with
+/// [`dns_json::parse_response()`](crate::dns_json::parse_response()).
+///
+/// The JSON API only supports the name and the type of the first question and the DO and CD
+/// bits, so any other part of the query (e.g. EDNS options) is not sent.
+#[cfg(all(feature = "http", feature = "json"))]
+pub fn send_query_http_json(
+    nameserver: &mut Nameserver,
+    connection_type: ConnectionType,
+    data: &[u8],
+) -> Result<(Vec<u8>, u16, QueryTiming)> {
+    let query = Message::parse_bytes(data).parse("Could not parse the query.")?;
+    let question = match query.questions.first() {
+        Some(question) => question,
+        None => bail!(
+            transport,
+            "The JSON API can only answer queries with a question."
+        ),
+    };
+    let dnssec_ok = query
+        .opt()
+        .is_some_and(|opt| opt.flags.contains(&OptFlags::DO));
+
+    let addr = http_url(nameserver, connection_type);
+    let json_connection_type = match connection_type {
+        ConnectionType::HttpGet | ConnectionType::HttpPost => ConnectionType::HttpGet,
+        _ => ConnectionType::HttpsGet,
+    };
+    let sent = SystemTime::now();
+    let before = Instant::now();
+
+    let response = ureq::get(&addr)
+        .set("Accept", "application/dns-json")
+        .query("name", &question.qname.to_string())
+        .query("type", &u16::from(question.qtype).to_string())
+        .query("do", if dnssec_ok { "1" } else { "0" })
+        .query("cd", if query.header.flags.cd { "1" } else { "0" })
+        .call()
+        .transport("HTTP(S) request to the JSON API unsuccessful.")?;
+
+    let elapsed = before.elapsed();
+    let received = SystemTime::now();
+    let http_cache = HttpCaching::from_response(&response);
+
+    let body = response
+        .into_string()
+        .transport("Could not read the HTTP(S) response.")?;
+    let res = crate::dns_json::parse_response(&body, query.header.msg_id)?
+        .encode()
+        .parse("Could not encode the answer from the JSON API.")?;
+
+    let timing = QueryTiming {
+        sent,
+        received,
+        elapsed,
+        handshake: None,
+        http_cache: Some(http_cache),
+        retransmissions: 0,
+        resolved_addrs: Vec::new(),
+        tcp_fallback: None,
+        attempts: vec![QueryAttempt {
+            transport: json_connection_type,
+            elapsed,
+            failure: None,
+        }],
+    };
+    let len = res.len() as u16;
+    Ok((res, len, timing))
 }
@@ -0,0 +1,313 @@
+//! Scripted probing with query templates (`probe`), e.g. for DNS-based measurement campaigns and
+//! cache probing: a [`ProbeSpec`] defines parameterized queries and lists of values for their
+//! variables, which are expanded into [`Probe`]s and sent with a [`Resolver`], see [`run()`].
+//!
+//! A spec consists of lines of the following kinds (empty lines and everything after a `#` are
+//! ignored):
+//! - `$<variable> = <value> ...`: the values of a variable, separated by whitespace or commas. A
+//!   value of the form `<start>..<end>` is the range of the integers from `start` to `end`
+//!   (inclusive), e.g. `$n = 1..10`.
+//! - `<name> [<type>]`: a query for the name, in which `{<variable>}` is replaced by each value of
+//!   the variable, e.g. `{label}.probe.example TXT`. The type is `A` by default.
+//!
+//! A template is expanded for each combination of the values of the variables it uses.
+
+use std::fmt::{self, Display};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use toluol_proto::{Name, RecordType};
+
+use crate::error::{bail, Context, Error, Result};
+use crate::resolver::{Lookup, Resolver};
+use crate::sweep::concurrently;
+
+/// The maximum number of queries a spec may expand to.
+pub const MAX_PROBES: usize = 100_000;
+
+/// A parameterized query, e.g. `{label}.probe.example TXT`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueryTemplate {
+    /// The name, with `{<variable>}` where a value is substituted.
+    pub name: String,
+    pub qtype: RecordType,
+}
+
+impl QueryTemplate {
+    /// Returns the variables used by the name, in the order of their first occurrence.
+    pub fn variables(&self) -> Result<Vec<&str>> {
+        let mut variables = Vec::new();
+        let mut rest = self.name.as_str();
+        while let Some((_, after)) = rest.split_once('{') {
+            let (variable, after) = match after.split_once('}') {
+                Some(split) => split,
+                None => bail!(configuration, "Unterminated variable in {}.", self.name),
+            };
+            if !variables.contains(&variable) {
+                variables.push(variable);
+            }
+            rest = after;
+        }
+        Ok(variables)
+    }
+}
+
+impl Display for QueryTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.name, self.qtype)
+    }
+}
+
+/// A query spec: the values of the variables, and the query templates (see the
+/// [module documentation](self) for the format).
+///
+/// # Examples
+/// ```rust
+/// use toluol::probe::ProbeSpec;
+///
+/// let spec: ProbeSpec = "
+///     $label = www, mail # the services
+///     $n = 1..2
+///     {label}.probe.example TXT
+///     {n}.{label}.example.com
+/// "
+/// .parse()
+/// .unwrap();
+///
+/// let probes = spec.expand().unwrap();
+/// let queries: Vec<_> = probes.iter().map(|p| format!("{} {}", p.name, p.qtype)).collect();
+/// assert_eq!(
+///     queries,
+///     [
+///         "www.probe.example TXT",
+///         "mail.probe.example TXT",
+///         "1.www.example.com A",
+///         "1.mail.example.com A",
+///         "2.www.example.com A",
+///         "2.mail.example.com A",
+///     ]
+/// );
+/// assert_eq!(probes[3].substitution_string(), "n=1 label=mail");
+///
+/// assert!("{label}.probe.example".parse::<ProbeSpec>().is_err());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProbeSpec {
+    /// The variables and their values, in the order in which they are defined.
+    pub variables: Vec<(String, Vec<String>)>,
+    pub templates: Vec<QueryTemplate>,
+}
+
+impl ProbeSpec {
+    /// Reads and parses the spec in the file at `path`.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let spec = fs::read_to_string(path)
+            .configuration(format!("Could not read {}.", path.display()))?;
+        spec.parse()
+            .configuration(format!("Invalid query spec {}.", path.display()))
+    }
+
+    /// Returns the values of `variable`, if it is defined.
+    pub fn values(&self, variable: &str) -> Option<&[String]> {
+        self.variables
+            .iter()
+            .find(|(name, _)| name == variable)
+            .map(|(_, values)| values.as_slice())
+    }
+
+    /// Expands the templates into queries, template by template. For each template, the first
+    /// variable it uses changes slowest.
+    ///
+    /// Returns an error if a name is invalid after substitution, or if there would be more than
+    /// [`MAX_PROBES`] queries.
+    pub fn expand(&self) -> Result<Vec<Probe>> {
+        let mut probes = Vec::new();
+        for template in &self.templates {
+            let variables = template.variables()?;
+            let values: Vec<_> = variables
+                .iter()
+                .map(|&variable| self.values(variable).unwrap_or_default())
+                .collect();
+            let count = values
+                .iter()
+                .try_fold(1usize, |count, values| count.checked_mul(values.len()));
+            match count {
+                Some(count) if probes.len() + count <= MAX_PROBES => {}
+                _ => bail!(
+                    configuration,
+                    "The query spec expands to more than {} queries.",
+                    MAX_PROBES
+                ),
+            }
+
+            // the indices of the current value of each variable, like the digits of a number
+            let mut indices = vec![0; variables.len()];
+            while values.iter().all(|values| !values.is_empty()) {
+                let substitution: Vec<_> = variables
+                    .iter()
+                    .zip(&values)
+                    .zip(&indices)
+                    .map(|((&variable, values), &i)| (variable.to_string(), values[i].clone()))
+                    .collect();
+                let mut name = template.name.clone();
+                for (variable, value) in &substitution {
+                    name = name.replace(&format!("{{{}}}", variable), value);
+                }
+                let name = Name::from_ascii(&name)
+                    .configuration(format!("Invalid name {} for {}.", name, template))?;
+                probes.push(Probe {
+                    substitution,
+                    name,
+                    qtype: template.qtype,
+                });
+
+                match (0..indices.len())
+                    .rev()
+                    .find(|&i| indices[i] + 1 < values[i].len())
+                {
+                    Some(i) => {
+                        indices[i] += 1;
+                        indices[i + 1..].fill(0);
+                    }
+                    None => break,
+                }
+            }
+        }
+        Ok(probes)
+    }
+}
+
+impl FromStr for ProbeSpec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut spec = Self::default();
+        for (number, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let number = number + 1;
+
+            if let Some(definition) = line.strip_prefix('$') {
+                let (variable, values) = match definition.split_once('=') {
+                    Some((variable, values)) => (variable.trim(), values),
+                    None => bail!(configuration, "Line {}: missing `=`.", number),
+                };
+                if variable.is_empty() || variable.contains(['{', '}']) {
+                    bail!(configuration, "Line {}: invalid variable name.", number);
+                }
+                if spec.values(variable).is_some() {
+                    bail!(
+                        configuration,
+                        "Line {}: {} is defined twice.",
+                        number,
+                        variable
+                    );
+                }
+                let mut expanded = Vec::new();
+                for value in values.split(|c: char| c == ',' || c.is_whitespace()) {
+                    if value.is_empty() {
+                        continue;
+                    }
+                    match value.split_once("..") {
+                        Some((start, end)) => match (start.parse::<u64>(), end.parse::<u64>()) {
+                            (Ok(start), Ok(end))
+                                if start <= end && end - start < MAX_PROBES as u64 =>
+                            {
+                                expanded.extend((start..=end).map(|i| i.to_string()))
+                            }
+                            _ => bail!(configuration, "Line {}: invalid range {}.", number, value),
+                        },
+                        None => expanded.push(value.to_string()),
+                    }
+                }
+                if expanded.is_empty() {
+                    bail!(
+                        configuration,
+                        "Line {}: {} has no values.",
+                        number,
+                        variable
+                    );
+                }
+                spec.variables.push((variable.to_string(), expanded));
+                continue;
+            }
+
+            let mut words = line.split_whitespace();
+            let name = words.next().unwrap_or_default().to_string();
+            let qtype = match words.next().map(RecordType::from_str) {
+                Some(Ok(qtype)) => qtype,
+                Some(Err(_)) => bail!(configuration, "Line {}: invalid record type.", number),
+                None => RecordType::A,
+            };
+            if words.next().is_some() {
+                bail!(
+                    configuration,
+                    "Line {}: expected a name and a type.",
+                    number
+                );
+            }
+            let template = QueryTemplate { name, qtype };
+            for variable in template.variables()? {
+                if spec.values(variable).is_none() {
+                    bail!(
+                        configuration,
+                        "Line {}: {} is not defined (before this line).",
+                        number,
+                        variable
+                    );
+                }
+            }
+            spec.templates.push(template);
+        }
+
+        if spec.templates.is_empty() {
+            bail!(configuration, "The query spec contains no queries.");
+        }
+        Ok(spec)
+    }
+}
+
+/// A query expanded from a [`QueryTemplate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Probe {
+    /// The variables used by the template and the values substituted for them.
+    pub substitution: Vec<(String, String)>,
+    pub name: Name,
+    pub qtype: RecordType,
+}
+
+impl Probe {
+    /// Returns the substitution as `<variable>=<value>` pairs separated by spaces.
+    pub fn substitution_string(&self) -> String {
+        self.substitution
+            .iter()
+            .map(|(variable, value)| format!("{}={}", variable, value))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// The answer to a [`Probe`], see [`run()`].
+#[derive(Debug)]
+pub struct ProbeResult {
+    pub probe: Probe,
+    /// The answer, or the reason why the query failed.
+    pub answer: Result<Lookup>,
+}
+
+/// Sends the queries of `probes` with `resolver`, and returns the results in the order of
+/// `probes`.
+///
+/// As with [`reverse_lookup()`](crate::sweep::reverse_lookup()), up to 16 queries are in flight;
+/// use [`Resolver::with_rate_limit()`] to limit their rate.
+pub fn run(resolver: &Resolver, probes: Vec<Probe>) -> Vec<ProbeResult> {
+    let answers = concurrently(&probes, |probe| resolver.lookup(&probe.name, probe.qtype));
+    probes
+        .into_iter()
+        .zip(answers)
+        .map(|(probe, answer)| ProbeResult { probe, answer })
+        .collect()
+}
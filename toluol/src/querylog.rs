@@ -0,0 +1,150 @@
+//! Logging every query and its response to a file, so that the queries toluol sends are
+//! auditable, see [`QueryLog`].
+
+use chrono::{DateTime, Utc};
+use std::fmt::Debug;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use toluol_proto::rdata::opt::OptionCode;
+use toluol_proto::{Message, OptFlags, Record};
+
+use crate::error::{Context, Error, Result};
+use crate::net::{Nameserver, QueryTiming};
+use crate::ConnectionType;
+
+/// Appends a line for each query and its response to a writer, usually a file.
+///
+/// The lines resemble those of BIND's query log, seen from the client, e.g.
+///
+/// ```text
+/// 17-Oct-2026 12:34:56.789 query: example.com IN AAAA +E(0)D (192.0.2.53#53) UDP: NOERROR 1/0/1 144 bytes 12 ms
+/// ```
+///
+/// The query flags are `+` if recursion is desired (`-` otherwise), `E(<version>)` if EDNS is
+/// used, `D` for the DO bit, `C` for the CD bit, and `K` if a cookie is sent. The response
+/// contains the numbers of answer, authority, and additional records, or the reason why the query
+/// failed. Times are in UTC.
+///
+/// A `QueryLog` is used for all queries that are sent with its [`QueryMetadata`](crate::QueryMetadata)
+/// (see [`QueryMetadata::query_log`](crate::QueryMetadata::query_log) and
+/// [`Resolver::with_query_log()`](crate::resolver::Resolver::with_query_log())).
+pub struct QueryLog {
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl QueryLog {
+    /// Creates a log that writes to `writer`.
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            writer: Mutex::new(Box::new(writer)),
+        }
+    }
+
+    /// Opens the file at `path` for appending, creating it if it does not exist.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file: File = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .configuration(format!("Could not open query log {}.", path.display()))?;
+        Ok(Self::new(BufWriter::new(file)))
+    }
+
+    /// Appends the line for the encoded `query` that was sent to `nameserver` via
+    /// `connection_type`, and its `response` (the encoded answer and the timing, or the reason
+    /// why the query failed).
+    pub fn log(
+        &self,
+        nameserver: &Nameserver,
+        connection_type: ConnectionType,
+        query: &[u8],
+        response: Result<(&[u8], &QueryTiming), &Error>,
+    ) -> Result<()> {
+        let sent = match response {
+            Ok((_, timing)) => timing.sent,
+            Err(_) => SystemTime::now(),
+        };
+        let time: DateTime<Utc> = sent.into();
+        let server = match nameserver.ip {
+            Some(ip) => format!("{}#{}", ip, nameserver.port),
+            None => nameserver.to_string(),
+        };
+        let line = format!(
+            "{} query: {} ({}) {}: {}",
+            time.format("%d-%b-%Y %H:%M:%S%.3f"),
+            describe_query(query),
+            server,
+            connection_type,
+            describe_response(response)
+        );
+
+        let mut writer = self.writer.lock().expect("Query log lock is poisoned.");
+        writeln!(writer, "{}", line)
+            .and_then(|()| writer.flush())
+            .configuration("Could not write to the query log.")
+    }
+}
+
+impl Debug for QueryLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QueryLog").finish_non_exhaustive()
+    }
+}
+
+/// Returns `<name> <class> <type> <flags>` for an encoded query.
+fn describe_query(query: &[u8]) -> String {
+    let msg = match Message::parse_bytes(query) {
+        Ok(msg) => msg,
+        Err(_) => return format!("<unparsable {} bytes>", query.len()),
+    };
+    let question = match msg.questions.first() {
+        Some(q) => format!("{} {} {}", q.qname, q.qclass, q.qtype),
+        None => "<no question>".to_string(),
+    };
+    let mut flags = String::from(if msg.header.flags.rd { "+" } else { "-" });
+    let opt = msg.additional_answers.iter().find_map(Record::as_opt);
+    if let Some(opt) = opt {
+        flags += &format!("E({})", opt.edns_version);
+        if opt.flags.contains(&OptFlags::DO) {
+            flags.push('D');
+        }
+    }
+    if msg.header.flags.cd {
+        flags.push('C');
+    }
+    if opt.is_some_and(|opt| opt.opt_rdata().get(OptionCode::Cookie).is_some()) {
+        flags.push('K');
+    }
+    format!("{} {}", question, flags)
+}
+
+/// Returns the RCODE, the section counts, the size, and the RTT of a response, or why the query
+/// failed.
+fn describe_response(response: Result<(&[u8], &QueryTiming), &Error>) -> String {
+    let (answer, timing) = match response {
+        Ok(response) => response,
+        Err(e) => return format!("failed: {:#}", e),
+    };
+    let status = match Message::parse_bytes(answer) {
+        Ok(msg) => format!(
+            "{} {}/{}/{}",
+            msg.header
+                .rcode
+                .map_or("-".to_string(), |rcode| rcode.to_string()),
+            msg.answers.len(),
+            msg.authoritative_answers.len(),
+            msg.additional_answers.len()
+        ),
+        Err(e) => format!("unparsable ({})", e),
+    };
+    format!(
+        "{} {} bytes {} ms",
+        status,
+        answer.len(),
+        timing.elapsed.as_millis()
+    )
+}
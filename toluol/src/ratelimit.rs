@@ -0,0 +1,214 @@
+//! Token bucket rate limiting for outgoing queries, so that large batches of queries don't overload
+//! nameservers.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::{Error, Result};
+
+/// A rate of queries, which may be exceeded for short bursts.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rate {
+    /// The number of queries per second that can be sent in the long run. If this is 0, no more
+    /// queries can be sent after the first burst.
+    pub per_second: f64,
+    /// The number of queries that can be sent at once, after no queries were sent for a while.
+    pub burst: u32,
+}
+
+impl Rate {
+    /// Creates a rate of `per_second` queries per second, with bursts of up to `per_second`
+    /// queries (but at least one).
+    pub fn per_second(per_second: u32) -> Self {
+        Self {
+            per_second: per_second as f64,
+            burst: per_second.max(1),
+        }
+    }
+}
+
+/// The configuration of a [`RateLimiter`].
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct RateLimit {
+    /// The rate of all queries, regardless of the nameserver.
+    pub global: Option<Rate>,
+    /// The rate of queries to each nameserver.
+    pub per_nameserver: Option<Rate>,
+    /// The longest time a query may be delayed. Queries that would have to wait longer are shed,
+    /// i.e. not sent at all. If [`None`], queries are never shed.
+    pub max_delay: Option<Duration>,
+}
+
+/// Statistics about the queries a [`RateLimiter`] has delayed or shed.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct RateLimitStats {
+    /// The number of queries that were sent without delay.
+    pub immediate: u64,
+    /// The number of queries that were delayed before being sent.
+    pub delayed: u64,
+    /// The number of queries that were not sent, as they would have been delayed too long.
+    pub shed: u64,
+    /// The sum of all delays.
+    pub total_delay: Duration,
+}
+
+/// A token bucket, holding the number of queries that may currently be sent. The number of tokens
+/// is negative if queries are waiting.
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl Bucket {
+    fn new(rate: Rate, now: Instant) -> Self {
+        Self {
+            tokens: rate.burst as f64,
+            last_refill: now,
+        }
+    }
+
+    fn refill(&mut self, rate: Rate, now: Instant) {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate.per_second).min(rate.burst as f64);
+        self.last_refill = now;
+    }
+
+    /// Returns how long a query has to wait for a token.
+    fn delay(&self, rate: Rate) -> Duration {
+        if self.tokens >= 1.0 {
+            Duration::ZERO
+        } else if rate.per_second > 0.0 {
+            Duration::from_secs_f64((1.0 - self.tokens) / rate.per_second)
+        } else {
+            Duration::MAX
+        }
+    }
+}
+
+/// Limits the rate of queries, both globally and to each nameserver.
+///
+/// # Examples
+/// ```rust
+/// use std::time::Duration;
+///
+/// use toluol::ratelimit::{Rate, RateLimit, RateLimiter};
+///
+/// let limiter = RateLimiter::new(RateLimit {
+///     global: None,
+///     per_nameserver: Some(Rate { per_second: 1.0, burst: 2 }),
+///     max_delay: Some(Duration::ZERO),
+/// });
+/// assert!(limiter.acquire("192.0.2.1").is_ok());
+/// assert!(limiter.acquire("192.0.2.1").is_ok());
+/// // the burst is used up, and the query may not be delayed
+/// assert!(limiter.acquire("192.0.2.1").is_err());
+/// // other nameservers have their own limit
+/// assert!(limiter.acquire("192.0.2.2").is_ok());
+///
+/// let stats = limiter.stats();
+/// assert_eq!((stats.immediate, stats.delayed, stats.shed), (3, 0, 1));
+/// ```
+#[derive(Debug)]
+pub struct RateLimiter {
+    config: RateLimit,
+    global: Mutex<Option<Bucket>>,
+    per_nameserver: Mutex<HashMap<String, Bucket>>,
+    immediate: AtomicU64,
+    delayed: AtomicU64,
+    shed: AtomicU64,
+    total_delay_nanos: AtomicU64,
+}
+
+impl RateLimiter {
+    /// Creates a rate limiter with the given configuration.
+    pub fn new(config: RateLimit) -> Self {
+        let now = Instant::now();
+        Self {
+            config,
+            global: Mutex::new(config.global.map(|rate| Bucket::new(rate, now))),
+            per_nameserver: Mutex::new(HashMap::new()),
+            immediate: AtomicU64::new(0),
+            delayed: AtomicU64::new(0),
+            shed: AtomicU64::new(0),
+            total_delay_nanos: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the limiter's configuration.
+    pub fn config(&self) -> &RateLimit {
+        &self.config
+    }
+
+    /// Waits until a query may be sent to `nameserver`.
+    ///
+    /// Returns an error without waiting if the query would have to wait longer than
+    /// [`RateLimit::max_delay`], i.e. if it is shed.
+    pub fn acquire(&self, nameserver: &str) -> Result<()> {
+        let delay = {
+            let now = Instant::now();
+            // always lock in the same order to prevent deadlocks
+            let mut global = self.global.lock().expect("Rate limiter mutex poisoned.");
+            let mut per_nameserver = self
+                .per_nameserver
+                .lock()
+                .expect("Rate limiter mutex poisoned.");
+
+            let mut delay = Duration::ZERO;
+            if let (Some(bucket), Some(rate)) = (global.as_mut(), self.config.global) {
+                bucket.refill(rate, now);
+                delay = delay.max(bucket.delay(rate));
+            }
+            let mut nameserver_bucket = None;
+            if let Some(rate) = self.config.per_nameserver {
+                let bucket = per_nameserver
+                    .entry(nameserver.to_string())
+                    .or_insert_with(|| Bucket::new(rate, now));
+                bucket.refill(rate, now);
+                delay = delay.max(bucket.delay(rate));
+                nameserver_bucket = Some(bucket);
+            }
+
+            if self.config.max_delay.is_some_and(|max| delay > max) {
+                self.shed.fetch_add(1, Ordering::Relaxed);
+                return Err(Error::transport(format!(
+                    "Query to {} was not sent due to the rate limit.",
+                    nameserver
+                )));
+            }
+            // reserve the tokens now, so queries arriving while we wait are delayed further
+            if let Some(bucket) = global.as_mut() {
+                bucket.tokens -= 1.0;
+            }
+            if let Some(bucket) = nameserver_bucket {
+                bucket.tokens -= 1.0;
+            }
+            delay
+        };
+
+        if delay.is_zero() {
+            self.immediate.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.delayed.fetch_add(1, Ordering::Relaxed);
+            self.total_delay_nanos
+                .fetch_add(delay.as_nanos() as u64, Ordering::Relaxed);
+            thread::sleep(delay);
+        }
+        Ok(())
+    }
+
+    /// Returns statistics about the queries that were delayed or shed so far.
+    pub fn stats(&self) -> RateLimitStats {
+        RateLimitStats {
+            immediate: self.immediate.load(Ordering::Relaxed),
+            delayed: self.delayed.load(Ordering::Relaxed),
+            shed: self.shed.load(Ordering::Relaxed),
+            total_delay: Duration::from_nanos(self.total_delay_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
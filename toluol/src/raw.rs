@@ -0,0 +1,338 @@
+//! Crafting raw DNS messages for negative testing of servers, see [`MessageTemplate`].
+//!
+//! In contrast to [`Message`](toluol_proto::Message), a [`MessageTemplate`] can describe messages
+//! that are malformed in arbitrary ways: every header bit can be set, the section counts can
+//! disagree with the actual number of entries, names can be given as raw bytes (e.g. with
+//! compression pointers that point anywhere), and RDATA and RDLENGTH are written as given.
+//!
+//! A template can be built from fields like those accepted by `toluol raw` (see
+//! [`MessageTemplate::set()`]):
+//! - `id=<id>`: the message ID (random by default).
+//! - `flags=<flag>,...`: the header bits that are set, out of `qr`, `aa`, `tc`, `rd`, `ra`, `z`,
+//!   `ad`, and `cd` (only `rd` by default). `flags=none` clears all of them.
+//! - `opcode=<opcode>`: the opcode, by name or number.
+//! - `rcode=<rcode>`: the lower four bits of the RCODE, by number.
+//! - `qdcount=<count>`, `ancount=<count>`, `nscount=<count>`, `arcount=<count>`: the section
+//!   counts to write instead of the actual numbers of entries.
+//! - `q=<name>:<type>[:<class>]`: a question (with class `IN` by default).
+//! - `an=<record>`, `ns=<record>`, `ar=<record>`: a record in the answer, authority, or additional
+//!   section, given as `<name>:<type>:<class>:<ttl>:<hex rdata>[:<rdlength>]`.
+//! - `trailing=<hex>`: bytes to append after the last record.
+//!
+//! Names are either domain names or `hex:` followed by their raw encoding. Types and classes are
+//! given by name or number, so e.g. the UDP payload size of an `OPT` record is its class.
+
+use data_encoding::HEXLOWER_PERMISSIVE;
+use std::str::FromStr;
+use toluol_proto::{Class, Name, Opcode, RecordType};
+
+use crate::error::{bail, Context, Result};
+
+/// A name in a [`MessageTemplate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RawName {
+    /// A domain name, which is encoded without compression.
+    Name(Name),
+    /// Bytes that are written as they are, e.g. a malformed name or a compression pointer.
+    Bytes(Vec<u8>),
+}
+
+/// A question in a [`MessageTemplate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawQuestion {
+    pub name: RawName,
+    pub qtype: u16,
+    pub qclass: u16,
+}
+
+/// A record in a [`MessageTemplate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawRecord {
+    pub name: RawName,
+    pub rtype: u16,
+    pub class: u16,
+    pub ttl: u32,
+    /// The RDLENGTH to write, or [`None`] to write the length of `rdata`.
+    pub rdlength: Option<u16>,
+    pub rdata: Vec<u8>,
+}
+
+/// A DNS message whose every byte can be chosen, including inconsistent and malformed ones, see
+/// the [module documentation](self).
+///
+/// # Examples
+/// ```rust
+/// use toluol::raw::MessageTemplate;
+///
+/// let mut template = MessageTemplate::default();
+/// for field in ["id=4660", "flags=rd,cd", "qdcount=2", "q=example.com:A"] {
+///     template.set(field).unwrap();
+/// }
+/// let encoded = template.encode();
+/// assert_eq!(&encoded[..12], b"\x12\x34\x01\x10\x00\x02\x00\x00\x00\x00\x00\x00");
+/// assert_eq!(&encoded[12..], b"\x07example\x03com\0\x00\x01\x00\x01");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MessageTemplate {
+    pub id: u16,
+    pub qr: bool,
+    /// The opcode; only the lower four bits are used.
+    pub opcode: u8,
+    pub aa: bool,
+    pub tc: bool,
+    pub rd: bool,
+    pub ra: bool,
+    /// The reserved header bit, which must be zero in valid messages.
+    pub z: bool,
+    pub ad: bool,
+    pub cd: bool,
+    /// The lower four bits of the RCODE; only these are used.
+    pub rcode: u8,
+    /// QDCOUNT, ANCOUNT, NSCOUNT, and ARCOUNT; [`None`] means the actual number of entries.
+    pub counts: [Option<u16>; 4],
+    pub questions: Vec<RawQuestion>,
+    /// The records of the answer, authority, and additional section.
+    pub sections: [Vec<RawRecord>; 3],
+    /// Bytes appended after the last record.
+    pub trailing: Vec<u8>,
+}
+
+impl Default for MessageTemplate {
+    /// Returns a template for a query without questions that has a random ID and only the RD
+    /// bit set.
+    fn default() -> Self {
+        Self {
+            id: rand::random(),
+            qr: false,
+            opcode: 0,
+            aa: false,
+            tc: false,
+            rd: true,
+            ra: false,
+            z: false,
+            ad: false,
+            cd: false,
+            rcode: 0,
+            counts: [None; 4],
+            questions: Vec::new(),
+            sections: Default::default(),
+            trailing: Vec::new(),
+        }
+    }
+}
+
+impl MessageTemplate {
+    /// Changes the template according to `field`, e.g. `flags=rd,cd` or `q=example.com:A` (see
+    /// the [module documentation](self)).
+    pub fn set(&mut self, field: &str) -> Result<()> {
+        let (key, value) = field
+            .split_once('=')
+            .configuration(format!("Invalid field: {}; expected <key>=<value>.", field))?;
+        let number = |value: &str| {
+            value
+                .parse::<u16>()
+                .configuration(format!("Invalid number for {}: {}.", key, value))
+        };
+        match key {
+            "id" => self.id = number(value)?,
+            "flags" => self.set_flags(value)?,
+            "opcode" => {
+                self.opcode = match value.parse::<u8>() {
+                    Ok(opcode) if opcode < 16 => opcode,
+                    Ok(_) => bail!(
+                        configuration,
+                        "Invalid opcode: {}; must be less than 16.",
+                        value
+                    ),
+                    Err(_) => Opcode::from_str(&value.to_uppercase())
+                        .configuration(format!("Invalid opcode: {}.", value))?
+                        .encode(),
+                }
+            }
+            "rcode" => {
+                self.rcode = match value.parse::<u8>() {
+                    Ok(rcode) if rcode < 16 => rcode,
+                    _ => bail!(
+                        configuration,
+                        "Invalid RCODE: {}; must be a number less than 16.",
+                        value
+                    ),
+                }
+            }
+            "qdcount" => self.counts[0] = Some(number(value)?),
+            "ancount" => self.counts[1] = Some(number(value)?),
+            "nscount" => self.counts[2] = Some(number(value)?),
+            "arcount" => self.counts[3] = Some(number(value)?),
+            "q" => self.questions.push(parse_question(value)?),
+            "an" => self.sections[0].push(parse_record(value)?),
+            "ns" => self.sections[1].push(parse_record(value)?),
+            "ar" => self.sections[2].push(parse_record(value)?),
+            "trailing" => self.trailing = parse_hex(value)?,
+            _ => bail!(configuration, "Unknown field: {}.", key),
+        }
+        Ok(())
+    }
+
+    /// Sets exactly the header bits in the comma-separated list `flags`.
+    fn set_flags(&mut self, flags: &str) -> Result<()> {
+        const FLAGS: [&str; 8] = ["qr", "aa", "tc", "rd", "ra", "z", "ad", "cd"];
+        let mut set = [false; 8];
+        for flag in flags.split(',').map(str::trim) {
+            let flag = flag.to_lowercase();
+            match FLAGS.iter().position(|f| *f == flag) {
+                Some(i) => set[i] = true,
+                None if flag == "none" => (),
+                None => bail!(configuration, "Unknown header flag: {}.", flag),
+            }
+        }
+        [
+            self.qr, self.aa, self.tc, self.rd, self.ra, self.z, self.ad, self.cd,
+        ] = set;
+        Ok(())
+    }
+
+    /// Encodes the message as described by the template.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&self.id.to_be_bytes());
+        let bit = |set: bool, shift: u8| (set as u8) << shift;
+        buf.push(
+            bit(self.qr, 7)
+                | (self.opcode & 0xf) << 3
+                | bit(self.aa, 2)
+                | bit(self.tc, 1)
+                | bit(self.rd, 0),
+        );
+        buf.push(
+            bit(self.ra, 7) | bit(self.z, 6) | bit(self.ad, 5) | bit(self.cd, 4) | self.rcode & 0xf,
+        );
+        let actual = [
+            self.questions.len(),
+            self.sections[0].len(),
+            self.sections[1].len(),
+            self.sections[2].len(),
+        ];
+        for (count, actual) in self.counts.iter().zip(actual) {
+            buf.extend_from_slice(&count.unwrap_or(actual as u16).to_be_bytes());
+        }
+
+        for question in &self.questions {
+            write_name(&mut buf, &question.name);
+            buf.extend_from_slice(&question.qtype.to_be_bytes());
+            buf.extend_from_slice(&question.qclass.to_be_bytes());
+        }
+        for record in self.sections.iter().flatten() {
+            write_name(&mut buf, &record.name);
+            buf.extend_from_slice(&record.rtype.to_be_bytes());
+            buf.extend_from_slice(&record.class.to_be_bytes());
+            buf.extend_from_slice(&record.ttl.to_be_bytes());
+            let rdlength = record.rdlength.unwrap_or(record.rdata.len() as u16);
+            buf.extend_from_slice(&rdlength.to_be_bytes());
+            buf.extend_from_slice(&record.rdata);
+        }
+        buf.extend_from_slice(&self.trailing);
+        buf
+    }
+}
+
+fn write_name(buf: &mut Vec<u8>, name: &RawName) {
+    match name {
+        RawName::Name(name) => {
+            name.encode_into(buf).expect("Writing to a Vec failed.");
+        }
+        RawName::Bytes(bytes) => buf.extend_from_slice(bytes),
+    }
+}
+
+/// Parses `<name>:<type>[:<class>]`.
+fn parse_question(value: &str) -> Result<RawQuestion> {
+    let fields = split_fields(value);
+    if !(2..=3).contains(&fields.len()) {
+        bail!(
+            configuration,
+            "Invalid question: {}; expected <name>:<type>[:<class>].",
+            value
+        );
+    }
+    Ok(RawQuestion {
+        name: parse_name(fields[0])?,
+        qtype: parse_type(fields[1])?,
+        qclass: fields
+            .get(2)
+            .map_or(Ok(Class::IN.encode()), |c| parse_class(c))?,
+    })
+}
+
+/// Parses `<name>:<type>:<class>:<ttl>:<hex rdata>[:<rdlength>]`.
+fn parse_record(value: &str) -> Result<RawRecord> {
+    let fields = split_fields(value);
+    if !(5..=6).contains(&fields.len()) {
+        bail!(
+            configuration,
+            "Invalid record: {}; expected <name>:<type>:<class>:<ttl>:<hex rdata>[:<rdlength>].",
+            value
+        );
+    }
+    let ttl = fields[3]
+        .parse()
+        .configuration(format!("Invalid TTL: {}.", fields[3]))?;
+    let rdlength = match fields.get(5) {
+        Some(rdlength) => Some(
+            rdlength
+                .parse()
+                .configuration(format!("Invalid RDLENGTH: {}.", rdlength))?,
+        ),
+        None => None,
+    };
+    Ok(RawRecord {
+        name: parse_name(fields[0])?,
+        rtype: parse_type(fields[1])?,
+        class: parse_class(fields[2])?,
+        ttl,
+        rdlength,
+        rdata: parse_hex(fields[4])?,
+    })
+}
+
+/// Splits `value` at colons, except for the one after a leading `hex:`.
+fn split_fields(value: &str) -> Vec<&str> {
+    let mut fields: Vec<_> = value.split(':').collect();
+    if fields.len() > 1 && fields[0] == "hex" {
+        fields.remove(0);
+        fields[0] = &value[..4 + fields[0].len()];
+    }
+    fields
+}
+
+fn parse_name(name: &str) -> Result<RawName> {
+    match name.strip_prefix("hex:") {
+        Some(hex) => Ok(RawName::Bytes(parse_hex(hex)?)),
+        None => Ok(RawName::Name(
+            Name::from_ascii(name).configuration(format!("Invalid name: {}.", name))?,
+        )),
+    }
+}
+
+fn parse_type(rtype: &str) -> Result<u16> {
+    if let Ok(rtype) = rtype.parse() {
+        return Ok(rtype);
+    }
+    RecordType::from_str(rtype)
+        .map(u16::from)
+        .configuration(format!("Invalid type: {}.", rtype))
+}
+
+fn parse_class(class: &str) -> Result<u16> {
+    if let Ok(class) = class.parse() {
+        return Ok(class);
+    }
+    Class::from_str(&class.to_uppercase())
+        .map(|class| class.encode())
+        .configuration(format!("Invalid class: {}.", class))
+}
+
+fn parse_hex(hex: &str) -> Result<Vec<u8>> {
+    HEXLOWER_PERMISSIVE
+        .decode(hex.as_bytes())
+        .configuration(format!("Invalid hex string: {}.", hex))
+}
@@ -0,0 +1,148 @@
+//! RDAP lookups for domains and IP addresses (`+rdap`).
+//!
+//! The RDAP server responsible for a domain or address is found via the
+//! [rdap.org](https://about.rdap.org) redirection service. See
+//! [RFC 9083](https://www.rfc-editor.org/rfc/rfc9083) for the response format.
+
+use serde_json::Value;
+use std::net::IpAddr;
+use std::time::Duration;
+use toluol_proto::Name;
+
+use crate::error::{bail, Context, Result};
+
+/// Base URL of the service that redirects to the responsible RDAP server.
+const RDAP_REDIRECTOR: &str = "https://rdap.org";
+
+/// Registration information for a domain.
+#[derive(Clone, Debug, Default)]
+pub struct DomainInfo {
+    /// The registered domain, which may be a parent of the queried name.
+    pub domain: String,
+    pub registrar: Option<String>,
+    pub registration: Option<String>,
+    pub expiration: Option<String>,
+    pub nameservers: Vec<String>,
+}
+
+/// Registration information for the network an IP address belongs to.
+#[derive(Clone, Debug)]
+pub struct IpInfo {
+    pub ip: IpAddr,
+    /// The network's handle, e.g. `NET-1-1-1-0-1`.
+    pub handle: Option<String>,
+    /// The network's name, e.g. `APNIC-LABS`.
+    pub name: Option<String>,
+    pub country: Option<String>,
+    /// The name of the organization the network is registered to.
+    pub org: Option<String>,
+}
+
+/// Looks up the registration information for `name`.
+///
+/// As we don't know which part of `name` is the registered domain, the lookup is tried for `name`
+/// and then for each of its parents (excluding the TLD) until an RDAP server knows the domain.
+pub fn lookup_domain(name: &Name) -> Result<DomainInfo> {
+    let queried = name;
+    let mut name = name.clone();
+    while name.label_count() >= 2 {
+        let domain = name.to_string();
+        let domain = domain.trim_end_matches('.');
+        if let Some(res) = get(&format!("{}/domain/{}", RDAP_REDIRECTOR, domain))? {
+            return Ok(DomainInfo {
+                domain: domain.to_string(),
+                registrar: find_entity(&res, "registrar"),
+                registration: find_event(&res, "registration"),
+                expiration: find_event(&res, "expiration"),
+                nameservers: res["nameservers"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|ns| ns["ldhName"].as_str())
+                    .map(|ns| ns.to_lowercase())
+                    .collect(),
+            });
+        }
+        name.pop_front_label();
+    }
+    bail!(
+        transport,
+        "No RDAP server knows {} or any of its parents.",
+        queried
+    )
+}
+
+/// Looks up the registration information for the network `ip` belongs to.
+pub fn lookup_ip(ip: IpAddr) -> Result<IpInfo> {
+    let res = match get(&format!("{}/ip/{}", RDAP_REDIRECTOR, ip))? {
+        Some(res) => res,
+        None => bail!(transport, "No RDAP server knows {}.", ip),
+    };
+    Ok(IpInfo {
+        ip,
+        handle: res["handle"].as_str().map(str::to_string),
+        name: res["name"].as_str().map(str::to_string),
+        country: res["country"].as_str().map(str::to_string),
+        org: find_entity(&res, "registrant"),
+    })
+}
+
+/// Fetches and parses the RDAP response at `url`. Returns [`None`] if the object was not found.
+fn get(url: &str) -> Result<Option<Value>> {
+    let response = ureq::get(url)
+        .set("Accept", "application/rdap+json")
+        .timeout(Duration::from_secs(10))
+        .call();
+    let response = match response {
+        Ok(response) => response,
+        Err(ureq::Error::Status(404, _)) => return Ok(None),
+        Err(e) => return Err(e).transport("RDAP request unsuccessful."),
+    };
+    let body = response
+        .into_string()
+        .transport("Could not read the RDAP response.")?;
+    serde_json::from_str(&body)
+        .map(Some)
+        .parse("Could not parse the RDAP response.")
+}
+
+/// Returns the name of the first entity with the given role, searching nested entities as well.
+fn find_entity(object: &Value, role: &str) -> Option<String> {
+    for entity in object["entities"].as_array().into_iter().flatten() {
+        let has_role = entity["roles"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .any(|r| r.as_str() == Some(role));
+        if has_role {
+            if let Some(name) = vcard_name(entity) {
+                return Some(name);
+            }
+        }
+        if let Some(name) = find_entity(entity, role) {
+            return Some(name);
+        }
+    }
+    None
+}
+
+/// Returns the formatted name (`fn` property) from an entity's jCard
+/// ([RFC 7095](https://www.rfc-editor.org/rfc/rfc7095)).
+fn vcard_name(entity: &Value) -> Option<String> {
+    entity["vcardArray"][1]
+        .as_array()?
+        .iter()
+        .find(|property| property[0].as_str() == Some("fn"))
+        .and_then(|property| property[3].as_str())
+        .map(str::to_string)
+}
+
+/// Returns the date of the first event with the given action.
+fn find_event(object: &Value, action: &str) -> Option<String> {
+    object["events"]
+        .as_array()?
+        .iter()
+        .find(|event| event["eventAction"].as_str() == Some(action))
+        .and_then(|event| event["eventDate"].as_str())
+        .map(str::to_string)
+}
@@ -0,0 +1,256 @@
+//! The interactive mode (`-i`): queries are read from a prompt and sent one after the other,
+//! reusing connections and cached answers (see [`Session`]).
+
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::PathBuf;
+
+use anyhow::Result;
+use owo_colors::OwoColorize;
+use toluol::session::Session;
+
+use crate::args::{Args, Command};
+
+const PROMPT: &str = "toluol> ";
+
+/// The file in the home directory that keeps the lines entered at the prompt.
+const HISTORY_FILE: &str = ".toluol_history";
+
+/// The number of lines kept in [`HISTORY_FILE`].
+const HISTORY_SIZE: usize = 1000;
+
+/// Reads lines from stdin until `quit` or the end of input, and runs each of them. `defaults`
+/// are the arguments each query starts with; lines can change them with `set`.
+pub fn run(defaults: &[String]) -> Result<()> {
+    let session = Session::default();
+    let mut settings = defaults.to_vec();
+    let mut history = History::load();
+    let stdin = io::stdin();
+    let terminal = stdin.is_terminal();
+    if terminal {
+        println!("Type help for the available commands, quit or Ctrl-D to leave.");
+    }
+
+    let mut lines = stdin.lock().lines();
+    loop {
+        if terminal {
+            print!(
+                "{}",
+                PROMPT.if_supports_color(owo_colors::Stream::Stdout, |s| s.bold())
+            );
+            io::stdout().flush()?;
+        }
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => {
+                if terminal {
+                    println!();
+                }
+                break;
+            }
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let line = match history.expand(line) {
+            Ok(expanded) => {
+                if expanded != line {
+                    println!("{}", expanded);
+                }
+                expanded
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                continue;
+            }
+        };
+        history.add(&line);
+        let words = match split_words(&line) {
+            Ok(words) => words,
+            Err(e) => {
+                eprintln!("{}", e);
+                continue;
+            }
+        };
+
+        match words[0].as_str() {
+            "quit" | "exit" => break,
+            "help" => print_help(),
+            "history" => history.print(),
+            "show" => println!("{}", settings.join(" ")),
+            "reset" => settings = defaults.to_vec(),
+            "set" => change_settings(&mut settings, &words[1..]),
+            "cache" => print_cache(&session),
+            "flush" => {
+                session.cache().clear();
+                session.dnskey_cache().clear();
+                session.close_connections();
+            }
+            // lines that only give a nameserver and flags change the settings as well
+            _ if words
+                .iter()
+                .all(|w| w.starts_with('@') || w.starts_with('+')) =>
+            {
+                change_settings(&mut settings, &words)
+            }
+            _ => query(&settings, words, &session),
+        }
+    }
+    Ok(())
+}
+
+/// Adds `words` to `settings`, if the result is valid.
+fn change_settings(settings: &mut Vec<String>, words: &[String]) {
+    let mut changed = settings.clone();
+    changed.extend_from_slice(words);
+    if Args::try_parse_from(changed.clone()).is_some() {
+        *settings = changed;
+    }
+}
+
+/// Runs the query (or subcommand) given by `words`, with `settings` as the defaults.
+fn query(settings: &[String], words: Vec<String>, session: &Session) {
+    let mut all = settings.to_vec();
+    all.extend(words);
+    let Some(args) = Args::try_parse_from(all) else {
+        return;
+    };
+    if let Command::Watch = args.command {
+        eprintln!("Cannot use the watch subcommand in interactive mode.");
+        return;
+    }
+    if let Err(e) = crate::run_args(&args, Some(session)) {
+        crate::print_error(&e);
+    }
+}
+
+fn print_cache(session: &Session) {
+    let (queries, hits) = session.query_counts();
+    println!(
+        "{} cached answers; {} of {} queries answered from the cache",
+        session.cache().len(),
+        hits,
+        queries
+    );
+    let zones = session.dnskey_cache().len();
+    if zones > 0 {
+        println!("DNSKEYs of {} zones cached", zones);
+    }
+    for addr in session.tcp_connections() {
+        println!("TCP connection open to {}", addr);
+    }
+}
+
+fn print_help() {
+    println!("Enter a query as you would give it on the command line, without toluol, e.g.");
+    println!("\texample.com MX +tcp");
+    println!("Arguments given with -i and set are the defaults for each query. Commands:");
+    println!("\tset <args>    add <args> to the defaults, e.g. set @9.9.9.9 +tcp;");
+    println!("\t              a line with only @nameserver and +flags does the same");
+    println!("\tshow          show the defaults");
+    println!("\treset         reset the defaults to those given with -i");
+    println!("\tcache         show the number of cached answers and the open connections");
+    println!("\tflush         clear the cache and close the connections");
+    println!("\thistory       show the previous lines; !! repeats the last one, !<n> line <n>");
+    println!("\thelp          show this help");
+    println!("\tquit | exit   leave (or press Ctrl-D)");
+    println!("See toluol --help for all arguments.");
+}
+
+/// Splits `line` at whitespace, except inside single or double quotes.
+fn split_words(line: &str) -> Result<Vec<String>, &'static str> {
+    let mut words = Vec::new();
+    let mut word = None::<String>;
+    let mut quote = None;
+    for c in line.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err("Unterminated quote.");
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// The lines entered at the prompt, including those of earlier sessions.
+struct History {
+    lines: Vec<String>,
+    /// The file the lines are appended to, if it could be opened.
+    file: Option<File>,
+}
+
+impl History {
+    /// Reads the lines of earlier sessions from [`HISTORY_FILE`] and opens it to append the
+    /// lines of this one. The history is only kept in memory if there is no home directory.
+    fn load() -> Self {
+        let Some(path) = env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE))
+        else {
+            return Self {
+                lines: Vec::new(),
+                file: None,
+            };
+        };
+        let mut lines: Vec<String> = fs::read_to_string(&path)
+            .map(|history| history.lines().map(String::from).collect())
+            .unwrap_or_default();
+        if lines.len() > HISTORY_SIZE {
+            lines.drain(..lines.len() - HISTORY_SIZE);
+            // rewrite the file so that it doesn't grow forever
+            let _ = fs::write(&path, lines.join("\n") + "\n");
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .ok();
+        Self { lines, file }
+    }
+
+    fn add(&mut self, line: &str) {
+        if self.lines.last().is_some_and(|last| last == line) {
+            return;
+        }
+        self.lines.push(line.to_string());
+        if let Some(file) = &mut self.file {
+            // the history is a convenience, so failing to save it is not worth an error
+            let _ = writeln!(file, "{}", line);
+        }
+    }
+
+    /// Replaces `!!` with the last line and `!<n>` with line `n` (see [`Self::print()`]).
+    fn expand(&self, line: &str) -> Result<String, String> {
+        let Some(reference) = line.strip_prefix('!') else {
+            return Ok(line.to_string());
+        };
+        let index = if reference == "!" {
+            self.lines.len().checked_sub(1)
+        } else {
+            match reference.parse::<usize>() {
+                Ok(n) => n.checked_sub(1).filter(|&i| i < self.lines.len()),
+                Err(_) => return Err(format!("Invalid history reference: {}.", line)),
+            }
+        };
+        index
+            .map(|i| self.lines[i].clone())
+            .ok_or_else(|| format!("No such line in the history: {}.", line))
+    }
+
+    fn print(&self) {
+        let width = self.lines.len().to_string().len();
+        for (i, line) in self.lines.iter().enumerate() {
+            println!("{:>width$}  {}", i + 1, line, width = width);
+        }
+    }
+}
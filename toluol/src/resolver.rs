@@ -0,0 +1,1042 @@
+//! A stub resolver that sends queries to a list of nameservers, configured like the system's stub
+//! resolver (see `resolv.conf(5)`).
+
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use std::collections::BTreeMap;
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use toluol_proto::dnssec::RrSet;
+use toluol_proto::rdata::SVCB;
+use toluol_proto::{
+    Class, HeaderFlags, Message, Name, Opcode, Question, RCode, Rdata, Record, RecordType,
+};
+
+use crate::cache::{CachedDnskeys, DnskeyCache};
+use crate::error::{Context, Error, Result};
+use crate::hosts::Hosts;
+use crate::net::{RawTransport, StdTransport};
+use crate::querylog::QueryLog;
+use crate::ratelimit::{RateLimit, RateLimitStats, RateLimiter};
+use crate::stats::{ServerStats, UNKNOWN_RTT};
+use crate::util::{query_single_via, use_tcp_for_large_query, Answer};
+use crate::QueryMetadata;
+
+/// The path of the system's stub resolver configuration.
+pub const RESOLV_CONF: &str = "/etc/resolv.conf";
+
+/// The maximum number of nameservers that are used, the same as for glibc.
+const MAX_NAMESERVERS: usize = 3;
+
+/// The number of random names queried by [`Resolver::detect_wildcard()`].
+const WILDCARD_PROBES: usize = 3;
+
+/// The maximum number of AliasMode `HTTPS` records followed by [`Resolver::lookup_ip()`].
+const MAX_HTTPS_ALIASES: usize = 8;
+
+/// The configuration of a [`Resolver`], usually read from `/etc/resolv.conf`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResolverConfig {
+    /// The nameservers to query, in order.
+    pub nameservers: Vec<String>,
+    /// The domains to search for names that are not fully qualified.
+    pub search: Vec<Name>,
+    /// Names with at least this many dots are tried as given before the search domains are
+    /// appended.
+    pub ndots: u8,
+    /// How long to wait for an answer from a nameserver before trying the next one.
+    pub timeout: Duration,
+    /// How often to try all nameservers before giving up.
+    pub attempts: u32,
+    /// Whether to distribute queries over all nameservers instead of always trying the first one
+    /// first.
+    pub rotate: bool,
+}
+
+impl Default for ResolverConfig {
+    /// The defaults used by glibc if `/etc/resolv.conf` doesn't set anything.
+    fn default() -> Self {
+        Self {
+            nameservers: vec!["127.0.0.1".into()],
+            search: Vec::new(),
+            ndots: 1,
+            timeout: Duration::from_secs(5),
+            attempts: 2,
+            rotate: false,
+        }
+    }
+}
+
+impl ResolverConfig {
+    /// Reads the system's stub resolver configuration from [`RESOLV_CONF`].
+    pub fn system() -> Result<Self> {
+        Self::from_file(RESOLV_CONF)
+    }
+
+    /// Reads the stub resolver configuration from the file at `path`, which must have the format
+    /// of `resolv.conf(5)`.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let conf = fs::read_to_string(path)
+            .configuration(format!("Could not read {}.", path.display()))?;
+        Ok(Self::parse(&conf))
+    }
+
+    /// Parses a stub resolver configuration in the format of `resolv.conf(5)`.
+    ///
+    /// Like the system's resolver, unknown or invalid lines and options are ignored, and defaults
+    /// are used for anything that is not set.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use std::time::Duration;
+    /// use toluol::resolver::ResolverConfig;
+    ///
+    /// let conf = ResolverConfig::parse(
+    ///     "nameserver 192.0.2.1\nnameserver 2001:db8::1\nsearch example.com\n\
+    ///      options timeout:2 attempts:3 rotate ndots:2",
+    /// );
+    /// assert_eq!(conf.nameservers, vec!["192.0.2.1", "2001:db8::1"]);
+    /// assert_eq!(conf.search.len(), 1);
+    /// assert_eq!(conf.timeout, Duration::from_secs(2));
+    /// assert_eq!(conf.attempts, 3);
+    /// assert!(conf.rotate);
+    /// assert_eq!(conf.ndots, 2);
+    /// ```
+    pub fn parse(conf: &str) -> Self {
+        let mut res = Self::default();
+        let mut nameservers = Vec::new();
+
+        for line in conf.lines() {
+            let mut words = line.split_whitespace();
+            let keyword = match words.next() {
+                Some(keyword) if !keyword.starts_with(['#', ';']) => keyword,
+                _ => continue,
+            };
+            match keyword {
+                "nameserver" => {
+                    if let Some(ns) = words.next() {
+                        if nameservers.len() < MAX_NAMESERVERS {
+                            nameservers.push(ns.to_string());
+                        }
+                    }
+                }
+                // the last domain or search line wins
+                "domain" | "search" => {
+                    res.search = words.filter_map(|d| Name::from_ascii(d).ok()).collect();
+                }
+                "options" => {
+                    for option in words {
+                        res.set_option(option);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if !nameservers.is_empty() {
+            res.nameservers = nameservers;
+        }
+        res
+    }
+
+    /// Applies a single option, e.g. `timeout:2`. The values are limited like glibc does.
+    fn set_option(&mut self, option: &str) {
+        let (name, value) = match option.split_once(':') {
+            Some((name, value)) => (name, value.parse::<u32>().ok()),
+            None => (option, None),
+        };
+        match (name, value) {
+            ("ndots", Some(ndots)) => self.ndots = ndots.min(15) as u8,
+            ("timeout", Some(timeout)) => {
+                self.timeout = Duration::from_secs(timeout.clamp(1, 30) as u64)
+            }
+            ("attempts", Some(attempts)) => self.attempts = attempts.clamp(1, 5),
+            ("rotate", None) => self.rotate = true,
+            _ => {}
+        }
+    }
+}
+
+/// Sends queries to the nameservers of a [`ResolverConfig`], the way the system's stub resolver
+/// does.
+///
+/// Each nameserver is tried in turn with the configured timeout, and all nameservers are tried up
+/// to the configured number of attempts. Nameservers answering with SERVFAIL, NOTIMP, or REFUSED
+/// are skipped as well. If `rotate` is set, each query starts with the next nameserver.
+///
+/// Optionally, [`Self::lookup()`] consults hosts files before sending any queries, see
+/// [`Self::with_hosts()`].
+///
+/// Optionally, outgoing queries are rate limited, see [`Self::with_rate_limit()`].
+///
+/// Optionally, nameservers are tried in the order of their smoothed RTT instead of the configured
+/// order, see [`Self::with_server_stats()`].
+///
+/// Queries are sent via [`StdTransport`] unless another transport is given, see
+/// [`Self::with_transport()`].
+///
+/// A `Resolver` can be shared between threads. Concurrent queries for the same name and type are
+/// coalesced: only the first caller sends queries, and all others wait for and share its result.
+#[derive(Debug)]
+pub struct Resolver {
+    config: ResolverConfig,
+    metadata: QueryMetadata,
+    bufsize: u16,
+    next_nameserver: AtomicUsize,
+    hosts: Option<Hosts>,
+    rate_limiter: Option<RateLimiter>,
+    transport: Box<dyn RawTransport>,
+    server_stats: Option<Arc<ServerStats>>,
+    dnskeys: Arc<DnskeyCache>,
+    /// The queries that are currently being sent, keyed on the name and type.
+    in_flight: Mutex<BTreeMap<(Name, u16), Arc<Flight>>>,
+}
+
+/// The shared result of a query that is currently being sent.
+#[derive(Debug, Default)]
+struct Flight {
+    /// The answer, or the error message. [`Error`] can't be cloned, so waiting callers
+    /// receive a new error with the same message.
+    result: Mutex<Option<Result<Answer, String>>>,
+    done: Condvar,
+}
+
+impl Flight {
+    /// Waits until the result is available and returns it.
+    fn wait(&self) -> Result<Answer> {
+        let result = self.result.lock().expect("Flight mutex poisoned.");
+        let result = self
+            .done
+            .wait_while(result, |result| result.is_none())
+            .expect("Flight mutex poisoned.");
+        match result.as_ref().expect("Flight finished without result.") {
+            Ok(answer) => Ok(answer.clone()),
+            Err(e) => Err(Error::transport(e)),
+        }
+    }
+
+    /// Stores the result and wakes up all waiting callers.
+    fn finish(&self, result: Result<Answer, String>) {
+        *self.result.lock().expect("Flight mutex poisoned.") = Some(result);
+        self.done.notify_all();
+    }
+}
+
+/// Removes a query from [`Resolver::in_flight`] when it is finished. If the query did not finish
+/// (i.e. it panicked), the waiting callers receive an error.
+struct FlightGuard<'a> {
+    resolver: &'a Resolver,
+    key: (Name, u16),
+    flight: Arc<Flight>,
+}
+
+impl Drop for FlightGuard<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut in_flight) = self.resolver.in_flight.lock() {
+            in_flight.remove(&self.key);
+        }
+        let finished = self
+            .flight
+            .result
+            .lock()
+            .map_or(true, |result| result.is_some());
+        if !finished {
+            self.flight.finish(Err("The query was aborted.".into()));
+        }
+    }
+}
+
+/// The result of [`Resolver::lookup()`].
+#[derive(Clone, Debug)]
+pub enum Lookup {
+    /// The answer was synthesized from the entries of a hosts file.
+    Hosts {
+        /// The hosts file the answer records come from.
+        path: PathBuf,
+        /// The synthesized response.
+        message: Message,
+    },
+    /// The answer was received from a nameserver.
+    Nameserver(Answer),
+}
+
+impl Lookup {
+    /// Returns the response.
+    pub fn message(&self) -> &Message {
+        match self {
+            Self::Hosts { message, .. } => message,
+            Self::Nameserver((_, message, _, _)) => message,
+        }
+    }
+
+    /// Returns one [`RecordRow`] for each record of the answer section, in order.
+    pub fn rows(&self) -> Vec<RecordRow> {
+        let (server, rtt) = match self {
+            Self::Hosts { path, .. } => (path.display().to_string(), None),
+            Self::Nameserver((nameserver, _, _, timing)) => {
+                let server = match (nameserver.ip, &nameserver.hostname) {
+                    (Some(ip), _) => ip.to_string(),
+                    (None, Some(hostname)) => hostname.clone(),
+                    (None, None) => String::new(),
+                };
+                (server, Some(timing.elapsed))
+            }
+        };
+        self.message()
+            .answers
+            .iter()
+            .filter_map(Record::as_nonopt)
+            .map(|record| RecordRow {
+                owner: record.owner.clone(),
+                ttl: record.ttl,
+                class: record.class,
+                rtype: record.rtype,
+                rdata: record.rdata().to_string(),
+                server: server.clone(),
+                rtt,
+            })
+            .collect()
+    }
+}
+
+/// A record of a [`Lookup`] with the columns `owner`, `ttl`, `class`, `type`, `rdata`, `server`,
+/// and `rtt` (in milliseconds), so that it can be processed with tools like `awk` or `cut`, see
+/// [`Lookup::rows()`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordRow {
+    pub owner: Name,
+    pub ttl: u32,
+    pub class: Class,
+    pub rtype: RecordType,
+    /// The record data in presentation format.
+    pub rdata: String,
+    /// The address of the nameserver that sent the answer, or the path of the hosts file it was
+    /// synthesized from.
+    pub server: String,
+    /// The round-trip time of the query, or [`None`] if the answer is from a hosts file.
+    pub rtt: Option<Duration>,
+}
+
+impl RecordRow {
+    /// Returns the row as a line of CSV (without the line break). Fields that contain commas,
+    /// quotes, or line breaks are quoted as described in
+    /// [RFC 4180](https://www.rfc-editor.org/rfc/rfc4180).
+    ///
+    /// # Examples
+    /// ```rust
+    /// use std::time::Duration;
+    /// use toluol::resolver::RecordRow;
+    /// use toluol_proto::{Class, Name, RecordType};
+    ///
+    /// let row = RecordRow {
+    ///     owner: Name::from_ascii("example.com").unwrap(),
+    ///     ttl: 300,
+    ///     class: Class::IN,
+    ///     rtype: RecordType::TXT,
+    ///     rdata: "\"a, b\"".into(),
+    ///     server: "192.0.2.53".into(),
+    ///     rtt: Some(Duration::from_micros(12500)),
+    /// };
+    /// assert_eq!(
+    ///     row.to_csv(),
+    ///     r#"example.com,300,IN,TXT,"""a, b""",192.0.2.53,12.500"#
+    /// );
+    /// assert_eq!(
+    ///     row.to_tsv(),
+    ///     "example.com\t300\tIN\tTXT\t\"a, b\"\t192.0.2.53\t12.500"
+    /// );
+    /// ```
+    pub fn to_csv(&self) -> String {
+        let fields: Vec<_> = self
+            .fields()
+            .into_iter()
+            .map(|field| {
+                if field.contains([',', '"', '\r', '\n']) {
+                    format!("\"{}\"", field.replace('"', "\"\""))
+                } else {
+                    field
+                }
+            })
+            .collect();
+        fields.join(",")
+    }
+
+    /// Returns the row as a line of tab-separated values (without the line break). Tabs and line
+    /// breaks in fields are replaced with spaces.
+    pub fn to_tsv(&self) -> String {
+        let fields: Vec<_> = self
+            .fields()
+            .into_iter()
+            .map(|field| field.replace(['\t', '\r', '\n'], " "))
+            .collect();
+        fields.join("\t")
+    }
+
+    fn fields(&self) -> [String; 7] {
+        [
+            self.owner.to_string(),
+            self.ttl.to_string(),
+            self.class.to_string(),
+            self.rtype.to_string(),
+            self.rdata.clone(),
+            self.server.clone(),
+            self.rtt
+                .map(|rtt| format!("{:.3}", rtt.as_secs_f64() * 1000.0))
+                .unwrap_or_default(),
+        ]
+    }
+}
+
+/// A wildcard found by [`Resolver::detect_wildcard()`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Wildcard {
+    /// The RDATA of the records that the non-existent names resolved to, without duplicates.
+    pub targets: Vec<Rdata>,
+}
+
+/// The result of [`Resolver::lookup_ip()`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IpLookup {
+    /// The name the addresses belong to: the target of the endpoint's `HTTPS` record, or the
+    /// looked up name (or the target of an alias) if there is no endpoint.
+    pub target: Name,
+    /// The `HTTPS` record of the endpoint, with its connection parameters like the ALPN protocols
+    /// and the port. [`None`] if there are no usable `HTTPS` records.
+    pub endpoint: Option<SVCB>,
+    /// The addresses of the target, IPv4 addresses first.
+    pub addresses: Vec<IpAddr>,
+}
+
+impl Resolver {
+    /// Creates a resolver using `config`. Queries are built from `metadata`, except for the name,
+    /// type, and nameserver.
+    pub fn new(config: ResolverConfig, metadata: QueryMetadata, bufsize: u16) -> Self {
+        Self {
+            config,
+            metadata,
+            bufsize,
+            next_nameserver: AtomicUsize::new(0),
+            hosts: None,
+            rate_limiter: None,
+            transport: Box::new(StdTransport),
+            server_stats: None,
+            dnskeys: Arc::default(),
+            in_flight: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Makes [`Self::lookup()`] consult `hosts` for A, AAAA, and PTR records before sending
+    /// queries, like applications using the system's resolver do.
+    pub fn with_hosts(mut self, hosts: Hosts) -> Self {
+        self.hosts = Some(hosts);
+        self
+    }
+
+    /// Limits the rate of queries sent by [`Self::query()`] as configured by `rate_limit`.
+    ///
+    /// Queries that would be delayed longer than [`RateLimit::max_delay`] are not sent; the
+    /// resolver tries the next nameserver instead.
+    pub fn with_rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limiter = Some(RateLimiter::new(rate_limit));
+        self
+    }
+
+    /// Sends all queries via `transport` instead of creating a socket for each query.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use std::io::Cursor;
+    /// use std::time::{Duration, Instant, SystemTime};
+    /// use toluol::error::{Context, Result};
+    /// use toluol::net::{Nameserver, QueryTiming, RawTransport, TcpOptions};
+    /// use toluol::resolver::{Resolver, ResolverConfig};
+    /// use toluol::{ConnectionType, QueryMetadata};
+    /// use toluol_proto::{Class, HeaderFlags, Message, Name, Opcode, RCode, RecordType};
+    ///
+    /// /// Answers every query with NXDOMAIN without sending anything.
+    /// #[derive(Debug)]
+    /// struct NxTransport;
+    ///
+    /// impl RawTransport for NxTransport {
+    ///     fn send_query(
+    ///         &self,
+    ///         _metadata: &QueryMetadata,
+    ///         _bufsize: u16,
+    ///         _nameserver: &mut Nameserver,
+    ///         data: &[u8],
+    ///     ) -> Result<(Vec<u8>, u16, QueryTiming)> {
+    ///         let before = Instant::now();
+    ///         let query = Message::parse(&mut Cursor::new(data)).parse("Invalid query.")?;
+    ///         let flags = HeaderFlags { aa: true, tc: false, rd: true, ra: false, ad: false, cd: false };
+    ///         let res = Message::new_response(
+    ///             query.header.msg_id,
+    ///             Opcode::QUERY,
+    ///             flags,
+    ///             RCode::NXDOMAIN,
+    ///             query.questions,
+    ///             [Vec::new(), Vec::new(), Vec::new()],
+    ///         )
+    ///         .encode()
+    ///         .parse("Could not encode the answer.")?;
+    ///         let timing = QueryTiming {
+    ///             sent: SystemTime::now(),
+    ///             received: SystemTime::now(),
+    ///             elapsed: before.elapsed(),
+    ///             handshake: None,
+    ///             http_cache: None,
+    ///             retransmissions: 0,
+    ///             resolved_addrs: Vec::new(),
+    ///             tcp_fallback: None,
+    ///             attempts: Vec::new(),
+    ///         };
+    ///         let len = res.len() as u16;
+    ///         Ok((res, len, timing))
+    ///     }
+    /// }
+    ///
+    /// let metadata = QueryMetadata {
+    ///     name: Name::root(),
+    ///     qtype: RecordType::A,
+    ///     qclass: Class::IN,
+    ///     opcode: Opcode::QUERY,
+    ///     nameserver: "192.0.2.1".into(),
+    ///     port: 53,
+    ///     connection_type: ConnectionType::Udp,
+    ///     fetch_dnssec: false,
+    ///     validate_dnssec: false,
+    ///     dnssec_ok: None,
+    ///     checking_disabled: true,
+    ///     edns: true,
+    ///     edns_version: 0,
+    ///     edns_options: Vec::new(),
+    ///     client_cookie: None,
+    ///     client_subnet: None,
+    ///     check_source: true,
+    ///     timeout: Duration::from_secs(1),
+    ///     retransmissions: 0,
+    ///     reuse_udp_sockets: false,
+    ///     tcp_options: TcpOptions::default(),
+    ///     doh_json_fallback: false,
+    ///     query_log: None,
+    /// };
+    /// let resolver = Resolver::new(ResolverConfig::default(), metadata, 1232)
+    ///     .with_transport(NxTransport);
+    /// let name = Name::from_ascii("nonexistent.example").unwrap();
+    /// let (_, res, _, _) = resolver.query(&name, RecordType::A).unwrap();
+    /// assert_eq!(res.header.rcode, Some(RCode::NXDOMAIN));
+    /// ```
+    pub fn with_transport(mut self, transport: impl RawTransport + 'static) -> Self {
+        self.transport = Box::new(transport);
+        self
+    }
+
+    /// Appends each query the resolver sends and its response to `log`, see [`QueryLog`].
+    pub fn with_query_log(mut self, log: Arc<QueryLog>) -> Self {
+        self.metadata.query_log = Some(log);
+        self
+    }
+
+    /// Records the RTT, timeouts, and EDNS support of the nameservers in `stats`, and tries the
+    /// nameservers in the order of their smoothed RTT (unless `rotate` is set), so that slow or
+    /// unreachable nameservers are avoided.
+    ///
+    /// `stats` may be shared with other resolvers and iterative queries, see
+    /// [`iter::query_with_stats()`](crate::iter::query_with_stats()).
+    pub fn with_server_stats(mut self, stats: Arc<ServerStats>) -> Self {
+        self.server_stats = Some(stats);
+        self
+    }
+
+    /// Returns the nameserver statistics, or [`None`] if there are none (see
+    /// [`Self::with_server_stats()`]).
+    pub fn server_stats(&self) -> Option<&Arc<ServerStats>> {
+        self.server_stats.as_ref()
+    }
+
+    /// Keeps the DNSKEYs fetched by [`Self::dnskeys()`] in `cache`, which may be shared with other
+    /// resolvers, sessions, and iterative queries (see
+    /// [`iter::query_with_caches()`](crate::iter::query_with_caches())).
+    pub fn with_dnskey_cache(mut self, cache: Arc<DnskeyCache>) -> Self {
+        self.dnskeys = cache;
+        self
+    }
+
+    /// Returns the cache of the DNSKEYs fetched by [`Self::dnskeys()`].
+    pub fn dnskey_cache(&self) -> &Arc<DnskeyCache> {
+        &self.dnskeys
+    }
+
+    /// Returns the DNSKEY records of `zone` and the RRSIGs covering them, querying the
+    /// nameservers like [`Self::query()`] (with the DO bit set) unless they are cached.
+    ///
+    /// The result includes whether the DNSKEYs are signed by themselves, see [`CachedDnskeys`].
+    pub fn dnskeys(&self, zone: &Name) -> Result<CachedDnskeys> {
+        self.dnskeys.get_or_fetch(zone, Instant::now(), || {
+            let mut metadata = self.metadata.clone();
+            metadata.opcode = Opcode::QUERY;
+            metadata.edns = true;
+            metadata.dnssec_ok = Some(true);
+            let (_, mut res, _, _) = self
+                .query_nameservers_with(metadata, zone, RecordType::DNSKEY)
+                .validation(format!("Could not get DNSKEYs for the {} zone.", zone))?;
+            Ok(res
+                .take_rrsets(|rec| matches!(rec.rtype, RecordType::DNSKEY | RecordType::RRSIG))
+                .into_iter()
+                .flat_map(RrSet::into_records)
+                .collect())
+        })
+    }
+
+    /// Returns statistics about the queries that were delayed or not sent due to the rate limit,
+    /// or [`None`] if there is no rate limit.
+    pub fn rate_limit_stats(&self) -> Option<RateLimitStats> {
+        self.rate_limiter.as_ref().map(RateLimiter::stats)
+    }
+
+    /// Returns the resolver's configuration.
+    pub fn config(&self) -> &ResolverConfig {
+        &self.config
+    }
+
+    /// Returns the metadata the resolver's queries are built from.
+    pub fn metadata(&self) -> &QueryMetadata {
+        &self.metadata
+    }
+
+    /// Queries for records of type `qtype` for `name`.
+    ///
+    /// Returns the first usable answer, or the last answer or error if no nameserver gave a usable
+    /// answer. If a query for the same name and type is already being sent, waits for its result
+    /// instead of sending another one.
+    pub fn query(&self, name: &Name, qtype: RecordType) -> Result<Answer> {
+        let key = (name.clone(), u16::from(qtype));
+        let flight = {
+            let mut in_flight = self.in_flight.lock().expect("Resolver mutex poisoned.");
+            if let Some(flight) = in_flight.get(&key) {
+                let flight = flight.clone();
+                drop(in_flight);
+                return flight.wait();
+            }
+            let flight = Arc::new(Flight::default());
+            in_flight.insert(key.clone(), flight.clone());
+            flight
+        };
+
+        let guard = FlightGuard {
+            resolver: self,
+            key,
+            flight,
+        };
+        let result = self.query_nameservers(name, qtype);
+        guard.flight.finish(match &result {
+            Ok(answer) => Ok(answer.clone()),
+            Err(e) => Err(format!("{:#}", e)),
+        });
+        result
+    }
+
+    /// Sends the queries for [`Self::query()`], without coalescing.
+    fn query_nameservers(&self, name: &Name, qtype: RecordType) -> Result<Answer> {
+        self.query_nameservers_with(self.metadata.clone(), name, qtype)
+    }
+
+    /// Like [`Self::query_nameservers()`], but builds the queries from `metadata` instead of the
+    /// resolver's metadata.
+    fn query_nameservers_with(
+        &self,
+        mut metadata: QueryMetadata,
+        name: &Name,
+        qtype: RecordType,
+    ) -> Result<Answer> {
+        metadata.name = name.clone();
+        metadata.qtype = qtype;
+        metadata.timeout = self.config.timeout;
+        // we retry by trying the next nameserver
+        metadata.retransmissions = 0;
+
+        let nameservers = &self.config.nameservers;
+        if nameservers.is_empty() {
+            return Err(Error::configuration("No nameservers configured."));
+        }
+        let start = if self.config.rotate {
+            self.next_nameserver.fetch_add(1, Ordering::Relaxed) % nameservers.len()
+        } else {
+            0
+        };
+        let mut order: Vec<_> = (0..nameservers.len())
+            .map(|i| &nameservers[(start + i) % nameservers.len()])
+            .collect();
+        if let (Some(stats), false) = (&self.server_stats, self.config.rotate) {
+            order.sort_by_key(|nameserver| match nameserver.parse::<IpAddr>() {
+                Ok(ip) => stats.srtt(ip),
+                Err(_) => UNKNOWN_RTT,
+            });
+        }
+
+        let connection_type = metadata.connection_type;
+        let mut last = Err(Error::configuration("No nameservers configured."));
+        for _ in 0..self.config.attempts {
+            for &nameserver in &order {
+                metadata.nameserver = nameserver.clone();
+                if let Some(rate_limiter) = &self.rate_limiter {
+                    if let Err(e) = rate_limiter.acquire(&metadata.nameserver) {
+                        last = Err(e);
+                        continue;
+                    }
+                }
+                metadata.connection_type = connection_type;
+                if let (Some(stats), Ok(ip)) = (&self.server_stats, metadata.nameserver.parse()) {
+                    use_tcp_for_large_query(&mut metadata, self.bufsize, stats.max_query_size(ip));
+                }
+                let answer = query_single_via(self.transport.as_ref(), &metadata, self.bufsize);
+                if let Some(stats) = &self.server_stats {
+                    match &answer {
+                        Ok((nameserver, res, _, timing)) => {
+                            if let Some(ip) = nameserver.ip {
+                                stats.record_answer(ip, timing.elapsed, res);
+                            }
+                        }
+                        Err(_) => {
+                            if let Ok(ip) = metadata.nameserver.parse() {
+                                stats.record_timeout(ip, metadata.timeout);
+                            }
+                        }
+                    }
+                }
+                if let Ok((_, res, _, _)) = &answer {
+                    let rcode = match res.opt() {
+                        Some(opt) => opt.rcode,
+                        None => res.header.rcode,
+                    };
+                    let rcode = rcode.unwrap_or(RCode::NOERROR);
+                    if ![RCode::SERVFAIL, RCode::NOTIMP, RCode::REFUSED].contains(&rcode) {
+                        return answer;
+                    }
+                }
+                last = answer;
+            }
+        }
+        last
+    }
+
+    /// Looks up records of type `qtype` for `name`, first in the hosts files (if any, see
+    /// [`Self::with_hosts()`]) and then by querying the nameservers with [`Self::query()`].
+    ///
+    /// The names from the search domains are tried in order (see [`Self::search_names()`]), until
+    /// one of them exists, i.e. the answer is not NXDOMAIN. If none of them does, the answer for
+    /// the last one is returned.
+    pub fn lookup(&self, name: &Name, qtype: RecordType) -> Result<Lookup> {
+        let dots = (name.label_count() as usize).saturating_sub(1);
+        let mut candidates = self.search_candidates(name.clone(), dots);
+        let last = candidates
+            .pop()
+            .expect("The name itself is always a candidate.");
+        for candidate in candidates {
+            let lookup = self.lookup_exact(&candidate, qtype)?;
+            if lookup.message().header.rcode != Some(RCode::NXDOMAIN) {
+                return Ok(lookup);
+            }
+        }
+        self.lookup_exact(&last, qtype)
+    }
+
+    /// Like [`Self::lookup()`], but only for `name` itself.
+    fn lookup_exact(&self, name: &Name, qtype: RecordType) -> Result<Lookup> {
+        let from_hosts = match &self.hosts {
+            Some(hosts) if self.metadata.qclass == Class::IN => hosts.lookup_with_path(name, qtype),
+            _ => None,
+        };
+        let (records, path) = match from_hosts {
+            Some(from_hosts) => from_hosts,
+            None => return self.query(name, qtype).map(Lookup::Nameserver),
+        };
+
+        let flags = HeaderFlags {
+            aa: false,
+            tc: false,
+            rd: true,
+            ra: true,
+            ad: false,
+            cd: false,
+        };
+        let message = Message::new_response(
+            0,
+            Opcode::QUERY,
+            flags,
+            RCode::NOERROR,
+            vec![Question::new(name.clone(), qtype, Class::IN)],
+            [
+                records.into_iter().map(Record::NONOPT).collect(),
+                Vec::new(),
+                Vec::new(),
+            ],
+        );
+        Ok(Lookup::Hosts {
+            path: path.to_path_buf(),
+            message,
+        })
+    }
+
+    /// Like [`Self::query_multiple()`], but uses [`Self::lookup()`] for each query.
+    pub fn lookup_multiple(
+        &self,
+        name: &Name,
+        qtypes: &[RecordType],
+    ) -> Vec<(RecordType, Result<Lookup>)> {
+        thread::scope(|s| {
+            let handles: Vec<_> = qtypes
+                .iter()
+                .map(|&qtype| s.spawn(move || (qtype, self.lookup(name, qtype))))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("Query thread panicked."))
+                .collect()
+        })
+    }
+
+    /// Sends one query for each of the given `qtypes` for `name`, concurrently.
+    ///
+    /// Returns the result of each query together with the queried type, in the order of `qtypes`.
+    pub fn query_multiple(
+        &self,
+        name: &Name,
+        qtypes: &[RecordType],
+    ) -> Vec<(RecordType, Result<Answer>)> {
+        thread::scope(|s| {
+            let handles: Vec<_> = qtypes
+                .iter()
+                .map(|&qtype| s.spawn(move || (qtype, self.query(name, qtype))))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("Query thread panicked."))
+                .collect()
+        })
+    }
+
+    /// Checks whether `zone` contains a wildcard for the resolver's query type (see
+    /// [`Self::metadata()`]) by querying several random names under `zone` that almost certainly
+    /// don't exist. The queries are sent with [`Self::query()`], i.e. to the resolver's nameservers
+    /// and subject to its rate limit.
+    ///
+    /// Returns the wildcard's targets if all of the names resolved, and [`None`] if any of them did
+    /// not. CNAME records in the answers are included in the targets. This is useful to avoid false
+    /// positives when checking whether names exist in a zone.
+    pub fn detect_wildcard(&self, zone: &Name) -> Result<Option<Wildcard>> {
+        let qtype = self.metadata.qtype;
+        let mut targets = Vec::new();
+        for _ in 0..WILDCARD_PROBES {
+            let label: String = rand::thread_rng()
+                .sample_iter(Alphanumeric)
+                .take(16)
+                .map(|c| char::from(c).to_ascii_lowercase())
+                .collect();
+            let mut name = zone.clone();
+            name.prepend_label(format!("toluol-{}", label))
+                .parse("Could not create random name.")?;
+
+            let (_, res, _, _) = self.query(&name, qtype)?;
+            let mut found = false;
+            for record in res.answers.iter().filter_map(Record::as_nonopt) {
+                if record.rtype != qtype && record.rtype != RecordType::CNAME {
+                    continue;
+                }
+                found = true;
+                if !targets.contains(record.rdata()) {
+                    targets.push(record.rdata().clone());
+                }
+            }
+            if !found {
+                return Ok(None);
+            }
+        }
+
+        Ok(Some(Wildcard { targets }))
+    }
+
+    /// Looks up the addresses to connect to for `name` via HTTPS, following the client algorithm
+    /// of [RFC 9460, Section 3](https://www.rfc-editor.org/rfc/rfc9460#section-3).
+    ///
+    /// The `HTTPS` records of `name` are looked up with [`Self::lookup()`]. AliasMode records are
+    /// followed to their target, up to [`MAX_HTTPS_ALIASES`] times. Of the ServiceMode records,
+    /// the one with the lowest priority is the endpoint, and the addresses of its `ipv4hint` and
+    /// `ipv6hint` parameters are used. For address families without hints, the `A` or `AAAA`
+    /// records of the endpoint's target are looked up instead.
+    ///
+    /// Without ServiceMode records, the `A` and `AAAA` records of the last alias target (or of
+    /// `name` itself) are looked up. If the service is marked as unavailable (an alias to `.`) or
+    /// there are too many aliases, the `HTTPS` records are ignored and the `A` and `AAAA` records
+    /// of `name` are looked up, as the `HTTPS` records are optional for clients.
+    ///
+    /// Returns an error if the `HTTPS` query fails, or if an address lookup fails and the others
+    /// yield no addresses.
+    pub fn lookup_ip(&self, name: &Name) -> Result<IpLookup> {
+        let both = [RecordType::A, RecordType::AAAA];
+        let without_https = |target: &Name, search| {
+            Ok(IpLookup {
+                target: target.clone(),
+                endpoint: None,
+                addresses: self.lookup_addresses(target, &both, search)?,
+            })
+        };
+
+        let mut lookup = self.lookup(name, RecordType::HTTPS)?;
+        let mut aliases = 0;
+        let mut alias_target = None;
+        loop {
+            let records: Vec<_> = lookup
+                .message()
+                .answers
+                .iter()
+                .filter_map(Record::as_nonopt)
+                .filter_map(|record| match record.rdata() {
+                    Rdata::HTTPS(https) => Some((&record.owner, https)),
+                    _ => None,
+                })
+                .collect();
+
+            // ServiceMode records are to be ignored if there is an alias
+            if let Some(&(owner, alias)) = records.iter().find(|(_, https)| https.is_alias_mode()) {
+                match alias.target_name(owner) {
+                    Some(target) if aliases < MAX_HTTPS_ALIASES => {
+                        aliases += 1;
+                        let target = target.clone();
+                        lookup = self.lookup_exact(&target, RecordType::HTTPS)?;
+                        alias_target = Some(target);
+                        continue;
+                    }
+                    _ => return without_https(name, true),
+                }
+            }
+
+            let (owner, endpoint) =
+                match records.into_iter().min_by_key(|(_, https)| https.priority) {
+                    Some(endpoint) => endpoint,
+                    None => {
+                        return match &alias_target {
+                            Some(target) => without_https(target, false),
+                            None => without_https(name, true),
+                        }
+                    }
+                };
+            let target = endpoint
+                .target_name(owner)
+                .expect("ServiceMode records always have a target.")
+                .clone();
+            let mut addresses: Vec<_> = endpoint
+                .ipv4_hint()
+                .iter()
+                .map(|&ip| IpAddr::V4(ip))
+                .chain(endpoint.ipv6_hint().iter().map(|&ip| IpAddr::V6(ip)))
+                .collect();
+            let mut qtypes = Vec::new();
+            if endpoint.ipv4_hint().is_empty() {
+                qtypes.push(RecordType::A);
+            }
+            if endpoint.ipv6_hint().is_empty() {
+                qtypes.push(RecordType::AAAA);
+            }
+            if !qtypes.is_empty() {
+                addresses.extend(self.lookup_addresses(&target, &qtypes, false)?);
+                addresses.sort_by_key(IpAddr::is_ipv6);
+            }
+            return Ok(IpLookup {
+                target,
+                endpoint: Some(endpoint.clone()),
+                addresses,
+            });
+        }
+    }
+
+    /// Looks up the `A` and/or `AAAA` records (as given by `qtypes`) of `name` concurrently for
+    /// [`Self::lookup_ip()`], with the search domains if `search` is set, and returns their
+    /// addresses in the order of `qtypes`.
+    ///
+    /// Returns an error if a lookup fails and the others yield no addresses.
+    fn lookup_addresses(
+        &self,
+        name: &Name,
+        qtypes: &[RecordType],
+        search: bool,
+    ) -> Result<Vec<IpAddr>> {
+        let lookups = if search {
+            self.lookup_multiple(name, qtypes)
+        } else {
+            thread::scope(|s| {
+                let handles: Vec<_> = qtypes
+                    .iter()
+                    .map(|&qtype| s.spawn(move || (qtype, self.lookup_exact(name, qtype))))
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|handle| handle.join().expect("Query thread panicked."))
+                    .collect()
+            })
+        };
+
+        let mut addresses = Vec::new();
+        let mut error = None;
+        for (qtype, lookup) in lookups {
+            let lookup = match lookup {
+                Ok(lookup) => lookup,
+                Err(e) => {
+                    error = Some(e);
+                    continue;
+                }
+            };
+            let records = lookup
+                .message()
+                .answers
+                .iter()
+                .filter_map(Record::as_nonopt);
+            for record in records.filter(|record| record.rtype == qtype) {
+                match record.rdata() {
+                    Rdata::A(a) => addresses.push(IpAddr::V4(a.address)),
+                    Rdata::AAAA(aaaa) => addresses.push(IpAddr::V6(aaaa.address)),
+                    _ => {}
+                }
+            }
+        }
+        match error {
+            Some(e) if addresses.is_empty() => Err(e),
+            _ => Ok(addresses),
+        }
+    }
+
+    /// Returns the names that should be tried, in order, when looking up `name` (which may be
+    /// relative), according to the search domains and `ndots`.
+    ///
+    /// Names ending with a dot are never searched.
+    pub fn search_names(&self, name: &str) -> Result<Vec<Name>> {
+        let absolute = Name::from_ascii(name).configuration(format!("Invalid name: {}.", name))?;
+        if name.ends_with('.') {
+            return Ok(vec![absolute]);
+        }
+        Ok(self.search_candidates(absolute, name.matches('.').count()))
+    }
+
+    /// Returns the names to try for `absolute`, which was given with `dots` dots, see
+    /// [`Self::search_names()`].
+    fn search_candidates(&self, absolute: Name, dots: usize) -> Vec<Name> {
+        let searched = self.config.search.iter().filter_map(|domain| {
+            let mut searched = absolute.clone();
+            searched.append_name(domain.clone());
+            // skip names that are too long
+            Name::from_ascii(searched.to_string()).ok()
+        });
+        let mut names: Vec<_> = searched.collect();
+        if dots >= self.config.ndots as usize {
+            names.insert(0, absolute);
+        } else {
+            names.push(absolute);
+        }
+        names
+    }
+}
@@ -0,0 +1,243 @@
+//! Successive queries that share connections and an answer cache, e.g. in an interactive session,
+//! see [`Session`].
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::cache::{AnswerCache, CacheKey, DnskeyCache};
+use crate::error::{Context, Result};
+use crate::net::{
+    connect_tcp, Nameserver, QueryAttempt, QueryTiming, RawTransport, StdTransport, TcpOptions,
+};
+use crate::util::{query_single_via, Answer};
+use crate::{ConnectionType, QueryMetadata};
+
+/// Sends queries like [`query_single()`](crate::util::query_single()), but keeps what can be
+/// reused by later queries:
+///
+/// - TCP connections stay open after the answer was received, and are used for later queries to
+///   the same nameserver. If the nameserver closed the connection in the meantime, a new one is
+///   opened.
+/// - UDP queries reuse connected sockets (see
+///   [`UdpOptions::reuse_socket`](crate::net::UdpOptions::reuse_socket)).
+/// - Answers are cached for as long as their TTLs allow (see [`AnswerCache`]), so repeating a
+///   query doesn't send it again.
+/// - The DNSKEYs fetched to validate answers are cached as well (see [`DnskeyCache`]).
+///
+/// Other transports (DoT, DoH) send each query as [`StdTransport`] does.
+#[derive(Debug, Default)]
+pub struct Session {
+    cache: AnswerCache,
+    dnskeys: Arc<DnskeyCache>,
+    /// The open TCP connections, by the socket address of the nameserver.
+    tcp_connections: Mutex<Vec<(SocketAddr, TcpStream)>>,
+    queries: AtomicUsize,
+    cache_hits: AtomicUsize,
+}
+
+impl Session {
+    /// Sends the query described by `metadata`, unless its answer is cached.
+    ///
+    /// Returns the answer and, if it was taken from the cache, how long ago it was received.
+    pub fn query(
+        &self,
+        metadata: &QueryMetadata,
+        bufsize: u16,
+    ) -> Result<(Answer, Option<Duration>)> {
+        self.queries.fetch_add(1, Ordering::Relaxed);
+        let key = CacheKey::from_metadata(metadata);
+        if let Some((answer, age)) = self.cache.get(&key, Instant::now()) {
+            self.cache_hits.fetch_add(1, Ordering::Relaxed);
+            return Ok((answer, Some(age)));
+        }
+
+        let mut metadata = metadata.clone();
+        metadata.reuse_udp_sockets = true;
+        let answer = query_single_via(self, &metadata, bufsize)?;
+        self.cache.insert(key, answer.clone(), Instant::now());
+        Ok((answer, None))
+    }
+
+    /// Returns the cache of this session's answers.
+    pub fn cache(&self) -> &AnswerCache {
+        &self.cache
+    }
+
+    /// Returns the cache of the DNSKEYs used to validate this session's answers. It can be shared
+    /// with a [`Resolver`](crate::resolver::Resolver), see
+    /// [`Resolver::with_dnskey_cache()`](crate::resolver::Resolver::with_dnskey_cache()).
+    pub fn dnskey_cache(&self) -> &Arc<DnskeyCache> {
+        &self.dnskeys
+    }
+
+    /// Returns the number of queries made with [`Self::query()`] so far, and how many of them
+    /// were answered from the cache.
+    pub fn query_counts(&self) -> (usize, usize) {
+        (
+            self.queries.load(Ordering::Relaxed),
+            self.cache_hits.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Returns the socket addresses of the nameservers to which a TCP connection is open.
+    pub fn tcp_connections(&self) -> Vec<SocketAddr> {
+        self.tcp_connections
+            .lock()
+            .expect("TCP connection list lock is poisoned.")
+            .iter()
+            .map(|(addr, _)| *addr)
+            .collect()
+    }
+
+    /// Closes all open TCP connections.
+    pub fn close_connections(&self) {
+        self.tcp_connections
+            .lock()
+            .expect("TCP connection list lock is poisoned.")
+            .clear();
+    }
+
+    fn send_query_tcp(
+        &self,
+        nameserver: &mut Nameserver,
+        data: &[u8],
+        options: &TcpOptions,
+    ) -> Result<(Vec<u8>, u16, QueryTiming)> {
+        let resolved_addrs = nameserver.resolve()?;
+        let addr = resolved_addrs[0];
+        let open = {
+            let mut connections = self
+                .tcp_connections
+                .lock()
+                .expect("TCP connection list lock is poisoned.");
+            let i = connections.iter().position(|(a, _)| *a == addr);
+            i.map(|i| connections.remove(i).1)
+        };
+
+        let mut sent = SystemTime::now();
+        let mut before = Instant::now();
+        let mut handshake = None;
+        let mut reconnect = || -> Result<TcpStream> {
+            let before_connect = Instant::now();
+            let stream = connect(addr, options)?;
+            handshake = Some(before_connect.elapsed());
+            sent = SystemTime::now();
+            before = Instant::now();
+            Ok(stream)
+        };
+        let (stream, answer) = match open {
+            Some(mut stream) => match exchange(&mut stream, data) {
+                Ok(answer) => (stream, answer),
+                // the nameserver may have closed the connection since the last query
+                Err(_) => {
+                    let mut stream = reconnect()?;
+                    let answer = exchange(&mut stream, data)?;
+                    (stream, answer)
+                }
+            },
+            None => {
+                let mut stream = reconnect()?;
+                let answer = exchange(&mut stream, data)?;
+                (stream, answer)
+            }
+        };
+        let elapsed = before.elapsed();
+        let received = SystemTime::now();
+        nameserver.ip = Some(addr.ip());
+
+        self.tcp_connections
+            .lock()
+            .expect("TCP connection list lock is poisoned.")
+            .push((addr, stream));
+
+        let len = answer.len() as u16;
+        let timing = QueryTiming {
+            sent,
+            received,
+            elapsed,
+            handshake,
+            http_cache: None,
+            retransmissions: 0,
+            resolved_addrs,
+            tcp_fallback: None,
+            attempts: vec![QueryAttempt {
+                transport: ConnectionType::Tcp,
+                elapsed,
+                failure: None,
+            }],
+        };
+        Ok((answer, len, timing))
+    }
+}
+
+impl RawTransport for Session {
+    fn send_query(
+        &self,
+        metadata: &QueryMetadata,
+        bufsize: u16,
+        nameserver: &mut Nameserver,
+        data: &[u8],
+    ) -> Result<(Vec<u8>, u16, QueryTiming)> {
+        match metadata.connection_type {
+            ConnectionType::Tcp => self.send_query_tcp(nameserver, data, &metadata.tcp_options),
+            _ => StdTransport.send_query(metadata, bufsize, nameserver, data),
+        }
+    }
+
+    fn send_query_retrying(
+        &self,
+        metadata: &QueryMetadata,
+        bufsize: u16,
+        nameserver: &mut Nameserver,
+        make_query: &mut dyn FnMut(u32) -> Result<Vec<u8>>,
+    ) -> Result<(Vec<u8>, u16, QueryTiming)> {
+        match metadata.connection_type {
+            ConnectionType::Tcp => {
+                self.send_query_tcp(nameserver, &make_query(0)?, &metadata.tcp_options)
+            }
+            _ => StdTransport.send_query_retrying(metadata, bufsize, nameserver, make_query),
+        }
+    }
+}
+
+/// Opens a TCP connection to `addr` with `options` and the same timeouts as
+/// [`send_query_tcp()`](crate::net::send_query_tcp()).
+fn connect(addr: SocketAddr, options: &TcpOptions) -> Result<TcpStream> {
+    let stream = connect_tcp(&addr, Duration::from_secs(10), options)
+        .transport(format!("Could not connect to {} via TCP.", addr))?;
+    stream
+        .set_write_timeout(Some(Duration::from_secs(2)))
+        .transport("Could not set TCP stream write timeout.")?;
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .transport("Could not set TCP stream read timeout.")?;
+    Ok(stream)
+}
+
+/// Sends the query `data` over `stream` and reads the answer, skipping messages with another ID
+/// like [`send_query_tcp()`](crate::net::send_query_tcp()) does.
+fn exchange(stream: &mut TcpStream, data: &[u8]) -> Result<Vec<u8>> {
+    let mut msg = Vec::with_capacity(data.len() + 2);
+    msg.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    msg.extend_from_slice(data);
+    stream
+        .write_all(&msg)
+        .transport("Could not write data to TCP stream.")?;
+
+    loop {
+        let mut len = [0; 2];
+        stream
+            .read_exact(&mut len)
+            .transport("Could not read answer length from TCP stream.")?;
+        let mut answer = vec![0; u16::from_be_bytes(len).into()];
+        stream
+            .read_exact(&mut answer)
+            .transport("Could not read answer from TCP stream.")?;
+        if answer.get(..2) == data.get(..2) {
+            return Ok(answer);
+        }
+    }
+}
@@ -0,0 +1,279 @@
+//! Detecting split-horizon and geo-dependent answers: the same query is sent with and without
+//! EDNS Client Subnets ([RFC 7871](https://www.rfc-editor.org/rfc/rfc7871)), over different
+//! transports, and to both the recursive nameserver and the authoritative nameservers of the
+//! queried name's zone, and the answers are compared.
+
+use std::fmt::Display;
+use std::thread;
+use toluol_proto::rdata::opt::{ClientSubnet, OptionCode};
+use toluol_proto::{Message, Name, RCode, Rdata, RecordType};
+
+use crate::error::{Context, Result};
+use crate::util::{lookup, query_single};
+use crate::{ConnectionType, QueryMetadata};
+
+/// Networks of the five regional Internet registries, see [`default_subnets()`].
+const DEFAULT_SUBNETS: [&str; 5] = [
+    "193.0.6.0/24",   // RIPE NCC, Europe
+    "199.43.0.0/24",  // ARIN, North America
+    "202.12.29.0/24", // APNIC, Asia-Pacific
+    "200.3.14.0/24",  // LACNIC, Latin America and the Caribbean
+    "196.216.2.0/24", // AFRINIC, Africa
+];
+
+/// Returns the client subnets to probe with if none are given: networks of the five regional
+/// Internet registries, so that answers for different continents can be compared.
+pub fn default_subnets() -> Vec<ClientSubnet> {
+    DEFAULT_SUBNETS
+        .iter()
+        .map(|subnet| subnet.parse().expect("default subnets are valid"))
+        .collect()
+}
+
+/// The nameserver a probe query is sent to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Server {
+    /// The recursive nameserver given in the query metadata.
+    Recursive(String),
+    /// One of the authoritative nameservers of the queried name's zone.
+    Authoritative(Name),
+}
+
+/// One variant of the query sent by [`probe()`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Probe {
+    pub server: Server,
+    pub connection_type: ConnectionType,
+    /// The EDNS Client Subnet sent with the query, if any.
+    pub client_subnet: Option<ClientSubnet>,
+}
+
+impl Display for Probe {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.server {
+            Server::Recursive(nameserver) => write!(f, "recursive {}", nameserver)?,
+            Server::Authoritative(nameserver) => write!(f, "authoritative {}", nameserver)?,
+        }
+        write!(f, " over {}", self.connection_type)?;
+        match &self.client_subnet {
+            Some(subnet) => write!(
+                f,
+                " with ECS {}/{}",
+                subnet.address, subnet.source_prefix_len
+            ),
+            None => write!(f, " without ECS"),
+        }
+    }
+}
+
+/// The parts of an answer that are compared by [`probe()`]. TTLs are ignored, as they differ
+/// between caches anyway.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AnswerSummary {
+    pub rcode: RCode,
+    /// The RDATA of the records of the answer section (except `RRSIG`s), sorted by their
+    /// presentation format and without duplicates.
+    pub records: Vec<Rdata>,
+}
+
+impl AnswerSummary {
+    pub(crate) fn new(res: &Message) -> Self {
+        let rcode = match res.opt() {
+            Some(opt) => opt.rcode,
+            None => res.header.rcode,
+        };
+        let mut records: Vec<_> = res
+            .answers
+            .iter()
+            .filter_map(|record| record.as_nonopt())
+            .filter(|record| !matches!(record.rdata(), Rdata::RRSIG(_)))
+            .map(|record| record.rdata().clone())
+            .collect();
+        records.sort_by_cached_key(|rdata| format!("{} {}", rdata.rtype(), rdata));
+        records.dedup();
+        Self {
+            rcode: rcode.unwrap_or(RCode::NOERROR),
+            records,
+        }
+    }
+}
+
+/// The outcome of a single [`Probe`].
+#[derive(Debug)]
+pub struct ProbeResult {
+    pub probe: Probe,
+    /// The answer, or the reason why the query failed.
+    pub answer: Result<AnswerSummary>,
+    /// The EDNS Client Subnet option of the response, if any. Its scope prefix length tells for
+    /// which networks the answer is valid.
+    pub client_subnet: Option<ClientSubnet>,
+}
+
+/// The result of [`probe()`].
+#[derive(Debug)]
+pub struct SplitHorizonReport {
+    pub name: Name,
+    pub qtype: RecordType,
+    /// The zone whose authoritative nameservers were probed, if it could be determined.
+    pub zone: Option<Name>,
+    /// The results of all probes, for the recursive nameserver first.
+    pub results: Vec<ProbeResult>,
+}
+
+impl SplitHorizonReport {
+    /// Returns the distinct answers, each with the indices of the results in [`Self::results`]
+    /// that got it, in the order they first occurred. Failed probes are left out.
+    pub fn distinct_answers(&self) -> Vec<(&AnswerSummary, Vec<usize>)> {
+        let mut answers: Vec<(&AnswerSummary, Vec<usize>)> = Vec::new();
+        for (i, result) in self.results.iter().enumerate() {
+            let answer = match &result.answer {
+                Ok(answer) => answer,
+                Err(_) => continue,
+            };
+            match answers.iter_mut().find(|(other, _)| *other == answer) {
+                Some((_, indices)) => indices.push(i),
+                None => answers.push((answer, vec![i])),
+            }
+        }
+        answers
+    }
+
+    /// Returns whether the probes got different answers, i.e. whether the name seems to have
+    /// split-horizon or geo-dependent answers.
+    pub fn differs(&self) -> bool {
+        self.distinct_answers().len() > 1
+    }
+}
+
+/// Sends the query specified by `metadata` once for each combination of
+/// - nameserver: the recursive one in `metadata` and each authoritative one of the zone the name
+///   belongs to,
+/// - transport: UDP, TCP, and the one in `metadata` (for the recursive nameserver only),
+/// - client subnet: none and each of `subnets`,
+///
+/// concurrently, and collects the answers so they can be compared.
+///
+/// Returns an error if the zone's authoritative nameservers cannot be looked up. The results of
+/// individual probes may be errors without failing the whole probe.
+pub fn probe(
+    metadata: &QueryMetadata,
+    subnets: &[ClientSubnet],
+    bufsize: u16,
+) -> Result<SplitHorizonReport> {
+    let zone = find_zone(metadata, bufsize)?;
+    let authoritative = match &zone {
+        Some(zone) => lookup(zone, RecordType::NS, metadata, bufsize)?
+            .into_iter()
+            .filter_map(|rdata| match rdata {
+                Rdata::NS(ns) => Some(ns.name),
+                _ => None,
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+
+    let mut transports = vec![ConnectionType::Udp, ConnectionType::Tcp];
+    if !transports.contains(&metadata.connection_type) {
+        transports.push(metadata.connection_type);
+    }
+    let client_subnets: Vec<_> = std::iter::once(None)
+        .chain(subnets.iter().copied().map(Some))
+        .collect();
+
+    let mut probes = Vec::new();
+    let servers = std::iter::once(Server::Recursive(metadata.nameserver.clone()))
+        .chain(authoritative.into_iter().map(Server::Authoritative));
+    for server in servers {
+        for &connection_type in &transports {
+            if matches!(server, Server::Authoritative(_))
+                && ![ConnectionType::Udp, ConnectionType::Tcp].contains(&connection_type)
+            {
+                continue;
+            }
+            for &client_subnet in &client_subnets {
+                probes.push(Probe {
+                    server: server.clone(),
+                    connection_type,
+                    client_subnet,
+                });
+            }
+        }
+    }
+
+    let results = thread::scope(|s| {
+        let handles: Vec<_> = probes
+            .into_iter()
+            .map(|probe| s.spawn(move || run_probe(metadata, probe, bufsize)))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("Query thread panicked."))
+            .collect()
+    });
+
+    Ok(SplitHorizonReport {
+        name: metadata.name.clone(),
+        qtype: metadata.qtype,
+        zone,
+        results,
+    })
+}
+
+/// Sends the query specified by `metadata`, changed as described by `probe`.
+fn run_probe(metadata: &QueryMetadata, probe: Probe, bufsize: u16) -> ProbeResult {
+    let mut metadata = metadata.clone();
+    match &probe.server {
+        Server::Recursive(_) => {
+            // the port may be the one for DoT or DoH
+            let plain = [ConnectionType::Udp, ConnectionType::Tcp];
+            if probe.connection_type != metadata.connection_type
+                && !(plain.contains(&probe.connection_type)
+                    && plain.contains(&metadata.connection_type))
+            {
+                metadata.port = 53;
+            }
+        }
+        Server::Authoritative(nameserver) => {
+            metadata.nameserver = nameserver.to_string();
+            metadata.port = 53;
+        }
+    }
+    metadata.connection_type = probe.connection_type;
+    metadata.client_subnet = probe.client_subnet;
+
+    match query_single(&metadata, bufsize) {
+        Ok((_, res, _, _)) => {
+            let client_subnet = res
+                .opt()
+                .and_then(|opt| opt.opt_rdata().get(OptionCode::ClientSubnet))
+                .and_then(|value| ClientSubnet::parse(value).ok());
+            ProbeResult {
+                probe,
+                answer: Ok(AnswerSummary::new(&res)),
+                client_subnet,
+            }
+        }
+        Err(e) => ProbeResult {
+            probe,
+            answer: Err(e),
+            client_subnet: None,
+        },
+    }
+}
+
+/// Returns the zone the name in `metadata` belongs to, i.e. the owner of the `SOA` record in the
+/// answer to a `SOA` query for the name, or [`None`] if there is none.
+fn find_zone(metadata: &QueryMetadata, bufsize: u16) -> Result<Option<Name>> {
+    let mut metadata = metadata.clone();
+    metadata.qtype = RecordType::SOA;
+    metadata.client_subnet = None;
+    let (_, res, _, _) = query_single(&metadata, bufsize)
+        .transport(format!("Could not look up the zone of {}.", metadata.name))?;
+    Ok(res
+        .answers
+        .iter()
+        .chain(res.authoritative_answers.iter())
+        .filter_map(|record| record.as_nonopt())
+        .find(|record| record.rtype == RecordType::SOA)
+        .map(|record| record.owner.clone()))
+}
@@ -0,0 +1,282 @@
+//! Statistics about how nameservers perform (smoothed RTT, timeouts, EDNS support), which are used
+//! to prefer fast and reliable nameservers and may be persisted between runs.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+use toluol_proto::Message;
+
+use crate::error::{bail, Context, Result};
+
+/// The RTT assumed for nameservers without statistics, so that they are preferred over
+/// nameservers that were slow or did not answer, but not over fast ones.
+pub const UNKNOWN_RTT: Duration = Duration::from_millis(100);
+
+/// The weight of a new RTT sample in the smoothed RTT, as for TCP (see
+/// [RFC 6298](https://www.rfc-editor.org/rfc/rfc6298)).
+const RTT_SAMPLE_WEIGHT: f64 = 1.0 / 8.0;
+
+/// The first line of files written by [`ServerStats::save()`].
+const FILE_HEADER: &str = "# toluol nameserver statistics: address srtt_us queries timeouts edns";
+
+/// The statistics of a single nameserver, see [`ServerStats`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ServerStat {
+    /// The smoothed RTT. Queries without answer count with the timeout.
+    pub srtt: Duration,
+    /// The number of queries sent to the nameserver.
+    pub queries: u64,
+    /// The number of queries that got no answer, e.g. because of a timeout.
+    pub timeouts: u64,
+    /// The UDP payload size the nameserver advertised in its last answer with EDNS, `Some(0)`
+    /// if its last answer had no OPT record, or [`None`] if this is unknown.
+    pub edns_payload_size: Option<u16>,
+}
+
+impl ServerStat {
+    /// Returns the fraction of queries that got no answer.
+    pub fn timeout_rate(&self) -> f64 {
+        if self.queries == 0 {
+            return 0.0;
+        }
+        self.timeouts as f64 / self.queries as f64
+    }
+
+    /// Returns whether the nameserver supports EDNS, or [`None`] if this is unknown.
+    pub fn supports_edns(&self) -> Option<bool> {
+        self.edns_payload_size.map(|size| size > 0)
+    }
+
+    /// Adds an RTT sample to the smoothed RTT.
+    fn add_rtt(&mut self, rtt: Duration) {
+        self.srtt = if self.queries == 0 {
+            rtt
+        } else {
+            self.srtt
+                .mul_f64(1.0 - RTT_SAMPLE_WEIGHT)
+                .saturating_add(rtt.mul_f64(RTT_SAMPLE_WEIGHT))
+        };
+        self.queries += 1;
+    }
+}
+
+/// Statistics about nameservers, keyed on their address.
+///
+/// `ServerStats` can be shared between threads. It is used by
+/// [`Resolver::with_server_stats()`](crate::resolver::Resolver::with_server_stats()) and
+/// [`iter::query_with_stats()`](crate::iter::query_with_stats()) to prefer nameservers with a low
+/// smoothed RTT, and may be saved to and loaded from a file to keep the statistics between runs.
+///
+/// # Examples
+/// ```rust
+/// use std::time::Duration;
+/// use toluol::stats::{ServerStats, UNKNOWN_RTT};
+///
+/// let stats = ServerStats::new();
+/// let fast = "192.0.2.1".parse().unwrap();
+/// let slow = "192.0.2.2".parse().unwrap();
+/// stats.record_rtt(fast, Duration::from_millis(10));
+/// stats.record_timeout(slow, Duration::from_secs(2));
+///
+/// assert_eq!(stats.get(fast).unwrap().srtt, Duration::from_millis(10));
+/// assert_eq!(stats.get(slow).unwrap().timeout_rate(), 1.0);
+/// assert_eq!(stats.srtt("192.0.2.3".parse().unwrap()), UNKNOWN_RTT);
+///
+/// let restored = ServerStats::parse(&stats.to_file_contents()).unwrap();
+/// assert_eq!(restored.get(slow), stats.get(slow));
+/// ```
+#[derive(Debug, Default)]
+pub struct ServerStats {
+    servers: Mutex<HashMap<IpAddr, ServerStat>>,
+}
+
+impl ServerStats {
+    /// Creates empty statistics.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads statistics saved by [`Self::save()`] from the file at `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)
+            .configuration(format!("Could not read {}.", path.display()))?;
+        Self::parse(&contents).configuration(format!(
+            "Invalid nameserver statistics in {}.",
+            path.display()
+        ))
+    }
+
+    /// Reads statistics saved by [`Self::save()`] from the file at `path`, or returns empty
+    /// statistics if it does not exist.
+    pub fn load_or_default(path: impl AsRef<Path>) -> Result<Self> {
+        if !path.as_ref().exists() {
+            return Ok(Self::new());
+        }
+        Self::load(path)
+    }
+
+    /// Parses statistics in the format written by [`Self::save()`]: one line per nameserver with
+    /// its address, smoothed RTT in microseconds, number of queries and timeouts, and EDNS
+    /// payload size (`-` if unknown). Empty lines and lines starting with `#` are ignored.
+    pub fn parse(contents: &str) -> Result<Self> {
+        let mut servers = HashMap::new();
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<_> = line.split_whitespace().collect();
+            if fields.len() != 5 {
+                bail!(configuration, "Expected 5 fields, got: {}", line);
+            }
+            let address = fields[0]
+                .parse()
+                .configuration(format!("Invalid address: {}", fields[0]))?;
+            let number = |field: &str| {
+                field
+                    .parse::<u64>()
+                    .configuration(format!("Invalid number: {}", field))
+            };
+            let edns_payload_size = match fields[4] {
+                "-" => None,
+                size => Some(
+                    size.parse()
+                        .configuration(format!("Invalid EDNS payload size: {}", size))?,
+                ),
+            };
+            let stat = ServerStat {
+                srtt: Duration::from_micros(number(fields[1])?),
+                queries: number(fields[2])?,
+                timeouts: number(fields[3])?,
+                edns_payload_size,
+            };
+            servers.insert(address, stat);
+        }
+        Ok(Self {
+            servers: Mutex::new(servers),
+        })
+    }
+
+    /// Returns the statistics in the format read by [`Self::parse()`].
+    pub fn to_file_contents(&self) -> String {
+        let mut contents = format!("{}\n", FILE_HEADER);
+        for (address, stat) in self.all() {
+            let edns = match stat.edns_payload_size {
+                Some(size) => size.to_string(),
+                None => "-".into(),
+            };
+            writeln!(
+                contents,
+                "{} {} {} {} {}",
+                address,
+                stat.srtt.as_micros(),
+                stat.queries,
+                stat.timeouts,
+                edns
+            )
+            .expect("Writing to a String failed.");
+        }
+        contents
+    }
+
+    /// Writes the statistics to the file at `path`, see [`Self::parse()`] for the format.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        fs::write(path, self.to_file_contents())
+            .configuration(format!("Could not write {}.", path.display()))
+    }
+
+    /// Returns the statistics of the nameserver at `address`, if there are any.
+    pub fn get(&self, address: IpAddr) -> Option<ServerStat> {
+        self.lock().get(&address).copied()
+    }
+
+    /// Returns the statistics of all nameservers, sorted by address.
+    pub fn all(&self) -> Vec<(IpAddr, ServerStat)> {
+        let mut all: Vec<_> = self
+            .lock()
+            .iter()
+            .map(|(address, stat)| (*address, *stat))
+            .collect();
+        all.sort_by_key(|(address, _)| *address);
+        all
+    }
+
+    /// Returns the smoothed RTT of the nameserver at `address`, or [`UNKNOWN_RTT`] if it was not
+    /// queried yet.
+    pub fn srtt(&self, address: IpAddr) -> Duration {
+        self.get(address).map_or(UNKNOWN_RTT, |stat| stat.srtt)
+    }
+
+    /// Returns the size of the largest query the nameserver at `address` accepts via UDP, or
+    /// [`None`] if it is unknown: the UDP payload size the nameserver advertised in its last answer
+    /// (at least 512 bytes), or 512 bytes if it does not support EDNS.
+    ///
+    /// This only limits the queries sent to the nameserver. The payload size advertised in the
+    /// queries limits the answers, so it is not reduced to this, as that would only cause more
+    /// truncated answers.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use std::io::Cursor;
+    /// use std::time::Duration;
+    /// use toluol::stats::ServerStats;
+    /// use toluol_proto::{EdnsConfig, HeaderFlags, Message, Name, Opcode, RecordType};
+    ///
+    /// let stats = ServerStats::new();
+    /// let server = "192.0.2.1".parse().unwrap();
+    /// assert_eq!(stats.max_query_size(server), None);
+    ///
+    /// let edns = EdnsConfig {
+    ///     bufsize: 1232,
+    ///     do_flag: false,
+    ///     client_cookie: None,
+    ///     client_subnet: None,
+    ///     edns_version: 0,
+    ///     extra_options: Vec::new(),
+    /// };
+    /// let name = Name::from_ascii("example.com").unwrap();
+    /// let flags = HeaderFlags { aa: false, tc: false, rd: true, ra: false, ad: false, cd: false };
+    /// let msg = Message::new_query(name, RecordType::A, Opcode::QUERY, flags, Some(edns)).unwrap();
+    /// stats.record_answer(server, Duration::from_millis(10), &msg);
+    /// assert_eq!(stats.max_query_size(server), Some(1232));
+    /// ```
+    pub fn max_query_size(&self, address: IpAddr) -> Option<u16> {
+        self.get(address)
+            .and_then(|stat| stat.edns_payload_size)
+            .map(|size| size.max(512))
+    }
+
+    /// Records that the nameserver at `address` answered after `rtt`.
+    pub fn record_rtt(&self, address: IpAddr, rtt: Duration) {
+        self.lock().entry(address).or_default().add_rtt(rtt);
+    }
+
+    /// Records that the nameserver at `address` sent the answer `res` after `rtt`, including
+    /// whether it supports EDNS.
+    pub fn record_answer(&self, address: IpAddr, rtt: Duration, res: &Message) {
+        let mut servers = self.lock();
+        let stat = servers.entry(address).or_default();
+        stat.add_rtt(rtt);
+        stat.edns_payload_size = Some(res.opt().map_or(0, |opt| opt.payload_size));
+    }
+
+    /// Records that the nameserver at `address` did not answer within `timeout`.
+    pub fn record_timeout(&self, address: IpAddr, timeout: Duration) {
+        let mut servers = self.lock();
+        let stat = servers.entry(address).or_default();
+        stat.add_rtt(timeout);
+        stat.timeouts += 1;
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<IpAddr, ServerStat>> {
+        self.servers
+            .lock()
+            .expect("Server statistics mutex poisoned.")
+    }
+}
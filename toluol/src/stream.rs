@@ -0,0 +1,301 @@
+//! Responses that span several messages, e.g. zone transfers over TCP or multicast DNS answers from
+//! several responders, see [`ResponseStream`].
+
+use crate::error::{bail, Context, Result};
+use crate::net::{create_udp_socket_for, Nameserver};
+use byteorder::{NetworkEndian, WriteBytesExt};
+use std::fmt::Debug;
+use std::io::{self, Cursor, Read, Write};
+use std::net::{TcpStream, UdpSocket};
+use std::time::{Duration, Instant};
+use toluol_proto::{Message, Record, RecordType};
+
+/// When a [`ResponseStream`] ends.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StreamEnd {
+    /// After the message that completes a zone transfer, i.e. whose last answer is the `SOA`
+    /// record the first message started with (see
+    /// [RFC 5936, Section 2.2](https://www.rfc-editor.org/rfc/rfc5936#section-2.2)). A response
+    /// without answers, e.g. because the transfer was refused, ends the stream as well.
+    FinalSoa,
+    /// Once no message arrived for the given duration, e.g. for multicast DNS, where any number of
+    /// responders may answer.
+    Quiet(Duration),
+    /// When the server closes the connection or stream.
+    Closed,
+}
+
+/// The outcome of [`MessageSource::recv_message()`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Received {
+    /// An encoded message.
+    Message(Vec<u8>),
+    /// No message arrived in time.
+    TimedOut,
+    /// The server closed the connection or stream.
+    Closed,
+}
+
+/// Receives the encoded messages of a [`ResponseStream`], e.g. from a TCP connection.
+///
+/// Implementing this trait allows transports that toluol does not support itself (e.g. DNS over
+/// QUIC) to return a [`ResponseStream`] from
+/// [`RawTransport::send_query_stream()`](crate::net::RawTransport::send_query_stream()).
+pub trait MessageSource: Debug + Send {
+    /// Waits up to `timeout` for the next message.
+    fn recv_message(&mut self, timeout: Duration) -> Result<Received>;
+}
+
+/// The messages of a response that spans several messages, e.g. a zone transfer.
+///
+/// A `ResponseStream` is an [`Iterator`] that yields the messages as they arrive until the
+/// condition given by [`StreamEnd`] is met. After an error, it yields nothing more.
+///
+/// # Examples
+/// ```rust
+/// use std::time::Duration;
+/// use toluol::error::Result;
+/// use toluol::stream::{MessageSource, Received, ResponseStream, StreamEnd};
+/// use toluol_proto::{HeaderFlags, Message, Name, Opcode, RecordType};
+///
+/// // a source that has a single message and is closed afterwards
+/// #[derive(Debug)]
+/// struct Single(Option<Vec<u8>>);
+///
+/// impl MessageSource for Single {
+///     fn recv_message(&mut self, _timeout: Duration) -> Result<Received> {
+///         Ok(self.0.take().map_or(Received::Closed, Received::Message))
+///     }
+/// }
+///
+/// let flags = HeaderFlags { aa: false, tc: false, rd: true, ra: false, ad: false, cd: false };
+/// let name = Name::from_ascii("example.com").unwrap();
+/// let msg = Message::new_query(name, RecordType::A, Opcode::QUERY, flags, None).unwrap();
+/// let source = Single(Some(msg.encode().unwrap()));
+///
+/// let stream = ResponseStream::new(Box::new(source), StreamEnd::Closed, Duration::from_secs(1));
+/// let messages: Vec<_> = stream.collect::<Result<_>>().unwrap();
+/// assert_eq!(messages, [msg]);
+/// ```
+#[derive(Debug)]
+pub struct ResponseStream {
+    source: Box<dyn MessageSource>,
+    end: StreamEnd,
+    /// How long to wait for each message, unless `end` is [`StreamEnd::Quiet`].
+    timeout: Duration,
+    /// The `SOA` record the zone transfer started with, for [`StreamEnd::FinalSoa`].
+    first_soa: Option<Record>,
+    /// The number of answers received so far.
+    answers: usize,
+    done: bool,
+}
+
+impl ResponseStream {
+    /// Creates a stream of the messages received from `source`, waiting up to `timeout` for each
+    /// of them (except with [`StreamEnd::Quiet`]).
+    pub fn new(source: Box<dyn MessageSource>, end: StreamEnd, timeout: Duration) -> Self {
+        Self {
+            source,
+            end,
+            timeout,
+            first_soa: None,
+            answers: 0,
+            done: false,
+        }
+    }
+
+    /// Sends the encoded query `data` to `nameserver` via TCP and returns the stream of the
+    /// answers, e.g. for zone transfers.
+    pub fn tcp(
+        nameserver: &mut Nameserver,
+        data: &[u8],
+        end: StreamEnd,
+        timeout: Duration,
+    ) -> Result<Self> {
+        let resolved_addrs = nameserver.resolve()?;
+        let mut socket =
+            TcpStream::connect_timeout(&resolved_addrs[0], timeout).transport(format!(
+                "Could not connect to {} via TCP, is the server running?",
+                nameserver
+            ))?;
+        let peer_addr = socket
+            .peer_addr()
+            .transport("Could not get peer address of TCP socket.")?;
+        nameserver.ip = Some(peer_addr.ip());
+
+        socket
+            .set_write_timeout(Some(timeout))
+            .transport("Could not set TCP stream write timeout.")?;
+        let mut msg = Vec::with_capacity(data.len() + 2);
+        msg.write_u16::<NetworkEndian>(data.len() as u16)
+            .expect("Writing to a Vec cannot fail.");
+        msg.extend_from_slice(data);
+        socket
+            .write_all(&msg)
+            .transport("Could not write data to TCP stream.")?;
+
+        Ok(Self::new(Box::new(TcpSource(socket)), end, timeout))
+    }
+
+    /// Sends the encoded query `data` to `nameserver` via UDP and returns the stream of the
+    /// answers with the query's message ID, from any source address, e.g. for multicast DNS.
+    /// Answers must not be longer than `bufsize` bytes.
+    ///
+    /// The query is not sent again if no answer arrives.
+    pub fn udp(
+        nameserver: &mut Nameserver,
+        bufsize: u16,
+        data: &[u8],
+        end: StreamEnd,
+        timeout: Duration,
+    ) -> Result<Self> {
+        let id = match data {
+            [high, low, ..] => [*high, *low],
+            _ => bail!(parse, "The query is too short."),
+        };
+        let target = nameserver.resolve()?[0];
+        let socket = create_udp_socket_for(nameserver, target)?;
+        socket
+            .send_to(data, target)
+            .transport("Could not send data to nameserver.")?;
+        let source = UdpSource {
+            socket,
+            bufsize,
+            id,
+        };
+        Ok(Self::new(Box::new(source), end, timeout))
+    }
+
+    /// Returns whether `msg` ends the stream, according to [`StreamEnd::FinalSoa`].
+    fn is_final(&mut self, msg: &Message) -> Result<bool> {
+        if msg.answers.is_empty() {
+            return Ok(self.first_soa.is_none());
+        }
+        let is_soa = |record: &Record| {
+            record
+                .as_nonopt()
+                .is_some_and(|record| record.rtype == RecordType::SOA)
+        };
+        let first_soa = match &self.first_soa {
+            Some(soa) => soa,
+            None if is_soa(&msg.answers[0]) => &*self.first_soa.insert(msg.answers[0].clone()),
+            None => bail!(
+                parse,
+                "The first answer of the zone transfer is no SOA record."
+            ),
+        };
+        let last = msg.answers.last().expect("answers are not empty");
+        self.answers += msg.answers.len();
+        Ok(self.answers > 1
+            && match (first_soa.as_nonopt(), last.as_nonopt()) {
+                (Some(first), Some(last)) => first.same_rrset_member(last),
+                _ => false,
+            })
+    }
+
+    fn next_message(&mut self) -> Result<Option<Message>> {
+        let timeout = match self.end {
+            StreamEnd::Quiet(quiet) => quiet,
+            _ => self.timeout,
+        };
+        let data = match self.source.recv_message(timeout)? {
+            Received::Message(data) => data,
+            Received::TimedOut if matches!(self.end, StreamEnd::Quiet(_)) => return Ok(None),
+            Received::TimedOut => bail!(transport, "The nameserver did not reply in time."),
+            Received::Closed if self.end == StreamEnd::FinalSoa => {
+                bail!(
+                    transport,
+                    "The nameserver closed the connection before the zone transfer was complete."
+                )
+            }
+            Received::Closed => return Ok(None),
+        };
+        let msg = Message::parse(&mut Cursor::new(&data)).parse("Could not parse message.")?;
+        if self.end == StreamEnd::FinalSoa && self.is_final(&msg)? {
+            self.done = true;
+        }
+        Ok(Some(msg))
+    }
+}
+
+impl Iterator for ResponseStream {
+    type Item = Result<Message>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let res = self.next_message();
+        if !matches!(res, Ok(Some(_))) {
+            self.done = true;
+        }
+        res.transpose()
+    }
+}
+
+/// Receives messages from a TCP connection, each of which is preceded by its length.
+#[derive(Debug)]
+struct TcpSource(TcpStream);
+
+impl MessageSource for TcpSource {
+    fn recv_message(&mut self, timeout: Duration) -> Result<Received> {
+        self.0
+            .set_read_timeout(Some(timeout))
+            .transport("Could not set TCP stream read timeout.")?;
+        let mut len = [0; 2];
+        match self.0.read_exact(&mut len) {
+            Ok(()) => (),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(Received::Closed),
+            Err(e) if is_timeout(&e) => return Ok(Received::TimedOut),
+            Err(e) => return Err(e).transport("Could not read from TCP stream."),
+        }
+        let mut msg = vec![0; u16::from_be_bytes(len) as usize];
+        match self.0.read_exact(&mut msg) {
+            Ok(()) => Ok(Received::Message(msg)),
+            Err(e) if is_timeout(&e) => Ok(Received::TimedOut),
+            Err(e) => Err(e).transport("Could not read from TCP stream."),
+        }
+    }
+}
+
+/// Receives datagrams with a given message ID from a UDP socket.
+#[derive(Debug)]
+struct UdpSource {
+    socket: UdpSocket,
+    bufsize: u16,
+    id: [u8; 2],
+}
+
+impl MessageSource for UdpSource {
+    fn recv_message(&mut self, timeout: Duration) -> Result<Received> {
+        let deadline = Instant::now() + timeout;
+        let mut buf = vec![0; self.bufsize as usize];
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Ok(Received::TimedOut);
+            }
+            self.socket
+                .set_read_timeout(Some(remaining))
+                .transport("Could not set UDP socket read timeout.")?;
+            match self.socket.recv_from(&mut buf) {
+                Ok((len, _)) if buf[..len].starts_with(&self.id) => {
+                    buf.truncate(len);
+                    return Ok(Received::Message(buf));
+                }
+                Ok(_) => (),
+                Err(e) if is_timeout(&e) => return Ok(Received::TimedOut),
+                Err(e) => return Err(e).transport("Could not receive data from nameserver."),
+            }
+        }
+    }
+}
+
+/// Returns whether `e` was caused by a read timeout, which is reported differently depending on the
+/// OS.
+fn is_timeout(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
@@ -0,0 +1,186 @@
+//! Surveying which record types exist for a name (`survey`), by querying for each type. For zones
+//! without DNSSEC, this reconstructs what the type bitmap of an `NSEC` record for the name would
+//! say.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use toluol_proto::{Message, Name, RCode, RecordType};
+
+use crate::error::{bail, Result};
+use crate::mux::Multiplexer;
+use crate::net::Nameserver;
+use crate::util::query_single;
+use crate::{ConnectionType, QueryMetadata};
+
+/// The number of queries that [`survey()`] has in flight at most.
+const MAX_CONCURRENT_QUERIES: usize = 16;
+
+/// The record types surveyed by default: the ones toluol knows, and a few other common ones (SPF,
+/// URI).
+pub const DEFAULT_TYPES: [RecordType; 31] = [
+    RecordType::A,
+    RecordType::NS,
+    RecordType::CNAME,
+    RecordType::SOA,
+    RecordType::PTR,
+    RecordType::HINFO,
+    RecordType::MX,
+    RecordType::TXT,
+    RecordType::RP,
+    RecordType::AAAA,
+    RecordType::LOC,
+    RecordType::SRV,
+    RecordType::NAPTR,
+    RecordType::CERT,
+    RecordType::DNAME,
+    RecordType::DS,
+    RecordType::SSHFP,
+    RecordType::RRSIG,
+    RecordType::NSEC,
+    RecordType::DNSKEY,
+    RecordType::NSEC3,
+    RecordType::NSEC3PARAM,
+    RecordType::TLSA,
+    RecordType::CDS,
+    RecordType::CDNSKEY,
+    RecordType::OPENPGPKEY,
+    RecordType::CAA,
+    RecordType::SVCB,
+    RecordType::HTTPS,
+    RecordType::Unknown(99),
+    RecordType::Unknown(256),
+];
+
+/// Returns all record types that IANA has assigned for data, i.e. without meta types like `OPT`
+/// and query types like `ANY`.
+pub fn assigned_types() -> Vec<RecordType> {
+    (1..=40)
+        .chain(42..=53)
+        .chain(55..=66)
+        .chain(99..=109)
+        .chain(256..=264)
+        .chain([32768, 32769])
+        .map(RecordType::from)
+        .collect()
+}
+
+/// The outcome of [`survey()`].
+#[derive(Debug)]
+pub struct Survey {
+    /// The surveyed name.
+    pub name: Name,
+    /// The number of types that were queried for.
+    pub queried: usize,
+    /// The types that exist for the name, with their number of records, in the order they were
+    /// queried for.
+    pub present: Vec<(RecordType, usize)>,
+    /// The types for which the query failed, with the reason.
+    pub failed: Vec<(RecordType, String)>,
+    /// Whether the nameserver said that the name does not exist at all.
+    pub nxdomain: bool,
+}
+
+/// Queries the nameserver in `metadata` for each of `types` at `metadata.name`, with up to
+/// [`MAX_CONCURRENT_QUERIES`] queries in flight over a single socket or connection (see
+/// [`Multiplexer`]), and reports which types exist.
+///
+/// Queries that time out are sent once more. Truncated answers are fetched again via TCP.
+///
+/// Returns an error if `metadata.connection_type` is neither UDP nor TCP, or if connecting to the
+/// nameserver fails.
+pub fn survey(metadata: &QueryMetadata, types: &[RecordType], bufsize: u16) -> Result<Survey> {
+    let nameserver = Nameserver::from_metadata(metadata)?;
+    let mux = match metadata.connection_type {
+        ConnectionType::Udp => Multiplexer::udp(&nameserver)?,
+        ConnectionType::Tcp => Multiplexer::tcp(&nameserver)?,
+        #[allow(unreachable_patterns)]
+        connection_type => bail!(
+            configuration,
+            "Surveys are not supported via {}.",
+            connection_type
+        ),
+    };
+
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::with_capacity(types.len()));
+    thread::scope(|s| {
+        for _ in 0..MAX_CONCURRENT_QUERIES.min(types.len()) {
+            s.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let qtype = match types.get(i) {
+                    Some(&qtype) => qtype,
+                    None => break,
+                };
+                let answer = query_type(&mux, metadata, qtype, bufsize);
+                results
+                    .lock()
+                    .expect("Result mutex poisoned.")
+                    .push((i, qtype, answer));
+            });
+        }
+    });
+    let mut results = results.into_inner().expect("Result mutex poisoned.");
+    results.sort_by_key(|(i, _, _)| *i);
+
+    let mut survey = Survey {
+        name: metadata.name.clone(),
+        queried: types.len(),
+        present: Vec::new(),
+        failed: Vec::new(),
+        nxdomain: false,
+    };
+    for (_, qtype, answer) in results {
+        let answer = match answer {
+            Ok(answer) => answer,
+            Err(e) => {
+                survey.failed.push((qtype, format!("{:#}", e)));
+                continue;
+            }
+        };
+        let rcode = match answer.opt() {
+            Some(opt) => opt.rcode,
+            None => answer.header.rcode,
+        };
+        match rcode.unwrap_or(RCode::NOERROR) {
+            RCode::NOERROR => {}
+            RCode::NXDOMAIN => {
+                survey.nxdomain = true;
+                continue;
+            }
+            rcode => {
+                survey.failed.push((qtype, rcode.to_string()));
+                continue;
+            }
+        }
+        // records of other types, e.g. a CNAME for the name, are reported when their type is
+        // queried for
+        let count = answer
+            .nonopt_records()
+            .filter(|record| record.rtype == qtype && record.owner == survey.name)
+            .count();
+        if count > 0 {
+            survey.present.push((qtype, count));
+        }
+    }
+    Ok(survey)
+}
+
+/// Queries for `qtype` via `mux`, retrying once on failure and via TCP if the answer is truncated.
+fn query_type(
+    mux: &Multiplexer,
+    metadata: &QueryMetadata,
+    qtype: RecordType,
+    bufsize: u16,
+) -> Result<Message> {
+    let mut metadata = metadata.clone();
+    metadata.qtype = qtype;
+    let answer = mux
+        .query(&metadata, bufsize, metadata.timeout)
+        .or_else(|_| mux.query(&metadata, bufsize, metadata.timeout))?;
+    if !answer.header.flags.tc {
+        return Ok(answer);
+    }
+    metadata.connection_type = ConnectionType::Tcp;
+    Ok(query_single(&metadata, bufsize)?.1)
+}
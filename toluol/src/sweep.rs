@@ -0,0 +1,343 @@
+//! Sweeping networks for `PTR` records, i.e. looking up the host names of all (or a sample of the)
+//! addresses in a network, with a limited rate of queries, and looking up the host names of many
+//! addresses at once with a [`Resolver`], see [`reverse_lookup()`].
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::iter::zip;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use toluol_proto::{Name, Rdata, RecordType};
+
+use crate::error::{bail, Context, Error, Result};
+use crate::ratelimit::{Rate, RateLimit, RateLimiter};
+use crate::resolver::Resolver;
+use crate::util::lookup;
+use crate::QueryMetadata;
+
+/// The number of queries that [`sweep()`] and [`reverse_lookup()`] have in flight at most.
+const MAX_CONCURRENT_QUERIES: usize = 16;
+
+/// An IPv4 or IPv6 network, e.g. `192.0.2.0/28` or `2001:db8::/64`.
+///
+/// # Examples
+/// ```rust
+/// use toluol::sweep::Network;
+///
+/// let network: Network = "192.0.2.13/30".parse().unwrap();
+/// assert_eq!(network.to_string(), "192.0.2.12/30");
+/// assert_eq!(network.size(), 4);
+/// let addresses: Vec<_> = network.addresses().map(|a| a.to_string()).collect();
+/// assert_eq!(addresses, ["192.0.2.12", "192.0.2.13", "192.0.2.14", "192.0.2.15"]);
+///
+/// // a single address is a network as well
+/// let network: Network = "2001:db8::1".parse().unwrap();
+/// assert_eq!(network.to_string(), "2001:db8::1/128");
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Network {
+    address: IpAddr,
+    prefix_len: u8,
+}
+
+impl Network {
+    /// Creates the network with the first `prefix_len` bits of `address`.
+    ///
+    /// Returns an error if `prefix_len` is greater than 32 (IPv4) or 128 (IPv6).
+    pub fn new(address: IpAddr, prefix_len: u8) -> Result<Self> {
+        let max_len = match address {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        if prefix_len > max_len {
+            bail!(
+                configuration,
+                "Invalid prefix length: must be at most {}, is {}.",
+                max_len,
+                prefix_len
+            );
+        }
+        let address = match address {
+            IpAddr::V4(address) => {
+                let mask = u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0);
+                IpAddr::V4((u32::from(address) & mask).into())
+            }
+            IpAddr::V6(address) => {
+                let mask = u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0);
+                IpAddr::V6((u128::from(address) & mask).into())
+            }
+        };
+        Ok(Self {
+            address,
+            prefix_len,
+        })
+    }
+
+    /// Returns the first address of the network.
+    pub fn address(&self) -> IpAddr {
+        self.address
+    }
+
+    /// Returns the length of the network's prefix in bits.
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    /// Returns the number of addresses in the network, or [`u128::MAX`] for `::/0`.
+    pub fn size(&self) -> u128 {
+        let bits = match self.address {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        } - self.prefix_len as u32;
+        1u128.checked_shl(bits).unwrap_or(u128::MAX)
+    }
+
+    /// Returns the reverse zone of the network, see [`Name::reverse_zone()`].
+    pub fn reverse_zone(&self) -> Name {
+        Name::reverse_zone(self.address, self.prefix_len).expect("prefix length is valid")
+    }
+
+    /// Returns an iterator over the addresses of the network, in ascending order.
+    pub fn addresses(&self) -> impl Iterator<Item = IpAddr> {
+        let address = self.address;
+        (0..self.size()).map(move |offset| match address {
+            IpAddr::V4(address) => IpAddr::V4(Ipv4Addr::from(u32::from(address) + offset as u32)),
+            IpAddr::V6(address) => IpAddr::V6(Ipv6Addr::from(u128::from(address) + offset)),
+        })
+    }
+}
+
+impl FromStr for Network {
+    type Err = Error;
+
+    /// Parses a network like `192.0.2.0/24`, or a single address like `192.0.2.1`.
+    fn from_str(s: &str) -> Result<Self> {
+        let (address, prefix_len) = match s.split_once('/') {
+            Some((address, prefix_len)) => (address, Some(prefix_len)),
+            None => (s, None),
+        };
+        let address: IpAddr = address
+            .parse()
+            .configuration(format!("Invalid network: {} is no IP address.", address))?;
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len
+                .parse()
+                .configuration(format!("Invalid prefix length: {}.", prefix_len))?,
+            None if address.is_ipv4() => 32,
+            None => 128,
+        };
+        Self::new(address, prefix_len)
+    }
+}
+
+impl Display for Network {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix_len)
+    }
+}
+
+/// Returns the addresses to sweep in `networks`: all of them for networks with at most `sample`
+/// addresses, and the lowest `sample` ones for larger networks, e.g. IPv6 /64s, as hosts are
+/// usually numbered from the start of a network. Duplicates are removed.
+///
+/// Also returns the networks that were sampled.
+pub fn select_addresses(networks: &[Network], sample: usize) -> (Vec<IpAddr>, Vec<Network>) {
+    let mut addresses = Vec::new();
+    let mut seen = HashSet::new();
+    let mut sampled = Vec::new();
+    for network in networks {
+        if network.size() > sample as u128 {
+            sampled.push(*network);
+        }
+        for address in network.addresses().take(sample) {
+            if seen.insert(address) {
+                addresses.push(address);
+            }
+        }
+    }
+    (addresses, sampled)
+}
+
+/// The outcome of looking up the `PTR` records of one address.
+#[derive(Debug)]
+pub struct PtrResult {
+    pub address: IpAddr,
+    /// The host names the address maps to (empty if there are none), or the reason why the lookup
+    /// failed.
+    pub names: Result<Vec<Name>>,
+}
+
+/// Looks up the `PTR` records of each of `addresses` via the nameserver in `metadata`, with up to
+/// [`MAX_CONCURRENT_QUERIES`] queries in flight and no more than `rate` queries per second.
+///
+/// Returns the results in the order of `addresses`.
+pub fn sweep(
+    addresses: &[IpAddr],
+    metadata: &QueryMetadata,
+    rate: Rate,
+    bufsize: u16,
+) -> Vec<PtrResult> {
+    let limiter = RateLimiter::new(RateLimit {
+        global: Some(rate),
+        ..Default::default()
+    });
+    concurrently(addresses, |&address| {
+        let names = limiter
+            .acquire(&metadata.nameserver)
+            .and_then(|()| lookup_ptr(address, metadata, bufsize));
+        PtrResult { address, names }
+    })
+}
+
+/// A host name of an address, see [`ReverseLookup`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HostName {
+    pub name: Name,
+    /// Whether the addresses of the name include the address it was found for (i.e. whether the
+    /// reverse DNS is forward-confirmed), or [`None`] if this was not checked or the lookup of the
+    /// name's addresses failed.
+    pub confirmed: Option<bool>,
+}
+
+/// The host names of one address, see [`reverse_lookup()`].
+#[derive(Debug)]
+pub struct ReverseLookup {
+    pub address: IpAddr,
+    /// The host names the address maps to (empty if there are none), or the reason why the lookup
+    /// failed.
+    pub names: Result<Vec<HostName>>,
+}
+
+/// Looks up the host names of `addresses` (e.g. taken from logs) with `resolver`, and returns one
+/// result for each distinct address, in the order in which they first appear in `addresses`.
+///
+/// Each distinct address is looked up once. If `confirm` is true, each distinct host name is then
+/// looked up once as well (`A` records for IPv4 addresses, `AAAA` records for IPv6 addresses) to
+/// check whether it maps back to the addresses it was found for, see [`HostName::confirmed`].
+///
+/// Up to [`MAX_CONCURRENT_QUERIES`] queries are in flight; use [`Resolver::with_rate_limit()`] to
+/// limit their rate.
+pub fn reverse_lookup(
+    resolver: &Resolver,
+    addresses: &[IpAddr],
+    confirm: bool,
+) -> Vec<ReverseLookup> {
+    let mut seen = HashSet::new();
+    let addresses: Vec<_> = addresses
+        .iter()
+        .copied()
+        .filter(|&address| seen.insert(address))
+        .collect();
+    let ptrs = concurrently(&addresses, |&address| {
+        let lookup = resolver.lookup(&Name::from_ip(address), RecordType::PTR)?;
+        Ok(lookup
+            .message()
+            .answers
+            .iter()
+            .filter_map(|record| match record.rdata() {
+                Rdata::PTR(ptr) => Some(ptr.location.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>())
+    });
+
+    let mut forward = HashMap::new();
+    if confirm {
+        let mut names = Vec::new();
+        for (address, ptrs) in zip(&addresses, &ptrs) {
+            for name in ptrs.iter().flatten() {
+                let key = (name.clone(), address_type(*address));
+                if !names.contains(&key) {
+                    names.push(key);
+                }
+            }
+        }
+        let results = concurrently(&names, |(name, rtype)| {
+            let lookup = resolver.lookup(name, *rtype)?;
+            Ok(lookup
+                .message()
+                .answers
+                .iter()
+                .filter_map(|record| match record.rdata() {
+                    Rdata::A(a) => Some(IpAddr::V4(a.address)),
+                    Rdata::AAAA(aaaa) => Some(IpAddr::V6(aaaa.address)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>())
+        });
+        forward = zip(names, results).collect::<HashMap<_, Result<_>>>();
+    }
+
+    zip(addresses, ptrs)
+        .map(|(address, names)| {
+            let names = names.map(|names| {
+                names
+                    .into_iter()
+                    .map(|name| {
+                        let key = (name, address_type(address));
+                        let confirmed = match forward.get(&key) {
+                            Some(Ok(addresses)) => Some(addresses.contains(&address)),
+                            _ => None,
+                        };
+                        HostName {
+                            name: key.0,
+                            confirmed,
+                        }
+                    })
+                    .collect()
+            });
+            ReverseLookup { address, names }
+        })
+        .collect()
+}
+
+/// Returns the type of the records that contain addresses of the same version as `address`.
+fn address_type(address: IpAddr) -> RecordType {
+    match address {
+        IpAddr::V4(_) => RecordType::A,
+        IpAddr::V6(_) => RecordType::AAAA,
+    }
+}
+
+/// Calls `f` for each of `items` in up to [`MAX_CONCURRENT_QUERIES`] threads, and returns the
+/// results in the order of `items`.
+pub(crate) fn concurrently<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::with_capacity(items.len()));
+
+    thread::scope(|s| {
+        for _ in 0..MAX_CONCURRENT_QUERIES.min(items.len()) {
+            s.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let item = match items.get(i) {
+                    Some(item) => item,
+                    None => break,
+                };
+                let result = f(item);
+                results
+                    .lock()
+                    .expect("Result mutex poisoned.")
+                    .push((i, result));
+            });
+        }
+    });
+
+    let mut results = results.into_inner().expect("Result mutex poisoned.");
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Looks up the `PTR` records of `address`.
+fn lookup_ptr(address: IpAddr, metadata: &QueryMetadata, bufsize: u16) -> Result<Vec<Name>> {
+    let name = Name::from_ip(address);
+    Ok(lookup(&name, RecordType::PTR, metadata, bufsize)?
+        .into_iter()
+        .filter_map(|rdata| match rdata {
+            Rdata::PTR(ptr) => Some(ptr.location),
+            _ => None,
+        })
+        .collect())
+}
@@ -0,0 +1,544 @@
+//! A nameserver on the loopback interface that answers queries with canned responses, so that
+//! code which sends queries can be tested without the internet, see [`TestServer`].
+//!
+//! Only available with the `test-server` feature.
+
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use toluol_proto::rdata::opt::OptionCode;
+use toluol_proto::{EdnsConfig, Message, Name, OptRecord, Rdata, Record, RecordType};
+
+use crate::net::TcpOptions;
+use crate::{ConnectionType, QueryMetadata};
+
+/// The server cookie that is sent along with the client cookie of a query, see
+/// [`Response::Message`].
+pub const SERVER_COOKIE: [u8; 8] = *b"toluol\0\0";
+
+/// How long the server threads wait for a query before checking whether the server was dropped.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// What the [`TestServer`] does with a query.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Response {
+    /// Answer with the message, after setting its ID and questions to those of the query, so that
+    /// the same message answers any query.
+    ///
+    /// If the query has an OPT record and the message doesn't, one is added; it echoes the client
+    /// cookie of the query along with [`SERVER_COOKIE`], if the query has one
+    /// ([RFC 7873](https://www.rfc-editor.org/rfc/rfc7873)). The message is sent as it is
+    /// otherwise, even if it is larger than the UDP payload size of the query.
+    Message(Message),
+    /// Answer with the bytes as they are, e.g. to send a malformed answer. Via TCP, the length
+    /// prefix is added.
+    Raw(Vec<u8>),
+    /// Don't answer. Via TCP, the connection is closed.
+    Ignore,
+}
+
+/// Faults that the [`TestServer`] injects into its answers, to test how queries cope with an
+/// unreliable network.
+///
+/// Each fault happens with the given probability, which must be between 0 and 1. The faults are
+/// decided by a random number generator seeded with `seed`, so they are the same in each run as
+/// long as the queries arrive in the same order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Chaos {
+    pub seed: u64,
+    /// The probability that an answer is lost. Via TCP, the connection is closed instead.
+    pub loss: f64,
+    /// How long to wait before sending each answer.
+    pub latency: Latency,
+    /// The probability that an answer via UDP is held back and sent right after the next one,
+    /// which is never held back itself.
+    pub reorder: f64,
+    /// The probability that an answer is cut off after a random number of bytes. Via TCP, the
+    /// length prefix still gives the full length, and the connection is closed after the answer.
+    pub truncate: f64,
+    /// The probability that a random byte of an answer (after the message ID) is changed.
+    pub corrupt: f64,
+    /// The probability that a copy of an answer with a different message ID is sent before it,
+    /// like a spoofed answer would be.
+    pub wrong_id: f64,
+}
+
+/// How long the [`TestServer`] waits before sending an answer, see [`Chaos::latency`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Latency {
+    Fixed(Duration),
+    /// Uniformly distributed between the two durations.
+    Uniform(Duration, Duration),
+    /// Exponentially distributed with the given mean, like the waiting times in a busy network.
+    Exponential(Duration),
+}
+
+impl Default for Latency {
+    fn default() -> Self {
+        Self::Fixed(Duration::ZERO)
+    }
+}
+
+impl Latency {
+    fn sample(&self, rng: &mut StdRng) -> Duration {
+        match *self {
+            Self::Fixed(latency) => latency,
+            Self::Uniform(min, max) if min < max => rng.gen_range(min..max),
+            Self::Uniform(min, _) => min,
+            Self::Exponential(mean) => mean.mul_f64(-(1.0 - rng.gen::<f64>()).ln()),
+        }
+    }
+}
+
+/// The [`Chaos`] of a [`TestServer`] and the state of its faults.
+struct ChaosState {
+    chaos: Chaos,
+    rng: StdRng,
+    /// An answer via UDP that is held back until the next one was sent, and its recipient.
+    held: Option<(Delivery, SocketAddr)>,
+}
+
+impl ChaosState {
+    fn new(chaos: Chaos) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(chaos.seed),
+            chaos,
+            held: None,
+        }
+    }
+
+    /// Returns how to deliver `answer`, or [`None`] if it is lost.
+    fn apply(&mut self, mut answer: Vec<u8>) -> Option<Delivery> {
+        let chaos = &self.chaos;
+        let rng = &mut self.rng;
+        if rng.gen_bool(chaos.loss) {
+            return None;
+        }
+        let mut messages = Vec::new();
+        if answer.len() >= 2 && rng.gen_bool(chaos.wrong_id) {
+            let id = u16::from_be_bytes([answer[0], answer[1]]);
+            let mut spoofed = answer.clone();
+            spoofed[..2]
+                .copy_from_slice(&id.wrapping_add(rng.gen_range(1..=u16::MAX)).to_be_bytes());
+            messages.push(spoofed);
+        }
+        let len = answer.len();
+        if len > 0 && rng.gen_bool(chaos.truncate) {
+            answer.truncate(rng.gen_range(0..len));
+        }
+        if answer.len() > 2 && rng.gen_bool(chaos.corrupt) {
+            let i = rng.gen_range(2..answer.len());
+            answer[i] ^= rng.gen_range(1..=u8::MAX);
+        }
+        messages.push(answer);
+        Some(Delivery {
+            messages,
+            len,
+            hold: rng.gen_bool(chaos.reorder),
+            delay: chaos.latency.sample(rng),
+        })
+    }
+}
+
+/// How an answer is sent, after the faults of [`Chaos`] were applied to it.
+struct Delivery {
+    /// The messages to send in order: the answer is the last one, which may be preceded by a copy
+    /// with a wrong ID.
+    messages: Vec<Vec<u8>>,
+    /// The length of the answer before it was cut off, if it was.
+    len: usize,
+    hold: bool,
+    delay: Duration,
+}
+
+/// A query the [`TestServer`] received.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReceivedQuery {
+    pub query: Message,
+    /// Either [`ConnectionType::Udp`] or [`ConnectionType::Tcp`].
+    pub transport: ConnectionType,
+}
+
+type Handler = dyn Fn(&Message, ConnectionType) -> Response + Send + Sync;
+
+struct Shared {
+    handler: Box<Handler>,
+    received: Mutex<Vec<ReceivedQuery>>,
+    /// The open TCP connections, so that they can be shut down when the server is dropped.
+    connections: Mutex<Vec<TcpStream>>,
+    tcp_connections: AtomicUsize,
+    chaos: Mutex<ChaosState>,
+    stopped: AtomicBool,
+}
+
+/// A nameserver listening on an ephemeral port of `127.0.0.1`, via UDP and TCP, that answers
+/// queries with [`Response`]s. It stops when it is dropped.
+///
+/// Faults like lost, delayed, or corrupted answers can be injected with [`Self::set_chaos()`].
+///
+/// # Examples
+/// ```rust
+/// use toluol::test_server::TestServer;
+/// use toluol::util::query_single;
+/// use toluol::ConnectionType;
+/// use toluol_proto::{HeaderFlags, Message, Opcode, RCode, RecordType};
+///
+/// let flags = HeaderFlags { aa: true, tc: false, rd: false, ra: false, ad: false, cd: false };
+/// let nxdomain = Message::new_response(
+///     0,
+///     Opcode::QUERY,
+///     flags,
+///     RCode::NXDOMAIN,
+///     Vec::new(),
+///     [Vec::new(), Vec::new(), Vec::new()],
+/// );
+/// let server = TestServer::with_answer(nxdomain).unwrap();
+///
+/// let metadata = server.metadata("nonexistent.example", RecordType::A);
+/// let (_, answer, _, _) = query_single(&metadata, 1232).unwrap();
+/// assert_eq!(answer.header.rcode, Some(RCode::NXDOMAIN));
+/// assert_eq!(server.received()[0].transport, ConnectionType::Udp);
+/// ```
+pub struct TestServer {
+    addr: SocketAddr,
+    shared: Arc<Shared>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl TestServer {
+    /// Starts a server that answers each query with what `handler` returns for it and the
+    /// transport it was received with.
+    pub fn start(
+        handler: impl Fn(&Message, ConnectionType) -> Response + Send + Sync + 'static,
+    ) -> io::Result<Self> {
+        let (udp, tcp) = bind()?;
+        let addr = udp.local_addr()?;
+        udp.set_read_timeout(Some(POLL_INTERVAL))?;
+        tcp.set_nonblocking(true)?;
+
+        let shared = Arc::new(Shared {
+            handler: Box::new(handler),
+            received: Mutex::new(Vec::new()),
+            connections: Mutex::new(Vec::new()),
+            tcp_connections: AtomicUsize::new(0),
+            chaos: Mutex::new(ChaosState::new(Chaos::default())),
+            stopped: AtomicBool::new(false),
+        });
+        let threads = vec![
+            {
+                let shared = shared.clone();
+                thread::spawn(move || serve_udp(udp, &shared))
+            },
+            {
+                let shared = shared.clone();
+                thread::spawn(move || serve_tcp(tcp, &shared))
+            },
+        ];
+        Ok(Self {
+            addr,
+            shared,
+            threads,
+        })
+    }
+
+    /// Starts a server that answers every query with `answer`, see [`Response::Message`].
+    pub fn with_answer(answer: Message) -> io::Result<Self> {
+        Self::start(move |_, _| Response::Message(answer.clone()))
+    }
+
+    /// Starts a server that handles the queries in the order they arrive with `responses`, and
+    /// the queries after the last one with the last one.
+    pub fn with_responses(responses: Vec<Response>) -> io::Result<Self> {
+        let next = AtomicUsize::new(0);
+        Self::start(move |_, _| {
+            let i = next.fetch_add(1, Ordering::Relaxed);
+            let i = i.min(responses.len().saturating_sub(1));
+            responses.get(i).cloned().unwrap_or(Response::Ignore)
+        })
+    }
+
+    /// Returns the address the server listens on, via UDP and TCP.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Returns the metadata of a query for `name` and `qtype` to this server via UDP, with the
+    /// defaults of the command line tool, except that it waits for a UDP answer for only half a
+    /// second and sends the query again up to two times, so that retransmissions can be tested.
+    ///
+    /// Panics if `name` is not a valid domain name.
+    pub fn metadata(&self, name: &str, qtype: RecordType) -> QueryMetadata {
+        QueryMetadata {
+            name: Name::from_ascii(name).expect("Invalid test query name."),
+            qtype,
+            qclass: toluol_proto::Class::IN,
+            opcode: toluol_proto::Opcode::QUERY,
+            nameserver: self.addr.ip().to_string(),
+            port: self.addr.port(),
+            connection_type: ConnectionType::Udp,
+            fetch_dnssec: false,
+            validate_dnssec: false,
+            dnssec_ok: None,
+            checking_disabled: true,
+            edns: true,
+            edns_version: 0,
+            edns_options: Vec::new(),
+            client_cookie: None,
+            client_subnet: None,
+            check_source: true,
+            timeout: Duration::from_millis(500),
+            retransmissions: 2,
+            reuse_udp_sockets: false,
+            tcp_options: TcpOptions::default(),
+            doh_json_fallback: false,
+            query_log: None,
+        }
+    }
+
+    /// Returns the queries received so far, in order.
+    pub fn received(&self) -> Vec<ReceivedQuery> {
+        self.shared
+            .received
+            .lock()
+            .expect("Received query list lock is poisoned.")
+            .clone()
+    }
+
+    /// Injects the faults of `chaos` into the following answers, instead of those given before.
+    /// An answer held back by [`Chaos::reorder`] is dropped.
+    pub fn set_chaos(&self, chaos: Chaos) {
+        *self.shared.chaos.lock().expect("Chaos lock is poisoned.") = ChaosState::new(chaos);
+    }
+
+    /// Returns the number of TCP connections accepted so far.
+    pub fn tcp_connections(&self) -> usize {
+        self.shared.tcp_connections.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.shared.stopped.store(true, Ordering::Relaxed);
+        for stream in self
+            .shared
+            .connections
+            .lock()
+            .expect("TCP connection list lock is poisoned.")
+            .drain(..)
+        {
+            let _ = stream.shutdown(std::net::Shutdown::Both);
+        }
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Binds a UDP socket and a TCP listener to the same ephemeral port.
+fn bind() -> io::Result<(UdpSocket, TcpListener)> {
+    let mut err = None;
+    // the port the UDP socket gets may already be in use for TCP
+    for _ in 0..10 {
+        let udp = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))?;
+        match TcpListener::bind(udp.local_addr()?) {
+            Ok(tcp) => return Ok((udp, tcp)),
+            Err(e) => err = Some(e),
+        }
+    }
+    Err(err.expect("The loop runs at least once."))
+}
+
+fn serve_udp(socket: UdpSocket, shared: &Shared) {
+    let mut buf = vec![0; u16::MAX as usize];
+    while !shared.stopped.load(Ordering::Relaxed) {
+        let (len, peer) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(_) => continue,
+        };
+        let Some(answer) = handle(&buf[..len], ConnectionType::Udp, shared) else {
+            continue;
+        };
+        let mut state = shared.chaos.lock().expect("Chaos lock is poisoned.");
+        let Some(delivery) = state.apply(answer) else {
+            continue;
+        };
+        if delivery.hold && state.held.is_none() {
+            state.held = Some((delivery, peer));
+            continue;
+        }
+        let held = state.held.take();
+        drop(state);
+        send_udp(&socket, delivery, peer, held);
+    }
+}
+
+/// Sends the messages of `delivery` to `peer` after its delay, followed by those of the answer
+/// that was `held` back, if any.
+fn send_udp(
+    socket: &UdpSocket,
+    delivery: Delivery,
+    peer: SocketAddr,
+    held: Option<(Delivery, SocketAddr)>,
+) {
+    let delay = delivery.delay;
+    let send = move |socket: &UdpSocket| {
+        let held = held.into_iter().flat_map(|(held, peer)| {
+            held.messages
+                .into_iter()
+                .map(move |message| (message, peer))
+        });
+        let messages = delivery.messages.into_iter().map(|message| (message, peer));
+        for (message, peer) in messages.chain(held) {
+            let _ = socket.send_to(&message, peer);
+        }
+    };
+    if delay.is_zero() {
+        send(socket);
+    } else if let Ok(socket) = socket.try_clone() {
+        // other queries are answered in the meantime
+        thread::spawn(move || {
+            thread::sleep(delay);
+            send(&socket);
+        });
+    }
+}
+
+fn serve_tcp(listener: TcpListener, shared: &Arc<Shared>) {
+    while !shared.stopped.load(Ordering::Relaxed) {
+        let stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(POLL_INTERVAL);
+                continue;
+            }
+            Err(_) => continue,
+        };
+        shared.tcp_connections.fetch_add(1, Ordering::Relaxed);
+        if stream.set_nonblocking(false).is_err() {
+            continue;
+        }
+        if let Ok(clone) = stream.try_clone() {
+            shared
+                .connections
+                .lock()
+                .expect("TCP connection list lock is poisoned.")
+                .push(clone);
+        }
+        let shared = shared.clone();
+        thread::spawn(move || serve_connection(stream, &shared));
+    }
+}
+
+/// Answers the queries on `stream`, then closes it.
+fn serve_connection(mut stream: TcpStream, shared: &Shared) {
+    answer_queries(&mut stream, shared);
+    // the stream is also kept in `Shared::connections`, so dropping it doesn't close it
+    let _ = stream.shutdown(std::net::Shutdown::Both);
+}
+
+/// Answers the queries on `stream` until the client closes it, or the connection should be
+/// closed because an answer is ignored, lost, or cut off.
+fn answer_queries(stream: &mut TcpStream, shared: &Shared) {
+    loop {
+        let mut len = [0; 2];
+        if stream.read_exact(&mut len).is_err() {
+            return;
+        }
+        let mut query = vec![0; u16::from_be_bytes(len).into()];
+        if stream.read_exact(&mut query).is_err() {
+            return;
+        }
+        let Some(answer) = handle(&query, ConnectionType::Tcp, shared) else {
+            return;
+        };
+        let delivery = shared
+            .chaos
+            .lock()
+            .expect("Chaos lock is poisoned.")
+            .apply(answer);
+        let Some(delivery) = delivery else {
+            return;
+        };
+        thread::sleep(delivery.delay);
+        let count = delivery.messages.len();
+        let mut truncated = false;
+        for (i, message) in delivery.messages.into_iter().enumerate() {
+            let len = if i + 1 == count {
+                truncated = message.len() < delivery.len;
+                delivery.len
+            } else {
+                message.len()
+            };
+            let mut msg = Vec::with_capacity(message.len() + 2);
+            msg.extend_from_slice(&(len as u16).to_be_bytes());
+            msg.extend_from_slice(&message);
+            if stream.write_all(&msg).is_err() {
+                return;
+            }
+        }
+        if truncated {
+            return;
+        }
+    }
+}
+
+/// Returns the encoded answer to the encoded `query`, or [`None`] if it is not answered.
+/// Queries that cannot be parsed are not answered.
+fn handle(query: &[u8], transport: ConnectionType, shared: &Shared) -> Option<Vec<u8>> {
+    let query = Message::parse_bytes(query).ok()?;
+    let response = (shared.handler)(&query, transport);
+    shared
+        .received
+        .lock()
+        .expect("Received query list lock is poisoned.")
+        .push(ReceivedQuery {
+            query: query.clone(),
+            transport,
+        });
+    match response {
+        Response::Message(answer) => answer_to(&query, answer).encode().ok(),
+        Response::Raw(answer) => Some(answer),
+        Response::Ignore => None,
+    }
+}
+
+/// Turns `answer` into an answer to `query`, see [`Response::Message`].
+fn answer_to(query: &Message, mut answer: Message) -> Message {
+    answer.header.msg_id = query.header.msg_id;
+    answer.questions = query.questions.clone();
+    answer.header.qdcount = answer.questions.len() as u16;
+
+    if let (Some(query_opt), None) = (query.opt(), answer.opt()) {
+        let client_cookie = match query_opt.rdata() {
+            Rdata::OPT(opt) => opt.get(OptionCode::Cookie).map(<[u8]>::to_vec),
+            _ => None,
+        };
+        let extra_options = match client_cookie {
+            Some(mut cookie) => {
+                cookie.truncate(8);
+                cookie.extend_from_slice(&SERVER_COOKIE);
+                vec![(OptionCode::Cookie, cookie)]
+            }
+            None => Vec::new(),
+        };
+        let edns = EdnsConfig {
+            do_flag: false,
+            bufsize: query_opt.payload_size,
+            client_cookie: None,
+            client_subnet: None,
+            edns_version: 0,
+            extra_options,
+        };
+        if let Ok(opt) = OptRecord::new(answer.header.rcode, edns) {
+            answer.additional_answers.push(Record::OPT(opt));
+            answer.header.arcount = answer.additional_answers.len() as u16;
+        }
+    }
+    answer
+}
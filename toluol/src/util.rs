@@ -1,64 +1,243 @@
 //! Utility functions.
 
-use anyhow::{Context, Result};
+use toluol_proto::dnssec::{validate_rrsets_with, RrSet, RrSetValidation, ValidationPolicy};
 use toluol_proto::{
-    EdnsConfig, HeaderFlags, Message, Name, NonOptRecord, Opcode, Record, RecordType,
+    EdnsConfig, HeaderFlags, Message, Name, NonOptRecord, Opcode, RCode, Rdata, Record, RecordType,
 };
 
 use std::io::Cursor;
-use std::time::Duration;
+use std::thread;
+use std::time::Instant;
 
-use crate::net::{send_query_tcp, send_query_udp, Nameserver};
+use crate::cache::DnskeyCache;
+use crate::error::{bail, Context, Result};
+use crate::net::{Nameserver, QueryTiming, RawTransport, StdTransport};
 use crate::{ConnectionType, QueryMetadata};
 
-#[cfg(feature = "tls")]
-use crate::net::send_query_tls;
-
-#[cfg(feature = "http")]
-use crate::net::send_query_http;
+/// The UDP payload size advertised with EDNS by default.
+pub const DEFAULT_BUFSIZE: u16 = 4096;
 
+/// Creates and encodes the query described by `metadata`. If `metadata.edns` is set, the query
+/// advertises `bufsize` as its UDP payload size.
 pub fn prepare_query(metadata: &QueryMetadata, bufsize: u16) -> Result<Vec<u8>> {
-    // see https://tools.ietf.org/html/rfc6840#section-5.9 for why the cd flag is set
     let flags = HeaderFlags {
         aa: false,
         tc: false,
         rd: true,
         ra: false,
         ad: true,
-        cd: true,
+        cd: metadata.checking_disabled,
     };
-    let msg = Message::new_query(
+    let mut msg = Message::new_query_with_class(
         metadata.name.clone(),
         metadata.qtype,
-        Opcode::QUERY,
+        metadata.qclass,
+        metadata.opcode,
         flags,
-        Some(EdnsConfig {
-            do_flag: metadata.fetch_dnssec,
+        metadata.edns.then(|| EdnsConfig {
+            do_flag: metadata.dnssec_ok.unwrap_or(metadata.fetch_dnssec),
             bufsize,
             client_cookie: metadata.client_cookie,
+            client_subnet: metadata.client_subnet,
+            edns_version: metadata.edns_version,
+            extra_options: metadata.edns_options.clone(),
         }),
     )
-    .context("Could not create query.")?;
-    msg.encode().context("Could not encode query.")
+    .parse("Could not create query.")?;
+    if metadata.connection_type.is_http() {
+        // identical queries can only be answered by HTTP caches if their IDs are the same, so
+        // RFC 8484, Section 4.1 recommends an ID of 0
+        msg.header.msg_id = 0;
+    }
+    msg.encode().parse("Could not encode query.")
 }
 
+/// Sends `data` to `nameserver`, using the connection type given by `metadata`.
 pub fn send_query(
-    connection_type: ConnectionType,
+    metadata: &QueryMetadata,
+    bufsize: u16,
+    nameserver: &mut Nameserver,
+    data: &[u8],
+) -> Result<(Vec<u8>, u16, QueryTiming)> {
+    send_query_via(&StdTransport, metadata, bufsize, nameserver, data)
+}
+
+/// The same as [`send_query()`], but sends `data` via `transport`.
+pub fn send_query_via(
+    transport: &dyn RawTransport,
+    metadata: &QueryMetadata,
     bufsize: u16,
     nameserver: &mut Nameserver,
     data: &[u8],
-) -> Result<(Vec<u8>, u16, Duration)> {
-    match connection_type {
-        ConnectionType::Udp => send_query_udp(nameserver, bufsize, data),
-        ConnectionType::Tcp => send_query_tcp(nameserver, bufsize, data),
-        #[cfg(feature = "tls")]
-        ConnectionType::Tls => send_query_tls(nameserver, data),
-        #[cfg(feature = "http")]
-        ConnectionType::HttpGet
-        | ConnectionType::HttpPost
-        | ConnectionType::HttpsGet
-        | ConnectionType::HttpsPost => send_query_http(nameserver, connection_type, bufsize, data),
+) -> Result<(Vec<u8>, u16, QueryTiming)> {
+    let res = transport.send_query(metadata, bufsize, nameserver, data);
+    log_query(metadata, nameserver, data, res)
+}
+
+/// Switches `metadata` from UDP to TCP if the query is larger than `max_size`, the size of the
+/// largest query the nameserver accepts via UDP (see
+/// [`ServerStats::max_query_size()`](crate::stats::ServerStats::max_query_size())).
+pub(crate) fn use_tcp_for_large_query(
+    metadata: &mut QueryMetadata,
+    bufsize: u16,
+    max_size: Option<u16>,
+) {
+    if let (ConnectionType::Udp, Some(max_size)) = (metadata.connection_type, max_size) {
+        if prepare_query(metadata, bufsize).is_ok_and(|query| query.len() > max_size as usize) {
+            metadata.connection_type = ConnectionType::Tcp;
+        }
+    }
+}
+
+/// Prepares the query specified by `metadata` and sends it to `nameserver`, using the connection
+/// type given by `metadata`.
+///
+/// Unlike [`send_query()`], UDP retransmissions use a new message ID and, if a client cookie is
+/// sent, a new cookie, so that answers to earlier transmissions are rejected (see
+/// [`send_query_udp_retrying()`](crate::net::send_query_udp_retrying())).
+pub fn prepare_and_send_query(
+    metadata: &QueryMetadata,
+    bufsize: u16,
+    nameserver: &mut Nameserver,
+) -> Result<(Vec<u8>, u16, QueryTiming)> {
+    prepare_and_send_query_via(&StdTransport, metadata, bufsize, nameserver)
+}
+
+/// The same as [`prepare_and_send_query()`], but sends the query via `transport`.
+pub fn prepare_and_send_query_via(
+    transport: &dyn RawTransport,
+    metadata: &QueryMetadata,
+    bufsize: u16,
+    nameserver: &mut Nameserver,
+) -> Result<(Vec<u8>, u16, QueryTiming)> {
+    // the query that was sent last, for the query log
+    let mut last_query = Vec::new();
+    let mut make_query = |retransmission| {
+        let query = if retransmission == 0 || metadata.client_cookie.is_none() {
+            prepare_query(metadata, bufsize)?
+        } else {
+            let mut metadata = metadata.clone();
+            metadata.client_cookie = Some(rand::random());
+            prepare_query(&metadata, bufsize)?
+        };
+        if metadata.query_log.is_some() {
+            last_query.clone_from(&query);
+        }
+        Ok(query)
+    };
+    let res = transport.send_query_retrying(metadata, bufsize, nameserver, &mut make_query);
+    log_query(metadata, nameserver, &last_query, res)
+}
+
+/// Appends `query` and its response `res` to the query log of `metadata`, if there is one, and
+/// returns `res`.
+fn log_query(
+    metadata: &QueryMetadata,
+    nameserver: &Nameserver,
+    query: &[u8],
+    res: Result<(Vec<u8>, u16, QueryTiming)>,
+) -> Result<(Vec<u8>, u16, QueryTiming)> {
+    if let Some(log) = &metadata.query_log {
+        let response = match &res {
+            Ok((answer, len, timing)) => Ok((&answer[..*len as usize], timing)),
+            Err(e) => Err(e),
+        };
+        log.log(nameserver, metadata.connection_type, query, response)?;
     }
+    res
+}
+
+/// Contains the following information for an answer received from a nameserver:
+/// - nameserver information
+/// - received answer
+/// - number of received bytes
+/// - timing information for the query
+pub type Answer = (Nameserver, Message, u16, QueryTiming);
+
+/// Prepares and sends the query specified by `metadata` and parses the answer.
+pub fn query_single(metadata: &QueryMetadata, bufsize: u16) -> Result<Answer> {
+    query_single_via(&StdTransport, metadata, bufsize)
+}
+
+/// The same as [`query_single()`], but sends the query via `transport`.
+pub fn query_single_via(
+    transport: &dyn RawTransport,
+    metadata: &QueryMetadata,
+    bufsize: u16,
+) -> Result<Answer> {
+    let mut nameserver = Nameserver::from_metadata(metadata)?;
+    let (answer, bytes_recvd, timing) =
+        prepare_and_send_query_via(transport, metadata, bufsize, &mut nameserver)?;
+    let answer = Message::parse(&mut Cursor::new(&answer)).parse("Could not parse answer.")?;
+    Ok((nameserver, answer, bytes_recvd, timing))
+}
+
+/// Sends one query for each of the given `qtypes` (ignoring `metadata.qtype`), concurrently.
+///
+/// Returns the result of each query together with the queried type, in the order of `qtypes`.
+pub fn query_multiple(
+    metadata: &QueryMetadata,
+    qtypes: &[RecordType],
+    bufsize: u16,
+) -> Vec<(RecordType, Result<Answer>)> {
+    thread::scope(|s| {
+        let handles: Vec<_> = qtypes
+            .iter()
+            .map(|&qtype| {
+                s.spawn(move || {
+                    let mut metadata = metadata.clone();
+                    metadata.qtype = qtype;
+                    (qtype, query_single(&metadata, bufsize))
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("Query thread panicked."))
+            .collect()
+    })
+}
+
+/// Sends the query specified by `metadata` to each of the given `nameservers` (ignoring
+/// `metadata.nameserver`), concurrently.
+///
+/// Returns the result of each query together with the queried nameserver, in the order of
+/// `nameservers`.
+pub fn query_nameservers<'a>(
+    metadata: &QueryMetadata,
+    nameservers: &'a [String],
+    bufsize: u16,
+) -> Vec<(&'a str, Result<Answer>)> {
+    thread::scope(|s| {
+        let handles: Vec<_> = nameservers
+            .iter()
+            .map(|nameserver| {
+                s.spawn(move || {
+                    let mut metadata = metadata.clone();
+                    metadata.nameserver = nameserver.clone();
+                    (nameserver.as_str(), query_single(&metadata, bufsize))
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("Query thread panicked."))
+            .collect()
+    })
+}
+
+/// Like [`get_dnskeys()`], but takes the DNSKEYs from `cache` if it has them, and otherwise keeps
+/// them there.
+pub fn get_dnskeys_cached(
+    zone: Name,
+    nameserver: Nameserver,
+    metadata: QueryMetadata,
+    cache: &DnskeyCache,
+) -> Result<Vec<NonOptRecord>> {
+    let keys = cache.get_or_fetch(&zone, Instant::now(), || {
+        get_dnskeys(zone.clone(), nameserver, metadata)
+    })?;
+    Ok(keys.records)
 }
 
 pub fn get_dnskeys(
@@ -66,27 +245,76 @@ pub fn get_dnskeys(
     mut nameserver: Nameserver,
     mut metadata: QueryMetadata,
 ) -> Result<Vec<NonOptRecord>> {
-    let bufsize = 4096;
+    let bufsize = DEFAULT_BUFSIZE;
     metadata.qtype = RecordType::DNSKEY;
+    metadata.opcode = Opcode::QUERY;
     metadata.name = zone;
-    let query = prepare_query(&metadata, bufsize)?;
-    let (reply, _, _) = send_query(metadata.connection_type, bufsize, &mut nameserver, &query)?;
-    let reply = Message::parse(&mut Cursor::new(&reply)).context("Could not parse answer.")?;
+    let (reply, _, _) = prepare_and_send_query(&metadata, bufsize, &mut nameserver)?;
+    let mut reply = Message::parse(&mut Cursor::new(&reply)).parse("Could not parse answer.")?;
     Ok(reply
-        .answers
+        .take_rrsets(|rec| matches!(rec.rtype, RecordType::DNSKEY | RecordType::RRSIG))
         .into_iter()
-        .filter_map(|rec| {
-            if let Record::NONOPT(
-                nonopt @ NonOptRecord {
-                    rtype: RecordType::DNSKEY | RecordType::RRSIG,
-                    ..
-                },
-            ) = rec
-            {
-                Some(nonopt)
-            } else {
-                None
-            }
-        })
+        .flat_map(RrSet::into_records)
+        .collect())
+}
+
+/// Validates the record sets in the answer section of `res`, and the NSEC and NSEC3 records in
+/// its authority section (which prove that the name or type does not exist), using `dnskeys`.
+pub fn validate_answer(
+    res: &Message,
+    dnskeys: &[NonOptRecord],
+    policy: &ValidationPolicy,
+) -> Vec<RrSetValidation> {
+    let is_denial = |rec: &&NonOptRecord| match rec.rdata() {
+        Rdata::NSEC(_) | Rdata::NSEC3(_) => true,
+        Rdata::RRSIG(rrsig) => matches!(rrsig.type_covered, RecordType::NSEC | RecordType::NSEC3),
+        _ => false,
+    };
+    let denials = res
+        .authoritative_answers
+        .iter()
+        .filter_map(Record::as_nonopt)
+        .filter(is_denial);
+    validate_rrsets_with(
+        res.answers
+            .iter()
+            .filter_map(|rec| rec.as_nonopt())
+            .chain(denials),
+        dnskeys,
+        policy,
+    )
+}
+
+/// Queries the records of type `qtype` for `name`. A non-existent name is treated like an empty
+/// answer.
+pub(crate) fn lookup(
+    name: &Name,
+    qtype: RecordType,
+    metadata: &QueryMetadata,
+    bufsize: u16,
+) -> Result<Vec<Rdata>> {
+    let mut metadata = metadata.clone();
+    metadata.name = name.clone();
+    metadata.qtype = qtype;
+    let (_, res, _, _) = query_single(&metadata, bufsize)
+        .transport(format!("Could not look up {} records for {}.", qtype, name))?;
+    let rcode = match res.opt() {
+        Some(opt) => opt.rcode,
+        None => res.header.rcode,
+    };
+    match rcode.unwrap_or(RCode::NOERROR) {
+        RCode::NOERROR | RCode::NXDOMAIN => {}
+        rcode => bail!(
+            transport,
+            "Looking up {} records for {} failed with {}.",
+            qtype,
+            name,
+            rcode
+        ),
+    }
+    Ok(res
+        .nonopt_records()
+        .filter(|record| record.rtype == qtype)
+        .map(|record| record.rdata().clone())
         .collect())
 }
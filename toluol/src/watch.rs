@@ -0,0 +1,149 @@
+//! Watching the records of a name (`watch`): the answer is kept like a cache would keep it, the
+//! remaining TTL of each record counts down, and the query is sent again as soon as a record
+//! expires. This shows how long an upstream resolver keeps records cached, see [`Watch`].
+
+use std::time::{Duration, Instant};
+use toluol_proto::{Message, NonOptRecord, RecordType};
+
+/// The shortest time between two queries, so that records with a TTL of 0 don't cause a flood of
+/// queries.
+pub const MIN_QUERY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The time after which the query is sent again if the answer contains no records whose TTL could
+/// tell when.
+pub const EMPTY_ANSWER_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A record, as received in an answer, and when it was received.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CachedRecord {
+    /// The record with the TTL it had in the answer.
+    pub record: NonOptRecord,
+    /// When the answer containing the record arrived.
+    pub received: Instant,
+}
+
+impl CachedRecord {
+    /// Returns when the record expires, i.e. when its TTL has elapsed since it was received.
+    pub fn expires(&self) -> Instant {
+        self.received + Duration::from_secs(self.record.ttl.into())
+    }
+
+    /// Returns the TTL the record has left at `now`, in whole seconds (rounded up, so that 0
+    /// means the record has expired).
+    pub fn remaining_ttl(&self, now: Instant) -> u32 {
+        let remaining = self.expires().saturating_duration_since(now);
+        let secs = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+        secs.min(u32::MAX.into()) as u32
+    }
+
+    /// Returns the record with its TTL set to [`Self::remaining_ttl()`], so that the TTL from the
+    /// answer is its [original TTL](NonOptRecord::original_ttl()).
+    pub fn at(&self, now: Instant) -> NonOptRecord {
+        let mut record = self.record.clone();
+        record.set_ttl(self.remaining_ttl(now));
+        record
+    }
+}
+
+/// The records of a watched name, each with the time it was received.
+///
+/// The records are those of the answer section. If it is empty (e.g. for `NXDOMAIN`), the `SOA`
+/// records of the authority section are kept instead, as their TTL determines how long the
+/// negative answer may be cached.
+///
+/// # Examples
+/// ```rust
+/// use std::time::{Duration, Instant};
+/// use toluol::watch::Watch;
+/// use toluol_proto::rdata::A;
+/// use toluol_proto::{Class, HeaderFlags, Message, Name, NonOptRecord, Opcode, Question, RCode};
+/// use toluol_proto::{Record, RecordType};
+///
+/// let name = Name::from_ascii("example.com").unwrap();
+/// let a = |ttl| {
+///     let rdata = A { address: "192.0.2.1".parse().unwrap() }.into();
+///     Record::NONOPT(NonOptRecord::new(name.clone(), Class::IN, ttl, rdata).unwrap())
+/// };
+/// let flags = HeaderFlags { aa: false, tc: false, rd: true, ra: true, ad: false, cd: false };
+/// let question = Question::new(name.clone(), RecordType::A, Class::IN);
+/// let answer = Message::new_response(
+///     1,
+///     Opcode::QUERY,
+///     flags,
+///     RCode::NOERROR,
+///     vec![question],
+///     [vec![a(300)], Vec::new(), Vec::new()],
+/// );
+///
+/// let start = Instant::now();
+/// let mut watch = Watch::default();
+/// watch.update(&answer, start);
+/// assert_eq!(watch.records()[0].at(start + Duration::from_secs(100)).ttl, 200);
+/// assert!(!watch.is_due(start + Duration::from_secs(299)));
+/// assert!(watch.is_due(start + Duration::from_secs(300)));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Watch {
+    records: Vec<CachedRecord>,
+    /// When the last answer arrived.
+    last_update: Option<Instant>,
+    /// The number of answers received so far.
+    updates: usize,
+}
+
+impl Watch {
+    /// Replaces the records with those of `answer`, which arrived at `received`.
+    pub fn update(&mut self, answer: &Message, received: Instant) {
+        let mut records: Vec<_> = answer
+            .answers
+            .iter()
+            .filter_map(|r| r.as_nonopt())
+            .collect();
+        if records.is_empty() {
+            records = answer
+                .authoritative_answers
+                .iter()
+                .filter_map(|r| r.as_nonopt())
+                .filter(|record| record.rtype == RecordType::SOA)
+                .collect();
+        }
+        self.records = records
+            .into_iter()
+            .map(|record| CachedRecord {
+                record: record.clone(),
+                received,
+            })
+            .collect();
+        self.last_update = Some(received);
+        self.updates += 1;
+    }
+
+    /// Returns the records of the last answer.
+    pub fn records(&self) -> &[CachedRecord] {
+        &self.records
+    }
+
+    /// Returns the number of answers received so far.
+    pub fn updates(&self) -> usize {
+        self.updates
+    }
+
+    /// Returns when the query should be sent again: when the first record expires, but not
+    /// earlier than [`MIN_QUERY_INTERVAL`] after the last answer. If there are no records, this
+    /// is [`EMPTY_ANSWER_INTERVAL`] after the last answer.
+    ///
+    /// Returns [`None`] if no answer was received yet.
+    pub fn next_query(&self) -> Option<Instant> {
+        let last_update = self.last_update?;
+        let next = match self.records.iter().map(CachedRecord::expires).min() {
+            Some(expires) => expires,
+            None => last_update + EMPTY_ANSWER_INTERVAL,
+        };
+        Some(next.max(last_update + MIN_QUERY_INTERVAL))
+    }
+
+    /// Returns whether the query should be sent (again) at `now`, see [`Self::next_query()`].
+    pub fn is_due(&self, now: Instant) -> bool {
+        self.next_query().is_none_or(|next| next <= now)
+    }
+}
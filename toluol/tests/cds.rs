@@ -0,0 +1,299 @@
+//! Tests for evaluating the `CDS`/`CDNSKEY` records of a child zone like a parent would.
+
+use std::slice;
+use std::time::{SystemTime, UNIX_EPOCH};
+use toluol::cds::{evaluate, ChildRecords, ParentAction, Problem};
+use toluol::error::Error;
+use toluol_proto::dnssec::ValidationPolicy;
+use toluol_proto::rdata::dnskey::{Algorithm, DNSKEY};
+use toluol_proto::rdata::ds::{DigestType, DS};
+use toluol_proto::rdata::RRSIG;
+use toluol_proto::{Class, Name, NonOptRecord, Rdata, RecordType};
+
+fn zone() -> Name {
+    Name::from_ascii("child.example").unwrap()
+}
+
+/// Returns a key signing key. Signatures with its algorithm are accepted without verifying them,
+/// as long as the key is trusted.
+fn key(n: u8) -> DNSKEY {
+    DNSKEY {
+        zone: true,
+        revoked: false,
+        secure_entry_point: true,
+        algorithm: Algorithm::ED25519,
+        key: vec![n; 32],
+    }
+}
+
+fn ds(key: &DNSKEY) -> DS {
+    key.to_ds(&zone(), DigestType::SHA256).unwrap()
+}
+
+/// Returns the records served by a nameserver of the child, with the `CDS` and `CDNSKEY` records
+/// signed by `signer`.
+fn child(dnskeys: &[DNSKEY], cds: &[DS], cdnskey: &[DNSKEY], signer: &DNSKEY) -> ChildRecords {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as u32;
+    let record = |rdata| NonOptRecord::new(zone(), Class::IN, 3600, rdata).unwrap();
+
+    let mut records: Vec<_> = dnskeys
+        .iter()
+        .map(|key| record(Rdata::DNSKEY(key.clone())))
+        .collect();
+    for (rtype, rdatas) in [
+        (
+            RecordType::CDS,
+            cds.iter().cloned().map(Rdata::CDS).collect::<Vec<_>>(),
+        ),
+        (
+            RecordType::CDNSKEY,
+            cdnskey.iter().cloned().map(Rdata::CDNSKEY).collect(),
+        ),
+    ] {
+        if rdatas.is_empty() {
+            continue;
+        }
+        records.extend(rdatas.into_iter().map(record));
+        records.push(record(Rdata::RRSIG(RRSIG {
+            type_covered: rtype,
+            algorithm: signer.algorithm,
+            labels: 2,
+            original_ttl: 3600,
+            signature_expiration: now + 86400,
+            signature_inception: now - 3600,
+            key_tag: signer.key_tag(),
+            signer_name: zone(),
+            signature: vec![0; 64],
+        })));
+    }
+    ChildRecords { records }
+}
+
+fn servers(children: Vec<ChildRecords>) -> Vec<(Name, Result<ChildRecords, Error>)> {
+    children
+        .into_iter()
+        .enumerate()
+        .map(|(i, child)| {
+            let nameserver = Name::from_ascii(format!("ns{}.example", i + 1)).unwrap();
+            (nameserver, Ok(child))
+        })
+        .collect()
+}
+
+#[test]
+fn no_cds_records() {
+    let child = child(&[key(1)], &[], &[], &key(1));
+    let (action, problems) = evaluate(
+        &zone(),
+        &[ds(&key(1))],
+        &servers(vec![child]),
+        &ValidationPolicy::default(),
+    );
+    assert_eq!(action, ParentAction::None);
+    assert!(problems.is_empty());
+}
+
+#[test]
+fn rollover() {
+    let (old, new) = (key(1), key(2));
+    // the new key is published and signed with the old one, which the current DS refers to
+    let child = child(
+        &[old.clone(), new.clone()],
+        &[ds(&new)],
+        slice::from_ref(&new),
+        &old,
+    );
+    let (action, problems) = evaluate(
+        &zone(),
+        &[ds(&old)],
+        &servers(vec![child]),
+        &ValidationPolicy::default(),
+    );
+    assert_eq!(
+        action,
+        ParentAction::Roll {
+            add: vec![ds(&new)],
+            remove: vec![ds(&old)]
+        }
+    );
+    assert!(problems.is_empty(), "{:?}", problems);
+}
+
+#[test]
+fn rollover_trusted_by_sha1_ds_follows_policy() {
+    let (old, new) = (key(1), key(2));
+    // toluol-proto cannot compute SHA-1 digests, so only the key tag and algorithm are compared
+    let old_ds = DS {
+        digest_type: DigestType::SHA1,
+        digest: vec![0; 20],
+        ..ds(&old)
+    };
+    let child = child(
+        &[old.clone(), new.clone()],
+        &[ds(&new)],
+        slice::from_ref(&new),
+        &old,
+    );
+    let servers = servers(vec![child]);
+
+    let (_, problems) = evaluate(
+        &zone(),
+        slice::from_ref(&old_ds),
+        &servers,
+        &ValidationPolicy::default(),
+    );
+    assert!(problems.is_empty(), "{:?}", problems);
+
+    let without_sha1 = ValidationPolicy {
+        accept_sha1: false,
+        ..Default::default()
+    };
+    let (_, problems) = evaluate(&zone(), &[old_ds], &servers, &without_sha1);
+    assert_eq!(
+        problems,
+        [
+            Problem::NotSignedByTrustedKey(RecordType::CDS),
+            Problem::NotSignedByTrustedKey(RecordType::CDNSKEY)
+        ]
+    );
+}
+
+#[test]
+fn rollover_signed_by_untrusted_key() {
+    let (old, new) = (key(1), key(2));
+    let child = child(
+        &[old.clone(), new.clone()],
+        &[ds(&new)],
+        slice::from_ref(&new),
+        &new,
+    );
+    let (_, problems) = evaluate(
+        &zone(),
+        &[ds(&old)],
+        &servers(vec![child]),
+        &ValidationPolicy::default(),
+    );
+    assert_eq!(
+        problems,
+        [
+            Problem::NotSignedByTrustedKey(RecordType::CDS),
+            Problem::NotSignedByTrustedKey(RecordType::CDNSKEY)
+        ]
+    );
+}
+
+#[test]
+fn unchanged_and_added() {
+    let key = key(1);
+    let child = child(
+        slice::from_ref(&key),
+        &[ds(&key)],
+        slice::from_ref(&key),
+        &key,
+    );
+    let servers = servers(vec![child]);
+    let (action, problems) = evaluate(&zone(), &[ds(&key)], &servers, &ValidationPolicy::default());
+    assert_eq!(action, ParentAction::Unchanged);
+    assert!(problems.is_empty());
+
+    // securing an insecure delegation: the records must be signed by the new key
+    let (action, problems) = evaluate(&zone(), &[], &servers, &ValidationPolicy::default());
+    assert_eq!(action, ParentAction::Add(vec![ds(&key)]));
+    assert!(problems.is_empty(), "{:?}", problems);
+}
+
+#[test]
+fn delete() {
+    let key = key(1);
+    let child = child(
+        slice::from_ref(&key),
+        &[DS::delete()],
+        &[DNSKEY::delete()],
+        &key,
+    );
+    let servers = servers(vec![child]);
+    let (action, problems) = evaluate(&zone(), &[ds(&key)], &servers, &ValidationPolicy::default());
+    assert_eq!(action, ParentAction::Delete);
+    assert!(problems.is_empty(), "{:?}", problems);
+
+    // there is nothing to delete for an insecure delegation
+    let (action, _) = evaluate(&zone(), &[], &servers, &ValidationPolicy::default());
+    assert_eq!(action, ParentAction::Unchanged);
+}
+
+#[test]
+fn delete_mixed_with_other_records() {
+    let key = key(1);
+    let child = child(
+        slice::from_ref(&key),
+        &[DS::delete(), ds(&key)],
+        slice::from_ref(&key),
+        &key,
+    );
+    let (action, problems) = evaluate(
+        &zone(),
+        &[ds(&key)],
+        &servers(vec![child]),
+        &ValidationPolicy::default(),
+    );
+    assert_eq!(action, ParentAction::Delete);
+    assert_eq!(
+        problems,
+        [
+            Problem::MixedDelete(RecordType::CDS),
+            Problem::CdsCdnskeyMismatch
+        ]
+    );
+}
+
+#[test]
+fn cds_cdnskey_mismatch() {
+    let (old, new) = (key(1), key(2));
+    let child = child(
+        &[old.clone(), new.clone()],
+        &[ds(&new)],
+        slice::from_ref(&old),
+        &old,
+    );
+    let (_, problems) = evaluate(
+        &zone(),
+        &[ds(&old)],
+        &servers(vec![child]),
+        &ValidationPolicy::default(),
+    );
+    assert_eq!(problems, [Problem::CdsCdnskeyMismatch]);
+}
+
+#[test]
+fn cds_without_dnskey() {
+    let (old, new) = (key(1), key(2));
+    let child = child(slice::from_ref(&old), &[ds(&new)], &[], &old);
+    let (_, problems) = evaluate(
+        &zone(),
+        &[ds(&old)],
+        &servers(vec![child]),
+        &ValidationPolicy::default(),
+    );
+    assert_eq!(problems, [Problem::CdsWithoutDnskey(ds(&new))]);
+}
+
+#[test]
+fn inconsistent_servers() {
+    let (old, new) = (key(1), key(2));
+    let keys = [old.clone(), new.clone()];
+    let mut servers = servers(vec![
+        child(&keys, &[ds(&new)], &[], &old),
+        child(&keys, &[ds(&old)], &[], &old),
+    ]);
+    let failed = Name::from_ascii("ns3.example").unwrap();
+    servers.push((failed.clone(), Err(Error::transport("timeout"))));
+
+    let (_, problems) = evaluate(&zone(), &[ds(&old)], &servers, &ValidationPolicy::default());
+    assert_eq!(
+        problems,
+        [Problem::ServerFailed(failed), Problem::Inconsistent]
+    );
+}
@@ -0,0 +1,514 @@
+//! Tests for sending queries via UDP and TCP, against the local server from the `test_server`
+//! module.
+//!
+//! Only run with the `test-server` feature.
+
+#![cfg(feature = "test-server")]
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+#[cfg(feature = "json")]
+use toluol::api::ApiServer;
+use toluol::mux::Multiplexer;
+use toluol::net::Nameserver;
+use toluol::resolver::{Resolver, ResolverConfig};
+use toluol::session::Session;
+use toluol::stats::ServerStats;
+use toluol::survey::survey;
+use toluol::test_server::{Chaos, Latency, Response, TestServer, SERVER_COOKIE};
+use toluol::util::query_single;
+use toluol::ConnectionType;
+use toluol_proto::rdata::opt::OptionCode;
+use toluol_proto::rdata::{SvcParam, SVCB, TXT};
+
+use toluol_proto::{Class, HeaderFlags, Message, Name, NonOptRecord, Opcode, RCode, Rdata};
+use toluol_proto::{EdnsConfig, OptRecord, Record, RecordType};
+
+/// Returns a `NOERROR` answer with `count` TXT records of 200 bytes each.
+fn txt_answer(count: usize) -> Message {
+    let name = Name::from_ascii("example.com").unwrap();
+    let records = (0..count)
+        .map(|i| {
+            let rdata = Rdata::TXT(TXT {
+                text: vec![format!("{:0200}", i)],
+            });
+            Record::NONOPT(NonOptRecord::new(name.clone(), Class::IN, 300, rdata).unwrap())
+        })
+        .collect();
+    let flags = HeaderFlags {
+        aa: true,
+        tc: false,
+        rd: false,
+        ra: false,
+        ad: false,
+        cd: false,
+    };
+    Message::new_response(
+        0,
+        Opcode::QUERY,
+        flags,
+        RCode::NOERROR,
+        Vec::new(),
+        [records, Vec::new(), Vec::new()],
+    )
+}
+
+/// Returns a `NOERROR` answer with the given records.
+fn answer(records: Vec<(&str, Rdata)>) -> Message {
+    let mut answer = txt_answer(0);
+    answer.answers = records
+        .into_iter()
+        .map(|(owner, rdata)| {
+            let owner = Name::from_ascii(owner).unwrap();
+            Record::NONOPT(NonOptRecord::new(owner, Class::IN, 300, rdata).unwrap())
+        })
+        .collect();
+    answer.header.ancount = answer.answers.len() as u16;
+    answer
+}
+
+fn transports(server: &TestServer) -> Vec<ConnectionType> {
+    server.received().iter().map(|q| q.transport).collect()
+}
+
+#[test]
+fn udp_and_tcp() {
+    let server = TestServer::with_answer(txt_answer(1)).unwrap();
+    let mut metadata = server.metadata("example.com", RecordType::TXT);
+    for transport in [ConnectionType::Udp, ConnectionType::Tcp] {
+        metadata.connection_type = transport;
+        let (_, answer, _, timing) = query_single(&metadata, 1232).unwrap();
+        assert_eq!(answer.answers.len(), 1);
+        assert_eq!(answer.questions[0].qname, metadata.name);
+        assert_eq!(timing.transport(), Some(transport));
+        assert_eq!(timing.handshake.is_some(), transport == ConnectionType::Tcp);
+    }
+    assert_eq!(
+        transports(&server),
+        [ConnectionType::Udp, ConnectionType::Tcp]
+    );
+}
+
+#[test]
+fn oversized_udp_answer_falls_back_to_tcp() {
+    // about 2 KB, more than the advertised 1232 bytes
+    let server = TestServer::with_answer(txt_answer(10)).unwrap();
+    let metadata = server.metadata("example.com", RecordType::TXT);
+    let (_, answer, _, timing) = query_single(&metadata, 1232).unwrap();
+    assert_eq!(answer.answers.len(), 10);
+    assert!(timing.tcp_fallback.is_some());
+    assert_eq!(timing.transport_summary().unwrap(), "UDP→TCP fallback");
+    assert_eq!(
+        transports(&server),
+        [ConnectionType::Udp, ConnectionType::Tcp]
+    );
+}
+
+#[test]
+fn retransmissions_use_new_ids_and_cookies() {
+    let server =
+        TestServer::with_responses(vec![Response::Ignore, Response::Message(txt_answer(1))])
+            .unwrap();
+    let mut metadata = server.metadata("example.com", RecordType::TXT);
+    metadata.client_cookie = Some([1; 8]);
+    let (_, _, _, timing) = query_single(&metadata, 1232).unwrap();
+    assert_eq!(timing.retransmissions, 1);
+
+    let received = server.received();
+    assert_eq!(received.len(), 2);
+    assert_ne!(
+        received[0].query.header.msg_id,
+        received[1].query.header.msg_id
+    );
+    let cookie = |i: usize| match received[i].query.opt().unwrap().rdata() {
+        Rdata::OPT(opt) => opt.get(OptionCode::Cookie).unwrap().to_vec(),
+        _ => unreachable!(),
+    };
+    assert_eq!(cookie(0), [1; 8]);
+    assert_ne!(cookie(1), [1; 8]);
+}
+
+#[test]
+fn server_cookie_is_returned() {
+    let server = TestServer::with_answer(txt_answer(1)).unwrap();
+    let mut metadata = server.metadata("example.com", RecordType::TXT);
+    metadata.client_cookie = Some([7; 8]);
+    let (_, answer, _, _) = query_single(&metadata, 1232).unwrap();
+    let cookie = match answer.opt().unwrap().rdata() {
+        Rdata::OPT(opt) => opt.get(OptionCode::Cookie).unwrap().to_vec(),
+        _ => unreachable!(),
+    };
+    assert_eq!(cookie[..8], [7; 8]);
+    assert_eq!(cookie[8..], SERVER_COOKIE);
+}
+
+#[test]
+fn session_reuses_tcp_connection() {
+    let server = TestServer::with_answer(txt_answer(1)).unwrap();
+    let session = Session::default();
+    let mut metadata = server.metadata("example.com", RecordType::TXT);
+    metadata.connection_type = ConnectionType::Tcp;
+    for name in ["a.example.com", "b.example.com"] {
+        metadata.name = Name::from_ascii(name).unwrap();
+        let ((_, answer, _, _), age) = session.query(&metadata, 1232).unwrap();
+        assert_eq!(answer.answers.len(), 1);
+        assert!(age.is_none());
+    }
+    assert_eq!(server.received().len(), 2);
+    assert_eq!(server.tcp_connections(), 1);
+}
+
+#[test]
+fn tcp_answer_larger_than_bufsize() {
+    // the advertised UDP payload size doesn't apply to TCP
+    let server = TestServer::with_answer(txt_answer(10)).unwrap();
+    let mut metadata = server.metadata("example.com", RecordType::TXT);
+    metadata.connection_type = ConnectionType::Tcp;
+    let (_, answer, _, _) = query_single(&metadata, 1232).unwrap();
+    assert_eq!(answer.answers.len(), 10);
+}
+
+#[test]
+fn lost_answers_time_out() {
+    let server = TestServer::with_answer(txt_answer(1)).unwrap();
+    server.set_chaos(Chaos {
+        loss: 1.0,
+        ..Chaos::default()
+    });
+    let mut metadata = server.metadata("example.com", RecordType::TXT);
+    metadata.timeout = Duration::from_millis(100);
+    assert!(query_single(&metadata, 1232).is_err());
+    // the query and two retransmissions
+    assert_eq!(server.received().len(), 3);
+}
+
+#[test]
+fn answers_with_wrong_id_are_skipped() {
+    let server = TestServer::with_answer(txt_answer(1)).unwrap();
+    server.set_chaos(Chaos {
+        wrong_id: 1.0,
+        ..Chaos::default()
+    });
+    let mut metadata = server.metadata("example.com", RecordType::TXT);
+    for transport in [ConnectionType::Udp, ConnectionType::Tcp] {
+        metadata.connection_type = transport;
+        let (_, answer, _, timing) = query_single(&metadata, 1232).unwrap();
+        assert_eq!(answer.answers.len(), 1);
+        assert_eq!(timing.retransmissions, 0);
+    }
+}
+
+#[test]
+fn cut_off_tcp_answer_is_an_error() {
+    let server = TestServer::with_answer(txt_answer(1)).unwrap();
+    server.set_chaos(Chaos {
+        truncate: 1.0,
+        ..Chaos::default()
+    });
+    let mut metadata = server.metadata("example.com", RecordType::TXT);
+    metadata.connection_type = ConnectionType::Tcp;
+    assert!(query_single(&metadata, 1232).is_err());
+}
+
+#[test]
+fn latency() {
+    let server = TestServer::with_answer(txt_answer(1)).unwrap();
+    server.set_chaos(Chaos {
+        latency: Latency::Fixed(Duration::from_millis(200)),
+        ..Chaos::default()
+    });
+    let mut metadata = server.metadata("example.com", RecordType::TXT);
+    metadata.timeout = Duration::from_secs(1);
+    let (_, _, _, timing) = query_single(&metadata, 1232).unwrap();
+    assert!(timing.elapsed >= Duration::from_millis(200));
+    assert_eq!(timing.retransmissions, 0);
+}
+
+#[test]
+fn multiplexer_matches_reordered_answers() {
+    let server = TestServer::with_answer(txt_answer(1)).unwrap();
+    server.set_chaos(Chaos {
+        reorder: 1.0,
+        ..Chaos::default()
+    });
+    let metadata = server.metadata("example.com", RecordType::TXT);
+    let mux = Multiplexer::udp(&Nameserver::from_metadata(&metadata).unwrap()).unwrap();
+    thread::scope(|s| {
+        for name in ["a.example.com", "b.example.com"] {
+            let mut metadata = metadata.clone();
+            let mux = &mux;
+            s.spawn(move || {
+                metadata.name = Name::from_ascii(name).unwrap();
+                let answer = mux.query(&metadata, 1232, Duration::from_secs(2)).unwrap();
+                assert_eq!(answer.questions[0].qname, metadata.name);
+            });
+        }
+    });
+}
+
+#[test]
+fn survey_fetches_truncated_answers_via_tcp() {
+    let mut truncated = txt_answer(0);
+    truncated.header.flags.tc = true;
+    let server = TestServer::with_responses(vec![
+        Response::Message(truncated.clone()),
+        Response::Message(truncated),
+        Response::Message(txt_answer(2)),
+    ])
+    .unwrap();
+    let metadata = server.metadata("example.com", RecordType::TXT);
+
+    // the multiplexer passes the truncated answer on instead of dropping it
+    let mux = Multiplexer::udp(&Nameserver::from_metadata(&metadata).unwrap()).unwrap();
+    let answer = mux.query(&metadata, 1232, Duration::from_secs(2)).unwrap();
+    assert!(answer.header.flags.tc);
+    assert_eq!(answer.questions[0].qname, metadata.name);
+
+    let survey = survey(&metadata, &[RecordType::TXT], 1232).unwrap();
+    assert_eq!(survey.present, [(RecordType::TXT, 2)]);
+    assert_eq!(
+        transports(&server),
+        [
+            ConnectionType::Udp,
+            ConnectionType::Udp,
+            ConnectionType::Tcp
+        ]
+    );
+}
+
+#[test]
+fn resolver_caches_dnskeys() {
+    let server = TestServer::with_answer(txt_answer(0)).unwrap();
+    let metadata = server.metadata("example.com", RecordType::DNSKEY);
+    let config = ResolverConfig {
+        nameservers: vec![metadata.nameserver.clone()],
+        ..ResolverConfig::default()
+    };
+    let resolver = Resolver::new(config, metadata, 1232);
+    let zone = Name::from_ascii("example.com").unwrap();
+    for _ in 0..2 {
+        let keys = resolver.dnskeys(&zone).unwrap();
+        assert!(keys.records.is_empty());
+    }
+    assert_eq!(server.received().len(), 1);
+    assert_eq!(resolver.dnskey_cache().len(), 1);
+}
+
+#[test]
+fn resolver_lookup_tries_search_domains() {
+    let mut nxdomain = txt_answer(0);
+    nxdomain.header.rcode = Some(RCode::NXDOMAIN);
+    let server = TestServer::with_responses(vec![
+        Response::Message(nxdomain),
+        Response::Message(txt_answer(1)),
+    ])
+    .unwrap();
+    let metadata = server.metadata("example.com", RecordType::TXT);
+    let config = ResolverConfig {
+        nameservers: vec![metadata.nameserver.clone()],
+        search: vec![Name::from_ascii("example.com").unwrap()],
+        ndots: 2,
+        ..ResolverConfig::default()
+    };
+    let resolver = Resolver::new(config, metadata, 1232);
+
+    // names with fewer than ndots dots are tried with the search domains first
+    for name in ["www.corp", "a.b.c"] {
+        let lookup = resolver
+            .lookup(&Name::from_ascii(name).unwrap(), RecordType::TXT)
+            .unwrap();
+        assert_eq!(lookup.message().answers.len(), 1);
+    }
+    let names: Vec<_> = server
+        .received()
+        .iter()
+        .map(|q| q.query.questions[0].qname.to_string())
+        .collect();
+    assert_eq!(names, ["www.corp.example.com", "www.corp", "a.b.c"]);
+}
+
+#[test]
+fn resolver_keeps_advertising_its_bufsize() {
+    // the nameserver accepts only small queries, but may send large answers
+    let mut answer = txt_answer(10);
+    let edns = EdnsConfig {
+        do_flag: false,
+        bufsize: 512,
+        client_cookie: None,
+        client_subnet: None,
+        edns_version: 0,
+        extra_options: Vec::new(),
+    };
+    answer.additional_answers.push(Record::OPT(
+        OptRecord::new(Some(RCode::NOERROR), edns).unwrap(),
+    ));
+    answer.header.arcount = 1;
+    let server = TestServer::with_answer(answer).unwrap();
+    let metadata = server.metadata("example.com", RecordType::TXT);
+    let config = ResolverConfig {
+        nameservers: vec![metadata.nameserver.clone()],
+        ..ResolverConfig::default()
+    };
+    let resolver =
+        Resolver::new(config, metadata, 4096).with_server_stats(Arc::new(ServerStats::new()));
+
+    for name in ["a.example.com", "b.example.com"] {
+        let (_, answer, _, _) = resolver
+            .query(&Name::from_ascii(name).unwrap(), RecordType::TXT)
+            .unwrap();
+        assert_eq!(answer.answers.len(), 10);
+    }
+    let sizes: Vec<_> = server
+        .received()
+        .iter()
+        .map(|q| q.query.opt().unwrap().payload_size)
+        .collect();
+    assert_eq!(sizes, [4096, 4096]);
+    assert_eq!(transports(&server), [ConnectionType::Udp; 2]);
+}
+
+#[test]
+fn resolver_lookup_ip_follows_https_records() {
+    let resolver = |server: &TestServer| {
+        let metadata = server.metadata("example.com", RecordType::A);
+        let config = ResolverConfig {
+            nameservers: vec![metadata.nameserver.clone()],
+            ..ResolverConfig::default()
+        };
+        Resolver::new(config, metadata, 1232)
+    };
+    let queries = |server: &TestServer| {
+        let mut queries: Vec<_> = server
+            .received()
+            .iter()
+            .map(|q| {
+                (
+                    q.query.questions[0].qname.to_string(),
+                    q.query.questions[0].qtype,
+                )
+            })
+            .collect();
+        // the address lookups are sent concurrently
+        queries.sort_by_key(|(_, qtype)| u16::from(*qtype));
+        queries
+    };
+    let (ipv4, ipv6) = (
+        Ipv4Addr::new(192, 0, 2, 1),
+        Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1),
+    );
+    let name = Name::from_ascii("www.example.com").unwrap();
+
+    // an alias to an endpoint with hints for both address families
+    let endpoint = SVCB {
+        priority: 1,
+        target: Name::root(),
+        params: vec![
+            SvcParam::Alpn(vec!["h2".to_string()]),
+            SvcParam::Ipv4Hint(vec![ipv4]),
+            SvcParam::Ipv6Hint(vec![ipv6]),
+        ],
+    };
+    let alias = SVCB {
+        priority: 0,
+        target: Name::from_ascii("cdn.example.net").unwrap(),
+        params: Vec::new(),
+    };
+    let server = TestServer::with_responses(vec![
+        Response::Message(answer(vec![("www.example.com", Rdata::HTTPS(alias))])),
+        Response::Message(answer(vec![(
+            "cdn.example.net",
+            Rdata::HTTPS(endpoint.clone()),
+        )])),
+    ])
+    .unwrap();
+    let lookup = resolver(&server).lookup_ip(&name).unwrap();
+    assert_eq!(lookup.target.to_string(), "cdn.example.net");
+    assert_eq!(lookup.endpoint, Some(endpoint));
+    assert_eq!(lookup.addresses, [IpAddr::V4(ipv4), IpAddr::V6(ipv6)]);
+    assert_eq!(
+        queries(&server),
+        [
+            ("www.example.com".to_string(), RecordType::HTTPS),
+            ("cdn.example.net".to_string(), RecordType::HTTPS)
+        ]
+    );
+
+    // without HTTPS records, the addresses are looked up
+    let addresses = answer(vec![
+        ("www.example.com", Rdata::from(ipv4)),
+        ("www.example.com", Rdata::from(ipv6)),
+    ]);
+    let server = TestServer::with_responses(vec![
+        Response::Message(answer(Vec::new())),
+        Response::Message(addresses),
+    ])
+    .unwrap();
+    let lookup = resolver(&server).lookup_ip(&name).unwrap();
+    assert_eq!(lookup.target, name);
+    assert_eq!(lookup.endpoint, None);
+    assert_eq!(lookup.addresses, [IpAddr::V4(ipv4), IpAddr::V6(ipv6)]);
+    assert_eq!(
+        queries(&server),
+        [
+            ("www.example.com".to_string(), RecordType::A),
+            ("www.example.com".to_string(), RecordType::AAAA),
+            ("www.example.com".to_string(), RecordType::HTTPS)
+        ]
+    );
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn api_server_resolves_and_caches() {
+    let server = TestServer::with_answer(txt_answer(2)).unwrap();
+    let metadata = server.metadata("example.com", RecordType::TXT);
+    let config = ResolverConfig {
+        nameservers: vec![metadata.nameserver.clone()],
+        ..ResolverConfig::default()
+    };
+    let api = ApiServer::new(Resolver::new(config, metadata, 1232));
+    for cached in [false, true] {
+        let (status, body) = api.handle("/resolve?name=example.com&type=TXT");
+        assert_eq!(status, 200);
+        assert_eq!(body["rcode"], "NOERROR");
+        assert_eq!(body["cached"], cached);
+        assert_eq!(body["answers"].as_array().unwrap().len(), 2);
+        assert_eq!(body["answers"][0]["type"], "TXT");
+        assert!(body.get("dnssec").is_none());
+    }
+    assert_eq!(server.received().len(), 1);
+    assert_eq!(api.handle("/resolve?name=example.com&type=NOPE").0, 400);
+}
+
+#[test]
+fn resolver_detects_wildcards() {
+    let zone = Name::from_ascii("example.com").unwrap();
+    let resolver = |server: &TestServer| {
+        let metadata = server.metadata("example.com", RecordType::TXT);
+        let config = ResolverConfig {
+            nameservers: vec![metadata.nameserver.clone()],
+            ..ResolverConfig::default()
+        };
+        Resolver::new(config, metadata, 1232)
+    };
+
+    // every name resolves
+    let server = TestServer::with_answer(txt_answer(2)).unwrap();
+    let wildcard = resolver(&server).detect_wildcard(&zone).unwrap().unwrap();
+    assert_eq!(wildcard.targets.len(), 2);
+    assert_eq!(server.received().len(), 3);
+    let names: Vec<_> = server
+        .received()
+        .iter()
+        .map(|received| received.query.questions[0].qname.to_string())
+        .collect();
+    assert!(names.iter().all(|name| name.ends_with(".example.com")));
+    assert_ne!(names[0], names[1]);
+
+    // the first name doesn't exist
+    let server = TestServer::with_answer(txt_answer(0)).unwrap();
+    assert_eq!(resolver(&server).detect_wildcard(&zone).unwrap(), None);
+    assert_eq!(server.received().len(), 1);
+}